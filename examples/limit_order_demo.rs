@@ -0,0 +1,125 @@
+//! 端到端的限价单演示，不需要网络、私钥或环境变量
+//!
+//! ```bash
+//! cargo run --example limit_order_demo --features testing
+//! ```
+//!
+//! 挂一笔 SOL -> USDC 的限价单，按脚本给出的价格逐轮检查触发条件，
+//! 触发后使用模拟的 Jupiter 指令组装交易，输出事件时间线、成交报告以及交易中每条指令的解析结果。
+
+use anyhow::Result;
+use limit_order::{
+    common::{
+        types::{price_triggered, TriggerCondition},
+        units::{Bps, Lamports, TokenAmount},
+        utils::compile_versioned_transaction_with_signers,
+    },
+    solana::{
+        decode::decode_instruction,
+        swap::{
+            assemble_swap_instructions, compute_unit_limit_with_margin, sub_tax,
+            with_compute_budget, TaxCharge,
+        },
+    },
+    testing::{fixed_keypair, MockJupiter, ScriptedPrices, SOL_DECIMALS, USDC, USDC_DECIMALS},
+    SOL,
+};
+use solana_sdk::{hash::Hash, signer::Signer};
+
+/// 价格缓存的轮询间隔
+const POLL_INTERVAL_MS: u64 = 800;
+/// 模拟交易消耗的计算单元
+const SIMULATED_UNITS_CONSUMED: u64 = 182_000;
+
+fn main() -> Result<()> {
+    let user = fixed_keypair(1);
+    let tax_account = fixed_keypair(2).pubkey();
+    let tax_bps = Bps::new(50)?;
+    let slippage_bps = Bps::new(100)?;
+    let priority_fee = 10_000;
+    let trigger_price: f32 = 150.0;
+    let amount = TokenAmount::from_ui(SOL, 2.0, SOL_DECIMALS)?;
+
+    println!("== 下单 ==");
+    println!("用户        {}", user.pubkey());
+    println!("卖出        {} SOL", amount.to_ui(SOL_DECIMALS));
+    println!("买入        USDC ({})", USDC);
+    println!("触发价格    {} USD", trigger_price);
+    println!("滑点        {}", slippage_bps);
+    println!("税率        {} -> {}", tax_bps, tax_account);
+    println!();
+
+    println!("== 事件时间线 ==");
+    let prices = ScriptedPrices::new(vec![146.20, 147.85, 149.10, 149.9995, 151.30]);
+    let mut fill_price = None;
+    for (round, price) in prices.enumerate() {
+        let t = round as u64 * POLL_INTERVAL_MS;
+        // 卖出 SOL，价格涨到触发价格及以上时成交，越过触发价格的那一轮同样触发
+        if price_triggered(TriggerCondition::Above, trigger_price, price) {
+            println!("[t+{:>5}ms] 价格 {:.4} 达到触发价格，开始交易", t, price);
+            fill_price = Some(price);
+            break;
+        }
+        println!("[t+{:>5}ms] 价格 {:.4} 未触发", t, price);
+    }
+    let Some(fill_price) = fill_price else {
+        println!("价格脚本结束，订单未触发");
+        return Ok(());
+    };
+
+    // 输入为 SOL，税在交易前以系统转账收取
+    let (swap_amount, tax) = sub_tax(amount, tax_bps);
+    let jup = MockJupiter {
+        price: fill_price,
+        output_decimals: USDC_DECIMALS,
+        lookup_tables: vec![],
+    };
+    let swap = jup.swap_instructions(&user.pubkey(), swap_amount, USDC);
+    println!(
+        "[t+{:>5}ms] 报价 {} lamports -> {} USDC 最小单位",
+        0, swap_amount.raw, swap.out_amount.raw
+    );
+
+    let ixs = assemble_swap_instructions(
+        &user.pubkey(),
+        &tax_account,
+        &TaxCharge::PreSwapSol(Lamports(tax.raw)),
+        &[],
+        &swap.setup_instructions,
+        &swap.swap_instruction,
+        swap.cleanup_instruction.as_ref(),
+    )?;
+    let compute_unit_limit = compute_unit_limit_with_margin(SIMULATED_UNITS_CONSUMED);
+    println!(
+        "[t+{:>5}ms] 模拟消耗 {} CU，计算单元上限设为 {}",
+        0, SIMULATED_UNITS_CONSUMED, compute_unit_limit
+    );
+    let ixs = with_compute_budget(&ixs, compute_unit_limit, Some(priority_fee));
+    let tx = compile_versioned_transaction_with_signers(
+        &ixs,
+        &user.pubkey(),
+        &[&user],
+        &[],
+        Hash::new_from_array([7; 32]),
+    )?;
+    println!("[t+{:>5}ms] 交易已签名，未发送", 0);
+    println!();
+
+    println!("== 成交报告 ==");
+    let out_ui = swap.out_amount.to_ui(USDC_DECIMALS);
+    println!("税收        {} SOL", tax.to_ui(SOL_DECIMALS));
+    println!("实际卖出    {} SOL", swap_amount.to_ui(SOL_DECIMALS));
+    println!("获得        {} USDC", out_ui);
+    println!(
+        "成交均价    {:.4} USD",
+        out_ui / swap_amount.to_ui(SOL_DECIMALS)
+    );
+    println!("交易签名    {}", tx.signatures[0]);
+    println!();
+
+    println!("== 交易指令 ==");
+    for (i, ix) in ixs.iter().enumerate() {
+        println!("#{:<2} {}", i, decode_instruction(ix));
+    }
+    Ok(())
+}
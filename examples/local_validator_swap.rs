@@ -0,0 +1,145 @@
+//! 在本地验证节点上真实执行一次带税收的交换
+//!
+//! ```bash
+//! # 自动启动 solana-test-validator（需要在 PATH 中）
+//! cargo run --example local_validator_swap --features testing
+//! # 或者连接已经运行的节点
+//! TEST_RPC_URL=http://127.0.0.1:8899 cargo run --example local_validator_swap --features testing
+//! ```
+//!
+//! 没有配置 `TEST_RPC_URL` 且找不到 `solana-test-validator` 时直接跳过。
+//! 现场创建两个测试代币并为新钱包注资，Jupiter 接口由本地 fixture 服务代替，
+//! 通过 [`swap_with_tax`] 发送交换交易后在链上核对税收、资金池和用户收到的数量，
+//! 同时检查价格源可以改用 fixture 服务的价格接口。交易由只接触消息字节的 [`MockSigner`] 签名，
+//! 与使用远程签名服务时的流程相同。
+
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{ensure, Result};
+use jupiter_swap_api_client::JupiterSwapApiClient;
+use limit_order::{
+    common::{
+        price_source::{JupiterPriceSource, PriceSource},
+        tax_policy::TaxPolicy,
+        units::{Bps, TokenAmount},
+        utils::{BlockhashProvider, BundleConfig, BLOCKHASH_EXPIRY_MARGIN},
+    },
+    solana::{
+        jup::{SwapMode, SwapOptions},
+        swap::{swap_with_tax, ExecutionOptions, SwapContext, SwapParams, TaxSide},
+    },
+    testing::{
+        fixed_keypair,
+        local::{
+            create_mint, create_token_account, fund, mint_to, token_balance, JupiterFixtureServer,
+            LocalValidator,
+        },
+        MockJito, MockSigner,
+    },
+};
+use solana_sdk::{
+    native_token::LAMPORTS_PER_SOL,
+    signature::{Keypair, Signer},
+};
+use spl_associated_token_account::get_associated_token_address;
+
+/// 测试代币的精度
+const DECIMALS: u8 = 6;
+/// 每个最小单位的输入换得的输出数量
+const RATE: f64 = 2.0;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let Some(validator) = LocalValidator::start().await? else {
+        println!("未配置 TEST_RPC_URL 且找不到 solana-test-validator，跳过");
+        return Ok(());
+    };
+    println!("使用节点 {}", validator.rpc_url());
+    let rpc = validator.rpc();
+
+    // 每次使用新钱包，连接共享节点时也不会互相影响
+    let user = Arc::new(Keypair::new());
+    let pool = Keypair::new().pubkey();
+    let tax_account = Keypair::new().pubkey();
+    fund(&rpc, &user.pubkey(), 10 * LAMPORTS_PER_SOL).await?;
+
+    let input_mint = create_mint(&rpc, &user, DECIMALS).await?;
+    let output_mint = create_mint(&rpc, &user, DECIMALS).await?;
+    mint_to(&rpc, &user, &input_mint, &user.pubkey(), 1_000_000_000).await?;
+    let pool_account = create_token_account(&rpc, &user, &pool, &input_mint).await?;
+    let tax_token_account = create_token_account(&rpc, &user, &tax_account, &input_mint).await?;
+    println!("输入代币 {}，输出代币 {}", input_mint, output_mint);
+
+    let jupiter = JupiterFixtureServer::start(RATE, pool).await;
+    jupiter
+        .set_prices(&HashMap::from([(input_mint, 1.0), (output_mint, 0.5)]))
+        .await;
+    let prices =
+        JupiterPriceSource::new(Arc::new(reqwest::Client::new()), jupiter.price_url(), false)
+            .get_prices(&[input_mint, output_mint])
+            .await?;
+    ensure!(
+        prices.get(&input_mint).map(|quote| quote.price) == Some(1.0),
+        "价格源没有读取 fixture 价格 {:?}",
+        prices
+    );
+
+    // 交换交易经签名者签名，与使用远程签名服务时相同
+    let signer = MockSigner::new(user.clone());
+    let amount = TokenAmount::new(input_mint, 100_000_000);
+    let tax_bps = Bps::new(100)?;
+    let jup = JupiterSwapApiClient::new(jupiter.url());
+    let jito = MockJito::new(fixed_keypair(9).pubkey());
+    let blockhashes = BlockhashProvider::new(BLOCKHASH_EXPIRY_MARGIN);
+    let ctx = SwapContext {
+        jup: &jup,
+        rpc: &rpc,
+        jito: &jito,
+        blockhashes: &blockhashes,
+        bundle: BundleConfig::default(),
+        tax_account,
+        tax_side: TaxSide::Input,
+    };
+    let outcome = swap_with_tax(
+        &ctx,
+        &signer,
+        &TaxPolicy::flat(tax_bps),
+        &SwapParams {
+            input_mint,
+            output_mint,
+            amount,
+            swap_mode: SwapMode::ExactIn,
+            slippage_bps: Bps::new(50)?,
+            min_out_amount: None,
+            max_price_impact_bps: None,
+            options: &SwapOptions::default(),
+            pin: None,
+        },
+        ExecutionOptions::default(),
+    )
+    .await?;
+    ensure!(jupiter.swap_requests().await == 1, "应只请求一次交换指令");
+    ensure!(!signer.messages().is_empty(), "交换交易没有经过签名者签名");
+    ensure!(outcome.bundle_id.is_none(), "没有 tip 时不应以 bundle 发送");
+    println!("交换交易 {}", outcome.signature);
+
+    // 1% 的税收在交换前以输入代币收取，其余输入按兑换率换成输出代币
+    let tax = amount.raw / 100;
+    let swapped = amount.raw - tax;
+    let user_output = get_associated_token_address(&user.pubkey(), &output_mint);
+    let balances = (
+        token_balance(&rpc, &tax_token_account).await?,
+        token_balance(&rpc, &pool_account).await?,
+        token_balance(&rpc, &user_output).await?,
+    );
+    println!(
+        "税收 {}，资金池收到 {}，用户收到 {}",
+        balances.0, balances.1, balances.2
+    );
+    ensure!(
+        balances == (tax, swapped, (swapped as f64 * RATE) as u64),
+        "链上余额与预期不符"
+    );
+    println!("交换成功");
+    Ok(())
+}
@@ -0,0 +1,18 @@
+CREATE TABLE orders (
+    order_id VARCHAR(36) NOT NULL PRIMARY KEY,
+    owner VARCHAR(44) NOT NULL,
+    input_mint VARCHAR(44) NOT NULL,
+    output_mint VARCHAR(44) NOT NULL,
+    status VARCHAR(32) NOT NULL,
+    signature VARCHAR(88),
+    out_amount BIGINT UNSIGNED,
+    tax_amount BIGINT UNSIGNED,
+    tax_mint VARCHAR(44),
+    error TEXT,
+    order_json TEXT NOT NULL,
+    created_at BIGINT UNSIGNED NOT NULL,
+    updated_at BIGINT UNSIGNED NOT NULL,
+    finalized_at BIGINT UNSIGNED,
+    INDEX idx_orders_status_created (status, created_at, order_id),
+    INDEX idx_orders_owner_created (owner, created_at, order_id)
+);
@@ -0,0 +1,10 @@
+CREATE TABLE execution_intents (
+    attempt_id VARCHAR(36) NOT NULL PRIMARY KEY,
+    order_id VARCHAR(36) NOT NULL,
+    signature VARCHAR(88) NOT NULL,
+    blockhash VARCHAR(44) NOT NULL,
+    last_valid_block_height BIGINT UNSIGNED NOT NULL,
+    created_at BIGINT UNSIGNED NOT NULL,
+    resolved_at BIGINT UNSIGNED,
+    INDEX idx_execution_intents_resolved (resolved_at, order_id)
+);
@@ -0,0 +1,9 @@
+CREATE TABLE order_events (
+    order_id VARCHAR(36) NOT NULL,
+    seq BIGINT UNSIGNED NOT NULL,
+    created_at BIGINT UNSIGNED NOT NULL,
+    kind VARCHAR(32) NOT NULL,
+    payload TEXT NOT NULL,
+    PRIMARY KEY (order_id, seq),
+    INDEX idx_order_events_created (created_at)
+);
@@ -0,0 +1,5 @@
+CREATE TABLE order_resume (
+    order_id VARCHAR(36) NOT NULL PRIMARY KEY,
+    resume_json TEXT NOT NULL,
+    updated_at BIGINT UNSIGNED NOT NULL
+);
@@ -1,44 +1,473 @@
-use std::sync::atomic;
+pub mod openapi;
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex as StdMutex},
+    time::{Duration, Instant},
+};
 
 use anyhow::anyhow;
-use rocket::{post, serde::json::Json, State};
+use rocket::{
+    catch, delete, get,
+    http::{Method, Status},
+    post,
+    request::{FromRequest, Outcome, Request},
+    response::{
+        content::RawText,
+        stream::{Event, EventStream},
+    },
+    routes,
+    serde::json::Json,
+    Route, Shutdown, State,
+};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
+use solana_sdk::{pubkey::Pubkey, signer::Signer};
 use tokio::sync::Mutex;
 use uuid::Uuid;
 
-use crate::common::{
-    encode::{decrypt, encrypt},
-    types::{Order, OrderBook},
+use crate::{
+    common::{
+        audit::AuditEvent,
+        auth::{ApiKeys, AuthContext, AuthError},
+        db::{
+            self, HistoryCursor, HistoryQuery, OrderHistoryPage, DEFAULT_HISTORY_LIMIT,
+            MAX_HISTORY_LIMIT,
+        },
+        encode::{decrypt, SecretString},
+        events::EventItem,
+        metrics::Metrics,
+        prepared::PreparedTransaction,
+        price::PriceCache,
+        price_source::ConfidenceLevel,
+        rate_limit::RateLimiter,
+        retry::PacingPolicy,
+        session::parse_keypair,
+        tax_policy::TaxPolicy,
+        treasury::{SweepResult, TreasuryBalances},
+        types::{
+            verify_cancel_all_signature, verify_cancel_signature, Bracket, CancelAllResult,
+            CancelFilter, ConfigPreview, FeeEstimate, Order, OrderBook, OrderChanges, OrderGroup,
+            OrderKind, OrderLeg, OrderQuote, OrderStatus, OrderStatusView, PauseState,
+            RevokedWallet, RuntimeConfig, SimulationTarget, TriggerCondition, TriggerOn,
+        },
+        units::{Bps, Lamports},
+        utils::{check_jito, check_price_feed, check_rpc, DependencyHealth},
+    },
+    error::LimitOrderError,
+    solana::{
+        jup::{QuoteSummary, SwapMode, SwapOptions},
+        multi_rpc::EndpointStatus,
+        swap::SwapSimulation,
+    },
 };
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 pub struct PlaceOrderRequest {
-    /// 输出代币
+    /// 输入代币
     pub input_mint: String,
     /// 输出代币
     pub output_mint: String,
-    /// 卖出价格
+    /// 触发价格，含义由 `trigger_on` 决定
     pub price: f32,
     /// 数量
     pub amount: u64,
-    /// 滑点
-    pub slippage_bps: u16,
+    /// 滑点，不超过 10000，为空时使用 `DEFAULT_SLIPPAGE_BPS`
+    pub slippage_bps: Option<Bps>,
     /// 是否有小费给jito
-    pub tip_amount: Option<u64>,
-    /// 加密后的pk
-    pub encrypt_pk: String,
+    pub tip_amount: Option<Lamports>,
+    /// 优先费，单位为 micro-lamports / CU，指定 tip 时同样生效；为空时使用 `DEFAULT_PRIORITY_FEE_MICRO_LAMPORTS`
+    pub priority_fee_micro_lamports: Option<u64>,
+    /// 失败重试的节奏，例如 `{"type": "SlotAware", "min_slots": 2}`，为空时使用全局配置
+    pub pacing: Option<PacingPolicy>,
+    /// `price` 所指的价格：`InputUsd`（默认）为输入代币 USD 价格，`OutputUsd` 为输出代币 USD 价格，
+    /// `Ratio` 为 1 个输入代币可换得的输出代币数量，`ExecutablePrice` 含义同 `Ratio`，
+    /// 但按订单数量向 Jupiter 询价得到实际成交价格
+    #[serde(default)]
+    pub trigger_on: TriggerOn,
+    /// 报价模式：`ExactIn`（默认）时 `amount` 为卖出的输入代币数量，`ExactOut` 时为买入的输出代币数量
+    #[serde(default)]
+    pub swap_mode: SwapMode,
+    /// 加密后的pk，提供 `session_token` 时可省略
+    pub encrypt_pk: Option<String>,
+    /// `POST /session` 返回的会话令牌
+    pub session_token: Option<String>,
+    /// `POST /quote` 返回的路由令牌，触发时优先使用其中的报价
+    pub route_token: Option<String>,
+    /// 路由令牌过期或不再适用时是否改用新的报价，默认 true，为 false 时订单执行失败
+    #[serde(default = "default_pin_fallback")]
+    pub pin_fallback: bool,
+    /// 分批执行的批数，每批数量为 `amount / split_parts`，余数计入最后一批；为空时一次性执行
+    pub split_parts: Option<u32>,
+    /// 订单类型：`{"type": "Limit"}`（默认）、`{"type": "TakeProfit"}`、`{"type": "StopLoss"}`
+    /// 或 `{"type": "TrailingStop", "trail_bps": 300}`；止盈、止损分别在价格涨到、跌到 `price` 时触发，
+    /// 跟踪止损在价格从下单后的最高点回落超过 `trail_bps` 时触发，此时 `price` 不使用
+    #[serde(default)]
+    pub kind: OrderKind,
+    /// 限价单的触发方向：`Above` 在价格涨到 `price` 及以上时触发，`Below` 在价格跌到 `price` 及以下时触发。
+    /// `Limit` 订单必须指定，其他类型不可指定
+    pub trigger_condition: Option<TriggerCondition>,
+    /// 订单成交、失败或过期时 POST 订单结果的地址，只支持 http/https
+    pub callback_url: Option<String>,
+    /// 发送前跳过模拟执行以降低延迟，默认 false；跳过后失败的交易同样会上链并支付手续费
+    #[serde(default)]
+    pub skip_simulation: bool,
+    /// 无论金额大小都以 Jito bundle 私有发送以防止被夹，默认 false；未指定 `tip_amount` 时按 `AUTO_TIP_*` 自动选择 tip。
+    /// 名义价值超过 `FORCE_JITO_ABOVE_LAMPORTS` 的订单总是私有发送
+    #[serde(default)]
+    pub force_private_execution: bool,
+    /// 扣税后的最低输出数量（输出代币最小单位），报价低于该数量时不执行，订单继续等待价格；`ExactOut` 时不可用
+    pub min_out_amount: Option<u64>,
+    /// 允许的最大价格影响，报价超过时不执行，订单继续等待价格；为空时使用 `DEFAULT_MAX_PRICE_IMPACT_BPS`
+    pub max_price_impact_bps: Option<Bps>,
+    /// 报价隐含的成交价格相对触发价格允许的最大偏离，超过时不执行，订单继续等待价格；为空时使用 `MAX_TRIGGER_DEVIATION_BPS`
+    pub max_trigger_deviation_bps: Option<Bps>,
+    /// `ExecutablePrice` 订单的最短询价间隔（毫秒），不低于 `EXECUTABLE_QUOTE_INTERVAL_MS`；
+    /// 价格远离触发价格时询价间隔会自动放宽，最长为 `EXECUTABLE_QUOTE_MAX_INTERVAL_MS`
+    pub poll_interval_ms: Option<u64>,
+    /// 成交后重新挂单的次数（1 到 1000），每次成交有各自的交易签名；为空时成交一次即结束，不支持与 `split_parts` 同时使用
+    pub repeat_count: Option<u32>,
+    /// 每次重新挂单时触发价格的调整（基点，可为负数），例如 200 表示每次提高 2%
+    pub reprice_offset_bps: Option<i16>,
+    /// 复利：重新挂单时反向交易，以上一次成交扣税后至少得到的输出数量作为输入数量，触发价格取倒数后再调整；
+    /// 只支持 `Ratio` / `ExecutablePrice` 触发的 `ExactIn` 订单
+    #[serde(default)]
+    pub compound: bool,
+    /// 交易选项，例如 `{"destination_token_account": "<冷钱包的 USDC ATA>"}` 将成交的代币直接发往冷钱包；
+    /// 另有 `wrap_and_unwrap_sol`（默认 true）、`use_shared_accounts`、`dynamic_compute_unit_limit`（默认 false）
+    #[serde(default)]
+    pub swap_options: SwapOptions,
+    /// 触发后执行的时限（毫秒，不低于 500），报价、重试和交易确认都需在时限内完成，否则放弃本次执行；
+    /// 已发送的交易会先确认是否上链，上链时照常按成交处理
+    pub post_trigger_deadline_ms: Option<u64>,
+    /// 执行超时后回到监控等待下一次触发，默认 false：订单以 `Unfilled` 结束
+    #[serde(default)]
+    pub rearm_after_timeout: bool,
+    /// 订单有效期（秒，大于 0），到期时仍未触发的订单以 `Expired` 结束；到期时已触发的订单继续执行。为空时一直等待触发
+    pub ttl_secs: Option<u64>,
+}
+
+fn default_pin_fallback() -> bool {
+    true
+}
+
+/// 单笔订单允许的最大 jito 小费（0.1 SOL）
+pub const MAX_TIP_LAMPORTS: u64 = 100_000_000;
+
+/// 带错误码的请求错误
+#[derive(Debug)]
+pub struct ApiError {
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl ApiError {
+    fn new(code: &'static str, message: impl Into<String>) -> ApiError {
+        ApiError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// 取得请求携带的 API key：`X-Api-Key` 或 `Authorization: Bearer <key>`
+fn request_api_key<'r>(request: &'r Request<'_>) -> Option<&'r str> {
+    request.headers().get_one("X-Api-Key").or_else(|| {
+        request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+    })
+}
+
+/// 认证失败时记录原因，由 [`unauthorized`] 等错误处理器输出
+fn auth_failure<T>(request: &Request<'_>, error: AuthError) -> Outcome<T, AuthError> {
+    let status = match error {
+        AuthError::Missing | AuthError::Invalid => Status::Unauthorized,
+        AuthError::Forbidden | AuthError::AdminDisabled => Status::Forbidden,
+        AuthError::RateLimited(_) => Status::TooManyRequests,
+    };
+    request.local_cache(|| Some(error.clone()));
+    Outcome::Error((status, error))
+}
+
+/// 修改状态的接口的请求守卫，校验 API key，见 [`ApiKeys`]
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AuthContext {
+    type Error = AuthError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(keys) = request.rocket().state::<ApiKeys>() else {
+            return Outcome::Success(AuthContext {
+                key_name: None,
+                is_admin: false,
+            });
+        };
+        match keys.authenticate(request_api_key(request)) {
+            Ok(auth) => Outcome::Success(auth),
+            Err(e) => auth_failure(request, e),
+        }
+    }
+}
+
+/// 管理接口的请求守卫，要求 admin key
+pub struct AdminContext(pub AuthContext);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminContext {
+    type Error = AuthError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.guard::<AuthContext>().await {
+            Outcome::Success(auth) if auth.is_admin => Outcome::Success(AdminContext(auth)),
+            Outcome::Success(_) => {
+                let has_admin = request
+                    .rocket()
+                    .state::<ApiKeys>()
+                    .is_some_and(ApiKeys::has_admin);
+                let error = if has_admin {
+                    AuthError::Forbidden
+                } else {
+                    AuthError::AdminDisabled
+                };
+                auth_failure(request, error)
+            }
+            Outcome::Error(e) => Outcome::Error(e),
+            Outcome::Forward(status) => Outcome::Forward(status),
+        }
+    }
+}
+
+/// 认证失败时返回标准的 JSON 响应，而不是 Rocket 默认的错误页面
+fn auth_error_response(request: &Request<'_>) -> Json<ApiResponse<()>> {
+    let error = request
+        .local_cache(|| None::<AuthError>)
+        .clone()
+        .unwrap_or(AuthError::Invalid);
+    let (code, retry_after_ms) = match error {
+        AuthError::RateLimited(wait) => ("RATE_LIMITED", Some(wait.as_millis() as u64)),
+        _ => ("UNAUTHORIZED", None),
+    };
+    Json(ApiResponse {
+        success: false,
+        data: None,
+        error: Some(error.to_string()),
+        error_code: Some(code.to_string()),
+        retry_after_ms,
+    })
+}
+
+#[catch(401)]
+pub fn unauthorized(request: &Request<'_>) -> Json<ApiResponse<()>> {
+    auth_error_response(request)
+}
+
+#[catch(403)]
+pub fn forbidden(request: &Request<'_>) -> Json<ApiResponse<()>> {
+    auth_error_response(request)
+}
+
+#[catch(429)]
+pub fn too_many_requests(request: &Request<'_>) -> Json<ApiResponse<()>> {
+    auth_error_response(request)
+}
+
+/// 400 错误：带请求体的请求为请求体不是有效的 JSON，其余请求返回通用的错误
+///
+/// 查询参数无效等可以说明原因的错误由接口自己返回 JSON 错误，不经过这里。
+#[catch(400)]
+pub fn bad_request(request: &Request<'_>) -> Json<ApiResponse<()>> {
+    let error = match request.method() {
+        Method::Post | Method::Put | Method::Patch => {
+            ApiError::new("INVALID_BODY", "请求体不是有效的 JSON")
+        }
+        _ => ApiError::new("BAD_REQUEST", "请求无效"),
+    };
+    Json(error.into())
+}
+
+/// 请求体是 JSON 但缺少必填字段、字段类型不正确或取值越界（例如 `slippage_bps` 超过 10000）时返回的错误
+#[catch(422)]
+pub fn unprocessable_entity() -> Json<ApiResponse<()>> {
+    Json(
+        ApiError::new(
+            "INVALID_BODY",
+            "请求参数无效：缺少必填字段、字段类型不正确或取值超出范围",
+        )
+        .into(),
+    )
+}
+
+impl<T> From<ApiError> for ApiResponse<T> {
+    fn from(e: ApiError) -> ApiResponse<T> {
+        ApiResponse {
+            success: false,
+            data: None,
+            error: Some(e.message),
+            error_code: Some(e.code.to_string()),
+            retry_after_ms: None,
+        }
+    }
+}
+
+/// 校验订单参数，单笔下单和订单组共用
+///
+/// 滑点在反序列化时已限制为不超过 10000
+pub fn validate_order_params(
+    input_mint: &str,
+    output_mint: &str,
+    price: f32,
+    amount: u64,
+    tip_amount: Option<Lamports>,
+    kind: OrderKind,
+    trigger_condition: Option<TriggerCondition>,
+) -> Result<(), ApiError> {
+    let input = input_mint
+        .parse::<Pubkey>()
+        .map_err(|_| ApiError::new("INVALID_MINT", format!("输入代币地址无效 {}", input_mint)))?;
+    let output = output_mint
+        .parse::<Pubkey>()
+        .map_err(|_| ApiError::new("INVALID_MINT", format!("输出代币地址无效 {}", output_mint)))?;
+    if input == output {
+        return Err(ApiError::new("SAME_MINT", "输入代币与输出代币相同"));
+    }
+    if amount == 0 {
+        return Err(ApiError::new("AMOUNT_ZERO", "数量必须大于 0"));
+    }
+    // 跟踪止损不使用 price
+    if !matches!(kind, OrderKind::TrailingStop { .. }) && (!price.is_finite() || price <= 0.0) {
+        return Err(ApiError::new("INVALID_PRICE", "触发价格必须为正数"));
+    }
+    if kind == OrderKind::Limit && trigger_condition.is_none() {
+        return Err(ApiError::new(
+            "TRIGGER_CONDITION_REQUIRED",
+            "限价单必须指定 trigger_condition：Above 或 Below",
+        ));
+    }
+    if let Some(tip) = tip_amount {
+        if tip.get() > MAX_TIP_LAMPORTS {
+            return Err(ApiError::new(
+                "TIP_TOO_LARGE",
+                format!("小费 {} 超过上限 {}", tip.get(), MAX_TIP_LAMPORTS),
+            ));
+        }
+    }
+    Ok(())
+}
+
+impl PlaceOrderRequest {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        validate_order_params(
+            &self.input_mint,
+            &self.output_mint,
+            self.price,
+            self.amount,
+            self.tip_amount,
+            self.kind,
+            self.trigger_condition,
+        )
+    }
+
+    /// 订单参数，未指定滑点时使用 `default_slippage_bps`
+    pub fn to_leg(&self, default_slippage_bps: Bps) -> OrderLeg {
+        OrderLeg {
+            input_mint: self.input_mint.clone(),
+            output_mint: self.output_mint.clone(),
+            price: self.price,
+            amount: self.amount,
+            slippage_bps: self.slippage_bps.unwrap_or(default_slippage_bps),
+            tip_amount: self.tip_amount,
+            priority_fee_micro_lamports: self.priority_fee_micro_lamports,
+            pacing: self.pacing,
+            trigger_on: self.trigger_on,
+            swap_mode: self.swap_mode,
+            route_token: self.route_token.clone(),
+            pin_fallback: self.pin_fallback,
+            split_parts: self.split_parts,
+            kind: self.kind,
+            trigger_condition: self.trigger_condition,
+            callback_url: self.callback_url.clone(),
+            skip_simulation: self.skip_simulation,
+            force_private_execution: self.force_private_execution,
+            min_out_amount: self.min_out_amount,
+            max_price_impact_bps: self.max_price_impact_bps,
+            max_trigger_deviation_bps: self.max_trigger_deviation_bps,
+            poll_interval_ms: self.poll_interval_ms,
+            repeat_count: self.repeat_count,
+            reprice_offset_bps: self.reprice_offset_bps,
+            compound: self.compound,
+            swap_options: self.swap_options.clone(),
+            post_trigger_deadline_ms: self.post_trigger_deadline_ms,
+            rearm_after_timeout: self.rearm_after_timeout,
+            ttl_secs: self.ttl_secs,
+        }
+    }
+}
+
+/// 所有接口统一的返回格式
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ApiResponse<T> {
+    pub success: bool,
+    pub data: Option<T>,
+    pub error: Option<String>,
+    /// 机器可读的错误码，例如 `INVALID_MINT`，客户端可以据此区分错误
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<String>,
+    /// 被限流时距离下一次可以请求的毫秒数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after_ms: Option<u64>,
+}
+
+/// 依次检查给定键的限流，任何一个超限时返回 `RATE_LIMITED` 和需要等待的毫秒数
+fn check_rate_limit<T>(limiter: &RateLimiter, keys: &[String]) -> Result<(), ApiResponse<T>> {
+    for key in keys {
+        if let Err(retry_after) = limiter.check(key) {
+            return Err(ApiResponse {
+                success: false,
+                data: None,
+                error: Some("请求过于频繁，请稍后重试".to_string()),
+                error_code: Some("RATE_LIMITED".to_string()),
+                retry_after_ms: Some(retry_after.as_millis() as u64),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// 按客户端 IP 限流的键，无法取得 IP 的请求共用一个桶
+fn ip_key(client_ip: Option<IpAddr>) -> String {
+    match client_ip {
+        Some(ip) => format!("ip:{}", ip),
+        None => "ip:unknown".to_string(),
+    }
 }
 
-#[derive(Serialize, Deserialize)]
-struct ApiResponse<T> {
-    success: bool,
-    data: Option<T>,
-    error: Option<String>,
+/// 按下单钱包限流的键，未开启钱包限流时为空
+fn user_keys(limiter: &RateLimiter, user: &Pubkey) -> Vec<String> {
+    if limiter.config().per_user {
+        vec![format!("user:{}", user)]
+    } else {
+        vec![]
+    }
+}
+
+/// 取得下单使用的私钥，优先使用会话令牌，否则解密请求中的私钥
+fn order_private_key(
+    order_book: &mut OrderBook,
+    encrypt_pk: Option<&str>,
+    session_token: Option<&str>,
+) -> anyhow::Result<SecretString> {
+    match (session_token, encrypt_pk) {
+        (Some(token), _) => order_book.sessions.private_key(token),
+        (None, Some(encrypt_pk)) => Ok(decrypt(encrypt_pk)?),
+        (None, None) => Err(anyhow!("缺少 encrypt_pk 或 session_token")),
+    }
 }
+
 /// 创建新订单的 API 端点。
 ///
-/// 该端点接受一个下单请求，解密私钥后在订单簿中创建订单，并返回订单的 UUID。
+/// 该端点接受一个下单请求，解密私钥（或从会话中取出私钥）后在订单簿中创建订单，并返回订单的 UUID。
 ///
 /// # 参数
 /// * `request` - 下单请求的 JSON 数据，包含交易参数和加密私钥。
@@ -47,7 +476,12 @@ struct ApiResponse<T> {
 /// # 返回值
 /// 返回一个 `Json<ApiResponse<Uuid>>`，其中：
 /// - `success: true` 和 `data: Some(uuid)` 表示订单创建成功。
-/// - `success: false` 和 `error: Some(msg)` 表示创建失败。
+/// - `success: false` 和 `error: Some(msg)` 表示创建失败，`error_code` 为错误码：
+///   `INVALID_MINT`、`SAME_MINT`、`AMOUNT_ZERO`、`INVALID_PRICE`、`TIP_TOO_LARGE` 表示参数无效，
+///   `TRIGGER_CONDITION_REQUIRED` 表示限价单没有指定 `trigger_condition`，
+///   `INVALID_BODY` 表示请求体不是有效的 JSON 或字段类型、取值不正确（例如 `slippage_bps` 超过 10000），
+///   `INVALID_KEY` 表示私钥或会话无效；创建订单失败时为 `LimitOrderError` 的错误码，
+///   例如 `UNAUTHORIZED`、`INVALID_REQUEST`，没有对应错误码时为 `PLACE_FAILED`。
 ///
 /// # 示例
 /// ```bash
@@ -65,56 +499,394 @@ struct ApiResponse<T> {
 /// ```
 #[post("/place_order", data = "<request>")]
 pub async fn place_order(
+    _auth: AuthContext,
     request: Json<PlaceOrderRequest>,
+    client_ip: Option<IpAddr>,
+    limiter: &State<Arc<RateLimiter>>,
     order_book: &State<Mutex<OrderBook>>,
 ) -> Json<ApiResponse<Uuid>> {
-    match decrypt(&request.encrypt_pk) {
-        Ok(prik) => {
-            let mut order_book = order_book.lock().await;
-            let result = order_book
-                .place_order(
-                    prik,
-                    request.input_mint.clone(),
-                    request.output_mint.clone(),
-                    request.price,
-                    request.amount,
-                    request.slippage_bps,
-                    request.tip_amount,
-                )
-                .await;
+    if let Err(e) = check_rate_limit(limiter, &[ip_key(client_ip)]) {
+        return Json(e);
+    }
+    if let Err(e) = request.validate() {
+        return Json(e.into());
+    }
+    let key = {
+        let mut order_book = order_book.lock().await;
+        order_private_key(
+            &mut order_book,
+            request.encrypt_pk.as_deref(),
+            request.session_token.as_deref(),
+        )
+        .and_then(|prik| parse_keypair(prik.expose()).map(|keypair| (prik, keypair.pubkey())))
+        .map(|(prik, owner)| (prik, owner, order_book.default_slippage_bps))
+    };
+    match key {
+        Ok((prik, owner, default_slippage_bps)) => {
+            if let Err(e) = check_rate_limit(limiter, &user_keys(limiter, &owner)) {
+                return Json(e);
+            }
+            let leg = request.to_leg(default_slippage_bps);
+            let result = OrderBook::place_order(order_book, prik, leg).await;
 
             match result {
                 Ok(id) => Json(ApiResponse {
                     success: true,
                     data: Some(id),
                     error: None,
+                    error_code: None,
+                    retry_after_ms: None,
                 }),
-                Err(e) => Json(ApiResponse {
-                    success: false,
-                    data: None,
-                    error: Some(format!("开单失败 {:?}", e)),
-                }),
+                Err(e) => Json(
+                    ApiError::new(
+                        e.code().unwrap_or("PLACE_FAILED"),
+                        format!("开单失败 {:#}", e),
+                    )
+                    .into(),
+                ),
             }
         }
-        Err(e) => Json(ApiResponse {
-            success: false,
-            data: None,
-            error: Some(format!("私钥解析失败")),
+        Err(e) => Json(ApiError::new("INVALID_KEY", format!("私钥解析失败 {}", e)).into()),
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct PlaceOrderGroupRequest {
+    /// 客户端生成的幂等键，重试时保持不变
+    pub client_group_id: String,
+    /// 组内订单
+    pub orders: Vec<OrderLeg>,
+    /// 加密后的pk，提供 `session_token` 时可省略
+    pub encrypt_pk: Option<String>,
+    /// `POST /session` 返回的会话令牌
+    pub session_token: Option<String>,
+}
+
+/// 创建订单组的 API 端点。
+///
+/// 用于阶梯单、批量单等一次创建多笔订单的场景。同一钱包使用相同的 `client_group_id` 重试时
+/// 返回第一次创建的订单组和订单 ID，不会重复创建；任意一笔订单参数无效时整组都不会创建。
+///
+/// # 示例
+/// ```bash
+/// curl -X POST http://localhost:8000/place_order_group \
+///   -H 'Content-Type: application/json' \
+///   -d '{"client_group_id": "ladder-7f3a", "encrypt_pk": "SGVsbG8gV29ybGQ=", "orders": [{"input_mint": "So11111111111111111111111111111111111111112", "output_mint": "JUPyiwrYJFskUPiHa7hkeR8VUtAeFoSYbKedZNsDvCN", "price": 150.0, "amount": 1000000000, "slippage_bps": 50, "tip_amount": null, "priority_fee_micro_lamports": null}]}'
+/// ```
+/// 响应：
+/// ```json
+/// {
+///     "success": true,
+///     "data": {
+///         "group_id": "0b7d6c1e-5d1a-4f7a-9a57-2f0a3c1d9e11",
+///         "client_group_id": "ladder-7f3a",
+///         "owner": "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM",
+///         "order_ids": ["550e8400-e29b-41d4-a716-446655440000"]
+///     },
+///     "error": null
+/// }
+/// ```
+#[post("/place_order_group", data = "<request>")]
+pub async fn place_order_group(
+    _auth: AuthContext,
+    request: Json<PlaceOrderGroupRequest>,
+    client_ip: Option<IpAddr>,
+    limiter: &State<Arc<RateLimiter>>,
+    order_book: &State<Mutex<OrderBook>>,
+) -> Json<ApiResponse<OrderGroup>> {
+    if let Err(e) = check_rate_limit(limiter, &[ip_key(client_ip)]) {
+        return Json(e);
+    }
+    let request = request.into_inner();
+    for leg in &request.orders {
+        if let Err(e) = validate_order_params(
+            &leg.input_mint,
+            &leg.output_mint,
+            leg.price,
+            leg.amount,
+            leg.tip_amount,
+            leg.kind,
+            leg.trigger_condition,
+        ) {
+            return Json(e.into());
+        }
+    }
+    let (prik, owner) = match order_private_key(
+        &mut *order_book.lock().await,
+        request.encrypt_pk.as_deref(),
+        request.session_token.as_deref(),
+    )
+    .and_then(|prik| parse_keypair(prik.expose()).map(|keypair| (prik, keypair.pubkey())))
+    {
+        Ok(key) => key,
+        Err(e) => return Json(ApiError::new("INVALID_KEY", format!("私钥解析失败 {}", e)).into()),
+    };
+    if let Err(e) = check_rate_limit(limiter, &user_keys(limiter, &owner)) {
+        return Json(e);
+    }
+    match OrderBook::place_order_group(order_book, prik, request.client_group_id, request.orders)
+        .await
+    {
+        Ok(group) => Json(ApiResponse {
+            success: true,
+            data: Some(group),
+            error: None,
+            error_code: None,
+            retry_after_ms: None,
+        }),
+        Err(e) => Json(
+            ApiError::new(
+                e.code().unwrap_or("PLACE_FAILED"),
+                format!("订单组创建失败 {:#}", e),
+            )
+            .into(),
+        ),
+    }
+}
+
+/// 单次批量下单的最大订单数
+pub const MAX_BATCH_ORDERS: usize = 50;
+
+#[derive(Deserialize, JsonSchema)]
+pub struct PlaceOrdersRequest {
+    /// 批量创建的订单，共用同一个私钥
+    pub orders: Vec<OrderLeg>,
+    /// 加密后的pk，提供 `session_token` 时可省略
+    pub encrypt_pk: Option<String>,
+    /// `POST /session` 返回的会话令牌
+    pub session_token: Option<String>,
+}
+
+/// 批量下单的 API 端点。
+///
+/// 所有订单的参数以及钱包的订单数量限制都检查通过后才开始创建，任意一笔无效时一笔都不会创建；
+/// 创建过程中失败时已创建的订单会被取消。返回的订单 ID 与请求中的顺序一致。
+/// 与订单组不同，批量下单没有幂等键，重试会重复创建。
+///
+/// # 示例
+/// ```bash
+/// curl -X POST http://localhost:8000/place_orders \
+///   -H 'Content-Type: application/json' \
+///   -d '{"encrypt_pk": "SGVsbG8gV29ybGQ=", "orders": [{"input_mint": "So11111111111111111111111111111111111111112", "output_mint": "JUPyiwrYJFskUPiHa7hkeR8VUtAeFoSYbKedZNsDvCN", "price": 150.0, "amount": 1000000000, "slippage_bps": 50, "tip_amount": null, "priority_fee_micro_lamports": null}, {"input_mint": "So11111111111111111111111111111111111111112", "output_mint": "JUPyiwrYJFskUPiHa7hkeR8VUtAeFoSYbKedZNsDvCN", "price": 155.0, "amount": 1000000000, "slippage_bps": 50, "tip_amount": null, "priority_fee_micro_lamports": null}]}'
+/// ```
+/// 响应：
+/// ```json
+/// {
+///     "success": true,
+///     "data": ["550e8400-e29b-41d4-a716-446655440000", "6fa459ea-ee8a-3ca4-894e-db77e160355e"],
+///     "error": null
+/// }
+/// ```
+#[post("/place_orders", data = "<request>")]
+pub async fn place_orders(
+    _auth: AuthContext,
+    request: Json<PlaceOrdersRequest>,
+    client_ip: Option<IpAddr>,
+    limiter: &State<Arc<RateLimiter>>,
+    order_book: &State<Mutex<OrderBook>>,
+) -> Json<ApiResponse<Vec<Uuid>>> {
+    if let Err(e) = check_rate_limit(limiter, &[ip_key(client_ip)]) {
+        return Json(e);
+    }
+    let request = request.into_inner();
+    if request.orders.len() > MAX_BATCH_ORDERS {
+        return Json(
+            ApiError::new(
+                "TOO_MANY_ORDERS",
+                format!("单次最多 {} 笔订单", MAX_BATCH_ORDERS),
+            )
+            .into(),
+        );
+    }
+    for (i, leg) in request.orders.iter().enumerate() {
+        if let Err(mut e) = validate_order_params(
+            &leg.input_mint,
+            &leg.output_mint,
+            leg.price,
+            leg.amount,
+            leg.tip_amount,
+            leg.kind,
+            leg.trigger_condition,
+        ) {
+            e.message = format!("第 {} 笔订单参数无效: {}", i, e.message);
+            return Json(e.into());
+        }
+    }
+    let (prik, owner) = match order_private_key(
+        &mut *order_book.lock().await,
+        request.encrypt_pk.as_deref(),
+        request.session_token.as_deref(),
+    )
+    .and_then(|prik| parse_keypair(prik.expose()).map(|keypair| (prik, keypair.pubkey())))
+    {
+        Ok(key) => key,
+        Err(e) => return Json(ApiError::new("INVALID_KEY", format!("私钥解析失败 {}", e)).into()),
+    };
+    if let Err(e) = check_rate_limit(limiter, &user_keys(limiter, &owner)) {
+        return Json(e);
+    }
+    match OrderBook::place_orders(order_book, prik, request.orders).await {
+        Ok(order_ids) => Json(ApiResponse {
+            success: true,
+            data: Some(order_ids),
+            error: None,
+            error_code: None,
+            retry_after_ms: None,
+        }),
+        Err(e) => Json(
+            ApiError::new(
+                e.code().unwrap_or("PLACE_FAILED"),
+                format!("批量下单失败 {:#}", e),
+            )
+            .into(),
+        ),
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct PlaceBracketRequest {
+    /// 止盈订单，价格涨到 `price` 及以上时触发，`kind` 会被设为 `TakeProfit`
+    pub take_profit: OrderLeg,
+    /// 止损订单，价格跌到 `price` 及以下时触发，`kind` 会被设为 `StopLoss`
+    pub stop_loss: OrderLeg,
+    /// 加密后的pk，提供 `session_token` 时可省略
+    pub encrypt_pk: Option<String>,
+    /// `POST /session` 返回的会话令牌
+    pub session_token: Option<String>,
+}
+
+/// 创建止盈止损（OCO）订单的 API 端点。
+///
+/// 同时创建止盈和止损两笔订单，两笔共用一个 `group_id`，其中一笔触发后另一笔自动取消；
+/// 两笔在同一轮价格中同时满足条件时只有一笔会执行。两笔订单必须是同一代币对、相同的 `trigger_on`，
+/// 止盈价格必须高于止损价格。订单的 `group_id` 和 `oco_sibling` 字段记录两笔订单的关联。
+///
+/// # 示例
+/// ```bash
+/// curl -X POST http://localhost:8000/place_bracket \
+///   -H 'Content-Type: application/json' \
+///   -d '{"encrypt_pk": "SGVsbG8gV29ybGQ=", "take_profit": {"input_mint": "So11111111111111111111111111111111111111112", "output_mint": "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v", "price": 180.0, "amount": 1000000000, "slippage_bps": 50, "tip_amount": null, "priority_fee_micro_lamports": null}, "stop_loss": {"input_mint": "So11111111111111111111111111111111111111112", "output_mint": "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v", "price": 130.0, "amount": 1000000000, "slippage_bps": 100, "tip_amount": null, "priority_fee_micro_lamports": null}}'
+/// ```
+/// 响应：
+/// ```json
+/// {
+///     "success": true,
+///     "data": {
+///         "group_id": "0b7d6c1e-5d1a-4f7a-9a57-2f0a3c1d9e11",
+///         "take_profit": "550e8400-e29b-41d4-a716-446655440000",
+///         "stop_loss": "6fa459ea-ee8a-3ca4-894e-db77e160355e"
+///     },
+///     "error": null
+/// }
+/// ```
+#[post("/place_bracket", data = "<request>")]
+pub async fn place_bracket(
+    _auth: AuthContext,
+    request: Json<PlaceBracketRequest>,
+    client_ip: Option<IpAddr>,
+    limiter: &State<Arc<RateLimiter>>,
+    order_book: &State<Mutex<OrderBook>>,
+) -> Json<ApiResponse<Bracket>> {
+    if let Err(e) = check_rate_limit(limiter, &[ip_key(client_ip)]) {
+        return Json(e);
+    }
+    let request = request.into_inner();
+    for (name, leg, kind) in [
+        ("止盈", &request.take_profit, OrderKind::TakeProfit),
+        ("止损", &request.stop_loss, OrderKind::StopLoss),
+    ] {
+        if let Err(mut e) = validate_order_params(
+            &leg.input_mint,
+            &leg.output_mint,
+            leg.price,
+            leg.amount,
+            leg.tip_amount,
+            kind,
+            None,
+        ) {
+            e.message = format!("{}订单参数无效: {}", name, e.message);
+            return Json(e.into());
+        }
+    }
+    let (prik, owner) = match order_private_key(
+        &mut *order_book.lock().await,
+        request.encrypt_pk.as_deref(),
+        request.session_token.as_deref(),
+    )
+    .and_then(|prik| parse_keypair(prik.expose()).map(|keypair| (prik, keypair.pubkey())))
+    {
+        Ok(key) => key,
+        Err(e) => return Json(ApiError::new("INVALID_KEY", format!("私钥解析失败 {}", e)).into()),
+    };
+    if let Err(e) = check_rate_limit(limiter, &user_keys(limiter, &owner)) {
+        return Json(e);
+    }
+    match OrderBook::place_bracket(order_book, prik, request.take_profit, request.stop_loss).await {
+        Ok(bracket) => Json(ApiResponse {
+            success: true,
+            data: Some(bracket),
+            error: None,
+            error_code: None,
+            retry_after_ms: None,
         }),
+        Err(e) => Json(
+            ApiError::new(
+                e.code().unwrap_or("PLACE_FAILED"),
+                format!("止盈止损下单失败 {:#}", e),
+            )
+            .into(),
+        ),
     }
 }
 
-#[derive(Deserialize)]
-struct CancelOrderRequest {
+/// 撤单请求，需要证明请求者是下单钱包：提供 `user` 和 `signature`，
+/// 或者提供与下单时相同的 `encrypt_pk` / `session_token`
+#[derive(Deserialize, JsonSchema)]
+pub struct CancelOrderRequest {
     pub order_id: Uuid,
+    /// 下单钱包的公钥
+    pub user: Option<String>,
+    /// `user` 对订单 ID 字符串的 ed25519 签名，bs58 编码
+    pub signature: Option<String>,
+    /// 加密后的pk
+    pub encrypt_pk: Option<String>,
+    /// `POST /session` 返回的会话令牌
+    pub session_token: Option<String>,
+}
+
+/// 确认撤单请求者的钱包地址
+fn cancel_requester(
+    order_book: &mut OrderBook,
+    request: &CancelOrderRequest,
+) -> anyhow::Result<Pubkey> {
+    match (&request.user, &request.signature) {
+        (Some(user), Some(signature)) => {
+            let user = user
+                .parse::<Pubkey>()
+                .map_err(|_| anyhow!("用户地址无效"))?;
+            verify_cancel_signature(&user, &request.order_id, signature)?;
+            Ok(user)
+        }
+        _ => {
+            let prik = order_private_key(
+                order_book,
+                request.encrypt_pk.as_deref(),
+                request.session_token.as_deref(),
+            )?;
+            Ok(parse_keypair(prik.expose())?.pubkey())
+        }
+    }
 }
 
 /// 取消订单的 API 端点。
 ///
-/// 该端点接受一个撤单请求，根据订单 ID 在订单簿中取消指定订单。
+/// 该端点接受一个撤单请求，确认请求者是下单钱包后取消指定订单。
+/// 请求者可以用钱包对订单 ID 签名证明身份，也可以沿用下单时的 `encrypt_pk` 或 `session_token`。
+/// 请求者不是下单钱包时返回 "无权限取消该订单"（`error_code` 为 `UNAUTHORIZED`），订单不受影响；
+/// 订单不存在时 `error_code` 为 `ORDER_NOT_FOUND`。
 ///
 /// # 参数
-/// * `request` - 撤单请求的 JSON 数据，包含订单 ID。
+/// * `request` - 撤单请求的 JSON 数据，包含订单 ID 和身份证明。
 /// * `order_book` - 订单簿的共享状态，使用 `Mutex` 保护以支持并发访问。
 ///
 /// # 返回值
@@ -126,7 +898,7 @@ struct CancelOrderRequest {
 /// ```bash
 /// curl -X POST http://localhost:8000/cancel_order \
 ///   -H 'Content-Type: application/json' \
-///   -d '{"order_id": "550e8400-e29b-41d4-a716-446655440000"}'
+///   -d '{"order_id": "550e8400-e29b-41d4-a716-446655440000", "user": "7xKX...", "signature": "5VER..."}'
 /// ```
 /// 响应：
 /// ```json
@@ -138,22 +910,2250 @@ struct CancelOrderRequest {
 /// ```
 #[post("/cancel_order", data = "<request>")]
 pub async fn cancel_order(
+    _auth: AuthContext,
     request: Json<CancelOrderRequest>,
     order_book: &State<Mutex<OrderBook>>,
 ) -> Json<ApiResponse<String>> {
     let mut order_book = order_book.lock().await;
-    let result = order_book.cancel_order(request.order_id).await;
+    let result = match cancel_requester(&mut order_book, &request) {
+        Ok(requester) => order_book.cancel_order(request.order_id, &requester).await,
+        Err(e) => Err(LimitOrderError::Unauthorized(e.to_string())),
+    };
 
     match result {
         Ok(()) => Json(ApiResponse {
             success: true,
             data: Some("撤单成功".to_string()),
             error: None,
+            error_code: None,
+            retry_after_ms: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            error: Some(e.to_string()),
+            error_code: e.code().map(str::to_string),
+            retry_after_ms: None,
+        }),
+    }
+}
+
+/// 管理员取消任意订单的 API 端点，需要 admin key，不需要下单钱包的签名。
+///
+/// # 示例
+/// ```bash
+/// curl -X POST http://localhost:8000/admin/cancel_order/550e8400-e29b-41d4-a716-446655440000 \
+///   -H 'X-Api-Key: <admin key>'
+/// ```
+#[post("/admin/cancel_order/<order_id>")]
+pub async fn admin_cancel_order(
+    _admin: AdminContext,
+    order_id: Uuid,
+    order_book: &State<Mutex<OrderBook>>,
+) -> Json<ApiResponse<String>> {
+    let mut order_book = order_book.lock().await;
+    match order_book.admin_cancel_order(order_id).await {
+        Ok(()) => Json(ApiResponse {
+            success: true,
+            data: Some("撤单成功".to_string()),
+            error: None,
+            error_code: None,
+            retry_after_ms: None,
         }),
         Err(e) => Json(ApiResponse {
             success: false,
             data: None,
             error: Some(e.to_string()),
+            error_code: e.code().map(str::to_string),
+            retry_after_ms: None,
+        }),
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ModifyOrderRequest {
+    pub order_id: Uuid,
+    /// 加密后的pk，提供 `session_token` 时可省略；修改后的订单需要私钥签名，因此不支持签名方式的身份证明
+    pub encrypt_pk: Option<String>,
+    /// `POST /session` 返回的会话令牌
+    pub session_token: Option<String>,
+    /// 新的参数，未提供的字段保持不变
+    #[serde(flatten)]
+    pub changes: OrderChanges,
+}
+
+/// 修改订单的 API 端点。
+///
+/// 修改等待触发的订单的 `price`、`amount` 或 `slippage_bps`，订单 ID 不变。旧的监控任务退出后才启动新任务，
+/// 不会出现新旧订单同时存在或都不存在的情况。订单已经触发时 `error_code` 为 `ORDER_ALREADY_TRIGGERED`。
+///
+/// # 示例
+/// ```bash
+/// curl -X POST http://localhost:8000/modify_order \
+///   -H 'Content-Type: application/json' \
+///   -d '{"order_id": "550e8400-e29b-41d4-a716-446655440000", "encrypt_pk": "SGVsbG8gV29ybGQ=", "price": 155.0}'
+/// ```
+/// 响应的 `data` 为修改后的订单。
+#[post("/modify_order", data = "<request>")]
+pub async fn modify_order(
+    _auth: AuthContext,
+    request: Json<ModifyOrderRequest>,
+    order_book: &State<Mutex<OrderBook>>,
+) -> Json<ApiResponse<Order>> {
+    let request = request.into_inner();
+    let mut order_book = order_book.lock().await;
+    let prik = match order_private_key(
+        &mut order_book,
+        request.encrypt_pk.as_deref(),
+        request.session_token.as_deref(),
+    ) {
+        Ok(prik) => prik,
+        Err(e) => return Json(ApiError::new("INVALID_KEY", format!("私钥解析失败 {}", e)).into()),
+    };
+    match order_book
+        .modify_order(request.order_id, prik, request.changes)
+        .await
+    {
+        Ok(order) => Json(ApiResponse {
+            success: true,
+            data: Some(order),
+            error: None,
+            error_code: None,
+            retry_after_ms: None,
+        }),
+        Err(e) => Json(
+            ApiError::new(
+                e.code().unwrap_or("MODIFY_FAILED"),
+                format!("修改订单失败 {:#}", e),
+            )
+            .into(),
+        ),
+    }
+}
+
+/// 批量撤单请求，身份证明与单笔撤单相同；签名方式为对 `cancel_all:<timestamp>` 签名
+#[derive(Deserialize, JsonSchema)]
+pub struct CancelAllRequest {
+    /// 下单钱包的公钥
+    pub user: Option<String>,
+    /// `user` 对 `cancel_all:<timestamp>` 的 ed25519 签名，bs58 编码
+    pub signature: Option<String>,
+    /// 签名中的 unix 秒，与服务器时间相差不超过 60 秒
+    pub timestamp: Option<u64>,
+    /// 加密后的pk
+    pub encrypt_pk: Option<String>,
+    /// `POST /session` 返回的会话令牌
+    pub session_token: Option<String>,
+    /// 只取消指定输入代币的订单
+    pub input_mint: Option<String>,
+    /// 只取消指定输出代币的订单
+    pub output_mint: Option<String>,
+}
+
+/// 确认批量撤单请求者的钱包地址，只有使用会话令牌时才需要订单簿的锁
+async fn cancel_all_requester(
+    order_book: &Mutex<OrderBook>,
+    request: &CancelAllRequest,
+) -> anyhow::Result<Pubkey> {
+    match (&request.user, &request.signature, request.timestamp) {
+        (Some(user), Some(signature), Some(timestamp)) => {
+            let user = user
+                .parse::<Pubkey>()
+                .map_err(|_| anyhow!("用户地址无效"))?;
+            verify_cancel_all_signature(&user, timestamp, signature)?;
+            Ok(user)
+        }
+        _ => {
+            let prik = order_private_key(
+                &mut *order_book.lock().await,
+                request.encrypt_pk.as_deref(),
+                request.session_token.as_deref(),
+            )?;
+            Ok(parse_keypair(prik.expose())?.pubkey())
+        }
+    }
+}
+
+/// 批量撤单的 API 端点。
+///
+/// 取消请求者钱包下所有等待触发的订单，可以用 `input_mint` / `output_mint` 只取消部分代币的订单。
+/// 撤单过程中已经触发或成交的订单放在 `already_filled` 中返回，不视为错误。
+///
+/// # 示例
+/// ```bash
+/// curl -X POST http://localhost:8000/cancel_all \
+///   -H 'Content-Type: application/json' \
+///   -d '{"user": "7xKX...", "timestamp": 1735689600, "signature": "5VER...", "input_mint": "JUPyiwrYJFskUPiHa7hkeR8VUtAeFoSYbKedZNsDvCN"}'
+/// ```
+/// 响应：
+/// ```json
+/// {
+///     "success": true,
+///     "data": {
+///         "canceled": ["550e8400-e29b-41d4-a716-446655440000"],
+///         "already_filled": [],
+///         "failed": []
+///     },
+///     "error": null
+/// }
+/// ```
+#[post("/cancel_all", data = "<request>")]
+pub async fn cancel_all(
+    _auth: AuthContext,
+    request: Json<CancelAllRequest>,
+    order_book: &State<Mutex<OrderBook>>,
+) -> Json<ApiResponse<CancelAllResult>> {
+    // 先在锁外确认请求者，签名校验不阻塞其他接口
+    let requester = match cancel_all_requester(order_book, &request).await {
+        Ok(requester) => requester,
+        Err(e) => return Json(ApiError::new("UNAUTHORIZED", e.to_string()).into()),
+    };
+    let filter = CancelFilter {
+        input_mint: request.input_mint.clone(),
+        output_mint: request.output_mint.clone(),
+    };
+    let result = order_book
+        .lock()
+        .await
+        .cancel_matching(&requester, &filter)
+        .await;
+    Json(ApiResponse {
+        success: true,
+        data: Some(result),
+        error: None,
+        error_code: None,
+        retry_after_ms: None,
+    })
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct ReadyStatus {
+    /// 存储是否处于降级状态，降级时订单仍在内存中正常执行，变更暂存在本地日志
+    pub degraded: bool,
+    /// 是否通过 `/pause` 暂停了全部订单的执行
+    pub paused: bool,
+}
+
+/// 就绪检查的 API 端点。
+///
+/// 服务在数据库不可用时仍然可以继续交易，因此该端点总是返回 `success: true`，
+/// 通过 `degraded` 字段告知调用方持久化是否处于降级状态。
+///
+/// # 示例
+/// ```bash
+/// curl http://localhost:8000/ready
+/// ```
+/// 响应：
+/// ```json
+/// {
+///     "success": true,
+///     "data": { "degraded": false },
+///     "error": null
+/// }
+/// ```
+#[get("/ready")]
+pub async fn ready(order_book: &State<Mutex<OrderBook>>) -> Json<ApiResponse<ReadyStatus>> {
+    let order_book = order_book.lock().await;
+    Json(ApiResponse {
+        success: true,
+        data: Some(ReadyStatus {
+            degraded: order_book.is_degraded(),
+            paused: order_book.pause.state().global,
         }),
+        error: None,
+        error_code: None,
+        retry_after_ms: None,
+    })
+}
+
+/// `/health` 结果的缓存时间，避免负载均衡的探测频繁请求上游
+const HEALTH_CACHE_TTL: Duration = Duration::from_secs(5);
+
+static HEALTH_CACHE: StdMutex<Option<(Instant, HealthReport)>> = StdMutex::new(None);
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct HealthReport {
+    /// RPC 可用时为 true；价格接口和 Jito 异常只影响各自的状态
+    pub ok: bool,
+    pub rpc: DependencyHealth,
+    /// 各 RPC 节点的健康状态，`rpc` 为故障切换后的整体结果
+    pub rpc_endpoints: Vec<EndpointStatus>,
+    pub price_feed: DependencyHealth,
+    pub jito: DependencyHealth,
+}
+
+/// 健康检查的 API 端点。
+///
+/// 并发检查 RPC（`getLatestBlockhash`）、Jupiter 价格接口和 Jito（`getTipAccounts`），
+/// 返回每个依赖的状态与耗时。只有 RPC 不可用时返回 503；价格接口或 Jito 异常时服务仍可降级运行，
+/// 只在对应字段中体现。配置多个 RPC 节点时，任一节点可用即视为 RPC 可用，`rpc_endpoints` 列出各节点的状态。
+/// 结果缓存 5 秒。
+///
+/// # 示例
+/// ```bash
+/// curl http://localhost:8000/health
+/// ```
+/// 响应：
+/// ```json
+/// {
+///     "success": true,
+///     "data": {
+///         "ok": true,
+///         "rpc": { "ok": true, "latency_ms": 85 },
+///         "rpc_endpoints": [
+///             { "url": "https://api.mainnet-beta.solana.com", "healthy": true, "consecutive_failures": 0, "last_latency_ms": 85, "last_error": null }
+///         ],
+///         "price_feed": { "ok": true, "latency_ms": 120 },
+///         "jito": { "ok": false, "latency_ms": 3000, "error": "超过 3s 未响应" }
+///     },
+///     "error": null
+/// }
+/// ```
+#[get("/health")]
+pub async fn health(
+    order_book: &State<Mutex<OrderBook>>,
+) -> (Status, Json<ApiResponse<HealthReport>>) {
+    let cached = match HEALTH_CACHE.lock().unwrap().as_ref() {
+        Some((checked_at, report)) if checked_at.elapsed() < HEALTH_CACHE_TTL => {
+            Some(report.clone())
+        }
+        _ => None,
+    };
+    let report = match cached {
+        Some(report) => report,
+        None => {
+            // 检查可能耗时数秒，不持有订单簿的锁
+            let (rpc, price_source, jito) = {
+                let order_book = order_book.lock().await;
+                (
+                    order_book.rpc.clone(),
+                    order_book.price_source.clone(),
+                    order_book.jito.clone(),
+                )
+            };
+            let (rpc_health, price_feed, jito) = tokio::join!(
+                check_rpc(&rpc),
+                check_price_feed(price_source),
+                check_jito(&jito)
+            );
+            let report = HealthReport {
+                ok: rpc_health.ok,
+                rpc: rpc_health,
+                rpc_endpoints: rpc.endpoint_status(),
+                price_feed,
+                jito,
+            };
+            *HEALTH_CACHE.lock().unwrap() = Some((Instant::now(), report.clone()));
+            report
+        }
+    };
+    let status = if report.ok {
+        Status::Ok
+    } else {
+        Status::ServiceUnavailable
+    };
+    (
+        status,
+        Json(ApiResponse {
+            success: report.ok,
+            error: (!report.ok).then(|| "RPC 不可用".to_string()),
+            data: Some(report),
+            error_code: None,
+            retry_after_ms: None,
+        }),
+    )
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct RevokeWalletRequest {
+    /// 需要吊销的钱包公钥
+    pub wallet: String,
+}
+
+/// 吊销钱包的 API 端点。
+///
+/// 用于私钥泄露的场景：取消该钱包的全部活跃订单，释放订单任务中持有的私钥，并拒绝该钱包后续的下单请求。
+///
+/// # 返回值
+/// 返回被取消的订单 ID 列表。
+///
+/// # 示例
+/// ```bash
+/// curl -X POST http://localhost:8000/admin/revoke_wallet \
+///   -H 'Content-Type: application/json' \
+///   -d '{"wallet": "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM"}'
+/// ```
+#[post("/admin/revoke_wallet", data = "<request>")]
+pub async fn revoke_wallet(
+    _admin: AdminContext,
+    request: Json<RevokeWalletRequest>,
+    order_book: &State<Mutex<OrderBook>>,
+) -> Json<ApiResponse<Vec<Uuid>>> {
+    let wallet = match request.wallet.parse::<Pubkey>() {
+        Ok(wallet) => wallet,
+        Err(_) => {
+            return Json(ApiResponse {
+                success: false,
+                data: None,
+                error: Some("钱包地址无效".to_string()),
+                error_code: None,
+                retry_after_ms: None,
+            })
+        }
+    };
+    let mut order_book = order_book.lock().await;
+    match order_book.revoke_wallet(wallet).await {
+        Ok(canceled) => Json(ApiResponse {
+            success: true,
+            data: Some(canceled),
+            error: None,
+            error_code: None,
+            retry_after_ms: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            error: Some(e.to_string()),
+            error_code: None,
+            retry_after_ms: None,
+        }),
+    }
+}
+
+/// 暂停全部订单执行的 API 端点，需要 admin key。
+///
+/// 用于 RPC 故障、脱锚等场景：订单保留并继续监控价格，触发后不发送交易，等待 `/resume` 或撤单；
+/// 恢复后按新一轮价格重新判断是否触发。返回当前的暂停状态。
+///
+/// # 示例
+/// ```bash
+/// curl -X POST http://localhost:8000/pause -H 'X-Api-Key: <admin key>'
+/// ```
+#[post("/pause")]
+pub async fn pause(
+    _admin: AdminContext,
+    order_book: &State<Mutex<OrderBook>>,
+) -> Json<ApiResponse<PauseState>> {
+    set_paused(order_book, true).await
+}
+
+/// 恢复全部订单执行的 API 端点，需要 admin key。单独暂停的订单保持暂停。
+///
+/// # 示例
+/// ```bash
+/// curl -X POST http://localhost:8000/resume -H 'X-Api-Key: <admin key>'
+/// ```
+#[post("/resume")]
+pub async fn resume(
+    _admin: AdminContext,
+    order_book: &State<Mutex<OrderBook>>,
+) -> Json<ApiResponse<PauseState>> {
+    set_paused(order_book, false).await
+}
+
+async fn set_paused(
+    order_book: &State<Mutex<OrderBook>>,
+    paused: bool,
+) -> Json<ApiResponse<PauseState>> {
+    let order_book = order_book.lock().await;
+    order_book.pause.set_global(paused);
+    println!(
+        "全部订单{}",
+        if paused {
+            "已暂停执行"
+        } else {
+            "已恢复执行"
+        }
+    );
+    Json(ApiResponse {
+        success: true,
+        data: Some(order_book.pause.state()),
+        error: None,
+        error_code: None,
+        retry_after_ms: None,
+    })
+}
+
+/// 暂停单笔订单执行的 API 端点，需要 admin key。订单的 `paused` 字段为 true。
+///
+/// # 示例
+/// ```bash
+/// curl -X POST http://localhost:8000/pause_order/550e8400-e29b-41d4-a716-446655440000 \
+///   -H 'X-Api-Key: <admin key>'
+/// ```
+#[post("/pause_order/<order_id>")]
+pub async fn pause_order(
+    order_id: Uuid,
+    _admin: AdminContext,
+    order_book: &State<Mutex<OrderBook>>,
+) -> Json<ApiResponse<PauseState>> {
+    set_order_paused(order_id, order_book, true).await
+}
+
+/// 恢复单笔订单执行的 API 端点，需要 admin key。
+///
+/// # 示例
+/// ```bash
+/// curl -X POST http://localhost:8000/resume_order/550e8400-e29b-41d4-a716-446655440000 \
+///   -H 'X-Api-Key: <admin key>'
+/// ```
+#[post("/resume_order/<order_id>")]
+pub async fn resume_order(
+    order_id: Uuid,
+    _admin: AdminContext,
+    order_book: &State<Mutex<OrderBook>>,
+) -> Json<ApiResponse<PauseState>> {
+    set_order_paused(order_id, order_book, false).await
+}
+
+async fn set_order_paused(
+    order_id: Uuid,
+    order_book: &State<Mutex<OrderBook>>,
+    paused: bool,
+) -> Json<ApiResponse<PauseState>> {
+    let order_book = order_book.lock().await;
+    match order_book.set_order_paused(order_id, paused).await {
+        Ok(()) => Json(ApiResponse {
+            success: true,
+            data: Some(order_book.pause.state()),
+            error: None,
+            error_code: None,
+            retry_after_ms: None,
+        }),
+        Err(e) => Json(ApiError::new(e.code().unwrap_or("PAUSE_FAILED"), e.to_string()).into()),
+    }
+}
+
+/// 查询已吊销钱包列表的 API 端点。
+///
+/// # 示例
+/// ```bash
+/// curl http://localhost:8000/admin/revoked
+/// ```
+#[get("/admin/revoked")]
+pub async fn revoked_wallets(
+    _admin: AdminContext,
+    order_book: &State<Mutex<OrderBook>>,
+) -> Json<ApiResponse<Vec<RevokedWallet>>> {
+    let order_book = order_book.lock().await;
+    Json(ApiResponse {
+        success: true,
+        data: Some(order_book.revoked.values().cloned().collect()),
+        error: None,
+        error_code: None,
+        retry_after_ms: None,
+    })
+}
+
+/// 查询收税账户余额的 API 端点。
+///
+/// 返回 SOL 余额（lamports）以及 spl-token 和 Token-2022 代币账户的余额（最小单位）。
+///
+/// # 示例
+/// ```bash
+/// curl http://localhost:8000/treasury
+/// ```
+/// 响应：
+/// ```json
+/// {
+///     "success": true,
+///     "data": {
+///         "account": "5Hn1...",
+///         "sol_lamports": 2350000000,
+///         "tokens": [{"token_account": "9Wq2...", "mint": "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v", "token_program": "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA", "amount": 182500000, "decimals": 6}]
+///     },
+///     "error": null
+/// }
+/// ```
+#[get("/treasury")]
+pub async fn treasury(
+    _auth: AuthContext,
+    order_book: &State<Mutex<OrderBook>>,
+) -> Json<ApiResponse<TreasuryBalances>> {
+    let order_book = order_book.lock().await;
+    match order_book.treasury().await {
+        Ok(balances) => Json(ApiResponse {
+            success: true,
+            data: Some(balances),
+            error: None,
+            error_code: None,
+            retry_after_ms: None,
+        }),
+        Err(e) => Json(ApiError::new("RPC_FAILED", format!("查询余额失败 {:#}", e)).into()),
+    }
+}
+
+/// 将收税账户余额归集到冷钱包的 API 端点。
+///
+/// 需要配置 `TAX_ACCOUNT_PK` 和 `TREASURY_COLD_WALLET`。SOL 保留 `TREASURY_SOL_FLOAT_LAMPORTS`，
+/// 每次最多 `TREASURY_MAX_TRANSFERS` 条转账，价值低于代币账户租金的代币不归集。交易先模拟再发送；
+/// 有订单的交易已发送但尚未确认时拒绝归集，错误码为 `SWEEP_FAILED`。
+///
+/// # 示例
+/// ```bash
+/// curl -X POST http://localhost:8000/treasury/sweep -H 'X-Api-Key: <admin key>'
+/// ```
+/// 响应：
+/// ```json
+/// {
+///     "success": true,
+///     "data": {
+///         "signature": "3xTf...",
+///         "swept": [{"mint": "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v", "amount": 182500000}, {"mint": null, "amount": 2340000000}],
+///         "skipped": []
+///     },
+///     "error": null
+/// }
+/// ```
+#[post("/treasury/sweep")]
+pub async fn sweep_treasury(
+    _admin: AdminContext,
+    order_book: &State<Mutex<OrderBook>>,
+) -> Json<ApiResponse<SweepResult>> {
+    let order_book = order_book.lock().await;
+    match order_book.sweep_treasury().await {
+        Ok(result) => Json(ApiResponse {
+            success: true,
+            data: Some(result),
+            error: None,
+            error_code: None,
+            retry_after_ms: None,
+        }),
+        Err(e) => Json(ApiError::new("SWEEP_FAILED", format!("归集失败 {:#}", e)).into()),
+    }
+}
+
+/// 终态订单历史查询的 API 端点。
+///
+/// 从数据库 `orders` 表读取成交、部分成交、取消、失败、签名过期和有效期内未触发的订单，按下单时间倒序分页。
+/// 可按下单钱包 `user`、终态 `status`（`filled`、`partially_filled`、`canceled`、`failed`、`resign_required`、`unfilled`、`expired`）
+/// 和代币 `mint`（输入或输出）过滤；`limit` 默认 50，最大 200。响应中的 `next_cursor` 作为下一页的 `cursor`，
+/// 为 null 时已经是最后一页。`out_amount` 为扣税后至少得到的输出数量（按报价的滑点下限计算）。
+/// `input_symbol`、`output_symbol` 为代币符号，`ui_amount` 为按代币精度换算后的订单数量，查不到代币信息时为 null。
+/// 未配置 `DATABASE_URL` 或数据库不可用时错误码为 `DATABASE_UNAVAILABLE`。
+///
+/// # 示例
+/// ```bash
+/// curl 'http://localhost:8000/orders/history?user=9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM&status=filled&limit=2'
+/// ```
+/// 响应：
+/// ```json
+/// {
+///     "success": true,
+///     "data": {
+///         "orders": [{
+///             "order_id": "550e8400-e29b-41d4-a716-446655440000",
+///             "owner": "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM",
+///             "input_mint": "So11111111111111111111111111111111111111112",
+///             "output_mint": "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v",
+///             "input_symbol": "SOL",
+///             "output_symbol": "USDC",
+///             "ui_amount": "0.1",
+///             "status": "filled",
+///             "signature": "5VER...",
+///             "out_amount": 148050000,
+///             "tax_amount": 1000000,
+///             "tax_mint": "So11111111111111111111111111111111111111112",
+///             "error": null,
+///             "created_at": 1760600000000,
+///             "finalized_at": 1760600420000,
+///             "order": {"order_id": "550e8400-e29b-41d4-a716-446655440000", "price": 150.0, "amount": 100000000, "...": "..."}
+///         }],
+///         "next_cursor": "1760600000000_550e8400-e29b-41d4-a716-446655440000"
+///     },
+///     "error": null
+/// }
+/// ```
+#[get("/orders/history?<user>&<status>&<mint>&<limit>&<cursor>")]
+pub async fn order_history(
+    _auth: AuthContext,
+    user: Option<&str>,
+    status: Option<&str>,
+    mint: Option<&str>,
+    limit: Option<usize>,
+    cursor: Option<&str>,
+    order_book: &State<Mutex<OrderBook>>,
+) -> Json<ApiResponse<OrderHistoryPage>> {
+    if user.is_some_and(|user| user.parse::<Pubkey>().is_err()) {
+        return Json(ApiError::new("INVALID_REQUEST", "user 地址无效").into());
+    }
+    if mint.is_some_and(|mint| mint.parse::<Pubkey>().is_err()) {
+        return Json(ApiError::new("INVALID_REQUEST", "mint 地址无效").into());
+    }
+    if status.is_some_and(|status| !OrderStatus::TERMINAL.contains(&status)) {
+        return Json(
+            ApiError::new(
+                "INVALID_REQUEST",
+                format!("status 必须为 {}", OrderStatus::TERMINAL.join("、")),
+            )
+            .into(),
+        );
+    }
+    let limit = limit.unwrap_or(DEFAULT_HISTORY_LIMIT);
+    if limit == 0 || limit > MAX_HISTORY_LIMIT {
+        return Json(
+            ApiError::new(
+                "INVALID_REQUEST",
+                format!("limit 必须在 1 到 {} 之间", MAX_HISTORY_LIMIT),
+            )
+            .into(),
+        );
+    }
+    let cursor = match cursor.map(HistoryCursor::parse) {
+        Some(None) => return Json(ApiError::new("INVALID_REQUEST", "cursor 无效").into()),
+        Some(cursor) => cursor,
+        None => None,
+    };
+    // 查询期间不持有订单簿的锁
+    let (db, tokens) = {
+        let order_book = order_book.lock().await;
+        (order_book.db.clone(), order_book.tokens.clone())
+    };
+    let Some(pool) = db else {
+        return Json(ApiError::new("DATABASE_UNAVAILABLE", "未配置 DATABASE_URL").into());
+    };
+    let query = HistoryQuery {
+        owner: user.map(str::to_string),
+        status: status.map(str::to_string),
+        mint: mint.map(str::to_string),
+        limit,
+        cursor,
+    };
+    match db::order_history(&pool, query).await {
+        Ok(mut page) => {
+            for entry in &mut page.orders {
+                let input = tokens.lookup(&entry.input_mint).await;
+                let output = tokens.lookup(&entry.output_mint).await;
+                let amount_token = match entry.order.swap_mode {
+                    SwapMode::ExactIn => &input,
+                    SwapMode::ExactOut => &output,
+                };
+                entry.ui_amount = amount_token
+                    .as_ref()
+                    .map(|token| token.ui_amount(entry.order.amount));
+                entry.input_symbol = input.and_then(|token| token.symbol);
+                entry.output_symbol = output.and_then(|token| token.symbol);
+            }
+            Json(ApiResponse {
+                success: true,
+                data: Some(page),
+                error: None,
+                error_code: None,
+                retry_after_ms: None,
+            })
+        }
+        Err(e) => Json(
+            ApiError::new(
+                e.code().unwrap_or("DATABASE_UNAVAILABLE"),
+                format!("查询历史订单失败 {:#}", e),
+            )
+            .into(),
+        ),
+    }
+}
+
+/// 订单列表的 API 端点。
+///
+/// 返回订单簿中的全部订单，按下单时间排序；指定 `user` 时只返回该钱包的订单。
+/// 配置 `DATABASE_URL` 时已结束的订单不在订单簿中，通过 `/orders/history` 查询。
+/// 没有订单时返回空数组。`user` 不是有效地址时错误码为 `INVALID_REQUEST`。
+///
+/// # 示例
+/// ```bash
+/// curl 'http://localhost:8000/orders?user=9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM'
+/// ```
+/// 响应：
+/// ```json
+/// {
+///     "success": true,
+///     "data": [{
+///         "order_id": "550e8400-e29b-41d4-a716-446655440000",
+///         "owner": "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM",
+///         "input_mint": "So11111111111111111111111111111111111111112",
+///         "output_mint": "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v",
+///         "price": 150.0,
+///         "amount": 100000000,
+///         "slippage_bps": 50,
+///         "tip_amount": null,
+///         "status": "Pending",
+///         "...": "..."
+///     }],
+///     "error": null
+/// }
+/// ```
+#[get("/orders?<user>")]
+pub async fn list_orders(
+    _auth: AuthContext,
+    user: Option<&str>,
+    order_book: &State<Mutex<OrderBook>>,
+) -> Json<ApiResponse<Vec<Order>>> {
+    if user.is_some_and(|user| user.parse::<Pubkey>().is_err()) {
+        return Json(ApiError::new("INVALID_REQUEST", "user 地址无效").into());
+    }
+    let orders = order_book.lock().await.list_orders(user).await;
+    Json(ApiResponse {
+        success: true,
+        data: Some(orders),
+        error: None,
+        error_code: None,
+        retry_after_ms: None,
+    })
+}
+
+/// 批量查询订单状态时单次最多的订单数
+pub const MAX_STATUS_BATCH: usize = 200;
+
+/// 批量查询订单状态的 API 端点。
+///
+/// 请求体为订单 ID 数组，单次最多 [`MAX_STATUS_BATCH`] 个，返回以订单 ID 为键的状态；
+/// 未结束的订单从内存中的订单簿读取，已结束并写入数据库的订单从数据库批量读取，
+/// 都查不到的订单不会被省略，状态为 `not_found`。`detail` 为完整的订单状态。
+/// 数量超过上限时错误码为 `TOO_MANY_ORDERS`；需要查询数据库但数据库不可用时错误码为 `DATABASE_UNAVAILABLE`。
+///
+/// # 示例
+/// ```bash
+/// curl -X POST http://localhost:8000/order_statuses \
+///   -H 'Content-Type: application/json' \
+///   -d '["550e8400-e29b-41d4-a716-446655440000", "6fa459ea-ee8a-3ca4-894e-db77e160355e", "0b0c2f9e-3c1f-4f7a-9d43-1b1f0f6c2a11"]'
+/// ```
+/// 响应：
+/// ```json
+/// {
+///     "success": true,
+///     "data": {
+///         "550e8400-e29b-41d4-a716-446655440000": {"status": "pending", "detail": "Pending", "filled_amount": 0, "fill_signatures": []},
+///         "6fa459ea-ee8a-3ca4-894e-db77e160355e": {"status": "filled", "detail": "Filled", "filled_amount": 100000000, "fill_signatures": ["5VER..."]},
+///         "0b0c2f9e-3c1f-4f7a-9d43-1b1f0f6c2a11": {"status": "not_found", "detail": null, "filled_amount": 0, "fill_signatures": []}
+///     },
+///     "error": null
+/// }
+/// ```
+#[post("/order_statuses", data = "<order_ids>")]
+pub async fn order_statuses(
+    _auth: AuthContext,
+    order_ids: Json<Vec<Uuid>>,
+    order_book: &State<Mutex<OrderBook>>,
+) -> Json<ApiResponse<HashMap<Uuid, OrderStatusView>>> {
+    let order_ids = order_ids.into_inner();
+    if order_ids.len() > MAX_STATUS_BATCH {
+        return Json(
+            ApiError::new(
+                "TOO_MANY_ORDERS",
+                format!("单次最多查询 {} 笔订单", MAX_STATUS_BATCH),
+            )
+            .into(),
+        );
+    }
+    // 先取出内存中订单的状态并释放锁，查询数据库期间不持有订单簿的锁
+    let (orders, db) = {
+        let order_book = order_book.lock().await;
+        (order_book.orders.clone(), order_book.db.clone())
+    };
+    let mut statuses: HashMap<Uuid, OrderStatusView> = {
+        let orders = orders.lock().await;
+        order_ids
+            .iter()
+            .filter_map(|id| orders.get(id).map(|order| (*id, order.into())))
+            .collect()
+    };
+    let mut missing: Vec<Uuid> = order_ids
+        .iter()
+        .filter(|id| !statuses.contains_key(id))
+        .copied()
+        .collect();
+    missing.sort();
+    missing.dedup();
+    // 未配置数据库时终态订单仍在订单簿中，查不到即不存在
+    if let Some(pool) = db.filter(|_| !missing.is_empty()) {
+        match db::orders_by_id(&pool, missing).await {
+            Ok(orders) => {
+                for order in &orders {
+                    statuses.insert(order.order_id, order.into());
+                }
+            }
+            Err(e) => {
+                return Json(
+                    ApiError::new(
+                        e.code().unwrap_or("DATABASE_UNAVAILABLE"),
+                        format!("查询订单状态失败 {:#}", e),
+                    )
+                    .into(),
+                )
+            }
+        }
+    }
+    for id in order_ids {
+        statuses
+            .entry(id)
+            .or_insert_with(OrderStatusView::not_found);
+    }
+    Json(ApiResponse {
+        success: true,
+        data: Some(statuses),
+        error: None,
+        error_code: None,
+        retry_after_ms: None,
+    })
+}
+
+/// 单笔订单状态查询的 API 端点。
+///
+/// 未结束的订单从内存中的订单簿读取，已结束并写入数据库的订单从数据库读取。
+/// 成交后 `last_fill.realized_price` 为实际成交价格，`fill.signature` 为上链的交易签名（托管订单）；
+/// 非托管订单的签名见 `fill_signatures`。字段与 `/order_statuses` 相同。
+/// 订单不存在时错误码为 `ORDER_NOT_FOUND`；需要查询数据库但数据库不可用时错误码为 `DATABASE_UNAVAILABLE`。
+///
+/// # 示例
+/// ```bash
+/// curl http://localhost:8000/order_status/550e8400-e29b-41d4-a716-446655440000
+/// ```
+/// 响应：
+/// ```json
+/// {
+///     "success": true,
+///     "data": {
+///         "status": "filled",
+///         "detail": "Filled",
+///         "filled_amount": 100000000,
+///         "fill_signatures": ["5VER..."],
+///         "deviation_rejections": 0,
+///         "last_rejection": null,
+///         "last_fill": {"in_amount": 99000000, "out_amount": 14850000, "realized_price": 150.0, "slippage_from_trigger_bps": 0, "estimated": false},
+///         "fill": {"signature": "5VER...", "bundle_id": null, "quoted_in_amount": 99000000, "quoted_out_amount": 14860000, "min_proceeds": 14785000, "tax_amount": 1000000, "tax_mint": "So11111111111111111111111111111111111111112"}
+///     },
+///     "error": null
+/// }
+/// ```
+#[get("/order_status/<order_id>")]
+pub async fn order_status(
+    order_id: Uuid,
+    _auth: AuthContext,
+    order_book: &State<Mutex<OrderBook>>,
+) -> Json<ApiResponse<OrderStatusView>> {
+    // 查询数据库期间不持有订单簿的锁
+    let (orders, db) = {
+        let order_book = order_book.lock().await;
+        (order_book.orders.clone(), order_book.db.clone())
+    };
+    let live = orders
+        .lock()
+        .await
+        .get(&order_id)
+        .map(OrderStatusView::from);
+    let found = match (live, db) {
+        (Some(status), _) => Some(status),
+        (None, Some(pool)) => match db::orders_by_id(&pool, vec![order_id]).await {
+            Ok(orders) => orders.first().map(OrderStatusView::from),
+            Err(e) => {
+                return Json(
+                    ApiError::new(
+                        e.code().unwrap_or("DATABASE_UNAVAILABLE"),
+                        format!("查询订单状态失败 {:#}", e),
+                    )
+                    .into(),
+                )
+            }
+        },
+        // 未配置数据库时终态订单仍在订单簿中
+        (None, None) => None,
+    };
+    match found {
+        Some(status) => Json(ApiResponse {
+            success: true,
+            data: Some(status),
+            error: None,
+            error_code: None,
+            retry_after_ms: None,
+        }),
+        None => Json(ApiError::new("ORDER_NOT_FOUND", format!("订单 {} 未找到", order_id)).into()),
+    }
+}
+
+/// 订单审计日志查询的 API 端点。
+///
+/// 按时间顺序返回订单的全部事件，`seq` 在同一订单内递增：状态变化（`kind` 与 `/events` 推送的 `type` 相同）、
+/// 触发时的报价（`quote`）、每次发送交易（`submission`，包含签名和执行意图）、交易确认（`submitted`，包含 bundle id）
+/// 以及执行中的错误（`error`，`stage` 为 build、intent 或 submit）。
+/// 超过 `AUDIT_RETENTION_DAYS` 的事件已被删除；未配置 `DATABASE_URL` 时事件只保存在内存中，重启后丢失。
+/// 订单没有任何事件时错误码为 `ORDER_NOT_FOUND`。
+///
+/// # 示例
+/// ```bash
+/// curl http://localhost:8000/order/550e8400-e29b-41d4-a716-446655440000/events
+/// ```
+/// 响应：
+/// ```json
+/// {
+///     "success": true,
+///     "data": [
+///         {"order_id": "550e8400-e29b-41d4-a716-446655440000", "seq": 1, "created_at": 1760600000000, "kind": "placed", "payload": {}},
+///         {"order_id": "550e8400-e29b-41d4-a716-446655440000", "seq": 2, "created_at": 1760600419000, "kind": "quote",
+///          "payload": {"in_amount": 99000000, "out_amount": 14850000, "tax_bps": 100, "...": "..."}},
+///         {"order_id": "550e8400-e29b-41d4-a716-446655440000", "seq": 3, "created_at": 1760600419100, "kind": "triggered", "payload": {"signature": "5VER..."}},
+///         {"order_id": "550e8400-e29b-41d4-a716-446655440000", "seq": 4, "created_at": 1760600419200, "kind": "submission",
+///          "payload": {"signature": "5VER...", "attempt_id": "0b0c...", "bundle": false, "resigned": false, "...": "..."}},
+///         {"order_id": "550e8400-e29b-41d4-a716-446655440000", "seq": 5, "created_at": 1760600420000, "kind": "submitted", "payload": {"signature": "5VER...", "bundle_id": null}},
+///         {"order_id": "550e8400-e29b-41d4-a716-446655440000", "seq": 6, "created_at": 1760600420100, "kind": "filled", "payload": {"signature": "5VER...", "...": "..."}}
+///     ],
+///     "error": null
+/// }
+/// ```
+#[get("/order/<order_id>/events")]
+pub async fn order_events(
+    order_id: Uuid,
+    _auth: AuthContext,
+    order_book: &State<Mutex<OrderBook>>,
+) -> Json<ApiResponse<Vec<AuditEvent>>> {
+    // 查询期间不持有订单簿的锁
+    let audit = order_book.lock().await.audit.clone();
+    match audit.events(order_id).await {
+        Ok(events) if events.is_empty() => {
+            Json(ApiError::new("ORDER_NOT_FOUND", format!("订单 {} 没有审计事件", order_id)).into())
+        }
+        Ok(events) => Json(ApiResponse {
+            success: true,
+            data: Some(events),
+            error: None,
+            error_code: None,
+            retry_after_ms: None,
+        }),
+        Err(e) => {
+            Json(ApiError::new("DATABASE_UNAVAILABLE", format!("查询审计日志失败 {:#}", e)).into())
+        }
+    }
+}
+
+/// 订单事件推送（Server-Sent Events）。
+///
+/// 推送 `user` 钱包订单的事件，每条事件为一个 JSON 对象，`type` 为 `placed`、`price_update`（每笔订单每秒最多一次）、
+/// `triggered`、`filled`、`partially_filled`、`failed`、`canceled`、`expired` 或 `warning`（例如私有发送失败后改用公开 RPC）。
+/// 客户端处理太慢时价格事件会被丢弃；状态事件丢失时推送 `{"type": "resync", "missed": N}`，客户端应重新查询订单状态。
+/// 服务停机或客户端断开时结束。`user` 不是有效的钱包地址时返回 400，`error_code` 为 `INVALID_USER`。
+///
+/// 与下单接口一样需要 API key，并按客户端 IP 限流，被限流时返回 429，`error_code` 为 `RATE_LIMITED`。
+///
+/// # 示例
+/// ```bash
+/// curl -N -H 'X-Api-Key: <key>' http://localhost:8000/events?user=9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM
+/// ```
+/// 推送：
+/// ```text
+/// data:{"order_id":"550e8400-e29b-41d4-a716-446655440000","owner":"9WzD...","type":"price_update","price":148.2}
+///
+/// data:{"order_id":"550e8400-e29b-41d4-a716-446655440000","owner":"9WzD...","type":"triggered","signature":"5VER..."}
+/// ```
+#[get("/events?<user>")]
+pub async fn events(
+    user: String,
+    _auth: AuthContext,
+    client_ip: Option<IpAddr>,
+    limiter: &State<Arc<RateLimiter>>,
+    order_book: &State<Mutex<OrderBook>>,
+    mut shutdown: Shutdown,
+) -> Result<EventStream![], (Status, Json<ApiResponse<()>>)> {
+    if let Err(e) = check_rate_limit(limiter, &[ip_key(client_ip)]) {
+        return Err((Status::TooManyRequests, Json(e)));
+    }
+    if user.parse::<Pubkey>().is_err() {
+        return Err((
+            Status::BadRequest,
+            Json(ApiError::new("INVALID_USER", "user 不是有效的钱包地址").into()),
+        ));
+    }
+    let mut subscription = order_book.lock().await.events.subscribe();
+    Ok(EventStream! {
+        loop {
+            let item = tokio::select! {
+                item = subscription.next() => item,
+                _ = &mut shutdown => break,
+            };
+            match item {
+                Some(EventItem::Event(event)) if event.owner == user => yield Event::json(&event),
+                Some(EventItem::Event(_)) => {}
+                Some(EventItem::Resync { missed }) => {
+                    yield Event::json(&serde_json::json!({ "type": "resync", "missed": missed }))
+                }
+                None => break,
+            }
+        }
+    })
+}
+
+/// Prometheus 指标，文本格式
+#[get("/metrics")]
+pub async fn metrics(
+    _admin: AdminContext,
+    registry: &State<&'static Metrics>,
+    order_book: &State<Mutex<OrderBook>>,
+) -> Result<RawText<String>, Status> {
+    order_book.lock().await.refresh_metrics().await;
+    registry.render().map(RawText).map_err(|e| {
+        println!("输出指标失败 {:?}", e);
+        Status::InternalServerError
+    })
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct PriceResponse {
+    pub mint: String,
+    /// USD 价格
+    pub price: f64,
+    /// 价格的置信区间，价格源未提供时为空
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f64>,
+    /// 价格源给出的置信等级，价格源未提供时为空
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confidence_level: Option<ConfidenceLevel>,
+    /// 最近一次成交的价格，价格源未提供时为空
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_swap_price: Option<f64>,
+    /// 距离价格时间的毫秒数
+    pub age_ms: u64,
+    /// 价格来源
+    pub source: &'static str,
+}
+
+/// 查询缓存价格的 API 端点。
+///
+/// 只读取服务内部的价格缓存，不会发起新的价格请求。缓存只包含有活跃订单的代币，
+/// 未缓存的代币返回 404，无效的 mint 地址返回 400。
+///
+/// # 示例
+/// ```bash
+/// curl http://localhost:8000/price/So11111111111111111111111111111111111111112
+/// ```
+/// 响应：
+/// ```json
+/// {
+///     "success": true,
+///     "data": {
+///         "mint": "So11111111111111111111111111111111111111112",
+///         "price": 148.52,
+///         "confidence": 0.04,
+///         "confidence_level": "high",
+///         "last_swap_price": 148.49,
+///         "age_ms": 312,
+///         "source": "jupiter"
+///     },
+///     "error": null
+/// }
+/// ```
+#[get("/price/<mint>")]
+pub async fn price(
+    mint: &str,
+    prices: &State<PriceCache>,
+) -> (Status, Json<ApiResponse<PriceResponse>>) {
+    if mint.parse::<Pubkey>().is_err() {
+        return (
+            Status::BadRequest,
+            Json(ApiResponse {
+                success: false,
+                data: None,
+                error: Some("mint 地址无效".to_string()),
+                error_code: Some("INVALID_MINT".to_string()),
+                retry_after_ms: None,
+            }),
+        );
+    }
+    match prices.get(mint) {
+        Some(point) => (
+            Status::Ok,
+            Json(ApiResponse {
+                success: true,
+                data: Some(PriceResponse {
+                    mint: mint.to_string(),
+                    price: point.price,
+                    confidence: point.confidence,
+                    confidence_level: point.confidence_level,
+                    last_swap_price: point.last_swap_price,
+                    age_ms: point.age_ms(),
+                    source: point.source,
+                }),
+                error: None,
+                error_code: None,
+                retry_after_ms: None,
+            }),
+        ),
+        None => (
+            Status::NotFound,
+            Json(ApiResponse {
+                success: false,
+                data: None,
+                error: Some(format!("代币 {} 暂无缓存价格", mint)),
+                error_code: None,
+                retry_after_ms: None,
+            }),
+        ),
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct QuoteRequest {
+    pub input_mint: String,
+    pub output_mint: String,
+    /// 数量，含义与 `place_order` 相同
+    pub amount: u64,
+    pub slippage_bps: Bps,
+    #[serde(default)]
+    pub swap_mode: SwapMode,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct QuoteResponse {
+    /// 不透明的路由令牌，下单时通过 `route_token` 传入
+    pub route_token: String,
+    /// 报价的输入数量（已扣除税收）
+    pub in_amount: u64,
+    /// 报价的输出数量
+    pub out_amount: u64,
+    pub slippage_bps: u16,
+    /// 价格影响，单位为基点
+    pub price_impact_bps: u64,
+    /// 路由令牌过期时间（unix 毫秒）
+    pub expires_at_ms: u64,
+}
+
+/// 报价并返回路由令牌的 API 端点。
+///
+/// 报价方式与订单执行时相同。下单时携带返回的 `route_token`，订单触发时若令牌未过期、
+/// 数量与滑点仍然匹配，则直接使用该报价的路由构造交易；否则按 `pin_fallback` 重新报价或放弃执行。
+///
+/// # 示例
+/// ```bash
+/// curl -X POST http://localhost:8000/quote \
+///   -H 'Content-Type: application/json' \
+///   -d '{"input_mint": "So11111111111111111111111111111111111111112", "output_mint": "JUPyiwrYJFskUPiHa7hkeR8VUtAeFoSYbKedZNsDvCN", "amount": 1000000000, "slippage_bps": 50}'
+/// ```
+#[post("/quote", data = "<request>")]
+pub async fn quote(
+    request: Json<QuoteRequest>,
+    client_ip: Option<IpAddr>,
+    limiter: &State<Arc<RateLimiter>>,
+    order_book: &State<Mutex<OrderBook>>,
+) -> Json<ApiResponse<QuoteResponse>> {
+    if let Err(e) = check_rate_limit(limiter, &[ip_key(client_ip)]) {
+        return Json(e);
+    }
+    let (input_mint, output_mint) = match (
+        request.input_mint.parse::<Pubkey>(),
+        request.output_mint.parse::<Pubkey>(),
+    ) {
+        (Ok(input_mint), Ok(output_mint)) => (input_mint, output_mint),
+        _ => {
+            return Json(ApiResponse {
+                success: false,
+                data: None,
+                error: Some("代币地址无效".to_string()),
+                error_code: None,
+                retry_after_ms: None,
+            })
+        }
+    };
+    let route = OrderBook::quote(
+        order_book,
+        input_mint,
+        output_mint,
+        request.amount,
+        request.slippage_bps,
+        request.swap_mode,
+    )
+    .await
+    .and_then(|route| Ok((route.to_token()?, route)));
+    match route {
+        Ok((route_token, route)) => Json(ApiResponse {
+            success: true,
+            data: Some(QuoteResponse {
+                route_token,
+                in_amount: route.quote.in_amount,
+                out_amount: route.quote.out_amount,
+                slippage_bps: route.quote.slippage_bps,
+                price_impact_bps: QuoteSummary::from_quote(&route.quote).price_impact_bps(),
+                expires_at_ms: route.expires_at_ms,
+            }),
+            error: None,
+            error_code: None,
+            retry_after_ms: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("报价失败 {:?}", e)),
+            error_code: None,
+            retry_after_ms: None,
+        }),
+    }
+}
+
+/// 预览下单结果的 API 端点。
+///
+/// 请求体与 `/place_order` 相同，`encrypt_pk` 和 `session_token` 不需要提供，提供时也不会使用。
+/// 按下单时相同的参数校验和报价逻辑计算当前触发时的预期输出、税收和价格影响，并返回 `trigger_on` 所指的当前价格；
+/// 不解密私钥，不创建订单。参数无效时的错误码与 `/place_order` 相同，报价失败时为 `QUOTE_FAILED`。
+/// 输出代币有 Token-2022 转账手续费时在 `output_transfer_fee_bps` 中返回费率，`out_amount` 已扣除手续费。
+///
+/// # 示例
+/// ```bash
+/// curl -X POST http://localhost:8000/quote_order \
+///   -H 'Content-Type: application/json' \
+///   -d '{"input_mint": "So11111111111111111111111111111111111111112", "output_mint": "JUPyiwrYJFskUPiHa7hkeR8VUtAeFoSYbKedZNsDvCN", "price": 150.0, "amount": 1000000000, "slippage_bps": 50}'
+/// ```
+/// 响应：
+/// ```json
+/// {
+///     "success": true,
+///     "data": {
+///         "in_amount": 990000000,
+///         "out_amount": 185230000,
+///         "tax_amount": 10000000,
+///         "tax_mint": "So11111111111111111111111111111111111111112",
+///         "tax_side": "Input",
+///         "output_transfer_fee_bps": null,
+///         "price_impact_bps": 3,
+///         "route_labels": ["Meteora DLMM"],
+///         "trigger_on": "InputUsd",
+///         "current_price": 148.72,
+///         "rejection": null
+///     },
+///     "error": null
+/// }
+/// ```
+#[post("/quote_order", data = "<request>")]
+pub async fn quote_order(
+    request: Json<PlaceOrderRequest>,
+    client_ip: Option<IpAddr>,
+    limiter: &State<Arc<RateLimiter>>,
+    order_book: &State<Mutex<OrderBook>>,
+) -> Json<ApiResponse<OrderQuote>> {
+    if let Err(e) = check_rate_limit(limiter, &[ip_key(client_ip)]) {
+        return Json(e);
+    }
+    if let Err(e) = request.validate() {
+        return Json(e.into());
+    }
+    let leg = request.to_leg(order_book.lock().await.default_slippage_bps);
+    match OrderBook::quote_order(order_book, leg).await {
+        Ok(preview) => Json(ApiResponse {
+            success: true,
+            data: Some(preview),
+            error: None,
+            error_code: None,
+            retry_after_ms: None,
+        }),
+        Err(e) => Json(
+            ApiError::new(
+                e.code().unwrap_or("QUOTE_FAILED"),
+                format!("预览订单失败 {:#}", e),
+            )
+            .into(),
+        ),
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct SimulateOrderRequest {
+    /// 已有订单的 id，与 `user`、`order` 二选一
+    pub order_id: Option<Uuid>,
+    /// 按新订单模拟时的下单钱包，作为交易的付款人
+    pub user: Option<String>,
+    /// 按新订单模拟时的下单参数，与 `/place_order` 相同，不需要私钥
+    pub order: Option<PlaceOrderRequest>,
+}
+
+/// 模拟订单成交的 API 端点。
+///
+/// 按订单此刻触发时会发送的交易（含税收、tip 指令和地址查找表）调用 `simulateTransaction`，返回完整的程序日志、
+/// 消耗的计算单元、交易错误，以及用户输入、输出代币账户和收税账户在模拟前后的余额，用于排查订单在触发时失败的原因。
+/// 不发送任何交易，也不改变订单。交易未签名，模拟时不校验签名；未指定计算单元上限时按最大上限模拟。
+/// 传入 `order_id` 时模拟已有的订单（分批订单按一批的数量），否则按 `user` 和 `order` 中的下单参数模拟。
+/// 模拟失败时 `success` 仍为 true，失败原因在 `err` 和 `logs` 中；报价或价格影响等检查失败时返回错误。
+///
+/// # 示例
+/// ```bash
+/// curl -X POST http://localhost:8000/simulate_order \
+///   -H 'Content-Type: application/json' \
+///   -d '{"order_id": "550e8400-e29b-41d4-a716-446655440000"}'
+/// ```
+/// 响应：
+/// ```json
+/// {
+///     "success": true,
+///     "data": {
+///         "err": "Error processing Instruction 3: custom program error: 0x1771",
+///         "logs": ["Program ComputeBudget111111111111111111111111111111 invoke [1]", "...", "Program log: Error Code: SlippageToleranceExceeded"],
+///         "units_consumed": 182345,
+///         "compute_unit_limit": 1400000,
+///         "compute_unit_price": 10000,
+///         "tip_amount": null,
+///         "separate_tip": false,
+///         "private": false,
+///         "in_amount": 99000000,
+///         "out_amount": 14850000,
+///         "min_proceeds": 14775750,
+///         "tax_amount": 1000000,
+///         "tax_mint": "So11111111111111111111111111111111111111112",
+///         "route_labels": ["Meteora DLMM"],
+///         "balances": [{
+///             "owner": "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM",
+///             "mint": "So11111111111111111111111111111111111111112",
+///             "account": "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM",
+///             "pre": 520000000,
+///             "post": 520000000
+///         }],
+///         "transaction": "AQAAAA..."
+///     },
+///     "error": null
+/// }
+/// ```
+#[post("/simulate_order", data = "<request>")]
+pub async fn simulate_order(
+    _auth: AuthContext,
+    request: Json<SimulateOrderRequest>,
+    client_ip: Option<IpAddr>,
+    limiter: &State<Arc<RateLimiter>>,
+    order_book: &State<Mutex<OrderBook>>,
+) -> Json<ApiResponse<SwapSimulation>> {
+    if let Err(e) = check_rate_limit(limiter, &[ip_key(client_ip)]) {
+        return Json(e);
+    }
+    let target = match (request.order_id, &request.user, &request.order) {
+        (Some(order_id), None, None) => SimulationTarget::Existing(order_id),
+        (None, Some(user), Some(order)) => {
+            let Ok(user) = user.parse::<Pubkey>() else {
+                return Json(ApiError::new("INVALID_REQUEST", "user 地址无效").into());
+            };
+            if let Err(e) = order.validate() {
+                return Json(e.into());
+            }
+            SimulationTarget::New {
+                user,
+                leg: Box::new(order.to_leg(order_book.lock().await.default_slippage_bps)),
+            }
+        }
+        _ => {
+            return Json(
+                ApiError::new(
+                    "INVALID_REQUEST",
+                    "需要提供 order_id，或者同时提供 user 和 order",
+                )
+                .into(),
+            )
+        }
+    };
+    match OrderBook::simulate_order(order_book, target).await {
+        Ok(simulation) => Json(ApiResponse {
+            success: true,
+            data: Some(simulation),
+            error: None,
+            error_code: None,
+            retry_after_ms: None,
+        }),
+        Err(e) => Json(
+            ApiError::new(
+                e.code().unwrap_or("SIMULATION_FAILED"),
+                format!("模拟订单失败 {:#}", e),
+            )
+            .into(),
+        ),
+    }
+}
+
+/// 估算一次交换费用的 API 端点。
+///
+/// 按 ExactIn 估算签名费、当前推荐的优先费（`getRecentPrioritizationFees`）、可选的 Jito tip 和税收，
+/// 返回明细与以 lamports 计的合计；税收不是 SOL 时不计入合计。`amount` 为输入代币的最小单位数量，
+/// `tip_amount` 单位为 lamports。优先费按估算的计算单元计算，实际交易以模拟结果为准。
+///
+/// # 示例
+/// ```bash
+/// curl 'http://localhost:8000/fees?input_mint=So11111111111111111111111111111111111111112&output_mint=JUPyiwrYJFskUPiHa7hkeR8VUtAeFoSYbKedZNsDvCN&amount=1000000000&tip_amount=100000'
+/// ```
+/// 响应：
+/// ```json
+/// {
+///     "success": true,
+///     "data": {
+///         "signatures": 1,
+///         "base_fee": 5000,
+///         "compute_unit_price": 12000,
+///         "compute_units": 300000,
+///         "priority_fee": 3600,
+///         "tip": 100000,
+///         "tax_amount": 10000000,
+///         "tax_mint": "So11111111111111111111111111111111111111112",
+///         "tax_side": "Input",
+///         "total_lamports": 10108600
+///     },
+///     "error": null
+/// }
+/// ```
+#[get("/fees?<input_mint>&<output_mint>&<amount>&<tip_amount>")]
+pub async fn fees(
+    input_mint: &str,
+    output_mint: &str,
+    amount: u64,
+    tip_amount: Option<u64>,
+    client_ip: Option<IpAddr>,
+    limiter: &State<Arc<RateLimiter>>,
+    order_book: &State<Mutex<OrderBook>>,
+) -> Json<ApiResponse<FeeEstimate>> {
+    if let Err(e) = check_rate_limit(limiter, &[ip_key(client_ip)]) {
+        return Json(e);
+    }
+    let Ok(input_mint) = input_mint.parse::<Pubkey>() else {
+        return Json(ApiError::new("INVALID_REQUEST", "input_mint 地址无效").into());
+    };
+    let Ok(output_mint) = output_mint.parse::<Pubkey>() else {
+        return Json(ApiError::new("INVALID_REQUEST", "output_mint 地址无效").into());
+    };
+    if amount == 0 {
+        return Json(ApiError::new("INVALID_REQUEST", "amount 必须大于 0").into());
+    }
+    match OrderBook::estimate_fees(
+        order_book,
+        input_mint,
+        output_mint,
+        amount,
+        tip_amount.map(Lamports),
+    )
+    .await
+    {
+        Ok(estimate) => Json(ApiResponse {
+            success: true,
+            data: Some(estimate),
+            error: None,
+            error_code: None,
+            retry_after_ms: None,
+        }),
+        Err(e) => Json(
+            ApiError::new(
+                e.code().unwrap_or("FEE_ESTIMATE_FAILED"),
+                format!("估算费用失败 {:#}", e),
+            )
+            .into(),
+        ),
+    }
+}
+
+/// 预览全局配置变更的 API 端点。
+///
+/// 对每个等待触发的订单，分别按当前配置和候选配置解析实际执行参数（税率、tip、滑点、重试节奏），
+/// 并标出候选配置下会被拒绝的订单。只读，不会应用候选配置。
+///
+/// # 示例
+/// ```bash
+/// curl -X POST http://localhost:8000/admin/config/preview \
+///   -H 'Content-Type: application/json' \
+///   -d '{"tax_bps": 150, "retry_policy": {"max_retries": 3, "base_delay_ms": 500, "max_delay_ms": 8000, "pacing": {"type": "FixedDelay"}}}'
+/// ```
+#[post("/admin/config/preview", data = "<candidate>")]
+pub async fn preview_config(
+    _admin: AdminContext,
+    candidate: Json<RuntimeConfig>,
+    order_book: &State<Mutex<OrderBook>>,
+) -> Json<ApiResponse<Vec<ConfigPreview>>> {
+    let order_book = order_book.lock().await;
+    Json(ApiResponse {
+        success: true,
+        data: Some(order_book.preview_config(&candidate).await),
+        error: None,
+        error_code: None,
+        retry_after_ms: None,
+    })
+}
+
+/// 替换税收策略的 API 端点，需要 admin key。
+///
+/// 依次按免税钱包、单个钱包的税率和名义价值分档（lamports，升序）解析税率，都不适用时使用 `default_bps`。
+/// 托管订单在触发时才解析税率，因此只影响之后触发的交易，已发送或已生成的待签名交易不变。
+/// 重启后恢复为环境变量中的配置。返回替换后的策略。
+///
+/// # 示例
+/// ```bash
+/// curl -X POST http://localhost:8000/admin/tax_policy \
+///   -H 'X-Api-Key: <admin key>' \
+///   -H 'Content-Type: application/json' \
+///   -d '{"default_bps": 100, "exempt": ["9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM"], "overrides": {}, "tiers": [{"min_notional_lamports": 100000000000, "bps": 80}]}'
+/// ```
+///
+/// # 响应示例
+/// ```json
+/// {
+///   "success": true,
+///   "data": {
+///     "default_bps": 100,
+///     "exempt": ["9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM"],
+///     "overrides": {},
+///     "tiers": [{"min_notional_lamports": 100000000000, "bps": 80}]
+///   },
+///   "error": null
+/// }
+/// ```
+#[post("/admin/tax_policy", data = "<policy>")]
+pub async fn set_tax_policy(
+    _admin: AdminContext,
+    policy: Json<TaxPolicy>,
+    order_book: &State<Mutex<OrderBook>>,
+) -> Json<ApiResponse<TaxPolicy>> {
+    let order_book = order_book.lock().await;
+    match order_book.set_tax_policy(policy.into_inner()) {
+        Ok(()) => Json(ApiResponse {
+            success: true,
+            data: Some(order_book.tax_policy()),
+            error: None,
+            error_code: None,
+            retry_after_ms: None,
+        }),
+        Err(e) => Json(ApiError::new("INVALID_TAX_POLICY", format!("税收策略无效 {:#}", e)).into()),
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct CreateSessionRequest {
+    /// 加密后的pk
+    pub encrypt_pk: String,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct SessionResponse {
+    /// 会话令牌，下单时作为 `session_token` 传入
+    pub session_token: String,
+    /// 会话绑定的钱包
+    pub wallet: String,
+    /// 有效期（秒）
+    pub expires_in_secs: u64,
+}
+
+/// 建立下单会话的 API 端点。
+///
+/// 提交一次加密私钥，返回绑定该钱包的会话令牌。有效期内下单只需携带令牌，不再传输私钥。
+/// 会话过期或删除后私钥从内存中清零，已创建的订单不受影响。令牌只能用于下单，不能撤销其他钱包的订单。
+///
+/// # 示例
+/// ```bash
+/// curl -X POST http://localhost:8000/session \
+///   -H 'Content-Type: application/json' \
+///   -d '{"encrypt_pk": "SGVsbG8gV29ybGQ="}'
+/// ```
+#[post("/session", data = "<request>")]
+pub async fn create_session(
+    _auth: AuthContext,
+    request: Json<CreateSessionRequest>,
+    order_book: &State<Mutex<OrderBook>>,
+) -> Json<ApiResponse<SessionResponse>> {
+    let prik = match decrypt(&request.encrypt_pk) {
+        Ok(prik) => prik,
+        Err(e) => {
+            return Json(ApiResponse {
+                success: false,
+                data: None,
+                error: Some(format!("私钥解析失败 {}", e)),
+                error_code: None,
+                retry_after_ms: None,
+            })
+        }
+    };
+    let mut order_book = order_book.lock().await;
+    match order_book.create_session(prik) {
+        Ok((session_token, wallet)) => Json(ApiResponse {
+            success: true,
+            data: Some(SessionResponse {
+                session_token,
+                wallet: wallet.to_string(),
+                expires_in_secs: order_book.sessions.ttl().as_secs(),
+            }),
+            error: None,
+            error_code: None,
+            retry_after_ms: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            error: Some(e.to_string()),
+            error_code: None,
+            retry_after_ms: None,
+        }),
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct DeleteSessionRequest {
+    pub session_token: String,
+}
+
+/// 删除下单会话的 API 端点，会话中缓存的私钥立即清零。
+///
+/// # 示例
+/// ```bash
+/// curl -X DELETE http://localhost:8000/session \
+///   -H 'Content-Type: application/json' \
+///   -d '{"session_token": "3xV9..."}'
+/// ```
+#[delete("/session", data = "<request>")]
+pub async fn delete_session(
+    _auth: AuthContext,
+    request: Json<DeleteSessionRequest>,
+    order_book: &State<Mutex<OrderBook>>,
+) -> Json<ApiResponse<String>> {
+    let mut order_book = order_book.lock().await;
+    if order_book.sessions.remove(&request.session_token) {
+        Json(ApiResponse {
+            success: true,
+            data: Some("会话已删除".to_string()),
+            error: None,
+            error_code: None,
+            retry_after_ms: None,
+        })
+    } else {
+        Json(ApiResponse {
+            success: false,
+            data: None,
+            error: Some("会话不存在或已过期".to_string()),
+            error_code: None,
+            retry_after_ms: None,
+        })
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct PrepareOrderRequest {
+    /// 下单用户的公钥，也是交易的手续费支付者
+    pub user: String,
+    /// 订单参数，与 `place_order` 相同
+    #[serde(flatten)]
+    pub order: OrderLeg,
+}
+
+/// 生成非托管订单待签名交易的 API 端点。
+///
+/// 不需要私钥：返回 base64 编码的未签名 `VersionedTransaction`，客户端签名后调用 `/submit_signed_order` 提交。
+/// 交易使用最新的 blockhash，超过 `last_valid_block_height` 后失效；订单触发前交易过期时，
+/// 订单状态变为 `ResignRequired`，需要重新生成并签名。
+///
+/// # 示例
+/// ```bash
+/// curl -X POST http://localhost:8000/prepare_order \
+///   -H 'Content-Type: application/json' \
+///   -d '{"user": "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM", "input_mint": "So11111111111111111111111111111111111111112", "output_mint": "JUPyiwrYJFskUPiHa7hkeR8VUtAeFoSYbKedZNsDvCN", "price": 150.0, "amount": 1000000000, "slippage_bps": 50, "tip_amount": null, "priority_fee_micro_lamports": 10000}'
+/// ```
+/// 响应：
+/// ```json
+/// {
+///     "success": true,
+///     "data": {
+///         "prepare_id": "0b7d6c1e-5d1a-4f7a-9a57-2f0a3c1d9e11",
+///         "transaction": "AQAAAA...",
+///         "last_valid_block_height": 291834512
+///     },
+///     "error": null
+/// }
+/// ```
+#[post("/prepare_order", data = "<request>")]
+pub async fn prepare_order(
+    _auth: AuthContext,
+    request: Json<PrepareOrderRequest>,
+    client_ip: Option<IpAddr>,
+    limiter: &State<Arc<RateLimiter>>,
+    order_book: &State<Mutex<OrderBook>>,
+) -> Json<ApiResponse<PreparedTransaction>> {
+    if let Err(e) = check_rate_limit(limiter, &[ip_key(client_ip)]) {
+        return Json(e);
+    }
+    let request = request.into_inner();
+    let user = match request.user.parse::<Pubkey>() {
+        Ok(user) => user,
+        Err(_) => {
+            return Json(ApiResponse {
+                success: false,
+                data: None,
+                error: Some("用户地址无效".to_string()),
+                error_code: None,
+                retry_after_ms: None,
+            })
+        }
+    };
+    if let Err(e) = check_rate_limit(limiter, &user_keys(limiter, &user)) {
+        return Json(e);
+    }
+    match OrderBook::prepare_order(order_book, user, request.order).await {
+        Ok(prepared) => Json(ApiResponse {
+            success: true,
+            data: Some(prepared),
+            error: None,
+            error_code: None,
+            retry_after_ms: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("生成交易失败 {:?}", e)),
+            error_code: None,
+            retry_after_ms: None,
+        }),
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct SubmitSignedOrderRequest {
+    /// `/prepare_order` 返回的 ID
+    pub prepare_id: Uuid,
+    /// base64 编码的已签名交易
+    pub signed_transaction: String,
+    /// 订单参数，必须与生成交易时一致
+    #[serde(flatten)]
+    pub order: OrderLeg,
+}
+
+/// 提交客户端签名交易的 API 端点。
+///
+/// 校验交易与生成时完全一致且签名有效后创建订单，价格触发时直接发送该交易。返回订单的 UUID。
+///
+/// # 示例
+/// ```bash
+/// curl -X POST http://localhost:8000/submit_signed_order \
+///   -H 'Content-Type: application/json' \
+///   -d '{"prepare_id": "0b7d6c1e-5d1a-4f7a-9a57-2f0a3c1d9e11", "signed_transaction": "AcP1...", "input_mint": "So11111111111111111111111111111111111111112", "output_mint": "JUPyiwrYJFskUPiHa7hkeR8VUtAeFoSYbKedZNsDvCN", "price": 150.0, "amount": 1000000000, "slippage_bps": 50, "tip_amount": null, "priority_fee_micro_lamports": 10000}'
+/// ```
+#[post("/submit_signed_order", data = "<request>")]
+pub async fn submit_signed_order(
+    _auth: AuthContext,
+    request: Json<SubmitSignedOrderRequest>,
+    order_book: &State<Mutex<OrderBook>>,
+) -> Json<ApiResponse<Uuid>> {
+    let request = request.into_inner();
+    let mut order_book = order_book.lock().await;
+    match order_book
+        .submit_signed_order(
+            request.prepare_id,
+            request.order,
+            &request.signed_transaction,
+        )
+        .await
+    {
+        Ok(id) => Json(ApiResponse {
+            success: true,
+            data: Some(id),
+            error: None,
+            error_code: None,
+            retry_after_ms: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("开单失败 {:?}", e)),
+            error_code: None,
+            retry_after_ms: None,
+        }),
+    }
+}
+
+/// 服务的全部接口，不含 [`openapi`] 中的文档页面；新增路由时需要同时在文档中登记
+pub fn api_routes() -> Vec<Route> {
+    routes![
+        place_order,
+        place_order_group,
+        place_orders,
+        place_bracket,
+        cancel_order,
+        admin_cancel_order,
+        cancel_all,
+        modify_order,
+        events,
+        ready,
+        health,
+        revoke_wallet,
+        revoked_wallets,
+        pause,
+        resume,
+        pause_order,
+        resume_order,
+        price,
+        quote,
+        quote_order,
+        simulate_order,
+        list_orders,
+        order_history,
+        order_status,
+        order_statuses,
+        order_events,
+        treasury,
+        sweep_treasury,
+        fees,
+        preview_config,
+        set_tax_policy,
+        create_session,
+        delete_session,
+        prepare_order,
+        submit_signed_order,
+        metrics
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::{
+        catchers,
+        http::ContentType,
+        local::asynchronous::{Client, LocalResponse},
+        routes,
+    };
+    use serde_json::Value;
+
+    use super::*;
+
+    #[post("/body", data = "<request>")]
+    fn body(request: Json<CancelOrderRequest>) -> String {
+        request.order_id.to_string()
+    }
+
+    #[get("/reject")]
+    fn reject() -> Status {
+        Status::BadRequest
+    }
+
+    async fn client() -> Client {
+        let rocket = rocket::build()
+            .mount("/", routes![body, reject])
+            .register("/", catchers![bad_request, unprocessable_entity]);
+        Client::tracked(rocket).await.unwrap()
+    }
+
+    async fn post_body<'c>(client: &'c Client, body: &str) -> LocalResponse<'c> {
+        client
+            .post("/body")
+            .header(ContentType::JSON)
+            .body(body)
+            .dispatch()
+            .await
+    }
+
+    async fn error_of(response: LocalResponse<'_>) -> (Status, Value) {
+        let status = response.status();
+        let body: Value = serde_json::from_str(&response.into_string().await.unwrap()).unwrap();
+        assert_eq!(body["success"], false);
+        (status, body)
+    }
+
+    #[rocket::async_test]
+    async fn malformed_body_is_invalid_body() {
+        let client = client().await;
+        let (status, body) = error_of(post_body(&client, r#"{"order_id": "#).await).await;
+        assert_eq!(status, Status::BadRequest);
+        assert_eq!(body["error_code"], "INVALID_BODY");
+    }
+
+    #[rocket::async_test]
+    async fn wrong_field_type_is_invalid_body() {
+        let client = client().await;
+        let (status, body) = error_of(post_body(&client, r#"{"order_id": 42}"#).await).await;
+        assert_eq!(status, Status::UnprocessableEntity);
+        assert_eq!(body["error_code"], "INVALID_BODY");
+    }
+
+    #[rocket::async_test]
+    async fn valid_body_reaches_handler() {
+        let client = client().await;
+        let order_id = Uuid::new_v4();
+        let response = post_body(&client, &format!(r#"{{"order_id": "{}"}}"#, order_id)).await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.into_string().await.unwrap(), order_id.to_string());
+    }
+
+    #[test]
+    fn limit_order_requires_trigger_condition() {
+        let mint = |byte| Pubkey::new_from_array([byte; 32]).to_string();
+        let validate = |kind, condition| {
+            validate_order_params(&mint(1), &mint(2), 10.0, 1_000, None, kind, condition)
+        };
+        let e = validate(OrderKind::Limit, None).unwrap_err();
+        assert_eq!(e.code, "TRIGGER_CONDITION_REQUIRED");
+        assert!(validate(OrderKind::Limit, Some(TriggerCondition::Above)).is_ok());
+        assert!(validate(OrderKind::Limit, Some(TriggerCondition::Below)).is_ok());
+        // 止盈止损的方向由类型决定
+        assert!(validate(OrderKind::TakeProfit, None).is_ok());
+        assert!(validate(OrderKind::StopLoss, None).is_ok());
+    }
+
+    /// 在有效请求的基础上修改一个字段
+    fn place_request(overrides: Value) -> PlaceOrderRequest {
+        let mut request = serde_json::json!({
+            "input_mint": Pubkey::new_from_array([1; 32]).to_string(),
+            "output_mint": Pubkey::new_from_array([2; 32]).to_string(),
+            "price": 10.0,
+            "amount": 1_000,
+            "trigger_condition": "Above",
+        });
+        for (field, value) in overrides.as_object().unwrap() {
+            request[field] = value.clone();
+        }
+        serde_json::from_value(request).unwrap()
+    }
+
+    fn rejection(overrides: Value) -> &'static str {
+        place_request(overrides).validate().unwrap_err().code
+    }
+
+    #[test]
+    fn valid_request_passes() {
+        assert!(place_request(serde_json::json!({})).validate().is_ok());
+    }
+
+    #[test]
+    fn invalid_mints_are_rejected() {
+        assert_eq!(
+            rejection(serde_json::json!({ "input_mint": "not-a-mint" })),
+            "INVALID_MINT"
+        );
+        assert_eq!(
+            rejection(serde_json::json!({ "output_mint": "" })),
+            "INVALID_MINT"
+        );
+        let mint = Pubkey::new_from_array([1; 32]).to_string();
+        assert_eq!(
+            rejection(serde_json::json!({ "output_mint": mint })),
+            "SAME_MINT"
+        );
+    }
+
+    #[test]
+    fn zero_amount_is_rejected() {
+        assert_eq!(rejection(serde_json::json!({ "amount": 0 })), "AMOUNT_ZERO");
+    }
+
+    #[test]
+    fn non_positive_price_is_rejected() {
+        assert_eq!(
+            rejection(serde_json::json!({ "price": 0.0 })),
+            "INVALID_PRICE"
+        );
+        assert_eq!(
+            rejection(serde_json::json!({ "price": -1.0 })),
+            "INVALID_PRICE"
+        );
+        // JSON 无法表示 NaN 和无穷大，直接调用校验函数
+        for price in [f32::NAN, f32::INFINITY] {
+            let e = validate_order_params(
+                &Pubkey::new_from_array([1; 32]).to_string(),
+                &Pubkey::new_from_array([2; 32]).to_string(),
+                price,
+                1_000,
+                None,
+                OrderKind::Limit,
+                Some(TriggerCondition::Above),
+            )
+            .unwrap_err();
+            assert_eq!(e.code, "INVALID_PRICE");
+        }
+        // 跟踪止损不使用触发价格
+        assert!(place_request(serde_json::json!({
+            "price": 0.0,
+            "kind": { "type": "TrailingStop", "trail_bps": 500 },
+            "trigger_condition": null,
+        }))
+        .validate()
+        .is_ok());
+    }
+
+    #[test]
+    fn tip_above_maximum_is_rejected() {
+        assert!(
+            place_request(serde_json::json!({ "tip_amount": MAX_TIP_LAMPORTS }))
+                .validate()
+                .is_ok()
+        );
+        assert_eq!(
+            rejection(serde_json::json!({ "tip_amount": MAX_TIP_LAMPORTS + 1 })),
+            "TIP_TOO_LARGE"
+        );
+    }
+
+    #[test]
+    fn to_leg_carries_ttl_and_default_slippage() {
+        let request: PlaceOrderRequest = serde_json::from_value(serde_json::json!({
+            "input_mint": Pubkey::new_unique().to_string(),
+            "output_mint": Pubkey::new_unique().to_string(),
+            "price": 1.5,
+            "amount": 1_000,
+            "trigger_condition": "Above",
+            "ttl_secs": 3_600,
+        }))
+        .unwrap();
+        let default_slippage = Bps::new(75).unwrap();
+        let leg = request.to_leg(default_slippage);
+        assert_eq!(leg.ttl_secs, Some(3_600));
+        assert_eq!(leg.slippage_bps, default_slippage);
+        assert_eq!(leg.trigger_condition, Some(TriggerCondition::Above));
+
+        let request = PlaceOrderRequest {
+            slippage_bps: Some(Bps::new(20).unwrap()),
+            ttl_secs: None,
+            ..request
+        };
+        let leg = request.to_leg(default_slippage);
+        assert_eq!(leg.ttl_secs, None);
+        assert_eq!(leg.slippage_bps, Bps::new(20).unwrap());
+    }
+
+    #[test]
+    fn to_leg_keeps_priority_fee_with_tip() {
+        let request: PlaceOrderRequest = serde_json::from_value(serde_json::json!({
+            "input_mint": Pubkey::new_unique().to_string(),
+            "output_mint": Pubkey::new_unique().to_string(),
+            "price": 1.5,
+            "amount": 1_000,
+            "trigger_condition": "Above",
+            "tip_amount": 10_000,
+            "priority_fee_micro_lamports": 5_000,
+        }))
+        .unwrap();
+        let leg = request.to_leg(Bps::new(50).unwrap());
+        // tip 和优先费同时保留，交换交易两者都会带上
+        assert_eq!(leg.tip_amount, Some(Lamports(10_000)));
+        assert_eq!(leg.priority_fee_micro_lamports, Some(5_000));
+    }
+
+    #[rocket::async_test]
+    async fn bad_request_without_body_is_generic() {
+        let client = client().await;
+        let (status, body) = error_of(client.get("/reject").dispatch().await).await;
+        assert_eq!(status, Status::BadRequest);
+        assert_eq!(body["error_code"], "BAD_REQUEST");
+        assert!(!body["error"].as_str().unwrap().contains("JSON"));
+    }
+
+    #[cfg(feature = "testing")]
+    #[rocket::async_test]
+    async fn price_is_served_from_managed_cache() {
+        use std::{collections::HashMap, time::Duration};
+
+        use crate::testing::MockPriceSource;
+
+        let mint = Pubkey::new_unique();
+        let source = MockPriceSource::new("mock", HashMap::from([(mint, 1.25)]));
+        let prices = PriceCache::spawn(Arc::new(source), Duration::from_millis(10));
+        let _subscription = prices.subscribe(&mint.to_string());
+        while prices.get(&mint.to_string()).is_none() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        // 只托管价格缓存，不需要订单簿
+        let rocket = rocket::build().manage(prices).mount("/", routes![price]);
+        let client = Client::tracked(rocket).await.unwrap();
+
+        let response = client.get(format!("/price/{}", mint)).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let body: Value = serde_json::from_str(&response.into_string().await.unwrap()).unwrap();
+        assert_eq!(body["data"]["price"], 1.25);
+        assert_eq!(body["data"]["source"], "mock");
+
+        let unknown = Pubkey::new_unique();
+        let (status, _) =
+            error_of(client.get(format!("/price/{}", unknown)).dispatch().await).await;
+        assert_eq!(status, Status::NotFound);
+        let (status, body) = error_of(client.get("/price/not-a-mint").dispatch().await).await;
+        assert_eq!(status, Status::BadRequest);
+        assert_eq!(body["error_code"], "INVALID_MINT");
+    }
+
+    /// 吊销持有三笔订单的钱包：订单全部取消，会话被清除，之后无法再下单
+    #[cfg(feature = "testing")]
+    #[rocket::async_test]
+    async fn revoke_wallet_cancels_orders_and_blocks_placement() {
+        use rocket::http::Header;
+
+        use crate::{
+            common::{
+                auth::ApiKeyConfig,
+                encode::SecretString,
+                types::{OrderBook, OrderStatus},
+            },
+            testing::{limit_leg, mock_config, wallet_secret, MockStack, USDC},
+            SOL,
+        };
+
+        let stack = MockStack::new(150.0);
+        let config = mock_config();
+        let mut order_book = stack.order_book(&config);
+        let (token, wallet) = order_book.create_session(wallet_secret(1)).unwrap();
+        let rocket = rocket::build()
+            .manage(Mutex::new(order_book))
+            .manage(Arc::new(RateLimiter::new(config.rate_limit)))
+            .manage(ApiKeys::new(vec![ApiKeyConfig {
+                name: "admin".to_string(),
+                key: SecretString::new("admin-key".to_string()),
+                is_admin: true,
+                per_second: None,
+            }]))
+            .mount("/", routes![place_order, revoke_wallet]);
+        let client = Client::tracked(rocket).await.unwrap();
+        let place = |price: f32| {
+            client
+                .post("/place_order")
+                .header(ContentType::JSON)
+                .header(Header::new("X-Api-Key", "admin-key"))
+                .body(
+                    serde_json::json!({
+                        "input_mint": SOL.to_string(),
+                        "output_mint": USDC.to_string(),
+                        "price": price,
+                        "amount": 1_000_000_000,
+                        "trigger_condition": "Above",
+                        "session_token": token,
+                    })
+                    .to_string(),
+                )
+                .dispatch()
+        };
+        async fn body_of(response: LocalResponse<'_>) -> Value {
+            serde_json::from_str(&response.into_string().await.unwrap()).unwrap()
+        }
+
+        let mut placed = vec![];
+        for price in [200.0, 210.0, 220.0] {
+            let body = body_of(place(price).await).await;
+            assert_eq!(body["success"], true, "{}", body);
+            placed.push(body["data"].as_str().unwrap().parse::<Uuid>().unwrap());
+        }
+
+        let response = client
+            .post("/admin/revoke_wallet")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Api-Key", "admin-key"))
+            .body(serde_json::json!({ "wallet": wallet.to_string() }).to_string())
+            .dispatch()
+            .await;
+        let body = body_of(response).await;
+        assert_eq!(body["success"], true, "{}", body);
+        let mut canceled: Vec<Uuid> = serde_json::from_value(body["data"].clone()).unwrap();
+        canceled.sort();
+        placed.sort();
+        assert_eq!(canceled, placed);
+
+        let order_book = client.rocket().state::<Mutex<OrderBook>>().unwrap();
+        {
+            let order_book = order_book.lock().await;
+            let orders = order_book.orders.lock().await;
+            for order_id in &placed {
+                assert_eq!(orders[order_id].status, OrderStatus::Canceled);
+            }
+            // 会话中缓存的私钥已清除
+            assert!(order_book.sessions.private_key(&token).is_err());
+        }
+
+        // 原来的会话令牌不能再下单，也不能为该钱包建立新会话或直接以私钥下单
+        let body = body_of(place(230.0).await).await;
+        assert_eq!(body["success"], false);
+        assert_eq!(body["error_code"], "INVALID_KEY");
+        assert!(order_book
+            .lock()
+            .await
+            .create_session(wallet_secret(1))
+            .is_err());
+        let err = OrderBook::place_order(
+            order_book,
+            wallet_secret(1),
+            limit_leg(200.0, 1_000_000_000),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.code(), Some("UNAUTHORIZED"));
     }
 }
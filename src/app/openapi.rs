@@ -0,0 +1,662 @@
+//! 接口的 OpenAPI 3 描述
+//!
+//! 请求和返回类型的结构由 schemars 从类型定义生成，接口列表在 [`endpoints`] 中登记，新增路由时需要同时登记。
+//! 启动时 [`unlisted_routes`] 会检查已挂载但未登记的路由。
+
+use std::{collections::HashMap, sync::OnceLock};
+
+use rocket::{get, response::content::RawHtml, serde::json::Json, Route};
+use schemars::{
+    gen::{SchemaGenerator, SchemaSettings},
+    schema::Schema,
+    JsonSchema,
+};
+use serde_json::{json, Map, Value};
+use uuid::Uuid;
+
+use super::{
+    ApiResponse, CancelAllRequest, CancelOrderRequest, CreateSessionRequest, DeleteSessionRequest,
+    HealthReport, ModifyOrderRequest, PlaceBracketRequest, PlaceOrderGroupRequest,
+    PlaceOrderRequest, PlaceOrdersRequest, PrepareOrderRequest, PriceResponse, QuoteRequest,
+    QuoteResponse, ReadyStatus, RevokeWalletRequest, SessionResponse, SimulateOrderRequest,
+    SubmitSignedOrderRequest,
+};
+use crate::{
+    common::{
+        audit::AuditEvent,
+        db::OrderHistoryPage,
+        prepared::PreparedTransaction,
+        tax_policy::TaxPolicy,
+        treasury::{SweepResult, TreasuryBalances},
+        types::{
+            Bracket, CancelAllResult, ConfigPreview, FeeEstimate, Order, OrderGroup, OrderQuote,
+            OrderStatusView, PauseState, RevokedWallet, RuntimeConfig,
+        },
+    },
+    solana::swap::SwapSimulation,
+};
+
+/// `ApiResponse.error_code` 的全部取值
+///
+/// 前半部分来自 [`crate::error::LimitOrderError::code`]，后半部分是接口层的参数校验和限流错误。
+pub const ERROR_CODES: &[&str] = &[
+    "QUOTE_FAILED",
+    "PRICE_FEED_UNAVAILABLE",
+    "SIMULATION_FAILED",
+    "BUNDLE_DROPPED",
+    "PRIVATE_EXECUTION_FAILED",
+    "ORDER_NOT_FOUND",
+    "ORDER_ALREADY_TRIGGERED",
+    "UNAUTHORIZED",
+    "INVALID_REQUEST",
+    "DECRYPT_FAILED",
+    "DATABASE_UNAVAILABLE",
+    "MIN_OUT_NOT_MET",
+    "PRICE_IMPACT_TOO_HIGH",
+    "TRIGGER_DEVIATION_TOO_HIGH",
+    "INSUFFICIENT_FUNDS",
+    "AMOUNT_ZERO",
+    "SAME_MINT",
+    "INVALID_KEY",
+    "INVALID_BODY",
+    "BAD_REQUEST",
+    "INVALID_USER",
+    "INVALID_MINT",
+    "INVALID_PRICE",
+    "INVALID_TAX_POLICY",
+    "TIP_TOO_LARGE",
+    "TOO_MANY_ORDERS",
+    "PLACE_FAILED",
+    "MODIFY_FAILED",
+    "PAUSE_FAILED",
+    "FEE_ESTIMATE_FAILED",
+    "RPC_FAILED",
+    "SWEEP_FAILED",
+    "RATE_LIMITED",
+];
+
+/// 接口需要的认证
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Access {
+    /// 不需要 API key
+    Public,
+    /// 任意有效的 API key，未配置 `API_KEYS` 时不需要
+    User,
+    /// 管理员 API key
+    Admin,
+}
+
+/// 路径或查询参数
+struct Param {
+    name: &'static str,
+    location: &'static str,
+    required: bool,
+    schema: Value,
+}
+
+fn path_param(name: &'static str, schema: Value) -> Param {
+    Param {
+        name,
+        location: "path",
+        required: true,
+        schema,
+    }
+}
+
+fn query_param(name: &'static str, required: bool, schema: Value) -> Param {
+    Param {
+        name,
+        location: "query",
+        required,
+        schema,
+    }
+}
+
+type SchemaFn = fn(&mut SchemaGenerator) -> Schema;
+
+/// 返回内容
+enum Body {
+    Json(SchemaFn),
+    /// 非 JSON 的返回，只给出内容类型
+    Raw(&'static str),
+}
+
+/// 一个接口的描述
+struct Endpoint {
+    method: &'static str,
+    /// OpenAPI 格式的路径，参数写作 `{name}`
+    path: &'static str,
+    summary: &'static str,
+    access: Access,
+    params: Vec<Param>,
+    request: Option<SchemaFn>,
+    response: Body,
+}
+
+fn schema<T: JsonSchema>(gen: &mut SchemaGenerator) -> Schema {
+    gen.subschema_for::<T>()
+}
+
+fn endpoint(
+    method: &'static str,
+    path: &'static str,
+    summary: &'static str,
+    access: Access,
+) -> Endpoint {
+    Endpoint {
+        method,
+        path,
+        summary,
+        access,
+        params: Vec::new(),
+        request: None,
+        response: Body::Json(schema::<ApiResponse<String>>),
+    }
+}
+
+impl Endpoint {
+    fn params(mut self, params: Vec<Param>) -> Endpoint {
+        self.params = params;
+        self
+    }
+
+    fn request(mut self, request: SchemaFn) -> Endpoint {
+        self.request = Some(request);
+        self
+    }
+
+    fn response(mut self, response: SchemaFn) -> Endpoint {
+        self.response = Body::Json(response);
+        self
+    }
+
+    fn raw(mut self, content_type: &'static str) -> Endpoint {
+        self.response = Body::Raw(content_type);
+        self
+    }
+}
+
+/// 全部接口，与 [`super::api_routes`] 和文档页面的路由一一对应
+fn endpoints() -> Vec<Endpoint> {
+    use Access::*;
+    let string = || json!({ "type": "string" });
+    let uuid = || json!({ "type": "string", "format": "uuid" });
+    let integer = || json!({ "type": "integer", "minimum": 0 });
+    vec![
+        endpoint("post", "/place_order", "托管下单", User)
+            .request(schema::<PlaceOrderRequest>)
+            .response(schema::<ApiResponse<Uuid>>),
+        endpoint(
+            "post",
+            "/place_order_group",
+            "按客户端分组 ID 幂等地批量下单",
+            User,
+        )
+        .request(schema::<PlaceOrderGroupRequest>)
+        .response(schema::<ApiResponse<OrderGroup>>),
+        endpoint(
+            "post",
+            "/place_orders",
+            "批量下单，全部校验通过才会下单",
+            User,
+        )
+        .request(schema::<PlaceOrdersRequest>)
+        .response(schema::<ApiResponse<Vec<Uuid>>>),
+        endpoint(
+            "post",
+            "/place_bracket",
+            "同时下止盈、止损单，一笔成交后取消另一笔",
+            User,
+        )
+        .request(schema::<PlaceBracketRequest>)
+        .response(schema::<ApiResponse<Bracket>>),
+        endpoint("post", "/cancel_order", "取消订单", User).request(schema::<CancelOrderRequest>),
+        endpoint(
+            "post",
+            "/admin/cancel_order/{order_id}",
+            "管理员取消任意订单",
+            Admin,
+        )
+        .params(vec![path_param("order_id", uuid())]),
+        endpoint("post", "/cancel_all", "取消钱包的全部订单", User)
+            .request(schema::<CancelAllRequest>)
+            .response(schema::<ApiResponse<CancelAllResult>>),
+        endpoint(
+            "post",
+            "/modify_order",
+            "修改未触发订单的价格、数量或滑点",
+            User,
+        )
+        .request(schema::<ModifyOrderRequest>)
+        .response(schema::<ApiResponse<Order>>),
+        endpoint(
+            "get",
+            "/events",
+            "订阅钱包的订单事件（Server-Sent Events）",
+            User,
+        )
+        .params(vec![query_param("user", true, string())])
+        .raw("text/event-stream"),
+        endpoint("get", "/ready", "就绪检查", Public).response(schema::<ApiResponse<ReadyStatus>>),
+        endpoint("get", "/health", "依赖的健康检查，不健康时返回 503", Public)
+            .response(schema::<ApiResponse<HealthReport>>),
+        endpoint(
+            "post",
+            "/admin/revoke_wallet",
+            "吊销钱包并取消其全部订单",
+            Admin,
+        )
+        .request(schema::<RevokeWalletRequest>)
+        .response(schema::<ApiResponse<Vec<Uuid>>>),
+        endpoint("get", "/admin/revoked", "已吊销的钱包", Admin)
+            .response(schema::<ApiResponse<Vec<RevokedWallet>>>),
+        endpoint("post", "/pause", "暂停全部订单的触发", Admin)
+            .response(schema::<ApiResponse<PauseState>>),
+        endpoint("post", "/resume", "恢复全部订单的触发", Admin)
+            .response(schema::<ApiResponse<PauseState>>),
+        endpoint("post", "/pause_order/{order_id}", "暂停单笔订单", Admin)
+            .params(vec![path_param("order_id", uuid())])
+            .response(schema::<ApiResponse<PauseState>>),
+        endpoint("post", "/resume_order/{order_id}", "恢复单笔订单", Admin)
+            .params(vec![path_param("order_id", uuid())])
+            .response(schema::<ApiResponse<PauseState>>),
+        endpoint(
+            "get",
+            "/price/{mint}",
+            "代币的 USD 价格，价格不可用时返回 503",
+            Public,
+        )
+        .params(vec![path_param("mint", string())])
+        .response(schema::<ApiResponse<PriceResponse>>),
+        endpoint(
+            "post",
+            "/quote",
+            "询价并返回可在下单时锁定路由的 route_token",
+            Public,
+        )
+        .request(schema::<QuoteRequest>)
+        .response(schema::<ApiResponse<QuoteResponse>>),
+        endpoint(
+            "post",
+            "/quote_order",
+            "按下单参数预估成交数量和税收",
+            Public,
+        )
+        .request(schema::<PlaceOrderRequest>)
+        .response(schema::<ApiResponse<OrderQuote>>),
+        endpoint("post", "/simulate_order", "模拟订单的交换交易", User)
+            .request(schema::<SimulateOrderRequest>)
+            .response(schema::<ApiResponse<SwapSimulation>>),
+        endpoint("get", "/orders", "订单簿中的订单，可按钱包过滤", User)
+            .params(vec![query_param("user", false, string())])
+            .response(schema::<ApiResponse<Vec<Order>>>),
+        endpoint(
+            "get",
+            "/orders/history",
+            "已结束订单的历史，按时间倒序分页",
+            User,
+        )
+        .params(vec![
+            query_param("user", false, string()),
+            query_param("status", false, string()),
+            query_param("mint", false, string()),
+            query_param("limit", false, integer()),
+            query_param("cursor", false, string()),
+        ])
+        .response(schema::<ApiResponse<OrderHistoryPage>>),
+        endpoint("post", "/order_statuses", "批量查询订单状态", User)
+            .request(schema::<Vec<Uuid>>)
+            .response(schema::<ApiResponse<HashMap<Uuid, OrderStatusView>>>),
+        endpoint(
+            "get",
+            "/order_status/{order_id}",
+            "查询单笔订单的状态",
+            User,
+        )
+        .params(vec![path_param("order_id", uuid())])
+        .response(schema::<ApiResponse<OrderStatusView>>),
+        endpoint("get", "/order/{order_id}/events", "订单的审计日志", User)
+            .params(vec![path_param("order_id", uuid())])
+            .response(schema::<ApiResponse<Vec<AuditEvent>>>),
+        endpoint("get", "/treasury", "收税账户的余额", User)
+            .response(schema::<ApiResponse<TreasuryBalances>>),
+        endpoint(
+            "post",
+            "/treasury/sweep",
+            "将收税账户的余额归集到冷钱包",
+            Admin,
+        )
+        .response(schema::<ApiResponse<SweepResult>>),
+        endpoint(
+            "get",
+            "/fees",
+            "预估交换的手续费、优先费、tip 和税收",
+            Public,
+        )
+        .params(vec![
+            query_param("input_mint", true, string()),
+            query_param("output_mint", true, string()),
+            query_param("amount", true, integer()),
+            query_param("tip_amount", false, integer()),
+        ])
+        .response(schema::<ApiResponse<FeeEstimate>>),
+        endpoint(
+            "post",
+            "/admin/config/preview",
+            "预览新的运行配置对待触发订单的影响",
+            Admin,
+        )
+        .request(schema::<RuntimeConfig>)
+        .response(schema::<ApiResponse<Vec<ConfigPreview>>>),
+        endpoint("post", "/admin/tax_policy", "替换税收策略", Admin)
+            .request(schema::<TaxPolicy>)
+            .response(schema::<ApiResponse<TaxPolicy>>),
+        endpoint("post", "/session", "用加密私钥创建会话", User)
+            .request(schema::<CreateSessionRequest>)
+            .response(schema::<ApiResponse<SessionResponse>>),
+        endpoint("delete", "/session", "删除会话", User).request(schema::<DeleteSessionRequest>),
+        endpoint(
+            "post",
+            "/prepare_order",
+            "非托管下单：生成待用户签名的交易",
+            User,
+        )
+        .request(schema::<PrepareOrderRequest>)
+        .response(schema::<ApiResponse<PreparedTransaction>>),
+        endpoint(
+            "post",
+            "/submit_signed_order",
+            "非托管下单：提交用户签名的交易",
+            User,
+        )
+        .request(schema::<SubmitSignedOrderRequest>)
+        .response(schema::<ApiResponse<Uuid>>),
+        endpoint("get", "/metrics", "Prometheus 指标", Admin).raw("text/plain"),
+        endpoint("get", "/openapi.json", "本文档", Public).raw("application/json"),
+        endpoint(
+            "get",
+            "/docs",
+            "Swagger UI，配置 SWAGGER_UI=true 时提供",
+            Public,
+        )
+        .raw("text/html"),
+    ]
+}
+
+/// 生成 OpenAPI 文档
+fn build_spec() -> Value {
+    let mut gen = SchemaSettings::openapi3().into_generator();
+    let auth_error = gen.subschema_for::<ApiResponse<()>>();
+    let mut paths = Map::new();
+    for endpoint in endpoints() {
+        let mut responses = Map::new();
+        let ok = match endpoint.response {
+            Body::Json(response) => json!({
+                "description": "成功时 `success` 为 true，失败时 `error_code` 给出错误码",
+                "content": { "application/json": { "schema": response(&mut gen) } }
+            }),
+            Body::Raw(content_type) => json!({
+                "description": "成功",
+                "content": { content_type: {} }
+            }),
+        };
+        responses.insert("200".to_string(), ok);
+        if endpoint.access != Access::Public {
+            for (status, description) in [
+                ("401", "缺少或无效的 API key"),
+                ("403", "API key 没有该接口的权限"),
+                (
+                    "429",
+                    "API key 被限流，`retry_after_ms` 给出需要等待的毫秒数",
+                ),
+            ] {
+                responses.insert(
+                    status.to_string(),
+                    json!({
+                        "description": description,
+                        "content": { "application/json": { "schema": auth_error } }
+                    }),
+                );
+            }
+        }
+        // 例如 `post_pause_order_order_id`
+        let operation_id = format!(
+            "{}_{}",
+            endpoint.method,
+            endpoint
+                .path
+                .trim_start_matches('/')
+                .replace(['/', '.'], "_")
+                .replace(['{', '}'], "")
+        );
+        let mut operation = json!({
+            "operationId": operation_id,
+            "summary": endpoint.summary,
+            "responses": responses,
+        });
+        if !endpoint.params.is_empty() {
+            operation["parameters"] = endpoint
+                .params
+                .into_iter()
+                .map(|param| {
+                    json!({
+                        "name": param.name,
+                        "in": param.location,
+                        "required": param.required,
+                        "schema": param.schema,
+                    })
+                })
+                .collect();
+        }
+        if let Some(request) = endpoint.request {
+            operation["requestBody"] = json!({
+                "required": true,
+                "content": { "application/json": { "schema": request(&mut gen) } }
+            });
+        }
+        match endpoint.access {
+            Access::Public => operation["security"] = json!([]),
+            Access::User => {}
+            Access::Admin => operation["description"] = json!("需要管理员 API key"),
+        }
+        let item = paths
+            .entry(endpoint.path.to_string())
+            .or_insert_with(|| json!({}));
+        item[endpoint.method] = operation;
+    }
+
+    let mut schemas = serde_json::to_value(gen.take_definitions()).unwrap_or_default();
+    // 所有 ApiResponse 的错误码都引用同一个枚举
+    if let Some(schemas) = schemas.as_object_mut() {
+        for (name, schema) in schemas.iter_mut() {
+            if name.starts_with("ApiResponse") {
+                schema["properties"]["error_code"] = json!({
+                    "allOf": [{ "$ref": "#/components/schemas/ErrorCode" }],
+                    "nullable": true,
+                });
+            }
+        }
+        schemas.insert(
+            "ErrorCode".to_string(),
+            json!({
+                "type": "string",
+                "description": "机器可读的错误码",
+                "enum": ERROR_CODES,
+            }),
+        );
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Jupiter limit order",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "components": {
+            "schemas": schemas,
+            "securitySchemes": {
+                "apiKey": { "type": "apiKey", "in": "header", "name": "X-Api-Key" },
+                "bearer": { "type": "http", "scheme": "bearer" },
+            },
+        },
+        // 未配置 API_KEYS 时接口不启用认证
+        "security": [{ "apiKey": [] }, { "bearer": [] }],
+    })
+}
+
+/// OpenAPI 文档，第一次调用时生成
+pub fn openapi_spec() -> &'static Value {
+    static SPEC: OnceLock<Value> = OnceLock::new();
+    SPEC.get_or_init(build_spec)
+}
+
+/// 已挂载但未在文档中登记的路由，格式为 `GET /path`
+pub fn unlisted_routes<'a>(routes: impl Iterator<Item = &'a Route>) -> Vec<String> {
+    let paths = openapi_spec()["paths"].as_object();
+    routes
+        .filter_map(|route| {
+            let method = route.method.as_str().to_ascii_lowercase();
+            // `/price/<mint>` => `/price/{mint}`，查询参数不是路径的一部分
+            let path = route
+                .uri
+                .origin
+                .path()
+                .as_str()
+                .replace('<', "{")
+                .replace('>', "}");
+            let listed = paths
+                .and_then(|paths| paths.get(&path))
+                .is_some_and(|item| item.get(&method).is_some());
+            (!listed).then(|| format!("{} {}", route.method, path))
+        })
+        .collect()
+}
+
+/// OpenAPI 3 格式的接口文档
+///
+/// 示例请求：
+/// ```bash
+/// curl http://localhost:8000/openapi.json
+/// ```
+///
+/// 返回示例：
+/// ```json
+/// {
+///     "openapi": "3.0.3",
+///     "info": { "title": "Jupiter limit order", "version": "0.1.0" },
+///     "paths": { "/place_order": { "post": { ... } }, ... },
+///     "components": { "schemas": { "PlaceOrderRequest": { ... }, "ErrorCode": { ... }, ... } }
+/// }
+/// ```
+#[get("/openapi.json")]
+pub fn openapi_json() -> Json<&'static Value> {
+    Json(openapi_spec())
+}
+
+/// Swagger UI，只在配置 `SWAGGER_UI=true` 时挂载；页面从 CDN 加载 swagger-ui，读取 `/openapi.json`
+///
+/// 示例请求：浏览器打开 `http://localhost:8000/docs`
+#[get("/docs")]
+pub fn swagger_ui() -> RawHtml<&'static str> {
+    RawHtml(
+        r##"<!DOCTYPE html>
+<html>
+<head>
+  <meta charset="utf-8" />
+  <title>Jupiter limit order API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css" />
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>
+    window.ui = SwaggerUIBundle({ url: "/openapi.json", dom_id: "#swagger-ui" });
+  </script>
+</body>
+</html>"##,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::routes;
+
+    use super::*;
+    use crate::app::api_routes;
+
+    fn mounted_routes() -> Vec<Route> {
+        let mut routes = api_routes();
+        routes.extend(routes![openapi_json, swagger_ui]);
+        routes
+    }
+
+    /// 收集文档中所有的 `$ref`
+    fn refs<'a>(value: &'a Value, found: &mut Vec<&'a str>) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map {
+                    match (key.as_str(), value) {
+                        ("$ref", Value::String(reference)) => found.push(reference),
+                        _ => refs(value, found),
+                    }
+                }
+            }
+            Value::Array(items) => items.iter().for_each(|item| refs(item, found)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn spec_round_trips_and_every_ref_resolves() {
+        let text = serde_json::to_string(openapi_spec()).unwrap();
+        let spec: Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(spec["openapi"], "3.0.3");
+        let schemas = spec["components"]["schemas"].as_object().unwrap();
+        let mut found = vec![];
+        refs(&spec, &mut found);
+        assert!(!found.is_empty());
+        for reference in found {
+            let name = reference
+                .strip_prefix("#/components/schemas/")
+                .unwrap_or_else(|| panic!("未知的引用 {}", reference));
+            assert!(schemas.contains_key(name), "{} 未定义", reference);
+        }
+    }
+
+    #[test]
+    fn every_mounted_route_is_listed() {
+        assert_eq!(
+            unlisted_routes(mounted_routes().iter()),
+            Vec::<String>::new()
+        );
+    }
+
+    /// 文档中的每个接口都有对应的路由，不会登记已删除的接口
+    #[test]
+    fn every_listed_operation_is_mounted() {
+        let mounted: Vec<(String, String)> = mounted_routes()
+            .iter()
+            .map(|route| {
+                let path = route
+                    .uri
+                    .origin
+                    .path()
+                    .as_str()
+                    .replace('<', "{")
+                    .replace('>', "}");
+                (route.method.as_str().to_ascii_lowercase(), path)
+            })
+            .collect();
+        for (path, item) in openapi_spec()["paths"].as_object().unwrap() {
+            for method in item.as_object().unwrap().keys() {
+                assert!(
+                    mounted.contains(&(method.clone(), path.clone())),
+                    "{} {} 没有挂载",
+                    method,
+                    path
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,641 @@
+//! 订单簿的命令行工具，直接以库的方式调用订单簿，不经过 HTTP
+//!
+//! ```bash
+//! # 常驻进程：恢复快照，执行命令目录中的下单和撤单，监控订单直到 Ctrl-C
+//! loctl run
+//! # 本地校验后提交订单，输出订单 UUID 后立即退出
+//! loctl place --key-file key.txt \
+//!     --input-mint So11111111111111111111111111111111111111112 \
+//!     --output-mint EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v \
+//!     --price 150 --amount 1000000000 --kind TakeProfit
+//! loctl cancel 1b4e28ba-2fa1-11d2-883f-0016d3cca427
+//! loctl list --json
+//! loctl price So11111111111111111111111111111111111111112
+//! # 跳过触发条件立即交换
+//! loctl swap-now --key-file key.txt --input-mint So111... --output-mint EPjF... --amount 1000000
+//! ```
+//!
+//! 与服务使用同一套环境变量。`place` 和 `cancel` 把命令写入 `LOCTL_SPOOL_DIR` 目录后退出，
+//! 由 `loctl run` 轮询执行；`run` 同时把订单表写入该目录下的 `orders.json`，供 `list` 读取。
+//! 私钥从 `--key-file` 读取（base58 或 Solana CLI 的 JSON 数组），未指定时在终端输入，
+//! 只以 `AES_KEY` 加密后的形式写入命令目录。
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Context, Result};
+use clap::{Args, Parser, Subcommand};
+use limit_order::{
+    common::{
+        config::AppConfig,
+        db::MysqlOrderStore,
+        dns::build_http_client,
+        encode::{encrypt, SecretString},
+        keys::install,
+        retry::PacingPolicy,
+        session::parse_keypair,
+        snapshot::{ResumeState, SuspendedOrder},
+        types::{Order, OrderBook, OrderKind, OrderLeg, TriggerCondition, TriggerOn},
+        units::{Bps, Lamports, TokenAmount},
+        utils::{BlockhashProvider, BLOCKHASH_EXPIRY_MARGIN},
+    },
+    error::LimitOrderError,
+    solana::{
+        jito::refresh_tip_accounts,
+        jup::{SwapMode, SwapOptions},
+        multi_rpc::MultiRpc,
+        signer::{LocalKeypairSigner, TransactionSigner},
+        swap::{
+            swap_with_tax, ExecutionOptions, PrivateExecution, SwapContext, SwapOutcome, SwapParams,
+        },
+    },
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use solana_sdk::{bs58, pubkey::Pubkey};
+use uuid::Uuid;
+use zeroize::Zeroizing;
+
+/// `run` 轮询命令目录的间隔
+const SPOOL_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Parser)]
+#[command(name = "loctl", about = "限价单订单簿的命令行工具")]
+struct Cli {
+    /// 以 JSON 输出结果
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// 常驻运行订单监控，执行 place / cancel 提交的命令
+    Run,
+    /// 校验并提交托管订单，输出订单 UUID
+    Place(PlaceArgs),
+    /// 提交撤单
+    Cancel { order_id: Uuid },
+    /// 列出订单，包括尚未被 run 取走的订单
+    List,
+    /// 查询代币的 USD 价格
+    Price { mint: Pubkey },
+    /// 跳过触发条件，立即执行一次带税收的交换
+    SwapNow(SwapNowArgs),
+}
+
+#[derive(Args)]
+struct KeyArgs {
+    /// 私钥文件，内容为 base58 私钥或 Solana CLI 的 JSON 数组；未指定时在终端输入
+    #[arg(long)]
+    key_file: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct PlaceArgs {
+    #[command(flatten)]
+    key: KeyArgs,
+    #[arg(long)]
+    input_mint: String,
+    #[arg(long)]
+    output_mint: String,
+    /// 触发价格，含义由 --trigger-on 决定
+    #[arg(long)]
+    price: f32,
+    /// 数量（最小单位），ExactIn 为输入数量，ExactOut 为输出数量
+    #[arg(long)]
+    amount: u64,
+    /// 滑点（基点），默认使用 DEFAULT_SLIPPAGE_BPS
+    #[arg(long, value_parser = json_arg::<Bps>)]
+    slippage_bps: Option<Bps>,
+    /// Jito tip（lamports），指定时以 bundle 发送
+    #[arg(long)]
+    tip_lamports: Option<u64>,
+    /// 优先费（micro-lamports / CU）
+    #[arg(long)]
+    priority_fee: Option<u64>,
+    /// 重试节奏：fixed 或 slot:<min_slots>
+    #[arg(long, value_parser = |s: &str| s.parse::<PacingPolicy>().map_err(|e| e.to_string()))]
+    pacing: Option<PacingPolicy>,
+    /// InputUsd、OutputUsd、Ratio 或 ExecutablePrice
+    #[arg(long, value_parser = json_arg::<TriggerOn>)]
+    trigger_on: Option<TriggerOn>,
+    /// ExactIn 或 ExactOut
+    #[arg(long, value_parser = json_arg::<SwapMode>)]
+    swap_mode: Option<SwapMode>,
+    /// /quote 返回的路由令牌
+    #[arg(long)]
+    route_token: Option<String>,
+    /// 固定路由失效时不回退到重新报价
+    #[arg(long)]
+    no_pin_fallback: bool,
+    #[arg(long)]
+    split_parts: Option<u32>,
+    /// Limit、TakeProfit、StopLoss，或 JSON，例如 '{"type":"TrailingStop","trail_bps":300}'
+    #[arg(long, value_parser = json_arg::<OrderKind>)]
+    kind: Option<OrderKind>,
+    /// 限价单的触发方向：Above 或 Below，限价单必须指定
+    #[arg(long, value_parser = json_arg::<TriggerCondition>)]
+    trigger_condition: Option<TriggerCondition>,
+    #[arg(long)]
+    callback_url: Option<String>,
+    #[arg(long)]
+    skip_simulation: bool,
+    /// 无论金额大小都以 Jito bundle 私有发送
+    #[arg(long)]
+    force_private_execution: bool,
+    /// 扣税后的最低输出数量
+    #[arg(long)]
+    min_out: Option<u64>,
+    #[arg(long, value_parser = json_arg::<Bps>)]
+    max_price_impact_bps: Option<Bps>,
+    /// 成交价格相对触发价格的最大偏离，未指定时使用 `MAX_TRIGGER_DEVIATION_BPS`
+    #[arg(long, value_parser = json_arg::<Bps>)]
+    max_trigger_deviation_bps: Option<Bps>,
+    #[arg(long)]
+    poll_interval_ms: Option<u64>,
+    #[arg(long)]
+    repeat_count: Option<u32>,
+    #[arg(long, allow_hyphen_values = true)]
+    reprice_offset_bps: Option<i16>,
+    #[arg(long)]
+    compound: bool,
+    /// Jupiter 交易选项（JSON），例如 '{"wrap_and_unwrap_sol":false}'
+    #[arg(long, value_parser = json_arg::<SwapOptions>)]
+    swap_options: Option<SwapOptions>,
+    /// 触发后执行的时限（毫秒），超时放弃本次执行
+    #[arg(long)]
+    post_trigger_deadline_ms: Option<u64>,
+    /// 执行超时后回到监控，不设置时订单以 Unfilled 结束
+    #[arg(long)]
+    rearm_after_timeout: bool,
+    /// 订单有效期（秒），到期仍未触发时订单以 Expired 结束
+    #[arg(long)]
+    ttl_secs: Option<u64>,
+}
+
+#[derive(Args)]
+struct SwapNowArgs {
+    #[command(flatten)]
+    key: KeyArgs,
+    #[arg(long)]
+    input_mint: Pubkey,
+    #[arg(long)]
+    output_mint: Pubkey,
+    /// 数量（最小单位），ExactIn 为含税的输入数量，ExactOut 为输出数量
+    #[arg(long)]
+    amount: u64,
+    #[arg(long, value_parser = json_arg::<SwapMode>)]
+    swap_mode: Option<SwapMode>,
+    #[arg(long, value_parser = json_arg::<Bps>)]
+    slippage_bps: Option<Bps>,
+    #[arg(long)]
+    tip_lamports: Option<u64>,
+    #[arg(long)]
+    priority_fee: Option<u64>,
+    /// 计算单元上限，未指定时由模拟结果推导
+    #[arg(long)]
+    compute_unit_limit: Option<u32>,
+    #[arg(long)]
+    min_out: Option<u64>,
+    #[arg(long, value_parser = json_arg::<Bps>)]
+    max_price_impact_bps: Option<Bps>,
+    #[arg(long, value_parser = json_arg::<SwapOptions>)]
+    swap_options: Option<SwapOptions>,
+    #[arg(long)]
+    skip_simulation: bool,
+    /// 无论金额大小都以 Jito bundle 私有发送
+    #[arg(long)]
+    force_private_execution: bool,
+}
+
+/// 以 JSON 解析命令行参数，枚举可以直接写变体名，例如 `ExactOut`、`TakeProfit`
+fn json_arg<T: DeserializeOwned>(s: &str) -> Result<T, String> {
+    serde_json::from_str(s)
+        .or_else(|_| serde_json::from_value(Value::String(s.to_string())))
+        .or_else(|_| serde_json::from_value(json!({ "type": s })))
+        .map_err(|e| format!("无法解析 {}：{}", s, e))
+}
+
+#[tokio::main]
+async fn main() {
+    dotenv::dotenv().ok();
+    let cli = Cli::parse();
+    let json = cli.json;
+    if let Err(e) = execute(cli).await {
+        if json {
+            let code = e
+                .downcast_ref::<LimitOrderError>()
+                .and_then(LimitOrderError::code);
+            println!(
+                "{}",
+                json!({ "success": false, "error": format!("{:#}", e), "error_code": code })
+            );
+        } else {
+            eprintln!("错误：{:#}", e);
+        }
+        std::process::exit(1);
+    }
+}
+
+async fn execute(cli: Cli) -> Result<()> {
+    let config = AppConfig::from_env()?;
+    let spool = Spool::from_env();
+    match cli.command {
+        Command::Run => run(config, &spool).await,
+        Command::Place(args) => {
+            let order_id = place(config, &spool, args).await?;
+            if cli.json {
+                print_json(&json!({ "order_id": order_id, "queued": true }));
+            } else {
+                println!("{}", order_id);
+            }
+            Ok(())
+        }
+        Command::Cancel { order_id } => {
+            spool.submit(&SpoolCommand::Cancel { order_id })?;
+            if cli.json {
+                print_json(&json!({ "order_id": order_id, "queued": true }));
+            } else {
+                println!("已提交撤单 {}", order_id);
+            }
+            Ok(())
+        }
+        Command::List => {
+            let list = spool.list()?;
+            if cli.json {
+                print_json(&list);
+            } else {
+                for order in &list.orders {
+                    println!("{}", order_line(order, order.status.label()));
+                }
+                for order in &list.queued {
+                    println!("{}", order_line(order, "queued"));
+                }
+            }
+            Ok(())
+        }
+        Command::Price { mint } => {
+            let source = config.price_sources.build(Arc::new(build_http_client()?));
+            let quote = source
+                .get_prices(&[mint])
+                .await?
+                .remove(&mint)
+                .ok_or_else(|| anyhow!("价格源没有返回 {} 的价格", mint))?;
+            if cli.json {
+                print_json(&json!({ "mint": mint.to_string(), "quote": quote }));
+            } else {
+                println!("{} {} USD（{}）", mint, quote.price, quote.source);
+            }
+            Ok(())
+        }
+        Command::SwapNow(args) => {
+            let (user, outcome) = swap_now(config, args).await?;
+            if cli.json {
+                print_json(
+                    &json!({ "user": user.to_string(), "confirmed": true, "outcome": outcome }),
+                );
+            } else {
+                println!(
+                    "交换已上链 {}，bundle {:?}，报价输出 {}",
+                    outcome.signature, outcome.bundle_id, outcome.quoted_out_amount
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+fn print_json(value: &impl Serialize) {
+    match serde_json::to_string_pretty(value) {
+        Ok(out) => println!("{}", out),
+        Err(e) => eprintln!("序列化输出失败 {:?}", e),
+    }
+}
+
+fn order_line(order: &Order, status: &str) -> String {
+    format!(
+        "{}  {:<16}  {} -> {}  price {}  amount {}",
+        order.order_id, status, order.input_mint, order.output_mint, order.price, order.amount
+    )
+}
+
+/// 读取私钥，支持 base58 和 Solana CLI 的 JSON 数组
+fn read_private_key(key: &KeyArgs) -> Result<SecretString> {
+    let raw = Zeroizing::new(match &key.key_file {
+        Some(path) => {
+            fs::read_to_string(path).with_context(|| format!("读取私钥文件 {:?} 失败", path))?
+        }
+        None => rpassword::prompt_password("私钥：")?,
+    });
+    let raw = raw.trim();
+    if raw.starts_with('[') {
+        let bytes: Zeroizing<Vec<u8>> =
+            Zeroizing::new(serde_json::from_str(raw).context("私钥文件格式错误")?);
+        return Ok(SecretString::new(bs58::encode(&*bytes).into_string()));
+    }
+    Ok(SecretString::new(raw.to_string()))
+}
+
+/// 按服务下单时的全部检查生成订单，私钥加密后写入命令目录
+async fn place(config: AppConfig, spool: &Spool, args: PlaceArgs) -> Result<Uuid> {
+    let private_key = read_private_key(&args.key)?;
+    let book = OrderBook::new(&config)?;
+    install(config.keys.load().await?)?;
+    let leg = OrderLeg {
+        input_mint: args.input_mint,
+        output_mint: args.output_mint,
+        price: args.price,
+        amount: args.amount,
+        slippage_bps: args.slippage_bps.unwrap_or(book.default_slippage_bps),
+        tip_amount: args.tip_lamports.map(Lamports),
+        priority_fee_micro_lamports: args
+            .priority_fee
+            .or(book.default_priority_fee_micro_lamports),
+        pacing: args.pacing,
+        trigger_on: args.trigger_on.unwrap_or_default(),
+        swap_mode: args.swap_mode.unwrap_or_default(),
+        route_token: args.route_token,
+        pin_fallback: !args.no_pin_fallback,
+        split_parts: args.split_parts,
+        kind: args.kind.unwrap_or_default(),
+        trigger_condition: args.trigger_condition,
+        callback_url: args.callback_url,
+        skip_simulation: args.skip_simulation,
+        force_private_execution: args.force_private_execution,
+        min_out_amount: args.min_out,
+        max_price_impact_bps: args
+            .max_price_impact_bps
+            .or(book.default_max_price_impact_bps),
+        max_trigger_deviation_bps: args.max_trigger_deviation_bps,
+        poll_interval_ms: args.poll_interval_ms,
+        repeat_count: args.repeat_count,
+        reprice_offset_bps: args.reprice_offset_bps,
+        compound: args.compound,
+        swap_options: args.swap_options.unwrap_or_default(),
+        post_trigger_deadline_ms: args.post_trigger_deadline_ms,
+        rearm_after_timeout: args.rearm_after_timeout,
+        ttl_secs: args.ttl_secs,
+    };
+    let (_, order) = book.accept_order(&private_key, leg).await?;
+    let order_id = order.order_id;
+    spool.submit(&SpoolCommand::Place {
+        order: Box::new(SuspendedOrder {
+            order,
+            resume: ResumeState::Custodial {
+                encrypt_pk: encrypt(private_key.expose().as_bytes())?,
+            },
+        }),
+    })?;
+    Ok(order_id)
+}
+
+/// 直接调用 [`swap_with_tax`]，不创建订单
+async fn swap_now(config: AppConfig, args: SwapNowArgs) -> Result<(Pubkey, SwapOutcome)> {
+    let signer = LocalKeypairSigner::new(parse_keypair(read_private_key(&args.key)?.expose())?);
+    // 单次交换不需要后台探测，节点故障时同样按顺序切换
+    let rpc = MultiRpc::new(&config.rpc);
+    let jup = jupiter_swap_api_client::JupiterSwapApiClient::new(config.jup_url.clone());
+    let jito = jito_sdk_rust::JitoJsonRpcSDK::new(&config.jito_url, None);
+    if let Err(e) = refresh_tip_accounts(&jito).await {
+        println!("获取 Jito tip 账户失败，使用内置列表 {:?}", e);
+    }
+    let blockhashes = BlockhashProvider::new(BLOCKHASH_EXPIRY_MARGIN);
+    let swap_mode = args.swap_mode.unwrap_or_default();
+    let amount_mint = match swap_mode {
+        SwapMode::ExactIn => args.input_mint,
+        SwapMode::ExactOut => args.output_mint,
+    };
+    let ctx = SwapContext {
+        jup: &jup,
+        rpc: &rpc,
+        jito: &jito,
+        blockhashes: &blockhashes,
+        bundle: config.bundle,
+        tax_account: config.tax_account,
+        tax_side: config.tax_side,
+    };
+    let outcome = swap_with_tax(
+        &ctx,
+        &signer,
+        &config.tax_policy,
+        &SwapParams {
+            input_mint: args.input_mint,
+            output_mint: args.output_mint,
+            amount: TokenAmount::new(amount_mint, args.amount),
+            swap_mode,
+            slippage_bps: args.slippage_bps.unwrap_or(config.default_slippage_bps),
+            min_out_amount: args.min_out,
+            max_price_impact_bps: args
+                .max_price_impact_bps
+                .or(config.default_max_price_impact_bps),
+            options: &args.swap_options.unwrap_or_default(),
+            pin: None,
+        },
+        ExecutionOptions {
+            // 输入、输出都不是 SOL 时名义价值未知，配置了阈值即按超过阈值处理
+            private: PrivateExecution {
+                config: config.private_execution,
+                force: args.force_private_execution,
+                input_lamports_per_unit: None,
+            },
+            tip_amount: args.tip_lamports.map(Lamports),
+            compute_unit_limit: args.compute_unit_limit,
+            compute_unit_price: args
+                .priority_fee
+                .or(config.default_priority_fee_micro_lamports),
+            nonce: None,
+            skip_simulation: args.skip_simulation,
+        },
+    )
+    .await?;
+    Ok((signer.pubkey(), outcome))
+}
+
+/// 常驻进程：与服务启动时相同地恢复快照，然后轮询命令目录，Ctrl-C 时保存快照退出
+async fn run(config: AppConfig, spool: &Spool) -> Result<()> {
+    let mut book = OrderBook::new(&config)?;
+    if let Some(pool) = book.db.clone() {
+        book.enable_persistence(Arc::new(MysqlOrderStore::new(pool)));
+    }
+    install(config.keys.load().await?)?;
+    if let Err(e) = refresh_tip_accounts(&book.jito).await {
+        println!("获取 Jito tip 账户失败，使用内置列表 {:?}", e);
+    }
+    match book.restore_snapshot().await {
+        Ok(count) => println!("已恢复 {} 笔订单", count),
+        Err(e) => println!("恢复订单快照失败 {:?}", e),
+    }
+    println!("监控订单中，命令目录 {:?}", spool.dir);
+
+    let stop = tokio::signal::ctrl_c();
+    tokio::pin!(stop);
+    let mut ticker = tokio::time::interval(SPOOL_POLL_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = &mut stop => break,
+            _ = ticker.tick() => {}
+        }
+        for (path, command) in spool.pending()? {
+            let result = match command {
+                Err(e) => Err(e),
+                Ok(SpoolCommand::Place { order }) => {
+                    let order_id = order.order.order_id;
+                    book.resume_order(*order)
+                        .await
+                        .map(|()| println!("订单 {:?} 已开始监控", order_id))
+                }
+                Ok(SpoolCommand::Cancel { order_id }) => book
+                    .admin_cancel_order(order_id)
+                    .await
+                    .map(|()| println!("订单 {:?} 已撤销", order_id))
+                    .map_err(anyhow::Error::from),
+            };
+            spool.finish(&path, result);
+        }
+        let orders: Vec<Order> = book.orders.lock().await.values().cloned().collect();
+        if let Err(e) = spool.write_orders(orders) {
+            println!("写入订单列表失败 {:?}", e);
+        }
+    }
+    book.shutdown().await
+}
+
+/// 命令目录中的一条命令
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+enum SpoolCommand {
+    Place { order: Box<SuspendedOrder> },
+    Cancel { order_id: Uuid },
+}
+
+/// `list` 的输出
+#[derive(Serialize, Deserialize, Default)]
+struct OrderList {
+    /// `run` 最后一次写入订单列表的时间（unix 毫秒），`run` 从未运行时为 None
+    updated_at_ms: Option<u64>,
+    orders: Vec<Order>,
+    /// 已提交、尚未被 `run` 取走的订单
+    #[serde(default)]
+    queued: Vec<Order>,
+}
+
+/// `place`、`cancel` 与 `run` 之间交换命令的目录，取自 `LOCTL_SPOOL_DIR`，默认为 `loctl_spool`
+///
+/// 命令写入 `commands/` 下以提交时间开头的文件，先写临时文件再重命名，`run` 按文件名顺序执行后删除；
+/// 执行失败的命令改名为 `*.failed` 保留。
+struct Spool {
+    dir: PathBuf,
+}
+
+impl Spool {
+    fn from_env() -> Spool {
+        Spool {
+            dir: env::var("LOCTL_SPOOL_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| PathBuf::from("loctl_spool")),
+        }
+    }
+
+    fn commands_dir(&self) -> PathBuf {
+        self.dir.join("commands")
+    }
+
+    fn orders_path(&self) -> PathBuf {
+        self.dir.join("orders.json")
+    }
+
+    fn submit(&self, command: &SpoolCommand) -> Result<()> {
+        let dir = self.commands_dir();
+        fs::create_dir_all(&dir)?;
+        let name = format!("{:013}-{}", unix_ms(), Uuid::new_v4());
+        write_atomic(
+            &dir.join(format!("{}.json", name)),
+            &serde_json::to_vec(command)?,
+        )
+    }
+
+    /// 尚未执行的命令，按提交顺序排列；无法解析的命令同样返回，由 `run` 标记为失败
+    fn pending(&self) -> Result<Vec<(PathBuf, Result<SpoolCommand>)>> {
+        let entries = match fs::read_dir(self.commands_dir()) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        paths.sort();
+        Ok(paths
+            .into_iter()
+            .map(|path| {
+                let command = fs::read(&path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|data| Ok(serde_json::from_slice(&data)?));
+                (path, command)
+            })
+            .collect())
+    }
+
+    fn finish(&self, path: &Path, result: Result<()>) {
+        let done = match result {
+            Ok(()) => fs::remove_file(path),
+            Err(e) => {
+                println!("执行命令 {:?} 失败 {:?}", path, e);
+                fs::rename(path, path.with_extension("failed"))
+            }
+        };
+        if let Err(e) = done {
+            println!("清理命令 {:?} 失败 {:?}", path, e);
+        }
+    }
+
+    fn write_orders(&self, mut orders: Vec<Order>) -> Result<()> {
+        orders.sort_by_key(|order| (order.created_at, order.order_id));
+        fs::create_dir_all(&self.dir)?;
+        let list = OrderList {
+            updated_at_ms: Some(unix_ms()),
+            orders,
+            queued: vec![],
+        };
+        write_atomic(&self.orders_path(), &serde_json::to_vec_pretty(&list)?)
+    }
+
+    fn list(&self) -> Result<OrderList> {
+        let mut list = match fs::read(self.orders_path()) {
+            Ok(data) => serde_json::from_slice(&data)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => OrderList::default(),
+            Err(e) => return Err(e.into()),
+        };
+        list.queued = self
+            .pending()?
+            .into_iter()
+            .filter_map(|(_, command)| match command {
+                Ok(SpoolCommand::Place { order }) => Some(order.order),
+                _ => None,
+            })
+            .collect();
+        Ok(list)
+    }
+}
+
+/// 先写临时文件再重命名，读取方不会看到写了一半的文件
+fn write_atomic(path: &Path, data: &[u8]) -> Result<()> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, data)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
@@ -0,0 +1,382 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex as StdMutex},
+    time::Duration,
+};
+
+use anyhow::Result;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::{mpsc, oneshot};
+use uuid::Uuid;
+
+use crate::common::{metrics::metrics, price::now_ms};
+
+/// 写入队列容量
+const QUEUE_CAPACITY: usize = 4096;
+/// 单条事件写入存储的最大重试次数
+const MAX_WRITE_RETRIES: u32 = 3;
+/// 清理过期事件的间隔
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// 订单审计日志中的一条事件
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AuditEvent {
+    pub order_id: Uuid,
+    /// 同一订单内从 1 开始递增的序号
+    pub seq: u64,
+    /// 事件发生的时间（毫秒时间戳）
+    pub created_at: u64,
+    /// 事件类型：订单状态事件与 `/events` 推送的 `type` 相同，执行过程另有
+    /// `quote`（触发时的报价）、`submission`（发送交易）、`submitted`（交易已确认）和 `error`
+    pub kind: String,
+    /// 事件的详细内容
+    pub payload: Value,
+}
+
+/// 审计日志的存储后端
+///
+/// 方法都是同步的，由后台任务在阻塞线程池中调用。写入需要是幂等的，重试可能重复写入同一条事件。
+pub trait AuditStore: Send + Sync + 'static {
+    fn append(&self, event: &AuditEvent) -> Result<()>;
+    /// 订单最后一条事件的序号，没有事件时为 0
+    fn last_seq(&self, order_id: Uuid) -> Result<u64>;
+    /// 订单的全部事件，按序号排序
+    fn events(&self, order_id: Uuid) -> Result<Vec<AuditEvent>>;
+    /// 删除早于 `before_ms` 的事件，返回删除的条数
+    fn prune(&self, before_ms: u64) -> Result<usize>;
+}
+
+/// 内存中的审计日志，未配置数据库时使用，重启后丢失
+#[derive(Default)]
+pub struct MemoryAuditStore {
+    events: StdMutex<HashMap<Uuid, Vec<AuditEvent>>>,
+}
+
+impl AuditStore for MemoryAuditStore {
+    fn append(&self, event: &AuditEvent) -> Result<()> {
+        let mut events = self.events.lock().unwrap();
+        let order_events = events.entry(event.order_id).or_default();
+        if order_events.iter().all(|e| e.seq != event.seq) {
+            order_events.push(event.clone());
+        }
+        Ok(())
+    }
+
+    fn last_seq(&self, order_id: Uuid) -> Result<u64> {
+        Ok(self
+            .events
+            .lock()
+            .unwrap()
+            .get(&order_id)
+            .and_then(|events| events.iter().map(|e| e.seq).max())
+            .unwrap_or(0))
+    }
+
+    fn events(&self, order_id: Uuid) -> Result<Vec<AuditEvent>> {
+        let mut events = self
+            .events
+            .lock()
+            .unwrap()
+            .get(&order_id)
+            .cloned()
+            .unwrap_or_default();
+        events.sort_by_key(|e| e.seq);
+        Ok(events)
+    }
+
+    fn prune(&self, before_ms: u64) -> Result<usize> {
+        let mut events = self.events.lock().unwrap();
+        let mut pruned = 0;
+        events.retain(|_, order_events| {
+            let before = order_events.len();
+            order_events.retain(|e| e.created_at >= before_ms);
+            pruned += before - order_events.len();
+            !order_events.is_empty()
+        });
+        Ok(pruned)
+    }
+}
+
+enum AuditMessage {
+    Event {
+        order_id: Uuid,
+        created_at: u64,
+        kind: String,
+        payload: Value,
+    },
+    /// 之前提交的事件全部写入后回复
+    Flush(oneshot::Sender<()>),
+}
+
+/// 订单审计日志的写后缓冲
+///
+/// 事件先进入有界队列，由后台任务分配序号并写入存储，监控和执行路径永远不会等待存储。
+/// 队列已满或多次写入失败时丢弃事件并计入 `audit_events_dropped_total`。
+#[derive(Clone)]
+pub struct AuditLog {
+    tx: mpsc::Sender<AuditMessage>,
+    store: Arc<dyn AuditStore>,
+}
+
+impl AuditLog {
+    /// 启动后台写入任务；`retention` 不为 None 时定期删除早于保留期的事件
+    pub fn spawn(store: Arc<dyn AuditStore>, retention: Option<Duration>) -> AuditLog {
+        let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+        tokio::spawn(writer(store.clone(), rx));
+        if let Some(retention) = retention {
+            tokio::spawn(pruner(store.clone(), retention));
+        }
+        AuditLog { tx, store }
+    }
+
+    /// 追加一条事件，不会阻塞，事件时间取调用时间
+    pub fn append_event(&self, order_id: Uuid, kind: &str, payload: Value) {
+        let message = AuditMessage::Event {
+            order_id,
+            created_at: now_ms(),
+            kind: kind.to_string(),
+            payload,
+        };
+        if self.tx.try_send(message).is_err() {
+            metrics().audit_events_dropped.inc();
+            println!("审计日志队列已满，丢弃订单 {} 的 {} 事件", order_id, kind);
+        }
+    }
+
+    /// 等待之前追加的事件全部写入存储
+    pub async fn flush(&self) {
+        let (tx, rx) = oneshot::channel();
+        if self.tx.send(AuditMessage::Flush(tx)).await.is_ok() {
+            let _ = rx.await;
+        }
+    }
+
+    /// 订单的全部事件，按序号排序；仍在写入队列中的事件不包含在内
+    pub async fn events(&self, order_id: Uuid) -> Result<Vec<AuditEvent>> {
+        let store = self.store.clone();
+        tokio::task::spawn_blocking(move || store.events(order_id)).await?
+    }
+}
+
+async fn writer(store: Arc<dyn AuditStore>, mut rx: mpsc::Receiver<AuditMessage>) {
+    // 每笔订单下一条事件的序号，第一次写入时从存储读取
+    let mut last_seq: HashMap<Uuid, u64> = HashMap::new();
+    while let Some(message) = rx.recv().await {
+        let (order_id, created_at, kind, payload) = match message {
+            AuditMessage::Event {
+                order_id,
+                created_at,
+                kind,
+                payload,
+            } => (order_id, created_at, kind, payload),
+            AuditMessage::Flush(done) => {
+                let _ = done.send(());
+                continue;
+            }
+        };
+        let seq = match last_seq.get(&order_id) {
+            Some(seq) => seq + 1,
+            None => {
+                let _store = store.clone();
+                let stored = tokio::task::spawn_blocking(move || _store.last_seq(order_id))
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .and_then(|seq| seq);
+                match stored {
+                    Ok(seq) => seq + 1,
+                    Err(e) => {
+                        println!("读取订单 {} 的审计序号失败 {:?}", order_id, e);
+                        metrics().audit_events_dropped.inc();
+                        continue;
+                    }
+                }
+            }
+        };
+        let event = AuditEvent {
+            order_id,
+            seq,
+            created_at,
+            kind,
+            payload,
+        };
+        match write_with_retry(store.clone(), event).await {
+            Ok(()) => {
+                last_seq.insert(order_id, seq);
+            }
+            Err(e) => {
+                println!("写入订单 {} 的审计事件失败 {:?}", order_id, e);
+                metrics().audit_events_dropped.inc();
+            }
+        }
+    }
+}
+
+async fn write_with_retry(store: Arc<dyn AuditStore>, event: AuditEvent) -> Result<()> {
+    let mut attempt = 0;
+    loop {
+        let _store = store.clone();
+        let _event = event.clone();
+        let result = tokio::task::spawn_blocking(move || _store.append(&_event)).await?;
+        match result {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt + 1 >= MAX_WRITE_RETRIES => return Err(e),
+            Err(_) => {
+                attempt += 1;
+                tokio::time::sleep(Duration::from_millis(200 * 2u64.pow(attempt))).await;
+            }
+        }
+    }
+}
+
+/// 定期删除早于保留期的事件
+async fn pruner(store: Arc<dyn AuditStore>, retention: Duration) {
+    let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+    loop {
+        interval.tick().await;
+        let before_ms = now_ms().saturating_sub(retention.as_millis() as u64);
+        let _store = store.clone();
+        let pruned = tokio::task::spawn_blocking(move || _store.prune(before_ms))
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|pruned| pruned);
+        match pruned {
+            Ok(0) => {}
+            Ok(pruned) => println!("已删除 {} 条过期的审计事件", pruned),
+            Err(e) => println!("删除过期的审计事件失败 {:?}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn events_are_numbered_per_order() {
+        let audit = AuditLog::spawn(Arc::new(MemoryAuditStore::default()), None);
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+        audit.append_event(first, "placed", Value::Null);
+        audit.append_event(second, "placed", Value::Null);
+        audit.append_event(first, "canceled", Value::Null);
+        audit.flush().await;
+
+        let events = audit.events(first).await.unwrap();
+        let seqs: Vec<(u64, &str)> = events.iter().map(|e| (e.seq, e.kind.as_str())).collect();
+        assert_eq!(seqs, vec![(1, "placed"), (2, "canceled")]);
+        assert_eq!(audit.events(second).await.unwrap()[0].seq, 1);
+    }
+
+    /// 订单簿在下单、执行和撤单时写入的事件序列
+    #[cfg(feature = "testing")]
+    mod mocked {
+        use tokio::sync::Mutex;
+
+        use super::*;
+        use crate::{
+            common::types::{OrderBook, OrderStatus},
+            testing::{
+                fixed_keypair, limit_leg, mock_config, wait_for_order, wallet_secret, MockRpc,
+                MockStack,
+            },
+            SOL,
+        };
+
+        async fn audit_events(book: &Mutex<OrderBook>, order_id: Uuid) -> Vec<AuditEvent> {
+            let audit = book.lock().await.audit.clone();
+            audit.flush().await;
+            let events = audit.events(order_id).await.unwrap();
+            let seqs: Vec<u64> = events.iter().map(|e| e.seq).collect();
+            assert_eq!(seqs, (1..=events.len() as u64).collect::<Vec<_>>());
+            events
+        }
+
+        fn kinds(events: &[AuditEvent]) -> Vec<&str> {
+            events.iter().map(|e| e.kind.as_str()).collect()
+        }
+
+        #[tokio::test]
+        async fn fill_records_quote_submission_and_fill() {
+            let stack = MockStack::new(150.0);
+            let book = Mutex::new(stack.order_book(&mock_config()));
+            let order_id =
+                OrderBook::place_order(&book, wallet_secret(1), limit_leg(200.0, 1_000_000_000))
+                    .await
+                    .unwrap();
+
+            stack.prices.set_price(SOL, 210.0);
+            wait_for_order(&book, order_id, |status| *status == OrderStatus::Filled).await;
+
+            let events = audit_events(&book, order_id).await;
+            assert_eq!(
+                kinds(&events),
+                vec![
+                    "placed",
+                    "quote",
+                    "triggered",
+                    "submission",
+                    "submitted",
+                    "filled"
+                ]
+            );
+            let signature = stack.rpc.sent()[0].signatures[0].to_string();
+            assert_eq!(events[2].payload["signature"], signature);
+            assert_eq!(events[3].payload["signature"], signature);
+            assert_eq!(events[4].payload["signature"], signature);
+        }
+
+        #[tokio::test]
+        async fn cancel_records_placed_and_canceled() {
+            let stack = MockStack::new(150.0);
+            let book = Mutex::new(stack.order_book(&mock_config()));
+            let order_id =
+                OrderBook::place_order(&book, wallet_secret(1), limit_leg(200.0, 1_000_000_000))
+                    .await
+                    .unwrap();
+
+            book.lock()
+                .await
+                .cancel_order(order_id, &fixed_keypair(1).pubkey())
+                .await
+                .unwrap();
+
+            let events = audit_events(&book, order_id).await;
+            assert_eq!(kinds(&events), vec!["placed", "canceled"]);
+        }
+
+        /// 模拟失败时记录构建阶段的错误，不重试时订单以失败结束，没有报价和发送事件
+        #[tokio::test]
+        async fn failed_simulation_records_the_build_error() {
+            let stack = MockStack::with_rpc(
+                MockRpc::failing_simulation(
+                    "InstructionError(3, Custom(6001))",
+                    &["Program log: AnchorError occurred. Error Code: SlippageToleranceExceeded."],
+                ),
+                150.0,
+            );
+            let mut config = mock_config();
+            config.retry_policy.max_retries = 0;
+            let book = Mutex::new(stack.order_book(&config));
+            let order_id =
+                OrderBook::place_order(&book, wallet_secret(1), limit_leg(200.0, 1_000_000_000))
+                    .await
+                    .unwrap();
+
+            stack.prices.set_price(SOL, 210.0);
+            wait_for_order(&book, order_id, |status| {
+                matches!(status, OrderStatus::Failed(_))
+            })
+            .await;
+
+            let events = audit_events(&book, order_id).await;
+            assert_eq!(kinds(&events), vec!["placed", "error", "failed"]);
+            assert_eq!(events[1].payload["stage"], "build");
+            assert!(events[1].payload["error"]
+                .as_str()
+                .unwrap()
+                .contains("SlippageToleranceExceeded"));
+            assert!(stack.rpc.sent().is_empty());
+        }
+    }
+}
@@ -0,0 +1,282 @@
+use std::{fmt, str::FromStr, time::Duration};
+
+use anyhow::{anyhow, Result};
+
+use crate::common::{
+    encode::SecretString,
+    rate_limit::{RateLimitConfig, RateLimiter},
+};
+
+/// 一个 API key 的配置
+///
+/// 环境变量 `API_KEYS` 中每一项的格式为 `名称:key[:admin][:每秒请求数]`，例如
+/// `ops:3f9c...:admin,bot:a71e...:20`。名称用于日志和限流，不会输出 key 本身。
+pub struct ApiKeyConfig {
+    pub name: String,
+    pub key: SecretString,
+    /// 是否可以调用管理接口
+    pub is_admin: bool,
+    /// 该 key 每秒允许的请求数，为 None 时只受按 IP 的全局限流
+    pub per_second: Option<f64>,
+}
+
+impl fmt::Debug for ApiKeyConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiKeyConfig")
+            .field("name", &self.name)
+            .field("key", &self.key)
+            .field("is_admin", &self.is_admin)
+            .field("per_second", &self.per_second)
+            .finish()
+    }
+}
+
+impl FromStr for ApiKeyConfig {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<ApiKeyConfig> {
+        let mut parts = s.trim().split(':');
+        let name = parts.next().unwrap_or_default().trim();
+        let key = parts.next().unwrap_or_default().trim();
+        if name.is_empty() || key.is_empty() {
+            return Err(anyhow!("API key 的格式应为 名称:key[:admin][:每秒请求数]"));
+        }
+        let mut config = ApiKeyConfig {
+            name: name.to_string(),
+            key: SecretString::new(key.to_string()),
+            is_admin: false,
+            per_second: None,
+        };
+        for option in parts.map(str::trim) {
+            if option == "admin" {
+                config.is_admin = true;
+            } else {
+                match option.parse::<f64>() {
+                    Ok(per_second) if per_second.is_finite() && per_second > 0.0 => {
+                        config.per_second = Some(per_second)
+                    }
+                    _ => return Err(anyhow!("API key {} 的选项 {} 无效", name, option)),
+                }
+            }
+        }
+        Ok(config)
+    }
+}
+
+impl ApiKeyConfig {
+    /// 从 `API_KEYS`（逗号分隔）读取，未配置时返回空列表
+    pub fn from_env() -> Result<Vec<ApiKeyConfig>> {
+        let Some(list) = std::env::var("API_KEYS")
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+        else {
+            return Ok(vec![]);
+        };
+        let keys = list
+            .split(',')
+            .filter(|entry| !entry.trim().is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<ApiKeyConfig>>>()?;
+        for (i, key) in keys.iter().enumerate() {
+            if keys[..i].iter().any(|other| other.name == key.name) {
+                return Err(anyhow!("API key 名称 {} 重复", key.name));
+            }
+        }
+        Ok(keys)
+    }
+}
+
+/// 通过认证的请求身份，由接口的请求守卫取得
+#[derive(Debug, Clone)]
+pub struct AuthContext {
+    /// API key 的名称，未启用认证时为 None
+    pub key_name: Option<String>,
+    pub is_admin: bool,
+}
+
+/// 认证失败的原因
+#[derive(Debug, Clone)]
+pub enum AuthError {
+    /// 请求未携带 API key
+    Missing,
+    /// API key 不存在
+    Invalid,
+    /// 非管理员 key 调用管理接口
+    Forbidden,
+    /// 没有配置 admin key，管理接口不可用
+    AdminDisabled,
+    /// 超过该 key 的请求频率
+    RateLimited(Duration),
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::Missing => f.write_str("缺少 API key"),
+            AuthError::Invalid => f.write_str("API key 无效"),
+            AuthError::Forbidden => f.write_str("该 API key 无权调用管理接口"),
+            AuthError::AdminDisabled => f.write_str("未配置 admin API key，管理接口不可用"),
+            AuthError::RateLimited(_) => f.write_str("请求过于频繁，请稍后重试"),
+        }
+    }
+}
+
+struct ApiKey {
+    config: ApiKeyConfig,
+    limiter: Option<RateLimiter>,
+}
+
+/// 已配置的 API key
+///
+/// 没有配置任何 key 时普通接口不需要认证，但管理接口一律拒绝，启动时会输出警告。
+pub struct ApiKeys {
+    keys: Vec<ApiKey>,
+}
+
+impl ApiKeys {
+    pub fn new(configs: Vec<ApiKeyConfig>) -> ApiKeys {
+        let keys = configs
+            .into_iter()
+            .map(|config| ApiKey {
+                limiter: config.per_second.map(|per_second| {
+                    RateLimiter::new(RateLimitConfig {
+                        per_second,
+                        burst: per_second.ceil().max(1.0) as u32,
+                        per_user: false,
+                    })
+                }),
+                config,
+            })
+            .collect();
+        ApiKeys { keys }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    /// 是否配置了至少一个 admin key，没有时管理接口不可用
+    pub fn has_admin(&self) -> bool {
+        self.keys.iter().any(|key| key.config.is_admin)
+    }
+
+    /// 校验请求携带的 key 并消耗该 key 的一个限流令牌
+    ///
+    /// 与每个 key 都做一次完整比较，耗时与匹配的位置无关。未启用认证时返回没有管理权限的身份。
+    pub fn authenticate(&self, provided: Option<&str>) -> Result<AuthContext, AuthError> {
+        if !self.is_enabled() {
+            return Ok(AuthContext {
+                key_name: None,
+                is_admin: false,
+            });
+        }
+        let provided = provided.ok_or(AuthError::Missing)?;
+        let matched = self.keys.iter().fold(None, |matched, key| {
+            if secrets_equal(key.config.key.expose(), provided) {
+                Some(key)
+            } else {
+                matched
+            }
+        });
+        let key = matched.ok_or(AuthError::Invalid)?;
+        if let Some(limiter) = &key.limiter {
+            limiter
+                .check(&key.config.name)
+                .map_err(AuthError::RateLimited)?;
+        }
+        Ok(AuthContext {
+            key_name: Some(key.config.name.clone()),
+            is_admin: key.config.is_admin,
+        })
+    }
+}
+
+/// 逐字节比较全部内容，耗时与不一致的位置无关
+fn secrets_equal(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |diff, (x, y)| diff | (x ^ y))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(list: &[&str]) -> ApiKeys {
+        ApiKeys::new(list.iter().map(|s| s.parse().unwrap()).collect())
+    }
+
+    #[test]
+    fn disabled_auth_never_grants_admin() {
+        let keys = keys(&[]);
+        assert!(!keys.has_admin());
+        let auth = keys.authenticate(None).unwrap();
+        assert_eq!(auth.key_name, None);
+        assert!(!auth.is_admin);
+        assert!(!keys.authenticate(Some("anything")).unwrap().is_admin);
+    }
+
+    #[test]
+    fn missing_key_is_rejected() {
+        let keys = keys(&["ops:secret-ops:admin"]);
+        assert!(matches!(keys.authenticate(None), Err(AuthError::Missing)));
+    }
+
+    #[test]
+    fn invalid_key_is_rejected() {
+        let keys = keys(&["ops:secret-ops:admin", "bot:secret-bot"]);
+        assert!(matches!(
+            keys.authenticate(Some("secret-other")),
+            Err(AuthError::Invalid)
+        ));
+        assert!(matches!(
+            keys.authenticate(Some("secret-op")),
+            Err(AuthError::Invalid)
+        ));
+    }
+
+    #[test]
+    fn non_admin_key_is_not_admin() {
+        let keys = keys(&["ops:secret-ops:admin", "bot:secret-bot"]);
+        let auth = keys.authenticate(Some("secret-bot")).unwrap();
+        assert_eq!(auth.key_name.as_deref(), Some("bot"));
+        assert!(!auth.is_admin);
+    }
+
+    #[test]
+    fn admin_key_is_admin() {
+        let keys = keys(&["ops:secret-ops:admin", "bot:secret-bot"]);
+        assert!(keys.has_admin());
+        let auth = keys.authenticate(Some("secret-ops")).unwrap();
+        assert_eq!(auth.key_name.as_deref(), Some("ops"));
+        assert!(auth.is_admin);
+    }
+
+    #[test]
+    fn keys_without_admin_disable_admin_routes() {
+        let keys = keys(&["bot:secret-bot", "web:secret-web:20"]);
+        assert!(keys.is_enabled());
+        assert!(!keys.has_admin());
+    }
+
+    #[test]
+    fn debug_redacts_key() {
+        let config: ApiKeyConfig = "ops:super-secret-key:admin".parse().unwrap();
+        let debug = format!("{:?}", config);
+        assert!(!debug.contains("super-secret-key"), "{}", debug);
+        assert!(debug.contains("key: [REDACTED]"), "{}", debug);
+        assert!(debug.contains("ops"));
+    }
+
+    #[test]
+    fn per_key_rate_limit_applies() {
+        let keys = keys(&["bot:secret-bot:1"]);
+        assert!(keys.authenticate(Some("secret-bot")).is_ok());
+        assert!(matches!(
+            keys.authenticate(Some("secret-bot")),
+            Err(AuthError::RateLimited(_))
+        ));
+    }
+}
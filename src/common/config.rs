@@ -0,0 +1,487 @@
+use std::{env, fmt, str::FromStr, time::Duration};
+
+use anyhow::Result;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    common::{
+        auth::ApiKeyConfig,
+        keys::KeySource,
+        nonce::NoncePool,
+        price_source::PriceSourceConfig,
+        quote_feed::QuoteFeedConfig,
+        rate_limit::RateLimitConfig,
+        retry::RetryPolicy,
+        session::DEFAULT_SESSION_TTL,
+        tax_policy::TaxPolicy,
+        token_info::TokenInfoConfig,
+        treasury::SweepConfig,
+        types::{FundingCheck, OrderLimits},
+        units::Bps,
+        utils::{BundleConfig, PrivateExecutionConfig},
+        webhook::WebhookConfig,
+    },
+    solana::{jup::QuotePolicy, multi_rpc::MultiRpcConfig, swap::TaxSide},
+};
+
+/// 服务配置，启动时从环境变量读取一次
+///
+/// 所有变量一起校验，缺失或无效的变量在 [`ConfigErrors`] 中一次性列出，而不是遇到第一个错误就退出。
+pub struct AppConfig {
+    /// RPC 节点，配置多个时按顺序故障切换
+    pub rpc: MultiRpcConfig,
+    pub jup_url: String,
+    pub jito_url: String,
+    /// 收税账户
+    pub tax_account: Pubkey,
+    /// 税收策略，默认税率以基点表示，100 => 1%
+    pub tax_policy: TaxPolicy,
+    pub tax_side: TaxSide,
+    /// 订单数据库连接串，未配置时订单只保存在内存中
+    pub database_url: Option<String>,
+    /// 数据库连接池大小
+    pub database_pool_size: u32,
+    /// 订单审计事件的保留时长，为 None 时不清理
+    pub audit_retention: Option<Duration>,
+    /// 加解密私钥使用的密钥
+    pub keys: KeySource,
+    /// 价格缓存的批量轮询间隔
+    pub price_poll_interval: Duration,
+    /// 共享 blockhash 缓存的刷新间隔
+    pub blockhash_refresh_interval: Duration,
+    /// 价格源及其回退顺序
+    pub price_sources: PriceSourceConfig,
+    /// 超过该时长的价格不用于触发订单
+    pub price_max_age: Duration,
+    /// 按成交价格触发的订单的询价间隔与限流
+    pub quote_feed: QuoteFeedConfig,
+    /// 代币符号、名称和精度的查询与缓存
+    pub token_info: TokenInfoConfig,
+    /// Jupiter 报价的重试退避与缓存
+    pub quote_policy: QuotePolicy,
+    /// 同时执行的兑换交易数上限
+    pub max_concurrent_swaps: usize,
+    /// 下单未指定滑点时使用的滑点
+    pub default_slippage_bps: Bps,
+    /// 下单未指定优先费时使用的优先费，单位为 micro-lamports / CU
+    pub default_priority_fee_micro_lamports: Option<u64>,
+    /// 订单没有优先费时按最近区块优先费的该百分位取值，未配置时不设置优先费
+    pub priority_fee_percentile: Option<u8>,
+    /// 下单未指定最大价格影响时使用的上限，未配置时不限制
+    pub default_max_price_impact_bps: Option<Bps>,
+    /// 报价隐含的成交价格相对触发价格的最大偏离，超过时不执行；未配置时不检查
+    pub max_trigger_deviation_bps: Option<Bps>,
+    pub session_ttl: Duration,
+    pub route_pin_ttl: Duration,
+    pub shutdown_timeout: Duration,
+    pub retry_policy: RetryPolicy,
+    pub bundle: BundleConfig,
+    /// 大额交换强制以 bundle 私有发送的阈值、自动 tip 和失败后的处理
+    pub private_execution: PrivateExecutionConfig,
+    pub limits: OrderLimits,
+    /// 下单时的余额检查
+    pub funding_check: FundingCheck,
+    pub webhook: WebhookConfig,
+    pub nonces: Option<NoncePool>,
+    /// 收税账户余额归集到冷钱包，未配置时只能查询余额
+    pub sweep: Option<SweepConfig>,
+    /// 下单、报价等接口的限流
+    pub rate_limit: RateLimitConfig,
+    /// 接口的 API key，未配置时不启用认证
+    pub api_keys: Vec<ApiKeyConfig>,
+    /// 是否在 `/docs` 提供 Swagger UI
+    pub swagger_ui: bool,
+}
+
+/// 配置错误，每一项对应一个缺失或无效的环境变量
+#[derive(Debug)]
+pub struct ConfigErrors(pub Vec<String>);
+
+impl fmt::Display for ConfigErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "配置错误 {} 项：", self.0.len())?;
+        for error in &self.0 {
+            write!(f, "\n  - {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigErrors {}
+
+impl AppConfig {
+    pub fn from_env() -> std::result::Result<AppConfig, ConfigErrors> {
+        let mut env = EnvReader::default();
+        let rpc = env.check("RPC_*", MultiRpcConfig::from_env());
+        let jup_url = env.required::<String>("JUP_URL");
+        let jito_url = env.required::<String>("JITO_URL");
+        let tax_account = env.required::<Pubkey>("TAX_ACCOUNT");
+        let tax_policy = env
+            .required::<u16>("TAX_BPS")
+            .and_then(|bps| env.check("TAX_BPS", Bps::new(bps)))
+            .and_then(|bps| env.check("TAX_EXEMPT_WALLETS/TAX_TIERS", TaxPolicy::from_env(bps)));
+        let tax_side = env.optional::<TaxSide>("TAX_SIDE").unwrap_or_default();
+        let database_url = env.optional::<String>("DATABASE_URL");
+        let database_pool_size = env.optional("DATABASE_POOL_SIZE").unwrap_or(10);
+        if database_pool_size == 0 {
+            env.errors.push("DATABASE_POOL_SIZE 必须大于 0".to_string());
+        }
+        let keys = env.check("AES_KEY/AES_KMS_*", KeySource::from_env());
+        let price_poll_interval = env.optional("PRICE_POLL_INTERVAL_MS").unwrap_or(800);
+        let blockhash_refresh_interval = env
+            .optional("BLOCKHASH_REFRESH_INTERVAL_MS")
+            .unwrap_or(2_000);
+        if blockhash_refresh_interval == 0 {
+            env.errors
+                .push("BLOCKHASH_REFRESH_INTERVAL_MS 必须大于 0".to_string());
+        }
+        let price_sources = env.check("PRICE_SOURCES", PriceSourceConfig::from_env());
+        let price_max_age = env.optional("PRICE_MAX_AGE_MS").unwrap_or(10_000);
+        let mut quote_feed = QuoteFeedConfig::default();
+        if let Some(interval) = env.optional("EXECUTABLE_QUOTE_INTERVAL_MS") {
+            quote_feed.interval = Duration::from_millis(interval);
+        }
+        if let Some(max_interval) = env.optional("EXECUTABLE_QUOTE_MAX_INTERVAL_MS") {
+            quote_feed.max_interval = Duration::from_millis(max_interval);
+        }
+        if let Some(far_bps) = env
+            .optional::<u16>("EXECUTABLE_QUOTE_FAR_BPS")
+            .and_then(|bps| env.check("EXECUTABLE_QUOTE_FAR_BPS", Bps::new(bps)))
+        {
+            quote_feed.far_bps = far_bps;
+        }
+        if quote_feed.interval.is_zero() {
+            env.errors
+                .push("EXECUTABLE_QUOTE_INTERVAL_MS 必须大于 0".to_string());
+        }
+        if let Some(per_second) = env.optional::<f64>("EXECUTABLE_QUOTE_PER_SEC") {
+            if per_second.is_finite() && per_second > 0.0 {
+                quote_feed.per_second = per_second;
+            } else {
+                env.errors
+                    .push("EXECUTABLE_QUOTE_PER_SEC 必须为正数".to_string());
+            }
+        }
+        if let Some(burst) = env.optional("EXECUTABLE_QUOTE_BURST") {
+            quote_feed.burst = burst;
+        }
+        let mut token_info = TokenInfoConfig::default();
+        if let Some(url) = env.optional("TOKEN_LIST_URL") {
+            token_info.url = url;
+        }
+        if let Some(ttl) = env.optional("TOKEN_INFO_TTL_SECS") {
+            token_info.ttl = Duration::from_secs(ttl);
+        }
+        let quote_policy = env.check("QUOTE_*", QuotePolicy::from_env());
+        let max_concurrent_swaps = env.optional("MAX_CONCURRENT_SWAPS").unwrap_or(32);
+        if max_concurrent_swaps == 0 {
+            env.errors
+                .push("MAX_CONCURRENT_SWAPS 必须大于 0".to_string());
+        }
+        let default_slippage_bps = env.optional::<u16>("DEFAULT_SLIPPAGE_BPS").unwrap_or(50);
+        let default_slippage_bps =
+            env.check("DEFAULT_SLIPPAGE_BPS", Bps::new(default_slippage_bps));
+        let default_priority_fee_micro_lamports =
+            env.optional::<u64>("DEFAULT_PRIORITY_FEE_MICRO_LAMPORTS");
+        let priority_fee_percentile = env.optional::<u8>("PRIORITY_FEE_PERCENTILE");
+        if priority_fee_percentile.is_some_and(|p| p > 100) {
+            env.errors
+                .push("PRIORITY_FEE_PERCENTILE 必须在 0 到 100 之间".to_string());
+        }
+        let default_max_price_impact_bps = env
+            .optional::<u16>("DEFAULT_MAX_PRICE_IMPACT_BPS")
+            .and_then(|bps| env.check("DEFAULT_MAX_PRICE_IMPACT_BPS", Bps::new(bps)));
+        let max_trigger_deviation_bps = env
+            .optional::<u16>("MAX_TRIGGER_DEVIATION_BPS")
+            .and_then(|bps| env.check("MAX_TRIGGER_DEVIATION_BPS", Bps::new(bps)));
+        let session_ttl = env
+            .optional("SESSION_TTL_SECS")
+            .unwrap_or(DEFAULT_SESSION_TTL.as_secs());
+        let route_pin_ttl = env.optional("ROUTE_PIN_TTL_SECS").unwrap_or(30);
+        let shutdown_timeout = env.optional("SHUTDOWN_TIMEOUT_SECS").unwrap_or(10);
+        let audit_retention_days: u64 = env.optional("AUDIT_RETENTION_DAYS").unwrap_or(30);
+        let retry_policy = env.check("SWAP_RETRY_*", RetryPolicy::from_env());
+        let bundle = env.check("BUNDLE_*", BundleConfig::from_env());
+        let private_execution = env.check(
+            "FORCE_JITO_ABOVE_LAMPORTS/AUTO_TIP_*/PRIVATE_FALLBACK_RPC",
+            PrivateExecutionConfig::from_env(),
+        );
+        let limits = env.check("MAX_*_ORDERS", OrderLimits::from_env());
+        let funding_check = env.optional("FUNDING_CHECK").unwrap_or_default();
+        let webhook = env.check("WEBHOOK_*", WebhookConfig::from_env());
+        let nonces = env.check("NONCE_ACCOUNTS", NoncePool::from_env());
+        let sweep = env
+            .check("TAX_ACCOUNT_PK/TAX_SIGNER_*", SweepConfig::from_env())
+            .flatten();
+        if let (Some(sweep), Some(tax_account)) = (&sweep, &tax_account) {
+            if sweep.tax_signer.pubkey() != *tax_account {
+                env.errors
+                    .push("TAX_ACCOUNT_PK 的公钥与 TAX_ACCOUNT 不一致".to_string());
+            }
+        }
+        let mut rate_limit = RateLimitConfig::default();
+        if let Some(per_second) = env.optional::<f64>("RATE_LIMIT_PER_SEC") {
+            if per_second.is_finite() && per_second >= 0.0 {
+                rate_limit.per_second = per_second;
+            } else {
+                env.errors
+                    .push("RATE_LIMIT_PER_SEC 必须为非负数".to_string());
+            }
+        }
+        if let Some(burst) = env.optional("RATE_LIMIT_BURST") {
+            rate_limit.burst = burst;
+        }
+        if let Some(per_user) = env.optional("RATE_LIMIT_PER_USER") {
+            rate_limit.per_user = per_user;
+        }
+        let api_keys = env.check("API_KEYS", ApiKeyConfig::from_env());
+        let swagger_ui = env.optional("SWAGGER_UI").unwrap_or(false);
+
+        if !env.errors.is_empty() {
+            return Err(ConfigErrors(env.errors));
+        }
+        // 没有错误时所有必填项都已读取
+        Ok(AppConfig {
+            rpc: rpc.unwrap(),
+            jup_url: jup_url.unwrap(),
+            jito_url: jito_url.unwrap(),
+            tax_account: tax_account.unwrap(),
+            tax_policy: tax_policy.unwrap(),
+            tax_side,
+            database_url,
+            database_pool_size,
+            audit_retention: (audit_retention_days > 0)
+                .then(|| Duration::from_secs(audit_retention_days * 24 * 3600)),
+            keys: keys.unwrap(),
+            price_poll_interval: Duration::from_millis(price_poll_interval),
+            blockhash_refresh_interval: Duration::from_millis(blockhash_refresh_interval),
+            price_sources: price_sources.unwrap(),
+            price_max_age: Duration::from_millis(price_max_age),
+            quote_feed,
+            token_info,
+            quote_policy: quote_policy.unwrap(),
+            max_concurrent_swaps,
+            default_slippage_bps: default_slippage_bps.unwrap(),
+            default_priority_fee_micro_lamports,
+            priority_fee_percentile,
+            default_max_price_impact_bps,
+            max_trigger_deviation_bps,
+            session_ttl: Duration::from_secs(session_ttl),
+            route_pin_ttl: Duration::from_secs(route_pin_ttl),
+            shutdown_timeout: Duration::from_secs(shutdown_timeout),
+            retry_policy: retry_policy.unwrap(),
+            bundle: bundle.unwrap(),
+            private_execution: private_execution.unwrap(),
+            limits: limits.unwrap(),
+            funding_check,
+            webhook: webhook.unwrap(),
+            nonces: nonces.unwrap(),
+            sweep,
+            rate_limit,
+            api_keys: api_keys.unwrap(),
+            swagger_ui,
+        })
+    }
+}
+
+/// 读取环境变量并收集错误，空字符串视为未配置
+#[derive(Default)]
+struct EnvReader {
+    errors: Vec<String>,
+}
+
+impl EnvReader {
+    fn var(name: &str) -> Option<String> {
+        env::var(name).ok().filter(|v| !v.trim().is_empty())
+    }
+
+    /// 必填项，缺失或无法解析时记录错误
+    fn required<T>(&mut self, name: &str) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        let Some(value) = Self::var(name) else {
+            self.errors.push(format!("缺少 {}", name));
+            return None;
+        };
+        self.parse(name, &value)
+    }
+
+    /// 可选项，未配置时返回 None；无法解析时记录错误并返回 None
+    fn optional<T>(&mut self, name: &str) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        let value = Self::var(name)?;
+        self.parse(name, &value)
+    }
+
+    fn parse<T>(&mut self, name: &str, value: &str) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        match value.trim().parse() {
+            Ok(v) => Some(v),
+            Err(e) => {
+                self.errors
+                    .push(format!("{} 无效 {:?}：{}", name, value, e));
+                None
+            }
+        }
+    }
+
+    /// 记录其他配置读取函数返回的错误
+    fn check<T>(&mut self, name: &str, result: Result<T>) -> Option<T> {
+        match result {
+            Ok(v) => Some(v),
+            Err(e) => {
+                self.errors.push(format!("{} 无效：{:#}", name, e));
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// 环境变量是进程级的，读取配置的测试依次执行
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    /// 测试会设置的变量，读取前全部清除
+    const VARS: &[&str] = &[
+        "RPC_URL",
+        "JUP_URL",
+        "JITO_URL",
+        "TAX_ACCOUNT",
+        "TAX_BPS",
+        "AES_KEY",
+        "AES_KMS_URL",
+        "DATABASE_POOL_SIZE",
+        "MAX_CONCURRENT_SWAPS",
+        "PRIORITY_FEE_PERCENTILE",
+    ];
+
+    /// 可以启动的最少配置
+    const REQUIRED: &[(&str, &str)] = &[
+        ("RPC_URL", "http://127.0.0.1:8899"),
+        ("JUP_URL", "https://quote-api.jup.ag/v6"),
+        ("JITO_URL", "https://mainnet.block-engine.jito.wtf/api/v1"),
+        ("TAX_ACCOUNT", "11111111111111111111111111111111"),
+        ("TAX_BPS", "100"),
+        ("AES_KEY", "AQIDBAUGBwgJCgsMDQ4PEBESExQVFhcYGRobHB0eHyA="),
+    ];
+
+    /// 只设置 `vars` 读取配置，读取后清除；同名变量以后出现的为准
+    fn from_vars(vars: &[(&str, &str)]) -> std::result::Result<AppConfig, ConfigErrors> {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        for name in VARS {
+            env::remove_var(name);
+        }
+        for (name, value) in vars {
+            env::set_var(name, value);
+        }
+        let config = AppConfig::from_env();
+        for (name, _) in vars {
+            env::remove_var(name);
+        }
+        config
+    }
+
+    fn with_required(
+        overrides: &[(&'static str, &'static str)],
+    ) -> Vec<(&'static str, &'static str)> {
+        let mut vars = REQUIRED.to_vec();
+        vars.extend_from_slice(overrides);
+        vars
+    }
+
+    fn assert_reports(errors: &ConfigErrors, names: &[&str]) {
+        for name in names {
+            assert!(
+                errors.0.iter().any(|error| error.contains(name)),
+                "{} 未报告：{}",
+                name,
+                errors
+            );
+        }
+    }
+
+    #[test]
+    fn required_variables_are_enough() {
+        let config = from_vars(REQUIRED).unwrap();
+        assert_eq!(config.rpc.urls, vec!["http://127.0.0.1:8899".to_string()]);
+        assert_eq!(config.database_url, None);
+        assert_eq!(config.max_concurrent_swaps, 32);
+        assert_eq!(config.session_ttl, DEFAULT_SESSION_TTL);
+    }
+
+    #[test]
+    fn empty_environment_reports_every_missing_variable() {
+        let errors = from_vars(&[]).err().unwrap();
+        assert_reports(
+            &errors,
+            &[
+                "RPC_URL",
+                "JUP_URL",
+                "JITO_URL",
+                "TAX_ACCOUNT",
+                "TAX_BPS",
+                "AES_KEY",
+            ],
+        );
+        assert_eq!(errors.0.len(), 6, "{}", errors);
+    }
+
+    /// 缺失和无效的变量一起报告，不会在第一个错误处停止
+    #[test]
+    fn invalid_values_are_reported_together() {
+        let errors = from_vars(&with_required(&[
+            ("TAX_ACCOUNT", "not-a-pubkey"),
+            ("TAX_BPS", "20000"),
+            ("DATABASE_POOL_SIZE", "0"),
+            ("MAX_CONCURRENT_SWAPS", "many"),
+            ("PRIORITY_FEE_PERCENTILE", "101"),
+        ]))
+        .err()
+        .unwrap();
+        assert_reports(
+            &errors,
+            &[
+                "TAX_ACCOUNT",
+                "TAX_BPS",
+                "DATABASE_POOL_SIZE",
+                "MAX_CONCURRENT_SWAPS",
+                "PRIORITY_FEE_PERCENTILE",
+            ],
+        );
+        assert_eq!(errors.0.len(), 5, "{}", errors);
+    }
+
+    #[test]
+    fn partial_setup_reports_only_what_is_missing() {
+        let vars: Vec<_> = REQUIRED
+            .iter()
+            .copied()
+            .filter(|(name, _)| *name != "JITO_URL" && *name != "AES_KEY")
+            .collect();
+        let errors = from_vars(&vars).err().unwrap();
+        assert_reports(&errors, &["JITO_URL", "AES_KEY"]);
+        assert_eq!(errors.0.len(), 2, "{}", errors);
+    }
+
+    /// 空白的值视为未配置
+    #[test]
+    fn blank_value_counts_as_missing() {
+        let errors = from_vars(&with_required(&[("JUP_URL", "  ")]))
+            .err()
+            .unwrap();
+        assert_eq!(errors.0, vec!["缺少 JUP_URL".to_string()]);
+    }
+}
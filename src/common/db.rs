@@ -0,0 +1,621 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use diesel::{
+    mysql::MysqlConnection,
+    prelude::*,
+    r2d2::{ConnectionManager, Pool},
+    QueryResult,
+};
+use schemars::JsonSchema;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    common::{
+        audit::{AuditEvent, AuditStore},
+        persist::{OrderStore, PersistRecord},
+        price::now_ms,
+        snapshot::{ResumeState, SuspendedOrder},
+        types::{Order, OrderStatus},
+    },
+    error::{self, LimitOrderError},
+};
+
+/// 从连接池取连接的最长等待时间
+const CHECKOUT_TIMEOUT: Duration = Duration::from_secs(3);
+
+pub type DbPool = Pool<ConnectionManager<MysqlConnection>>;
+
+/// 创建 MySQL 连接池
+///
+/// 连接在第一次使用时才建立，数据库暂时不可用不会影响服务启动。
+pub fn build_pool(database_url: &str, max_size: u32) -> DbPool {
+    let manager = ConnectionManager::<MysqlConnection>::new(database_url);
+    Pool::builder()
+        .max_size(max_size)
+        .connection_timeout(CHECKOUT_TIMEOUT)
+        .build_unchecked(manager)
+}
+
+/// 在阻塞线程池中取出连接并执行查询
+///
+/// diesel 的查询是同步的，直接在请求处理中调用会占住 Rocket 的异步工作线程；所有数据库访问都应经过这里。
+/// 取不到连接时返回 `LimitOrderError::DatabaseUnavailable`。
+pub async fn run<T, F>(pool: &DbPool, query: F) -> error::Result<T>
+where
+    F: FnOnce(&mut MysqlConnection) -> QueryResult<T> + Send + 'static,
+    T: Send + 'static,
+{
+    let pool = pool.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut conn = pool
+            .get()
+            .map_err(|e| LimitOrderError::DatabaseUnavailable(e.to_string()))?;
+        query(&mut conn).map_err(|e| LimitOrderError::Other(e.into()))
+    })
+    .await
+    .map_err(|e| LimitOrderError::Other(e.into()))?
+}
+
+diesel::table! {
+    /// 订单的最新状态，终态订单只保存在这里
+    orders (order_id) {
+        order_id -> Varchar,
+        owner -> Varchar,
+        input_mint -> Varchar,
+        output_mint -> Varchar,
+        status -> Varchar,
+        signature -> Nullable<Varchar>,
+        out_amount -> Nullable<Unsigned<Bigint>>,
+        tax_amount -> Nullable<Unsigned<Bigint>>,
+        tax_mint -> Nullable<Varchar>,
+        error -> Nullable<Text>,
+        order_json -> Text,
+        created_at -> Unsigned<Bigint>,
+        updated_at -> Unsigned<Bigint>,
+        finalized_at -> Nullable<Unsigned<Bigint>>,
+    }
+}
+
+/// `orders` 表的一行，`order_json` 为订单的完整快照
+#[derive(Debug, Clone, Queryable, Selectable, Insertable)]
+#[diesel(table_name = orders)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+struct OrderRow {
+    order_id: String,
+    owner: String,
+    input_mint: String,
+    output_mint: String,
+    status: String,
+    signature: Option<String>,
+    out_amount: Option<u64>,
+    tax_amount: Option<u64>,
+    tax_mint: Option<String>,
+    error: Option<String>,
+    order_json: String,
+    created_at: u64,
+    updated_at: u64,
+    finalized_at: Option<u64>,
+}
+
+impl OrderRow {
+    fn new(order: &Order, updated_at: u64, finalized_at: Option<u64>) -> Result<OrderRow> {
+        // 交易签名在 Triggered 状态中，成交后在 fill_signatures 中
+        let signature = match &order.status {
+            OrderStatus::Triggered { signature, .. } => Some(signature.clone()),
+            _ => order.fill_signatures.last().cloned(),
+        };
+        let error = match &order.status {
+            OrderStatus::Failed(e) | OrderStatus::Unfilled(e) => Some(e.clone()),
+            _ => None,
+        };
+        // 成交过才有输出数量和税收
+        let filled = order.tax_mint.is_some();
+        Ok(OrderRow {
+            order_id: order.order_id.to_string(),
+            owner: order.owner.clone(),
+            input_mint: order.input_mint.clone(),
+            output_mint: order.output_mint.clone(),
+            status: order.status.label().to_string(),
+            signature,
+            out_amount: filled.then_some(order.out_amount),
+            tax_amount: filled.then_some(order.tax_amount),
+            tax_mint: order.tax_mint.clone(),
+            error,
+            order_json: serde_json::to_string(order)?,
+            created_at: order.created_at,
+            updated_at,
+            finalized_at,
+        })
+    }
+}
+
+diesel::table! {
+    /// 未结束订单恢复执行所需的信息（[`ResumeState`] 的 JSON）：托管订单为加密的私钥，
+    /// 非托管订单为客户端签名的交易。订单进入终态后删除
+    order_resume (order_id) {
+        order_id -> Varchar,
+        resume_json -> Text,
+        updated_at -> Unsigned<Bigint>,
+    }
+}
+
+diesel::joinable!(order_resume -> orders (order_id));
+diesel::allow_tables_to_appear_in_same_query!(orders, order_resume);
+
+/// 以 `orders` 表实现的订单存储
+///
+/// 整行写入使用 `REPLACE INTO`，状态变更按主键更新，重复写入同一条记录的结果相同。
+/// 恢复信息单独写入 `order_resume` 表，整行写入订单时不会被覆盖，订单进入终态时一并删除。
+pub struct MysqlOrderStore {
+    pool: DbPool,
+}
+
+impl MysqlOrderStore {
+    pub fn new(pool: DbPool) -> MysqlOrderStore {
+        MysqlOrderStore { pool }
+    }
+}
+
+impl OrderStore for MysqlOrderStore {
+    fn write(&self, record: &PersistRecord) -> Result<()> {
+        let mut conn = self.pool.get()?;
+        let now = now_ms();
+        let row = match record {
+            PersistRecord::Placed(order) => OrderRow::new(order, now, None)?,
+            PersistRecord::RepeatFilled(order, signature) => OrderRow {
+                signature: Some(signature.clone()),
+                ..OrderRow::new(order, now, None)?
+            },
+            PersistRecord::Finalized(order, finalized_at) => {
+                let row = OrderRow::new(order, now, Some(*finalized_at))?;
+                diesel::replace_into(orders::table)
+                    .values(&row)
+                    .execute(&mut conn)?;
+                return forget_resume(&mut conn, &order.order_id);
+            }
+            PersistRecord::Resumable(order_id, resume) => {
+                diesel::replace_into(order_resume::table)
+                    .values((
+                        order_resume::order_id.eq(order_id.to_string()),
+                        order_resume::resume_json.eq(serde_json::to_string(resume)?),
+                        order_resume::updated_at.eq(now),
+                    ))
+                    .execute(&mut conn)?;
+                return Ok(());
+            }
+            PersistRecord::Triggered(order_id, signature) => {
+                diesel::update(orders::table.find(order_id.to_string()))
+                    .set((
+                        orders::status.eq("triggered"),
+                        orders::signature.eq(signature),
+                        orders::updated_at.eq(now),
+                    ))
+                    .execute(&mut conn)?;
+                return Ok(());
+            }
+            PersistRecord::Canceled(order_id) => {
+                return finish(&mut conn, order_id, "canceled", None, now)
+            }
+            PersistRecord::Filled(order_id) => {
+                return finish(&mut conn, order_id, "filled", None, now)
+            }
+            PersistRecord::PartiallyFilled(order_id, _) => {
+                return finish(&mut conn, order_id, "partially_filled", None, now)
+            }
+            PersistRecord::Failed(order_id, e) => {
+                return finish(&mut conn, order_id, "failed", Some(e.as_str()), now)
+            }
+        };
+        diesel::replace_into(orders::table)
+            .values(&row)
+            .execute(&mut conn)?;
+        Ok(())
+    }
+
+    fn ping(&self) -> bool {
+        self.pool
+            .get()
+            .is_ok_and(|mut conn| diesel::sql_query("SELECT 1").execute(&mut conn).is_ok())
+    }
+}
+
+/// 只更新终态，用于回放旧版本日志中的状态记录
+fn finish(
+    conn: &mut MysqlConnection,
+    order_id: &Uuid,
+    status: &str,
+    error: Option<&str>,
+    now: u64,
+) -> Result<()> {
+    diesel::update(orders::table.find(order_id.to_string()))
+        .set((
+            orders::status.eq(status),
+            orders::error.eq(error),
+            orders::updated_at.eq(now),
+            orders::finalized_at.eq(now),
+        ))
+        .execute(conn)?;
+    forget_resume(conn, order_id)
+}
+
+/// 删除已结束订单的恢复信息，托管订单的加密私钥不再保留
+fn forget_resume(conn: &mut MysqlConnection, order_id: &Uuid) -> Result<()> {
+    diesel::delete(order_resume::table.find(order_id.to_string())).execute(conn)?;
+    Ok(())
+}
+
+/// 有恢复信息且尚未结束的订单，订单快照取自 `orders` 表，按下单时间排序
+///
+/// 进程意外退出时来不及保存快照，启动时据此恢复订单任务，见 [`crate::common::types::OrderBook::restore_unfinished`]。
+pub async fn unfinished_orders(pool: &DbPool) -> error::Result<Vec<SuspendedOrder>> {
+    let rows = run(pool, |conn| {
+        order_resume::table
+            .inner_join(orders::table)
+            .filter(orders::finalized_at.is_null())
+            .order(orders::created_at.asc())
+            .select((orders::order_json, order_resume::resume_json))
+            .load::<(String, String)>(conn)
+    })
+    .await?;
+    rows.iter()
+        .map(|(order, resume)| {
+            Ok(SuspendedOrder {
+                order: serde_json::from_str(order).map_err(|e| LimitOrderError::Other(e.into()))?,
+                resume: serde_json::from_str::<ResumeState>(resume)
+                    .map_err(|e| LimitOrderError::Other(e.into()))?,
+            })
+        })
+        .collect()
+}
+
+diesel::table! {
+    /// 执行日志：托管订单发送交换交易前写入的意图，交易确认或确定未上链后标记为已解决
+    execution_intents (attempt_id) {
+        attempt_id -> Varchar,
+        order_id -> Varchar,
+        signature -> Varchar,
+        blockhash -> Varchar,
+        last_valid_block_height -> Unsigned<Bigint>,
+        created_at -> Unsigned<Bigint>,
+        resolved_at -> Nullable<Unsigned<Bigint>>,
+    }
+}
+
+/// 一次发送交换交易的意图，`signature` 取自已签名的交易，发送前即可确定
+#[derive(Debug, Clone, Queryable, Selectable, Insertable)]
+#[diesel(table_name = execution_intents)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct ExecutionIntent {
+    pub attempt_id: String,
+    pub order_id: String,
+    pub signature: String,
+    pub blockhash: String,
+    /// 区块高度超过该值后交易不会再上链
+    pub last_valid_block_height: u64,
+    pub created_at: u64,
+    pub resolved_at: Option<u64>,
+}
+
+impl ExecutionIntent {
+    pub fn new(
+        order_id: Uuid,
+        signature: String,
+        blockhash: String,
+        last_valid_block_height: u64,
+    ) -> ExecutionIntent {
+        ExecutionIntent {
+            attempt_id: Uuid::new_v4().to_string(),
+            order_id: order_id.to_string(),
+            signature,
+            blockhash,
+            last_valid_block_height,
+            created_at: now_ms(),
+            resolved_at: None,
+        }
+    }
+}
+
+/// 写入执行意图，必须在发送交易前完成
+pub async fn record_intent(pool: &DbPool, intent: ExecutionIntent) -> error::Result<()> {
+    run(pool, move |conn| {
+        diesel::insert_into(execution_intents::table)
+            .values(&intent)
+            .execute(conn)
+    })
+    .await?;
+    Ok(())
+}
+
+/// 将执行意图标记为已解决，重复标记不影响结果
+pub async fn resolve_intent(pool: &DbPool, attempt_id: String) -> error::Result<()> {
+    run(pool, move |conn| {
+        diesel::update(execution_intents::table.find(attempt_id))
+            .filter(execution_intents::resolved_at.is_null())
+            .set(execution_intents::resolved_at.eq(now_ms()))
+            .execute(conn)
+    })
+    .await?;
+    Ok(())
+}
+
+/// 未解决的执行意图，按写入时间排序
+pub async fn outstanding_intents(pool: &DbPool) -> error::Result<Vec<ExecutionIntent>> {
+    run(pool, |conn| {
+        execution_intents::table
+            .filter(execution_intents::resolved_at.is_null())
+            .order(execution_intents::created_at.asc())
+            .select(ExecutionIntent::as_select())
+            .load(conn)
+    })
+    .await
+}
+
+diesel::table! {
+    /// 订单的审计日志，每笔订单的事件按 seq 递增
+    order_events (order_id, seq) {
+        order_id -> Varchar,
+        seq -> Unsigned<Bigint>,
+        created_at -> Unsigned<Bigint>,
+        kind -> Varchar,
+        payload -> Text,
+    }
+}
+
+/// `order_events` 表的一行，`payload` 为事件内容的 JSON
+#[derive(Debug, Clone, Queryable, Selectable, Insertable)]
+#[diesel(table_name = order_events)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+struct OrderEventRow {
+    order_id: String,
+    seq: u64,
+    created_at: u64,
+    kind: String,
+    payload: String,
+}
+
+impl OrderEventRow {
+    fn into_event(self) -> Result<AuditEvent> {
+        Ok(AuditEvent {
+            order_id: self.order_id.parse()?,
+            seq: self.seq,
+            created_at: self.created_at,
+            kind: self.kind,
+            payload: serde_json::from_str(&self.payload)?,
+        })
+    }
+}
+
+/// 以 `order_events` 表实现的审计日志存储，按 (order_id, seq) 整行写入，重复写入的结果相同
+pub struct MysqlAuditStore {
+    pool: DbPool,
+}
+
+impl MysqlAuditStore {
+    pub fn new(pool: DbPool) -> MysqlAuditStore {
+        MysqlAuditStore { pool }
+    }
+}
+
+impl AuditStore for MysqlAuditStore {
+    fn append(&self, event: &AuditEvent) -> Result<()> {
+        let row = OrderEventRow {
+            order_id: event.order_id.to_string(),
+            seq: event.seq,
+            created_at: event.created_at,
+            kind: event.kind.clone(),
+            payload: serde_json::to_string(&event.payload)?,
+        };
+        diesel::replace_into(order_events::table)
+            .values(&row)
+            .execute(&mut self.pool.get()?)?;
+        Ok(())
+    }
+
+    fn last_seq(&self, order_id: Uuid) -> Result<u64> {
+        let seq: Option<u64> = order_events::table
+            .filter(order_events::order_id.eq(order_id.to_string()))
+            .select(diesel::dsl::max(order_events::seq))
+            .first(&mut self.pool.get()?)?;
+        Ok(seq.unwrap_or(0))
+    }
+
+    fn events(&self, order_id: Uuid) -> Result<Vec<AuditEvent>> {
+        order_events::table
+            .filter(order_events::order_id.eq(order_id.to_string()))
+            .order(order_events::seq.asc())
+            .select(OrderEventRow::as_select())
+            .load(&mut self.pool.get()?)?
+            .into_iter()
+            .map(OrderEventRow::into_event)
+            .collect()
+    }
+
+    fn prune(&self, before_ms: u64) -> Result<usize> {
+        Ok(
+            diesel::delete(order_events::table.filter(order_events::created_at.lt(before_ms)))
+                .execute(&mut self.pool.get()?)?,
+        )
+    }
+}
+
+/// 历史查询每页的默认条数
+pub const DEFAULT_HISTORY_LIMIT: usize = 50;
+/// 历史查询每页的最大条数
+pub const MAX_HISTORY_LIMIT: usize = 200;
+
+/// 终态订单的查询条件
+#[derive(Debug, Clone, Default)]
+pub struct HistoryQuery {
+    /// 下单钱包
+    pub owner: Option<String>,
+    /// 终态名称，见 [`OrderStatus::TERMINAL`]，为 None 时返回全部终态
+    pub status: Option<String>,
+    /// 输入或输出代币
+    pub mint: Option<String>,
+    pub limit: usize,
+    pub cursor: Option<HistoryCursor>,
+}
+
+/// 分页游标：上一页最后一笔订单的下单时间和订单 ID
+///
+/// 按 `(created_at, order_id)` 倒序分页，下单时间相同的订单以订单 ID 区分，翻页时不会重复或遗漏。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryCursor {
+    pub created_at: u64,
+    pub order_id: Uuid,
+}
+
+impl HistoryCursor {
+    /// 编码为 `<created_at>_<order_id>`
+    pub fn encode(&self) -> String {
+        format!("{}_{}", self.created_at, self.order_id)
+    }
+
+    pub fn parse(cursor: &str) -> Option<HistoryCursor> {
+        let (created_at, order_id) = cursor.split_once('_')?;
+        Some(HistoryCursor {
+            created_at: created_at.parse().ok()?,
+            order_id: order_id.parse().ok()?,
+        })
+    }
+}
+
+/// 一笔终态订单
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct OrderHistoryEntry {
+    pub order_id: String,
+    pub owner: String,
+    pub input_mint: String,
+    pub output_mint: String,
+    /// 输入、输出代币的符号，查不到时为 None
+    pub input_symbol: Option<String>,
+    pub output_symbol: Option<String>,
+    /// 按代币精度换算后的订单数量，`ExactIn` 以输入代币计价，`ExactOut` 以输出代币计价
+    pub ui_amount: Option<String>,
+    pub status: String,
+    /// 最后一笔成交的交易签名
+    pub signature: Option<String>,
+    /// 扣税后至少得到的输出数量（按报价的滑点下限计算），未成交时为 None
+    pub out_amount: Option<u64>,
+    /// 收取的税收，以 `tax_mint` 计价
+    pub tax_amount: Option<u64>,
+    pub tax_mint: Option<String>,
+    /// 失败原因
+    pub error: Option<String>,
+    /// 下单时间（毫秒时间戳）
+    pub created_at: u64,
+    /// 进入终态的时间（毫秒时间戳）
+    pub finalized_at: Option<u64>,
+    /// 订单的完整快照
+    pub order: Order,
+}
+
+/// 一页历史订单，`next_cursor` 为 None 时已经是最后一页
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct OrderHistoryPage {
+    pub orders: Vec<OrderHistoryEntry>,
+    pub next_cursor: Option<String>,
+}
+
+/// 按下单时间倒序查询终态订单
+pub async fn order_history(pool: &DbPool, query: HistoryQuery) -> error::Result<OrderHistoryPage> {
+    let limit = query.limit;
+    let rows = run(pool, move |conn| {
+        let mut select = orders::table.into_boxed();
+        select = match query.status {
+            Some(status) => select.filter(orders::status.eq(status)),
+            None => select.filter(orders::status.eq_any(OrderStatus::TERMINAL)),
+        };
+        if let Some(owner) = query.owner {
+            select = select.filter(orders::owner.eq(owner));
+        }
+        if let Some(mint) = query.mint {
+            select = select.filter(
+                orders::input_mint
+                    .eq(mint.clone())
+                    .or(orders::output_mint.eq(mint)),
+            );
+        }
+        if let Some(cursor) = query.cursor {
+            select = select.filter(
+                orders::created_at
+                    .lt(cursor.created_at)
+                    .or(orders::created_at
+                        .eq(cursor.created_at)
+                        .and(orders::order_id.lt(cursor.order_id.to_string()))),
+            );
+        }
+        // 多取一条判断是否还有下一页
+        select
+            .order((orders::created_at.desc(), orders::order_id.desc()))
+            .limit(limit as i64 + 1)
+            .select(OrderRow::as_select())
+            .load(conn)
+    })
+    .await?;
+    paginate(rows, limit)
+}
+
+/// 按订单 ID 查询订单的最新快照，不存在的订单不在结果中
+pub async fn orders_by_id(pool: &DbPool, order_ids: Vec<Uuid>) -> error::Result<Vec<Order>> {
+    let order_ids: Vec<String> = order_ids.iter().map(Uuid::to_string).collect();
+    let rows = run(pool, move |conn| {
+        orders::table
+            .filter(orders::order_id.eq_any(order_ids))
+            .select(orders::order_json)
+            .load::<String>(conn)
+    })
+    .await?;
+    rows.iter()
+        .map(|json| serde_json::from_str(json).map_err(|e| LimitOrderError::Other(e.into())))
+        .collect()
+}
+
+/// 截取一页，取到的行数超过 `limit` 时以本页最后一行生成下一页的游标
+fn paginate(mut rows: Vec<OrderRow>, limit: usize) -> error::Result<OrderHistoryPage> {
+    let next_cursor = if rows.len() > limit {
+        rows.truncate(limit);
+        rows.last().and_then(|row| {
+            Some(
+                HistoryCursor {
+                    created_at: row.created_at,
+                    order_id: row.order_id.parse().ok()?,
+                }
+                .encode(),
+            )
+        })
+    } else {
+        None
+    };
+    let orders = rows
+        .into_iter()
+        .map(|row| {
+            Ok(OrderHistoryEntry {
+                order: serde_json::from_str(&row.order_json)
+                    .map_err(|e| LimitOrderError::Other(e.into()))?,
+                order_id: row.order_id,
+                owner: row.owner,
+                input_mint: row.input_mint,
+                output_mint: row.output_mint,
+                // 代币信息由接口层补充
+                input_symbol: None,
+                output_symbol: None,
+                ui_amount: None,
+                status: row.status,
+                signature: row.signature,
+                out_amount: row.out_amount,
+                tax_amount: row.tax_amount,
+                tax_mint: row.tax_mint,
+                error: row.error,
+                created_at: row.created_at,
+                finalized_at: row.finalized_at,
+            })
+        })
+        .collect::<error::Result<Vec<_>>>()?;
+    Ok(OrderHistoryPage {
+        orders,
+        next_cursor,
+    })
+}
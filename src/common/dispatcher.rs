@@ -0,0 +1,629 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use tokio::sync::{oneshot, watch};
+use uuid::Uuid;
+
+use crate::common::{
+    events::{EventBus, OrderEvent, OrderEventKind},
+    price::{PriceCache, PriceSnapshot, MAX_PRICE_MISSES},
+    types::{price_ratio, Order, OrderKind, TriggerCondition, TriggerOn, PRICE_EVENT_INTERVAL},
+};
+
+/// 触发判断使用的价格
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PriceKey {
+    /// 单个代币的 USD 价格
+    Usd(String),
+    /// 输入代币 USD 价格 / 输出代币 USD 价格
+    Ratio { input: String, output: String },
+}
+
+impl PriceKey {
+    /// 从同一轮快照中取得价格，两个代币的比值不会混用不同轮次的价格；超过 `max_age` 的价格不可用
+    fn price(&self, snapshot: &PriceSnapshot, max_age: Duration) -> Option<f64> {
+        let fresh = |mint: &str| snapshot.prices.get(mint)?.fresh_price(max_age);
+        let price = match self {
+            PriceKey::Usd(mint) => fresh(mint)?,
+            PriceKey::Ratio { input, output } => price_ratio(fresh(input)?, fresh(output)?).ok()?,
+        };
+        Some(price).filter(|price| price.is_finite())
+    }
+
+    /// 价格源连续多轮未返回的代币，见 [`PriceSubscription::lookup`](crate::common::price::PriceSubscription::lookup)
+    fn unsupported_mint(&self, snapshot: &PriceSnapshot) -> Option<&str> {
+        let missing = |mint: &&String| {
+            snapshot.misses.get(mint.as_str()).copied().unwrap_or(0) >= MAX_PRICE_MISSES
+        };
+        match self {
+            PriceKey::Usd(mint) => Some(mint).filter(missing),
+            PriceKey::Ratio { input, output } => [input, output].into_iter().find(missing),
+        }
+        .map(String::as_str)
+    }
+}
+
+/// 固定触发价格的订单在价格索引中的位置
+#[derive(Debug, Clone, PartialEq)]
+pub struct TriggerLevel {
+    pub key: PriceKey,
+    pub condition: TriggerCondition,
+    pub price: f64,
+}
+
+impl TriggerLevel {
+    /// 限价、止盈和止损订单按 USD 价格或比值触发时返回触发位置
+    ///
+    /// 跟踪止损的触发价格随最高价格变化，按成交价格触发的订单各自询价，都返回 None，仍由订单任务逐轮判断。
+    pub fn of(order: &Order) -> Option<TriggerLevel> {
+        let condition = match order.kind {
+            OrderKind::Limit => order.trigger_condition?,
+            OrderKind::TakeProfit => TriggerCondition::Above,
+            OrderKind::StopLoss => TriggerCondition::Below,
+            OrderKind::TrailingStop { .. } => return None,
+        };
+        let key = match order.trigger_on {
+            TriggerOn::InputUsd => PriceKey::Usd(order.input_mint.clone()),
+            TriggerOn::OutputUsd => PriceKey::Usd(order.output_mint.clone()),
+            TriggerOn::Ratio => PriceKey::Ratio {
+                input: order.input_mint.clone(),
+                output: order.output_mint.clone(),
+            },
+            TriggerOn::ExecutablePrice => return None,
+        };
+        Some(TriggerLevel {
+            key,
+            condition,
+            price: order.price as f64,
+        })
+    }
+
+    /// 与 [`price_triggered`](crate::common::types::price_triggered) 的判断一致
+    fn crossed(&self, price: f64) -> bool {
+        match self.condition {
+            TriggerCondition::Above => price >= self.price,
+            TriggerCondition::Below => price <= self.price,
+        }
+    }
+}
+
+/// 分发器唤醒订单时的价格
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Trigger {
+    pub price: f64,
+    /// 价格所在的轮次，订单回到监控时传给 [`TriggerDispatcher::wait`]，不会用同一轮价格再次触发
+    pub round: u64,
+}
+
+/// 按 [`f64::total_cmp`] 排序的触发价格
+#[derive(Debug, Clone, Copy)]
+struct Level(f64);
+
+impl PartialEq for Level {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Level {}
+
+impl PartialOrd for Level {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Level {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+struct Waiter {
+    owner: String,
+    tx: oneshot::Sender<Result<Trigger>>,
+}
+
+type Side = BTreeMap<Level, HashMap<Uuid, Waiter>>;
+
+/// 同一价格下等待触发的订单，按触发方向分开并按触发价格排序
+#[derive(Default)]
+struct Levels {
+    /// 价格涨到触发价格及以上时触发
+    above: Side,
+    /// 价格跌到触发价格及以下时触发
+    below: Side,
+}
+
+impl Levels {
+    fn side(&mut self, condition: TriggerCondition) -> &mut Side {
+        match condition {
+            TriggerCondition::Above => &mut self.above,
+            TriggerCondition::Below => &mut self.below,
+        }
+    }
+
+    /// 取出价格越过的全部订单，只访问越过的价格区间
+    fn take_crossed(&mut self, price: f64) -> Vec<Waiter> {
+        let above: Vec<Level> = self
+            .above
+            .range(..=Level(price))
+            .map(|(level, _)| *level)
+            .collect();
+        let below: Vec<Level> = self
+            .below
+            .range(Level(price)..)
+            .map(|(level, _)| *level)
+            .collect();
+        let mut crossed = vec![];
+        for level in above {
+            crossed.extend(
+                self.above
+                    .remove(&level)
+                    .into_iter()
+                    .flat_map(|w| w.into_values()),
+            );
+        }
+        for level in below {
+            crossed.extend(
+                self.below
+                    .remove(&level)
+                    .into_iter()
+                    .flat_map(|w| w.into_values()),
+            );
+        }
+        crossed
+    }
+
+    fn drain(&mut self) -> impl Iterator<Item = Waiter> {
+        std::mem::take(&mut self.above)
+            .into_values()
+            .chain(std::mem::take(&mut self.below).into_values())
+            .flat_map(|waiters| waiters.into_values())
+    }
+
+    fn waiters(&self) -> impl Iterator<Item = (&Uuid, &Waiter)> {
+        self.above.values().chain(self.below.values()).flatten()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.above.is_empty() && self.below.is_empty()
+    }
+}
+
+#[derive(Default)]
+struct Book {
+    keys: HashMap<PriceKey, Levels>,
+    /// 已分发的价格轮次
+    round: u64,
+    latest: Arc<PriceSnapshot>,
+    /// 价格缓存已停止，不再接受等待
+    closed: bool,
+}
+
+impl Book {
+    fn remove(&mut self, level: &TriggerLevel, order_id: &Uuid) {
+        let Some(levels) = self.keys.get_mut(&level.key) else {
+            return;
+        };
+        let side = levels.side(level.condition);
+        if let Some(waiters) = side.get_mut(&Level(level.price)) {
+            waiters.remove(order_id);
+            if waiters.is_empty() {
+                side.remove(&Level(level.price));
+            }
+        }
+        if levels.is_empty() {
+            self.keys.remove(&level.key);
+        }
+    }
+
+    /// 按新一轮价格唤醒越过触发价格的订单；`events` 不为空时同时推送所有等待订单的价格
+    fn dispatch(
+        &mut self,
+        snapshot: Arc<PriceSnapshot>,
+        max_age: Duration,
+        events: Option<&EventBus>,
+    ) {
+        self.round += 1;
+        let round = self.round;
+        self.keys.retain(|key, levels| {
+            if let Some(mint) = key.unsupported_mint(&snapshot) {
+                for waiter in levels.drain() {
+                    let _ = waiter.tx.send(Err(unsupported(mint)));
+                }
+                return false;
+            }
+            let Some(price) = key.price(&snapshot, max_age) else {
+                return true;
+            };
+            if let Some(events) = events {
+                for (order_id, waiter) in levels.waiters() {
+                    events.publish(OrderEvent {
+                        order_id: *order_id,
+                        owner: waiter.owner.clone(),
+                        kind: OrderEventKind::PriceUpdate { price },
+                    });
+                }
+            }
+            for waiter in levels.take_crossed(price) {
+                // 订单已撤销时接收端已释放，忽略发送失败
+                let _ = waiter.tx.send(Ok(Trigger { price, round }));
+            }
+            !levels.is_empty()
+        });
+        self.latest = snapshot;
+    }
+
+    fn close(&mut self) {
+        self.closed = true;
+        for (_, mut levels) in self.keys.drain() {
+            for waiter in levels.drain() {
+                let _ = waiter.tx.send(Err(anyhow!("价格缓存已停止")));
+            }
+        }
+    }
+}
+
+fn unsupported(mint: &str) -> anyhow::Error {
+    anyhow!("价格源不支持代币 {}", mint)
+}
+
+/// 固定触发价格订单的触发分发
+///
+/// 等待触发的订单按价格（单个代币的 USD 价格或代币对的比值）索引，同一价格下按触发方向和触发价格排序。
+/// 一个后台任务接收价格缓存的每一轮刷新，只访问价格越过的区间并唤醒其中的订单，
+/// 每轮的开销与越过触发价格的订单数成正比，而不是与等待中的订单总数成正比。
+/// 订单的价格事件由分发器统一推送，每 [`PRICE_EVENT_INTERVAL`] 最多一次。
+#[derive(Clone)]
+pub struct TriggerDispatcher {
+    book: Arc<Mutex<Book>>,
+    max_age: Duration,
+}
+
+impl TriggerDispatcher {
+    /// 启动分发任务，超过 `max_age` 的价格不用于触发
+    pub fn spawn(prices: &PriceCache, max_age: Duration, events: EventBus) -> TriggerDispatcher {
+        TriggerDispatcher::from_snapshots(prices.snapshots(), max_age, Some(events))
+    }
+
+    fn from_snapshots(
+        mut rx: watch::Receiver<Arc<PriceSnapshot>>,
+        max_age: Duration,
+        events: Option<EventBus>,
+    ) -> TriggerDispatcher {
+        let book = Arc::new(Mutex::new(Book::default()));
+        let dispatcher = TriggerDispatcher {
+            book: book.clone(),
+            max_age,
+        };
+        tokio::spawn(async move {
+            let mut last_events: Option<Instant> = None;
+            while rx.changed().await.is_ok() {
+                let snapshot = rx.borrow_and_update().clone();
+                let publish = events.as_ref().filter(|_| {
+                    last_events.is_none_or(|last| last.elapsed() >= PRICE_EVENT_INTERVAL)
+                });
+                if publish.is_some() {
+                    last_events = Some(Instant::now());
+                }
+                book.lock().unwrap().dispatch(snapshot, max_age, publish);
+            }
+            book.lock().unwrap().close();
+        });
+        dispatcher
+    }
+
+    /// 等待价格越过 `level`，返回触发时的价格
+    ///
+    /// 只使用 `after_round` 之后的价格轮次：新订单传 0，最近一轮价格已越过时立即返回；
+    /// 触发后回到监控的订单传入上次的 [`Trigger::round`]，等待下一轮价格。
+    /// 返回的 future 被丢弃（撤单、停机）时订单从索引中移除。
+    /// 价格源连续多轮未返回所需代币时返回错误。
+    pub async fn wait(
+        &self,
+        level: &TriggerLevel,
+        order_id: Uuid,
+        owner: &str,
+        after_round: u64,
+    ) -> Result<Trigger> {
+        let (rx, _registration) = {
+            let mut book = self.book.lock().unwrap();
+            if book.closed {
+                return Err(anyhow!("价格缓存已停止"));
+            }
+            if book.round > after_round {
+                if let Some(mint) = level.key.unsupported_mint(&book.latest) {
+                    return Err(unsupported(mint));
+                }
+                let price = level.key.price(&book.latest, self.max_age);
+                if let Some(price) = price.filter(|price| level.crossed(*price)) {
+                    return Ok(Trigger {
+                        price,
+                        round: book.round,
+                    });
+                }
+            }
+            let (tx, rx) = oneshot::channel();
+            book.keys
+                .entry(level.key.clone())
+                .or_default()
+                .side(level.condition)
+                .entry(Level(level.price))
+                .or_default()
+                .insert(
+                    order_id,
+                    Waiter {
+                        owner: owner.to_string(),
+                        tx,
+                    },
+                );
+            let registration = Registration {
+                book: self.book.clone(),
+                level: level.clone(),
+                order_id,
+            };
+            (rx, registration)
+        };
+        rx.await.unwrap_or_else(|_| Err(anyhow!("价格缓存已停止")))
+    }
+
+    /// 等待触发的订单数
+    pub fn len(&self) -> usize {
+        let book = self.book.lock().unwrap();
+        book.keys
+            .values()
+            .map(|levels| levels.waiters().count())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.book.lock().unwrap().keys.is_empty()
+    }
+}
+
+/// 等待中的订单，释放时从索引中移除；已被唤醒的订单已不在索引中
+struct Registration {
+    book: Arc<Mutex<Book>>,
+    level: TriggerLevel,
+    order_id: Uuid,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.book
+            .lock()
+            .unwrap()
+            .remove(&self.level, &self.order_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::*;
+    use crate::common::{price::now_ms, price_source::PriceQuote};
+
+    fn quote(price: f64) -> PriceQuote {
+        PriceQuote {
+            price,
+            confidence: None,
+            confidence_level: None,
+            last_swap_price: None,
+            timestamp_ms: now_ms(),
+            source: "test",
+        }
+    }
+
+    fn snapshot(prices: &[(&str, f64)]) -> Arc<PriceSnapshot> {
+        Arc::new(PriceSnapshot {
+            prices: prices
+                .iter()
+                .map(|(mint, price)| (mint.to_string(), quote(*price)))
+                .collect(),
+            misses: HashMap::new(),
+        })
+    }
+
+    fn level(mint: &str, condition: TriggerCondition, price: f64) -> TriggerLevel {
+        TriggerLevel {
+            key: PriceKey::Usd(mint.to_string()),
+            condition,
+            price,
+        }
+    }
+
+    fn dispatcher() -> (watch::Sender<Arc<PriceSnapshot>>, TriggerDispatcher) {
+        let (tx, rx) = watch::channel(Arc::new(PriceSnapshot::default()));
+        let dispatcher = TriggerDispatcher::from_snapshots(rx, Duration::from_secs(60), None);
+        (tx, dispatcher)
+    }
+
+    /// 在后台等待触发，返回等待任务
+    fn spawn_wait(
+        dispatcher: &TriggerDispatcher,
+        level: TriggerLevel,
+    ) -> tokio::task::JoinHandle<Result<Trigger>> {
+        let dispatcher = dispatcher.clone();
+        tokio::spawn(async move { dispatcher.wait(&level, Uuid::new_v4(), "owner", 0).await })
+    }
+
+    async fn settle() {
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn wakes_only_the_crossed_range() {
+        let (tx, dispatcher) = dispatcher();
+        let mint = Pubkey::new_unique().to_string();
+        let above: Vec<_> = [1.0, 2.0, 3.0]
+            .into_iter()
+            .map(|price| spawn_wait(&dispatcher, level(&mint, TriggerCondition::Above, price)))
+            .collect();
+        let below: Vec<_> = [1.5, 2.5]
+            .into_iter()
+            .map(|price| spawn_wait(&dispatcher, level(&mint, TriggerCondition::Below, price)))
+            .collect();
+        settle().await;
+        assert_eq!(dispatcher.len(), 5);
+
+        tx.send(snapshot(&[(&mint, 2.0)])).unwrap();
+        settle().await;
+        // 达到触发价格即触发：上涨到 1.0、2.0，下跌到 2.5
+        assert!(above[0].is_finished() && above[1].is_finished());
+        assert!(!above[2].is_finished());
+        assert!(below[1].is_finished());
+        assert!(!below[0].is_finished());
+        assert_eq!(dispatcher.len(), 2);
+
+        let [first, second, third]: [_; 3] = above.try_into().unwrap();
+        assert_eq!(
+            first.await.unwrap().unwrap(),
+            Trigger {
+                price: 2.0,
+                round: 1
+            }
+        );
+        assert_eq!(second.await.unwrap().unwrap().price, 2.0);
+        third.abort();
+    }
+
+    #[tokio::test]
+    async fn dropped_wait_leaves_the_index() {
+        let (_tx, dispatcher) = dispatcher();
+        let mint = Pubkey::new_unique().to_string();
+        let task = spawn_wait(&dispatcher, level(&mint, TriggerCondition::Above, 1.0));
+        settle().await;
+        assert_eq!(dispatcher.len(), 1);
+        task.abort();
+        settle().await;
+        assert!(dispatcher.is_empty());
+    }
+
+    #[tokio::test]
+    async fn later_round_is_needed_after_a_trigger() {
+        let (tx, dispatcher) = dispatcher();
+        let mint = Pubkey::new_unique().to_string();
+        tx.send(snapshot(&[(&mint, 5.0)])).unwrap();
+        settle().await;
+
+        // 新订单使用最近一轮已越过的价格立即触发
+        let level = level(&mint, TriggerCondition::Above, 4.0);
+        let trigger = dispatcher
+            .wait(&level, Uuid::new_v4(), "owner", 0)
+            .await
+            .unwrap();
+        assert_eq!(
+            trigger,
+            Trigger {
+                price: 5.0,
+                round: 1
+            }
+        );
+
+        // 回到监控后等待下一轮价格
+        let task = {
+            let dispatcher = dispatcher.clone();
+            let level = level.clone();
+            tokio::spawn(async move {
+                dispatcher
+                    .wait(&level, Uuid::new_v4(), "owner", trigger.round)
+                    .await
+            })
+        };
+        settle().await;
+        assert!(!task.is_finished());
+        tx.send(snapshot(&[(&mint, 4.5)])).unwrap();
+        assert_eq!(
+            task.await.unwrap().unwrap(),
+            Trigger {
+                price: 4.5,
+                round: 2
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn ratio_uses_both_prices_from_one_round() {
+        let (tx, dispatcher) = dispatcher();
+        let (input, output) = (
+            Pubkey::new_unique().to_string(),
+            Pubkey::new_unique().to_string(),
+        );
+        let level = TriggerLevel {
+            key: PriceKey::Ratio {
+                input: input.clone(),
+                output: output.clone(),
+            },
+            condition: TriggerCondition::Above,
+            price: 150.0,
+        };
+        let task = spawn_wait(&dispatcher, level);
+        settle().await;
+        // 只有输入代币的价格时无法计算比值
+        tx.send(snapshot(&[(&input, 300.0)])).unwrap();
+        settle().await;
+        assert!(!task.is_finished());
+        tx.send(snapshot(&[(&input, 300.0), (&output, 2.0)]))
+            .unwrap();
+        assert_eq!(task.await.unwrap().unwrap().price, 150.0);
+    }
+
+    #[tokio::test]
+    async fn stale_price_does_not_trigger() {
+        let (tx, dispatcher) = dispatcher();
+        let mint = Pubkey::new_unique().to_string();
+        let task = spawn_wait(&dispatcher, level(&mint, TriggerCondition::Below, 1.0));
+        settle().await;
+        let mut stale = quote(0.5);
+        stale.timestamp_ms -= 120_000;
+        tx.send(Arc::new(PriceSnapshot {
+            prices: HashMap::from([(mint.clone(), stale)]),
+            misses: HashMap::new(),
+        }))
+        .unwrap();
+        settle().await;
+        assert!(!task.is_finished());
+        assert_eq!(dispatcher.len(), 1);
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn unsupported_mint_fails_waiters() {
+        let (tx, dispatcher) = dispatcher();
+        let mint = Pubkey::new_unique().to_string();
+        let task = spawn_wait(&dispatcher, level(&mint, TriggerCondition::Above, 1.0));
+        settle().await;
+        tx.send(Arc::new(PriceSnapshot {
+            prices: HashMap::new(),
+            misses: HashMap::from([(mint.clone(), MAX_PRICE_MISSES)]),
+        }))
+        .unwrap();
+        let e = task.await.unwrap().unwrap_err();
+        assert!(e.to_string().contains("价格源不支持代币"));
+        assert!(dispatcher.is_empty());
+    }
+
+    #[tokio::test]
+    async fn stopped_cache_fails_waiters() {
+        let (tx, dispatcher) = dispatcher();
+        let mint = Pubkey::new_unique().to_string();
+        let task = spawn_wait(&dispatcher, level(&mint, TriggerCondition::Above, 1.0));
+        settle().await;
+        drop(tx);
+        assert!(task.await.unwrap().is_err());
+        let level = level(&mint, TriggerCondition::Above, 1.0);
+        assert!(dispatcher
+            .wait(&level, Uuid::new_v4(), "owner", 0)
+            .await
+            .is_err());
+    }
+}
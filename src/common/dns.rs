@@ -0,0 +1,267 @@
+use std::{
+    collections::HashMap,
+    env, fmt, io,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use hyper::client::connect::dns::Name;
+use reqwest::{
+    dns::{Addrs, Resolve, Resolving},
+    Client,
+};
+
+/// 单次 DNS 解析的默认超时
+const DEFAULT_LOOKUP_TIMEOUT: Duration = Duration::from_millis(2_000);
+/// 解析结果的默认缓存时间
+const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(60);
+/// 建立连接的超时
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// 单个 HTTP 请求的总超时
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// DNS 解析错误
+#[derive(Debug)]
+pub enum DnsError {
+    /// 在超时时间内未返回
+    Timeout { host: String, timeout: Duration },
+    /// 解析失败或没有地址
+    Failed { host: String, reason: String },
+}
+
+impl fmt::Display for DnsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DnsError::Timeout { host, timeout } => {
+                write!(f, "解析 {} 超时（{:?}）", host, timeout)
+            }
+            DnsError::Failed { host, reason } => write!(f, "解析 {} 失败: {}", host, reason),
+        }
+    }
+}
+
+impl std::error::Error for DnsError {}
+
+/// 实际执行解析的查询，生产环境为 [`SystemLookup`]
+#[async_trait]
+pub trait HostLookup: Send + Sync {
+    async fn lookup(&self, host: &str) -> io::Result<Vec<SocketAddr>>;
+}
+
+/// 操作系统解析器
+pub struct SystemLookup;
+
+#[async_trait]
+impl HostLookup for SystemLookup {
+    async fn lookup(&self, host: &str) -> io::Result<Vec<SocketAddr>> {
+        Ok(tokio::net::lookup_host((host, 0)).await?.collect())
+    }
+}
+
+struct CachedAddrs {
+    addrs: Vec<SocketAddr>,
+    resolved_at: Instant,
+}
+
+/// 带缓存和超时的 DNS 解析器
+///
+/// 系统解析器在 DNS 故障时会阻塞到操作系统的超时（数秒），期间所有价格轮询都会卡住。
+/// 这里每次解析最多等待 `timeout`，超时返回 [`DnsError::Timeout`]；
+/// 解析成功的结果缓存 `ttl`，解析失败时如果有过期的缓存则继续使用旧地址。
+pub struct CachingResolver {
+    lookup: Arc<dyn HostLookup>,
+    cache: Arc<Mutex<HashMap<String, CachedAddrs>>>,
+    timeout: Duration,
+    ttl: Duration,
+}
+
+impl CachingResolver {
+    pub fn new(timeout: Duration, ttl: Duration) -> CachingResolver {
+        CachingResolver::with_lookup(Arc::new(SystemLookup), timeout, ttl)
+    }
+
+    /// 使用指定的查询，例如测试中不会返回的查询
+    pub fn with_lookup(
+        lookup: Arc<dyn HostLookup>,
+        timeout: Duration,
+        ttl: Duration,
+    ) -> CachingResolver {
+        CachingResolver {
+            lookup,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+            timeout,
+            ttl,
+        }
+    }
+
+    /// 从环境变量 `DNS_LOOKUP_TIMEOUT_MS`、`DNS_CACHE_TTL_SECS` 读取，未配置时使用默认值
+    pub fn from_env() -> Result<CachingResolver> {
+        let timeout = match env::var("DNS_LOOKUP_TIMEOUT_MS") {
+            Ok(v) => Duration::from_millis(v.parse()?),
+            Err(_) => DEFAULT_LOOKUP_TIMEOUT,
+        };
+        let ttl = match env::var("DNS_CACHE_TTL_SECS") {
+            Ok(v) => Duration::from_secs(v.parse()?),
+            Err(_) => DEFAULT_CACHE_TTL,
+        };
+        Ok(CachingResolver::new(timeout, ttl))
+    }
+}
+
+impl Resolve for CachingResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        let lookup = self.lookup.clone();
+        let cache = self.cache.clone();
+        let timeout = self.timeout;
+        let ttl = self.ttl;
+        Box::pin(async move {
+            let stale = {
+                let cache = cache.lock().unwrap();
+                match cache.get(&host) {
+                    Some(cached) if cached.resolved_at.elapsed() < ttl => {
+                        let addrs: Addrs = Box::new(cached.addrs.clone().into_iter());
+                        return Ok(addrs);
+                    }
+                    Some(cached) => Some(cached.addrs.clone()),
+                    None => None,
+                }
+            };
+
+            let result = tokio::time::timeout(timeout, lookup.lookup(&host)).await;
+            let error = match result {
+                Ok(Ok(addrs)) => {
+                    if !addrs.is_empty() {
+                        cache.lock().unwrap().insert(
+                            host,
+                            CachedAddrs {
+                                addrs: addrs.clone(),
+                                resolved_at: Instant::now(),
+                            },
+                        );
+                        let addrs: Addrs = Box::new(addrs.into_iter());
+                        return Ok(addrs);
+                    }
+                    DnsError::Failed {
+                        host,
+                        reason: "没有可用地址".to_string(),
+                    }
+                }
+                Ok(Err(e)) => DnsError::Failed {
+                    host,
+                    reason: e.to_string(),
+                },
+                Err(_) => DnsError::Timeout { host, timeout },
+            };
+
+            if let Some(addrs) = stale {
+                println!("{}，使用缓存的旧地址", error);
+                let addrs: Addrs = Box::new(addrs.into_iter());
+                return Ok(addrs);
+            }
+            Err(error.into())
+        })
+    }
+}
+
+/// 构建共享的 HTTP 客户端，使用 [`CachingResolver`] 并设置连接和请求超时
+pub fn build_http_client() -> Result<Client> {
+    Ok(Client::builder()
+        .dns_resolver(Arc::new(CachingResolver::from_env()?))
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(REQUEST_TIMEOUT)
+        .build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        future,
+        str::FromStr,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::*;
+
+    /// 前 `answers` 次查询返回固定地址，之后的查询一直不返回
+    struct ScriptedLookup {
+        answers: usize,
+        calls: AtomicUsize,
+    }
+
+    impl ScriptedLookup {
+        fn new(answers: usize) -> Arc<ScriptedLookup> {
+            Arc::new(ScriptedLookup {
+                answers,
+                calls: AtomicUsize::new(0),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl HostLookup for ScriptedLookup {
+        async fn lookup(&self, _host: &str) -> io::Result<Vec<SocketAddr>> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) < self.answers {
+                return Ok(vec![SocketAddr::from(([93, 184, 216, 34], 0))]);
+            }
+            future::pending().await
+        }
+    }
+
+    async fn resolve(resolver: &CachingResolver) -> std::result::Result<Vec<SocketAddr>, String> {
+        match resolver
+            .resolve(Name::from_str("example.com").unwrap())
+            .await
+        {
+            Ok(addrs) => Ok(addrs.collect()),
+            Err(e) => Err(match e.downcast_ref::<DnsError>() {
+                Some(DnsError::Timeout { .. }) => "timeout".to_string(),
+                _ => e.to_string(),
+            }),
+        }
+    }
+
+    /// 查询不返回时在配置的超时时间返回超时错误，而不是等到系统解析器的超时
+    #[tokio::test(start_paused = true)]
+    async fn hanging_lookup_times_out_within_the_bound() {
+        let timeout = Duration::from_millis(300);
+        let resolver =
+            CachingResolver::with_lookup(ScriptedLookup::new(0), timeout, DEFAULT_CACHE_TTL);
+        let started = tokio::time::Instant::now();
+        assert_eq!(resolve(&resolver).await, Err("timeout".to_string()));
+        let elapsed = started.elapsed();
+        assert!(elapsed >= timeout, "{:?}", elapsed);
+        assert!(
+            elapsed < timeout + Duration::from_millis(50),
+            "{:?}",
+            elapsed
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cached_addresses_skip_the_lookup() {
+        let lookup = ScriptedLookup::new(1);
+        let resolver =
+            CachingResolver::with_lookup(lookup.clone(), DEFAULT_LOOKUP_TIMEOUT, DEFAULT_CACHE_TTL);
+        let first = resolve(&resolver).await.unwrap();
+        assert_eq!(resolve(&resolver).await.unwrap(), first);
+        assert_eq!(lookup.calls.load(Ordering::SeqCst), 1);
+    }
+
+    /// 缓存过期后查询超时时继续使用旧地址
+    #[tokio::test(start_paused = true)]
+    async fn stale_addresses_are_used_when_the_lookup_times_out() {
+        let lookup = ScriptedLookup::new(1);
+        let resolver = CachingResolver::with_lookup(
+            lookup.clone(),
+            Duration::from_millis(300),
+            Duration::ZERO,
+        );
+        let first = resolve(&resolver).await.unwrap();
+        assert_eq!(resolve(&resolver).await.unwrap(), first);
+        assert_eq!(lookup.calls.load(Ordering::SeqCst), 2);
+    }
+}
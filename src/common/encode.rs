@@ -1,79 +1,361 @@
-use crate::common::AES_KEY;
+use std::fmt;
+
+use crate::common::keys::{keys, KeyProvider, LEGACY_KEY};
+use crate::common::metrics::metrics;
+use crate::error::{self, LimitOrderError};
 use aes_gcm::aead::{Aead, KeyInit};
-use aes_gcm::{Aes256Gcm, Key, Nonce};
+use aes_gcm::{Aes256Gcm, Nonce};
 use base64::engine::general_purpose;
 use base64::Engine;
 // AES-GCM 256-bit 密钥
 use anyhow::{anyhow, Result};
 use rand::Rng;
+use zeroize::Zeroizing;
+/// nonce 长度
+const NONCE_LEN: usize = 12;
+/// AES-GCM 认证标签长度
+const TAG_LEN: usize = 16;
+
+/// 解密得到的敏感字符串（例如 base58 私钥）
+///
+/// 释放时清零，`Debug` 只输出 `[REDACTED]`，避免私钥出现在日志和错误信息中。
+#[derive(Clone)]
+pub struct SecretString(Zeroizing<String>);
+
+impl SecretString {
+    pub fn new(secret: String) -> SecretString {
+        SecretString(Zeroizing::new(secret))
+    }
+
+    /// 取出明文，调用方应尽快用完，不要复制到普通的 `String` 中
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
 /// 使用 AES-256-GCM 算法对输入数据进行加密，并将结果编码为 Base64 字符串。
 ///
-/// 该函数首先生成一个随机的 12 字节 nonce，将其与加密后的密文拼接在一起，然后将整个结果编码为 Base64 字符串。
-/// 加密使用的密钥是从 `crate::common::AES_KEY` 导入的静态 256 位密钥。
+/// 该函数首先生成一个随机的 12 字节 nonce，将 1 字节的密钥版本、nonce 与加密后的密文依次拼接，
+/// 然后将整个结果编码为 Base64 字符串。加密使用 `KeyProvider` 的当前密钥。
 ///
 /// # 参数
 /// * `plaintext` - 要加密的明文数据，以字节数组形式传入。
 ///
 /// # 返回值
-/// 返回一个 Base64 编码的字符串，包含 nonce 和密文。
-///
-/// # 异常
-/// 如果加密过程中发生错误（例如输入数据过长或密钥无效），函数会通过 `expect` panic。
-/// 在生产环境中，建议使用 `Result` 类型替换 `expect` 以更好地处理错误。
+/// 返回一个 `Result<String>`，其中：
+/// - `Ok(String)`: Base64 编码的字符串，包含密钥版本、nonce 和密文。
+/// - `Err(anyhow::Error)`: 加密失败（例如密钥未初始化或输入数据过长）。
 ///
 /// # 示例
-/// ```rust
+/// ```no_run
+/// # use limit_order::common::encode::encrypt;
+/// # fn main() -> anyhow::Result<()> {
 /// let plaintext = b"my secret data";
-/// let encrypted = encrypt(plaintext);
+/// let encrypted = encrypt(plaintext)?;
 /// println!("Encrypted: {}", encrypted);
+/// # Ok(())
+/// # }
 /// ```
-pub fn encrypt(plaintext: &[u8]) -> String {
-    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&AES_KEY)); // 添加泛型提示
-    let nonce_bytes: [u8; 12] = rand::thread_rng().gen(); // 生成随机 nonce
-    let nonce = Nonce::from_slice(&nonce_bytes);
+pub fn encrypt(plaintext: &[u8]) -> Result<String> {
+    let (version, key) = keys()?.active();
+    seal(version, key, plaintext)
+}
+
+/// 使用指定版本的密钥加密，格式见 [`encrypt`]
+fn seal(version: u8, key: &[u8], plaintext: &[u8]) -> Result<String> {
+    let cipher = Aes256Gcm::new_from_slice(key).map_err(|_| anyhow!("AES 密钥长度无效"))?;
+    let nonce_bytes: [u8; NONCE_LEN] = rand::rng().random(); // 生成随机 nonce
+    let nonce = Nonce::from(nonce_bytes);
     let mut ciphertext = cipher
-        .encrypt(nonce, plaintext)
-        .expect("encryption failure");
+        .encrypt(&nonce, plaintext)
+        .map_err(|e| anyhow!("加密失败 {:?}", e))?;
     ciphertext.splice(0..0, nonce_bytes.iter().cloned()); // 在密文前面加上 nonce
-    general_purpose::STANDARD.encode(&ciphertext)
+    ciphertext.insert(0, version); // 最前面是密钥版本
+    Ok(general_purpose::STANDARD.encode(&ciphertext))
 }
 
 /// 解密使用 AES-256-GCM 算法加密并以 Base64 编码的密文，返回解密后的字符串。
 ///
-/// 该函数首先将输入的 Base64 字符串解码为字节数组，从中提取前 12 字节作为 nonce，
-/// 然后使用剩余的字节作为密文进行解密。解密后的字节数组会被转换为 UTF-8 字符串。
-/// 解密使用的密钥是从 `crate::common::AES_KEY` 导入的静态 256 位密钥。
+/// 该函数首先将输入的 Base64 字符串解码为字节数组，第 1 个字节为密钥版本，随后 12 字节为 nonce，
+/// 剩余的字节作为密文进行解密。解密后的字节数组会被转换为 UTF-8 字符串，中间缓冲区释放时清零。
+/// 解密使用 `KeyProvider` 中与版本对应的密钥，因此密钥轮换前加密的数据仍可解密。
+///
+/// 引入密钥版本之前的密文没有版本字节（`nonce + 密文 + 标签`），使用公开的旧密钥 [`LEGACY_KEY`] 加密。
+/// 只有配置 `AES_ALLOW_LEGACY_KEY=true` 时，按版本解密失败后才会再按这种格式尝试一次，
+/// 都失败时返回按版本解密的错误；每次用旧密钥解密成功都会记录日志，提醒用 [`re_encrypt`] 迁移。
 ///
 /// # 参数
-/// * `ciphertext_bs64` - Base64 编码的密文字符串，包含 nonce 和加密数据。
+/// * `ciphertext_bs64` - Base64 编码的密文字符串，包含密钥版本、nonce 和加密数据。
 ///
 /// # 返回值
 /// 返回一个 `Result<String>`，其中：
-/// - `Ok(String)`: 成功解密后的明文字符串。
-/// - `Err(anyhow::Error)`: 如果解密失败（例如 Base64 解码失败、nonce 无效或密文损坏）。
+/// - `Ok(SecretString)`: 成功解密后的明文字符串。
+/// - `Err(LimitOrderError::DecryptFailed)`: 如果解密失败（例如 Base64 解码失败、密文过短或密文损坏）。
 ///
 /// # 错误
-/// - 如果 Base64 解码失败，会通过 `expect` panic（建议在生产环境中替换为错误返回）。
-/// - 如果解密失败（例如密文被篡改或密钥不匹配），返回 `Err` 并包含错误信息。
-/// - 如果解密结果不是有效的 UTF-8 字符串，使用 `from_utf8_lossy` 可能会导致部分数据丢失。
+/// - 输入不是有效的 Base64，返回 "密文不是有效的 base64"。
+/// - 解码后的长度不足版本、nonce 加认证标签，返回 "密文长度不足"。
+/// - 密钥版本不存在，返回 "未知的密钥版本"。
+/// - 密文被篡改或密钥不匹配，返回 "解码私钥失败"。
+/// - 如果解密结果不是有效的 UTF-8 字符串，返回 "解密结果不是有效的 UTF-8"。
 ///
 /// # 示例
-/// ```rust
+/// ```no_run
+/// # use limit_order::common::encode::decrypt;
 /// let encrypted = "some_base64_encoded_string";
 /// match decrypt(encrypted) {
-///     Ok(plain) => println!("Decrypted: {}", plain),
+///     Ok(plain) => println!("Decrypted: {:?}", plain), // 输出 [REDACTED]
 ///     Err(e) => eprintln!("Decryption failed: {:?}", e),
 /// }
 /// ```
-pub fn decrypt(ciphertext_bs64: &str) -> Result<String> {
+pub fn decrypt(ciphertext_bs64: &str) -> error::Result<SecretString> {
     let ciphertext = general_purpose::STANDARD
         .decode(ciphertext_bs64)
-        .expect("base64 decode failure");
-
-    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&AES_KEY)); // 添加泛型提示
-    let nonce = Nonce::from_slice(&ciphertext[..12]); // 提取 nonce
-    let res = cipher
-        .decrypt(nonce, &ciphertext[12..])
-        .map_err(|e| anyhow!("解码私钥失败 {:?}", e))?;
-    Ok(String::from_utf8_lossy(&res).to_string())
+        .map_err(|e| LimitOrderError::DecryptFailed(format!("密文不是有效的 base64 {}", e)))?;
+    decrypt_with(keys()?, &ciphertext)
+}
+
+/// 使用 `keys` 解密 base64 解码后的密文，见 [`decrypt`]
+fn decrypt_with(keys: &KeyProvider, ciphertext: &[u8]) -> error::Result<SecretString> {
+    let err = match decrypt_versioned(keys, ciphertext) {
+        Ok(plaintext) => return Ok(plaintext),
+        Err(e) => e,
+    };
+    if !keys.legacy_fallback() {
+        return Err(err);
+    }
+    let plaintext = decrypt_legacy(ciphertext).map_err(|_| err)?;
+    metrics().legacy_key_decryptions.inc();
+    println!(
+        "使用旧的固定密钥解密了没有版本字节的密文，请用 re_encrypt 迁移后关闭 AES_ALLOW_LEGACY_KEY"
+    );
+    Ok(plaintext)
+}
+
+/// 解密 `版本 + nonce + 密文 + 标签` 格式的密文
+fn decrypt_versioned(keys: &KeyProvider, ciphertext: &[u8]) -> error::Result<SecretString> {
+    if ciphertext.len() < 1 + NONCE_LEN + TAG_LEN {
+        return Err(LimitOrderError::DecryptFailed(format!(
+            "密文长度不足，至少 {} 字节，实际 {} 字节",
+            1 + NONCE_LEN + TAG_LEN,
+            ciphertext.len()
+        )));
+    }
+
+    let key = keys
+        .get(ciphertext[0])
+        .map_err(|e| LimitOrderError::DecryptFailed(e.to_string()))?;
+    open(key, &ciphertext[1..])
+}
+
+/// 解密没有版本字节的旧密文
+fn decrypt_legacy(ciphertext: &[u8]) -> error::Result<SecretString> {
+    if ciphertext.len() < NONCE_LEN + TAG_LEN {
+        return Err(LimitOrderError::DecryptFailed("密文长度不足".to_string()));
+    }
+    open(&LEGACY_KEY, ciphertext)
+}
+
+/// 解密 `nonce + 密文 + 标签`
+fn open(key: &[u8], ciphertext: &[u8]) -> error::Result<SecretString> {
+    let cipher = Aes256Gcm::new_from_slice(key)
+        .map_err(|_| LimitOrderError::DecryptFailed("AES 密钥长度无效".to_string()))?;
+    let (nonce, ciphertext) = ciphertext.split_at(NONCE_LEN); // 提取 nonce
+    let nonce: [u8; NONCE_LEN] = nonce.try_into().expect("nonce 长度固定");
+    let res = Zeroizing::new(
+        cipher
+            .decrypt(&Nonce::from(nonce), ciphertext)
+            .map_err(|e| LimitOrderError::DecryptFailed(format!("解码私钥失败 {:?}", e)))?,
+    );
+    let plaintext = std::str::from_utf8(&res)
+        .map_err(|_| LimitOrderError::DecryptFailed("解密结果不是有效的 UTF-8".to_string()))?;
+    Ok(SecretString::new(plaintext.to_string()))
+}
+
+/// 用当前密钥重新加密旧密文，用于密钥轮换后迁移已保存的数据，也可以迁移没有版本字节的旧密文
+pub fn re_encrypt(old_ciphertext_bs64: &str) -> Result<String> {
+    encrypt(decrypt(old_ciphertext_bs64)?.expose().as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Once;
+
+    use super::*;
+    use crate::common::keys::{install, KeyProvider, KEY_LEN};
+
+    /// 引入密钥版本之前 readme 中的示例密文，使用 [`LEGACY_KEY`] 加密
+    const LEGACY_EXAMPLE: &str = "c3wVtufBPy2EHVAP/RjjQoZOb8wzyAgtxp0mPXwJ4CO7K53ot5t4hkKNjYzepxZxzuPB+Q8xFt3ft11xzISVdWly7VKqX6h2QOLzCT7GLWCwcopyNFa0jMCSUoUUBLHCAmAYOulDKV+q/2oaK6iSs9QBxHo=";
+    /// readme 中当前的示例密文，版本 1
+    const VERSIONED_EXAMPLE: &str = "AedGwrGdhnXF295cMCz2dRUu8s1JEjmw+P7GOMHK+KPDeEqPze1s+4/+R2B0nLWq4kY14S/KAT0GGWC82tSCtGVsd5UEtpQeKvNat+dkabgXLHq/Dpi58y9OTyHcQJpBP+ALCKQH1ZqpMRYVjlq/4NuRikdy";
+
+    /// 当前密钥为版本 2，版本 1 为轮换前的旧密钥，允许解密没有版本字节的旧密文
+    fn install_test_keys() {
+        static INSTALL: Once = Once::new();
+        INSTALL.call_once(|| {
+            let provider = KeyProvider::new(2, [2; KEY_LEN])
+                .with_previous(1, LEGACY_KEY)
+                .unwrap()
+                .with_legacy_fallback(true);
+            install(provider).unwrap();
+        });
+    }
+
+    fn version_of(ciphertext_bs64: &str) -> u8 {
+        general_purpose::STANDARD.decode(ciphertext_bs64).unwrap()[0]
+    }
+
+    #[test]
+    fn encrypt_uses_active_key() {
+        install_test_keys();
+        let ciphertext = encrypt(b"secret").unwrap();
+        assert_eq!(version_of(&ciphertext), 2);
+        assert_eq!(decrypt(&ciphertext).unwrap().expose(), "secret");
+    }
+
+    #[test]
+    fn rotation_round_trip() {
+        install_test_keys();
+        let old = seal(1, &LEGACY_KEY, b"rotate me").unwrap();
+        assert_eq!(decrypt(&old).unwrap().expose(), "rotate me");
+
+        let new = re_encrypt(&old).unwrap();
+        assert_ne!(new, old);
+        assert_eq!(version_of(&new), 2);
+        assert_eq!(decrypt(&new).unwrap().expose(), "rotate me");
+    }
+
+    #[test]
+    fn legacy_ciphertext_decrypts_and_migrates() {
+        install_test_keys();
+        let plaintext = decrypt(LEGACY_EXAMPLE).unwrap();
+        assert_eq!(plaintext.expose().len(), 88);
+
+        let migrated = re_encrypt(LEGACY_EXAMPLE).unwrap();
+        assert_eq!(version_of(&migrated), 2);
+        assert_eq!(decrypt(&migrated).unwrap().expose(), plaintext.expose());
+    }
+
+    #[test]
+    fn legacy_ciphertext_is_rejected_unless_enabled() {
+        let ciphertext = general_purpose::STANDARD.decode(LEGACY_EXAMPLE).unwrap();
+        let provider = KeyProvider::new(2, [2; KEY_LEN])
+            .with_previous(1, LEGACY_KEY)
+            .unwrap();
+        match decrypt_with(&provider, &ciphertext) {
+            Err(LimitOrderError::DecryptFailed(reason)) => {
+                assert!(reason.contains("未知的密钥版本"), "{}", reason)
+            }
+            other => panic!("应返回 DecryptFailed，实际为 {:?}", other),
+        }
+
+        let provider = provider.with_legacy_fallback(true);
+        let before = metrics().legacy_key_decryptions.get();
+        assert_eq!(
+            decrypt_with(&provider, &ciphertext).unwrap().expose().len(),
+            88
+        );
+        assert!(metrics().legacy_key_decryptions.get() > before);
+    }
+
+    #[test]
+    fn readme_examples_hold_the_same_key() {
+        install_test_keys();
+        assert_eq!(version_of(VERSIONED_EXAMPLE), 1);
+        assert_eq!(
+            decrypt(VERSIONED_EXAMPLE).unwrap().expose(),
+            decrypt(LEGACY_EXAMPLE).unwrap().expose()
+        );
+    }
+
+    fn tamper(ciphertext_bs64: &str, change: impl FnOnce(&mut Vec<u8>)) -> String {
+        let mut bytes = general_purpose::STANDARD.decode(ciphertext_bs64).unwrap();
+        change(&mut bytes);
+        general_purpose::STANDARD.encode(bytes)
+    }
+
+    fn decrypt_error(ciphertext_bs64: &str) -> String {
+        match decrypt(ciphertext_bs64) {
+            Err(LimitOrderError::DecryptFailed(reason)) => reason,
+            other => panic!("应返回 DecryptFailed，实际为 {:?}", other),
+        }
+    }
+
+    #[test]
+    fn invalid_base64_is_rejected() {
+        install_test_keys();
+        assert!(decrypt_error("not base64!").contains("base64"));
+    }
+
+    #[test]
+    fn truncated_ciphertext_is_rejected() {
+        install_test_keys();
+        assert!(decrypt_error("").contains("长度不足"));
+        let ciphertext = encrypt(b"secret").unwrap();
+        let truncated = tamper(&ciphertext, |bytes| {
+            bytes.truncate(1 + NONCE_LEN + TAG_LEN - 1)
+        });
+        assert!(decrypt_error(&truncated).contains("长度不足"));
+        // 长度足够但截掉了部分认证标签
+        let truncated = tamper(&ciphertext, |bytes| {
+            bytes.pop();
+        });
+        assert!(decrypt_error(&truncated).contains("解码私钥失败"));
+    }
+
+    #[test]
+    fn wrong_key_version_is_rejected() {
+        install_test_keys();
+        let ciphertext = encrypt(b"secret").unwrap();
+        // 版本 1 的密钥存在，但不是加密时使用的密钥
+        let wrong = tamper(&ciphertext, |bytes| bytes[0] = 1);
+        assert!(decrypt_error(&wrong).contains("解码私钥失败"));
+    }
+
+    #[test]
+    fn tampered_tag_is_rejected() {
+        install_test_keys();
+        let ciphertext = encrypt(b"secret").unwrap();
+        let tampered = tamper(&ciphertext, |bytes| *bytes.last_mut().unwrap() ^= 0x01);
+        assert!(decrypt_error(&tampered).contains("解码私钥失败"));
+        let tampered = tamper(&ciphertext, |bytes| bytes[1 + NONCE_LEN] ^= 0x01);
+        assert!(decrypt_error(&tampered).contains("解码私钥失败"));
+    }
+
+    #[test]
+    fn non_utf8_plaintext_is_rejected() {
+        install_test_keys();
+        let ciphertext = encrypt(&[0xff, 0xfe, 0xfd]).unwrap();
+        assert!(decrypt_error(&ciphertext).contains("UTF-8"));
+    }
+
+    #[test]
+    fn invalid_base58_key_is_rejected_after_decrypt() {
+        install_test_keys();
+        let ciphertext = encrypt(b"0OIl is not base58").unwrap();
+        let plaintext = decrypt(&ciphertext).unwrap();
+        assert!(crate::common::session::parse_keypair(plaintext.expose()).is_err());
+    }
+
+    #[test]
+    fn unknown_key_version_is_reported() {
+        install_test_keys();
+        let ciphertext = encrypt(b"secret").unwrap();
+        let unknown = tamper(&ciphertext, |bytes| bytes[0] = 9);
+        assert!(decrypt_error(&unknown).contains("未知的密钥版本 9"));
+    }
+
+    #[test]
+    fn secret_string_debug_is_redacted() {
+        let secret = SecretString::new("5Kd3NBUAdUnhyzenEwVLy9pBKxSwXvE9FMPyR4UKZvpe".to_string());
+        assert_eq!(format!("{:?}", secret), "[REDACTED]");
+        assert_eq!(format!("{:#?}", secret), "[REDACTED]");
+        assert_eq!(format!("{:?}", Some(secret)), "Some([REDACTED])");
+    }
 }
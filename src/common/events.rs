@@ -0,0 +1,197 @@
+use serde::Serialize;
+use tokio::sync::broadcast::{self, error::RecvError};
+use uuid::Uuid;
+
+use crate::{
+    common::{audit::AuditLog, types::Order},
+    solana::fill::FillReport,
+};
+
+/// 订单状态事件的缓冲容量，订阅方落后超过该数量时会丢失事件并收到 `Resync`
+const LIFECYCLE_CAPACITY: usize = 4096;
+/// 价格事件的缓冲容量，订阅方跟不上时直接丢弃旧的价格事件
+const PRICE_CAPACITY: usize = 64;
+
+/// 订单事件
+#[derive(Debug, Clone, Serialize)]
+pub struct OrderEvent {
+    pub order_id: Uuid,
+    /// 下单钱包的公钥
+    pub owner: String,
+    #[serde(flatten)]
+    pub kind: OrderEventKind,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OrderEventKind {
+    Placed,
+    /// 订单监控到的最新价格，每笔订单限频发送
+    PriceUpdate {
+        price: f64,
+    },
+    /// 交易已发送
+    Triggered {
+        signature: String,
+    },
+    Filled {
+        signature: Option<String>,
+        /// 实际得到的输出代币数量（已扣税），未知时为 None
+        out_amount: Option<u64>,
+        /// 最近一次成交的实际数量和成交价格
+        fill: Option<FillReport>,
+    },
+    PartiallyFilled {
+        filled_amount: u64,
+    },
+    /// 重复订单完成一次成交，订单已按新的参数重新挂单
+    RepeatFilled {
+        signature: String,
+        fills_completed: u32,
+        fills_remaining: u32,
+    },
+    Failed {
+        reason: String,
+    },
+    /// 需要注意但不影响订单状态的情况，例如私有发送失败后改用公开 RPC
+    Warning {
+        message: String,
+    },
+    Canceled,
+    /// 签名交易已过期需要重新签名，或订单在有效期内未触发
+    Expired,
+    /// 触发后报价隐含的成交价格偏离触发时的价格超过 `max_trigger_deviation_bps`，本次不执行，订单回到监控
+    DeviationRejected {
+        /// 触发时价格源的价格
+        trigger_price: f64,
+        /// 按报价换算的成交价格，与 `trigger_price` 同一单位
+        execution_price: f64,
+        deviation_bps: u64,
+        max_bps: u16,
+    },
+    /// 触发后未能在执行时限内完成，本次执行已放弃；`rearmed` 为 true 时订单回到监控
+    ExecutionTimedOut {
+        reason: String,
+        /// 已发送但未上链的交易
+        signature: Option<String>,
+        rearmed: bool,
+    },
+    /// 执行超时后订单结束，没有交易上链
+    Unfilled {
+        reason: String,
+    },
+}
+
+impl OrderEvent {
+    pub fn new(order: &Order, kind: OrderEventKind) -> OrderEvent {
+        OrderEvent {
+            order_id: order.order_id,
+            owner: order.owner.clone(),
+            kind,
+        }
+    }
+}
+
+/// 订单事件的广播
+///
+/// 状态事件与价格事件使用两个通道：价格事件量大且可以丢弃，缓冲较小；
+/// 状态事件（下单、触发、成交、失败等）缓冲较大，订阅方优先接收，不会因为价格事件堆积而丢失。
+/// 配置了审计日志时状态事件同时写入订单的审计日志，价格事件不写入。
+#[derive(Clone)]
+pub struct EventBus {
+    lifecycle: broadcast::Sender<OrderEvent>,
+    prices: broadcast::Sender<OrderEvent>,
+    audit: Option<AuditLog>,
+}
+
+impl EventBus {
+    pub fn new() -> EventBus {
+        EventBus {
+            lifecycle: broadcast::channel(LIFECYCLE_CAPACITY).0,
+            prices: broadcast::channel(PRICE_CAPACITY).0,
+            audit: None,
+        }
+    }
+
+    /// 状态事件同时写入 `audit`
+    pub fn with_audit(audit: AuditLog) -> EventBus {
+        EventBus {
+            audit: Some(audit),
+            ..EventBus::new()
+        }
+    }
+
+    /// 发布事件，没有订阅方时直接丢弃
+    pub fn publish(&self, event: OrderEvent) {
+        let tx = match event.kind {
+            OrderEventKind::PriceUpdate { .. } => &self.prices,
+            _ => {
+                if let Some(audit) = &self.audit {
+                    record(audit, &event);
+                }
+                &self.lifecycle
+            }
+        };
+        let _ = tx.send(event);
+    }
+
+    pub fn subscribe(&self) -> EventSubscription {
+        EventSubscription {
+            lifecycle: self.lifecycle.subscribe(),
+            prices: self.prices.subscribe(),
+        }
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        EventBus::new()
+    }
+}
+
+/// 以事件的 `type` 作为审计事件类型，其余字段作为内容
+fn record(audit: &AuditLog, event: &OrderEvent) {
+    let mut payload = serde_json::to_value(&event.kind).unwrap_or_default();
+    let kind = payload
+        .as_object_mut()
+        .and_then(|fields| fields.remove("type"))
+        .and_then(|kind| kind.as_str().map(str::to_string))
+        .unwrap_or_default();
+    audit.append_event(event.order_id, &kind, payload);
+}
+
+/// 订阅收到的内容
+pub enum EventItem {
+    Event(OrderEvent),
+    /// 订阅方处理太慢，丢失了 `missed` 条状态事件，应重新查询订单状态
+    Resync {
+        missed: u64,
+    },
+}
+
+pub struct EventSubscription {
+    lifecycle: broadcast::Receiver<OrderEvent>,
+    prices: broadcast::Receiver<OrderEvent>,
+}
+
+impl EventSubscription {
+    /// 等待下一条事件，广播关闭时返回 None
+    pub async fn next(&mut self) -> Option<EventItem> {
+        loop {
+            tokio::select! {
+                biased;
+                event = self.lifecycle.recv() => match event {
+                    Ok(event) => return Some(EventItem::Event(event)),
+                    Err(RecvError::Lagged(missed)) => return Some(EventItem::Resync { missed }),
+                    Err(RecvError::Closed) => return None,
+                },
+                event = self.prices.recv() => match event {
+                    Ok(event) => return Some(EventItem::Event(event)),
+                    // 落后的价格事件直接跳过
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return None,
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,397 @@
+use std::{collections::HashMap, env, sync::OnceLock, time::Duration};
+
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose, Engine};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::common::encode::SecretString;
+
+/// AES-256 密钥长度
+pub const KEY_LEN: usize = 32;
+
+/// 引入密钥版本之前写死在代码中的密钥，只用于解密没有版本字节的旧密文，不会用于加密
+///
+/// 该密钥是公开的，只有 [`KeyProvider::with_legacy_fallback`] 开启后才会使用。
+pub const LEGACY_KEY: [u8; KEY_LEN] = [1; KEY_LEN];
+
+/// 请求 KMS 解密密钥的超时
+const KMS_TIMEOUT: Duration = Duration::from_secs(10);
+
+static KEYS: OnceLock<KeyProvider> = OnceLock::new();
+
+/// 加解密使用的 AES 密钥
+///
+/// 新数据总是用当前版本的密钥加密，密文的第一个字节记录密钥版本；
+/// 解密时按版本取对应的密钥，轮换后旧密钥放进 `AES_PREVIOUS_KEYS` 即可继续解密旧密文。
+pub struct KeyProvider {
+    active_version: u8,
+    keys: HashMap<u8, [u8; KEY_LEN]>,
+    legacy_fallback: bool,
+}
+
+impl KeyProvider {
+    pub fn new(active_version: u8, active_key: [u8; KEY_LEN]) -> KeyProvider {
+        KeyProvider {
+            active_version,
+            keys: HashMap::from([(active_version, active_key)]),
+            legacy_fallback: false,
+        }
+    }
+
+    /// 添加只用于解密的旧密钥
+    pub fn with_previous(mut self, version: u8, key: [u8; KEY_LEN]) -> Result<KeyProvider> {
+        if self.keys.contains_key(&version) {
+            return Err(anyhow!("密钥版本 {} 重复", version));
+        }
+        self.keys.insert(version, key);
+        Ok(self)
+    }
+
+    /// 是否允许用 [`LEGACY_KEY`] 解密没有版本字节的旧密文，默认不允许
+    ///
+    /// 只应在迁移期间开启，旧密文全部用 `re_encrypt` 迁移后关闭。
+    pub fn with_legacy_fallback(mut self, enabled: bool) -> KeyProvider {
+        self.legacy_fallback = enabled;
+        self
+    }
+
+    pub fn legacy_fallback(&self) -> bool {
+        self.legacy_fallback
+    }
+
+    /// 从环境变量读取密钥
+    ///
+    /// - `AES_KEY`：当前密钥，base64 编码的 32 字节
+    /// - `AES_KEY_VERSION`：当前密钥的版本，默认 1
+    /// - `AES_PREVIOUS_KEYS`：可选，逗号分隔的 `<版本>:<base64 密钥>`
+    /// - `AES_ALLOW_LEGACY_KEY`：是否允许用 [`LEGACY_KEY`] 解密旧密文，默认 false
+    pub fn from_env() -> Result<KeyProvider> {
+        let active_key = parse_key(&env::var("AES_KEY").map_err(|_| anyhow!("缺少 AES_KEY"))?)?;
+        let mut provider = KeyProvider::new(active_version_from_env()?, active_key)
+            .with_legacy_fallback(legacy_fallback_from_env()?);
+        for (version, key) in previous_from_env()? {
+            provider = provider.with_previous(version, parse_key(&key)?)?;
+        }
+        Ok(provider)
+    }
+
+    /// 当前用于加密的密钥及其版本
+    pub fn active(&self) -> (u8, &[u8; KEY_LEN]) {
+        (self.active_version, &self.keys[&self.active_version])
+    }
+
+    pub fn get(&self, version: u8) -> Result<&[u8; KEY_LEN]> {
+        self.keys
+            .get(&version)
+            .ok_or_else(|| anyhow!("未知的密钥版本 {}", version))
+    }
+}
+
+/// 加密密钥的来源
+pub enum KeySource {
+    /// 环境变量中 base64 编码的明文密钥，见 [`KeyProvider::from_env`]
+    Env(KeyProvider),
+    /// 由 KMS 加密的密钥，启动时请求 KMS 解密
+    Kms(KmsKeySource),
+}
+
+impl KeySource {
+    /// 配置了 `AES_KMS_URL` 时从 KMS 解密密钥，否则从环境变量读取明文密钥
+    pub fn from_env() -> Result<KeySource> {
+        match env::var("AES_KMS_URL")
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+        {
+            Some(url) => Ok(KeySource::Kms(KmsKeySource::from_env(url.trim())?)),
+            None => Ok(KeySource::Env(KeyProvider::from_env()?)),
+        }
+    }
+
+    /// 取得全部密钥，KMS 来源在这里逐个解密
+    pub async fn load(self) -> Result<KeyProvider> {
+        match self {
+            KeySource::Env(provider) => Ok(provider),
+            KeySource::Kms(kms) => kms.load().await,
+        }
+    }
+}
+
+/// 信封加密的密钥：环境变量中只保存 KMS 加密后的密文，明文密钥只在内存中
+///
+/// KMS 使用 Vault Transit 的解密接口 `POST {url}/v1/transit/decrypt/{key_name}`，
+/// 以 `X-Vault-Token` 认证，请求 `{"ciphertext": "vault:v1:..."}`，返回 `{"data": {"plaintext": "<base64>"}}`。
+/// 密文由 `vault write transit/encrypt/<key_name> plaintext=<base64 密钥>` 生成。
+pub struct KmsKeySource {
+    http: Client,
+    url: String,
+    token: SecretString,
+    key_name: String,
+    active_version: u8,
+    active: String,
+    previous: Vec<(u8, String)>,
+    legacy_fallback: bool,
+}
+
+#[derive(Deserialize)]
+struct KmsDecryptResponse {
+    data: KmsPlaintext,
+}
+
+#[derive(Deserialize)]
+struct KmsPlaintext {
+    plaintext: String,
+}
+
+impl KmsKeySource {
+    /// 从环境变量读取 KMS 配置和密钥密文
+    ///
+    /// - `AES_KMS_TOKEN`：访问 KMS 的 token
+    /// - `AES_KMS_KEY_NAME`：KMS 中加密密钥使用的主密钥名称
+    /// - `AES_KEY`：当前密钥的 KMS 密文
+    /// - `AES_KEY_VERSION`：当前密钥的版本，默认 1
+    /// - `AES_PREVIOUS_KEYS`：可选，逗号分隔的 `<版本>:<KMS 密文>`
+    /// - `AES_ALLOW_LEGACY_KEY`：是否允许用 [`LEGACY_KEY`] 解密旧密文，默认 false
+    pub fn from_env(url: &str) -> Result<KmsKeySource> {
+        let required = |name: &str| {
+            env::var(name)
+                .ok()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+                .ok_or_else(|| anyhow!("配置 AES_KMS_URL 时缺少 {}", name))
+        };
+        let source = KmsKeySource {
+            http: Client::new(),
+            url: url.trim_end_matches('/').to_string(),
+            token: SecretString::new(required("AES_KMS_TOKEN")?),
+            key_name: required("AES_KMS_KEY_NAME")?,
+            active_version: active_version_from_env()?,
+            active: required("AES_KEY")?,
+            previous: previous_from_env()?,
+            legacy_fallback: legacy_fallback_from_env()?,
+        };
+        for (i, (version, _)) in source.previous.iter().enumerate() {
+            if *version == source.active_version
+                || source.previous[..i].iter().any(|(v, _)| v == version)
+            {
+                return Err(anyhow!("密钥版本 {} 重复", version));
+            }
+        }
+        Ok(source)
+    }
+
+    /// 解密当前密钥和全部旧密钥，任何一个解密失败都返回错误
+    pub async fn load(&self) -> Result<KeyProvider> {
+        let mut provider = KeyProvider::new(
+            self.active_version,
+            self.decrypt(self.active_version, &self.active).await?,
+        )
+        .with_legacy_fallback(self.legacy_fallback);
+        for (version, ciphertext) in &self.previous {
+            provider =
+                provider.with_previous(*version, self.decrypt(*version, ciphertext).await?)?;
+        }
+        Ok(provider)
+    }
+
+    async fn decrypt(&self, version: u8, ciphertext: &str) -> Result<[u8; KEY_LEN]> {
+        let resp = self
+            .http
+            .post(format!("{}/v1/transit/decrypt/{}", self.url, self.key_name))
+            .header("X-Vault-Token", self.token.expose())
+            .json(&json!({ "ciphertext": ciphertext }))
+            .timeout(KMS_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| anyhow!("请求 KMS 失败 {}", e))?;
+        if !resp.status().is_success() {
+            return Err(anyhow!(
+                "KMS 解密版本 {} 的密钥返回 {}",
+                version,
+                resp.status()
+            ));
+        }
+        let body: KmsDecryptResponse = resp
+            .json()
+            .await
+            .map_err(|e| anyhow!("KMS 响应格式错误 {}", e))?;
+        parse_key(&body.data.plaintext)
+            .with_context(|| format!("KMS 返回的版本 {} 密钥无效", version))
+    }
+}
+
+/// `AES_KEY_VERSION`，默认 1
+fn active_version_from_env() -> Result<u8> {
+    match env::var("AES_KEY_VERSION") {
+        Ok(v) => parse_version(&v),
+        Err(_) => Ok(1),
+    }
+}
+
+/// `AES_ALLOW_LEGACY_KEY`，默认 false
+fn legacy_fallback_from_env() -> Result<bool> {
+    match env::var("AES_ALLOW_LEGACY_KEY") {
+        Ok(v) if !v.trim().is_empty() => v
+            .trim()
+            .parse()
+            .map_err(|_| anyhow!("AES_ALLOW_LEGACY_KEY 必须为 true 或 false")),
+        _ => Ok(false),
+    }
+}
+
+/// `AES_PREVIOUS_KEYS` 中的 `<版本>:<密钥>`，密钥按来源为 base64 明文或 KMS 密文
+fn previous_from_env() -> Result<Vec<(u8, String)>> {
+    let Ok(previous) = env::var("AES_PREVIOUS_KEYS") else {
+        return Ok(vec![]);
+    };
+    previous
+        .split(',')
+        .filter(|s| !s.trim().is_empty())
+        .map(|entry| {
+            let (version, key) = entry
+                .trim()
+                .split_once(':')
+                .ok_or_else(|| anyhow!("无法解析的旧密钥 {}", entry))?;
+            Ok((parse_version(version)?, key.trim().to_string()))
+        })
+        .collect()
+}
+
+fn parse_version(version: &str) -> Result<u8> {
+    version
+        .trim()
+        .parse()
+        .map_err(|_| anyhow!("密钥版本必须为 0 到 255 的整数，实际为 {}", version))
+}
+
+fn parse_key(key_bs64: &str) -> Result<[u8; KEY_LEN]> {
+    let bytes = general_purpose::STANDARD
+        .decode(key_bs64.trim())
+        .map_err(|_| anyhow!("密钥不是有效的 base64"))?;
+    bytes.try_into().map_err(|bytes: Vec<u8>| {
+        anyhow!("密钥长度应为 {} 字节，实际 {} 字节", KEY_LEN, bytes.len())
+    })
+}
+
+/// 启动时设置全局密钥，只能设置一次
+pub fn install(provider: KeyProvider) -> Result<()> {
+    KEYS.set(provider).map_err(|_| anyhow!("加密密钥已初始化"))
+}
+
+/// 全局密钥
+pub fn keys() -> Result<&'static KeyProvider> {
+    KEYS.get().ok_or_else(|| anyhow!("加密密钥未初始化"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_must_fit_in_a_byte() {
+        assert_eq!(parse_version(" 3 ").unwrap(), 3);
+        assert_eq!(parse_version("255").unwrap(), 255);
+        for invalid in ["256", "-1", "v2", ""] {
+            let err = parse_version(invalid).unwrap_err().to_string();
+            assert!(err.contains("0 到 255"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn unknown_version_is_reported() {
+        let provider = KeyProvider::new(2, [2; KEY_LEN])
+            .with_previous(1, [1; KEY_LEN])
+            .unwrap();
+        assert_eq!(provider.active().0, 2);
+        assert_eq!(provider.get(1).unwrap(), &[1; KEY_LEN]);
+        let err = provider.get(9).unwrap_err().to_string();
+        assert!(err.contains("未知的密钥版本 9"), "{}", err);
+    }
+
+    #[test]
+    fn duplicate_version_is_rejected() {
+        assert!(KeyProvider::new(1, [1; KEY_LEN])
+            .with_previous(1, [2; KEY_LEN])
+            .is_err());
+    }
+
+    #[test]
+    fn legacy_fallback_is_opt_in() {
+        let provider = KeyProvider::new(1, [2; KEY_LEN]);
+        assert!(!provider.legacy_fallback());
+        assert!(provider.with_legacy_fallback(true).legacy_fallback());
+    }
+
+    /// KMS 来源按 Vault Transit 的接口解密
+    #[cfg(feature = "testing")]
+    mod kms {
+        use wiremock::{
+            matchers::{body_json, header, method, path},
+            Mock, MockServer, ResponseTemplate,
+        };
+
+        use super::*;
+
+        fn source(url: &str, previous: Vec<(u8, String)>) -> KmsKeySource {
+            KmsKeySource {
+                http: Client::new(),
+                url: url.to_string(),
+                token: SecretString::new("vault-token".to_string()),
+                key_name: "orders".to_string(),
+                active_version: 2,
+                active: "vault:v2:active".to_string(),
+                previous,
+                legacy_fallback: false,
+            }
+        }
+
+        /// KMS 对 `ciphertext` 返回 `plaintext`
+        async fn mount_decrypt(server: &MockServer, ciphertext: &str, plaintext: &[u8]) {
+            Mock::given(method("POST"))
+                .and(path("/v1/transit/decrypt/orders"))
+                .and(header("X-Vault-Token", "vault-token"))
+                .and(body_json(json!({ "ciphertext": ciphertext })))
+                .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                    "data": { "plaintext": general_purpose::STANDARD.encode(plaintext) }
+                })))
+                .mount(server)
+                .await;
+        }
+
+        #[tokio::test]
+        async fn decrypts_active_and_previous_keys() {
+            let server = MockServer::start().await;
+            mount_decrypt(&server, "vault:v2:active", &[2; KEY_LEN]).await;
+            mount_decrypt(&server, "vault:v1:old", &[1; KEY_LEN]).await;
+
+            let provider =
+                KeySource::Kms(source(&server.uri(), vec![(1, "vault:v1:old".to_string())]))
+                    .load()
+                    .await
+                    .unwrap();
+            assert_eq!(provider.active(), (2, &[2; KEY_LEN]));
+            assert_eq!(provider.get(1).unwrap(), &[1; KEY_LEN]);
+            assert!(!provider.legacy_fallback());
+        }
+
+        #[tokio::test]
+        async fn kms_errors_fail_loading() {
+            let server = MockServer::start().await;
+            Mock::given(method("POST"))
+                .respond_with(ResponseTemplate::new(403))
+                .mount(&server)
+                .await;
+            let err = source(&server.uri(), vec![]).load().await.unwrap_err();
+            assert!(err.to_string().contains("403"), "{}", err);
+        }
+
+        #[tokio::test]
+        async fn plaintext_must_be_a_full_key() {
+            let server = MockServer::start().await;
+            mount_decrypt(&server, "vault:v2:active", &[2; 16]).await;
+            let err = source(&server.uri(), vec![]).load().await.unwrap_err();
+            assert!(format!("{:#}", err).contains("实际 16 字节"), "{:#}", err);
+        }
+    }
+}
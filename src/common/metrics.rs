@@ -0,0 +1,115 @@
+use std::sync::LazyLock;
+
+use anyhow::Result;
+use prometheus::{
+    Encoder, Gauge, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+
+static METRICS: LazyLock<Metrics> =
+    LazyLock::new(|| Metrics::new().expect("Prometheus 指标注册失败"));
+
+/// 订单与交易的 Prometheus 指标
+///
+/// 报价、bundle 确认等埋点位于没有订单簿引用的函数中，因此指标是进程级的单例，通过 [`metrics`] 访问。
+pub struct Metrics {
+    registry: Registry,
+    pub orders_placed: IntCounter,
+    pub orders_filled: IntCounter,
+    pub orders_canceled: IntCounter,
+    pub orders_failed: IntCounter,
+    pub orders_expired: IntCounter,
+    /// 执行超时后结束的订单数
+    pub orders_unfilled: IntCounter,
+    /// 触发后执行超时的次数，包括超时后重新监控的订单
+    pub execution_timeouts: IntCounter,
+    /// 报价隐含的成交价格偏离触发价格过大而放弃执行的次数
+    pub deviation_rejections: IntCounter,
+    /// 等待触发的订单数，抓取时更新
+    pub open_orders: IntGauge,
+    /// 缓存中最旧价格的年龄（秒），抓取时更新
+    pub price_cache_age_seconds: Gauge,
+    /// 从触发到交易确认的耗时
+    pub trigger_to_confirm_seconds: Histogram,
+    /// Jupiter 报价耗时
+    pub quote_latency_seconds: Histogram,
+    /// 报价被限流或服务端错误后的重试次数
+    pub quote_retries: IntCounter,
+    /// bundle 确认结果，按最终状态区分
+    pub bundle_status: IntCounterVec,
+    /// 写入队列已满或写入失败而丢失的审计事件数
+    pub audit_events_dropped: IntCounter,
+    /// 用公开的旧密钥解密的密文数，降为 0 后即可关闭 `AES_ALLOW_LEGACY_KEY`
+    pub legacy_key_decryptions: IntCounter,
+}
+
+impl Metrics {
+    fn new() -> prometheus::Result<Metrics> {
+        let registry = Registry::new_custom(Some("limit_order".to_string()), None)?;
+        let counter = |name: &str, help: &str| -> prometheus::Result<IntCounter> {
+            let counter = IntCounter::new(name, help)?;
+            registry.register(Box::new(counter.clone()))?;
+            Ok(counter)
+        };
+        let histogram = |name: &str, help: &str, buckets: Vec<f64>| {
+            let histogram = Histogram::with_opts(HistogramOpts::new(name, help).buckets(buckets))?;
+            registry.register(Box::new(histogram.clone()))?;
+            prometheus::Result::Ok(histogram)
+        };
+        let metrics = Metrics {
+            orders_placed: counter("orders_placed_total", "已创建的订单数")?,
+            orders_filled: counter("orders_filled_total", "已成交的订单数")?,
+            orders_canceled: counter("orders_canceled_total", "已取消的订单数")?,
+            orders_failed: counter("orders_failed_total", "执行失败的订单数")?,
+            orders_expired: counter("orders_expired_total", "已过期的订单数")?,
+            orders_unfilled: counter("orders_unfilled_total", "执行超时后结束的订单数")?,
+            execution_timeouts: counter("execution_timeouts_total", "触发后执行超时的次数")?,
+            deviation_rejections: counter(
+                "deviation_rejections_total",
+                "成交价格偏离触发价格过大而放弃执行的次数",
+            )?,
+            open_orders: IntGauge::new("open_orders", "等待触发的订单数")?,
+            price_cache_age_seconds: Gauge::new(
+                "price_cache_age_seconds",
+                "缓存中最旧价格的年龄（秒）",
+            )?,
+            trigger_to_confirm_seconds: histogram(
+                "trigger_to_confirm_seconds",
+                "从触发到交易确认的耗时（秒）",
+                vec![0.5, 1.0, 2.0, 5.0, 10.0, 20.0, 30.0, 60.0],
+            )?,
+            quote_latency_seconds: histogram(
+                "quote_latency_seconds",
+                "Jupiter 报价耗时（秒）",
+                vec![0.05, 0.1, 0.25, 0.5, 1.0, 2.0, 5.0],
+            )?,
+            quote_retries: counter("quote_retries_total", "报价失败后的重试次数")?,
+            bundle_status: IntCounterVec::new(
+                Opts::new("bundle_status_total", "bundle 确认结果"),
+                &["status"],
+            )?,
+            audit_events_dropped: counter("audit_events_dropped_total", "丢失的订单审计事件数")?,
+            legacy_key_decryptions: counter(
+                "legacy_key_decryptions_total",
+                "用旧的固定密钥解密的密文数",
+            )?,
+            registry: registry.clone(),
+        };
+        registry.register(Box::new(metrics.open_orders.clone()))?;
+        registry.register(Box::new(metrics.price_cache_age_seconds.clone()))?;
+        registry.register(Box::new(metrics.bundle_status.clone()))?;
+        Ok(metrics)
+    }
+
+    /// 以 Prometheus 文本格式输出全部指标
+    pub fn render(&self) -> Result<String> {
+        let mut buffer = vec![];
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8(buffer)?)
+    }
+}
+
+/// 全局指标
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
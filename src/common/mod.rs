@@ -1,7 +1,27 @@
+pub mod audit;
+pub mod auth;
+pub mod config;
+pub mod db;
+pub mod dispatcher;
+pub mod dns;
 pub mod encode;
+pub mod events;
+pub mod keys;
+pub mod metrics;
+pub mod nonce;
+pub mod persist;
+pub mod prepared;
+pub mod price;
+pub mod price_source;
+pub mod quote_feed;
+pub mod rate_limit;
+pub mod retry;
+pub mod session;
+pub mod snapshot;
+pub mod tax_policy;
+pub mod token_info;
+pub mod treasury;
 pub mod types;
+pub mod units;
 pub mod utils;
-
-/// 注意！！！
-/// 此处需要配置真正的加密私钥
-pub const AES_KEY: [u8; 32] = [1; 32];
+pub mod webhook;
@@ -0,0 +1,182 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    env,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{anyhow, Result};
+use solana_sdk::{pubkey::Pubkey, signature::Keypair};
+use uuid::Uuid;
+
+use crate::common::session::parse_keypair;
+
+/// durable nonce 账户池
+///
+/// 预签名的交易使用 nonce 代替 blockhash，在 nonce 被推进之前一直有效。每笔等待触发的预签名订单
+/// 租用一个 nonce 账户，订单成交、取消或失败后归还。所有 nonce 账户的 authority 为同一个服务端密钥，
+/// 由服务端在提交时补充 authority 签名。
+#[derive(Clone)]
+pub struct NoncePool {
+    authority: Arc<Keypair>,
+    inner: Arc<Mutex<NoncePoolInner>>,
+}
+
+struct NoncePoolInner {
+    free: VecDeque<Pubkey>,
+    leased: HashMap<Uuid, Pubkey>,
+}
+
+impl NoncePool {
+    pub fn new(authority: Keypair, accounts: Vec<Pubkey>) -> NoncePool {
+        NoncePool {
+            authority: Arc::new(authority),
+            inner: Arc::new(Mutex::new(NoncePoolInner {
+                free: accounts.into(),
+                leased: HashMap::new(),
+            })),
+        }
+    }
+
+    /// 从环境变量 `NONCE_AUTHORITY_PK`（base58 私钥）和 `NONCE_ACCOUNTS`（逗号分隔）读取，未配置时返回 None
+    pub fn from_env() -> Result<Option<NoncePool>> {
+        let (Ok(authority), Ok(accounts)) =
+            (env::var("NONCE_AUTHORITY_PK"), env::var("NONCE_ACCOUNTS"))
+        else {
+            return Ok(None);
+        };
+        if authority.trim().is_empty() && accounts.trim().is_empty() {
+            return Ok(None);
+        }
+        let authority = parse_keypair(authority.trim())
+            .map_err(|_| anyhow!("NONCE_AUTHORITY_PK 不是有效的 base58 私钥"))?;
+        let accounts = accounts
+            .split(',')
+            .map(|account| account.trim())
+            .filter(|account| !account.is_empty())
+            .map(|account| {
+                account
+                    .parse::<Pubkey>()
+                    .map_err(|_| anyhow!("nonce 账户地址无效 {}", account))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Some(NoncePool::new(authority, accounts)))
+    }
+
+    /// 所有 nonce 账户的 authority
+    pub fn authority(&self) -> Arc<Keypair> {
+        self.authority.clone()
+    }
+
+    /// 为 `id` 租用一个 nonce 账户，已租用时返回同一个账户，池中没有空闲账户时返回 None
+    pub fn lease(&self, id: Uuid) -> Option<Pubkey> {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(account) = inner.leased.get(&id) {
+            return Some(*account);
+        }
+        let account = inner.free.pop_front()?;
+        inner.leased.insert(id, account);
+        Some(account)
+    }
+
+    /// 为 `id` 租用指定的 nonce 账户，用于重启后恢复已签名的订单，账户不空闲时返回 false
+    pub fn reserve(&self, id: Uuid, account: Pubkey) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.leased.get(&id) == Some(&account) {
+            return true;
+        }
+        match inner.free.iter().position(|free| *free == account) {
+            Some(index) => {
+                inner.free.remove(index);
+                inner.leased.insert(id, account);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 归还 `id` 租用的 nonce 账户，返回是否有租用
+    pub fn release(&self, id: &Uuid) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        match inner.leased.remove(id) {
+            Some(account) => {
+                inner.free.push_back(account);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 空闲的 nonce 账户数量
+    pub fn available(&self) -> usize {
+        self.inner.lock().unwrap().free.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(size: usize) -> (NoncePool, Vec<Pubkey>) {
+        let accounts: Vec<Pubkey> = (0..size).map(|_| Pubkey::new_unique()).collect();
+        (NoncePool::new(Keypair::new(), accounts.clone()), accounts)
+    }
+
+    #[test]
+    fn lease_is_stable_per_id() {
+        let (pool, accounts) = pool(2);
+        let id = Uuid::new_v4();
+        let account = pool.lease(id).unwrap();
+        assert_eq!(account, accounts[0]);
+        assert_eq!(pool.lease(id), Some(account));
+        assert_eq!(pool.available(), 1);
+
+        let other = pool.lease(Uuid::new_v4()).unwrap();
+        assert_ne!(other, account);
+        assert_eq!(pool.available(), 0);
+    }
+
+    #[test]
+    fn exhausted_pool_returns_none_until_release() {
+        let (pool, _) = pool(1);
+        let first = Uuid::new_v4();
+        let account = pool.lease(first).unwrap();
+        let second = Uuid::new_v4();
+        assert_eq!(pool.lease(second), None);
+
+        assert!(pool.release(&first));
+        assert_eq!(pool.lease(second), Some(account));
+        assert_eq!(pool.available(), 0);
+    }
+
+    /// 重复归还不会让同一个账户在池中出现两次
+    #[test]
+    fn double_release_is_a_no_op() {
+        let (pool, _) = pool(1);
+        let id = Uuid::new_v4();
+        pool.lease(id).unwrap();
+        assert!(pool.release(&id));
+        assert!(!pool.release(&id));
+        assert_eq!(pool.available(), 1);
+
+        assert!(pool.lease(Uuid::new_v4()).is_some());
+        assert_eq!(pool.lease(Uuid::new_v4()), None);
+    }
+
+    #[test]
+    fn releasing_an_unknown_id_returns_false() {
+        let (pool, _) = pool(1);
+        assert!(!pool.release(&Uuid::new_v4()));
+        assert_eq!(pool.available(), 1);
+    }
+
+    #[test]
+    fn reserve_takes_a_specific_free_account() {
+        let (pool, accounts) = pool(2);
+        let id = Uuid::new_v4();
+        assert!(pool.reserve(id, accounts[1]));
+        assert!(pool.reserve(id, accounts[1]));
+        assert_eq!(pool.lease(id), Some(accounts[1]));
+        assert!(!pool.reserve(Uuid::new_v4(), accounts[1]));
+        assert_eq!(pool.available(), 1);
+    }
+}
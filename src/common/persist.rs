@@ -0,0 +1,443 @@
+use std::{
+    fs::OpenOptions,
+    io::{BufRead, BufReader, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex as StdMutex,
+    },
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{self, error::TrySendError};
+use uuid::Uuid;
+
+use crate::common::{snapshot::ResumeState, types::Order};
+
+/// 写入队列容量
+const QUEUE_CAPACITY: usize = 1024;
+/// 单条记录写入数据库的最大重试次数
+const MAX_WRITE_RETRIES: u32 = 3;
+/// 降级状态下探测数据库恢复的间隔
+const PROBE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 需要持久化的订单变更
+///
+/// 订单进入终态时统一写入 `Finalized`；`Canceled`、`Filled`、`PartiallyFilled` 和 `Failed`
+/// 保留用于回放旧版本写入的日志。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PersistRecord {
+    /// 新订单
+    Placed(Order),
+    /// 订单被取消
+    Canceled(Uuid),
+    /// 交易已发送，记录交易签名
+    Triggered(Uuid, String),
+    /// 订单成交
+    Filled(Uuid),
+    /// 分批执行的订单部分成交，记录已成交数量
+    PartiallyFilled(Uuid, u64),
+    /// 重复订单完成一次成交，记录交易签名和重新挂单后的订单
+    RepeatFilled(Order, String),
+    /// 订单执行失败
+    Failed(Uuid, String),
+    /// 订单进入终态，记录订单的完整快照和结束时间（毫秒时间戳），之后订单只保存在存储中
+    Finalized(Order, u64),
+    /// 订单任务启动，记录进程意外退出后恢复执行所需的信息，订单进入终态后删除
+    Resumable(Uuid, ResumeState),
+}
+
+/// 订单存储后端，实现方需要保证写入是幂等的，因为日志回放可能重复写入同一条记录
+pub trait OrderStore: Send + Sync + 'static {
+    /// 写入一条记录
+    fn write(&self, record: &PersistRecord) -> Result<()>;
+    /// 检查存储是否可用
+    fn ping(&self) -> bool;
+}
+
+/// 持久化的写后缓冲
+///
+/// 所有写操作先进入有界队列，由后台任务异步写入存储，执行路径永远不会等待数据库。
+/// 数据库不可用时记录会追加到本地 JSONL 日志中，并将 `degraded` 置为 true；
+/// 后台任务定期探测数据库，恢复后按顺序回放日志并删除已回放的部分。
+///
+/// 日志只由后台任务写入。队列已满时记录暂存在溢出缓冲中，后台任务处理完队列中更早的记录后
+/// 再将其写入日志，保证日志顺序与提交顺序一致。
+#[derive(Clone)]
+pub struct PersistQueue {
+    tx: mpsc::Sender<PersistRecord>,
+    overflow: Arc<StdMutex<Vec<PersistRecord>>>,
+    journal: Arc<Journal>,
+    degraded: Arc<AtomicBool>,
+}
+
+impl PersistQueue {
+    /// 启动后台写入任务
+    pub fn spawn(store: Arc<dyn OrderStore>, journal_path: PathBuf) -> PersistQueue {
+        let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+        let overflow = Arc::new(StdMutex::new(vec![]));
+        let journal = Arc::new(Journal { path: journal_path });
+        let degraded = Arc::new(AtomicBool::new(false));
+        tokio::spawn(writer(
+            store,
+            rx,
+            overflow.clone(),
+            journal.clone(),
+            degraded.clone(),
+        ));
+        PersistQueue {
+            tx,
+            overflow,
+            journal,
+            degraded,
+        }
+    }
+
+    /// 提交一条记录，不会阻塞；队列已满时放入溢出缓冲，由后台任务写入本地日志
+    pub fn enqueue(&self, record: PersistRecord) {
+        let mut overflow = self.overflow.lock().unwrap();
+        // 溢出缓冲非空时后续记录也必须排在它后面
+        if !overflow.is_empty() {
+            overflow.push(record);
+            return;
+        }
+        match self.tx.try_send(record) {
+            Ok(()) => {}
+            Err(TrySendError::Full(record)) => {
+                self.degraded.store(true, Ordering::SeqCst);
+                overflow.push(record);
+            }
+            Err(TrySendError::Closed(record)) => {
+                // 后台任务已经退出，不会再有并发写入，只能直接写日志留待下次启动回放
+                if let Err(e) = self.journal.append(&record) {
+                    println!("写入持久化日志失败 {:?}", e);
+                }
+            }
+        }
+    }
+
+    /// 存储是否处于降级状态
+    pub fn is_degraded(&self) -> bool {
+        self.degraded.load(Ordering::SeqCst)
+    }
+}
+
+/// 追加写入的本地日志，每行一条 JSON 记录
+struct Journal {
+    path: PathBuf,
+}
+
+impl Journal {
+    fn append(&self, record: &PersistRecord) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(record)?)?;
+        Ok(())
+    }
+
+    fn read_all(&self) -> Result<Vec<PersistRecord>> {
+        let file = match OpenOptions::new().read(true).open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+        let mut records = vec![];
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            records.push(serde_json::from_str(&line)?);
+        }
+        Ok(records)
+    }
+
+    /// 删除前 `count` 条记录，保留之后追加的内容
+    fn drop_prefix(&self, count: usize) -> Result<()> {
+        if count == 0 {
+            return Ok(());
+        }
+        let file = OpenOptions::new().read(true).open(&self.path)?;
+        let mut skipped = 0;
+        let mut rest = String::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if skipped < count {
+                skipped += 1;
+            } else {
+                rest.push_str(&line);
+                rest.push('\n');
+            }
+        }
+        // 先写临时文件再替换，中途退出时原日志保持完整
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, rest)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// 队列排空后取出溢出缓冲中的记录，持有锁期间 `enqueue` 不会再向队列写入
+fn take_overflow(
+    rx: &mpsc::Receiver<PersistRecord>,
+    overflow: &StdMutex<Vec<PersistRecord>>,
+) -> Vec<PersistRecord> {
+    let mut overflow = overflow.lock().unwrap();
+    if rx.is_empty() {
+        std::mem::take(&mut *overflow)
+    } else {
+        vec![]
+    }
+}
+
+async fn writer(
+    store: Arc<dyn OrderStore>,
+    mut rx: mpsc::Receiver<PersistRecord>,
+    overflow: Arc<StdMutex<Vec<PersistRecord>>>,
+    journal: Arc<Journal>,
+    degraded: Arc<AtomicBool>,
+) {
+    let mut probe = tokio::time::interval(PROBE_INTERVAL);
+    // 启动时先尝试回放上次遗留的日志
+    if !journal.read_all().map(|r| r.is_empty()).unwrap_or(true) {
+        degraded.store(true, Ordering::SeqCst);
+    }
+    loop {
+        tokio::select! {
+            record = rx.recv() => {
+                let Some(record) = record else { break };
+                write_record(&store, &journal, &degraded, record).await;
+                let spilled = take_overflow(&rx, &overflow);
+                if !spilled.is_empty() {
+                    // 溢出的记录不再逐条重试数据库，直接写日志等待回放
+                    degraded.store(true, Ordering::SeqCst);
+                    for record in spilled {
+                        write_record(&store, &journal, &degraded, record).await;
+                    }
+                }
+            }
+            _ = probe.tick() => {
+                if degraded.load(Ordering::SeqCst) {
+                    match replay(store.clone(), journal.clone()).await {
+                        Ok(count) => {
+                            println!("数据库已恢复，回放 {} 条日志记录", count);
+                            degraded.store(false, Ordering::SeqCst);
+                        }
+                        Err(e) => println!("数据库仍不可用 {:?}", e),
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn write_record(
+    store: &Arc<dyn OrderStore>,
+    journal: &Journal,
+    degraded: &AtomicBool,
+    record: PersistRecord,
+) {
+    // 降级期间直接写日志，保证回放顺序与提交顺序一致
+    if degraded.load(Ordering::SeqCst) {
+        if let Err(e) = journal.append(&record) {
+            println!("写入持久化日志失败 {:?}", e);
+        }
+        return;
+    }
+    if let Err(e) = write_with_retry(store.clone(), record.clone()).await {
+        println!("数据库写入失败，进入降级模式 {:?}", e);
+        degraded.store(true, Ordering::SeqCst);
+        if let Err(e) = journal.append(&record) {
+            println!("写入持久化日志失败 {:?}", e);
+        }
+    }
+}
+
+async fn write_with_retry(store: Arc<dyn OrderStore>, record: PersistRecord) -> Result<()> {
+    let mut attempt = 0;
+    loop {
+        let _store = store.clone();
+        let _record = record.clone();
+        let result = tokio::task::spawn_blocking(move || _store.write(&_record)).await?;
+        match result {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt + 1 >= MAX_WRITE_RETRIES => return Err(e),
+            Err(_) => {
+                attempt += 1;
+                tokio::time::sleep(Duration::from_millis(200 * 2u64.pow(attempt))).await;
+            }
+        }
+    }
+}
+
+async fn replay(store: Arc<dyn OrderStore>, journal: Arc<Journal>) -> Result<usize> {
+    let _store = store.clone();
+    if !tokio::task::spawn_blocking(move || _store.ping()).await? {
+        return Err(anyhow!("存储探测失败"));
+    }
+    let records = journal.read_all()?;
+    let mut count = 0;
+    for record in records {
+        if let Err(e) = write_with_retry(store.clone(), record).await {
+            // 只删除已经写入存储的部分，其余的下次继续回放
+            journal.drop_prefix(count)?;
+            return Err(e);
+        }
+        count += 1;
+    }
+    journal.drop_prefix(count)?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 内存中的存储，可以模拟数据库不可用或拒绝某条记录
+    #[derive(Default)]
+    struct MockStore {
+        available: AtomicBool,
+        reject: StdMutex<Option<Uuid>>,
+        written: StdMutex<Vec<Uuid>>,
+    }
+
+    impl MockStore {
+        fn new(available: bool) -> Arc<MockStore> {
+            Arc::new(MockStore {
+                available: AtomicBool::new(available),
+                ..Default::default()
+            })
+        }
+
+        fn written(&self) -> Vec<Uuid> {
+            self.written.lock().unwrap().clone()
+        }
+    }
+
+    impl OrderStore for MockStore {
+        fn write(&self, record: &PersistRecord) -> Result<()> {
+            let PersistRecord::Canceled(id) = record else {
+                return Err(anyhow!("意外的记录 {:?}", record));
+            };
+            if !self.ping() || *self.reject.lock().unwrap() == Some(*id) {
+                return Err(anyhow!("存储不可用"));
+            }
+            self.written.lock().unwrap().push(*id);
+            Ok(())
+        }
+
+        fn ping(&self) -> bool {
+            self.available.load(Ordering::SeqCst)
+        }
+    }
+
+    fn journal_path() -> PathBuf {
+        std::env::temp_dir().join(format!("persist-test-{}.jsonl", Uuid::new_v4()))
+    }
+
+    fn journal_ids(journal: &Journal) -> Vec<Uuid> {
+        journal
+            .read_all()
+            .unwrap()
+            .into_iter()
+            .map(|record| match record {
+                PersistRecord::Canceled(id) => id,
+                other => panic!("意外的记录 {:?}", other),
+            })
+            .collect()
+    }
+
+    fn paused_runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .start_paused(true)
+            .build()
+            .unwrap()
+    }
+
+    async fn wait_until(mut done: impl FnMut() -> bool) {
+        for _ in 0..1000 {
+            if done() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("等待超时");
+    }
+
+    #[test]
+    fn journal_survives_restart_and_replays_in_order() {
+        let path = journal_path();
+        let ids: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+
+        let down = MockStore::new(false);
+        paused_runtime().block_on(async {
+            let queue = PersistQueue::spawn(down.clone(), path.clone());
+            for id in &ids {
+                queue.enqueue(PersistRecord::Canceled(*id));
+            }
+            let journal = Journal { path: path.clone() };
+            wait_until(|| journal.read_all().map(|r| r.len()).unwrap_or(0) == ids.len()).await;
+            assert!(queue.is_degraded());
+        });
+        // 运行时随 block_on 返回后被丢弃，写入任务随之终止，相当于进程被杀掉
+        assert!(down.written().is_empty());
+
+        let up = MockStore::new(true);
+        paused_runtime().block_on(async {
+            let queue = PersistQueue::spawn(up.clone(), path.clone());
+            wait_until(|| up.written().len() == ids.len() && !queue.is_degraded()).await;
+        });
+        assert_eq!(up.written(), ids);
+        assert!(journal_ids(&Journal { path: path.clone() }).is_empty());
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn overflow_is_written_in_submission_order() {
+        let path = journal_path();
+        let store = MockStore::new(true);
+        let queue = PersistQueue::spawn(store.clone(), path.clone());
+        let ids: Vec<Uuid> = (0..QUEUE_CAPACITY + 10).map(|_| Uuid::new_v4()).collect();
+        // 写入任务在第一次 await 之前不会运行，队列必然被填满
+        for id in &ids {
+            queue.enqueue(PersistRecord::Canceled(*id));
+        }
+        assert!(queue.is_degraded());
+        assert_eq!(queue.overflow.lock().unwrap().len(), 10);
+
+        wait_until(|| store.written().len() == ids.len() && !queue.is_degraded()).await;
+        assert_eq!(store.written(), ids);
+        assert!(journal_ids(&Journal { path: path.clone() }).is_empty());
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failed_replay_only_drops_written_prefix() {
+        let path = journal_path();
+        let journal = Arc::new(Journal { path: path.clone() });
+        let ids: Vec<Uuid> = (0..4).map(|_| Uuid::new_v4()).collect();
+        for id in &ids {
+            journal.append(&PersistRecord::Canceled(*id)).unwrap();
+        }
+        let store = MockStore::new(true);
+        *store.reject.lock().unwrap() = Some(ids[2]);
+
+        assert!(replay(store.clone(), journal.clone()).await.is_err());
+        assert_eq!(store.written(), ids[..2]);
+        assert_eq!(journal_ids(&journal), ids[2..]);
+
+        *store.reject.lock().unwrap() = None;
+        assert_eq!(replay(store.clone(), journal.clone()).await.unwrap(), 2);
+        assert_eq!(store.written(), ids);
+        assert!(journal_ids(&journal).is_empty());
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,106 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose, Engine};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    message::VersionedMessage, pubkey::Pubkey, signature::Keypair, signer::Signer,
+    transaction::VersionedTransaction,
+};
+use uuid::Uuid;
+
+use crate::common::{types::OrderLeg, units::Bps};
+
+/// 待签名交易的保留时间，略长于 blockhash 的有效期（约 150 个区块）
+pub const PREPARED_ORDER_TTL: Duration = Duration::from_secs(120);
+
+/// 已生成、等待客户端签名的非托管订单
+pub struct PreparedOrder {
+    pub user: Pubkey,
+    pub leg: OrderLeg,
+    /// 生成交易时按税收策略解析的税率，已包含在交易中
+    pub tax_bps: Bps,
+    /// 发给客户端签名的交易消息，提交时必须完全一致
+    pub message: VersionedMessage,
+    /// 使用最新 blockhash 时的最后有效区块高度，使用 durable nonce 时为 None
+    pub last_valid_block_height: Option<u64>,
+    /// 租用的 nonce 账户
+    pub nonce_account: Option<Pubkey>,
+    pub created_at: Instant,
+}
+
+impl PreparedOrder {
+    pub fn is_expired(&self) -> bool {
+        self.created_at.elapsed() > PREPARED_ORDER_TTL
+    }
+}
+
+/// 客户端签名交易的有效期
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum SignedTxLifetime {
+    /// 使用最新的 blockhash，超过该区块高度后失效
+    Blockhash { last_valid_block_height: u64 },
+    /// 使用 durable nonce，nonce 被推进后失效
+    Nonce { nonce_account: Pubkey },
+}
+
+/// `POST /prepare_order` 的返回
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct PreparedTransaction {
+    /// 提交签名交易时使用
+    pub prepare_id: Uuid,
+    /// base64 编码的未签名 `VersionedTransaction`
+    pub transaction: String,
+    /// 超过该区块高度后交易失效，需要重新生成并签名；使用 durable nonce 时为 None，交易不会过期
+    pub last_valid_block_height: Option<u64>,
+    /// 交易使用的 nonce 账户
+    pub nonce_account: Option<String>,
+}
+
+pub fn encode_transaction(tx: &VersionedTransaction) -> Result<String> {
+    Ok(general_purpose::STANDARD.encode(bincode::serialize(tx)?))
+}
+
+pub fn decode_transaction(tx_bs64: &str) -> Result<VersionedTransaction> {
+    let bytes = general_purpose::STANDARD
+        .decode(tx_bs64)
+        .map_err(|_| anyhow!("交易不是有效的 base64"))?;
+    bincode::deserialize(&bytes).map_err(|_| anyhow!("交易格式错误"))
+}
+
+/// 校验客户端签名的交易：消息与生成时一致、手续费支付者为下单用户、用户签名有效
+///
+/// 使用 durable nonce 时交易还需要 nonce authority 的签名，由 `cosigner` 补充。返回签名完整的交易。
+pub fn verify_signed_transaction(
+    mut signed: VersionedTransaction,
+    expected: &VersionedMessage,
+    user: &Pubkey,
+    cosigner: Option<&Keypair>,
+) -> Result<VersionedTransaction> {
+    if signed.message != *expected {
+        return Err(anyhow!("交易内容与生成的交易不一致"));
+    }
+    let signers = signed.message.static_account_keys()
+        [..signed.message.header().num_required_signatures as usize]
+        .to_vec();
+    if signers.first() != Some(user) {
+        return Err(anyhow!("交易的手续费支付者不是下单用户"));
+    }
+    if signed.signatures.len() != signers.len() {
+        return Err(anyhow!("交易签名数量错误"));
+    }
+    let message_bytes = signed.message.serialize();
+    if !signed.signatures[0].verify(user.as_ref(), &message_bytes) {
+        return Err(anyhow!("交易签名无效"));
+    }
+    if let Some(cosigner) = cosigner {
+        if let Some(index) = signers.iter().position(|key| *key == cosigner.pubkey()) {
+            signed.signatures[index] = cosigner.sign_message(&message_bytes);
+        }
+    }
+    if !signed.verify_with_results().into_iter().all(|ok| ok) {
+        return Err(anyhow!("交易签名无效"));
+    }
+    Ok(signed)
+}
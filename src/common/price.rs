@@ -0,0 +1,405 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Result};
+use solana_sdk::pubkey::Pubkey;
+use tokio::sync::watch;
+
+use crate::common::price_source::{PriceQuote, PriceSource};
+
+/// 连续多少轮未返回价格后，认为价格源不支持该代币
+pub(crate) const MAX_PRICE_MISSES: u32 = 3;
+
+/// 某一轮刷新后的全部价格
+#[derive(Debug, Default)]
+pub struct PriceSnapshot {
+    pub prices: HashMap<String, PriceQuote>,
+    /// 每个代币连续未返回价格的次数
+    pub misses: HashMap<String, u32>,
+}
+
+/// 所有订单共享的价格缓存
+///
+/// 由一个后台任务按固定间隔向 [`PriceSource`] 批量请求所有被订阅代币的价格，并通过 watch 通道广播，
+/// 订单任务只读取缓存，不再各自请求价格接口。代币按订阅数计数，最后一个订阅释放后停止轮询。
+/// 无论有多少订单，每个代币每轮只请求一次；触发后的执行并发由订单簿的 `swap_permits` 限制。
+#[derive(Clone)]
+pub struct PriceCache {
+    subscribers: Arc<Mutex<HashMap<String, usize>>>,
+    rx: watch::Receiver<Arc<PriceSnapshot>>,
+}
+
+impl PriceCache {
+    /// 启动后台轮询任务
+    pub fn spawn(source: Arc<dyn PriceSource>, interval: Duration) -> PriceCache {
+        let subscribers = Arc::new(Mutex::new(HashMap::new()));
+        let (tx, rx) = watch::channel(Arc::new(PriceSnapshot::default()));
+        tokio::spawn(poll_prices(source, interval, subscribers.clone(), tx));
+        PriceCache { subscribers, rx }
+    }
+
+    /// 订阅代币价格，返回的订阅在释放时自动取消
+    pub fn subscribe(&self, mint: &str) -> PriceSubscription {
+        *self
+            .subscribers
+            .lock()
+            .unwrap()
+            .entry(mint.to_string())
+            .or_insert(0) += 1;
+        PriceSubscription {
+            mint: mint.to_string(),
+            subscribers: self.subscribers.clone(),
+            rx: self.rx.clone(),
+        }
+    }
+
+    /// 每一轮刷新后的价格快照，见 [`TriggerDispatcher`](crate::common::dispatcher::TriggerDispatcher)
+    pub fn snapshots(&self) -> watch::Receiver<Arc<PriceSnapshot>> {
+        self.rx.clone()
+    }
+
+    /// 读取缓存中的价格，不会发起请求
+    pub fn get(&self, mint: &str) -> Option<PriceQuote> {
+        self.rx.borrow().prices.get(mint).copied()
+    }
+
+    /// 缓存中最旧价格的年龄（毫秒），缓存为空时返回 None
+    pub fn max_age_ms(&self) -> Option<u64> {
+        self.rx
+            .borrow()
+            .prices
+            .values()
+            .map(PriceQuote::age_ms)
+            .max()
+    }
+}
+
+/// 单个代币的价格订阅
+pub struct PriceSubscription {
+    mint: String,
+    subscribers: Arc<Mutex<HashMap<String, usize>>>,
+    rx: watch::Receiver<Arc<PriceSnapshot>>,
+}
+
+impl PriceSubscription {
+    /// 等待下一轮包含该代币价格的刷新并返回最新价格
+    pub async fn next(&mut self) -> Result<PriceQuote> {
+        loop {
+            self.rx
+                .changed()
+                .await
+                .map_err(|_| anyhow!("价格缓存已停止"))?;
+            if let Some(price) = self.lookup()? {
+                return Ok(price);
+            }
+        }
+    }
+
+    /// 返回当前缓存中的价格
+    pub fn latest(&self) -> Result<PriceQuote> {
+        self.lookup()?
+            .ok_or_else(|| anyhow!("代币 {} 暂无价格", self.mint))
+    }
+
+    /// 价格源连续多轮未返回该代币时返回错误，避免订单无限等待一个不存在的价格；
+    /// 刚订阅、尚未轮询到时返回 None
+    pub fn lookup(&self) -> Result<Option<PriceQuote>> {
+        let snapshot = self.rx.borrow();
+        if snapshot.misses.get(&self.mint).copied().unwrap_or(0) >= MAX_PRICE_MISSES {
+            return Err(anyhow!("价格源不支持代币 {}", self.mint));
+        }
+        Ok(snapshot.prices.get(&self.mint).copied())
+    }
+}
+
+impl Drop for PriceSubscription {
+    fn drop(&mut self) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        if let Some(count) = subscribers.get_mut(&self.mint) {
+            *count -= 1;
+            if *count == 0 {
+                subscribers.remove(&self.mint);
+            }
+        }
+    }
+}
+
+async fn poll_prices(
+    source: Arc<dyn PriceSource>,
+    interval: Duration,
+    subscribers: Arc<Mutex<HashMap<String, usize>>>,
+    tx: watch::Sender<Arc<PriceSnapshot>>,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    // 价格源重试时一轮请求可能超过轮询间隔，之后顺延而不是连续补发错过的轮次
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        let mints: Vec<String> = subscribers.lock().unwrap().keys().cloned().collect();
+        if mints.is_empty() {
+            continue;
+        }
+        // 订单创建时已校验 mint，无法解析的视为价格源未返回
+        let ids: Vec<Pubkey> = mints.iter().filter_map(|m| m.parse().ok()).collect();
+        let fetched = match source.get_prices(&ids).await {
+            Ok(fetched) => fetched,
+            Err(e) => {
+                println!("批量获取价格失败 {:?}", e);
+                continue;
+            }
+        };
+
+        let previous = tx.borrow().clone();
+        let mut snapshot = PriceSnapshot::default();
+        for mint in mints {
+            let quote = mint
+                .parse::<Pubkey>()
+                .ok()
+                .and_then(|id| fetched.get(&id).copied());
+            match quote {
+                Some(quote) => {
+                    snapshot.prices.insert(mint, quote);
+                }
+                None => {
+                    let misses = previous.misses.get(&mint).copied().unwrap_or(0) + 1;
+                    // 保留上一轮的价格，由 misses 判断是否可用
+                    if let Some(point) = previous.prices.get(&mint) {
+                        snapshot.prices.insert(mint.clone(), *point);
+                    }
+                    snapshot.misses.insert(mint, misses);
+                }
+            }
+        }
+        if tx.send(Arc::new(snapshot)).is_err() {
+            break;
+        }
+    }
+}
+
+pub(crate) fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use async_trait::async_trait;
+    use tokio::sync::{oneshot, Semaphore};
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::common::{
+        dispatcher::{PriceKey, TriggerDispatcher, TriggerLevel},
+        events::EventBus,
+        types::TriggerCondition,
+    };
+
+    /// 每次请求价格上涨 `step`，记录每次请求的代币数
+    struct SteppedSource {
+        price: Mutex<f64>,
+        step: f64,
+        requests: Mutex<Vec<usize>>,
+    }
+
+    impl SteppedSource {
+        fn new(price: f64, step: f64) -> Arc<SteppedSource> {
+            Arc::new(SteppedSource {
+                price: Mutex::new(price),
+                step,
+                requests: Mutex::new(vec![]),
+            })
+        }
+
+        fn requests(&self) -> Vec<usize> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl PriceSource for SteppedSource {
+        fn name(&self) -> &'static str {
+            "stepped"
+        }
+
+        async fn get_prices(&self, mints: &[Pubkey]) -> Result<HashMap<Pubkey, PriceQuote>> {
+            self.requests.lock().unwrap().push(mints.len());
+            let mut price = self.price.lock().unwrap();
+            *price += self.step;
+            let quote = PriceQuote {
+                price: *price,
+                confidence: None,
+                confidence_level: None,
+                last_swap_price: None,
+                timestamp_ms: now_ms(),
+                source: "stepped",
+            };
+            Ok(mints.iter().map(|mint| (*mint, quote)).collect())
+        }
+    }
+
+    fn mints(count: usize) -> Vec<String> {
+        (0..count)
+            .map(|_| Pubkey::new_unique().to_string())
+            .collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn one_request_per_round_for_thousands_of_subscribers() {
+        let source = SteppedSource::new(1.0, 0.0);
+        let cache = PriceCache::spawn(source.clone(), Duration::from_millis(100));
+        let mints = mints(20);
+        let mut subscriptions: Vec<PriceSubscription> = (0..5_000)
+            .map(|i| cache.subscribe(&mints[i % mints.len()]))
+            .collect();
+
+        for _ in 0..3 {
+            for subscription in subscriptions.iter_mut() {
+                subscription.next().await.unwrap();
+            }
+        }
+        // 每轮一次批量请求，包含全部代币且不重复
+        assert_eq!(source.requests(), vec![20, 20, 20]);
+
+        drop(subscriptions);
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(source.requests().len(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn thousands_of_orders_trigger_with_bounded_execution() {
+        const ORDERS: usize = 2_000;
+        const MAX_CONCURRENT_SWAPS: usize = 8;
+        let source = SteppedSource::new(0.9, 0.01);
+        let cache = PriceCache::spawn(source.clone(), Duration::from_millis(100));
+        let dispatcher = TriggerDispatcher::spawn(&cache, Duration::from_secs(60), EventBus::new());
+        let mints = mints(10);
+        let permits = Arc::new(Semaphore::new(MAX_CONCURRENT_SWAPS));
+        let started = tokio::time::Instant::now();
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        let mut tasks = vec![];
+        for i in 0..ORDERS {
+            let mint = &mints[i % mints.len()];
+            // 订单任务持有订阅，价格缓存才会轮询该代币
+            let subscription = cache.subscribe(mint);
+            let level = TriggerLevel {
+                key: PriceKey::Usd(mint.clone()),
+                condition: TriggerCondition::Above,
+                price: 1.0 + (i % 20) as f64 / 100.0,
+            };
+            let (cancel_tx, mut cancel) = oneshot::channel::<()>();
+            let dispatcher = dispatcher.clone();
+            let permits = permits.clone();
+            let running = running.clone();
+            let peak = peak.clone();
+            let task = tokio::spawn(async move {
+                let _subscription = subscription;
+                tokio::select! {
+                    biased;
+                    _ = &mut cancel => return false,
+                    trigger = dispatcher.wait(&level, Uuid::new_v4(), "owner", 0) => trigger.unwrap(),
+                };
+                let _permit = permits.acquire().await.unwrap();
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                // 模拟报价和发送交易
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                true
+            });
+            // 每 4 笔撤销 1 笔，撤单后订单不会再执行；其余订单保留撤单通道
+            let cancel_tx = if i % 4 == 0 {
+                cancel_tx.send(()).unwrap();
+                None
+            } else {
+                Some(cancel_tx)
+            };
+            tasks.push((i, task, cancel_tx));
+        }
+
+        let mut executed = 0;
+        for (i, task, _cancel_tx) in tasks {
+            let done = task.await.unwrap();
+            assert_eq!(done, i % 4 != 0, "订单 {}", i);
+            executed += done as usize;
+        }
+        assert_eq!(executed, ORDERS - ORDERS / 4);
+        assert!(peak.load(Ordering::SeqCst) <= MAX_CONCURRENT_SWAPS);
+        // 触发和撤销的订单都已离开分发器的索引
+        assert!(dispatcher.is_empty());
+        // 所有订单共享每轮一次的批量请求
+        let requests = source.requests();
+        assert!(requests.iter().all(|&count| count <= mints.len()));
+        let rounds = started.elapsed().as_millis() / 100 + 1;
+        assert!(
+            requests.len() as u128 <= rounds,
+            "{} 轮请求了 {} 次",
+            rounds,
+            requests.len()
+        );
+    }
+
+    /// 第一次请求耗时 `stall`，记录每次请求开始的时间
+    struct StallingSource {
+        stall: Duration,
+        calls: Mutex<Vec<tokio::time::Instant>>,
+    }
+
+    #[async_trait]
+    impl PriceSource for StallingSource {
+        fn name(&self) -> &'static str {
+            "stalling"
+        }
+
+        async fn get_prices(&self, mints: &[Pubkey]) -> Result<HashMap<Pubkey, PriceQuote>> {
+            let first = {
+                let mut calls = self.calls.lock().unwrap();
+                calls.push(tokio::time::Instant::now());
+                calls.len() == 1
+            };
+            if first {
+                tokio::time::sleep(self.stall).await;
+            }
+            let quote = PriceQuote {
+                price: 1.0,
+                confidence: None,
+                confidence_level: None,
+                last_swap_price: None,
+                timestamp_ms: now_ms(),
+                source: "stalling",
+            };
+            Ok(mints.iter().map(|mint| (*mint, quote)).collect())
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn slow_poll_does_not_burst_missed_ticks() {
+        let interval = Duration::from_millis(100);
+        let source = Arc::new(StallingSource {
+            stall: Duration::from_secs(1),
+            calls: Mutex::new(vec![]),
+        });
+        let cache = PriceCache::spawn(source.clone(), interval);
+        let mut subscription = cache.subscribe(&Pubkey::new_unique().to_string());
+        for _ in 0..5 {
+            subscription.next().await.unwrap();
+        }
+
+        let calls = source.calls.lock().unwrap().clone();
+        assert!(calls.len() >= 5);
+        // 第一轮请求耗时 1 秒，期间错过约 10 个轮次
+        assert!(calls[1] - calls[0] >= Duration::from_secs(1));
+        // 错过的轮次不会连续补发，之后仍按间隔请求
+        for pair in calls[1..].windows(2) {
+            assert!(pair[1] - pair[0] >= interval, "{:?}", pair[1] - pair[0]);
+        }
+    }
+}
@@ -0,0 +1,550 @@
+use std::{collections::HashMap, str::FromStr, sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use reqwest::Client;
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::Value;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::common::{
+    price::now_ms,
+    utils::{get_prices, PriceDatum, PriceRequestPolicy, JUPITER_PRICE_URL},
+};
+
+const BIRDEYE_PRICE_URL: &str = "https://public-api.birdeye.so/defi/multi_price";
+
+/// 价格源返回的一条价格
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct PriceQuote {
+    /// USD 价格
+    pub price: f64,
+    /// 价格的置信区间（USD），价格源未提供时为 None
+    pub confidence: Option<f64>,
+    /// 价格源给出的置信等级，价格源未提供时为 None
+    pub confidence_level: Option<ConfidenceLevel>,
+    /// 最近一次成交的价格（USD），价格源未提供时为 None
+    pub last_swap_price: Option<f64>,
+    /// 价格的时间（unix 毫秒），价格源未提供时为获取时间
+    pub timestamp_ms: u64,
+    /// 价格来源
+    pub source: &'static str,
+}
+
+/// 价格的置信等级
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfidenceLevel {
+    High,
+    Medium,
+    Low,
+}
+
+impl FromStr for ConfidenceLevel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "high" => Ok(ConfidenceLevel::High),
+            "medium" => Ok(ConfidenceLevel::Medium),
+            "low" => Ok(ConfidenceLevel::Low),
+            other => Err(anyhow!("未知的置信等级 {}", other)),
+        }
+    }
+}
+
+impl PriceQuote {
+    /// 距离价格时间的毫秒数
+    pub fn age_ms(&self) -> u64 {
+        now_ms().saturating_sub(self.timestamp_ms)
+    }
+
+    /// 价格未超过 `max_age` 时返回价格，过期的价格不用于触发判断
+    pub fn fresh_price(&self, max_age: Duration) -> Option<f64> {
+        let age_ms = self.age_ms();
+        if age_ms > max_age.as_millis() as u64 {
+            println!("{} 的价格已 {}ms 未更新，跳过", self.source, age_ms);
+            return None;
+        }
+        Some(self.price)
+    }
+}
+
+/// 代币 USD 价格的来源
+///
+/// 价格源没有返回的代币不会出现在结果中，由调用方决定如何处理。
+#[async_trait]
+pub trait PriceSource: Send + Sync + 'static {
+    /// 价格源名称，用于日志和接口返回
+    fn name(&self) -> &'static str;
+
+    /// 一次请求批量获取多个代币的价格
+    async fn get_prices(&self, mints: &[Pubkey]) -> Result<HashMap<Pubkey, PriceQuote>>;
+}
+
+/// Jupiter 价格接口（`api.jup.ag/price/v2`），默认的价格源
+///
+/// `show_extra_info` 为 true 时请求附加信息：置信区间取报价买卖价差的一半，价格时间取报价时间，
+/// 同时带上置信等级和最近一次成交的价格。
+///
+/// 请求超时和暂时性错误的重试由 [`PriceRequestPolicy`] 控制，默认使用 [`PriceRequestPolicy::default`]。
+pub struct JupiterPriceSource {
+    http: Arc<Client>,
+    url: String,
+    show_extra_info: bool,
+    request_policy: PriceRequestPolicy,
+}
+
+impl JupiterPriceSource {
+    pub fn new(http: Arc<Client>, url: String, show_extra_info: bool) -> JupiterPriceSource {
+        JupiterPriceSource {
+            http,
+            url,
+            show_extra_info,
+            request_policy: PriceRequestPolicy::default(),
+        }
+    }
+
+    pub fn with_request_policy(mut self, request_policy: PriceRequestPolicy) -> JupiterPriceSource {
+        self.request_policy = request_policy;
+        self
+    }
+
+    fn quote(&self, datum: &PriceDatum, fetched_at_ms: u64) -> Option<PriceQuote> {
+        let extra = datum.extra_info.as_ref();
+        Some(PriceQuote {
+            price: datum.price?,
+            confidence: datum.confidence(),
+            confidence_level: extra
+                .and_then(|extra| extra.confidence_level.as_deref())
+                .and_then(|level| level.parse().ok()),
+            last_swap_price: datum.last_swap_price(),
+            // 报价时间不会晚于获取时间
+            timestamp_ms: datum
+                .quoted_at_ms()
+                .map_or(fetched_at_ms, |quoted_at| quoted_at.min(fetched_at_ms)),
+            source: self.name(),
+        })
+    }
+}
+
+#[async_trait]
+impl PriceSource for JupiterPriceSource {
+    fn name(&self) -> &'static str {
+        "jupiter"
+    }
+
+    async fn get_prices(&self, mints: &[Pubkey]) -> Result<HashMap<Pubkey, PriceQuote>> {
+        let ids: Vec<String> = mints.iter().map(|m| m.to_string()).collect();
+        let ids: Vec<&str> = ids.iter().map(|m| m.as_str()).collect();
+        let prices = get_prices(
+            self.http.clone(),
+            &self.url,
+            &ids,
+            self.show_extra_info,
+            self.request_policy,
+        )
+        .await?;
+        // 未请求附加信息时 Jupiter 不返回价格时间，以获取时间为准
+        let fetched_at_ms = now_ms();
+        Ok(mints
+            .iter()
+            .filter_map(|mint| {
+                let quote = self.quote(prices.get(&mint.to_string())?, fetched_at_ms)?;
+                Some((*mint, quote))
+            })
+            .collect())
+    }
+}
+
+/// Birdeye 价格接口（`/defi/multi_price`），需要 API key
+pub struct BirdeyePriceSource {
+    http: Arc<Client>,
+    api_key: String,
+}
+
+impl BirdeyePriceSource {
+    pub fn new(http: Arc<Client>, api_key: String) -> BirdeyePriceSource {
+        BirdeyePriceSource { http, api_key }
+    }
+}
+
+#[async_trait]
+impl PriceSource for BirdeyePriceSource {
+    fn name(&self) -> &'static str {
+        "birdeye"
+    }
+
+    async fn get_prices(&self, mints: &[Pubkey]) -> Result<HashMap<Pubkey, PriceQuote>> {
+        if mints.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let list: Vec<String> = mints.iter().map(|m| m.to_string()).collect();
+        let resp = self
+            .http
+            .get(BIRDEYE_PRICE_URL)
+            .query(&[("list_address", list.join(","))])
+            .header("X-API-KEY", &self.api_key)
+            .header("x-chain", "solana")
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            return Err(anyhow!("Birdeye 价格接口返回错误状态 {}", resp.status()));
+        }
+        let resp_json: Value = resp.json().await?;
+        if resp_json["success"].as_bool() == Some(false) {
+            return Err(anyhow!("Birdeye 价格接口返回错误 {}", resp_json));
+        }
+        let fetched_at_ms = now_ms();
+        let mut prices = HashMap::new();
+        for mint in mints {
+            // 未知代币的条目为 null
+            let data = &resp_json["data"][mint.to_string()];
+            let Some(price) = data["value"].as_f64() else {
+                continue;
+            };
+            let timestamp_ms = data["updateUnixTime"]
+                .as_u64()
+                .map(|secs| secs * 1000)
+                .unwrap_or(fetched_at_ms);
+            prices.insert(
+                *mint,
+                PriceQuote {
+                    price,
+                    confidence: None,
+                    confidence_level: None,
+                    last_swap_price: None,
+                    timestamp_ms,
+                    source: self.name(),
+                },
+            );
+        }
+        Ok(prices)
+    }
+}
+
+/// 按顺序组合多个价格源
+///
+/// 先请求第一个价格源，请求失败或未返回的代币再向下一个价格源请求，直到全部代币都有价格或价格源用尽。
+/// 所有价格源都失败且没有得到任何价格时返回最后一个错误。
+pub struct FallbackPriceSource {
+    sources: Vec<Arc<dyn PriceSource>>,
+}
+
+impl FallbackPriceSource {
+    pub fn new(sources: Vec<Arc<dyn PriceSource>>) -> FallbackPriceSource {
+        FallbackPriceSource { sources }
+    }
+}
+
+#[async_trait]
+impl PriceSource for FallbackPriceSource {
+    fn name(&self) -> &'static str {
+        "fallback"
+    }
+
+    async fn get_prices(&self, mints: &[Pubkey]) -> Result<HashMap<Pubkey, PriceQuote>> {
+        let mut prices = HashMap::new();
+        let mut missing = mints.to_vec();
+        let mut last_error = None;
+        for source in &self.sources {
+            if missing.is_empty() {
+                break;
+            }
+            match source.get_prices(&missing).await {
+                Ok(fetched) => {
+                    missing.retain(|mint| !fetched.contains_key(mint));
+                    prices.extend(fetched);
+                }
+                Err(e) => {
+                    println!("价格源 {} 请求失败 {:?}", source.name(), e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        match last_error {
+            Some(e) if prices.is_empty() => Err(e),
+            _ => Ok(prices),
+        }
+    }
+}
+
+/// 可配置的价格源
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PriceSourceKind {
+    Jupiter,
+    Birdeye,
+}
+
+impl FromStr for PriceSourceKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "jupiter" => Ok(PriceSourceKind::Jupiter),
+            "birdeye" => Ok(PriceSourceKind::Birdeye),
+            other => Err(anyhow!("未知的价格源 {}，可选 jupiter、birdeye", other)),
+        }
+    }
+}
+
+/// 价格源配置
+#[derive(Debug, Clone)]
+pub struct PriceSourceConfig {
+    /// 按优先级排列的价格源，多于一个时依次回退
+    pub sources: Vec<PriceSourceKind>,
+    pub birdeye_api_key: Option<String>,
+    /// 是否向 Jupiter 请求置信等级、报价价差和最近成交价格
+    pub jupiter_extra_info: bool,
+    /// Jupiter 价格接口地址，默认 [`JUPITER_PRICE_URL`]
+    pub jupiter_price_url: String,
+    /// Jupiter 价格请求的超时和重试策略
+    pub jupiter_request_policy: PriceRequestPolicy,
+}
+
+impl Default for PriceSourceConfig {
+    fn default() -> Self {
+        PriceSourceConfig {
+            sources: vec![PriceSourceKind::Jupiter],
+            birdeye_api_key: None,
+            jupiter_extra_info: true,
+            jupiter_price_url: JUPITER_PRICE_URL.to_string(),
+            jupiter_request_policy: PriceRequestPolicy::default(),
+        }
+    }
+}
+
+impl PriceSourceConfig {
+    /// 从 `PRICE_SOURCES`（逗号分隔，默认 jupiter）、`BIRDEYE_API_KEY`、`JUPITER_PRICE_EXTRA_INFO`（默认 true）
+    /// `JUPITER_PRICE_URL`、`PRICE_REQUEST_TIMEOUT_MS`（默认 3000）和 `PRICE_REQUEST_ATTEMPTS`（默认 3）读取
+    pub fn from_env() -> Result<PriceSourceConfig> {
+        let mut config = PriceSourceConfig::default();
+        if let Some(list) = std::env::var("PRICE_SOURCES")
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+        {
+            config.sources = list
+                .split(',')
+                .map(str::parse)
+                .collect::<Result<Vec<_>>>()?;
+        }
+        config.birdeye_api_key = std::env::var("BIRDEYE_API_KEY")
+            .ok()
+            .filter(|v| !v.trim().is_empty());
+        if let Some(v) = std::env::var("JUPITER_PRICE_EXTRA_INFO")
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+        {
+            config.jupiter_extra_info = v
+                .trim()
+                .parse()
+                .map_err(|_| anyhow!("JUPITER_PRICE_EXTRA_INFO 必须为 true 或 false"))?;
+        }
+        if let Some(url) = std::env::var("JUPITER_PRICE_URL")
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+        {
+            config.jupiter_price_url = url.trim().to_string();
+        }
+        if let Some(v) = std::env::var("PRICE_REQUEST_TIMEOUT_MS")
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+        {
+            let ms: u64 = v
+                .trim()
+                .parse()
+                .ok()
+                .filter(|ms| *ms > 0)
+                .ok_or_else(|| anyhow!("PRICE_REQUEST_TIMEOUT_MS 必须为正整数"))?;
+            config.jupiter_request_policy.timeout = Duration::from_millis(ms);
+        }
+        if let Some(v) = std::env::var("PRICE_REQUEST_ATTEMPTS")
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+        {
+            config.jupiter_request_policy.attempts = v
+                .trim()
+                .parse()
+                .ok()
+                .filter(|attempts| *attempts > 0)
+                .ok_or_else(|| anyhow!("PRICE_REQUEST_ATTEMPTS 必须为正整数"))?;
+        }
+        if config.sources.contains(&PriceSourceKind::Birdeye) && config.birdeye_api_key.is_none() {
+            return Err(anyhow!("使用 birdeye 价格源需要配置 BIRDEYE_API_KEY"));
+        }
+        Ok(config)
+    }
+
+    /// 创建价格源，配置了多个价格源时组合为 [`FallbackPriceSource`]
+    pub fn build(&self, http: Arc<Client>) -> Arc<dyn PriceSource> {
+        let mut sources: Vec<Arc<dyn PriceSource>> = self
+            .sources
+            .iter()
+            .map(|kind| -> Arc<dyn PriceSource> {
+                match kind {
+                    PriceSourceKind::Jupiter => Arc::new(
+                        JupiterPriceSource::new(
+                            http.clone(),
+                            self.jupiter_price_url.clone(),
+                            self.jupiter_extra_info,
+                        )
+                        .with_request_policy(self.jupiter_request_policy),
+                    ),
+                    PriceSourceKind::Birdeye => Arc::new(BirdeyePriceSource::new(
+                        http.clone(),
+                        self.birdeye_api_key.clone().unwrap_or_default(),
+                    )),
+                }
+            })
+            .collect();
+        if sources.len() == 1 {
+            sources.remove(0)
+        } else {
+            Arc::new(FallbackPriceSource::new(sources))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote(age_ms: u64) -> PriceQuote {
+        PriceQuote {
+            price: 150.0,
+            confidence: None,
+            confidence_level: None,
+            last_swap_price: None,
+            timestamp_ms: now_ms() - age_ms,
+            source: "test",
+        }
+    }
+
+    #[test]
+    fn stale_quotes_are_skipped() {
+        let max_age = Duration::from_secs(30);
+        assert_eq!(quote(0).fresh_price(max_age), Some(150.0));
+        assert_eq!(quote(29_000).fresh_price(max_age), Some(150.0));
+        assert_eq!(quote(31_000).fresh_price(max_age), None);
+    }
+
+    #[test]
+    fn confidence_levels_parse_case_insensitively() {
+        assert_eq!(
+            " High ".parse::<ConfidenceLevel>().unwrap(),
+            ConfidenceLevel::High
+        );
+        assert_eq!(
+            "medium".parse::<ConfidenceLevel>().unwrap(),
+            ConfidenceLevel::Medium
+        );
+        assert!("unknown".parse::<ConfidenceLevel>().is_err());
+    }
+
+    /// 用模拟价格源组合 [`FallbackPriceSource`]
+    #[cfg(feature = "testing")]
+    mod fallback {
+        use super::*;
+        use crate::{
+            testing::{MockPriceSource, USDC},
+            SOL,
+        };
+
+        fn fallback(sources: &[&Arc<MockPriceSource>]) -> FallbackPriceSource {
+            FallbackPriceSource::new(
+                sources
+                    .iter()
+                    .map(|source| -> Arc<dyn PriceSource> { (*source).clone() })
+                    .collect(),
+            )
+        }
+
+        #[tokio::test]
+        async fn first_source_is_used_when_it_has_every_price() {
+            let primary = Arc::new(MockPriceSource::new(
+                "primary",
+                HashMap::from([(SOL, 150.0), (USDC, 1.0)]),
+            ));
+            let secondary = Arc::new(MockPriceSource::new(
+                "secondary",
+                HashMap::from([(SOL, 151.0), (USDC, 1.0)]),
+            ));
+            let prices = fallback(&[&primary, &secondary])
+                .get_prices(&[SOL, USDC])
+                .await
+                .unwrap();
+            assert_eq!(prices[&SOL].price, 150.0);
+            assert!(prices.values().all(|quote| quote.source == "primary"));
+            assert_eq!(primary.calls(), 1);
+            assert_eq!(secondary.calls(), 0);
+        }
+
+        #[tokio::test]
+        async fn failed_sources_fall_through_in_order() {
+            let first = Arc::new(MockPriceSource::failing("first"));
+            let second = Arc::new(MockPriceSource::failing("second"));
+            let third = Arc::new(MockPriceSource::new("third", HashMap::from([(SOL, 152.0)])));
+            let fourth = Arc::new(MockPriceSource::new(
+                "fourth",
+                HashMap::from([(SOL, 153.0)]),
+            ));
+            let prices = fallback(&[&first, &second, &third, &fourth])
+                .get_prices(&[SOL])
+                .await
+                .unwrap();
+            assert_eq!(prices[&SOL].price, 152.0);
+            assert_eq!(prices[&SOL].source, "third");
+            assert_eq!(
+                [first.calls(), second.calls(), third.calls(), fourth.calls()],
+                [1, 1, 1, 0]
+            );
+        }
+
+        #[tokio::test]
+        async fn missing_mints_are_requested_from_the_next_source() {
+            let primary = Arc::new(MockPriceSource::new(
+                "primary",
+                HashMap::from([(SOL, 150.0)]),
+            ));
+            let secondary = Arc::new(MockPriceSource::new(
+                "secondary",
+                HashMap::from([(SOL, 151.0), (USDC, 1.0)]),
+            ));
+            let prices = fallback(&[&primary, &secondary])
+                .get_prices(&[SOL, USDC])
+                .await
+                .unwrap();
+            assert_eq!(prices[&SOL].source, "primary");
+            assert_eq!(prices[&SOL].price, 150.0);
+            assert_eq!(prices[&USDC].source, "secondary");
+            assert_eq!(secondary.calls(), 1);
+        }
+
+        #[tokio::test]
+        async fn all_sources_failing_returns_the_last_error() {
+            let first = Arc::new(MockPriceSource::failing("first"));
+            let second = Arc::new(MockPriceSource::failing("second"));
+            let err = fallback(&[&first, &second])
+                .get_prices(&[SOL])
+                .await
+                .unwrap_err();
+            assert!(err.to_string().contains("second"), "{}", err);
+        }
+
+        #[tokio::test]
+        async fn stale_quotes_from_a_source_are_skipped() {
+            let mut stale = MockPriceSource::new("stale", HashMap::from([(SOL, 150.0)]));
+            stale.age_ms = 120_000;
+            let stale = Arc::new(stale);
+            let fresh = Arc::new(MockPriceSource::new("fresh", HashMap::from([(USDC, 1.0)])));
+            let prices = fallback(&[&stale, &fresh])
+                .get_prices(&[SOL, USDC])
+                .await
+                .unwrap();
+            let max_age = Duration::from_secs(60);
+            assert_eq!(prices[&SOL].fresh_price(max_age), None);
+            assert_eq!(prices[&USDC].fresh_price(max_age), Some(1.0));
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use dashmap::DashMap;
+use solana_sdk::pubkey::Pubkey;
+use tokio::sync::Mutex;
+
+use crate::{
+    common::{
+        rate_limit::{RateLimitConfig, RateLimiter},
+        token_info::TokenInfoCache,
+        units::{Bps, TokenAmount},
+    },
+    solana::{
+        clients::SwapApi,
+        jup::{quote_only, SwapMode},
+    },
+};
+
+/// 缓存条目超过该数量时清理过期条目
+const MAX_CACHED_QUOTES: usize = 1024;
+/// 限流器中所有询价共用的键
+const QUOTE_LIMIT_KEY: &str = "jupiter";
+
+/// 按实际成交价格触发的订单的询价配置
+#[derive(Debug, Clone, Copy)]
+pub struct QuoteFeedConfig {
+    /// 同一代币对、同一数量区间的报价在该时长内共享，也是价格接近触发价格时的询价间隔
+    pub interval: Duration,
+    /// 价格远离触发价格时的询价间隔
+    pub max_interval: Duration,
+    /// 价格与触发价格相差超过该比例时按 `max_interval` 询价
+    pub far_bps: Bps,
+    /// 每秒最多向 Jupiter 发起的询价数
+    pub per_second: f64,
+    /// 允许的突发询价数
+    pub burst: u32,
+}
+
+impl Default for QuoteFeedConfig {
+    fn default() -> Self {
+        QuoteFeedConfig {
+            interval: Duration::from_secs(2),
+            max_interval: Duration::from_secs(10),
+            far_bps: Bps::new(500).expect("500 bps 有效"),
+            per_second: 2.0,
+            burst: 5,
+        }
+    }
+}
+
+/// 下一次询价前的等待时间
+///
+/// 价格距离触发价格超过 `far_bps` 时使用 `max_interval`，越接近触发价格间隔越短，
+/// 到达触发价格时为 `interval`，中间按距离线性插值。
+pub fn next_poll_interval(
+    now_price: f64,
+    trigger_price: f64,
+    config: &QuoteFeedConfig,
+) -> Duration {
+    let floor = config.interval;
+    let ceiling = config.max_interval.max(floor);
+    if !(trigger_price > 0.0 && now_price.is_finite()) || config.far_bps == Bps::ZERO {
+        return floor;
+    }
+    let distance = (now_price - trigger_price).abs() / trigger_price;
+    let far = config.far_bps.get() as f64 / Bps::MAX as f64;
+    let ratio = (distance / far).min(1.0);
+    floor + (ceiling - floor).mul_f64(ratio)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct QuoteKey {
+    input_mint: Pubkey,
+    output_mint: Pubkey,
+    swap_mode: SwapMode,
+    /// 分桶后的数量
+    amount: u64,
+}
+
+/// 一个桶最近一次的报价：报价时间和价格
+type CachedQuote = Arc<Mutex<Option<(Instant, f64)>>>;
+
+/// 按订单实际数量询价得到的成交价格
+///
+/// 大量订单定期询价时很容易超过 Jupiter 的报价限额，因此：
+/// - 数量按三位有效数字分桶，同一代币对、同一桶内的订单在 `interval` 内共享一次报价；
+/// - 同一桶同时只有一个询价请求，其他订单等待结果；
+/// - 所有询价经过同一个令牌桶限流，超出时排队等待。
+///
+/// 代币精度取自共享的 [`TokenInfoCache`]。
+pub struct QuoteFeed {
+    jup: Arc<dyn SwapApi>,
+    tokens: Arc<TokenInfoCache>,
+    config: QuoteFeedConfig,
+    quotes: DashMap<QuoteKey, CachedQuote>,
+    limiter: RateLimiter,
+}
+
+impl QuoteFeed {
+    pub fn new(
+        jup: Arc<dyn SwapApi>,
+        tokens: Arc<TokenInfoCache>,
+        config: QuoteFeedConfig,
+    ) -> QuoteFeed {
+        QuoteFeed {
+            jup,
+            tokens,
+            config,
+            quotes: DashMap::new(),
+            limiter: RateLimiter::new(RateLimitConfig {
+                per_second: config.per_second,
+                burst: config.burst,
+                per_user: false,
+            }),
+        }
+    }
+
+    pub fn config(&self) -> QuoteFeedConfig {
+        self.config
+    }
+
+    /// 代币精度，见 [`TokenInfoCache::decimals`]
+    pub async fn decimals(&self, mint: &Pubkey) -> Result<u8> {
+        self.tokens.decimals(mint).await
+    }
+
+    /// 1 个输入代币实际可换得的输出代币数量（界面单位），与 [`TriggerOn::Ratio`](crate::common::types::TriggerOn::Ratio) 的含义一致
+    ///
+    /// `amount` 为订单数量，`ExactIn` 时以输入代币计价，`ExactOut` 时以输出代币计价。
+    pub async fn price(
+        &self,
+        input_mint: Pubkey,
+        output_mint: Pubkey,
+        amount: TokenAmount,
+        slippage_bps: Bps,
+        swap_mode: SwapMode,
+    ) -> Result<f64> {
+        let key = QuoteKey {
+            input_mint,
+            output_mint,
+            swap_mode,
+            amount: bucket_amount(amount.raw),
+        };
+        let entry = self.quotes.entry(key).or_default().clone();
+        // 同一桶的询价串行，等待者直接使用前一个请求的结果
+        let mut cached = entry.lock().await;
+        if let Some((quoted_at, price)) = *cached {
+            if quoted_at.elapsed() < self.config.interval {
+                return Ok(price);
+            }
+        }
+
+        let input_decimals = self.decimals(&input_mint).await?;
+        let output_decimals = self.decimals(&output_mint).await?;
+        self.acquire().await;
+        let quoted = quote_only(
+            &self.jup,
+            input_mint,
+            output_mint,
+            TokenAmount::new(amount.mint, key.amount),
+            slippage_bps,
+            swap_mode,
+        )
+        .await?;
+        let in_ui = quoted.in_amount.to_ui(input_decimals);
+        if in_ui <= 0.0 {
+            return Err(anyhow!("报价的输入数量为 0"));
+        }
+        let price = quoted.out_amount.to_ui(output_decimals) / in_ui;
+        *cached = Some((Instant::now(), price));
+        drop(cached);
+
+        if self.quotes.len() > MAX_CACHED_QUOTES {
+            self.cleanup();
+        }
+        Ok(price)
+    }
+
+    /// 等待限流令牌
+    async fn acquire(&self) {
+        while let Err(wait) = self.limiter.check(QUOTE_LIMIT_KEY) {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// 移除过期且没有订单正在使用的报价
+    fn cleanup(&self) {
+        self.quotes.retain(|_, entry| match entry.try_lock() {
+            Ok(cached) => {
+                cached.is_some_and(|(quoted_at, _)| quoted_at.elapsed() < self.config.interval)
+            }
+            Err(_) => true,
+        });
+    }
+}
+
+/// 数量分桶：保留三位有效数字并向下取整，桶内数量的差异不超过 1%
+fn bucket_amount(raw: u64) -> u64 {
+    if raw < 1000 {
+        return raw;
+    }
+    let unit = 10u64.pow(raw.ilog10() - 2);
+    raw / unit * unit
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(interval_secs: u64, max_interval_secs: u64, far_bps: u16) -> QuoteFeedConfig {
+        QuoteFeedConfig {
+            interval: Duration::from_secs(interval_secs),
+            max_interval: Duration::from_secs(max_interval_secs),
+            far_bps: Bps::new(far_bps).unwrap(),
+            ..QuoteFeedConfig::default()
+        }
+    }
+
+    #[test]
+    fn interval_is_clamped_between_floor_and_ceiling() {
+        let config = config(2, 10, 500);
+        assert_eq!(
+            next_poll_interval(100.0, 100.0, &config),
+            Duration::from_secs(2)
+        );
+        // 相差 5% 及以上时按最长间隔询价，价格高于或低于触发价格相同
+        for now_price in [105.0, 95.0, 200.0, 0.0, 1e12] {
+            assert_eq!(
+                next_poll_interval(now_price, 100.0, &config),
+                Duration::from_secs(10),
+                "{}",
+                now_price
+            );
+        }
+    }
+
+    #[test]
+    fn interval_is_interpolated_by_distance() {
+        // far_bps 为 100% 时距离即为插值比例
+        let config = config(2, 10, 10_000);
+        assert_eq!(
+            next_poll_interval(125.0, 100.0, &config),
+            Duration::from_secs(4)
+        );
+        assert_eq!(
+            next_poll_interval(50.0, 100.0, &config),
+            Duration::from_secs(6)
+        );
+        let near = next_poll_interval(101.0, 100.0, &config);
+        let nearer = next_poll_interval(100.5, 100.0, &config);
+        assert!(Duration::from_secs(2) < nearer && nearer < near);
+    }
+
+    #[test]
+    fn zero_far_bps_always_uses_floor() {
+        let config = config(2, 10, 0);
+        for now_price in [100.0, 150.0, 1e12] {
+            assert_eq!(
+                next_poll_interval(now_price, 100.0, &config),
+                Duration::from_secs(2)
+            );
+        }
+    }
+
+    #[test]
+    fn ceiling_below_floor_uses_floor() {
+        let config = config(5, 1, 500);
+        assert_eq!(
+            next_poll_interval(200.0, 100.0, &config),
+            Duration::from_secs(5)
+        );
+    }
+
+    #[test]
+    fn invalid_prices_use_floor() {
+        let config = config(2, 10, 500);
+        let cases = [
+            (f64::NAN, 100.0),
+            (f64::INFINITY, 100.0),
+            (f64::NEG_INFINITY, 100.0),
+            (100.0, f64::NAN),
+            (100.0, 0.0),
+            (100.0, -1.0),
+        ];
+        for (now_price, trigger_price) in cases {
+            assert_eq!(
+                next_poll_interval(now_price, trigger_price, &config),
+                Duration::from_secs(2),
+                "{} {}",
+                now_price,
+                trigger_price
+            );
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+
+/// 清理空闲令牌桶的间隔
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 限流配置，`per_second` 为 0 时不限流
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitConfig {
+    /// 每秒补充的令牌数
+    pub per_second: f64,
+    /// 桶容量，即允许的突发请求数
+    pub burst: u32,
+    /// 除客户端 IP 外是否再按下单钱包限流
+    pub per_user: bool,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            per_second: 5.0,
+            burst: 10,
+            per_user: true,
+        }
+    }
+}
+
+/// 限流器读取当前时间的方式，测试中可以替换为手动推进的时钟
+pub trait Clock: Send + Sync + 'static {
+    fn now(&self) -> Instant;
+}
+
+/// 系统时钟
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// 按客户端的令牌桶限流
+///
+/// 每个键（例如 `ip:1.2.3.4`、`user:<公钥>`）一个桶，桶满时为 `burst` 个令牌，每个请求消耗一个，
+/// 按 `per_second` 的速度补充。回满的桶由后台任务定期清理，避免大量一次性客户端占用内存。
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: DashMap<String, Bucket>,
+    clock: Arc<dyn Clock>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> RateLimiter {
+        RateLimiter::with_clock(config, Arc::new(SystemClock))
+    }
+
+    /// 使用指定的时钟创建限流器
+    pub fn with_clock(config: RateLimitConfig, clock: Arc<dyn Clock>) -> RateLimiter {
+        RateLimiter {
+            config,
+            buckets: DashMap::new(),
+            clock,
+        }
+    }
+
+    /// 创建限流器并启动定期清理任务
+    pub fn spawn(config: RateLimitConfig) -> Arc<RateLimiter> {
+        let limiter = Arc::new(RateLimiter::new(config));
+        let weak = Arc::downgrade(&limiter);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(CLEANUP_INTERVAL);
+            loop {
+                interval.tick().await;
+                let Some(limiter) = weak.upgrade() else { break };
+                limiter.cleanup();
+            }
+        });
+        limiter
+    }
+
+    pub fn config(&self) -> RateLimitConfig {
+        self.config
+    }
+
+    /// 消耗一个令牌，令牌不足时返回需要等待的时间
+    pub fn check(&self, key: &str) -> Result<(), Duration> {
+        if self.config.per_second <= 0.0 {
+            return Ok(());
+        }
+        let now = self.clock.now();
+        let mut bucket = self.buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: self.config.burst as f64,
+            updated_at: now,
+        });
+        self.refill(&mut bucket, now);
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / self.config.per_second,
+            ))
+        }
+    }
+
+    /// 移除已经回满的桶，回满的桶与新建的桶没有区别
+    pub fn cleanup(&self) {
+        let now = self.clock.now();
+        self.buckets.retain(|_, bucket| {
+            self.refill(bucket, now);
+            bucket.tokens < self.config.burst as f64
+        });
+    }
+
+    fn refill(&self, bucket: &mut Bucket, now: Instant) {
+        let elapsed = now.saturating_duration_since(bucket.updated_at);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * self.config.per_second)
+            .min(self.config.burst as f64);
+        bucket.updated_at = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// 只在调用 `advance` 时前进的时钟
+    struct ManualClock(Mutex<Instant>);
+
+    impl ManualClock {
+        fn new() -> Arc<ManualClock> {
+            Arc::new(ManualClock(Mutex::new(Instant::now())))
+        }
+
+        fn advance(&self, duration: Duration) {
+            *self.0.lock().unwrap() += duration;
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> Instant {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    fn limiter(per_second: f64, burst: u32) -> (RateLimiter, Arc<ManualClock>) {
+        let clock = ManualClock::new();
+        let config = RateLimitConfig {
+            per_second,
+            burst,
+            per_user: false,
+        };
+        (RateLimiter::with_clock(config, clock.clone()), clock)
+    }
+
+    #[test]
+    fn burst_is_allowed_then_limited() {
+        let (limiter, _clock) = limiter(1.0, 3);
+        for _ in 0..3 {
+            assert!(limiter.check("ip:1.2.3.4").is_ok());
+        }
+        assert_eq!(limiter.check("ip:1.2.3.4"), Err(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn tokens_refill_over_time_up_to_burst() {
+        let (limiter, clock) = limiter(2.0, 3);
+        for _ in 0..3 {
+            limiter.check("key").unwrap();
+        }
+        assert_eq!(limiter.check("key"), Err(Duration::from_millis(500)));
+
+        clock.advance(Duration::from_millis(500));
+        assert!(limiter.check("key").is_ok());
+        assert!(limiter.check("key").is_err());
+
+        // 空闲很久后最多恢复到 burst 个令牌
+        clock.advance(Duration::from_secs(60));
+        for _ in 0..3 {
+            assert!(limiter.check("key").is_ok());
+        }
+        assert!(limiter.check("key").is_err());
+    }
+
+    #[test]
+    fn keys_have_separate_buckets() {
+        let (limiter, _clock) = limiter(1.0, 1);
+        assert!(limiter.check("user:alice").is_ok());
+        assert!(limiter.check("user:alice").is_err());
+        assert!(limiter.check("user:bob").is_ok());
+        assert!(limiter.check("ip:1.2.3.4").is_ok());
+        assert!(limiter.check("user:bob").is_err());
+    }
+
+    #[test]
+    fn zero_rate_disables_limit() {
+        let (limiter, _clock) = limiter(0.0, 0);
+        for _ in 0..100 {
+            assert!(limiter.check("key").is_ok());
+        }
+    }
+
+    #[test]
+    fn cleanup_drops_only_refilled_buckets() {
+        let (limiter, clock) = limiter(1.0, 2);
+        limiter.check("idle").unwrap();
+        clock.advance(Duration::from_millis(900));
+        limiter.check("busy").unwrap();
+        limiter.check("busy").unwrap();
+        clock.advance(Duration::from_millis(200));
+        limiter.cleanup();
+        assert!(!limiter.buckets.contains_key("idle"));
+        assert!(limiter.buckets.contains_key("busy"));
+    }
+}
@@ -0,0 +1,255 @@
+use std::{env, str::FromStr, time::Duration};
+
+use anyhow::{anyhow, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{common::utils::BlockhashProvider, solana::clients::SolanaRpc};
+
+/// 重试发送的节奏
+///
+/// 同一个 slot 内重复发送没有意义，`SlotAware` 会等到链上至少前进 `min_slots` 个 slot 后再重发。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type")]
+pub enum PacingPolicy {
+    /// 按重试策略的指数退避等待
+    #[default]
+    FixedDelay,
+    /// 等待 slot 前进后再重发
+    SlotAware { min_slots: u64 },
+}
+
+impl FromStr for PacingPolicy {
+    type Err = anyhow::Error;
+
+    /// 支持 `fixed` 和 `slot:<min_slots>` 两种写法
+    fn from_str(s: &str) -> Result<PacingPolicy> {
+        match s.split_once(':') {
+            None if s == "fixed" => Ok(PacingPolicy::FixedDelay),
+            Some(("slot", n)) => Ok(PacingPolicy::SlotAware {
+                min_slots: n.parse()?,
+            }),
+            _ => Err(anyhow!("无法解析的重试节奏 {}", s)),
+        }
+    }
+}
+
+/// 交易失败后的重试策略
+///
+/// 每次重试都会重新获取价格、重新报价并使用新的 blockhash 构造交易，
+/// 重试之间按指数退避等待。
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
+pub struct RetryPolicy {
+    /// 首次失败后的最大重试次数
+    pub max_retries: u32,
+    /// 第一次重试前的等待时间
+    pub base_delay_ms: u64,
+    /// 单次等待的上限
+    pub max_delay_ms: u64,
+    /// 全局重试节奏，订单可以单独覆盖
+    pub pacing: PacingPolicy,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            base_delay_ms: 500,
+            max_delay_ms: 8_000,
+            pacing: PacingPolicy::FixedDelay,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// 从环境变量 `SWAP_MAX_RETRIES`、`SWAP_RETRY_BASE_DELAY_MS`、`SWAP_RETRY_PACING` 读取，未配置时使用默认值
+    pub fn from_env() -> Result<RetryPolicy> {
+        let mut policy = RetryPolicy::default();
+        if let Ok(v) = env::var("SWAP_MAX_RETRIES") {
+            policy.max_retries = v.parse()?;
+        }
+        if let Ok(v) = env::var("SWAP_RETRY_BASE_DELAY_MS") {
+            policy.base_delay_ms = v.parse()?;
+        }
+        if let Ok(v) = env::var("SWAP_RETRY_PACING") {
+            policy.pacing = v.parse()?;
+        }
+        Ok(policy)
+    }
+
+    /// 第 `attempt` 次重试（从 1 开始）前需要等待的时间
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u64.saturating_pow(attempt.saturating_sub(1));
+        Duration::from_millis(
+            self.base_delay_ms
+                .saturating_mul(factor)
+                .min(self.max_delay_ms),
+        )
+    }
+}
+
+/// 按节奏策略等待第 `attempt` 次重发
+///
+/// `failed_slot` 为上一次发送失败时观察到的 slot，`SlotAware` 策略下等待 `blockhashes` 记录的 slot 至少前进 `min_slots`，
+/// slot 由 blockhash 的后台刷新推动，这里不轮询。最多等待 `max_delay_ms`，之后向 `rpc` 查询一次 slot 后直接重发；
+/// 没有 `failed_slot` 时退化为指数退避。返回重发时观察到的 slot，供发送记录使用。
+pub async fn wait_for_next_attempt(
+    rpc: &dyn SolanaRpc,
+    blockhashes: &BlockhashProvider,
+    policy: &RetryPolicy,
+    pacing: PacingPolicy,
+    attempt: u32,
+    failed_slot: Option<u64>,
+) -> Option<u64> {
+    match (pacing, failed_slot) {
+        (PacingPolicy::SlotAware { min_slots }, Some(failed_slot)) => {
+            let target = failed_slot.saturating_add(min_slots);
+            let max_wait = Duration::from_millis(policy.max_delay_ms);
+            match tokio::time::timeout(max_wait, blockhashes.wait_for_slot(target)).await {
+                Ok(slot) => Some(slot),
+                Err(_) => blockhashes.refresh_slot(rpc).await.ok(),
+            }
+        }
+        _ => {
+            tokio::time::sleep(policy.backoff(attempt)).await;
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pacing_parses_fixed_and_slot() {
+        assert_eq!(
+            "fixed".parse::<PacingPolicy>().unwrap(),
+            PacingPolicy::FixedDelay
+        );
+        assert_eq!(
+            "slot:2".parse::<PacingPolicy>().unwrap(),
+            PacingPolicy::SlotAware { min_slots: 2 }
+        );
+        assert!("slot:x".parse::<PacingPolicy>().is_err());
+        assert!("slots".parse::<PacingPolicy>().is_err());
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.backoff(1), Duration::from_millis(500));
+        assert_eq!(policy.backoff(2), Duration::from_millis(1_000));
+        assert_eq!(policy.backoff(10), Duration::from_millis(8_000));
+    }
+
+    /// 模拟 RPC 的 slot 由测试推动
+    #[cfg(feature = "testing")]
+    mod slot_pacing {
+        use tokio::time::Instant;
+
+        use super::*;
+        use crate::testing::MockRpc;
+
+        /// 每隔 400ms 前进一个 slot 并由后台刷新记录，共前进 `slots` 个
+        async fn advance(rpc: &MockRpc, blockhashes: &BlockhashProvider, from: u64, slots: u64) {
+            for slot in from + 1..=from + slots {
+                tokio::time::sleep(Duration::from_millis(400)).await;
+                rpc.set_slot(slot);
+                blockhashes.refresh_slot(rpc).await.unwrap();
+            }
+        }
+
+        #[tokio::test(start_paused = true)]
+        async fn resend_waits_for_the_slot_to_advance() {
+            let rpc = MockRpc::new();
+            rpc.set_slot(100);
+            let blockhashes = BlockhashProvider::new(0);
+            let failed_slot = blockhashes.refresh_slot(&rpc).await.unwrap();
+            let policy = RetryPolicy {
+                max_delay_ms: 60_000,
+                ..RetryPolicy::default()
+            };
+            let pacing = PacingPolicy::SlotAware { min_slots: 2 };
+
+            let started = Instant::now();
+            let wait = async {
+                let slot = wait_for_next_attempt(
+                    &rpc,
+                    &blockhashes,
+                    &policy,
+                    pacing,
+                    1,
+                    Some(failed_slot),
+                )
+                .await;
+                (slot, started.elapsed())
+            };
+            let ((slot, waited), _) = tokio::join!(wait, advance(&rpc, &blockhashes, 100, 4));
+            assert_eq!(slot, Some(102));
+            // 第二个 slot 在 800ms 时记录，没有提前重发
+            assert_eq!(waited, Duration::from_millis(800));
+        }
+
+        #[tokio::test(start_paused = true)]
+        async fn slot_already_reached_resends_immediately() {
+            let rpc = MockRpc::new();
+            rpc.set_slot(105);
+            let blockhashes = BlockhashProvider::new(0);
+            blockhashes.refresh_slot(&rpc).await.unwrap();
+            let pacing = PacingPolicy::SlotAware { min_slots: 2 };
+
+            let started = Instant::now();
+            let slot = wait_for_next_attempt(
+                &rpc,
+                &blockhashes,
+                &RetryPolicy::default(),
+                pacing,
+                1,
+                Some(100),
+            )
+            .await;
+            assert_eq!(slot, Some(105));
+            assert_eq!(started.elapsed(), Duration::ZERO);
+        }
+
+        #[tokio::test(start_paused = true)]
+        async fn stalled_slot_gives_up_after_the_max_delay() {
+            let rpc = MockRpc::new();
+            rpc.set_slot(100);
+            let blockhashes = BlockhashProvider::new(0);
+            blockhashes.refresh_slot(&rpc).await.unwrap();
+            let policy = RetryPolicy::default();
+            let pacing = PacingPolicy::SlotAware { min_slots: 2 };
+
+            let started = Instant::now();
+            let slot =
+                wait_for_next_attempt(&rpc, &blockhashes, &policy, pacing, 1, Some(100)).await;
+            assert_eq!(slot, Some(100));
+            assert_eq!(
+                started.elapsed(),
+                Duration::from_millis(policy.max_delay_ms)
+            );
+        }
+
+        #[tokio::test(start_paused = true)]
+        async fn fixed_delay_ignores_the_slot() {
+            let rpc = MockRpc::new();
+            let blockhashes = BlockhashProvider::new(0);
+            let policy = RetryPolicy::default();
+
+            let started = Instant::now();
+            let slot = wait_for_next_attempt(
+                &rpc,
+                &blockhashes,
+                &policy,
+                PacingPolicy::FixedDelay,
+                2,
+                Some(100),
+            )
+            .await;
+            assert_eq!(slot, None);
+            assert_eq!(started.elapsed(), policy.backoff(2));
+        }
+    }
+}
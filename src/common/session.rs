@@ -0,0 +1,241 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use solana_sdk::{bs58, pubkey::Pubkey, signature::Keypair, signer::Signer};
+use zeroize::Zeroizing;
+
+use crate::common::encode::SecretString;
+
+/// 会话默认有效期
+pub const DEFAULT_SESSION_TTL: Duration = Duration::from_secs(3600);
+
+/// 一次会话，持有解密后的私钥
+struct Session {
+    wallet: Pubkey,
+    /// base58 私钥，释放时清零
+    private_key: SecretString,
+    expires_at: Instant,
+}
+
+/// 会话存储
+///
+/// 客户端建立会话时提交一次加密私钥，之后下单只需携带会话令牌。令牌是随机生成的不透明字符串，
+/// 只用于下单，不能用于撤单。会话过期或被删除时私钥随之清零释放；已经创建的订单在自己的任务中持有私钥，
+/// 不受会话过期影响。
+pub struct SessionStore {
+    sessions: HashMap<String, Session>,
+    ttl: Duration,
+}
+
+impl SessionStore {
+    pub fn new(ttl: Duration) -> SessionStore {
+        SessionStore {
+            sessions: HashMap::new(),
+            ttl,
+        }
+    }
+
+    /// 会话有效期
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// 创建会话，返回会话令牌和绑定的钱包
+    pub fn create(&mut self, private_key: SecretString) -> Result<(String, Pubkey)> {
+        let wallet = parse_keypair(private_key.expose())?.pubkey();
+        self.purge_expired();
+        let token = bs58::encode(rand::random::<[u8; 32]>()).into_string();
+        self.sessions.insert(
+            token.clone(),
+            Session {
+                wallet,
+                private_key,
+                expires_at: Instant::now() + self.ttl,
+            },
+        );
+        Ok((token, wallet))
+    }
+
+    /// 根据令牌取出私钥，过期的会话会被移除
+    pub fn private_key(&mut self, token: &str) -> Result<SecretString> {
+        self.purge_expired();
+        self.sessions
+            .get(token)
+            .map(|session| session.private_key.clone())
+            .ok_or_else(|| anyhow!("会话不存在或已过期"))
+    }
+
+    /// 删除会话，返回会话是否存在
+    pub fn remove(&mut self, token: &str) -> bool {
+        self.sessions.remove(token).is_some()
+    }
+
+    /// 删除某个钱包的全部会话，返回删除的数量
+    pub fn remove_wallet(&mut self, wallet: &Pubkey) -> usize {
+        let before = self.sessions.len();
+        self.sessions.retain(|_, session| session.wallet != *wallet);
+        before - self.sessions.len()
+    }
+
+    fn purge_expired(&mut self) {
+        let now = Instant::now();
+        self.sessions.retain(|_, session| session.expires_at > now);
+    }
+}
+
+/// 解析 base58 私钥，格式错误时返回错误而不是 panic
+pub fn parse_keypair(private_key: &str) -> Result<Keypair> {
+    let bytes = Zeroizing::new(
+        bs58::decode(private_key)
+            .into_vec()
+            .map_err(|_| anyhow!("私钥格式错误"))?,
+    );
+    Keypair::from_bytes(&bytes).map_err(|_| anyhow!("私钥格式错误"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secret(keypair: &Keypair) -> SecretString {
+        SecretString::new(keypair.to_base58_string())
+    }
+
+    #[test]
+    fn session_returns_the_key_of_its_wallet() {
+        let keypair = Keypair::new();
+        let mut store = SessionStore::new(DEFAULT_SESSION_TTL);
+        let (token, wallet) = store.create(secret(&keypair)).unwrap();
+        assert_eq!(wallet, keypair.pubkey());
+        let key = store.private_key(&token).unwrap();
+        assert_eq!(parse_keypair(key.expose()).unwrap().pubkey(), wallet);
+        assert!(store.private_key("unknown").is_err());
+    }
+
+    #[test]
+    fn invalid_private_key_creates_no_session() {
+        let mut store = SessionStore::new(DEFAULT_SESSION_TTL);
+        assert!(store
+            .create(SecretString::new("not-a-key".to_string()))
+            .is_err());
+        assert!(store.sessions.is_empty());
+    }
+
+    #[test]
+    fn expired_session_is_removed() {
+        let mut store = SessionStore::new(Duration::ZERO);
+        let (token, _) = store.create(secret(&Keypair::new())).unwrap();
+        assert!(store.private_key(&token).is_err());
+        assert!(!store.remove(&token));
+    }
+
+    #[test]
+    fn removing_a_wallet_drops_only_its_sessions() {
+        let (keypair, other) = (Keypair::new(), Keypair::new());
+        let mut store = SessionStore::new(DEFAULT_SESSION_TTL);
+        let (first, wallet) = store.create(secret(&keypair)).unwrap();
+        let (second, _) = store.create(secret(&keypair)).unwrap();
+        let (kept, _) = store.create(secret(&other)).unwrap();
+
+        assert_eq!(store.remove_wallet(&wallet), 2);
+        assert!(store.private_key(&first).is_err());
+        assert!(store.private_key(&second).is_err());
+        assert!(store.private_key(&kept).is_ok());
+    }
+
+    /// 通过会话令牌在订单簿中下单
+    #[cfg(feature = "testing")]
+    mod orders {
+        use tokio::sync::Mutex;
+
+        use super::*;
+        use crate::{
+            common::types::{OrderBook, OrderStatus},
+            testing::{limit_leg, mock_config, wallet_secret, MockStack},
+            SOL,
+        };
+
+        /// 与下单接口相同：从会话中取出私钥
+        async fn session_key(book: &Mutex<OrderBook>, token: &str) -> Result<SecretString> {
+            book.lock().await.sessions.private_key(token)
+        }
+
+        async fn status(book: &Mutex<OrderBook>, order_id: &uuid::Uuid) -> OrderStatus {
+            book.lock().await.orders.lock().await[order_id]
+                .status
+                .clone()
+        }
+
+        #[tokio::test]
+        async fn order_placed_with_a_token_belongs_to_the_session_wallet() {
+            let stack = MockStack::new(150.0);
+            let book = Mutex::new(stack.order_book(&mock_config()));
+            let (token, wallet) = book.lock().await.create_session(wallet_secret(1)).unwrap();
+
+            let key = session_key(&book, &token).await.unwrap();
+            let order_id = OrderBook::place_order(&book, key, limit_leg(200.0, 1_000_000_000))
+                .await
+                .unwrap();
+
+            let order = book.lock().await.orders.lock().await[&order_id].clone();
+            assert_eq!(order.owner, wallet.to_string());
+            assert_eq!(order.status, OrderStatus::Pending);
+        }
+
+        /// 会话在订单等待触发期间过期不影响订单：订单任务自己持有私钥，触发后照常以会话钱包签名
+        #[tokio::test]
+        async fn session_expiry_does_not_stop_a_watching_order() {
+            let stack = MockStack::new(150.0);
+            let mut config = mock_config();
+            config.session_ttl = Duration::from_millis(50);
+            let book = Mutex::new(stack.order_book(&config));
+            let (token, wallet) = book.lock().await.create_session(wallet_secret(1)).unwrap();
+            let key = session_key(&book, &token).await.unwrap();
+            let order_id = OrderBook::place_order(&book, key, limit_leg(200.0, 1_000_000_000))
+                .await
+                .unwrap();
+
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            assert!(session_key(&book, &token).await.is_err());
+            assert_eq!(status(&book, &order_id).await, OrderStatus::Pending);
+
+            stack.prices.set_price(SOL, 210.0);
+            tokio::time::timeout(Duration::from_secs(10), async {
+                while stack.rpc.sent().is_empty() {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+            })
+            .await
+            .expect("价格越过触发价格后订单应发送交易");
+            let sent = stack.rpc.sent();
+            assert_eq!(sent.len(), 1);
+            assert_eq!(sent[0].message.static_account_keys()[0], wallet);
+        }
+
+        /// 吊销钱包时删除其会话并取消订单，之后既不能建立会话也不能下单
+        #[tokio::test]
+        async fn revoking_a_wallet_ends_its_sessions() {
+            let stack = MockStack::new(150.0);
+            let book = Mutex::new(stack.order_book(&mock_config()));
+            let (token, wallet) = book.lock().await.create_session(wallet_secret(1)).unwrap();
+            let key = session_key(&book, &token).await.unwrap();
+            let order_id = OrderBook::place_order(&book, key, limit_leg(200.0, 1_000_000_000))
+                .await
+                .unwrap();
+
+            let canceled = book.lock().await.revoke_wallet(wallet).await.unwrap();
+            assert_eq!(canceled, vec![order_id]);
+            assert_eq!(status(&book, &order_id).await, OrderStatus::Canceled);
+            assert!(session_key(&book, &token).await.is_err());
+            assert!(book.lock().await.create_session(wallet_secret(1)).is_err());
+            let err =
+                OrderBook::place_order(&book, wallet_secret(1), limit_leg(200.0, 1_000_000_000))
+                    .await
+                    .unwrap_err();
+            assert_eq!(err.code(), Some("UNAUTHORIZED"));
+        }
+    }
+}
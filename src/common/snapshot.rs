@@ -0,0 +1,74 @@
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::common::{prepared::SignedTxLifetime, types::Order};
+
+/// 停机时保存的订单快照，下次启动时恢复
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct OrderSnapshot {
+    /// 停机时仍在等待触发、可以继续执行的订单
+    pub suspended: Vec<SuspendedOrder>,
+    /// 停机时交易已经发出的订单，恢复后只保留状态，不会再次发送
+    pub triggered: Vec<Order>,
+}
+
+/// 停机时暂停的订单及恢复执行所需的信息
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SuspendedOrder {
+    pub order: Order,
+    pub resume: ResumeState,
+}
+
+/// 恢复订单任务所需的信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ResumeState {
+    /// 托管订单，私钥按下单请求中 `encrypt_pk` 的方式加密保存
+    Custodial { encrypt_pk: String },
+    /// 非托管订单，保存客户端签名的交易（base64）
+    Signed {
+        transaction: String,
+        lifetime: SignedTxLifetime,
+    },
+}
+
+impl OrderSnapshot {
+    /// 快照文件路径，取自环境变量 `ORDER_SNAPSHOT_PATH`，默认为 `orders_snapshot.json`
+    pub fn path() -> PathBuf {
+        env::var("ORDER_SNAPSHOT_PATH")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from("orders_snapshot.json"))
+    }
+
+    /// 写入快照，先写临时文件再重命名，避免停机中途留下不完整的快照
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let tmp = path.with_extension("tmp");
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&tmp)?;
+        file.write_all(&serde_json::to_vec_pretty(self)?)?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// 读取快照并将文件重命名为 `*.restored`，同一份快照不会被恢复两次；文件不存在时返回 None
+    pub fn take(path: &Path) -> Result<Option<OrderSnapshot>> {
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let snapshot = serde_json::from_slice(&data)?;
+        fs::rename(path, path.with_extension("restored"))?;
+        Ok(Some(snapshot))
+    }
+}
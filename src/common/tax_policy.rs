@@ -0,0 +1,223 @@
+use std::{collections::HashMap, env};
+
+use anyhow::{anyhow, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::common::units::Bps;
+
+/// 按名义价值分档的税率：名义价值不低于 `min_notional_lamports` 时使用 `bps`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct TaxTier {
+    pub min_notional_lamports: u64,
+    pub bps: Bps,
+}
+
+/// 税收策略：按下单钱包和交换的名义价值决定税率
+///
+/// 解析顺序为免税钱包、单个钱包的固定税率、名义价值分档，都不适用时使用 `default_bps`。
+/// 只有 `default_bps` 时即为统一税率。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct TaxPolicy {
+    /// 默认税率
+    pub default_bps: Bps,
+    /// 免税的钱包地址
+    #[serde(default)]
+    pub exempt: Vec<String>,
+    /// 单个钱包地址的固定税率，优先于分档
+    #[serde(default)]
+    pub overrides: HashMap<String, Bps>,
+    /// 名义价值分档，按 `min_notional_lamports` 升序排列，取名义价值达到的最高一档
+    #[serde(default)]
+    pub tiers: Vec<TaxTier>,
+}
+
+impl TaxPolicy {
+    /// 所有钱包使用同一税率
+    pub fn flat(bps: Bps) -> TaxPolicy {
+        TaxPolicy {
+            default_bps: bps,
+            exempt: vec![],
+            overrides: HashMap::new(),
+            tiers: vec![],
+        }
+    }
+
+    /// 以 `default_bps` 为默认税率，从环境变量 `TAX_EXEMPT_WALLETS`（逗号分隔的钱包地址）
+    /// 和 `TAX_TIERS`（逗号分隔的 `名义价值lamports:税率bps`，例如 `100000000000:80,1000000000000:50`）读取
+    pub fn from_env(default_bps: Bps) -> Result<TaxPolicy> {
+        let mut policy = TaxPolicy::flat(default_bps);
+        let var = |name| env::var(name).ok().filter(|v| !v.trim().is_empty());
+        if let Some(wallets) = var("TAX_EXEMPT_WALLETS") {
+            policy.exempt = wallets
+                .split(',')
+                .map(|wallet| wallet.trim().to_string())
+                .collect();
+        }
+        if let Some(tiers) = var("TAX_TIERS") {
+            policy.tiers = tiers
+                .split(',')
+                .map(|tier| {
+                    let (notional, bps) = tier
+                        .trim()
+                        .split_once(':')
+                        .ok_or_else(|| anyhow!("TAX_TIERS 的格式应为 名义价值:税率，{}", tier))?;
+                    Ok(TaxTier {
+                        min_notional_lamports: notional
+                            .trim()
+                            .parse()
+                            .map_err(|_| anyhow!("TAX_TIERS 中的名义价值无效 {}", notional))?,
+                        bps: Bps::new(
+                            bps.trim()
+                                .parse()
+                                .map_err(|_| anyhow!("TAX_TIERS 中的税率无效 {}", bps))?,
+                        )?,
+                    })
+                })
+                .collect::<Result<_>>()?;
+        }
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    /// 检查钱包地址有效、分档按名义价值严格升序排列
+    pub fn validate(&self) -> Result<()> {
+        for wallet in self.exempt.iter().chain(self.overrides.keys()) {
+            wallet
+                .parse::<Pubkey>()
+                .map_err(|_| anyhow!("钱包地址无效 {}", wallet))?;
+        }
+        if self
+            .tiers
+            .windows(2)
+            .any(|pair| pair[0].min_notional_lamports >= pair[1].min_notional_lamports)
+        {
+            return Err(anyhow!("税率分档必须按名义价值严格升序排列"));
+        }
+        Ok(())
+    }
+
+    /// `user` 名义价值为 `notional` lamports 的交换适用的税率
+    ///
+    /// `user` 为 None 时（例如不指定钱包的报价）不检查免税和固定税率；名义价值未知时不按分档，使用默认税率。
+    pub fn resolve(&self, user: Option<&Pubkey>, notional: Option<u64>) -> Bps {
+        if let Some(user) = user.map(Pubkey::to_string) {
+            if self.exempt.contains(&user) {
+                return Bps::ZERO;
+            }
+            if let Some(bps) = self.overrides.get(&user) {
+                return *bps;
+            }
+        }
+        notional
+            .and_then(|notional| {
+                self.tiers
+                    .iter()
+                    .rev()
+                    .find(|tier| notional >= tier.min_notional_lamports)
+            })
+            .map_or(self.default_bps, |tier| tier.bps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bps(value: u16) -> Bps {
+        Bps::new(value).unwrap()
+    }
+
+    /// 默认 1%，达到 100 SOL 时 0.8%，达到 1000 SOL 时 0.5%
+    fn tiered() -> TaxPolicy {
+        TaxPolicy {
+            tiers: vec![
+                TaxTier {
+                    min_notional_lamports: 100_000_000_000,
+                    bps: bps(80),
+                },
+                TaxTier {
+                    min_notional_lamports: 1_000_000_000_000,
+                    bps: bps(50),
+                },
+            ],
+            ..TaxPolicy::flat(bps(100))
+        }
+    }
+
+    #[test]
+    fn tiers_apply_from_their_lower_bound() {
+        let policy = tiered();
+        let cases = [
+            (None, 100),
+            (Some(0), 100),
+            (Some(99_999_999_999), 100),
+            (Some(100_000_000_000), 80),
+            (Some(999_999_999_999), 80),
+            (Some(1_000_000_000_000), 50),
+            (Some(u64::MAX), 50),
+        ];
+        for (notional, expected) in cases {
+            assert_eq!(
+                policy.resolve(None, notional),
+                bps(expected),
+                "名义价值 {:?}",
+                notional
+            );
+        }
+    }
+
+    #[test]
+    fn exemptions_and_overrides_take_precedence_over_tiers() {
+        let exempt = Pubkey::new_unique();
+        let vip = Pubkey::new_unique();
+        let both = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        let mut policy = tiered();
+        policy.exempt = vec![exempt.to_string(), both.to_string()];
+        policy.overrides = HashMap::from([(vip.to_string(), bps(20)), (both.to_string(), bps(20))]);
+        policy.validate().unwrap();
+
+        let cases = [
+            (&exempt, None, 0),
+            (&exempt, Some(1_000_000_000_000), 0),
+            (&vip, None, 20),
+            (&vip, Some(1_000_000_000_000), 20),
+            // 同时免税和设置了固定税率时免税优先
+            (&both, Some(1_000_000_000_000), 0),
+            (&other, Some(100_000_000_000), 80),
+            (&other, None, 100),
+        ];
+        for (user, notional, expected) in cases {
+            assert_eq!(
+                policy.resolve(Some(user), notional),
+                bps(expected),
+                "{} 名义价值 {:?}",
+                user,
+                notional
+            );
+        }
+        // 不指定钱包时不检查免税和固定税率
+        assert_eq!(policy.resolve(None, Some(1_000_000_000_000)), bps(50));
+    }
+
+    #[test]
+    fn validate_rejects_unordered_tiers_and_invalid_wallets() {
+        let mut policy = tiered();
+        policy.tiers.swap(0, 1);
+        assert!(policy.validate().is_err());
+
+        let mut policy = tiered();
+        policy.tiers[1].min_notional_lamports = policy.tiers[0].min_notional_lamports;
+        assert!(policy.validate().is_err(), "分档的名义价值不能重复");
+
+        let mut policy = TaxPolicy::flat(bps(100));
+        policy.exempt = vec!["not-a-wallet".to_string()];
+        assert!(policy.validate().is_err());
+
+        let mut policy = TaxPolicy::flat(bps(100));
+        policy.overrides = HashMap::from([("not-a-wallet".to_string(), bps(10))]);
+        assert!(policy.validate().is_err());
+    }
+}
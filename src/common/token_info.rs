@@ -0,0 +1,190 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use dashmap::DashMap;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use solana_sdk::{pubkey, pubkey::Pubkey};
+
+use crate::{
+    common::utils::{format_amount, get_mint_info},
+    solana::clients::SolanaRpc,
+};
+
+/// Jupiter 代币信息接口，按 `{url}/{mint}` 查询单个代币
+pub const DEFAULT_TOKEN_LIST_URL: &str = "https://lite-api.jup.ag/tokens/v1/token";
+
+/// Metaplex Token Metadata 程序
+const METADATA_PROGRAM_ID: Pubkey = pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+
+/// 代币信息缓存的配置
+#[derive(Debug, Clone)]
+pub struct TokenInfoConfig {
+    /// Jupiter 代币信息接口
+    pub url: String,
+    /// 代币名称、符号的缓存时长，精度不会变化，过期后仍然复用
+    pub ttl: Duration,
+}
+
+impl Default for TokenInfoConfig {
+    fn default() -> Self {
+        TokenInfoConfig {
+            url: DEFAULT_TOKEN_LIST_URL.to_string(),
+            ttl: Duration::from_secs(3600),
+        }
+    }
+}
+
+/// 代币的符号、名称和精度
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TokenInfo {
+    pub mint: String,
+    /// 代币符号，Jupiter 和链上元数据都查不到时为 None
+    pub symbol: Option<String>,
+    pub name: Option<String>,
+    /// 代币精度，取自 mint 账户
+    pub decimals: u8,
+}
+
+impl TokenInfo {
+    /// 按代币精度把最小单位的数量格式化为界面显示的字符串
+    pub fn ui_amount(&self, amount: u64) -> String {
+        format_amount(amount, self.decimals)
+    }
+}
+
+/// Jupiter 代币信息接口返回的字段
+#[derive(Debug, Deserialize)]
+struct JupiterToken {
+    symbol: String,
+    name: String,
+}
+
+/// 代币信息缓存
+///
+/// 精度以 mint 账户为准；符号和名称优先使用 Jupiter 代币信息接口，查询失败时读取链上的 Metaplex 元数据。
+/// 首次使用时查询，`ttl` 内直接返回缓存。符号和名称查不到不算错误，只有 mint 账户查询失败时返回错误。
+pub struct TokenInfoCache {
+    http: Arc<Client>,
+    rpc: Arc<dyn SolanaRpc>,
+    config: TokenInfoConfig,
+    tokens: DashMap<Pubkey, (Instant, TokenInfo)>,
+}
+
+impl TokenInfoCache {
+    pub fn new(http: Arc<Client>, rpc: Arc<dyn SolanaRpc>, config: TokenInfoConfig) -> Self {
+        TokenInfoCache {
+            http,
+            rpc,
+            config,
+            tokens: DashMap::new(),
+        }
+    }
+
+    /// 代币信息，缓存过期或未缓存时重新查询
+    pub async fn get(&self, mint: &Pubkey) -> Result<TokenInfo> {
+        let cached = self.tokens.get(mint).map(|entry| entry.clone());
+        if let Some((fetched_at, info)) = &cached {
+            if fetched_at.elapsed() < self.config.ttl {
+                return Ok(info.clone());
+            }
+        }
+        let decimals = match &cached {
+            Some((_, info)) => info.decimals,
+            None => get_mint_info(self.rpc.as_ref(), mint).await?.decimals,
+        };
+        let (symbol, name) = match self.fetch_jupiter(mint).await {
+            Ok(token) => (Some(token.symbol), Some(token.name)),
+            Err(e) => match self.fetch_metadata(mint).await {
+                Ok((symbol, name)) => (Some(symbol), Some(name)),
+                Err(metadata_err) => {
+                    println!(
+                        "获取代币 {} 的信息失败 {:?}，链上元数据 {:?}",
+                        mint, e, metadata_err
+                    );
+                    // 沿用上一次查到的符号，避免接口暂时不可用时显示变为空
+                    cached
+                        .map(|(_, info)| (info.symbol, info.name))
+                        .unwrap_or_default()
+                }
+            },
+        };
+        let info = TokenInfo {
+            mint: mint.to_string(),
+            symbol,
+            name,
+            decimals,
+        };
+        self.tokens.insert(*mint, (Instant::now(), info.clone()));
+        Ok(info)
+    }
+
+    /// 代币精度，精度不会变化，缓存过期后也直接使用
+    pub async fn decimals(&self, mint: &Pubkey) -> Result<u8> {
+        if let Some(entry) = self.tokens.get(mint) {
+            return Ok(entry.1.decimals);
+        }
+        Ok(self.get(mint).await?.decimals)
+    }
+
+    /// 用于展示的代币信息，地址无效或查询失败时返回 None
+    pub async fn lookup(&self, mint: &str) -> Option<TokenInfo> {
+        let mint = mint.parse().ok()?;
+        match self.get(&mint).await {
+            Ok(info) => Some(info),
+            Err(e) => {
+                println!("查询代币 {} 的信息失败 {:?}", mint, e);
+                None
+            }
+        }
+    }
+
+    async fn fetch_jupiter(&self, mint: &Pubkey) -> Result<JupiterToken> {
+        let url = format!("{}/{}", self.config.url.trim_end_matches('/'), mint);
+        Ok(self
+            .http
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<JupiterToken>()
+            .await?)
+    }
+
+    /// 读取 Metaplex 元数据账户中的符号和名称
+    async fn fetch_metadata(&self, mint: &Pubkey) -> Result<(String, String)> {
+        let (address, _) = Pubkey::find_program_address(
+            &[b"metadata", METADATA_PROGRAM_ID.as_ref(), mint.as_ref()],
+            &METADATA_PROGRAM_ID,
+        );
+        let account = self
+            .rpc
+            .get_account(&address)
+            .await?
+            .ok_or_else(|| anyhow!("代币 {} 没有链上元数据", mint))?;
+        parse_metadata(&account.data).ok_or_else(|| anyhow!("无法解析代币 {} 的链上元数据", mint))
+    }
+}
+
+/// 解析元数据账户开头的固定字段：key(1)、update_authority(32)、mint(32)，之后是 borsh 编码的名称和符号
+fn parse_metadata(data: &[u8]) -> Option<(String, String)> {
+    let mut offset = 1 + 32 + 32;
+    let mut read_string = || {
+        let len = u32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?) as usize;
+        let bytes = data.get(offset + 4..offset + 4 + len)?;
+        offset += 4 + len;
+        // 名称和符号以 \0 填充到固定长度
+        Some(
+            String::from_utf8_lossy(bytes)
+                .trim_end_matches('\0')
+                .trim()
+                .to_string(),
+        )
+    };
+    let name = read_string()?;
+    let symbol = read_string()?;
+    Some((symbol, name))
+}
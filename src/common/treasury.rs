@@ -0,0 +1,331 @@
+use std::{env, sync::Arc};
+
+use anyhow::{anyhow, Result};
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::Value;
+use solana_sdk::{pubkey::Pubkey, system_instruction};
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+
+use crate::{
+    common::{
+        dns::build_http_client,
+        price_source::PriceSource,
+        session::parse_keypair,
+        units::TokenAmount,
+        utils::{compile_versioned_transaction, ensure_ata_ix, simulate_or_fail, MintInfo},
+    },
+    solana::{
+        clients::SolanaRpc,
+        signer::{LocalKeypairSigner, RemoteHttpSigner, TransactionSigner},
+        swap::{token_tax_ixs, LAMPORTS_PER_SIGNATURE, TOKEN_2022_PROGRAM_ID},
+    },
+    SOL,
+};
+
+/// 代币账户的大小，用于计算创建 ATA 的租金
+const TOKEN_ACCOUNT_LEN: usize = 165;
+
+/// 收税账户余额归集的配置
+///
+/// 需要收税账户签名转账，因此单独配置；未配置时只能查询余额。
+#[derive(Clone)]
+pub struct SweepConfig {
+    /// 收税账户的签名者，公钥必须与 `TAX_ACCOUNT` 一致
+    pub tax_signer: Arc<dyn TransactionSigner>,
+    /// 归集的目标冷钱包
+    pub cold_wallet: Pubkey,
+    /// 收税账户保留的 SOL（lamports），用于支付手续费和创建 ATA 的租金
+    pub sol_float: u64,
+    /// 一次归集最多包含的转账指令数
+    pub max_transfers: usize,
+}
+
+impl SweepConfig {
+    /// 从 `TAX_ACCOUNT_PK`（base58 私钥）或 `TAX_SIGNER_URL`（远程签名服务）和 `TREASURY_COLD_WALLET` 读取，
+    /// 都未配置时返回 None
+    ///
+    /// 使用远程签名服务时私钥不进入本进程，签名者的公钥取 `TAX_ACCOUNT`，可选的 `TAX_SIGNER_TOKEN` 用于认证。
+    pub fn from_env() -> Result<Option<SweepConfig>> {
+        let var = |name| env::var(name).ok().filter(|v| !v.trim().is_empty());
+        let tax_signer: Option<Arc<dyn TransactionSigner>> =
+            match (var("TAX_ACCOUNT_PK"), var("TAX_SIGNER_URL")) {
+                (None, None) => None,
+                (Some(keypair), None) => Some(Arc::new(LocalKeypairSigner::new(
+                    parse_keypair(keypair.trim())
+                        .map_err(|_| anyhow!("TAX_ACCOUNT_PK 不是有效的 base58 私钥"))?,
+                ))),
+                (None, Some(url)) => {
+                    let pubkey = var("TAX_ACCOUNT")
+                        .ok_or_else(|| anyhow!("使用 TAX_SIGNER_URL 时需要配置 TAX_ACCOUNT"))?
+                        .trim()
+                        .parse()
+                        .map_err(|_| anyhow!("TAX_ACCOUNT 地址无效"))?;
+                    Some(Arc::new(RemoteHttpSigner::new(
+                        build_http_client()?,
+                        url.trim().to_string(),
+                        pubkey,
+                        var("TAX_SIGNER_TOKEN").map(|token| token.trim().to_string()),
+                    )))
+                }
+                (Some(_), Some(_)) => {
+                    return Err(anyhow!("TAX_ACCOUNT_PK 和 TAX_SIGNER_URL 只能配置一个"))
+                }
+            };
+        let (tax_signer, cold_wallet) = match (tax_signer, var("TREASURY_COLD_WALLET")) {
+            (None, None) => return Ok(None),
+            (Some(tax_signer), Some(cold_wallet)) => (tax_signer, cold_wallet),
+            _ => {
+                return Err(anyhow!(
+                    "TAX_ACCOUNT_PK（或 TAX_SIGNER_URL）和 TREASURY_COLD_WALLET 需要同时配置"
+                ))
+            }
+        };
+        let cold_wallet = cold_wallet
+            .trim()
+            .parse()
+            .map_err(|_| anyhow!("TREASURY_COLD_WALLET 地址无效"))?;
+        let sol_float = match var("TREASURY_SOL_FLOAT_LAMPORTS") {
+            Some(v) => v
+                .trim()
+                .parse()
+                .map_err(|_| anyhow!("TREASURY_SOL_FLOAT_LAMPORTS 无效"))?,
+            None => 10_000_000,
+        };
+        let max_transfers = match var("TREASURY_MAX_TRANSFERS") {
+            Some(v) => v
+                .trim()
+                .parse()
+                .ok()
+                .filter(|n: &usize| *n > 0)
+                .ok_or_else(|| anyhow!("TREASURY_MAX_TRANSFERS 必须为正整数"))?,
+            None => 8,
+        };
+        Ok(Some(SweepConfig {
+            tax_signer,
+            cold_wallet,
+            sol_float,
+            max_transfers,
+        }))
+    }
+}
+
+/// 收税账户的余额
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct TreasuryBalances {
+    pub account: String,
+    pub sol_lamports: u64,
+    pub tokens: Vec<TokenBalance>,
+}
+
+/// 收税账户的一个代币账户
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct TokenBalance {
+    pub token_account: String,
+    pub mint: String,
+    pub token_program: String,
+    /// 最小单位的数量
+    pub amount: u64,
+    pub decimals: u8,
+}
+
+impl TokenBalance {
+    /// 从 `getTokenAccountsByOwner` 的 jsonParsed 结果中解析
+    fn parse(token_account: &str, token_program: Pubkey, data: &Value) -> Option<TokenBalance> {
+        let info = data.get("parsed")?.get("info")?;
+        let token_amount = info.get("tokenAmount")?;
+        Some(TokenBalance {
+            token_account: token_account.to_string(),
+            mint: info.get("mint")?.as_str()?.to_string(),
+            token_program: token_program.to_string(),
+            amount: token_amount.get("amount")?.as_str()?.parse().ok()?,
+            decimals: token_amount.get("decimals")?.as_u64()? as u8,
+        })
+    }
+}
+
+/// 查询收税账户的 SOL 余额和 spl-token、Token-2022 代币账户的余额
+pub async fn treasury_balances(rpc: &dyn SolanaRpc, account: &Pubkey) -> Result<TreasuryBalances> {
+    let sol_lamports = rpc.get_balance(account).await?;
+    let mut tokens = vec![];
+    for token_program in [spl_token::id(), TOKEN_2022_PROGRAM_ID] {
+        let accounts = rpc
+            .get_token_accounts_by_owner(account, &token_program)
+            .await?;
+        for keyed in accounts {
+            let data = serde_json::to_value(&keyed.account.data)?;
+            match TokenBalance::parse(&keyed.pubkey, token_program, &data) {
+                Some(balance) => tokens.push(balance),
+                None => println!("无法解析代币账户 {}", keyed.pubkey),
+            }
+        }
+    }
+    Ok(TreasuryBalances {
+        account: account.to_string(),
+        sol_lamports,
+        tokens,
+    })
+}
+
+/// 一次归集的结果
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct SweepResult {
+    /// 归集交易的签名，没有需要归集的余额时为 None
+    pub signature: Option<String>,
+    pub swept: Vec<SweptBalance>,
+    pub skipped: Vec<SkippedBalance>,
+}
+
+/// 已归集的余额，`mint` 为 None 时为原生 SOL
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct SweptBalance {
+    pub mint: Option<String>,
+    pub amount: u64,
+}
+
+/// 未归集的代币账户及原因
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct SkippedBalance {
+    pub token_account: String,
+    pub mint: String,
+    pub amount: u64,
+    pub reason: String,
+}
+
+/// 将收税账户的余额归集到冷钱包，构建一笔最多包含 `max_transfers` 条转账的交易，模拟成功后发送
+///
+/// 代币的 USD 价值低于创建一个代币账户的租金时不值得归集，直接跳过；价格未知或不是 ATA 的代币账户同样跳过。
+/// 超出本次条数上限的代币留到下一次归集。SOL 保留 `sol_float` 以及本次交易的手续费和新建 ATA 的租金，其余全部归集。
+/// 调用方负责确认没有正在执行的交换。
+pub async fn sweep(
+    rpc: &dyn SolanaRpc,
+    price_source: &dyn PriceSource,
+    config: &SweepConfig,
+) -> Result<SweepResult> {
+    let treasury = config.tax_signer.pubkey();
+    let balances = treasury_balances(rpc, &treasury).await?;
+    let rent = rpc
+        .get_minimum_balance_for_rent_exemption(TOKEN_ACCOUNT_LEN)
+        .await?;
+
+    let mut mints: Vec<Pubkey> = balances
+        .tokens
+        .iter()
+        .filter_map(|token| token.mint.parse().ok())
+        .collect();
+    mints.push(SOL);
+    mints.sort();
+    mints.dedup();
+    let prices = price_source.get_prices(&mints).await?;
+    let rent_usd = prices
+        .get(&SOL)
+        .map(|sol| rent as f64 / 1e9 * sol.price)
+        .ok_or_else(|| anyhow!("无法获取 SOL 价格"))?;
+
+    let mut ixs = vec![];
+    let mut transfers = 0;
+    let mut created_atas = 0;
+    let mut swept = vec![];
+    let mut skipped = vec![];
+    let mut skip = |token: &TokenBalance, reason: String| {
+        skipped.push(SkippedBalance {
+            token_account: token.token_account.clone(),
+            mint: token.mint.clone(),
+            amount: token.amount,
+            reason,
+        })
+    };
+    for token in balances.tokens.iter().filter(|token| token.amount > 0) {
+        let (Ok(mint), Ok(token_program)) = (
+            token.mint.parse::<Pubkey>(),
+            token.token_program.parse::<Pubkey>(),
+        ) else {
+            continue;
+        };
+        if get_associated_token_address_with_program_id(&treasury, &mint, &token_program)
+            .to_string()
+            != token.token_account
+        {
+            skip(token, "不是 ATA".to_string());
+            continue;
+        }
+        let Some(price) = prices.get(&mint) else {
+            skip(token, "无法获取价格".to_string());
+            continue;
+        };
+        let value_usd = token.amount as f64 / 10f64.powi(token.decimals as i32) * price.price;
+        if value_usd < rent_usd {
+            skip(
+                token,
+                format!(
+                    "价值 {:.4} USD 低于代币账户租金 {:.4} USD",
+                    value_usd, rent_usd
+                ),
+            );
+            continue;
+        }
+        if transfers >= config.max_transfers {
+            skip(token, "超过本次归集的转账条数上限".to_string());
+            continue;
+        }
+        if let Some(create) =
+            ensure_ata_ix(rpc, &treasury, &config.cold_wallet, &mint, &token_program).await?
+        {
+            ixs.push(create);
+            created_atas += 1;
+        }
+        ixs.extend(token_tax_ixs(
+            &treasury,
+            &config.cold_wallet,
+            TokenAmount::new(mint, token.amount),
+            MintInfo {
+                token_program,
+                decimals: token.decimals,
+                // 转账手续费由代币程序从转入数量中扣除，归集时按余额全额转出即可
+                transfer_fee: None,
+            },
+        )?);
+        transfers += 1;
+        swept.push(SweptBalance {
+            mint: Some(token.mint.clone()),
+            amount: token.amount,
+        });
+    }
+
+    let reserved = config
+        .sol_float
+        .saturating_add(LAMPORTS_PER_SIGNATURE)
+        .saturating_add(rent.saturating_mul(created_atas));
+    let sol_amount = balances.sol_lamports.saturating_sub(reserved);
+    if sol_amount > 0 && transfers < config.max_transfers {
+        ixs.push(system_instruction::transfer(
+            &treasury,
+            &config.cold_wallet,
+            sol_amount,
+        ));
+        swept.push(SweptBalance {
+            mint: None,
+            amount: sol_amount,
+        });
+    }
+    if ixs.is_empty() {
+        return Ok(SweepResult {
+            signature: None,
+            swept,
+            skipped,
+        });
+    }
+
+    let blockhash = rpc.get_latest_blockhash().await?;
+    let tx =
+        compile_versioned_transaction(&ixs, &treasury, config.tax_signer.as_ref(), &[], blockhash)
+            .await?;
+    simulate_or_fail(rpc, &tx).await?;
+    let signature = rpc.send(&tx).await?;
+    println!("归集收税账户余额 {:?}，交易 {}", swept, signature);
+    Ok(SweepResult {
+        signature: Some(signature.to_string()),
+        swept,
+        skipped,
+    })
+}
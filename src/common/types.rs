@@ -1,172 +1,5940 @@
-use std::{collections::HashMap, env, sync::Arc, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    future::Future,
+    path::PathBuf,
+    str::FromStr,
+    sync::{Arc, Mutex as StdMutex, RwLock as StdRwLock},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
-use anyhow::{anyhow, Context, Ok, Result};
+use anyhow::{anyhow, Result};
 use jito_sdk_rust::JitoJsonRpcSDK;
 use jupiter_swap_api_client::JupiterSwapApiClient;
 use reqwest::Client;
-use solana_client::nonblocking::rpc_client::RpcClient;
-use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
-use tokio::sync::oneshot::{self, Sender};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use solana_sdk::{
+    message::Message,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::Signer,
+    system_instruction,
+    transaction::VersionedTransaction,
+};
+use tokio::{
+    sync::{
+        oneshot::{self, Receiver, Sender},
+        watch, Mutex, Semaphore,
+    },
+    task::JoinSet,
+};
 use uuid::Uuid;
+use zeroize::Zeroizing;
 
-use crate::{common::utils::get_price, solana::swap::swap_with_tax};
+use crate::{
+    common::{
+        audit::{AuditLog, AuditStore, MemoryAuditStore},
+        config::AppConfig,
+        db::{
+            build_pool, outstanding_intents, record_intent, resolve_intent, unfinished_orders,
+            DbPool, ExecutionIntent, MysqlAuditStore,
+        },
+        dispatcher::{TriggerDispatcher, TriggerLevel},
+        dns::build_http_client,
+        encode::{decrypt, encrypt, SecretString},
+        events::{EventBus, OrderEvent, OrderEventKind},
+        metrics::metrics,
+        nonce::NoncePool,
+        persist::{OrderStore, PersistQueue, PersistRecord},
+        prepared::{
+            decode_transaction, encode_transaction, verify_signed_transaction, PreparedOrder,
+            PreparedTransaction, SignedTxLifetime,
+        },
+        price::{now_ms, PriceCache, PriceSubscription},
+        price_source::{PriceQuote, PriceSource},
+        quote_feed::{next_poll_interval, QuoteFeed, QuoteFeedConfig},
+        retry::{wait_for_next_attempt, PacingPolicy, RetryPolicy},
+        session::{parse_keypair, SessionStore},
+        snapshot::{OrderSnapshot, ResumeState, SuspendedOrder},
+        tax_policy::TaxPolicy,
+        token_info::TokenInfoCache,
+        treasury::{sweep, treasury_balances, SweepConfig, SweepResult, TreasuryBalances},
+        units::{Bps, Lamports, TokenAmount},
+        utils::{
+            advance_nonce, get_mint_info, get_nonce, is_blockhash_not_found,
+            recommended_priority_fee, BlockhashProvider, BundleConfig, NonceInfo,
+            PrivateExecutionConfig, DEFAULT_PRIORITY_FEE_PERCENTILE,
+        },
+        webhook::{deliver, validate_callback_url, WebhookConfig, WebhookPayload},
+    },
+    error::{self, LimitOrderError},
+    solana::{
+        clients::{BundleSender, SignatureStatus, SolanaRpc, SwapApi},
+        fill::{fetch_parsed_transaction, parse_balance_changes, swap_fill_amounts, FillReport},
+        jup::{
+            configure_quotes, get_quote, quote_only, PinnedRoute, RoutePin, SwapMode, SwapOptions,
+        },
+        multi_rpc::MultiRpc,
+        signer::{LocalKeypairSigner, TransactionSigner},
+        swap::{
+            amount_notional_lamports, build_signed_swap, check_destination_account, check_funding,
+            check_min_out, check_price_impact, check_swap_mode, check_swap_options,
+            net_of_transfer_fee, net_out_amount, prepare_unsigned_swap, quote_amount,
+            simulate_swap, sub_tax, submit_signed_swap, tax_amount, with_compute_budget,
+            ExecutionOptions, PrivateExecution, SignedSwap, SwapContext, SwapOutcome, SwapParams,
+            SwapSimulation, TaxSide, ESTIMATED_SWAP_COMPUTE_UNITS, LAMPORTS_PER_SIGNATURE,
+        },
+    },
+    SOL,
+};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Order {
     pub order_id: Uuid,
+    /// 下单钱包的公钥
+    pub owner: String,
     pub price: f32,
     pub input_mint: String,
     pub output_mint: String,
     pub amount: u64,
-    pub slippage_bps: u16,
-    pub tip_amount: Option<u64>,
+    pub slippage_bps: Bps,
+    pub tip_amount: Option<Lamports>,
+    /// 优先费，单位为 micro-lamports / CU
+    pub priority_fee_micro_lamports: Option<u64>,
+    /// 重试节奏，为 None 时使用全局配置
+    #[serde(default)]
+    pub pacing: Option<PacingPolicy>,
+    #[serde(default)]
+    pub status: OrderStatus,
+    /// 所属订单组，单独下单时为 None
+    #[serde(default)]
+    pub group_id: Option<Uuid>,
+    /// 止盈止损（OCO）订单中的另一笔订单，其中一笔触发时另一笔被取消
+    #[serde(default)]
+    pub oco_sibling: Option<Uuid>,
+    /// `price` 所指的价格
+    #[serde(default)]
+    pub trigger_on: TriggerOn,
+    /// 报价模式，`ExactOut` 时 `amount` 为期望得到的输出代币数量
+    #[serde(default)]
+    pub swap_mode: SwapMode,
+    /// `/quote` 返回的路由令牌，触发时优先使用其中的报价
+    #[serde(default)]
+    pub route_token: Option<String>,
+    /// 固定路由过期或不再适用时是否改用新的报价
+    #[serde(default = "default_pin_fallback")]
+    pub pin_fallback: bool,
+    /// 分批执行的批数，为 None 时一次性执行
+    #[serde(default)]
+    pub split_parts: Option<u32>,
+    /// 订单类型
+    #[serde(default)]
+    pub kind: OrderKind,
+    /// 限价单的触发方向，下单时必须指定；其他类型的订单为 None
+    #[serde(default)]
+    pub trigger_condition: Option<TriggerCondition>,
+    /// 订单成交、失败或过期时回调的地址
+    #[serde(default)]
+    pub callback_url: Option<String>,
+    /// 发送前是否跳过模拟执行
+    #[serde(default)]
+    pub skip_simulation: bool,
+    /// 是否无论金额大小都以 Jito bundle 私有发送，未指定 tip 时按 tip 表自动选择
+    #[serde(default)]
+    pub force_private_execution: bool,
+    /// 扣税后的最低输出数量，报价低于该数量时不执行，继续等待价格
+    #[serde(default)]
+    pub min_out_amount: Option<u64>,
+    /// 允许的最大价格影响，报价超过时不执行，继续等待价格
+    #[serde(default)]
+    pub max_price_impact_bps: Option<Bps>,
+    /// 报价隐含的成交价格相对触发时价格的最大偏离，超过时不执行，继续等待价格；为 None 时使用全局配置
+    #[serde(default)]
+    pub max_trigger_deviation_bps: Option<Bps>,
+    /// 按成交价格触发时的最短询价间隔（毫秒），不低于全局配置
+    #[serde(default)]
+    pub poll_interval_ms: Option<u64>,
+    /// 成交后重新挂单的次数，为 None 时成交一次即结束
+    #[serde(default)]
+    pub repeat_count: Option<u32>,
+    /// 每次重新挂单时触发价格的调整（基点，可为负数）
+    #[serde(default)]
+    pub reprice_offset_bps: Option<i16>,
+    /// 重新挂单时反向交易，以上一次成交得到的输出代币作为输入
+    #[serde(default)]
+    pub compound: bool,
+    /// 已完成的成交次数
+    #[serde(default)]
+    pub fills_completed: u32,
+    /// 剩余的成交次数，包括当前等待触发的一次
+    #[serde(default = "default_fills_remaining")]
+    pub fills_remaining: u32,
+    /// 是否单独暂停执行，见 [`PauseSwitch`]
+    #[serde(default)]
+    pub paused: bool,
+    /// SOL 包装、目标代币账户等交易选项
+    #[serde(default)]
+    pub swap_options: SwapOptions,
+    /// 触发后执行的时限（毫秒），报价、重试和交易确认需在时限内完成，否则放弃本次执行
+    #[serde(default)]
+    pub post_trigger_deadline_ms: Option<u64>,
+    /// 执行超时后回到监控等待下一次触发；为 false 时订单以 `Unfilled` 结束
+    #[serde(default)]
+    pub rearm_after_timeout: bool,
+    /// 执行超时的次数，最近一次的原因记录在 `last_rejection` 中
+    #[serde(default)]
+    pub execution_timeouts: u32,
+    /// 触发后因报价隐含的成交价格偏离触发价格过大而放弃执行的次数，最近一次的原因记录在 `last_rejection` 中
+    #[serde(default)]
+    pub deviation_rejections: u32,
+    /// 分批执行时已成交的数量
+    #[serde(default)]
+    pub filled_amount: u64,
+    /// 分批执行时已成交批次的交易签名
+    #[serde(default)]
+    pub fill_signatures: Vec<String>,
+    /// 已成交部分扣税后至少得到的输出数量（按报价的滑点下限计算）
+    #[serde(default)]
+    pub out_amount: u64,
+    /// 已成交部分收取的税收，以 `tax_mint` 计价
+    #[serde(default)]
+    pub tax_amount: u64,
+    /// 税收的代币，尚未成交时为 None
+    #[serde(default)]
+    pub tax_mint: Option<String>,
+    /// 最近一次触发时按税收策略解析的税率，非托管订单为生成待签名交易时的税率；尚未触发时为 None
+    #[serde(default)]
+    pub tax_bps: Option<Bps>,
+    /// 最近一次成交的实际数量、成交价格和相对触发价格的偏离，尚未成交时为 None
+    #[serde(default)]
+    pub last_fill: Option<FillReport>,
+    /// 托管订单最近一次成交的交易签名、bundle id 和报价，尚未成交或非托管订单时为 None
+    #[serde(default)]
+    pub fill: Option<SwapOutcome>,
+    /// 下单时间（毫秒时间戳）
+    #[serde(default)]
+    pub created_at: u64,
+    /// 过期时间（毫秒时间戳），到期时仍在等待触发的订单以 `Expired` 结束；为 None 时一直等待触发
+    #[serde(default)]
+    pub expires_at_ms: Option<u64>,
+    /// 最近一次触发后未执行的原因（最低输出、价格影响等），订单仍在等待价格
+    #[serde(default)]
+    pub last_rejection: Option<String>,
+    /// 下单时余额不足的警告，只在 `FUNDING_CHECK=warn` 时出现
+    #[serde(default)]
+    pub funding_warning: Option<String>,
+}
+
+fn default_pin_fallback() -> bool {
+    true
+}
+
+fn default_fills_remaining() -> u32 {
+    1
+}
+
+impl Order {
+    /// 重复订单成交一次后重新挂单，`proceeds` 为本次成交扣税后至少得到的输出数量
+    ///
+    /// 触发价格按 `reprice_offset_bps` 调整；复利时交换输入输出代币，以 `proceeds` 作为下一次的数量，
+    /// 比值价格反向后取倒数再调整。
+    fn rearm(&mut self, proceeds: u64) {
+        let mut price = self.price;
+        if self.compound {
+            std::mem::swap(&mut self.input_mint, &mut self.output_mint);
+            self.amount = proceeds;
+            price = 1.0 / price;
+            // 取倒数后价格的大小关系反向
+            self.trigger_condition = self.trigger_condition.map(TriggerCondition::opposite);
+        }
+        if let Some(offset) = self.reprice_offset_bps {
+            price *= 1.0 + offset as f32 / Bps::MAX as f32;
+        }
+        self.price = price;
+        self.filled_amount = 0;
+        self.out_amount = 0;
+        self.tax_amount = 0;
+        self.tax_mint = None;
+        self.status = OrderStatus::Pending;
+    }
+}
+
+/// 限价单的触发方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum TriggerCondition {
+    /// 价格涨到 `price` 及以上时触发，例如高价卖出
+    Above,
+    /// 价格跌到 `price` 及以下时触发，例如低价买入或止损
+    Below,
+}
+
+impl TriggerCondition {
+    pub fn opposite(self) -> TriggerCondition {
+        match self {
+            TriggerCondition::Above => TriggerCondition::Below,
+            TriggerCondition::Below => TriggerCondition::Above,
+        }
+    }
+}
+
+/// 订单类型
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type")]
+pub enum OrderKind {
+    /// 限价单，价格按 `trigger_condition` 的方向越过 `price` 时触发
+    #[default]
+    Limit,
+    /// 跟踪止损，记录下单后观察到的最高价格，价格从最高点回落超过 `trail_bps` 时触发，不使用 `price`
+    TrailingStop { trail_bps: Bps },
+    /// 止盈，价格涨到 `price` 及以上时触发
+    TakeProfit,
+    /// 止损，价格跌到 `price` 及以下时触发
+    StopLoss,
+}
+
+/// 触发判断所需的状态，由订单任务在每轮价格刷新时更新
+#[derive(Debug, Clone, Copy)]
+pub struct TriggerState {
+    /// 限价单的触发价格
+    pub limit_price: f32,
+    /// 限价单的触发方向
+    pub condition: Option<TriggerCondition>,
+    /// 跟踪止损观察到的最高价格，尚未观察到价格时为 None
+    pub high_water: Option<f64>,
+}
+
+impl TriggerState {
+    pub fn new(limit_price: f32, condition: Option<TriggerCondition>) -> TriggerState {
+        TriggerState {
+            limit_price,
+            condition,
+            high_water: None,
+        }
+    }
+
+    /// 当前的触发价格，跟踪止损尚未观察到价格时为 None
+    pub fn trigger_price(&self, kind: OrderKind) -> Option<f64> {
+        match kind {
+            OrderKind::TrailingStop { trail_bps } => self
+                .high_water
+                .map(|high_water| high_water * (1.0 - trail_bps.get() as f64 / Bps::MAX as f64)),
+            _ => Some(self.limit_price as f64),
+        }
+    }
+}
+
+/// 根据订单类型判断当前价格是否触发，跟踪止损会先用当前价格更新最高价格
+///
+/// 没有触发方向的限价单不会触发，下单时已校验必须指定方向。
+pub fn should_trigger(kind: OrderKind, state: &mut TriggerState, now_price: f64) -> bool {
+    match kind {
+        OrderKind::Limit => state
+            .condition
+            .is_some_and(|condition| price_triggered(condition, state.limit_price, now_price)),
+        OrderKind::TrailingStop { trail_bps } => {
+            let high_water = state
+                .high_water
+                .map_or(now_price, |high_water| high_water.max(now_price));
+            state.high_water = Some(high_water);
+            let stop_price = high_water * (1.0 - trail_bps.get() as f64 / Bps::MAX as f64);
+            now_price < stop_price
+        }
+        OrderKind::TakeProfit => {
+            price_triggered(TriggerCondition::Above, state.limit_price, now_price)
+        }
+        OrderKind::StopLoss => {
+            price_triggered(TriggerCondition::Below, state.limit_price, now_price)
+        }
+    }
+}
+
+/// 订单 `price` 字段所指的价格
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum TriggerOn {
+    /// 输入代币的 USD 价格
+    #[default]
+    InputUsd,
+    /// 输出代币的 USD 价格
+    OutputUsd,
+    /// 1 个输入代币可换得的输出代币数量，即输入代币 USD 价格 / 输出代币 USD 价格
+    ///
+    /// 两边都是界面单位（已按各自精度换算）的价格，因此与代币精度无关，
+    /// 例如 SOL 为 150 USD、USDC 为 1 USD 时比值为 150。
+    Ratio,
+    /// 按订单实际数量向 Jupiter 询价得到的成交价格：报价输出数量 / 输入数量（均为界面单位），含义同 `Ratio`
+    ///
+    /// 流动性较差或数量较大时，USD 价格与实际成交价格可能相差很多，此时应使用该模式。
+    /// 询价间隔由 `EXECUTABLE_QUOTE_INTERVAL_MS` 配置，同一代币对、相近数量的订单共享报价。
+    ExecutablePrice,
+}
+
+/// 计算输入代币相对输出代币的价格比值
+pub fn price_ratio(input_usd: f64, output_usd: f64) -> Result<f64> {
+    if output_usd <= 0.0 || !output_usd.is_finite() || !input_usd.is_finite() {
+        return Err(anyhow!(
+            "无法计算价格比值，输入 {} 输出 {}",
+            input_usd,
+            output_usd
+        ));
+    }
+    Ok(input_usd / output_usd)
+}
+
+/// 按 `trigger_on` 所指的价格换算成交价格，数量为最小单位，按各自的精度换算为界面单位
+///
+/// `InputUsd` 以输出代币的 USD 价格换算，`OutputUsd` 以输入代币的 USD 价格换算，所需价格不可用或数量为 0 时返回 None。
+pub fn execution_price(
+    trigger_on: TriggerOn,
+    input: (u64, u8),
+    output: (u64, u8),
+    input_usd: Option<f64>,
+    output_usd: Option<f64>,
+) -> Option<f64> {
+    let in_ui = input.0 as f64 / 10f64.powi(input.1 as i32);
+    let out_ui = output.0 as f64 / 10f64.powi(output.1 as i32);
+    if in_ui <= 0.0 {
+        return None;
+    }
+    let ratio = out_ui / in_ui;
+    let price = match trigger_on {
+        TriggerOn::Ratio | TriggerOn::ExecutablePrice => ratio,
+        TriggerOn::InputUsd => ratio * output_usd?,
+        TriggerOn::OutputUsd if ratio > 0.0 => input_usd? / ratio,
+        TriggerOn::OutputUsd => return None,
+    };
+    Some(price).filter(|price| price.is_finite())
+}
+
+/// 成交价格相对触发价格的偏离（基点，取绝对值），触发价格无效时返回 None
+pub fn trigger_deviation_bps(trigger_price: f64, execution_price: f64) -> Option<u64> {
+    if trigger_price <= 0.0 || !trigger_price.is_finite() || !execution_price.is_finite() {
+        return None;
+    }
+    Some(((execution_price - trigger_price).abs() / trigger_price * Bps::MAX as f64).round() as u64)
+}
+
+/// 检查报价隐含的成交价格相对触发价格的偏离是否超过 `max_bps`，偏离无法计算时不拒绝
+pub fn check_trigger_deviation(
+    trigger_price: f64,
+    execution_price: f64,
+    max_bps: Bps,
+) -> error::Result<()> {
+    match trigger_deviation_bps(trigger_price, execution_price) {
+        Some(deviation_bps) if deviation_bps > max_bps.get() as u64 => {
+            Err(LimitOrderError::TriggerDeviationTooHigh {
+                trigger_price,
+                execution_price,
+                deviation_bps,
+                max_bps: max_bps.get(),
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// 订单组中的一笔订单参数
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct OrderLeg {
+    pub input_mint: String,
+    pub output_mint: String,
+    pub price: f32,
+    pub amount: u64,
+    pub slippage_bps: Bps,
+    pub tip_amount: Option<Lamports>,
+    pub priority_fee_micro_lamports: Option<u64>,
+    #[serde(default)]
+    pub pacing: Option<PacingPolicy>,
+    #[serde(default)]
+    pub trigger_on: TriggerOn,
+    #[serde(default)]
+    pub swap_mode: SwapMode,
+    #[serde(default)]
+    pub route_token: Option<String>,
+    #[serde(default = "default_pin_fallback")]
+    pub pin_fallback: bool,
+    #[serde(default)]
+    pub split_parts: Option<u32>,
+    #[serde(default)]
+    pub kind: OrderKind,
+    #[serde(default)]
+    pub trigger_condition: Option<TriggerCondition>,
+    #[serde(default)]
+    pub callback_url: Option<String>,
+    #[serde(default)]
+    pub skip_simulation: bool,
+    #[serde(default)]
+    pub force_private_execution: bool,
+    #[serde(default)]
+    pub min_out_amount: Option<u64>,
+    #[serde(default)]
+    pub max_price_impact_bps: Option<Bps>,
+    #[serde(default)]
+    pub max_trigger_deviation_bps: Option<Bps>,
+    #[serde(default)]
+    pub poll_interval_ms: Option<u64>,
+    #[serde(default)]
+    pub repeat_count: Option<u32>,
+    #[serde(default)]
+    pub reprice_offset_bps: Option<i16>,
+    #[serde(default)]
+    pub compound: bool,
+    #[serde(default)]
+    pub swap_options: SwapOptions,
+    #[serde(default)]
+    pub post_trigger_deadline_ms: Option<u64>,
+    #[serde(default)]
+    pub rearm_after_timeout: bool,
+    #[serde(default)]
+    pub ttl_secs: Option<u64>,
+}
+
+/// 分批执行的最大批数
+pub const MAX_SPLIT_PARTS: u32 = 100;
+/// 成交后重新挂单的最大次数
+pub const MAX_REPEAT_COUNT: u32 = 1000;
+/// 触发后执行时限的下限，低于该值时报价都来不及完成
+pub const MIN_POST_TRIGGER_DEADLINE_MS: u64 = 500;
+
+impl OrderLeg {
+    /// 在创建任何订单之前检查参数，避免订单组创建到一半才失败
+    fn validate(&self) -> Result<()> {
+        self.input_mint
+            .parse::<Pubkey>()
+            .map_err(|_| anyhow!("输入代币地址无效 {}", self.input_mint))?;
+        self.output_mint
+            .parse::<Pubkey>()
+            .map_err(|_| anyhow!("输出代币地址无效 {}", self.output_mint))?;
+        if self.amount == 0 {
+            return Err(anyhow!("订单数量不能为 0"));
+        }
+        self.check_route_token()?;
+        self.check_split_parts()?;
+        self.check_repeat()?;
+        self.check_min_out()?;
+        self.check_deadline()?;
+        self.check_ttl()?;
+        self.swap_options.destination()?;
+        self.check_kind()
+    }
+
+    /// 执行时限不能低于 [`MIN_POST_TRIGGER_DEADLINE_MS`]，`rearm_after_timeout` 需要同时设置时限
+    fn check_deadline(&self) -> Result<()> {
+        match self.post_trigger_deadline_ms {
+            Some(ms) if ms < MIN_POST_TRIGGER_DEADLINE_MS => Err(anyhow!(
+                "post_trigger_deadline_ms 不能低于 {}",
+                MIN_POST_TRIGGER_DEADLINE_MS
+            )),
+            None if self.rearm_after_timeout => Err(anyhow!(
+                "rearm_after_timeout 需要同时设置 post_trigger_deadline_ms"
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// 有效期为 0 的订单在开始监控前就已过期
+    fn check_ttl(&self) -> Result<()> {
+        if self.ttl_secs == Some(0) {
+            return Err(anyhow!("ttl_secs 必须大于 0"));
+        }
+        Ok(())
+    }
+
+    /// `ExactOut` 的输出数量固定，最低输出没有意义
+    fn check_min_out(&self) -> Result<()> {
+        if self.min_out_amount.is_some() && self.swap_mode == SwapMode::ExactOut {
+            return Err(anyhow!("ExactOut 模式不支持 min_out_amount"));
+        }
+        Ok(())
+    }
+
+    fn check_callback_url(&self, allow_private: bool) -> Result<()> {
+        if let Some(url) = &self.callback_url {
+            validate_callback_url(url, allow_private)?;
+        }
+        Ok(())
+    }
+
+    fn check_kind(&self) -> Result<()> {
+        match (self.kind, self.trigger_condition) {
+            (OrderKind::Limit, None) => {
+                return Err(anyhow!("限价单必须指定 trigger_condition：Above 或 Below"));
+            }
+            (OrderKind::Limit, Some(_)) | (_, None) => {}
+            (_, Some(_)) => return Err(anyhow!("trigger_condition 只适用于 Limit 订单")),
+        }
+        if let OrderKind::TrailingStop { trail_bps } = self.kind {
+            if trail_bps == Bps::ZERO || trail_bps.get() >= Bps::MAX {
+                return Err(anyhow!("跟踪止损的回撤比例必须在 0 到 10000 之间"));
+            }
+        }
+        Ok(())
+    }
+
+    /// 批数必须在 1 到 [`MAX_SPLIT_PARTS`] 之间且每批数量不为 0，分批执行时报价数量与路由令牌不一致，不能同时使用
+    fn check_split_parts(&self) -> Result<()> {
+        let Some(parts) = self.split_parts else {
+            return Ok(());
+        };
+        if parts == 0 || parts > MAX_SPLIT_PARTS {
+            return Err(anyhow!("分批数量必须在 1 到 {} 之间", MAX_SPLIT_PARTS));
+        }
+        if self.amount < parts as u64 {
+            return Err(anyhow!("订单数量 {} 不足以分成 {} 批", self.amount, parts));
+        }
+        if self.route_token.is_some() {
+            return Err(anyhow!("分批执行不支持固定路由"));
+        }
+        Ok(())
+    }
+
+    /// 重复次数必须在 1 到 [`MAX_REPEAT_COUNT`] 之间，不能与分批执行同时使用，调整后的价格必须仍为正数
+    ///
+    /// 复利时下一次反向交易、触发价格取倒数，因此只支持按比值触发的 ExactIn 订单，
+    /// 成交的代币必须留在下单钱包，最低输出只对第一次交易有意义，不能设置。
+    fn check_repeat(&self) -> Result<()> {
+        let Some(count) = self.repeat_count else {
+            if self.reprice_offset_bps.is_some() || self.compound {
+                return Err(anyhow!(
+                    "reprice_offset_bps 和 compound 需要同时设置 repeat_count"
+                ));
+            }
+            return Ok(());
+        };
+        if count == 0 || count > MAX_REPEAT_COUNT {
+            return Err(anyhow!("重复次数必须在 1 到 {} 之间", MAX_REPEAT_COUNT));
+        }
+        if self.split_parts.is_some() {
+            return Err(anyhow!("重复挂单不支持分批执行"));
+        }
+        if self
+            .reprice_offset_bps
+            .is_some_and(|bps| bps <= -(Bps::MAX as i16))
+        {
+            return Err(anyhow!("价格调整必须大于 -10000 基点"));
+        }
+        if self.compound {
+            if self.swap_mode != SwapMode::ExactIn {
+                return Err(anyhow!("复利只支持 ExactIn 模式"));
+            }
+            if !matches!(
+                self.trigger_on,
+                TriggerOn::Ratio | TriggerOn::ExecutablePrice
+            ) {
+                return Err(anyhow!("复利只支持 Ratio 或 ExecutablePrice 触发"));
+            }
+            if self.min_out_amount.is_some() {
+                return Err(anyhow!("复利不支持 min_out_amount"));
+            }
+            if self.swap_options.destination_token_account.is_some() {
+                return Err(anyhow!("复利时成交的代币必须留在下单钱包"));
+            }
+        }
+        Ok(())
+    }
+
+    /// 路由令牌必须有效且与订单的代币一致，报价数量和滑点在执行时按当时的税率再次校验
+    fn check_route_token(&self) -> Result<()> {
+        if let Some(route) = route_pin(self.route_token.as_deref(), self.pin_fallback)? {
+            if route.route.quote.input_mint.to_string() != self.input_mint
+                || route.route.quote.output_mint.to_string() != self.output_mint
+            {
+                return Err(anyhow!("路由令牌的代币与订单不一致"));
+            }
+        }
+        Ok(())
+    }
+
+    fn into_order(self, owner: Pubkey, group_id: Option<Uuid>) -> Order {
+        let created_at = now_ms();
+        Order {
+            order_id: Uuid::new_v4(),
+            owner: owner.to_string(),
+            price: self.price,
+            input_mint: self.input_mint,
+            output_mint: self.output_mint,
+            amount: self.amount,
+            slippage_bps: self.slippage_bps,
+            tip_amount: self.tip_amount,
+            priority_fee_micro_lamports: self.priority_fee_micro_lamports,
+            pacing: self.pacing,
+            status: OrderStatus::Pending,
+            group_id,
+            oco_sibling: None,
+            trigger_on: self.trigger_on,
+            swap_mode: self.swap_mode,
+            route_token: self.route_token,
+            pin_fallback: self.pin_fallback,
+            split_parts: self.split_parts,
+            kind: self.kind,
+            trigger_condition: self.trigger_condition,
+            callback_url: self.callback_url,
+            skip_simulation: self.skip_simulation,
+            force_private_execution: self.force_private_execution,
+            min_out_amount: self.min_out_amount,
+            max_price_impact_bps: self.max_price_impact_bps,
+            max_trigger_deviation_bps: self.max_trigger_deviation_bps,
+            poll_interval_ms: self.poll_interval_ms,
+            repeat_count: self.repeat_count,
+            reprice_offset_bps: self.reprice_offset_bps,
+            compound: self.compound,
+            fills_completed: 0,
+            fills_remaining: self.repeat_count.unwrap_or(0) + 1,
+            paused: false,
+            swap_options: self.swap_options,
+            post_trigger_deadline_ms: self.post_trigger_deadline_ms,
+            rearm_after_timeout: self.rearm_after_timeout,
+            execution_timeouts: 0,
+            deviation_rejections: 0,
+            filled_amount: 0,
+            fill_signatures: vec![],
+            out_amount: 0,
+            tax_amount: 0,
+            tax_mint: None,
+            tax_bps: None,
+            last_fill: None,
+            fill: None,
+            created_at,
+            expires_at_ms: self
+                .ttl_secs
+                .map(|secs| created_at.saturating_add(secs.saturating_mul(1000))),
+            last_rejection: None,
+            funding_warning: None,
+        }
+    }
+}
+
+/// 解析订单携带的路由令牌
+fn route_pin(route_token: Option<&str>, fallback: bool) -> Result<Option<RoutePin>> {
+    route_token
+        .map(|token| {
+            Ok(RoutePin {
+                route: PinnedRoute::from_token(token)?,
+                fallback,
+            })
+        })
+        .transpose()
+}
+
+/// 可在运行时调整的全局交易配置
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
+pub struct RuntimeConfig {
+    /// 税收策略的默认税率，免税钱包和分档见 [`TaxPolicy`]
+    pub tax_bps: Bps,
+    /// 收税的一侧，未提供时为 `Input`
+    #[serde(default)]
+    pub tax_side: TaxSide,
+    pub retry_policy: RetryPolicy,
+}
+
+/// [`OrderBook::simulate_order`] 模拟的订单
+pub enum SimulationTarget {
+    /// 已有的订单
+    Existing(Uuid),
+    /// 按下单参数新建的订单，以 `user` 为付款人
+    New { user: Pubkey, leg: Box<OrderLeg> },
+}
+
+/// [`OrderBook::quote_order`] 的预览结果，数量均为最小单位
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct OrderQuote {
+    /// 报价的输入数量，不含以输入代币收取的税收
+    pub in_amount: u64,
+    /// 扣税后预计得到的输出数量，已扣除输出代币的转账手续费
+    pub out_amount: u64,
+    /// 税收数量，代币为 `tax_mint`
+    pub tax_amount: u64,
+    pub tax_mint: String,
+    pub tax_side: TaxSide,
+    /// 按税收策略解析的税率，预览不区分钱包，免税钱包和单独税率不生效
+    pub tax_bps: Bps,
+    /// 输出代币的 Token-2022 转账手续费（基点），没有手续费时为 None
+    pub output_transfer_fee_bps: Option<u16>,
+    pub price_impact_bps: u64,
+    /// 路由经过的 AMM
+    pub route_labels: Vec<String>,
+    pub trigger_on: TriggerOn,
+    /// `trigger_on` 所指的当前价格，价格源请求失败时为 None
+    pub current_price: Option<f64>,
+    /// 按当前报价触发时会被跳过的原因（低于最低输出或价格影响过大）
+    pub rejection: Option<String>,
+}
+
+/// [`OrderBook::estimate_fees`] 的费用明细，SOL 费用的单位均为 lamports
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct FeeEstimate {
+    /// 交换交易需要的签名数
+    pub signatures: u8,
+    /// 签名费
+    pub base_fee: u64,
+    /// 推荐的计算单元价格，单位为 micro-lamports / CU
+    pub compute_unit_price: u64,
+    /// 估算优先费使用的计算单元数
+    pub compute_units: u32,
+    pub priority_fee: u64,
+    /// Jito tip，未提供时为 0
+    pub tip: u64,
+    /// 税收数量，代币为 `tax_mint`
+    pub tax_amount: u64,
+    pub tax_mint: String,
+    pub tax_side: TaxSide,
+    /// 各项费用之和，税收不是 SOL 时不计入
+    pub total_lamports: u64,
+}
+
+/// 按全局配置解析出的订单实际执行参数
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct ResolvedOrder {
+    /// 实际税率
+    pub tax_bps: Bps,
+    pub tip_amount: Option<Lamports>,
+    pub slippage_bps: Bps,
+    /// 实际重试节奏
+    pub pacing: PacingPolicy,
+    pub max_retries: u32,
+    /// 该配置下订单会被拒绝的原因
+    pub rejection: Option<String>,
+}
+
+/// 下单时解析订单参数，下单校验和配置预览使用同一套逻辑
+pub fn resolve_order(order: &Order, config: &RuntimeConfig) -> ResolvedOrder {
+    let rejection = order
+        .input_mint
+        .parse::<Pubkey>()
+        .map_err(|_| anyhow!("输入代币地址无效 {}", order.input_mint))
+        .and_then(|_| check_swap_mode(config.tax_side, order.swap_mode, config.tax_bps))
+        .and_then(|_| {
+            let output_mint = order
+                .output_mint
+                .parse()
+                .map_err(|_| anyhow!("输出代币地址无效 {}", order.output_mint))?;
+            check_swap_options(
+                config.tax_side,
+                config.tax_bps,
+                output_mint,
+                &order.swap_options,
+            )
+        })
+        .err()
+        .map(|e| e.to_string());
+    ResolvedOrder {
+        tax_bps: config.tax_bps,
+        tip_amount: order.tip_amount,
+        slippage_bps: order.slippage_bps,
+        pacing: order.pacing.unwrap_or(config.retry_policy.pacing),
+        max_retries: config.retry_policy.max_retries,
+        rejection,
+    }
+}
+
+/// 配置变更对单个活跃订单的影响
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct ConfigPreview {
+    pub order_id: Uuid,
+    pub current: ResolvedOrder,
+    pub candidate: ResolvedOrder,
+    pub changed: bool,
+    /// 当前配置下有效、候选配置下会被拒绝
+    pub newly_rejected: bool,
+}
+
+/// 一次请求创建的一组订单（阶梯单、批量单等）
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct OrderGroup {
+    pub group_id: Uuid,
+    /// 客户端提供的幂等键，同一钱包使用相同的键重试时返回同一个订单组
+    pub client_group_id: String,
+    pub owner: String,
+    /// 组内订单，顺序与请求一致
+    pub order_ids: Vec<Uuid>,
+}
+
+/// 止盈止损订单的两笔订单 ID
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct Bracket {
+    pub group_id: Uuid,
+    pub take_profit: Uuid,
+    pub stop_loss: Uuid,
+}
+
+/// 订单任务的撤单信号
+///
+/// OCO 订单组内的订单持有彼此的句柄，一笔触发时通过同一个信号取消另一笔。
+#[derive(Clone)]
+pub struct CancelHandle(Arc<StdMutex<Option<Sender<()>>>>);
+
+impl CancelHandle {
+    fn new(tx: Sender<()>) -> CancelHandle {
+        CancelHandle(Arc::new(StdMutex::new(Some(tx))))
+    }
+
+    /// 通知订单任务退出，信号已经发出过时返回 false
+    pub fn cancel(&self) -> bool {
+        match self.0.lock().unwrap().take() {
+            Some(tx) => {
+                let _ = tx.send(());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 信号是否已经发出，订单已被取消或已过期
+    fn is_spent(&self) -> bool {
+        self.0.lock().unwrap().is_none()
+    }
+}
+
+/// OCO 订单组内订单任务共享的状态
+///
+/// 两笔订单可能在同一轮价格中同时满足条件，因此触发前必须先取得组内的锁并登记为胜出的订单，
+/// 先登记的订单执行，另一笔不会发送交易。
+#[derive(Default)]
+struct OcoGroup {
+    /// 胜出的订单
+    winner: StdMutex<Option<Uuid>>,
+    /// 组内订单任务的撤单信号
+    cancels: StdMutex<HashMap<Uuid, CancelHandle>>,
+}
+
+impl OcoGroup {
+    /// 登记为胜出的订单，另一笔已经胜出时返回 false；分批执行的订单每批都会调用
+    fn claim(&self, order_id: Uuid) -> bool {
+        let mut winner = self.winner.lock().unwrap();
+        match *winner {
+            Some(winner) => winner == order_id,
+            None => {
+                *winner = Some(order_id);
+                true
+            }
+        }
+    }
+
+    fn register(&self, order_id: Uuid, cancel: CancelHandle) {
+        self.cancels.lock().unwrap().insert(order_id, cancel);
+    }
+
+    /// 通知组内其他订单的任务退出
+    fn cancel_siblings(&self, order_id: Uuid) {
+        for (id, cancel) in self.cancels.lock().unwrap().iter() {
+            if *id != order_id {
+                cancel.cancel();
+            }
+        }
+    }
+}
+
+/// 暂停执行的开关
+///
+/// 暂停期间订单继续监控价格，但触发后不发送交易，等待恢复或撤单；恢复后按新一轮价格重新判断是否触发。
+#[derive(Clone)]
+pub struct PauseSwitch(Arc<watch::Sender<PauseState>>);
+
+/// 当前的暂停状态
+#[derive(Debug, Clone, Default, Serialize, JsonSchema)]
+pub struct PauseState {
+    /// 是否暂停全部订单
+    pub global: bool,
+    /// 单独暂停的订单
+    pub orders: HashSet<Uuid>,
+}
+
+impl PauseState {
+    fn is_paused(&self, order_id: &Uuid) -> bool {
+        self.global || self.orders.contains(order_id)
+    }
+}
+
+impl Default for PauseSwitch {
+    fn default() -> Self {
+        PauseSwitch(Arc::new(watch::channel(PauseState::default()).0))
+    }
+}
+
+impl PauseSwitch {
+    pub fn state(&self) -> PauseState {
+        self.0.borrow().clone()
+    }
+
+    pub fn is_paused(&self, order_id: &Uuid) -> bool {
+        self.0.borrow().is_paused(order_id)
+    }
+
+    pub fn set_global(&self, paused: bool) {
+        self.0.send_modify(|state| state.global = paused);
+    }
+
+    /// 暂停或恢复单笔订单，状态没有变化时返回 false
+    pub fn set_order(&self, order_id: Uuid, paused: bool) -> bool {
+        self.0.send_if_modified(|state| {
+            if paused {
+                state.orders.insert(order_id)
+            } else {
+                state.orders.remove(&order_id)
+            }
+        })
+    }
+
+    /// 等待订单恢复执行
+    async fn resumed(&self, order_id: Uuid) {
+        let mut rx = self.0.subscribe();
+        // 发送端由本结构持有，不会关闭
+        let _ = rx.wait_for(|state| !state.is_paused(&order_id)).await;
+    }
+}
+
+/// 修改订单的参数，为 None 的字段保持不变
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+pub struct OrderChanges {
+    pub price: Option<f32>,
+    pub amount: Option<u64>,
+    pub slippage_bps: Option<Bps>,
+}
+
+impl OrderChanges {
+    fn validate(&self) -> error::Result<()> {
+        if let Some(price) = self.price {
+            if !(price.is_finite() && price > 0.0) {
+                return Err(LimitOrderError::invalid("price", "触发价格必须为正数"));
+            }
+        }
+        if self.amount == Some(0) {
+            return Err(LimitOrderError::invalid("amount", "数量必须大于 0"));
+        }
+        Ok(())
+    }
+
+    fn apply(&self, order: &mut Order) {
+        if let Some(price) = self.price {
+            order.price = price;
+        }
+        if let Some(amount) = self.amount {
+            order.amount = amount;
+        }
+        if let Some(slippage_bps) = self.slippage_bps {
+            order.slippage_bps = slippage_bps;
+        }
+    }
+}
+
+/// 批量撤单的筛选条件，为 None 的条件不限制
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CancelFilter {
+    pub input_mint: Option<String>,
+    pub output_mint: Option<String>,
+}
+
+impl CancelFilter {
+    fn matches(&self, order: &Order) -> bool {
+        self.input_mint
+            .as_ref()
+            .is_none_or(|mint| *mint == order.input_mint)
+            && self
+                .output_mint
+                .as_ref()
+                .is_none_or(|mint| *mint == order.output_mint)
+    }
+}
+
+/// 批量撤单的结果
+#[derive(Debug, Clone, Default, Serialize, JsonSchema)]
+pub struct CancelAllResult {
+    /// 已取消的订单
+    pub canceled: Vec<Uuid>,
+    /// 撤单时已经触发或成交的订单，这些订单不受影响
+    pub already_filled: Vec<Uuid>,
+    /// 因其他原因未能取消的订单及原因
+    pub failed: Vec<(Uuid, String)>,
+}
+
+/// 批量查询中一笔订单的状态
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct OrderStatusView {
+    /// 状态名称，见 [`OrderStatus::label`]；订单不存在时为 `not_found`
+    pub status: String,
+    /// 完整的订单状态，包含交易签名、失败原因等，订单不存在时为 None
+    pub detail: Option<OrderStatus>,
+    /// 已成交的数量，单位与订单 `amount` 相同
+    pub filled_amount: u64,
+    /// 已成交批次的交易签名
+    pub fill_signatures: Vec<String>,
+    /// 触发后因成交价格偏离触发价格过大而放弃执行的次数
+    pub deviation_rejections: u32,
+    /// 最近一次触发后未执行的原因
+    pub last_rejection: Option<String>,
+    /// 最近一次成交的实际数量和成交价格
+    pub last_fill: Option<FillReport>,
+    /// 托管订单最近一次成交的交易签名和 bundle id
+    pub fill: Option<SwapOutcome>,
+}
+
+impl OrderStatusView {
+    pub fn not_found() -> OrderStatusView {
+        OrderStatusView {
+            status: "not_found".to_string(),
+            detail: None,
+            filled_amount: 0,
+            fill_signatures: vec![],
+            deviation_rejections: 0,
+            last_rejection: None,
+            last_fill: None,
+            fill: None,
+        }
+    }
+}
+
+impl From<&Order> for OrderStatusView {
+    fn from(order: &Order) -> OrderStatusView {
+        OrderStatusView {
+            status: order.status.label().to_string(),
+            detail: Some(order.status.clone()),
+            filled_amount: order.filled_amount,
+            fill_signatures: order.fill_signatures.clone(),
+            deviation_rejections: order.deviation_rejections,
+            last_rejection: order.last_rejection.clone(),
+            last_fill: order.last_fill,
+            fill: order.fill.clone(),
+        }
+    }
+}
+
+/// 订单状态
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub enum OrderStatus {
+    /// 等待价格触发
+    #[default]
+    Pending,
+    /// 交易已发送，等待上链；重启后不会再次发送
+    Triggered {
+        signature: String,
+        /// 通过 Jito bundle 发送时的 bundle id
+        bundle_id: Option<String>,
+    },
+    /// 已成交
+    Filled,
+    /// 分批执行的订单只成交了一部分，剩余批次因撤单、价格偏离或失败被放弃
+    PartiallyFilled {
+        /// 已成交的数量，单位与订单 `amount` 相同
+        filled_amount: u64,
+        /// 已成交批次的交易签名
+        signatures: Vec<String>,
+    },
+    /// 已取消
+    Canceled,
+    /// 重试耗尽后失败
+    Failed(String),
+    /// 非托管订单的签名交易已过期，需要客户端重新生成并签名
+    ResignRequired,
+    /// 触发后未能在 `post_trigger_deadline_ms` 内完成执行，没有交易上链，携带超时原因
+    Unfilled(String),
+    /// 到达 `expires_at_ms` 时仍未触发
+    Expired,
+}
+
+impl OrderStatus {
+    /// 终态在数据库和历史查询中使用的名称
+    pub const TERMINAL: [&'static str; 7] = [
+        "filled",
+        "partially_filled",
+        "canceled",
+        "failed",
+        "resign_required",
+        "unfilled",
+        "expired",
+    ];
+
+    /// 数据库中保存的状态名称
+    pub fn label(&self) -> &'static str {
+        match self {
+            OrderStatus::Pending => "pending",
+            OrderStatus::Triggered { .. } => "triggered",
+            OrderStatus::Filled => "filled",
+            OrderStatus::PartiallyFilled { .. } => "partially_filled",
+            OrderStatus::Canceled => "canceled",
+            OrderStatus::Failed(_) => "failed",
+            OrderStatus::ResignRequired => "resign_required",
+            OrderStatus::Unfilled(_) => "unfilled",
+            OrderStatus::Expired => "expired",
+        }
+    }
+
+    /// 订单是否已经结束，不会再触发
+    pub fn is_terminal(&self) -> bool {
+        !matches!(self, OrderStatus::Pending | OrderStatus::Triggered { .. })
+    }
 }
 
 pub struct OrderBook {
-    pub orders: HashMap<Uuid, Order>,
-    pub tokens: HashMap<Pubkey, f32>,
+    /// 订单表，后台任务会更新其中的订单状态；启用持久化时终态订单写入数据库后从这里移除
+    pub orders: Arc<Mutex<HashMap<Uuid, Order>>>,
+    /// 所有订单共享的价格缓存
+    pub prices: PriceCache,
+    /// 固定触发价格的订单按价格索引，价格越过触发价格时唤醒
+    pub dispatcher: TriggerDispatcher,
+    /// 代币符号、名称和精度的缓存
+    pub tokens: Arc<TokenInfoCache>,
+    /// 价格缓存使用的价格源
+    pub price_source: Arc<dyn PriceSource>,
+    /// 超过该时长的价格不用于触发订单
+    pub price_max_age: Duration,
+    /// 按成交价格触发的订单共享的询价，缓存代币精度
+    pub quotes: Arc<QuoteFeed>,
+    /// 限制同时执行的兑换交易数，价格剧烈波动时大量订单同一轮触发也不会同时请求报价和发送交易
+    pub swap_permits: Arc<Semaphore>,
     /// 以基点的方式进行税收，100 => 1%
     pub tax_account: Pubkey,
-    pub tax_bps: u16,
-    pub cancel_tasks: HashMap<Uuid, Sender<()>>,
+    /// 税收策略，触发时按下单钱包和名义价值解析税率，可通过 [`OrderBook::set_tax_policy`] 在运行时替换
+    tax_policy: Arc<StdRwLock<TaxPolicy>>,
+    /// 收税的一侧
+    pub tax_side: TaxSide,
+    /// 下单未指定滑点时使用的滑点
+    pub default_slippage_bps: Bps,
+    /// 下单未指定优先费时使用的优先费
+    pub default_priority_fee_micro_lamports: Option<u64>,
+    /// 订单没有优先费时按最近区块优先费的该百分位取值，为 None 时不设置优先费
+    pub priority_fee_percentile: Option<u8>,
+    /// 下单未指定最大价格影响时使用的上限，为 None 时不限制
+    pub default_max_price_impact_bps: Option<Bps>,
+    /// 订单未指定时报价隐含的成交价格相对触发价格的最大偏离，为 None 时不检查
+    pub max_trigger_deviation_bps: Option<Bps>,
+    pub cancel_tasks: HashMap<Uuid, CancelHandle>,
+    /// 订单任务的结束信号，任务退出时发送端被释放
+    task_done: HashMap<Uuid, Receiver<()>>,
+    /// 非托管订单，交易由客户端签名，不能修改参数
+    signed_orders: HashSet<Uuid>,
     pub http: Arc<Client>,
-    pub jito: Arc<JitoJsonRpcSDK>,
-    pub jup: Arc<JupiterSwapApiClient>,
-    pub rpc: Arc<RpcClient>,
+    pub jito: Arc<dyn BundleSender>,
+    pub jup: Arc<dyn SwapApi>,
+    /// RPC 客户端，生产环境为 [`MultiRpc`]，配置多个节点时按健康状态故障切换
+    pub rpc: Arc<dyn SolanaRpc>,
+    /// 托管订单交换共享的 blockhash 缓存，由后台任务定期刷新
+    pub blockhashes: Arc<BlockhashProvider>,
+    /// 订单持久化的写后缓冲，未配置存储时为 None
+    pub persist: Option<PersistQueue>,
+    /// 数据库连接池，未配置 `DATABASE_URL` 时为 None
+    pub db: Option<DbPool>,
+    /// 已吊销的钱包，这些钱包不能再下单
+    pub revoked: HashMap<Pubkey, RevokedWallet>,
+    /// 交易失败后的重试策略
+    pub retry_policy: RetryPolicy,
+    /// Jito bundle 的确认配置
+    pub bundle: BundleConfig,
+    /// 大额交换强制以 bundle 私有发送的配置
+    pub private_execution: PrivateExecutionConfig,
+    /// 已创建的订单组，按（钱包，客户端幂等键）索引
+    pub groups: HashMap<(Pubkey, String), OrderGroup>,
+    /// 止盈止损订单组的共享状态，按 group_id 索引
+    oco_groups: HashMap<Uuid, Arc<OcoGroup>>,
+    /// 下单会话
+    pub sessions: SessionStore,
+    /// 等待客户端签名的非托管订单
+    pub prepared: HashMap<Uuid, PreparedOrder>,
+    /// 预签名订单使用的 durable nonce 账户池，未配置时为 None
+    pub nonces: Option<NoncePool>,
+    /// 收税账户余额归集，未配置时为 None
+    pub sweep: Option<SweepConfig>,
+    /// 下单数量限制
+    pub limits: OrderLimits,
+    /// 下单时的余额检查
+    pub funding_check: FundingCheck,
+    /// `/quote` 返回的路由令牌有效期
+    pub route_pin_ttl: Duration,
+    /// 停机信号，发出后不再接收新订单，等待触发的订单任务暂停
+    shutdown: watch::Sender<bool>,
+    /// 停机时等待订单任务退出的最长时间
+    pub shutdown_timeout: Duration,
+    /// 订单后台任务
+    tasks: JoinSet<()>,
+    /// 停机时暂停的订单，由订单任务在退出前写入
+    suspended: Arc<Mutex<Vec<SuspendedOrder>>>,
+    /// 订单回调配置
+    pub webhook: WebhookConfig,
+    /// 订单事件广播，供 `/events` 推送
+    pub events: EventBus,
+    /// 订单的审计日志，状态事件经 `events` 写入，执行过程中的报价、发送和错误直接写入
+    pub audit: AuditLog,
+    /// 全局和单笔订单的暂停开关
+    pub pause: PauseSwitch,
+}
+
+/// 下单数量限制，None 表示不限制
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct OrderLimits {
+    /// 每个钱包同时等待触发的订单数上限
+    pub max_orders_per_user: Option<usize>,
+    /// 全局同时等待触发的订单数上限
+    pub max_total_orders: Option<usize>,
+}
+
+impl OrderLimits {
+    /// 从环境变量 `MAX_ORDERS_PER_USER`、`MAX_TOTAL_ORDERS` 读取，未配置时不限制
+    pub fn from_env() -> Result<OrderLimits> {
+        let mut limits = OrderLimits::default();
+        if let Ok(v) = env::var("MAX_ORDERS_PER_USER") {
+            limits.max_orders_per_user = Some(v.parse()?);
+        }
+        if let Ok(v) = env::var("MAX_TOTAL_ORDERS") {
+            limits.max_total_orders = Some(v.parse()?);
+        }
+        Ok(limits)
+    }
+}
+
+/// 下单时余额检查的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FundingCheck {
+    /// 余额不足时拒绝下单
+    #[default]
+    Reject,
+    /// 余额不足时仍然下单，在订单的 `funding_warning` 中记录，适用于稍后再转入资金的用户
+    Warn,
+    /// 不检查
+    Off,
+}
+
+impl FromStr for FundingCheck {
+    type Err = anyhow::Error;
+
+    /// 支持 `reject`、`warn`、`off` 三种写法
+    fn from_str(s: &str) -> Result<FundingCheck> {
+        match s {
+            "reject" => Ok(FundingCheck::Reject),
+            "warn" => Ok(FundingCheck::Warn),
+            "off" => Ok(FundingCheck::Off),
+            _ => Err(anyhow!("无法解析的余额检查方式 {}", s)),
+        }
+    }
+}
+
+/// 被吊销钱包的记录
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RevokedWallet {
+    pub wallet: String,
+    /// 吊销时间（unix 秒）
+    pub revoked_at: u64,
+    /// 吊销时被取消的订单
+    pub canceled_orders: Vec<Uuid>,
+}
+
+/// 下单时需要访问 RPC 的检查（目标代币账户、余额）
+///
+/// 由 [`OrderBook`] 取出后在释放订单簿的锁之后执行，慢速的 RPC 请求不会阻塞其他接口。
+#[derive(Clone)]
+struct OrderChecks {
+    rpc: Arc<dyn SolanaRpc>,
+    funding_check: FundingCheck,
+}
+
+impl OrderChecks {
+    /// 单笔订单的目标代币账户和余额检查
+    async fn check_order(&self, order: &mut Order) -> error::Result<()> {
+        self.check_destinations(std::slice::from_ref(order))
+            .await
+            .map_err(|e| LimitOrderError::invalid("swap_options", e.to_string()))?;
+        self.check_funding(std::slice::from_mut(order)).await
+    }
+
+    /// 检查下单钱包的余额能否支付订单，见 [`check_funding`]
+    ///
+    /// 每笔订单单独检查，同一钱包一次创建的多笔订单不会累加，止盈止损订单只有一笔会执行。
+    /// `FundingCheck::Warn` 时余额不足或查询失败都不拒绝下单，只在订单上记录警告。
+    async fn check_funding(&self, orders: &mut [Order]) -> error::Result<()> {
+        if self.funding_check == FundingCheck::Off {
+            return Ok(());
+        }
+        for order in orders.iter_mut() {
+            let owner: Pubkey = order
+                .owner
+                .parse()
+                .map_err(|_| LimitOrderError::invalid("owner", "钱包地址无效"))?;
+            let input_mint: Pubkey = order
+                .input_mint
+                .parse()
+                .map_err(|_| LimitOrderError::invalid("input_mint", "输入代币地址无效"))?;
+            let result = check_funding(
+                self.rpc.as_ref(),
+                &owner,
+                &input_mint,
+                (order.swap_mode == SwapMode::ExactIn).then_some(order.amount),
+                order.swap_options.wrap_and_unwrap_sol,
+                order.tip_amount,
+                order.priority_fee_micro_lamports,
+            )
+            .await;
+            match result {
+                Ok(()) => {}
+                Err(e) if self.funding_check == FundingCheck::Warn => {
+                    println!("订单 {:?} 余额检查未通过 {}", order.order_id, e);
+                    order.funding_warning = Some(e.to_string());
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// 检查订单的目标代币账户是输出代币的 ATA，见 [`check_destination_account`]
+    async fn check_destinations(&self, orders: &[Order]) -> Result<()> {
+        for order in orders {
+            if let Some(account) = order.swap_options.destination()? {
+                let output_mint: Pubkey = order.output_mint.parse()?;
+                check_destination_account(&self.rpc, &account, &output_mint).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 报价、模拟和构建交易使用的客户端与配置，从订单簿中取出，Jupiter 和 RPC 请求不持有订单簿的锁
+///
+/// 这些请求可能耗时数秒，持有锁时所有下单、撤单和查询都要等待。
+struct SwapClients {
+    jup: Arc<dyn SwapApi>,
+    rpc: Arc<dyn SolanaRpc>,
+    jito: Arc<dyn BundleSender>,
+    blockhashes: Arc<BlockhashProvider>,
+    bundle: BundleConfig,
+    tax_account: Pubkey,
+    tax_side: TaxSide,
+    tax_policy: Arc<StdRwLock<TaxPolicy>>,
+    prices: PriceCache,
+    quotes: Arc<QuoteFeed>,
+    price_source: Arc<dyn PriceSource>,
+    private_execution: PrivateExecutionConfig,
+    priority_fee_percentile: Option<u8>,
+    default_slippage_bps: Bps,
+    route_pin_ttl: Duration,
+}
+
+impl SwapClients {
+    /// 模拟和构建待签名交易使用的客户端与收税配置
+    fn swap_context(&self) -> SwapContext<'_> {
+        SwapContext {
+            jup: &self.jup,
+            rpc: &self.rpc,
+            jito: &self.jito,
+            blockhashes: &self.blockhashes,
+            bundle: self.bundle,
+            tax_account: self.tax_account,
+            tax_side: self.tax_side,
+        }
+    }
+
+    /// `user` 交换 `amount` 适用的税率，`user` 为 None 时不检查免税和单独税率
+    async fn tax_bps(&self, user: Option<&Pubkey>, amount: TokenAmount) -> Bps {
+        resolve_tax_bps(&self.tax_policy, &self.prices, &self.quotes, user, amount).await
+    }
+
+    /// 订单 `trigger_on` 所指的当前价格，与订单任务判断触发时使用相同的价格来源
+    async fn current_price(
+        &self,
+        order: &Order,
+        input_mint: Pubkey,
+        output_mint: Pubkey,
+    ) -> Result<f64> {
+        if order.trigger_on == TriggerOn::ExecutablePrice {
+            let amount = match order.swap_mode {
+                SwapMode::ExactIn => TokenAmount::new(input_mint, order.amount),
+                SwapMode::ExactOut => TokenAmount::new(output_mint, order.amount),
+            };
+            return self
+                .quotes
+                .price(
+                    input_mint,
+                    output_mint,
+                    amount,
+                    order.slippage_bps,
+                    order.swap_mode,
+                )
+                .await;
+        }
+        let prices = self
+            .price_source
+            .get_prices(&[input_mint, output_mint])
+            .await?;
+        let usd = |mint: &Pubkey| {
+            prices
+                .get(mint)
+                .map(|quote| quote.price)
+                .ok_or_else(|| anyhow!("价格源未返回代币 {} 的价格", mint))
+        };
+        match order.trigger_on {
+            TriggerOn::OutputUsd => usd(&output_mint),
+            TriggerOn::Ratio => price_ratio(usd(&input_mint)?, usd(&output_mint)?),
+            _ => usd(&input_mint),
+        }
+    }
+}
+
+/// 订单簿访问外部服务使用的客户端
+///
+/// [`OrderBook::new`] 按配置创建真实的客户端，测试中可以换成 `testing` 模块中的模拟客户端。
+pub struct OrderClients {
+    pub rpc: Arc<dyn SolanaRpc>,
+    pub jup: Arc<dyn SwapApi>,
+    pub jito: Arc<dyn BundleSender>,
+    pub price_source: Arc<dyn PriceSource>,
+    /// 代币列表、回调等 HTTP 请求使用的客户端
+    pub http: Arc<Client>,
+}
+
+impl OrderBook {
+    pub fn new(config: &AppConfig) -> Result<OrderBook> {
+        let http = Arc::new(build_http_client()?);
+        let clients = OrderClients {
+            rpc: MultiRpc::spawn(&config.rpc),
+            jup: Arc::new(JupiterSwapApiClient::new(config.jup_url.clone())),
+            jito: Arc::new(JitoJsonRpcSDK::new(&config.jito_url, None)),
+            price_source: config.price_sources.build(http.clone()),
+            http,
+        };
+        Ok(OrderBook::with_clients(config, clients))
+    }
+
+    /// 使用已创建的客户端，`config` 中的 RPC、Jupiter、Jito 地址和价格源配置不再使用
+    pub fn with_clients(config: &AppConfig, clients: OrderClients) -> OrderBook {
+        let OrderClients {
+            rpc,
+            jup,
+            jito,
+            price_source,
+            http,
+        } = clients;
+        let prices = PriceCache::spawn(price_source.clone(), config.price_poll_interval);
+        let tokens = Arc::new(TokenInfoCache::new(
+            http.clone(),
+            rpc.clone(),
+            config.token_info.clone(),
+        ));
+        let quotes = Arc::new(QuoteFeed::new(
+            jup.clone(),
+            tokens.clone(),
+            config.quote_feed,
+        ));
+        let blockhashes = BlockhashProvider::spawn(rpc.clone(), config.blockhash_refresh_interval);
+        configure_quotes(config.quote_policy);
+        let db = config
+            .database_url
+            .as_deref()
+            .map(|url| build_pool(url, config.database_pool_size));
+        // 未配置数据库时审计日志与订单一样只保存在内存中
+        let audit_store: Arc<dyn AuditStore> = match &db {
+            Some(pool) => Arc::new(MysqlAuditStore::new(pool.clone())),
+            None => Arc::new(MemoryAuditStore::default()),
+        };
+        let audit = AuditLog::spawn(audit_store, config.audit_retention);
+        let events = EventBus::with_audit(audit.clone());
+        let dispatcher = TriggerDispatcher::spawn(&prices, config.price_max_age, events.clone());
+
+        OrderBook {
+            orders: Arc::new(Mutex::new(HashMap::new())),
+            prices,
+            dispatcher,
+            tokens,
+            price_source,
+            price_max_age: config.price_max_age,
+            quotes,
+            swap_permits: Arc::new(Semaphore::new(config.max_concurrent_swaps)),
+            tax_account: config.tax_account,
+            tax_policy: Arc::new(StdRwLock::new(config.tax_policy.clone())),
+            tax_side: config.tax_side,
+            default_slippage_bps: config.default_slippage_bps,
+            default_priority_fee_micro_lamports: config.default_priority_fee_micro_lamports,
+            priority_fee_percentile: config.priority_fee_percentile,
+            default_max_price_impact_bps: config.default_max_price_impact_bps,
+            max_trigger_deviation_bps: config.max_trigger_deviation_bps,
+            cancel_tasks: HashMap::new(),
+            task_done: HashMap::new(),
+            signed_orders: HashSet::new(),
+            http,
+            jito,
+            jup,
+            rpc,
+            blockhashes,
+            persist: None,
+            db,
+            revoked: HashMap::new(),
+            retry_policy: config.retry_policy,
+            bundle: config.bundle,
+            private_execution: config.private_execution,
+            groups: HashMap::new(),
+            oco_groups: HashMap::new(),
+            sessions: SessionStore::new(config.session_ttl),
+            prepared: HashMap::new(),
+            nonces: config.nonces.clone(),
+            sweep: config.sweep.clone(),
+            limits: config.limits,
+            funding_check: config.funding_check,
+            route_pin_ttl: config.route_pin_ttl,
+            shutdown: watch::channel(false).0,
+            shutdown_timeout: config.shutdown_timeout,
+            tasks: JoinSet::new(),
+            suspended: Arc::new(Mutex::new(vec![])),
+            webhook: config.webhook,
+            events,
+            audit,
+            pause: PauseSwitch::default(),
+        }
+    }
+
+    /// 启用订单持久化，数据库不可用时记录会暂存到 `PERSIST_JOURNAL` 指定的日志文件
+    pub fn enable_persistence(&mut self, store: Arc<dyn OrderStore>) {
+        let journal_path = env::var("PERSIST_JOURNAL")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from("persist_journal.jsonl"));
+        self.persist = Some(PersistQueue::spawn(store, journal_path));
+    }
+
+    /// 更新只在抓取时计算的指标：等待触发的订单数和价格缓存年龄
+    pub async fn refresh_metrics(&self) {
+        let open_orders = self
+            .orders
+            .lock()
+            .await
+            .values()
+            .filter(|order| order.status == OrderStatus::Pending)
+            .count();
+        metrics().open_orders.set(open_orders as i64);
+        let age_ms = self.prices.max_age_ms().unwrap_or_default();
+        metrics()
+            .price_cache_age_seconds
+            .set(age_ms as f64 / 1000.0);
+    }
+
+    /// 存储是否处于降级状态（数据库不可用，仅使用内存数据）
+    pub fn is_degraded(&self) -> bool {
+        self.persist
+            .as_ref()
+            .map(|p| p.is_degraded())
+            .unwrap_or(false)
+    }
+
+    /// 开单，`leg` 未指定的优先费和最大价格影响使用订单簿的默认值，滑点由调用方填好
+    ///
+    /// 余额和目标代币账户等需要访问 RPC 的检查不持有订单簿的锁，检查通过后再加锁启动订单任务；
+    /// 启动前重新检查停机、吊销和数量限制，检查期间这些状态发生变化时仍会拒绝下单。
+    pub async fn place_order(
+        book: &Mutex<OrderBook>,
+        private_key: SecretString,
+        mut leg: OrderLeg,
+    ) -> error::Result<Uuid> {
+        let (keypair, mut order, checks) = {
+            let book = book.lock().await;
+            leg.priority_fee_micro_lamports = leg
+                .priority_fee_micro_lamports
+                .or(book.default_priority_fee_micro_lamports);
+            leg.max_price_impact_bps = leg
+                .max_price_impact_bps
+                .or(book.default_max_price_impact_bps);
+            let keypair = parse_keypair(private_key.expose())?;
+            book.check_owner(&keypair.pubkey())?;
+            let order = book.validated_order(leg, keypair.pubkey())?;
+            (keypair, order, book.order_checks())
+        };
+        checks.check_order(&mut order).await?;
+        let mut book = book.lock().await;
+        book.check_admission(&keypair.pubkey(), 1).await?;
+        metrics().orders_placed.inc();
+        Ok(book.spawn_order(keypair, order).await)
+    }
+
+    /// 按下单时的全部检查（停机、吊销、参数、余额、数量限制）生成托管订单，不启动订单任务
+    ///
+    /// `leg` 中的滑点等默认值由调用方填好。命令行工具据此在本地校验订单，再交给常驻进程执行。
+    pub async fn accept_order(
+        &self,
+        private_key: &SecretString,
+        leg: OrderLeg,
+    ) -> error::Result<(Keypair, Order)> {
+        let keypair = parse_keypair(private_key.expose())?;
+        let owner = keypair.pubkey();
+        self.check_owner(&owner)?;
+        let mut order = self.validated_order(leg, owner)?;
+        self.order_checks().check_order(&mut order).await?;
+        self.check_order_limits(&owner, 1).await?;
+        Ok((keypair, order))
+    }
+
+    /// 检查是否还在接收新订单，以及钱包是否已被吊销
+    fn check_owner(&self, owner: &Pubkey) -> error::Result<()> {
+        self.check_accepting()?;
+        if self.revoked.contains_key(owner) {
+            return Err(LimitOrderError::Unauthorized(format!(
+                "钱包 {} 已被吊销",
+                owner
+            )));
+        }
+        Ok(())
+    }
+
+    /// RPC 检查结束、启动订单任务之前重新检查停机、吊销和数量限制，需要持有订单簿的锁
+    async fn check_admission(&self, owner: &Pubkey, new_orders: usize) -> error::Result<()> {
+        self.check_owner(owner)?;
+        Ok(self.check_order_limits(owner, new_orders).await?)
+    }
+
+    /// 取出报价、模拟和构建交易使用的客户端与配置，释放订单簿的锁后使用
+    fn swap_clients(&self) -> SwapClients {
+        SwapClients {
+            jup: self.jup.clone(),
+            rpc: self.rpc.clone(),
+            jito: self.jito.clone(),
+            blockhashes: self.blockhashes.clone(),
+            bundle: self.bundle,
+            tax_account: self.tax_account,
+            tax_side: self.tax_side,
+            tax_policy: self.tax_policy.clone(),
+            prices: self.prices.clone(),
+            quotes: self.quotes.clone(),
+            price_source: self.price_source.clone(),
+            private_execution: self.private_execution,
+            priority_fee_percentile: self.priority_fee_percentile,
+            default_slippage_bps: self.default_slippage_bps,
+            route_pin_ttl: self.route_pin_ttl,
+        }
+    }
+
+    /// 取出下单时需要访问 RPC 的检查，释放订单簿的锁后执行
+    fn order_checks(&self) -> OrderChecks {
+        OrderChecks {
+            rpc: self.rpc.clone(),
+            funding_check: self.funding_check,
+        }
+    }
+
+    /// 校验单笔订单中不需要访问 RPC 的参数并生成订单
+    fn validated_order(&self, leg: OrderLeg, owner: Pubkey) -> error::Result<Order> {
+        let invalid = |field: &'static str| {
+            move |e: anyhow::Error| LimitOrderError::invalid(field, e.to_string())
+        };
+        leg.check_route_token().map_err(invalid("route_token"))?;
+        leg.check_split_parts().map_err(invalid("split_parts"))?;
+        leg.check_repeat().map_err(invalid("repeat_count"))?;
+        leg.check_kind().map_err(invalid("kind"))?;
+        leg.check_ttl().map_err(invalid("ttl_secs"))?;
+        leg.check_callback_url(self.webhook.allow_private)
+            .map_err(invalid("callback_url"))?;
+        let order = leg.into_order(owner, None);
+        if let Some(reason) = resolve_order(&order, &self.runtime_config()).rejection {
+            return Err(LimitOrderError::invalid("swap_mode", reason));
+        }
+        Ok(order)
+    }
+
+    /// 预览订单：按下单时相同的校验、报价和收税逻辑计算当前价格下触发的预期结果
+    ///
+    /// 不需要私钥，不创建订单也不启动订单任务。未指定最大价格影响时使用默认上限，
+    /// 当前报价不满足最低输出或价格影响上限时不返回错误，而是在 `rejection` 中说明触发后会被跳过的原因。
+    /// 只在校验参数时持有订单簿的锁，报价和 RPC 检查不阻塞其他请求。
+    pub async fn quote_order(
+        book: &Mutex<OrderBook>,
+        mut leg: OrderLeg,
+    ) -> error::Result<OrderQuote> {
+        let (order, checks, clients) = {
+            let book = book.lock().await;
+            leg.max_price_impact_bps = leg
+                .max_price_impact_bps
+                .or(book.default_max_price_impact_bps);
+            let order = book.validated_order(leg, Pubkey::default())?;
+            (order, book.order_checks(), book.swap_clients())
+        };
+        checks
+            .check_destinations(std::slice::from_ref(&order))
+            .await
+            .map_err(|e| LimitOrderError::invalid("swap_options", e.to_string()))?;
+        // 代币地址已由 resolve_order 校验
+        let input_mint: Pubkey = order
+            .input_mint
+            .parse()
+            .map_err(|_| LimitOrderError::invalid("input_mint", "输入代币地址无效"))?;
+        let output_mint: Pubkey = order
+            .output_mint
+            .parse()
+            .map_err(|_| LimitOrderError::invalid("output_mint", "输出代币地址无效"))?;
+        let amount = match order.swap_mode {
+            SwapMode::ExactIn => TokenAmount::new(input_mint, order.amount),
+            SwapMode::ExactOut => TokenAmount::new(output_mint, order.amount),
+        };
+        let tax_bps = clients.tax_bps(None, amount).await;
+        let quoted = quote_only(
+            &clients.jup,
+            input_mint,
+            output_mint,
+            quote_amount(clients.tax_side, amount, order.swap_mode, tax_bps),
+            order.slippage_bps,
+            order.swap_mode,
+        )
+        .await?;
+        let output_transfer_fee = if output_mint == SOL {
+            None
+        } else {
+            get_mint_info(&clients.rpc, &output_mint)
+                .await?
+                .transfer_fee
+        };
+        let quoted = net_of_transfer_fee(quoted, output_transfer_fee);
+        let rejection = check_min_out(
+            quoted.out_amount,
+            clients.tax_side,
+            tax_bps,
+            order.min_out_amount,
+        )
+        .and_then(|_| check_price_impact(&quoted, order.max_price_impact_bps))
+        .err()
+        .map(|e| e.to_string());
+        let current_price = match clients.current_price(&order, input_mint, output_mint).await {
+            Ok(price) => Some(price),
+            Err(e) => {
+                println!("预览订单获取当前价格失败 {:?}", e);
+                None
+            }
+        };
+        let tax = tax_amount(clients.tax_side, order.swap_mode, amount, &quoted, tax_bps);
+        Ok(OrderQuote {
+            in_amount: quoted.in_amount.raw,
+            out_amount: net_out_amount(quoted.out_amount, clients.tax_side, tax_bps).raw,
+            tax_amount: tax.raw,
+            tax_mint: tax.mint.to_string(),
+            tax_side: clients.tax_side,
+            tax_bps,
+            output_transfer_fee_bps: output_transfer_fee.map(|fee| fee.bps),
+            price_impact_bps: quoted.price_impact_bps(),
+            route_labels: quoted.route_labels,
+            trigger_on: order.trigger_on,
+            current_price,
+            rejection,
+        })
+    }
+
+    /// 按订单当前会发送的交易模拟执行，返回完整日志、计算单元消耗和模拟前后的余额，不发送交易也不改变订单
+    ///
+    /// `target` 为已有订单时以订单的下单钱包和参数模拟，不使用固定路由；分批订单按一批的数量模拟。
+    /// 新的订单参数按下单时的规则校验。只在取出订单和校验参数时持有订单簿的锁，构建交易和模拟不阻塞其他请求。
+    pub async fn simulate_order(
+        book: &Mutex<OrderBook>,
+        target: SimulationTarget,
+    ) -> error::Result<SwapSimulation> {
+        let (order, user, checks, clients) = {
+            let book = book.lock().await;
+            let (order, user, checks) = match target {
+                SimulationTarget::Existing(order_id) => {
+                    let order = book
+                        .orders
+                        .lock()
+                        .await
+                        .get(&order_id)
+                        .cloned()
+                        .ok_or(LimitOrderError::OrderNotFound)?;
+                    let user = order
+                        .owner
+                        .parse()
+                        .map_err(|_| LimitOrderError::invalid("order_id", "订单的下单钱包无效"))?;
+                    (order, user, None)
+                }
+                // 新的订单参数与下单时一样检查目标代币账户
+                SimulationTarget::New { user, mut leg } => {
+                    leg.max_price_impact_bps = leg
+                        .max_price_impact_bps
+                        .or(book.default_max_price_impact_bps);
+                    (
+                        book.validated_order(*leg, user)?,
+                        user,
+                        Some(book.order_checks()),
+                    )
+                }
+            };
+            (order, user, checks, book.swap_clients())
+        };
+        if let Some(checks) = checks {
+            checks
+                .check_destinations(std::slice::from_ref(&order))
+                .await
+                .map_err(|e| LimitOrderError::invalid("swap_options", e.to_string()))?;
+        }
+        let input_mint: Pubkey = order
+            .input_mint
+            .parse()
+            .map_err(|_| LimitOrderError::invalid("input_mint", "输入代币地址无效"))?;
+        let output_mint: Pubkey = order
+            .output_mint
+            .parse()
+            .map_err(|_| LimitOrderError::invalid("output_mint", "输出代币地址无效"))?;
+        let amount_mint = match order.swap_mode {
+            SwapMode::ExactIn => input_mint,
+            SwapMode::ExactOut => output_mint,
+        };
+        let chunk = split_amount(order.amount, order.split_parts.unwrap_or(1), 0);
+        let amount = TokenAmount::new(amount_mint, chunk);
+        Ok(simulate_swap(
+            &clients.swap_context(),
+            user,
+            clients.tax_bps(Some(&user), amount).await,
+            &order_swap_params(&order, input_mint, output_mint, amount, None),
+            &ExecutionOptions {
+                private: PrivateExecution {
+                    config: clients.private_execution,
+                    force: order.force_private_execution,
+                    input_lamports_per_unit: lamports_per_unit(
+                        &clients.prices,
+                        &clients.quotes,
+                        &input_mint,
+                    )
+                    .await,
+                },
+                tip_amount: order.tip_amount,
+                compute_unit_price: compute_unit_price(
+                    &clients.rpc,
+                    order.priority_fee_micro_lamports,
+                    clients.priority_fee_percentile,
+                )
+                .await,
+                ..Default::default()
+            },
+        )
+        .await?)
+    }
+
+    /// 估算一次 ExactIn 交换的费用：签名费、按推荐计算单元价格估算的优先费、tip 和税收
+    ///
+    /// 签名数按与交换交易相同付款人的原型交易统计；以输出代币收税时需要报价才能算出税收。
+    /// 计算单元价格按 `PRIORITY_FEE_PERCENTILE`（未配置时取中位数）从最近区块的优先费中选取。
+    /// 指定 tip 时交换交易同样带有优先费，bundle 未上链改用 RPC 发送的是同一笔交易，费用与 bundle 上链时相同。
+    /// 只在取出客户端和配置时持有订单簿的锁，报价和查询优先费不阻塞其他请求。
+    pub async fn estimate_fees(
+        book: &Mutex<OrderBook>,
+        input_mint: Pubkey,
+        output_mint: Pubkey,
+        amount: u64,
+        tip_amount: Option<Lamports>,
+    ) -> error::Result<FeeEstimate> {
+        let clients = book.lock().await.swap_clients();
+        let amount = TokenAmount::new(input_mint, amount);
+        let tax_bps = clients.tax_bps(None, amount).await;
+        let tax = match clients.tax_side {
+            TaxSide::Input => sub_tax(amount, tax_bps).1,
+            TaxSide::Output => {
+                let quoted = quote_only(
+                    &clients.jup,
+                    input_mint,
+                    output_mint,
+                    amount,
+                    clients.default_slippage_bps,
+                    SwapMode::ExactIn,
+                )
+                .await?;
+                tax_amount(
+                    clients.tax_side,
+                    SwapMode::ExactIn,
+                    amount,
+                    &quoted,
+                    tax_bps,
+                )
+            }
+        };
+        let compute_unit_price = recommended_priority_fee(
+            clients.rpc.as_ref(),
+            &[],
+            clients
+                .priority_fee_percentile
+                .unwrap_or(DEFAULT_PRIORITY_FEE_PERCENTILE),
+        )
+        .await?;
+        // 交换和收税指令只需要用户签名；原型交易包含计算预算和 tip 指令，付款人与交换交易相同
+        let payer = Pubkey::new_unique();
+        let ixs: Vec<_> = tip_amount
+            .map(|tip| system_instruction::transfer(&payer, &Pubkey::new_unique(), tip.get()))
+            .into_iter()
+            .collect();
+        let prototype = Message::new(
+            &with_compute_budget(&ixs, ESTIMATED_SWAP_COMPUTE_UNITS, Some(compute_unit_price)),
+            Some(&payer),
+        );
+        let signatures = prototype.header.num_required_signatures;
+        let base_fee = LAMPORTS_PER_SIGNATURE * signatures as u64;
+        let priority_fee =
+            compute_unit_price.saturating_mul(ESTIMATED_SWAP_COMPUTE_UNITS as u64) / 1_000_000;
+        let tip = tip_amount.map_or(0, |tip| tip.get());
+        let total_lamports = base_fee
+            .saturating_add(priority_fee)
+            .saturating_add(tip)
+            .saturating_add(if tax.mint == SOL { tax.raw } else { 0 });
+        Ok(FeeEstimate {
+            signatures,
+            base_fee,
+            compute_unit_price,
+            compute_units: ESTIMATED_SWAP_COMPUTE_UNITS,
+            priority_fee,
+            tip,
+            tax_amount: tax.raw,
+            tax_mint: tax.mint.to_string(),
+            tax_side: clients.tax_side,
+            total_lamports,
+        })
+    }
+
+    /// 收税账户的 SOL 和代币余额
+    pub async fn treasury(&self) -> Result<TreasuryBalances> {
+        treasury_balances(self.rpc.as_ref(), &self.tax_account).await
+    }
+
+    /// 将收税账户超出保留额度的余额归集到冷钱包
+    ///
+    /// 有订单处于 `Triggered`（交易已发送、尚未确认）时拒绝归集，不动用执行中交换的资金。
+    pub async fn sweep_treasury(&self) -> Result<SweepResult> {
+        let config = self
+            .sweep
+            .as_ref()
+            .ok_or_else(|| anyhow!("未配置 TAX_ACCOUNT_PK 和 TREASURY_COLD_WALLET"))?;
+        let in_flight = self
+            .orders
+            .lock()
+            .await
+            .values()
+            .filter(|order| matches!(order.status, OrderStatus::Triggered { .. }))
+            .count();
+        if in_flight > 0 {
+            return Err(anyhow!("有 {} 笔订单正在执行，稍后再归集", in_flight));
+        }
+        sweep(self.rpc.as_ref(), self.price_source.as_ref(), config).await
+    }
+
+    /// 按下单时相同的方式报价（以输入代币收税时 ExactIn 先扣除税收），返回可在下单时使用的固定路由
+    ///
+    /// 只在取出客户端和收税配置时持有订单簿的锁，Jupiter 报价不阻塞其他请求。
+    pub async fn quote(
+        book: &Mutex<OrderBook>,
+        input_mint: Pubkey,
+        output_mint: Pubkey,
+        amount: u64,
+        slippage_bps: Bps,
+        swap_mode: SwapMode,
+    ) -> Result<PinnedRoute> {
+        let amount = match swap_mode {
+            SwapMode::ExactIn => TokenAmount::new(input_mint, amount),
+            SwapMode::ExactOut => TokenAmount::new(output_mint, amount),
+        };
+        let clients = book.lock().await.swap_clients();
+        let tax_bps = clients.tax_bps(None, amount).await;
+        check_swap_mode(clients.tax_side, swap_mode, tax_bps)?;
+        let quote = get_quote(
+            &clients.jup,
+            input_mint,
+            output_mint,
+            quote_amount(clients.tax_side, amount, swap_mode, tax_bps),
+            slippage_bps,
+            swap_mode,
+        )
+        .await?;
+        Ok(PinnedRoute::new(quote, clients.route_pin_ttl))
+    }
+
+    /// 原子地创建一组订单
+    ///
+    /// 同一钱包使用相同的 `client_group_id` 重复请求时直接返回已创建的订单组，不会再创建订单。
+    /// 所有订单参数在创建任何订单之前统一校验，校验通过后创建订单不会失败，
+    /// 因此订单组要么全部创建，要么一笔都不创建。RPC 检查期间不持有订单簿的锁，
+    /// 同一订单组的并发请求在加锁创建订单前重新查找，只有一个请求会创建订单。
+    pub async fn place_order_group(
+        book: &Mutex<OrderBook>,
+        private_key: SecretString,
+        client_group_id: String,
+        legs: Vec<OrderLeg>,
+    ) -> error::Result<OrderGroup> {
+        let owner = parse_keypair(private_key.expose())?.pubkey();
+        let key = (owner, client_group_id);
+        let (group_id, mut orders, checks) = {
+            let book = book.lock().await;
+            if let Some(group) = book.groups.get(&key) {
+                return Ok(group.clone());
+            }
+            book.check_owner(&owner)?;
+            if legs.is_empty() {
+                return Err(LimitOrderError::invalid("orders", "订单组不能为空"));
+            }
+            let group_id = Uuid::new_v4();
+            let orders = book
+                .build_orders(owner, legs, Some(group_id))
+                .map_err(|e| LimitOrderError::invalid("orders", e.to_string()))?;
+            (group_id, orders, book.order_checks())
+        };
+        checks
+            .check_destinations(&orders)
+            .await
+            .map_err(|e| LimitOrderError::invalid("orders", e.to_string()))?;
+        checks.check_funding(&mut orders).await?;
+
+        let mut book = book.lock().await;
+        if let Some(group) = book.groups.get(&key) {
+            return Ok(group.clone());
+        }
+        book.check_admission(&owner, orders.len()).await?;
+        let order_ids = book.spawn_orders(&private_key, orders).await?;
+        let group = OrderGroup {
+            group_id,
+            client_group_id: key.1.clone(),
+            owner: owner.to_string(),
+            order_ids,
+        };
+        book.groups.insert(key, group.clone());
+        Ok(group)
+    }
+
+    /// 批量下单，全部成功或全部不创建
+    ///
+    /// 先检查所有订单的参数和钱包的订单数量限制，再依次创建；返回的订单 ID 与 `legs` 顺序一致。
+    /// RPC 检查期间不持有订单簿的锁。
+    pub async fn place_orders(
+        book: &Mutex<OrderBook>,
+        private_key: SecretString,
+        legs: Vec<OrderLeg>,
+    ) -> error::Result<Vec<Uuid>> {
+        let owner = parse_keypair(private_key.expose())?.pubkey();
+        let (mut orders, checks) = {
+            let book = book.lock().await;
+            book.check_owner(&owner)?;
+            if legs.is_empty() {
+                return Err(LimitOrderError::invalid("orders", "订单列表不能为空"));
+            }
+            let orders = book
+                .build_orders(owner, legs, None)
+                .map_err(|e| LimitOrderError::invalid("orders", e.to_string()))?;
+            (orders, book.order_checks())
+        };
+        checks
+            .check_destinations(&orders)
+            .await
+            .map_err(|e| LimitOrderError::invalid("orders", e.to_string()))?;
+        checks.check_funding(&mut orders).await?;
+
+        let mut book = book.lock().await;
+        book.check_admission(&owner, orders.len()).await?;
+        Ok(book.spawn_orders(&private_key, orders).await?)
+    }
+
+    /// 止盈止损（OCO）订单：同时创建止盈和止损两笔订单，其中一笔触发后另一笔自动取消
+    ///
+    /// 两笔订单的类型分别设为 `TakeProfit` 和 `StopLoss`，共用一个 group_id 并互相记录为 `oco_sibling`。
+    /// 两笔订单必须是同一代币对、按同一种价格触发，且止盈价格高于止损价格。
+    /// 同一轮价格中两笔同时满足条件时，先登记触发的一笔执行，另一笔取消且不发送交易。
+    /// RPC 检查期间不持有订单簿的锁。
+    pub async fn place_bracket(
+        book: &Mutex<OrderBook>,
+        private_key: SecretString,
+        mut take_profit: OrderLeg,
+        mut stop_loss: OrderLeg,
+    ) -> error::Result<Bracket> {
+        let owner = parse_keypair(private_key.expose())?.pubkey();
+        if take_profit.input_mint != stop_loss.input_mint
+            || take_profit.output_mint != stop_loss.output_mint
+        {
+            return Err(LimitOrderError::invalid(
+                "stop_loss",
+                "止盈和止损订单的代币对必须一致",
+            ));
+        }
+        if take_profit.trigger_on != stop_loss.trigger_on {
+            return Err(LimitOrderError::invalid(
+                "stop_loss",
+                "止盈和止损订单的 trigger_on 必须一致",
+            ));
+        }
+        if take_profit.price <= stop_loss.price {
+            return Err(LimitOrderError::invalid(
+                "take_profit",
+                "止盈价格必须高于止损价格",
+            ));
+        }
+        take_profit.kind = OrderKind::TakeProfit;
+        stop_loss.kind = OrderKind::StopLoss;
+
+        let group_id = Uuid::new_v4();
+        let (mut orders, checks) = {
+            let book = book.lock().await;
+            book.check_owner(&owner)?;
+            let orders = book
+                .build_orders(owner, vec![take_profit, stop_loss], Some(group_id))
+                .map_err(|e| LimitOrderError::invalid("orders", e.to_string()))?;
+            (orders, book.order_checks())
+        };
+        let (take_profit, stop_loss) = (orders[0].order_id, orders[1].order_id);
+        orders[0].oco_sibling = Some(stop_loss);
+        orders[1].oco_sibling = Some(take_profit);
+        checks
+            .check_destinations(&orders)
+            .await
+            .map_err(|e| LimitOrderError::invalid("orders", e.to_string()))?;
+        checks.check_funding(&mut orders).await?;
+
+        let mut book = book.lock().await;
+        book.check_admission(&owner, orders.len()).await?;
+        if let Err(e) = book.spawn_orders(&private_key, orders).await {
+            book.oco_groups.remove(&group_id);
+            return Err(e.into());
+        }
+        Ok(Bracket {
+            group_id,
+            take_profit,
+            stop_loss,
+        })
+    }
+
+    /// 检查参数并生成订单，任何一笔无效时返回错误，不创建任何订单
+    fn build_orders(
+        &self,
+        owner: Pubkey,
+        legs: Vec<OrderLeg>,
+        group_id: Option<Uuid>,
+    ) -> Result<Vec<Order>> {
+        let config = self.runtime_config();
+        let mut orders = vec![];
+        for (i, mut leg) in legs.into_iter().enumerate() {
+            leg.max_price_impact_bps = leg
+                .max_price_impact_bps
+                .or(self.default_max_price_impact_bps);
+            leg.validate()
+                .and_then(|_| leg.check_callback_url(self.webhook.allow_private))
+                .map_err(|e| anyhow!("第 {} 笔订单参数无效: {}", i, e))?;
+            let order = leg.into_order(owner, group_id);
+            if let Some(reason) = resolve_order(&order, &config).rejection {
+                return Err(anyhow!("第 {} 笔订单参数无效: {}", i, reason));
+            }
+            orders.push(order);
+        }
+        Ok(orders)
+    }
+
+    /// 依次启动订单任务
+    ///
+    /// 中途失败时取消已经启动的订单再返回错误，不会留下只创建了一部分的批次。
+    async fn spawn_orders(
+        &mut self,
+        private_key: &SecretString,
+        orders: Vec<Order>,
+    ) -> Result<Vec<Uuid>> {
+        let mut order_ids = vec![];
+        for order in orders {
+            let keypair = match parse_keypair(private_key.expose()) {
+                Ok(keypair) => keypair,
+                Err(e) => {
+                    for order_id in order_ids {
+                        if let Err(e) = self.force_cancel_order(order_id).await {
+                            println!("回滚订单 {:?} 失败 {:?}", order_id, e);
+                        }
+                    }
+                    return Err(e);
+                }
+            };
+            metrics().orders_placed.inc();
+            order_ids.push(self.spawn_order(keypair, order).await);
+        }
+        Ok(order_ids)
+    }
+
+    /// 建立下单会话，返回会话令牌和绑定的钱包
+    pub fn create_session(&mut self, private_key: SecretString) -> Result<(String, Pubkey)> {
+        let (token, wallet) = self.sessions.create(private_key)?;
+        if self.revoked.contains_key(&wallet) {
+            self.sessions.remove(&token);
+            return Err(anyhow!("钱包 {} 已被吊销", wallet));
+        }
+        Ok((token, wallet))
+    }
+
+    /// 检查再创建 `new_orders` 笔订单是否超过数量限制
+    ///
+    /// 只统计等待触发的订单，订单成交、失败、过期或取消后状态随之改变，占用的额度自动释放。
+    async fn check_order_limits(&self, owner: &Pubkey, new_orders: usize) -> Result<()> {
+        let owner = owner.to_string();
+        let orders = self.orders.lock().await;
+        let pending = orders
+            .values()
+            .filter(|order| order.status == OrderStatus::Pending);
+        let (total, user) = pending.fold((0, 0), |(total, user), order| {
+            (total + 1, user + (order.owner == owner) as usize)
+        });
+        if let Some(max) = self.limits.max_orders_per_user {
+            if user + new_orders > max {
+                return Err(anyhow!(
+                    "钱包 {} 已有 {} 笔活跃订单，超过上限 {}",
+                    owner,
+                    user,
+                    max
+                ));
+            }
+        }
+        if let Some(max) = self.limits.max_total_orders {
+            if total + new_orders > max {
+                return Err(anyhow!("活跃订单总数 {} 已达上限 {}", total, max));
+            }
+        }
+        Ok(())
+    }
+
+    /// 当前的税收策略
+    pub fn tax_policy(&self) -> TaxPolicy {
+        self.tax_policy.read().unwrap().clone()
+    }
+
+    /// 替换税收策略
+    ///
+    /// 等待触发的托管订单在触发时才解析税率，因此只影响之后触发的交易；已生成的非托管交易中的税率不变。
+    pub fn set_tax_policy(&self, policy: TaxPolicy) -> Result<()> {
+        policy.validate()?;
+        println!("税收策略更新为 {:?}", policy);
+        *self.tax_policy.write().unwrap() = policy;
+        Ok(())
+    }
+
+    /// 当前生效的全局交易配置
+    pub fn runtime_config(&self) -> RuntimeConfig {
+        RuntimeConfig {
+            tax_bps: self.tax_policy().default_bps,
+            tax_side: self.tax_side,
+            retry_policy: self.retry_policy,
+        }
+    }
+
+    /// 预览配置变更对活跃订单的影响，不修改任何状态
+    ///
+    /// 对每个等待触发的订单，分别用当前配置和候选配置执行下单时的解析逻辑并比较结果。
+    pub async fn preview_config(&self, candidate: &RuntimeConfig) -> Vec<ConfigPreview> {
+        let current_config = self.runtime_config();
+        self.orders
+            .lock()
+            .await
+            .values()
+            .filter(|order| order.status == OrderStatus::Pending)
+            .map(|order| {
+                let current = resolve_order(order, &current_config);
+                let candidate = resolve_order(order, candidate);
+                ConfigPreview {
+                    order_id: order.order_id,
+                    changed: current != candidate,
+                    newly_rejected: current.rejection.is_none() && candidate.rejection.is_some(),
+                    current,
+                    candidate,
+                }
+            })
+            .collect()
+    }
+
+    /// 生成非托管订单的待签名交易
+    ///
+    /// 私钥不离开客户端：服务端按订单参数构造交易并返回，客户端签名后通过
+    /// [`OrderBook::submit_signed_order`] 提交。配置了 nonce 账户池时交易使用 durable nonce，
+    /// 在订单结束前一直有效；否则（或池中没有空闲账户时）使用最新的 blockhash，有效期约 1 分钟，
+    /// 价格在有效期内未触发时订单状态变为 `ResignRequired`，客户端需要重新生成并签名。
+    ///
+    /// 与 [`OrderBook::place_order`] 相同，加锁校验参数并租用 nonce 账户，余额检查、读取 nonce、
+    /// 报价和构建交易不持有订单簿的锁，完成后重新加锁记录待签名订单；失败时归还 nonce 账户。
+    pub async fn prepare_order(
+        book: &Mutex<OrderBook>,
+        user: Pubkey,
+        leg: OrderLeg,
+    ) -> Result<PreparedTransaction> {
+        let prepare_id = Uuid::new_v4();
+        let (order, checks, clients, nonces, nonce_account, max_price_impact_bps) = {
+            let mut book = book.lock().await;
+            book.check_owner(&user)?;
+            leg.validate()?;
+            leg.check_callback_url(book.webhook.allow_private)?;
+            if leg.tip_amount.is_some() {
+                return Err(anyhow!("非托管订单暂不支持 tip"));
+            }
+            if leg.split_parts.is_some() {
+                return Err(anyhow!("非托管订单不支持分批执行"));
+            }
+            if leg.repeat_count.is_some() {
+                return Err(anyhow!("非托管订单不支持重复挂单"));
+            }
+            if leg.post_trigger_deadline_ms.is_some() {
+                return Err(anyhow!("非托管订单的交易已签名，不支持执行时限"));
+            }
+            let order = leg.clone().into_order(user, None);
+            if let Some(reason) = resolve_order(&order, &book.runtime_config()).rejection {
+                return Err(anyhow!(reason));
+            }
+            book.purge_expired_prepared();
+            let nonce_account = book.nonces.as_ref().and_then(|pool| {
+                let leased = pool.lease(prepare_id);
+                if leased.is_none() {
+                    println!("nonce 账户已全部租出，使用最新的 blockhash");
+                }
+                leased
+            });
+            (
+                order,
+                book.order_checks(),
+                book.swap_clients(),
+                book.nonces.clone(),
+                nonce_account,
+                leg.max_price_impact_bps
+                    .or(book.default_max_price_impact_bps),
+            )
+        };
+        let release = || {
+            if let Some(pool) = &nonces {
+                pool.release(&prepare_id);
+            }
+        };
+
+        let prepared = async {
+            checks
+                .check_destinations(std::slice::from_ref(&order))
+                .await?;
+            checks.check_funding(&mut [order]).await?;
+            let input_mint: Pubkey = leg.input_mint.parse()?;
+            let output_mint: Pubkey = leg.output_mint.parse()?;
+            let amount = match leg.swap_mode {
+                SwapMode::ExactIn => TokenAmount::new(input_mint, leg.amount),
+                SwapMode::ExactOut => TokenAmount::new(output_mint, leg.amount),
+            };
+            let pin = route_pin(leg.route_token.as_deref(), leg.pin_fallback)?;
+            let nonce = match (nonce_account, &nonces) {
+                (Some(nonce_account), Some(pool)) => Some(NonceInfo {
+                    nonce_account,
+                    nonce: get_nonce(&clients.rpc, &nonce_account).await?,
+                    authority: pool.authority(),
+                }),
+                _ => None,
+            };
+            let tax_bps = clients.tax_bps(Some(&user), amount).await;
+            let (tx, last_valid_block_height) = prepare_unsigned_swap(
+                &clients.swap_context(),
+                user,
+                tax_bps,
+                &SwapParams {
+                    input_mint,
+                    output_mint,
+                    amount,
+                    swap_mode: leg.swap_mode,
+                    slippage_bps: leg.slippage_bps,
+                    min_out_amount: leg.min_out_amount,
+                    max_price_impact_bps,
+                    options: &leg.swap_options,
+                    pin: pin.as_ref(),
+                },
+                compute_unit_price(
+                    &clients.rpc,
+                    leg.priority_fee_micro_lamports,
+                    clients.priority_fee_percentile,
+                )
+                .await,
+                nonce.as_ref(),
+            )
+            .await?;
+            Ok::<_, anyhow::Error>((tx, last_valid_block_height, tax_bps))
+        }
+        .await;
+        let (tx, last_valid_block_height, tax_bps) = match prepared {
+            Ok(prepared) => prepared,
+            Err(e) => {
+                release();
+                return Err(e);
+            }
+        };
+        let transaction = match encode_transaction(&tx) {
+            Ok(transaction) => transaction,
+            Err(e) => {
+                release();
+                return Err(e);
+            }
+        };
+
+        let mut book = book.lock().await;
+        // 构建交易期间停机或钱包被吊销时不再接受
+        if let Err(e) = book.check_owner(&user) {
+            release();
+            return Err(e.into());
+        }
+        book.prepared.insert(
+            prepare_id,
+            PreparedOrder {
+                user,
+                leg,
+                tax_bps,
+                message: tx.message,
+                last_valid_block_height,
+                nonce_account,
+                created_at: Instant::now(),
+            },
+        );
+        Ok(PreparedTransaction {
+            prepare_id,
+            transaction,
+            last_valid_block_height,
+            nonce_account: nonce_account.map(|account| account.to_string()),
+        })
+    }
+
+    /// 移除超时未提交的待签名订单并归还其 nonce 账户
+    fn purge_expired_prepared(&mut self) {
+        let nonces = self.nonces.clone();
+        self.prepared.retain(|prepare_id, prepared| {
+            let expired = prepared.is_expired();
+            if expired {
+                if let Some(pool) = &nonces {
+                    pool.release(prepare_id);
+                }
+            }
+            !expired
+        });
+    }
+
+    /// 提交客户端签名的非托管订单
+    ///
+    /// 订单参数必须与生成交易时一致，交易消息必须与生成的完全相同且签名有效。
+    /// 校验通过后订单开始监控价格，触发时直接发送该交易。
+    pub async fn submit_signed_order(
+        &mut self,
+        prepare_id: Uuid,
+        leg: OrderLeg,
+        signed_transaction: &str,
+    ) -> Result<Uuid> {
+        self.check_accepting()?;
+        let prepared = self
+            .prepared
+            .get(&prepare_id)
+            .filter(|prepared| !prepared.is_expired())
+            .ok_or_else(|| anyhow!("待签名订单不存在或已过期，请重新生成"))?;
+        if prepared.leg != leg {
+            return Err(anyhow!("订单参数与生成交易时不一致"));
+        }
+        if self.revoked.contains_key(&prepared.user) {
+            return Err(anyhow!("钱包 {} 已被吊销", prepared.user));
+        }
+        self.check_order_limits(&prepared.user, 1).await?;
+        let authority = match (&prepared.nonce_account, &self.nonces) {
+            (Some(_), Some(pool)) => Some(pool.authority()),
+            _ => None,
+        };
+        let tx = verify_signed_transaction(
+            decode_transaction(signed_transaction)?,
+            &prepared.message,
+            &prepared.user,
+            authority.as_deref(),
+        )?;
+
+        let prepared = self.prepared.remove(&prepare_id).unwrap();
+        let lifetime = match (prepared.nonce_account, prepared.last_valid_block_height) {
+            (Some(nonce_account), _) => SignedTxLifetime::Nonce { nonce_account },
+            (None, Some(last_valid_block_height)) => SignedTxLifetime::Blockhash {
+                last_valid_block_height,
+            },
+            (None, None) => return Err(anyhow!("待签名订单缺少有效期信息")),
+        };
+        let mut order = prepared.leg.into_order(prepared.user, None);
+        // 订单 ID 与 prepare_id 相同，nonce 账户的租用随订单结束归还
+        order.order_id = prepare_id;
+        order.tax_bps = Some(prepared.tax_bps);
+        metrics().orders_placed.inc();
+        Ok(self.spawn_signed_order(order, tx, lifetime).await)
+    }
+
+    /// 非托管订单：触发时发送客户端签名的交易
+    async fn spawn_signed_order(
+        &mut self,
+        order: Order,
+        tx: VersionedTransaction,
+        lifetime: SignedTxLifetime,
+    ) -> Uuid {
+        self.signed_orders.insert(order.order_id);
+        let resume = encode_transaction(&tx).map(|transaction| ResumeState::Signed {
+            transaction,
+            lifetime,
+        });
+        let nonce_authority = self.nonces.as_ref().map(NoncePool::authority);
+        self.spawn_order_task(order, resume, move |ctx, order, cancel| {
+            _signed_order(ctx, order, tx, lifetime, nonce_authority, cancel)
+        })
+        .await
+    }
+
+    /// 托管订单：由服务端持有的私钥签名交易
+    async fn spawn_order(&mut self, keypair: Keypair, order: Order) -> Uuid {
+        // 私钥按下单请求中 `encrypt_pk` 的方式加密保存
+        let resume = encrypt(Zeroizing::new(keypair.to_base58_string()).as_bytes())
+            .map(|encrypt_pk| ResumeState::Custodial { encrypt_pk });
+        self.spawn_order_task(order, resume, move |ctx, order, cancel| async move {
+            _order(ctx, Arc::new(keypair), order, cancel).await
+        })
+        .await
+    }
+
+    /// 记录订单并启动后台任务，任务结束后更新订单状态
+    ///
+    /// 启用持久化时同时写入 `resume`，进程意外退出后由 [`OrderBook::restore_unfinished`] 恢复订单任务。
+    async fn spawn_order_task<F, Fut>(
+        &mut self,
+        order: Order,
+        resume: Result<ResumeState>,
+        run: F,
+    ) -> Uuid
+    where
+        F: FnOnce(OrderContext, Order, Receiver<()>) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<OrderOutcome>> + Send + 'static,
+    {
+        let order_id = order.order_id;
+        self.orders.lock().await.insert(order_id, order.clone());
+        if let Some(persist) = &self.persist {
+            persist.enqueue(PersistRecord::Placed(order.clone()));
+            match resume {
+                Ok(resume) => persist.enqueue(PersistRecord::Resumable(order_id, resume)),
+                Err(e) => println!(
+                    "订单 {:?} 无法保存恢复信息，进程意外退出后不会被恢复 {:?}",
+                    order_id, e
+                ),
+            }
+        }
+        self.events
+            .publish(OrderEvent::new(&order, OrderEventKind::Placed));
+
+        let (tx, rx) = oneshot::channel();
+        let cancel = CancelHandle::new(tx);
+        self.cancel_tasks.insert(order_id, cancel.clone());
+        let expiry = order.expires_at_ms.map(|at| (at, cancel.clone()));
+        let oco = match (order.oco_sibling, order.group_id) {
+            (Some(_), Some(group_id)) => {
+                let oco = self.oco_groups.entry(group_id).or_default().clone();
+                oco.register(order_id, cancel);
+                Some(oco)
+            }
+            _ => None,
+        };
+        let (done_tx, done_rx) = oneshot::channel::<()>();
+        self.task_done.insert(order_id, done_rx);
+        if order.paused {
+            self.pause.set_order(order_id, true);
+        }
+
+        let ctx = OrderContext {
+            rpc: self.rpc.clone(),
+            jito: self.jito.clone(),
+            jup: self.jup.clone(),
+            blockhashes: self.blockhashes.clone(),
+            prices: self.prices.clone(),
+            dispatcher: self.dispatcher.clone(),
+            price_max_age: self.price_max_age,
+            quotes: self.quotes.clone(),
+            swap_permits: self.swap_permits.clone(),
+            tax_account: self.tax_account,
+            tax_policy: self.tax_policy.clone(),
+            tax_side: self.tax_side,
+            priority_fee_percentile: self.priority_fee_percentile,
+            max_trigger_deviation_bps: self.max_trigger_deviation_bps,
+            retry_policy: self.retry_policy,
+            bundle: self.bundle,
+            private_execution: self.private_execution,
+            orders: self.orders.clone(),
+            persist: self.persist.clone(),
+            db: self.db.clone(),
+            shutdown: self.shutdown.subscribe(),
+            events: self.events.clone(),
+            audit: self.audit.clone(),
+            oco,
+            pause: self.pause.clone(),
+        };
+        let orders = self.orders.clone();
+        let persist = self.persist.clone();
+        let nonces = self.nonces.clone();
+        let suspended = self.suspended.clone();
+        let http = self.http.clone();
+        let webhook = self.webhook;
+        let events = self.events.clone();
+        let pause = self.pause.clone();
+        // 回收已结束的任务，已过期订单的撤单信号一并移除
+        while self.tasks.try_join_next().is_some() {}
+        self.cancel_tasks.retain(|_, cancel| !cancel.is_spent());
+        self.tasks.spawn(async move {
+            // 任务结束（包括提前返回）时释放，通知等待的 modify_order
+            let _done = done_tx;
+            let result = run_until_expiry(run(ctx, order, rx), expiry, &orders, order_id).await;
+            pause.set_order(order_id, false);
+            if let Some(pool) = &nonces {
+                pool.release(&order_id);
+            }
+            let status = match result {
+                Ok(OrderOutcome::Filled) => {
+                    metrics().orders_filled.inc();
+                    OrderStatus::Filled
+                }
+                Ok(OrderOutcome::Canceled) => {
+                    // 撤单时已经记录过状态
+                    println!("Deal task was canceled");
+                    return;
+                }
+                Ok(OrderOutcome::PartiallyFilled) => {
+                    let Some(order) = orders.lock().await.get(&order_id).cloned() else {
+                        return;
+                    };
+                    println!("订单 {:?} 部分成交 {}", order_id, order.filled_amount);
+                    OrderStatus::PartiallyFilled {
+                        filled_amount: order.filled_amount,
+                        signatures: order.fill_signatures,
+                    }
+                }
+                Ok(OrderOutcome::Suspended(resume)) => {
+                    // 订单保持 Pending，停机时写入快照
+                    if let Some(order) = orders.lock().await.get(&order_id) {
+                        suspended.lock().await.push(SuspendedOrder {
+                            order: order.clone(),
+                            resume,
+                        });
+                    }
+                    return;
+                }
+                Ok(OrderOutcome::Expired) => {
+                    println!("订单 {:?} 的签名交易已过期", order_id);
+                    metrics().orders_expired.inc();
+                    OrderStatus::ResignRequired
+                }
+                Ok(OrderOutcome::Unfilled(reason)) => {
+                    println!("订单 {:?} 未成交 {}", order_id, reason);
+                    metrics().orders_unfilled.inc();
+                    OrderStatus::Unfilled(reason)
+                }
+                Ok(OrderOutcome::Lapsed) => {
+                    println!("订单 {:?} 在有效期内未触发，已过期", order_id);
+                    metrics().orders_expired.inc();
+                    OrderStatus::Expired
+                }
+                Err(e) => {
+                    println!("Deal task failed {:?}", e);
+                    metrics().orders_failed.inc();
+                    OrderStatus::Failed(e.to_string())
+                }
+            };
+            let mut orders = orders.lock().await;
+            let callback = match orders.get_mut(&order_id) {
+                Some(order) => {
+                    // 交易签名记录在 Triggered 状态中，覆盖前取出
+                    let (signature, bundle_id) = match &order.status {
+                        OrderStatus::Triggered {
+                            signature,
+                            bundle_id,
+                        } => (Some(signature.clone()), bundle_id.clone()),
+                        _ => (order.fill_signatures.last().cloned(), None),
+                    };
+                    if let Some(kind) = terminal_event(&status, signature.clone(), order.last_fill)
+                    {
+                        events.publish(OrderEvent::new(order, kind));
+                    }
+                    order.status = status;
+                    order
+                        .callback_url
+                        .clone()
+                        .map(|url| (url, WebhookPayload::new(order, signature, bundle_id)))
+                }
+                None => None,
+            };
+            finalize_order(&mut orders, persist.as_ref(), order_id);
+            drop(orders);
+            if let Some((url, payload)) = callback {
+                if let Err(e) = deliver(&http, &url, &payload, webhook.timeout).await {
+                    println!("订单 {:?} 回调 {} 失败 {:?}", order_id, url, e);
+                }
+            }
+        });
+
+        order_id
+    }
+
+    /// 暂停或恢复单笔等待触发的订单，暂停期间订单触发后等待恢复，不会发送交易
+    pub async fn set_order_paused(&self, order_id: Uuid, paused: bool) -> error::Result<()> {
+        let mut orders = self.orders.lock().await;
+        let order = orders
+            .get_mut(&order_id)
+            .ok_or(LimitOrderError::OrderNotFound)?;
+        if order.status != OrderStatus::Pending {
+            return Err(LimitOrderError::invalid(
+                "order_id",
+                format!("订单已结束，状态为 {:?}", order.status),
+            ));
+        }
+        order.paused = paused;
+        self.pause.set_order(order_id, paused);
+        println!(
+            "订单 {:?} {}",
+            order_id,
+            if paused { "已暂停" } else { "已恢复" }
+        );
+        Ok(())
+    }
+
+    /// 取消订单，只有下单钱包可以取消
+    ///
+    /// `requester` 与订单的下单钱包不一致时返回 "无权限取消该订单"，订单继续运行。
+    pub async fn cancel_order(&mut self, order_id: Uuid, requester: &Pubkey) -> error::Result<()> {
+        authorize_cancel(self.orders.lock().await.get(&order_id), requester)?;
+        Ok(self.force_cancel_order(order_id).await?)
+    }
+
+    /// 管理员取消任意钱包的订单
+    pub async fn admin_cancel_order(&mut self, order_id: Uuid) -> error::Result<()> {
+        if !self.orders.lock().await.contains_key(&order_id) {
+            return Err(LimitOrderError::OrderNotFound);
+        }
+        Ok(self.force_cancel_order(order_id).await?)
+    }
+
+    /// 不校验下单钱包直接取消订单，供吊销钱包等管理操作使用
+    ///
+    /// 整个过程持有订单表的锁，订单任务不会在撤单途中触发。
+    async fn force_cancel_order(&mut self, order_id: Uuid) -> Result<()> {
+        let mut orders = self.orders.lock().await;
+        let order = cancel_running_order(&mut orders, &mut self.cancel_tasks, order_id)?;
+        self.events
+            .publish(OrderEvent::new(order, OrderEventKind::Canceled));
+        self.task_done.remove(&order_id);
+        finalize_order(&mut orders, self.persist.as_ref(), order_id);
+        metrics().orders_canceled.inc();
+        println!("订单 {:?} 成功取消", order_id);
+        Ok(())
+    }
+
+    /// 修改等待触发的托管订单，订单 ID 保持不变
+    ///
+    /// 先在订单表中把订单标记为取消，阻止旧任务继续触发；再通知旧任务退出并等待它结束，
+    /// 最后用新参数在同一个 ID 下启动新任务。旧任务已经发出交易或订单已部分成交时返回
+    /// `OrderAlreadyTriggered`，订单不受影响。需要下单钱包的私钥以启动新任务。
+    pub async fn modify_order(
+        &mut self,
+        order_id: Uuid,
+        private_key: SecretString,
+        changes: OrderChanges,
+    ) -> error::Result<Order> {
+        let keypair = parse_keypair(private_key.expose())?;
+        if self.signed_orders.contains(&order_id) {
+            return Err(LimitOrderError::invalid(
+                "order_id",
+                "非托管订单的交易已签名，请撤单后重新下单",
+            ));
+        }
+        changes.validate()?;
+        let updated = {
+            let mut orders = self.orders.lock().await;
+            let order = orders
+                .get_mut(&order_id)
+                .ok_or(LimitOrderError::OrderNotFound)?;
+            if order.owner != keypair.pubkey().to_string() {
+                return Err(LimitOrderError::Unauthorized(
+                    "无权限修改该订单".to_string(),
+                ));
+            }
+            match order.status {
+                OrderStatus::Pending if order.filled_amount == 0 => {}
+                OrderStatus::Pending
+                | OrderStatus::Triggered { .. }
+                | OrderStatus::Filled
+                | OrderStatus::PartiallyFilled { .. } => {
+                    return Err(LimitOrderError::OrderAlreadyTriggered)
+                }
+                ref status => {
+                    return Err(LimitOrderError::invalid(
+                        "order_id",
+                        format!("订单已结束，状态为 {:?}", status),
+                    ))
+                }
+            }
+            let mut updated = order.clone();
+            changes.apply(&mut updated);
+            // 旧任务在发送交易前会检查状态，标记后不会再触发
+            order.status = OrderStatus::Canceled;
+            updated
+        };
+
+        if let Some(cancel) = self.cancel_tasks.remove(&order_id) {
+            cancel.cancel();
+        }
+        if let Some(done) = self.task_done.remove(&order_id) {
+            let _ = done.await;
+        }
+        self.spawn_order(keypair, updated.clone()).await;
+        println!("订单 {:?} 已修改", order_id);
+        Ok(updated)
+    }
+
+    /// 订单簿中的全部订单，`owner` 不为 None 时只返回该钱包的订单，按下单时间排序
+    ///
+    /// 配置数据库时已结束的订单写入后即从订单簿移除，见 [`crate::common::db::order_history`]。
+    pub async fn list_orders(&self, owner: Option<&str>) -> Vec<Order> {
+        let mut orders: Vec<Order> = self
+            .orders
+            .lock()
+            .await
+            .values()
+            .filter(|order| owner.is_none_or(|owner| order.owner == owner))
+            .cloned()
+            .collect();
+        orders.sort_by_key(|order| (order.created_at, order.order_id));
+        orders
+    }
+
+    /// 取消钱包所有符合条件的活跃订单
+    ///
+    /// 逐笔撤单，每笔只短暂持有订单表的锁。订单可能在撤单过程中被触发或成交，
+    /// 此时该订单记入 `already_filled` 而不是失败。订单按 ID 排序处理，结果顺序是确定的。
+    pub async fn cancel_matching(
+        &mut self,
+        owner: &Pubkey,
+        filter: &CancelFilter,
+    ) -> CancelAllResult {
+        let owner = owner.to_string();
+        let mut candidates: Vec<Uuid> = self
+            .orders
+            .lock()
+            .await
+            .values()
+            .filter(|order| {
+                order.owner == owner
+                    && self.cancel_tasks.contains_key(&order.order_id)
+                    && filter.matches(order)
+            })
+            .map(|order| order.order_id)
+            .collect();
+        candidates.sort();
+
+        let mut result = CancelAllResult::default();
+        for order_id in candidates {
+            let cancel_error = match self.force_cancel_order(order_id).await {
+                Ok(()) => {
+                    result.canceled.push(order_id);
+                    continue;
+                }
+                Err(e) => e,
+            };
+            // 撤单失败时按订单当前状态区分是已经成交还是其他原因
+            let status = self
+                .orders
+                .lock()
+                .await
+                .get(&order_id)
+                .map(|order| order.status.clone());
+            match status {
+                Some(
+                    OrderStatus::Triggered { .. }
+                    | OrderStatus::Filled
+                    | OrderStatus::PartiallyFilled { .. },
+                ) => result.already_filled.push(order_id),
+                _ => result.failed.push((order_id, cancel_error.to_string())),
+            }
+        }
+        result
+    }
+
+    /// 吊销钱包：取消该钱包的全部活跃订单并拒绝其后续下单
+    ///
+    /// 私钥只存在于订单的后台任务中，任务被取消后密钥随之释放。
+    /// 返回被取消的订单 ID。
+    pub async fn revoke_wallet(&mut self, wallet: Pubkey) -> Result<Vec<Uuid>> {
+        let owner = wallet.to_string();
+        let active: Vec<Uuid> = self
+            .orders
+            .lock()
+            .await
+            .values()
+            .filter(|order| order.owner == owner && self.cancel_tasks.contains_key(&order.order_id))
+            .map(|order| order.order_id)
+            .collect();
+
+        // 会话中缓存的私钥一并释放
+        self.sessions.remove_wallet(&wallet);
+
+        let mut canceled = vec![];
+        for order_id in active {
+            if self.force_cancel_order(order_id).await.is_ok() {
+                println!("钱包 {} 被吊销，订单 {:?} 已取消", owner, order_id);
+                canceled.push(order_id);
+            }
+        }
+
+        let revoked_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        self.revoked
+            .entry(wallet)
+            .and_modify(|record| record.canceled_orders.extend_from_slice(&canceled))
+            .or_insert_with(|| RevokedWallet {
+                wallet: owner,
+                revoked_at,
+                canceled_orders: canceled.clone(),
+            });
+        Ok(canceled)
+    }
+
+    /// 停机后拒绝新订单
+    fn check_accepting(&self) -> Result<()> {
+        if *self.shutdown.borrow() {
+            return Err(anyhow!("服务正在停机，暂停接收新订单"));
+        }
+        Ok(())
+    }
+
+    /// 优雅停机
+    ///
+    /// 停止接收新订单并通知所有订单任务：等待触发的订单暂停并交出恢复信息，已在发送交易的订单执行完当前这笔交易。
+    /// 最多等待 `shutdown_timeout`，超时仍未退出的任务被强制终止。
+    /// 暂停的订单和交易已发出的订单写入快照，下次启动时由 [`OrderBook::restore_snapshot`] 恢复。
+    pub async fn shutdown(&mut self) -> Result<()> {
+        self.shutdown.send_replace(true);
+        let mut tasks = std::mem::take(&mut self.tasks);
+        let drained = tokio::time::timeout(self.shutdown_timeout, async {
+            while tasks.join_next().await.is_some() {}
+        })
+        .await
+        .is_ok();
+        if !drained {
+            println!("等待订单任务退出超时，剩余 {} 个任务被终止", tasks.len());
+            tasks.abort_all();
+        }
+
+        let suspended = std::mem::take(&mut *self.suspended.lock().await);
+        let orders = self.orders.lock().await;
+        let triggered: Vec<Order> = orders
+            .values()
+            .filter(|order| matches!(order.status, OrderStatus::Triggered { .. }))
+            .cloned()
+            .collect();
+        for order in orders.values() {
+            if order.status == OrderStatus::Pending
+                && !suspended.iter().any(|s| s.order.order_id == order.order_id)
+            {
+                println!("订单 {:?} 未能暂停，不会被恢复", order.order_id);
+            }
+        }
+        drop(orders);
+
+        let path = OrderSnapshot::path();
+        println!(
+            "保存订单快照 {:?}：暂停 {} 笔，已发送 {} 笔",
+            path,
+            suspended.len(),
+            triggered.len()
+        );
+        OrderSnapshot {
+            suspended,
+            triggered,
+        }
+        .write(&path)
+    }
+
+    /// 恢复上次停机时保存的订单快照，返回重新启动的订单数
+    ///
+    /// 交易已发出的订单只恢复状态，不会再次发送。单笔订单恢复失败不影响其他订单。
+    /// 配置数据库时先按执行日志核对每笔订单未解决的执行意图（见 [`OrderBook::reconcile_intents`]）：
+    /// 交易已上链的订单记为成交；状态无法确定的订单保持 `Triggered`，不重新监控，等待人工核对；
+    /// 只有交易确定未上链的订单才重新启动。执行日志读取失败时不恢复，快照留到下次启动。
+    /// 缺少 `trigger_condition` 的旧限价单不会触发，记为失败（见 [`OrderBook::resume_order`]）。
+    pub async fn restore_snapshot(&mut self) -> Result<usize> {
+        let path = OrderSnapshot::path();
+        if !path.exists() {
+            return Ok(0);
+        }
+        let intents = self.reconcile_intents().await?;
+        let Some(snapshot) = OrderSnapshot::take(&path)? else {
+            return Ok(0);
+        };
+        let suspended = snapshot
+            .suspended
+            .into_iter()
+            .map(|s| (s.order.clone(), Some(s)));
+        let triggered = snapshot.triggered.into_iter().map(|order| (order, None));
+        Ok(self
+            .restore_orders(&intents, suspended.chain(triggered).collect())
+            .await)
+    }
+
+    /// 从数据库恢复进程意外退出时未结束的订单，返回重新启动的订单数
+    ///
+    /// 正常停机时订单由 [`OrderBook::restore_snapshot`] 恢复，已在订单簿中的订单跳过，因此应在恢复快照之后调用。
+    /// 执行意图的核对与恢复快照相同。未配置数据库时返回 0。
+    pub async fn restore_unfinished(&mut self) -> Result<usize> {
+        let Some(db) = self.db.clone() else {
+            return Ok(0);
+        };
+        let unfinished = unfinished_orders(&db)
+            .await
+            .map_err(|e| anyhow!("读取未结束的订单失败 {}", e))?;
+        let unfinished: Vec<(Order, Option<SuspendedOrder>)> = {
+            let orders = self.orders.lock().await;
+            unfinished
+                .into_iter()
+                .filter(|s| !orders.contains_key(&s.order.order_id))
+                .map(|s| (s.order.clone(), Some(s)))
+                .collect()
+        };
+        if unfinished.is_empty() {
+            return Ok(0);
+        }
+        let intents = self.reconcile_intents().await?;
+        Ok(self.restore_orders(&intents, unfinished).await)
+    }
+
+    /// 按执行意图的核对结果恢复订单，`suspended` 为 None 的订单只恢复状态，返回重新启动的订单数
+    async fn restore_orders(
+        &mut self,
+        intents: &HashMap<Uuid, IntentStatus>,
+        entries: Vec<(Order, Option<SuspendedOrder>)>,
+    ) -> usize {
+        let mut resumable = vec![];
+        {
+            let mut orders = self.orders.lock().await;
+            for (mut order, suspended) in entries {
+                let order_id = order.order_id;
+                match intents.get(&order_id) {
+                    Some(IntentStatus::Landed(signature)) => {
+                        println!("订单 {:?} 的交易 {} 已上链，记为成交", order_id, signature);
+                        if !order.fill_signatures.contains(signature) {
+                            order.fill_signatures.push(signature.clone());
+                        }
+                        order.status = OrderStatus::Filled;
+                        orders.insert(order_id, order);
+                        finalize_order(&mut orders, self.persist.as_ref(), order_id);
+                        continue;
+                    }
+                    Some(IntentStatus::Unknown(signature)) => {
+                        println!(
+                            "订单 {:?} 的交易 {} 状态无法确定，不重新监控",
+                            order_id, signature
+                        );
+                        order.status = OrderStatus::Triggered {
+                            signature: signature.clone(),
+                            bundle_id: None,
+                        };
+                        orders.insert(order_id, order);
+                        continue;
+                    }
+                    Some(IntentStatus::Dropped) | None => {}
+                }
+                match suspended {
+                    Some(suspended) => resumable.push(suspended),
+                    None => {
+                        orders.insert(order_id, order);
+                    }
+                }
+            }
+        }
+        let mut restored = 0;
+        for suspended in resumable {
+            let order_id = suspended.order.order_id;
+            match self.resume_order(suspended).await {
+                Ok(()) => restored += 1,
+                Err(e) => println!("恢复订单 {:?} 失败 {:?}", order_id, e),
+            }
+        }
+        restored
+    }
+
+    /// 核对执行日志中未解决的执行意图，返回每笔订单的核对结果
+    ///
+    /// 按签名查询交易状态（包括历史交易）：交易已确认且执行成功为已上链；交易已确认但执行失败，
+    /// 或查不到交易且 blockhash 已过期为未上链；其他情况（包括查询失败）无法确定。
+    /// 已上链和未上链的意图标记为已解决。同一笔订单有多个意图时已上链优先，其次为无法确定。
+    /// 未配置数据库时返回空表。
+    async fn reconcile_intents(&self) -> Result<HashMap<Uuid, IntentStatus>> {
+        let Some(db) = &self.db else {
+            return Ok(HashMap::new());
+        };
+        let intents = outstanding_intents(db)
+            .await
+            .map_err(|e| anyhow!("读取执行日志失败 {}", e))?;
+        let block_height = self.rpc.get_block_height().await.ok();
+        let mut results: HashMap<Uuid, IntentStatus> = HashMap::new();
+        for chunk in intents.chunks(MAX_SIGNATURE_STATUSES) {
+            let signatures: Vec<Option<Signature>> = chunk
+                .iter()
+                .map(|intent| intent.signature.parse().ok())
+                .collect();
+            let queried: Vec<Signature> = signatures.iter().flatten().copied().collect();
+            let statuses = self.rpc.signature_statuses(&queried).await.ok();
+            let mut found = statuses.into_iter().flatten();
+            for (intent, signature) in chunk.iter().zip(&signatures) {
+                let Ok(order_id) = intent.order_id.parse::<Uuid>() else {
+                    continue;
+                };
+                // 只有签名有效且查询成功时才能判断
+                let status = match (signature, &block_height) {
+                    (Some(_), Some(height)) => match found.next() {
+                        Some(SignatureStatus::Confirmed { succeeded: true }) => {
+                            IntentStatus::Landed(intent.signature.clone())
+                        }
+                        Some(SignatureStatus::Confirmed { succeeded: false }) => {
+                            IntentStatus::Dropped
+                        }
+                        Some(SignatureStatus::Unknown)
+                            if *height > intent.last_valid_block_height =>
+                        {
+                            IntentStatus::Dropped
+                        }
+                        _ => IntentStatus::Unknown(intent.signature.clone()),
+                    },
+                    (Some(_), None) => {
+                        found.next();
+                        IntentStatus::Unknown(intent.signature.clone())
+                    }
+                    (None, _) => IntentStatus::Unknown(intent.signature.clone()),
+                };
+                if !matches!(status, IntentStatus::Unknown(_)) {
+                    if let Err(e) = resolve_intent(db, intent.attempt_id.clone()).await {
+                        println!("标记执行意图 {} 失败 {:?}", intent.attempt_id, e);
+                    }
+                }
+                let merged = match (results.remove(&order_id), status) {
+                    (Some(landed @ IntentStatus::Landed(_)), _) => landed,
+                    (_, landed @ IntentStatus::Landed(_)) => landed,
+                    (Some(unknown @ IntentStatus::Unknown(_)), _) => unknown,
+                    (_, status) => status,
+                };
+                results.insert(order_id, merged);
+            }
+        }
+        Ok(results)
+    }
+
+    /// 为暂停的订单重新启动订单任务，快照恢复和命令行工具提交的订单都经过这里
+    ///
+    /// 缺少 `trigger_condition` 的旧限价单不重新启动，记为失败后返回错误。
+    pub async fn resume_order(&mut self, suspended: SuspendedOrder) -> Result<()> {
+        let SuspendedOrder { order, resume } = suspended;
+        let order_id = order.order_id;
+        let failed = {
+            let mut orders = self.orders.lock().await;
+            fail_missing_trigger_condition(&mut orders, self.persist.as_ref(), &order)
+        };
+        if let Some(reason) = failed {
+            return Err(anyhow!("订单 {:?} {}", order_id, reason));
+        }
+        match resume {
+            ResumeState::Custodial { encrypt_pk } => {
+                let keypair = parse_keypair(decrypt(&encrypt_pk)?.expose())?;
+                self.spawn_order(keypair, order).await;
+            }
+            ResumeState::Signed {
+                transaction,
+                lifetime,
+            } => {
+                let tx = decode_transaction(&transaction)?;
+                // 重新占用 nonce 账户，避免被新的待签名订单租走后推进
+                if let (SignedTxLifetime::Nonce { nonce_account }, Some(pool)) =
+                    (lifetime, &self.nonces)
+                {
+                    if !pool.reserve(order_id, nonce_account) {
+                        println!(
+                            "订单 {:?} 的 nonce 账户 {} 不在池中",
+                            order_id, nonce_account
+                        );
+                    }
+                }
+                self.spawn_signed_order(order, tx, lifetime).await;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 撤单请求者必须是下单钱包
+fn authorize_cancel(order: Option<&Order>, requester: &Pubkey) -> error::Result<()> {
+    match order {
+        Some(order) if order.owner != requester.to_string() => Err(LimitOrderError::Unauthorized(
+            "无权限取消该订单".to_string(),
+        )),
+        Some(_) => Ok(()),
+        None => Err(LimitOrderError::OrderNotFound),
+    }
+}
+
+/// 先通知订单任务退出，成功后才把订单标记为取消
+///
+/// 订单已结束或没有在运行的任务时返回错误，订单状态不变，也不会推送撤单事件。
+fn cancel_running_order<'a>(
+    orders: &'a mut HashMap<Uuid, Order>,
+    cancel_tasks: &mut HashMap<Uuid, CancelHandle>,
+    order_id: Uuid,
+) -> Result<&'a Order> {
+    let order = orders
+        .get_mut(&order_id)
+        .ok_or(LimitOrderError::OrderNotFound)?;
+    if order.status != OrderStatus::Pending {
+        return Err(anyhow!("订单已结束，状态为 {:?}", order.status));
+    }
+    if !cancel_tasks
+        .remove(&order_id)
+        .is_some_and(|cancel| cancel.cancel())
+    {
+        return Err(LimitOrderError::OrderNotFound.into());
+    }
+    order.status = OrderStatus::Canceled;
+    Ok(order)
+}
+
+/// 校验撤单签名：`signature` 为 `user` 对订单 ID 字符串（小写、带连字符）的 ed25519 签名，bs58 编码
+pub fn verify_cancel_signature(user: &Pubkey, order_id: &Uuid, signature: &str) -> Result<()> {
+    let signature = signature
+        .parse::<Signature>()
+        .map_err(|_| anyhow!("撤单签名格式无效"))?;
+    if !signature.verify(user.as_ref(), order_id.to_string().as_bytes()) {
+        return Err(anyhow!("撤单签名校验失败"));
+    }
+    Ok(())
+}
+
+/// 批量撤单签名的有效期（秒）
+pub const CANCEL_ALL_SIGNATURE_TTL_SECS: u64 = 60;
+
+/// 校验批量撤单签名：`signature` 为 `user` 对 `cancel_all:<timestamp>` 的 ed25519 签名，bs58 编码
+///
+/// `timestamp` 为 unix 秒，与服务器时间相差超过 [`CANCEL_ALL_SIGNATURE_TTL_SECS`] 的签名不被接受，避免签名被重放。
+pub fn verify_cancel_all_signature(user: &Pubkey, timestamp: u64, signature: &str) -> Result<()> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    if now.abs_diff(timestamp) > CANCEL_ALL_SIGNATURE_TTL_SECS {
+        return Err(anyhow!("撤单签名已过期"));
+    }
+    let signature = signature
+        .parse::<Signature>()
+        .map_err(|_| anyhow!("撤单签名格式无效"))?;
+    let message = format!("cancel_all:{}", timestamp);
+    if !signature.verify(user.as_ref(), message.as_bytes()) {
+        return Err(anyhow!("撤单签名校验失败"));
+    }
+    Ok(())
+}
+
+/// 同一笔订单两次价格事件的最小间隔
+pub(crate) const PRICE_EVENT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 订单结束时推送的事件，撤单在撤单时已经推送
+fn terminal_event(
+    status: &OrderStatus,
+    signature: Option<String>,
+    fill: Option<FillReport>,
+) -> Option<OrderEventKind> {
+    match status {
+        OrderStatus::Filled => Some(OrderEventKind::Filled {
+            signature,
+            out_amount: fill.map(|fill| fill.out_amount),
+            fill,
+        }),
+        OrderStatus::PartiallyFilled { filled_amount, .. } => {
+            Some(OrderEventKind::PartiallyFilled {
+                filled_amount: *filled_amount,
+            })
+        }
+        OrderStatus::Failed(reason) => Some(OrderEventKind::Failed {
+            reason: reason.clone(),
+        }),
+        OrderStatus::ResignRequired | OrderStatus::Expired => Some(OrderEventKind::Expired),
+        OrderStatus::Unfilled(reason) => Some(OrderEventKind::Unfilled {
+            reason: reason.clone(),
+        }),
+        _ => None,
+    }
+}
+
+/// 订单后台任务共享的客户端与配置
+struct OrderContext {
+    rpc: Arc<dyn SolanaRpc>,
+    jito: Arc<dyn BundleSender>,
+    jup: Arc<dyn SwapApi>,
+    blockhashes: Arc<BlockhashProvider>,
+    prices: PriceCache,
+    dispatcher: TriggerDispatcher,
+    price_max_age: Duration,
+    quotes: Arc<QuoteFeed>,
+    swap_permits: Arc<Semaphore>,
+    tax_account: Pubkey,
+    tax_policy: Arc<StdRwLock<TaxPolicy>>,
+    tax_side: TaxSide,
+    priority_fee_percentile: Option<u8>,
+    /// 订单未指定时报价隐含的成交价格相对触发价格的最大偏离
+    max_trigger_deviation_bps: Option<Bps>,
+    retry_policy: RetryPolicy,
+    bundle: BundleConfig,
+    private_execution: PrivateExecutionConfig,
+    orders: Arc<Mutex<HashMap<Uuid, Order>>>,
+    persist: Option<PersistQueue>,
+    /// 写入执行日志的数据库，未配置时不记录执行意图
+    db: Option<DbPool>,
+    shutdown: watch::Receiver<bool>,
+    events: EventBus,
+    audit: AuditLog,
+    /// 止盈止损订单所在的 OCO 组
+    oco: Option<Arc<OcoGroup>>,
+    pause: PauseSwitch,
+}
+
+impl OrderContext {
+    /// 推送订单监控到的价格，每笔订单每 [`PRICE_EVENT_INTERVAL`] 最多一次
+    fn publish_price(&self, order: &Order, price: f64, last: &mut Option<Instant>) {
+        if last.is_some_and(|last| last.elapsed() < PRICE_EVENT_INTERVAL) {
+            return;
+        }
+        *last = Some(Instant::now());
+        self.events.publish(OrderEvent::new(
+            order,
+            OrderEventKind::PriceUpdate { price },
+        ));
+    }
+
+    /// 等待停机信号
+    async fn shutdown_requested(&self) {
+        let mut shutdown = self.shutdown.clone();
+        if shutdown.wait_for(|stop| *stop).await.is_err() {
+            // 订单簿已释放，不会再有停机信号
+            std::future::pending::<()>().await;
+        }
+    }
+
+    /// 发送交易前将订单从 `Pending` 标记为 `Triggered` 并记录交易签名，重启后不会重复发送
+    ///
+    /// 订单已被取消等不再是 `Pending` 时返回 false，调用方不应发送交易。
+    /// OCO 订单在这里决定胜负：胜出的订单同时取消另一笔，落败的订单返回 false。
+    /// `tax_bps` 为本次交易使用的税率，记录在订单上。
+    async fn mark_triggered(
+        &self,
+        order_id: Uuid,
+        signature: String,
+        tax_bps: Option<Bps>,
+    ) -> bool {
+        let mut orders = self.orders.lock().await;
+        let sibling = match orders.get_mut(&order_id) {
+            Some(order) if order.status == OrderStatus::Pending => {
+                if self.oco.as_ref().is_some_and(|oco| !oco.claim(order_id)) {
+                    return false;
+                }
+                order.status = OrderStatus::Triggered {
+                    signature: signature.clone(),
+                    bundle_id: None,
+                };
+                order.tax_bps = tax_bps;
+                self.events.publish(OrderEvent::new(
+                    order,
+                    OrderEventKind::Triggered {
+                        signature: signature.clone(),
+                    },
+                ));
+                order.oco_sibling
+            }
+            _ => return false,
+        };
+        if let Some(persist) = &self.persist {
+            persist.enqueue(PersistRecord::Triggered(order_id, signature));
+        }
+        if let (Some(sibling_id), Some(oco)) = (sibling, &self.oco) {
+            // 先在订单表中标记取消，另一笔即使已经在执行也不会再发送交易
+            if let Some(sibling) = orders.get_mut(&sibling_id) {
+                if sibling.status == OrderStatus::Pending {
+                    sibling.status = OrderStatus::Canceled;
+                    self.events
+                        .publish(OrderEvent::new(sibling, OrderEventKind::Canceled));
+                    // 另一笔的任务在订单表中找不到订单时同样不会发送交易
+                    finalize_order(&mut orders, self.persist.as_ref(), sibling_id);
+                    metrics().orders_canceled.inc();
+                    println!("OCO 订单 {:?} 已触发，取消订单 {:?}", order_id, sibling_id);
+                }
+            }
+            oco.cancel_siblings(order_id);
+        }
+        true
+    }
+
+    /// 交易换用新的 blockhash 重新签名后，更新 `Triggered` 状态中的签名，重启后按新的签名确认
+    async fn update_signature(&self, order_id: Uuid, signature: String) {
+        if let Some(order) = self.orders.lock().await.get_mut(&order_id) {
+            if let OrderStatus::Triggered {
+                signature: current, ..
+            } = &mut order.status
+            {
+                *current = signature.clone();
+            }
+        }
+        if let Some(persist) = &self.persist {
+            persist.enqueue(PersistRecord::Triggered(order_id, signature));
+        }
+    }
+
+    /// 记录一批成交：交易签名取自 `Triggered` 状态，订单回到 `Pending` 继续执行剩余批次
+    ///
+    /// `proceeds` 为这一批扣税后至少得到的输出数量，`tax` 为这一批收取的税收，均累计到订单上。
+    async fn record_fill(&self, order_id: Uuid, amount: u64, proceeds: u64, tax: TokenAmount) {
+        if let Some(order) = self.orders.lock().await.get_mut(&order_id) {
+            if let OrderStatus::Triggered { signature, .. } = &order.status {
+                order.fill_signatures.push(signature.clone());
+            }
+            order.filled_amount += amount;
+            order.out_amount = order.out_amount.saturating_add(proceeds);
+            order.tax_amount = order.tax_amount.saturating_add(tax.raw);
+            order.tax_mint = Some(tax.mint.to_string());
+            order.status = OrderStatus::Pending;
+        }
+    }
+
+    /// 记录一次完整成交；重复订单还有剩余次数时按 [`Order::rearm`] 重新挂单并推送本次成交
+    async fn complete_fill(&self, order_id: Uuid, proceeds: u64) -> Rearm {
+        let mut orders = self.orders.lock().await;
+        let Some(order) = orders.get_mut(&order_id) else {
+            return Rearm::Done;
+        };
+        order.fills_completed += 1;
+        order.fills_remaining = order.fills_remaining.saturating_sub(1);
+        if order.fills_remaining == 0 {
+            return Rearm::Done;
+        }
+        // 两次成交之间订单为 Pending，可以撤单
+        if order.status != OrderStatus::Pending {
+            return Rearm::Canceled;
+        }
+        let signature = order.fill_signatures.last().cloned().unwrap_or_default();
+        self.events.publish(OrderEvent::new(
+            order,
+            OrderEventKind::RepeatFilled {
+                signature: signature.clone(),
+                fills_completed: order.fills_completed,
+                fills_remaining: order.fills_remaining,
+            },
+        ));
+        order.rearm(proceeds);
+        if let Some(persist) = &self.persist {
+            persist.enqueue(PersistRecord::RepeatFilled(order.clone(), signature));
+        }
+        metrics().orders_filled.inc();
+        Rearm::Next(Box::new(order.clone()))
+    }
+
+    /// 记录触发后未执行的原因，供查询订单时展示
+    async fn record_rejection(&self, order_id: Uuid, reason: String) {
+        if let Some(order) = self.orders.lock().await.get_mut(&order_id) {
+            order.last_rejection = Some(reason);
+        }
+    }
+
+    /// 记录一次因成交价格偏离触发价格而放弃的执行，累计次数并推送 `DeviationRejected` 事件
+    async fn record_deviation_rejection(&self, order_id: Uuid, error: &LimitOrderError) {
+        let LimitOrderError::TriggerDeviationTooHigh {
+            trigger_price,
+            execution_price,
+            deviation_bps,
+            max_bps,
+        } = *error
+        else {
+            return;
+        };
+        metrics().deviation_rejections.inc();
+        if let Some(order) = self.orders.lock().await.get_mut(&order_id) {
+            order.deviation_rejections += 1;
+            order.last_rejection = Some(error.to_string());
+            self.events.publish(OrderEvent::new(
+                order,
+                OrderEventKind::DeviationRejected {
+                    trigger_price,
+                    execution_price,
+                    deviation_bps,
+                    max_bps,
+                },
+            ));
+        }
+    }
+
+    /// 按 `trigger_on` 所指的价格换算两笔数量的成交价格，代币精度或所需的 USD 价格不可用时返回 None
+    async fn execution_price(
+        &self,
+        trigger_on: TriggerOn,
+        input: TokenAmount,
+        output: TokenAmount,
+    ) -> Option<f64> {
+        let input_decimals = self.quotes.decimals(&input.mint).await.ok()?;
+        let output_decimals = self.quotes.decimals(&output.mint).await.ok()?;
+        let usd = |mint: Pubkey| self.prices.get(&mint.to_string()).map(|quote| quote.price);
+        execution_price(
+            trigger_on,
+            (input.raw, input_decimals),
+            (output.raw, output_decimals),
+            usd(input.mint),
+            usd(output.mint),
+        )
+    }
+
+    /// 更新订单状态
+    async fn set_status(&self, order_id: Uuid, status: OrderStatus) {
+        if let Some(order) = self.orders.lock().await.get_mut(&order_id) {
+            order.status = status;
+        }
+    }
+
+    /// 发送交换交易前写入执行意图，返回意图的 attempt_id；未配置数据库时返回 None
+    ///
+    /// 写入失败时不能发送，否则进程在发送后退出时无法判断交易是否已上链。
+    async fn record_intent(&self, order_id: Uuid, swap: &SignedSwap) -> Result<Option<String>> {
+        let Some(db) = &self.db else {
+            return Ok(None);
+        };
+        let intent = ExecutionIntent::new(
+            order_id,
+            swap.signature().to_string(),
+            swap.blockhash.blockhash.to_string(),
+            swap.blockhash.last_valid_block_height,
+        );
+        let attempt_id = intent.attempt_id.clone();
+        record_intent(db, intent)
+            .await
+            .map_err(|e| anyhow!("写入执行日志失败，不发送交易 {}", e))?;
+        Ok(Some(attempt_id))
+    }
+
+    /// 在审计日志中记录一次发送，`resigned` 为 true 表示 blockhash 失效后重新签名的发送
+    fn audit_submission(
+        &self,
+        order_id: Uuid,
+        swap: &SignedSwap,
+        attempt_id: Option<&str>,
+        resigned: bool,
+    ) {
+        self.audit.append_event(
+            order_id,
+            "submission",
+            json!({
+                "signature": swap.signature().to_string(),
+                "attempt_id": attempt_id,
+                "bundle": swap.use_bundle,
+                "private": swap.private,
+                "blockhash": swap.blockhash.blockhash.to_string(),
+                "resigned": resigned,
+            }),
+        );
+    }
+
+    /// 构建交换交易使用的客户端与收税配置
+    fn swap_context(&self) -> SwapContext<'_> {
+        SwapContext {
+            jup: &self.jup,
+            rpc: &self.rpc,
+            jito: &self.jito,
+            blockhashes: &self.blockhashes,
+            bundle: self.bundle,
+            tax_account: self.tax_account,
+            tax_side: self.tax_side,
+        }
+    }
+
+    /// 在审计日志中记录执行错误，`stage` 为出错的步骤：build（报价、模拟）、intent（执行日志）或 submit
+    fn audit_error(
+        &self,
+        order_id: Uuid,
+        stage: &str,
+        signature: Option<&str>,
+        error: &anyhow::Error,
+    ) {
+        self.audit.append_event(
+            order_id,
+            "error",
+            json!({
+                "stage": stage,
+                "signature": signature,
+                "error": format!("{:#}", error),
+            }),
+        );
+    }
+
+    /// 交易已确认或确定不会上链后将执行意图标记为已解决，失败时意图留到下次启动时核对
+    async fn resolve_intent(&self, attempt_id: Option<String>) {
+        let (Some(db), Some(attempt_id)) = (&self.db, attempt_id) else {
+            return;
+        };
+        if let Err(e) = resolve_intent(db, attempt_id.clone()).await {
+            println!("标记执行意图 {} 失败 {:?}", attempt_id, e);
+        }
+    }
+
+    /// 记录最近一次成交的实际结果，订单结束时随事件和回调发送
+    async fn set_last_fill(&self, order_id: Uuid, fill: FillReport) {
+        if let Some(order) = self.orders.lock().await.get_mut(&order_id) {
+            order.last_fill = Some(fill);
+        }
+    }
+
+    /// 记录最近一次成交的交易签名、bundle id 和报价
+    async fn set_fill(&self, order_id: Uuid, outcome: SwapOutcome) {
+        if let Some(order) = self.orders.lock().await.get_mut(&order_id) {
+            order.fill = Some(outcome);
+        }
+    }
+
+    /// 触发后的执行超过 `post_trigger_deadline_ms`，放弃本次执行
+    ///
+    /// 已发送的交易先确认结果：上链且执行成功时返回这笔交易扣税后至少得到的输出数量和税收，按成交处理。
+    /// 否则订单回到 `Pending`，记录超时次数和原因并推送 `ExecutionTimedOut` 事件，返回超时原因。
+    async fn abandon_execution(
+        &self,
+        order: &Order,
+        in_flight: Option<InFlightSwap>,
+    ) -> std::result::Result<(u64, TokenAmount), String> {
+        let deadline_ms = order.post_trigger_deadline_ms.unwrap_or_default();
+        let signature = in_flight.as_ref().map(|swap| swap.signature.to_string());
+        if let Some(swap) = in_flight {
+            let (min_proceeds, tax) = (swap.min_proceeds, swap.tax);
+            let outcome = swap.outcome.clone();
+            if self.settle_abandoned(swap).await {
+                println!(
+                    "订单 {:?} 执行超时，但交易 {:?} 已上链，记为成交",
+                    order.order_id, signature
+                );
+                self.set_fill(order.order_id, outcome).await;
+                return Ok((min_proceeds, tax));
+            }
+        }
+        let reason = match &signature {
+            Some(signature) => format!(
+                "触发后 {} ms 内未完成执行，交易 {} 未上链",
+                deadline_ms, signature
+            ),
+            None => format!("触发后 {} ms 内未完成执行，未发送交易", deadline_ms),
+        };
+        println!("订单 {:?} {}", order.order_id, reason);
+        metrics().execution_timeouts.inc();
+        if let Some(current) = self.orders.lock().await.get_mut(&order.order_id) {
+            // 超时时可能已经标记为 Triggered
+            if matches!(current.status, OrderStatus::Triggered { .. }) {
+                current.status = OrderStatus::Pending;
+            }
+            current.execution_timeouts += 1;
+            current.last_rejection = Some(reason.clone());
+            self.events.publish(OrderEvent::new(
+                current,
+                OrderEventKind::ExecutionTimedOut {
+                    reason: reason.clone(),
+                    signature,
+                    rearmed: order.rearm_after_timeout,
+                },
+            ));
+        }
+        Err(reason)
+    }
+
+    /// 发送返回错误（超时、连接断开等）后确认交易是否上链，之后才能重试或回到监控
+    ///
+    /// 交易上链且执行成功时记录成交结果，返回这笔交易扣税后至少得到的输出数量和税收，不再重新报价；
+    /// 否则订单回到 `Pending`，返回 None。
+    async fn settle_failed_submission(
+        &self,
+        order: &Order,
+        swap: InFlightSwap,
+    ) -> Option<(u64, TokenAmount)> {
+        let (min_proceeds, tax) = (swap.min_proceeds, swap.tax);
+        let outcome = swap.outcome.clone();
+        let signature = swap.signature;
+        if self.settle_abandoned(swap).await {
+            println!(
+                "订单 {:?} 发送交易 {} 时返回错误，但交易已上链，记为成交",
+                order.order_id, signature
+            );
+            self.set_fill(order.order_id, outcome).await;
+            return Some((min_proceeds, tax));
+        }
+        self.set_status(order.order_id, OrderStatus::Pending).await;
+        None
+    }
+
+    /// 等到放弃或发送失败的交易确定是否上链，见 [`settle_signature`]；结果确定后执行意图标记为已解决
+    async fn settle_abandoned(&self, swap: InFlightSwap) -> bool {
+        let landed = settle_signature(
+            self.rpc.as_ref(),
+            &swap.signature,
+            swap.last_valid_block_height,
+        )
+        .await;
+        self.resolve_intent(swap.attempt).await;
+        landed
+    }
+}
+
+/// 订单进入终态后写入完整快照
+///
+/// 启用持久化时数据库是终态订单的唯一来源，订单随即从订单表中移除，内存只保留活跃订单；
+/// 未启用持久化时订单留在订单表中。
+fn finalize_order(
+    orders: &mut HashMap<Uuid, Order>,
+    persist: Option<&PersistQueue>,
+    order_id: Uuid,
+) {
+    let Some(persist) = persist else {
+        return;
+    };
+    if let Some(order) = orders.remove(&order_id) {
+        persist.enqueue(PersistRecord::Finalized(order, now_ms()));
+    }
+}
+
+/// 限价单缺少触发方向时在订单表中记为失败并写入终态，返回失败原因
+///
+/// 要求指定 `trigger_condition` 之前下的限价单永远不会触发，恢复后会一直占用活跃订单数上限。
+fn fail_missing_trigger_condition(
+    orders: &mut HashMap<Uuid, Order>,
+    persist: Option<&PersistQueue>,
+    order: &Order,
+) -> Option<String> {
+    if order.kind != OrderKind::Limit || order.trigger_condition.is_some() {
+        return None;
+    }
+    let reason = "限价单缺少 trigger_condition，无法判断触发方向，请重新下单".to_string();
+    let mut order = order.clone();
+    order.status = OrderStatus::Failed(reason.clone());
+    let order_id = order.order_id;
+    orders.insert(order_id, order);
+    finalize_order(orders, persist, order_id);
+    Some(reason)
+}
+
+/// 订单任务正常结束的方式
+enum OrderOutcome {
+    Filled,
+    Canceled,
+    /// 非托管订单的签名交易在触发前过期，或发送后失效前未上链
+    Expired,
+    /// 停机时暂停，携带恢复订单所需的信息
+    Suspended(ResumeState),
+    /// 分批执行的订单放弃了剩余批次，已成交的部分记录在订单中
+    PartiallyFilled,
+    /// 执行超时且不再重新监控，携带超时原因
+    Unfilled(String),
+    /// 订单到达 `expires_at_ms` 时仍在等待触发
+    Lapsed,
+}
+
+/// 让订单任务与订单的有效期竞争，`expiry` 为过期时间和订单任务的撤单信号
+///
+/// 到期时只有仍在等待触发的订单会过期：先在订单表中把状态改为 `Expired`，订单任务之后无法再标记触发，
+/// 再通知任务退出并等待它结束。到期时已经触发的订单继续执行直到结束，恰好在到期时成交的订单按成交处理；
+/// 分批执行已成交部分批次的订单以 `PartiallyFilled` 结束。
+async fn run_until_expiry<F: Future<Output = Result<OrderOutcome>>>(
+    task: F,
+    expiry: Option<(u64, CancelHandle)>,
+    orders: &Mutex<HashMap<Uuid, Order>>,
+    order_id: Uuid,
+) -> Result<OrderOutcome> {
+    let Some((expires_at_ms, cancel)) = expiry else {
+        return task.await;
+    };
+    tokio::pin!(task);
+    let remaining = Duration::from_millis(expires_at_ms.saturating_sub(now_ms()));
+    tokio::select! {
+        result = &mut task => return result,
+        _ = tokio::time::sleep(remaining) => {}
+    }
+    let expired = match orders.lock().await.get_mut(&order_id) {
+        Some(order) if order.status == OrderStatus::Pending => {
+            order.status = OrderStatus::Expired;
+            true
+        }
+        _ => false,
+    };
+    if !expired {
+        return task.await;
+    }
+    cancel.cancel();
+    match task.await? {
+        OrderOutcome::Canceled => Ok(OrderOutcome::Lapsed),
+        outcome => Ok(outcome),
+    }
+}
+
+/// 执行日志中一笔订单未解决的执行意图的核对结果，见 [`OrderBook::reconcile_intents`]
+enum IntentStatus {
+    /// 交易已上链，携带交易签名
+    Landed(String),
+    /// 交易确定不会上链，可以重新监控
+    Dropped,
+    /// 无法确定交易是否上链，携带交易签名
+    Unknown(String),
+}
+
+/// 单次 `getSignatureStatuses` 请求最多查询的签名数
+const MAX_SIGNATURE_STATUSES: usize = 256;
+
+/// 执行超时后确认交易结果时查询签名状态的间隔
+const SETTLE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// 等到交易确定是否上链：交易确认时返回是否执行成功，blockhash 过期后仍查不到交易时返回 false
+///
+/// 先查询区块高度再查询签名状态，查不到交易且查询前的区块高度已超过有效高度时交易不会再上链；
+/// 查询失败或交易尚未确认时继续等待。
+async fn settle_signature(
+    rpc: &dyn SolanaRpc,
+    signature: &Signature,
+    last_valid_block_height: u64,
+) -> bool {
+    loop {
+        let height = rpc.get_block_height().await.ok();
+        match (rpc.signature_status(signature).await, height) {
+            (Ok(SignatureStatus::Confirmed { succeeded }), _) => return succeeded,
+            (Ok(SignatureStatus::Unknown), Some(height)) if height > last_valid_block_height => {
+                return false
+            }
+            _ => {}
+        }
+        tokio::time::sleep(SETTLE_POLL_INTERVAL).await;
+    }
+}
+
+/// 等到非托管订单的交易确定是否上链：交易确认时返回是否执行成功，交易失效后仍查不到时返回 None
+///
+/// 与 [`settle_signature`] 相同，先检查交易是否失效再查询签名状态：使用 blockhash 的交易按区块高度判断，
+/// 使用 nonce 的交易在 nonce 已被推进时失效。查询失败或交易尚未确认时继续等待。
+///
+/// 使用 nonce 的交易不会自行过期，提供 `nonce_authority` 时由 authority 支付手续费推进 nonce，
+/// 交易尚未上链时随即失效；推进失败时下一轮重试。
+async fn settle_signed_transaction(
+    rpc: &dyn SolanaRpc,
+    tx: &VersionedTransaction,
+    lifetime: SignedTxLifetime,
+    nonce_authority: Option<&Keypair>,
+) -> Option<bool> {
+    let signature = tx.signatures[0];
+    loop {
+        let expired = match lifetime {
+            SignedTxLifetime::Blockhash {
+                last_valid_block_height,
+            } => rpc
+                .get_block_height()
+                .await
+                .ok()
+                .map(|height| height > last_valid_block_height),
+            SignedTxLifetime::Nonce { nonce_account } => get_nonce(rpc, &nonce_account)
+                .await
+                .ok()
+                .map(|nonce| nonce != *tx.message.recent_blockhash()),
+        };
+        match (rpc.signature_status(&signature).await, expired) {
+            (Ok(SignatureStatus::Confirmed { succeeded }), _) => return Some(succeeded),
+            (Ok(SignatureStatus::Unknown), Some(true)) => return None,
+            (Ok(SignatureStatus::Unknown), Some(false)) => {
+                if let (Some(authority), SignedTxLifetime::Nonce { nonce_account }) =
+                    (nonce_authority, lifetime)
+                {
+                    if let Err(e) = advance_nonce(rpc, authority, &nonce_account, authority).await {
+                        println!("推进 nonce 账户 {} 失败 {:#}", nonce_account, e);
+                    }
+                }
+            }
+            _ => {}
+        }
+        tokio::time::sleep(SETTLE_POLL_INTERVAL).await;
+    }
+}
+
+/// 已签名并准备发送的交换交易，发送返回错误或执行超时后据此确认交易是否上链，
+/// 见 [`OrderContext::settle_failed_submission`] 和 [`OrderContext::abandon_execution`]
+struct InFlightSwap {
+    signature: Signature,
+    last_valid_block_height: u64,
+    /// 执行意图的 attempt_id，未配置数据库时为 None
+    attempt: Option<String>,
+    min_proceeds: u64,
+    tax: TokenAmount,
+    /// 交易上链时记录在订单上的结果，超时前未确认时不知道是否以 bundle 上链，bundle id 为 None
+    outcome: SwapOutcome,
+}
+
+impl InFlightSwap {
+    fn new(swap: &SignedSwap, attempt: Option<String>) -> InFlightSwap {
+        InFlightSwap {
+            signature: swap.signature(),
+            last_valid_block_height: swap.blockhash.last_valid_block_height,
+            attempt,
+            min_proceeds: swap.min_proceeds,
+            tax: swap.tax,
+            outcome: swap.outcome(None),
+        }
+    }
+}
+
+/// 订单一次完整成交后的去向，见 [`OrderContext::complete_fill`]
+enum Rearm {
+    /// 没有剩余次数，订单成交
+    Done,
+    /// 订单已被取消，不再重新挂单
+    Canceled,
+    /// 按新的参数继续监控
+    Next(Box<Order>),
+}
+
+/// 按订单的 `trigger_on` 订阅并计算触发价格
+///
+/// 超过 `max_age` 的价格（价格源长时间未更新）不会用于触发，等待下一轮刷新。
+struct TriggerFeed {
+    trigger_on: TriggerOn,
+    source: FeedSource,
+    max_age: Duration,
+}
+
+enum FeedSource {
+    /// 价格缓存中两个代币的 USD 价格
+    Cache {
+        input: PriceSubscription,
+        output: PriceSubscription,
+    },
+    /// 按订单数量定期询价
+    Executable(ExecutableFeed),
+}
+
+impl TriggerFeed {
+    fn subscribe(ctx: &OrderContext, order: &Order) -> Result<TriggerFeed> {
+        let source = match order.trigger_on {
+            TriggerOn::ExecutablePrice => {
+                let input_mint: Pubkey = order.input_mint.parse()?;
+                let output_mint: Pubkey = order.output_mint.parse()?;
+                let amount_mint = match order.swap_mode {
+                    SwapMode::ExactIn => input_mint,
+                    SwapMode::ExactOut => output_mint,
+                };
+                // 分批执行的订单按单批数量询价
+                let parts = order.split_parts.unwrap_or(1).max(1);
+                let amount = TokenAmount::new(amount_mint, split_amount(order.amount, parts, 0));
+                let mut config = ctx.quotes.config();
+                // 订单的询价间隔不低于报价共享时长，更短的间隔只会读到同一个缓存报价
+                if let Some(ms) = order.poll_interval_ms {
+                    config.interval = config.interval.max(Duration::from_millis(ms));
+                }
+                FeedSource::Executable(ExecutableFeed {
+                    quotes: ctx.quotes.clone(),
+                    input_mint,
+                    output_mint,
+                    amount,
+                    slippage_bps: order.slippage_bps,
+                    swap_mode: order.swap_mode,
+                    delay: config.interval,
+                    config,
+                    next_at: Instant::now(),
+                    last: None,
+                })
+            }
+            _ => FeedSource::Cache {
+                input: ctx.prices.subscribe(&order.input_mint),
+                output: ctx.prices.subscribe(&order.output_mint),
+            },
+        };
+        Ok(TriggerFeed {
+            trigger_on: order.trigger_on,
+            source,
+            max_age: ctx.price_max_age,
+        })
+    }
+
+    /// 等待下一轮价格刷新；两个代币的价格由同一次批量请求得到，比值不会混用不同轮次的价格
+    async fn next(&mut self) -> Result<f64> {
+        loop {
+            let price = match &mut self.source {
+                FeedSource::Executable(feed) => Some(feed.next().await),
+                FeedSource::Cache { input, output } => match self.trigger_on {
+                    TriggerOn::OutputUsd => output.next().await?.fresh_price(self.max_age),
+                    TriggerOn::Ratio => {
+                        let input_usd = input.next().await?;
+                        match output.lookup()? {
+                            Some(output_usd) => ratio(input_usd, output_usd, self.max_age)?,
+                            None => None,
+                        }
+                    }
+                    _ => input.next().await?.fresh_price(self.max_age),
+                },
+            };
+            if let Some(price) = price {
+                return Ok(price);
+            }
+        }
+    }
+
+    /// 按当前价格与触发价格的距离调整下一次询价的时间，只影响按成交价格触发的订单；
+    /// 价格缓存由所有订单共享，按 `PRICE_POLL_INTERVAL_MS` 统一刷新
+    fn pace(&mut self, trigger_price: Option<f64>, now_price: f64) {
+        if let FeedSource::Executable(feed) = &mut self.source {
+            feed.pace(trigger_price, now_price);
+        }
+    }
+
+    /// 当前缓存中的价格，价格已过期时返回 None
+    fn latest(&self) -> Result<Option<f64>> {
+        match &self.source {
+            FeedSource::Executable(feed) => Ok(feed
+                .last
+                .filter(|(quoted_at, _)| quoted_at.elapsed() <= self.max_age)
+                .map(|(_, price)| price)),
+            FeedSource::Cache { input, output } => match self.trigger_on {
+                TriggerOn::OutputUsd => Ok(output.latest()?.fresh_price(self.max_age)),
+                TriggerOn::Ratio => ratio(input.latest()?, output.latest()?, self.max_age),
+                _ => Ok(input.latest()?.fresh_price(self.max_age)),
+            },
+        }
+    }
+}
+
+fn ratio(input_usd: PriceQuote, output_usd: PriceQuote, max_age: Duration) -> Result<Option<f64>> {
+    match (
+        input_usd.fresh_price(max_age),
+        output_usd.fresh_price(max_age),
+    ) {
+        (Some(input_usd), Some(output_usd)) => Ok(Some(price_ratio(input_usd, output_usd)?)),
+        _ => Ok(None),
+    }
+}
+
+/// 按订单数量定期向 Jupiter 询价，见 [`QuoteFeed`]
+struct ExecutableFeed {
+    quotes: Arc<QuoteFeed>,
+    input_mint: Pubkey,
+    output_mint: Pubkey,
+    amount: TokenAmount,
+    slippage_bps: Bps,
+    swap_mode: SwapMode,
+    /// 询价间隔的上下限
+    config: QuoteFeedConfig,
+    /// 当前的询价间隔，见 [`next_poll_interval`]
+    delay: Duration,
+    next_at: Instant,
+    last: Option<(Instant, f64)>,
+}
+
+impl ExecutableFeed {
+    /// 等待下一次询价，询价失败时在下一个间隔重试
+    async fn next(&mut self) -> f64 {
+        loop {
+            tokio::time::sleep_until(self.next_at.into()).await;
+            self.next_at = Instant::now() + self.delay;
+            match self
+                .quotes
+                .price(
+                    self.input_mint,
+                    self.output_mint,
+                    self.amount,
+                    self.slippage_bps,
+                    self.swap_mode,
+                )
+                .await
+            {
+                Ok(price) => {
+                    self.last = Some((Instant::now(), price));
+                    return price;
+                }
+                Err(e) => println!("询价失败 {:?}", e),
+            }
+        }
+    }
+
+    /// 价格远离触发价格时放慢询价，接近时加快；触发价格未知时按最短间隔询价
+    fn pace(&mut self, trigger_price: Option<f64>, now_price: f64) {
+        self.delay = match trigger_price {
+            Some(trigger_price) => next_poll_interval(now_price, trigger_price, &self.config),
+            None => self.config.interval,
+        };
+        if let Some((quoted_at, _)) = self.last {
+            self.next_at = quoted_at + self.delay;
+        }
+    }
+}
+
+/// 当前价格是否按 `condition` 的方向达到或越过触发价格，价格在两次轮询之间越过触发价格时同样触发
+pub fn price_triggered(condition: TriggerCondition, until_price: f32, now_price: f64) -> bool {
+    match condition {
+        TriggerCondition::Above => now_price >= until_price as f64,
+        TriggerCondition::Below => now_price <= until_price as f64,
+    }
+}
+
+/// 监控价格并在触发后执行交易，触发条件由订单类型决定，见 [`should_trigger`]
+///
+/// 限价、止盈和止损订单按固定价格触发，等待 [`TriggerDispatcher`] 唤醒；跟踪止损和按成交价格触发的订单逐轮判断。
+///
+/// 交易失败时按重试策略重新获取价格、重新报价并重建交易；若价格已不再满足条件则回到监控状态。
+/// 撤单信号在等待价格、交易执行和退避等待期间都会被及时响应。
+///
+/// 设置了 `split_parts` 的订单分成 N 批依次执行，每批使用新一轮的价格，
+/// 价格偏离触发价格超过订单滑点或收到撤单时放弃剩余批次，订单状态为 `PartiallyFilled`。
+///
+/// 设置了 `repeat_count` 的订单成交后按 [`Order::rearm`] 重新挂单并继续监控，每次成交有各自的交易签名；
+/// 撤单后不再重新挂单。
+async fn _order(
+    ctx: OrderContext,
+    user_keypair: Arc<Keypair>,
+    mut order: Order,
+    mut cancel: Receiver<()>,
+) -> Result<OrderOutcome> {
+    let user_signer = LocalKeypairSigner::from(user_keypair.clone());
+    let mut trigger_state = TriggerState::new(order.price, order.trigger_condition);
+    let mut input_mint: Pubkey = order.input_mint.parse()?;
+    let mut output_mint: Pubkey = order.output_mint.parse()?;
+    let mut pin = route_pin(order.route_token.as_deref(), order.pin_fallback)?;
+    let parts = order.split_parts.unwrap_or(1).max(1);
+    // 从快照恢复的订单从上次的进度继续
+    let mut filled_parts = order.fill_signatures.len() as u32;
+    let mut filled_amount = order.filled_amount;
+    // 停机时以加密的私钥保存订单，重启后继续执行
+    let suspend = || -> Result<OrderOutcome> {
+        Ok(OrderOutcome::Suspended(ResumeState::Custodial {
+            encrypt_pk: encrypt(Zeroizing::new(user_keypair.to_base58_string()).as_bytes())?,
+        }))
+    };
+    // 已有部分成交时撤单只放弃剩余批次
+    let stop = |filled_amount: u64| {
+        if filled_amount > 0 {
+            OrderOutcome::PartiallyFilled
+        } else {
+            OrderOutcome::Canceled
+        }
+    };
+    // 固定触发价格的订单也保持订阅，价格缓存继续轮询这两个代币，执行期间的重试和分批使用订阅的价格
+    let mut price_feed = TriggerFeed::subscribe(&ctx, &order)?;
+    let mut last_price_event = None;
+    let mut trigger_round = 0;
+    'monitor: loop {
+        let now_price = match TriggerLevel::of(&order) {
+            // 固定触发价格的订单由分发器在价格越过触发价格时唤醒，不再逐轮判断
+            Some(level) => {
+                let trigger = tokio::select! {
+                    _ = &mut cancel => return Ok(stop(filled_amount)),
+                    _ = ctx.shutdown_requested() => return suspend(),
+                    trigger = ctx.dispatcher.wait(&level, order.order_id, &order.owner, trigger_round) => trigger?,
+                };
+                trigger_round = trigger.round;
+                println!("now price {:?}", trigger.price);
+                trigger.price
+            }
+            None => {
+                // 价格缓存按轮询间隔刷新，这里等待下一轮价格
+                let now_price = tokio::select! {
+                    _ = &mut cancel => return Ok(stop(filled_amount)),
+                    _ = ctx.shutdown_requested() => return suspend(),
+                    price = price_feed.next() => price?,
+                };
+                println!("now price {:?}", now_price);
+                ctx.publish_price(&order, now_price, &mut last_price_event);
+                if !should_trigger(order.kind, &mut trigger_state, now_price) {
+                    price_feed.pace(trigger_state.trigger_price(order.kind), now_price);
+                    continue;
+                }
+                now_price
+            }
+        };
+        // 分批执行时后续批次以触发时的价格为基准
+        let trigger_price = now_price;
+        loop {
+            // ExactOut 时订单数量以输出代币计价
+            let amount_mint = match order.swap_mode {
+                SwapMode::ExactIn => input_mint,
+                SwapMode::ExactOut => output_mint,
+            };
+            let chunk = split_amount(order.amount, parts, filled_parts);
+            let amount = TokenAmount::new(amount_mint, chunk);
+            // 每一批从开始执行起计算执行时限，报价、重试等待和交易确认都计入
+            let deadline = order
+                .post_trigger_deadline_ms
+                .map(|ms| Instant::now() + Duration::from_millis(ms));
+            let mut attempt = 0;
+            let proceeds;
+            let tax;
+            loop {
+                // 暂停期间不发送交易，恢复后按新一轮价格重新判断
+                if ctx.pause.is_paused(&order.order_id) {
+                    println!("订单 {:?} 已触发，执行已暂停，等待恢复", order.order_id);
+                    tokio::select! {
+                        _ = &mut cancel => return Ok(stop(filled_amount)),
+                        _ = ctx.shutdown_requested() => return suspend(),
+                        _ = ctx.pause.resumed(order.order_id) => continue 'monitor,
+                    }
+                }
+                let mut in_flight = None;
+                let result = tokio::select! {
+                    _ = &mut cancel => return Ok(stop(filled_amount)),
+                    // 停机信号不会打断交易，已经开始的交易执行完再退出；
+                    // 排队等待执行许可期间仍可撤单
+                    res = within_deadline(deadline, async {
+                        let _permit = ctx.swap_permits.acquire().await?;
+                        execute_swap(
+                            &ctx,
+                            &user_signer,
+                            &order,
+                            trigger_price,
+                            &order_swap_params(
+                                &order,
+                                input_mint,
+                                output_mint,
+                                amount,
+                                pin.as_ref(),
+                            ),
+                            &mut in_flight,
+                        )
+                        .await
+                    }) => res,
+                };
+                let e = match result {
+                    Some(Ok(Some((min_proceeds, swap_tax)))) => {
+                        proceeds = min_proceeds;
+                        tax = swap_tax;
+                        break;
+                    }
+                    Some(Ok(None)) => return Ok(stop(filled_amount)),
+                    // 发送返回错误的交易仍可能上链，确认结果前不能重新报价
+                    Some(Err(e)) => match in_flight.take() {
+                        Some(swap) => match ctx.settle_failed_submission(&order, swap).await {
+                            Some((min_proceeds, swap_tax)) => {
+                                proceeds = min_proceeds;
+                                tax = swap_tax;
+                                break;
+                            }
+                            None => e,
+                        },
+                        None => e,
+                    },
+                    // 超过执行时限，不再报价或重试；已发送的交易上链时照常记为成交
+                    None => match ctx.abandon_execution(&order, in_flight.take()).await {
+                        Ok((min_proceeds, swap_tax)) => {
+                            proceeds = min_proceeds;
+                            tax = swap_tax;
+                            break;
+                        }
+                        Err(_) if order.rearm_after_timeout => continue 'monitor,
+                        Err(_) if filled_amount > 0 => return Ok(OrderOutcome::PartiallyFilled),
+                        Err(reason) => return Ok(OrderOutcome::Unfilled(reason)),
+                    },
+                };
+                // 报价偏离触发价格过大、低于最低输出、价格影响过大或私有发送失败不算失败，不消耗重试次数，回到监控等待价格
+                if let Some(e @ LimitOrderError::TriggerDeviationTooHigh { .. }) =
+                    e.downcast_ref::<LimitOrderError>()
+                {
+                    println!("{}，继续监控", e);
+                    ctx.record_deviation_rejection(order.order_id, e).await;
+                    continue 'monitor;
+                }
+                if let Some(
+                    LimitOrderError::MinOutNotMet { .. }
+                    | LimitOrderError::PriceImpactTooHigh { .. }
+                    | LimitOrderError::PrivateExecutionFailed(_),
+                ) = e.downcast_ref::<LimitOrderError>()
+                {
+                    println!("{}，继续监控", e);
+                    ctx.record_rejection(order.order_id, e.to_string()).await;
+                    continue 'monitor;
+                }
+                if attempt >= ctx.retry_policy.max_retries {
+                    let e = e.context(format!("交易失败，已重试 {} 次", attempt));
+                    if filled_amount > 0 {
+                        println!("订单 {:?} 剩余批次放弃执行 {:?}", order.order_id, e);
+                        return Ok(OrderOutcome::PartiallyFilled);
+                    }
+                    return Err(e);
+                }
+                attempt += 1;
+                let failed_slot = ctx.blockhashes.refresh_slot(ctx.rpc.as_ref()).await.ok();
+                println!(
+                    "交易失败 {:?}，slot {:?}，第 {} 次重试",
+                    e, failed_slot, attempt
+                );
+                let pacing = order.pacing.unwrap_or(ctx.retry_policy.pacing);
+                let slot = tokio::select! {
+                    _ = &mut cancel => return Ok(stop(filled_amount)),
+                    _ = ctx.shutdown_requested() => return suspend(),
+                    slot = within_deadline(deadline, wait_for_next_attempt(
+                        ctx.rpc.as_ref(),
+                        &ctx.blockhashes,
+                        &ctx.retry_policy,
+                        pacing,
+                        attempt,
+                        failed_slot,
+                    )) => match slot {
+                        Some(slot) => slot,
+                        // 等待重试期间超过执行时限，下一轮直接按超时处理
+                        None => continue,
+                    },
+                };
+                println!("第 {} 次重试，节奏 {:?}，slot {:?}", attempt, pacing, slot);
+                // 重新确认价格条件，价格过期或不再满足时回到监控
+                let Some(now_price) = price_feed.latest()? else {
+                    println!("价格已过期，继续监控");
+                    continue 'monitor;
+                };
+                if !should_trigger(order.kind, &mut trigger_state, now_price) {
+                    println!("价格 {:?} 已不满足触发条件，继续监控", now_price);
+                    continue 'monitor;
+                }
+            }
+
+            filled_parts += 1;
+            filled_amount += chunk;
+            ctx.record_fill(order.order_id, chunk, proceeds, tax).await;
+            if filled_parts >= parts {
+                match ctx.complete_fill(order.order_id, proceeds).await {
+                    Rearm::Done => return Ok(OrderOutcome::Filled),
+                    Rearm::Canceled => return Ok(OrderOutcome::Canceled),
+                    Rearm::Next(next) => order = *next,
+                }
+                println!(
+                    "订单 {:?} 第 {} 次成交，重新挂单价格 {}，数量 {}，剩余 {} 次",
+                    order.order_id,
+                    order.fills_completed,
+                    order.price,
+                    order.amount,
+                    order.fills_remaining
+                );
+                trigger_state = TriggerState::new(order.price, order.trigger_condition);
+                input_mint = order.input_mint.parse()?;
+                output_mint = order.output_mint.parse()?;
+                // 固定路由只对第一次成交有效
+                pin = None;
+                filled_parts = 0;
+                filled_amount = 0;
+                price_feed = TriggerFeed::subscribe(&ctx, &order)?;
+                continue 'monitor;
+            }
+            println!(
+                "订单 {:?} 第 {}/{} 批成交，累计 {}",
+                order.order_id, filled_parts, parts, filled_amount
+            );
+
+            // 下一批使用下一轮价格，偏离触发价格超过滑点时放弃剩余批次
+            let now_price = tokio::select! {
+                _ = &mut cancel => return Ok(stop(filled_amount)),
+                _ = ctx.shutdown_requested() => return suspend(),
+                price = price_feed.next() => price?,
+            };
+            if !within_tolerance(trigger_price, now_price, order.slippage_bps) {
+                println!(
+                    "价格 {:?} 偏离触发价格超过 {}，放弃剩余批次",
+                    now_price, order.slippage_bps
+                );
+                return Ok(OrderOutcome::PartiallyFilled);
+            }
+        }
+    }
+}
+
+/// 在执行时限内等待 `future`，没有时限时一直等待；超过时限时返回 None，时限已过时不再开始执行
+async fn within_deadline<F: Future>(deadline: Option<Instant>, future: F) -> Option<F::Output> {
+    match deadline {
+        Some(deadline) if Instant::now() >= deadline => None,
+        Some(deadline) => tokio::time::timeout_at(tokio::time::Instant::from_std(deadline), future)
+            .await
+            .ok(),
+        None => Some(future.await),
+    }
+}
+
+/// 订单一批交换的参数，`amount` 为本批的数量
+fn order_swap_params<'a>(
+    order: &'a Order,
+    input_mint: Pubkey,
+    output_mint: Pubkey,
+    amount: TokenAmount,
+    pin: Option<&'a RoutePin>,
+) -> SwapParams<'a> {
+    SwapParams {
+        input_mint,
+        output_mint,
+        amount,
+        swap_mode: order.swap_mode,
+        slippage_bps: order.slippage_bps,
+        min_out_amount: min_out_for_chunk(order, amount.raw),
+        max_price_impact_bps: order.max_price_impact_bps,
+        options: &order.swap_options,
+        pin,
+    }
+}
+
+/// 本批数量对应的最低输出，按本批占订单数量的比例向上取整
+fn min_out_for_chunk(order: &Order, chunk: u64) -> Option<u64> {
+    let min_out = order.min_out_amount? as u128;
+    if order.amount == 0 {
+        return Some(min_out as u64);
+    }
+    Some((min_out * chunk as u128).div_ceil(order.amount as u128) as u64)
+}
+
+/// 分批执行时第 `index` 批（从 0 开始）的数量，余数计入最后一批
+fn split_amount(total: u64, parts: u32, index: u32) -> u64 {
+    let parts = parts.max(1) as u64;
+    let base = total / parts;
+    if index as u64 + 1 >= parts {
+        total - base * (parts - 1)
+    } else {
+        base
+    }
+}
+
+/// 当前价格相对触发价格的偏离是否在 `tolerance` 以内
+fn within_tolerance(trigger_price: f64, now_price: f64, tolerance: Bps) -> bool {
+    if trigger_price <= 0.0 {
+        return false;
+    }
+    (now_price - trigger_price).abs() / trigger_price * Bps::MAX as f64 <= tolerance.get() as f64
 }
 
-impl OrderBook {
-    pub fn new() -> Result<OrderBook> {
-        let rpc = Arc::new(RpcClient::new(env::var("RPC_URL")?));
-        let http = Arc::new(Client::new());
-        let jito = Arc::new(JitoJsonRpcSDK::new(&env::var("JITO_URL")?, None));
-        let jup = Arc::new(JupiterSwapApiClient::new(env::var("JUP_URL")?));
-        let tax_account = env::var("TAX_ACCOUNT")?.parse::<Pubkey>()?; // 替换为实际税收账户
-        let tax_bps = env::var("TAX_BPS")?.parse::<u16>()?; // 替换为实际税收账户
-
-        Ok(OrderBook {
-            orders: HashMap::new(),
-            tokens: HashMap::new(),
-            tax_account,
-            tax_bps,
-            cancel_tasks: HashMap::new(),
-            http,
-            jito,
-            jup,
-            rpc,
-        })
+/// 订单指定的优先费；未指定且配置了 `PRIORITY_FEE_PERCENTILE` 时按最近区块的优先费取值
+///
+/// 查询失败时不设置优先费，交易仍然发送。
+async fn compute_unit_price(
+    rpc: &dyn SolanaRpc,
+    priority_fee_micro_lamports: Option<u64>,
+    percentile: Option<u8>,
+) -> Option<u64> {
+    if priority_fee_micro_lamports.is_some() {
+        return priority_fee_micro_lamports;
     }
-    // 开单
-    pub async fn place_order(
-        &mut self,
-        keypair_str: String,
-        input_mint: String,
-        output_mint: String,
-        price: f32,
-        amount: u64,
-        slippage_bps: u16,
-        tip_amount: Option<u64>,
-    ) -> Result<Uuid> {
-        let order_id = Uuid::new_v4();
-        let order = Order {
-            order_id,
-            price,
-            input_mint,
-            output_mint,
-            amount,
-            slippage_bps,
-            tip_amount,
-        };
+    match recommended_priority_fee(rpc, &[], percentile?).await {
+        Ok(fee) => Some(fee),
+        Err(e) => {
+            println!("获取推荐优先费失败 {:?}", e);
+            None
+        }
+    }
+}
 
-        self.orders.insert(order_id.clone(), order.clone());
+/// 代币每个最小单位折合多少 lamports，按缓存中的 USD 价格换算，用于计算不含 SOL 的交换的名义价值
+async fn lamports_per_unit(prices: &PriceCache, quotes: &QuoteFeed, mint: &Pubkey) -> Option<f64> {
+    let input = prices.get(&mint.to_string())?;
+    let sol = prices.get(&SOL.to_string())?;
+    let decimals = quotes.decimals(mint).await.ok()?;
+    if sol.price <= 0.0 {
+        return None;
+    }
+    Some(input.price / sol.price * 1e9 / 10f64.powi(decimals as i32))
+}
 
-        let (tx, rx) = oneshot::channel();
-        self.cancel_tasks.insert(order_id.clone(), tx);
+/// 按当前的税收策略解析 `user` 交换 `amount` 适用的税率，名义价值按价格缓存换算，无法换算时不按分档
+async fn resolve_tax_bps(
+    policy: &StdRwLock<TaxPolicy>,
+    prices: &PriceCache,
+    quotes: &QuoteFeed,
+    user: Option<&Pubkey>,
+    amount: TokenAmount,
+) -> Bps {
+    let notional = amount_notional_lamports(
+        amount,
+        lamports_per_unit(prices, quotes, &amount.mint).await,
+    );
+    policy.read().unwrap().resolve(user, notional)
+}
 
-        let rpc = self.rpc.clone();
-        let http = self.http.clone();
-        let jito = self.jito.clone();
-        let jup = self.jup.clone();
-        let tax_account = self.tax_account;
-        let tax_bps = self.tax_bps;
-        let slippage_bps = order.slippage_bps;
-        let keypair = Keypair::from_base58_string(&keypair_str);
-        tokio::spawn(async move {
-            let result = tokio::select! {
-                _ = rx => {
-                    Err(anyhow!("Task canceled"))
-                }
-                res = _order(
-                    rpc,
-                    jito,
-                    jup,
-                    &keypair,
-                    tax_account,
-                    tax_bps,
-                    slippage_bps,
-                    tip_amount,
-                    http,
+/// 构建并发送一次交换交易，成交时返回扣税后至少得到的输出数量和本次收取的税收
+///
+/// 发送前把订单标记为 `Triggered`；订单已被取消时不发送，返回 None。发送前失败时订单回到 `Pending` 以便重试；
+/// 发送返回错误时交易仍可能上链，订单保持 `Triggered`，由调用方按 `in_flight` 通过
+/// [`OrderContext::settle_failed_submission`] 确认结果后再决定是否重试。
+/// 配置了最大触发偏离时，报价隐含的成交价格相对 `trigger_price` 偏离过大则不发送，返回
+/// [`LimitOrderError::TriggerDeviationTooHigh`]。
+/// 名义价值超过私有发送阈值或订单要求私有发送时以 bundle 发送，bundle 失败后改用公开 RPC 成交时发布警告事件。
+/// blockhash 在构建后即将过期时先重新签名再记录签名；发送因 blockhash 不存在被拒绝时重新签名并更新签名后再发送一次。
+/// 配置数据库时发送前写入执行意图，确认后标记为已解决；发送失败时交易仍可能上链，意图保留到下次启动时由
+/// [`OrderBook::restore_snapshot`] 核对。
+async fn execute_swap(
+    ctx: &OrderContext,
+    user_signer: &dyn TransactionSigner,
+    order: &Order,
+    trigger_price: f64,
+    params: &SwapParams<'_>,
+    in_flight: &mut Option<InFlightSwap>,
+) -> Result<Option<(u64, TokenAmount)>> {
+    let triggered_at = Instant::now();
+    // 触发时才解析税率，运行时替换的税收策略只影响之后触发的订单
+    let tax_bps = resolve_tax_bps(
+        &ctx.tax_policy,
+        &ctx.prices,
+        &ctx.quotes,
+        Some(&user_signer.pubkey()),
+        params.amount,
+    )
+    .await;
+    let built = build_signed_swap(
+        &ctx.swap_context(),
+        user_signer,
+        tax_bps,
+        params,
+        ExecutionOptions {
+            private: PrivateExecution {
+                config: ctx.private_execution,
+                force: order.force_private_execution,
+                input_lamports_per_unit: lamports_per_unit(
+                    &ctx.prices,
+                    &ctx.quotes,
+                    &params.input_mint,
+                )
+                .await,
+            },
+            tip_amount: order.tip_amount,
+            compute_unit_limit: None,
+            compute_unit_price: compute_unit_price(
+                &ctx.rpc,
+                order.priority_fee_micro_lamports,
+                ctx.priority_fee_percentile,
+            )
+            .await,
+            // 托管订单在触发时才构建交易，使用最新的 blockhash 即可
+            nonce: None,
+            skip_simulation: order.skip_simulation,
+        },
+    )
+    .await;
+    // 报价失败、模拟失败等都在构建交易时返回
+    let mut swap = match built {
+        Ok(swap) => swap,
+        Err(e) => {
+            ctx.audit_error(order.order_id, "build", None, &e);
+            return Err(e);
+        }
+    };
+    // 价格源过期或被操纵时，触发价格与实际报价可能相差很多
+    if let Some(max_bps) = order
+        .max_trigger_deviation_bps
+        .or(ctx.max_trigger_deviation_bps)
+    {
+        let quoted = ctx
+            .execution_price(
+                order.trigger_on,
+                swap.quote.in_amount,
+                swap.quote.out_amount,
+            )
+            .await;
+        match quoted {
+            Some(quoted) => check_trigger_deviation(trigger_price, quoted, max_bps)?,
+            None => println!(
+                "订单 {:?} 无法换算报价的成交价格，跳过偏离检查",
+                order.order_id
+            ),
+        }
+    }
+    ctx.audit.append_event(
+        order.order_id,
+        "quote",
+        json!({
+            "input_mint": swap.quote.in_amount.mint.to_string(),
+            "output_mint": swap.quote.out_amount.mint.to_string(),
+            "in_amount": swap.quote.in_amount.raw,
+            "out_amount": swap.quote.out_amount.raw,
+            "min_out_amount": swap.quote.min_out_amount.raw,
+            "price_impact_pct": swap.quote.price_impact_pct,
+            "route": swap.quote.route_labels,
+            "tax_bps": tax_bps.get(),
+            "tax_amount": swap.tax.raw,
+            "tax_mint": swap.tax.mint.to_string(),
+            "min_proceeds": swap.min_proceeds,
+        }),
+    );
+    swap.renew_blockhash(&ctx.rpc, &ctx.blockhashes, user_signer)
+        .await?;
+    let mut signature = swap.signature().to_string();
+    let (min_proceeds, tax) = (swap.min_proceeds, swap.tax);
+    if !ctx
+        .mark_triggered(order.order_id, signature.clone(), Some(tax_bps))
+        .await
+    {
+        return Ok(None);
+    }
+    let mut attempt = match ctx.record_intent(order.order_id, &swap).await {
+        Ok(attempt) => attempt,
+        Err(e) => {
+            ctx.audit_error(order.order_id, "intent", Some(&signature), &e);
+            ctx.set_status(order.order_id, OrderStatus::Pending).await;
+            return Err(e);
+        }
+    };
+    *in_flight = Some(InFlightSwap::new(&swap, attempt.clone()));
+    ctx.audit_submission(order.order_id, &swap, attempt.as_deref(), false);
+    let mut submitted = submit_signed_swap(&ctx.rpc, &ctx.jito, &swap, ctx.bundle).await;
+    if matches!(&submitted, Err(e) if is_blockhash_not_found(e)) {
+        println!(
+            "订单 {:?} 的交易 blockhash 已失效，重新签名后发送",
+            order.order_id
+        );
+        submitted = async {
+            swap.resign(ctx.blockhashes.refresh(&ctx.rpc).await?, user_signer)
+                .await?;
+            // 原交易因 blockhash 不存在被拒绝，不会上链
+            ctx.resolve_intent(attempt.take()).await;
+            attempt = ctx.record_intent(order.order_id, &swap).await?;
+            *in_flight = Some(InFlightSwap::new(&swap, attempt.clone()));
+            signature = swap.signature().to_string();
+            ctx.update_signature(order.order_id, signature.clone())
+                .await;
+            ctx.audit_submission(order.order_id, &swap, attempt.as_deref(), true);
+            submit_signed_swap(&ctx.rpc, &ctx.jito, &swap, ctx.bundle).await
+        }
+        .await;
+    }
+    match submitted {
+        Ok(bundle_id) => {
+            ctx.resolve_intent(attempt).await;
+            ctx.audit.append_event(
+                order.order_id,
+                "submitted",
+                json!({ "signature": signature, "bundle_id": bundle_id }),
+            );
+            metrics()
+                .trigger_to_confirm_seconds
+                .observe(triggered_at.elapsed().as_secs_f64());
+            if swap.private && bundle_id.is_none() {
+                ctx.events.publish(OrderEvent::new(
                     order,
+                    OrderEventKind::Warning {
+                        message: format!("私有发送失败，交易 {} 已改用公开 RPC 发送", signature),
+                    },
+                ));
+            }
+            ctx.set_fill(order.order_id, swap.outcome(bundle_id.clone()))
+                .await;
+            if bundle_id.is_some() {
+                ctx.set_status(
+                    order.order_id,
+                    OrderStatus::Triggered {
+                        signature,
+                        bundle_id,
+                    },
                 )
-                => res,
-            };
-            if let Err(_) = result {
-                println!("Deal task failed or was canceled");
+                .await;
             }
-        });
-
-        Ok(order_id)
+            let fill = swap_fill_report(ctx, order, &user_signer.pubkey(), &swap).await;
+            ctx.set_last_fill(order.order_id, fill).await;
+            Ok(Some((min_proceeds, tax)))
+        }
+        Err(e) => {
+            // 订单保持 Triggered，期间不能撤单，由调用方确认交易是否上链
+            ctx.audit_error(order.order_id, "submit", Some(&signature), &e);
+            Err(e)
+        }
     }
+}
 
-    // 取消订单
-    pub async fn cancel_order(&mut self, order_id: Uuid) -> Result<()> {
-        if let Some(tx) = self.cancel_tasks.remove(&order_id) {
-            let _ = tx.send(());
-            println!("订单 {:?} 成功取消", order_id);
-            Ok(())
-        } else {
-            Err(anyhow!("订单未找到"))
+/// 读取已确认的交换交易中的实际成交数量，读取不到时按报价估算，见 [`swap_fill_amounts`]
+async fn swap_fill_report(
+    ctx: &OrderContext,
+    order: &Order,
+    user: &Pubkey,
+    swap: &SignedSwap,
+) -> FillReport {
+    let (input_mint, output_mint) = (swap.quote.in_amount.mint, swap.quote.out_amount.mint);
+    let changes = match fetch_parsed_transaction(ctx.rpc.as_ref(), &swap.signature()).await {
+        Ok(tx) => parse_balance_changes(
+            &tx,
+            user,
+            &input_mint,
+            &output_mint,
+            swap.merged_tip_lamports,
+        ),
+        Err(e) => {
+            println!("订单 {:?} 读取成交交易失败 {:?}", order.order_id, e);
+            None
+        }
+    };
+    let (in_amount, out_amount, estimated) = swap_fill_amounts(changes, &swap.quote, swap.tax);
+    fill_report(
+        ctx,
+        order,
+        input_mint,
+        output_mint,
+        in_amount,
+        out_amount,
+        estimated,
+    )
+    .await
+}
+
+/// 读取已确认的非托管交易中的实际成交数量，没有报价可供估算，读取不到时返回 None
+///
+/// 交易由客户端构建，以输入代币收取的税收无法从余额变化中区分，计入输入数量；tip 按订单的 `tip_amount` 扣除。
+async fn signed_fill_report(
+    ctx: &OrderContext,
+    order: &Order,
+    tx: &VersionedTransaction,
+) -> Option<FillReport> {
+    let user = *tx.message.static_account_keys().first()?;
+    let input_mint = order.input_mint.parse().ok()?;
+    let output_mint = order.output_mint.parse().ok()?;
+    let parsed = match fetch_parsed_transaction(ctx.rpc.as_ref(), &tx.signatures[0]).await {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            println!("订单 {:?} 读取成交交易失败 {:?}", order.order_id, e);
+            return None;
         }
+    };
+    let (in_amount, out_amount) = parse_balance_changes(
+        &parsed,
+        &user,
+        &input_mint,
+        &output_mint,
+        order.tip_amount.map_or(0, Lamports::get),
+    )?;
+    Some(
+        fill_report(
+            ctx,
+            order,
+            input_mint,
+            output_mint,
+            in_amount,
+            out_amount,
+            false,
+        )
+        .await,
+    )
+}
+
+/// 按订单的 `trigger_on` 换算成交价格，并计算相对触发价格的偏离
+///
+/// 比值和成交价格模式直接取输出数量 / 输入数量（界面单位）；USD 价格模式按价格缓存中另一侧代币的 USD 价格换算，
+/// 取不到时成交价格为 None。跟踪止损的触发价格随行情移动，不计算偏离。
+async fn fill_report(
+    ctx: &OrderContext,
+    order: &Order,
+    input_mint: Pubkey,
+    output_mint: Pubkey,
+    in_amount: u64,
+    out_amount: u64,
+    estimated: bool,
+) -> FillReport {
+    let realized_price = ctx
+        .execution_price(
+            order.trigger_on,
+            TokenAmount::new(input_mint, in_amount),
+            TokenAmount::new(output_mint, out_amount),
+        )
+        .await;
+    let slippage_from_trigger_bps = match order.kind {
+        OrderKind::TrailingStop { .. } => None,
+        _ if order.price > 0.0 => realized_price.map(|price| {
+            ((price - order.price as f64) / order.price as f64 * Bps::MAX as f64).round() as i64
+        }),
+        _ => None,
+    };
+    FillReport {
+        in_amount,
+        out_amount,
+        realized_price,
+        slippage_from_trigger_bps,
+        estimated,
     }
 }
 
-async fn _order(
-    rpc: Arc<RpcClient>,
-    jito: Arc<jito_sdk_rust::JitoJsonRpcSDK>,
-    jup: Arc<JupiterSwapApiClient>,
-    user_keypair: &Keypair,
-    tax_account: Pubkey,
-    tax_bps: u16,
-    slippage_bps: u16,
-    tip_amount: Option<u64>,
-    http: Arc<Client>,
+/// 监控价格并在触发后发送客户端签名的交易
+///
+/// 交易已由客户端签名，无法重新报价或重建，因此不做重试。使用 blockhash 的交易在每轮价格刷新时检查是否过期；
+/// 使用 nonce 的交易在触发时确认 nonce 未被推进。失效后结束任务并要求客户端重新签名。
+/// 发送后等到交易确认才记为成交，交易失效前未上链时同样要求重新签名，上链但执行失败时订单失败。
+/// 发送返回错误时使用 nonce 的交易不会自行过期，先由 `nonce_authority` 推进 nonce 使之失效，再确认结果。
+async fn _signed_order(
+    ctx: OrderContext,
     order: Order,
-) -> Result<()> {
-    let until_price = order.price;
-    let input_mint: Pubkey = order.input_mint.parse()?;
-    let output_mint: Pubkey = order.output_mint.parse()?;
-    let amount = order.amount;
+    tx: VersionedTransaction,
+    lifetime: SignedTxLifetime,
+    nonce_authority: Option<Arc<Keypair>>,
+    mut cancel: Receiver<()>,
+) -> Result<OrderOutcome> {
+    let mut trigger_state = TriggerState::new(order.price, order.trigger_condition);
+    let mut price_feed = TriggerFeed::subscribe(&ctx, &order)?;
+    let mut last_price_event = None;
     loop {
-        let now_price = get_price(http.clone(), &order.input_mint).await?;
-        println!("now price {:?}", now_price);
-        if (now_price - until_price).abs() < 0.001 {
+        let now_price = tokio::select! {
+            _ = &mut cancel => return Ok(OrderOutcome::Canceled),
+            _ = ctx.shutdown_requested() => {
+                return Ok(OrderOutcome::Suspended(ResumeState::Signed {
+                    transaction: encode_transaction(&tx)?,
+                    lifetime,
+                }))
+            }
+            price = price_feed.next() => price?,
+        };
+        ctx.publish_price(&order, now_price, &mut last_price_event);
+        if let SignedTxLifetime::Blockhash {
+            last_valid_block_height,
+        } = lifetime
+        {
+            if ctx.rpc.get_block_height().await? > last_valid_block_height {
+                return Ok(OrderOutcome::Expired);
+            }
+        }
+        if should_trigger(order.kind, &mut trigger_state, now_price) {
+            if ctx.pause.is_paused(&order.order_id) {
+                println!("订单 {:?} 已触发，执行已暂停，等待恢复", order.order_id);
+                tokio::select! {
+                    _ = &mut cancel => return Ok(OrderOutcome::Canceled),
+                    _ = ctx.shutdown_requested() => {
+                        return Ok(OrderOutcome::Suspended(ResumeState::Signed {
+                            transaction: encode_transaction(&tx)?,
+                            lifetime,
+                        }))
+                    }
+                    _ = ctx.pause.resumed(order.order_id) => continue,
+                }
+            }
+            if let SignedTxLifetime::Nonce { nonce_account } = lifetime {
+                if get_nonce(&ctx.rpc, &nonce_account).await? != *tx.message.recent_blockhash() {
+                    return Ok(OrderOutcome::Expired);
+                }
+            }
+            if !ctx
+                .mark_triggered(order.order_id, tx.signatures[0].to_string(), order.tax_bps)
+                .await
+            {
+                return Ok(OrderOutcome::Canceled);
+            }
+            let sent = tokio::select! {
+                _ = &mut cancel => return Ok(OrderOutcome::Canceled),
+                res = ctx.rpc.send(&tx) => res,
+            };
+            let signature = tx.signatures[0];
+            // 发送返回错误时交易仍可能上链：使用 blockhash 的交易等到过期，使用 nonce 的交易推进 nonce 使之失效
+            let invalidate = match sent {
+                Ok(_) => None,
+                Err(e) => {
+                    println!(
+                        "非托管订单 {:?} 发送交易 {} 返回错误 {:#}，确认是否上链",
+                        order.order_id, signature, e
+                    );
+                    match (lifetime, &nonce_authority) {
+                        (SignedTxLifetime::Nonce { .. }, None) => {
+                            return Err(e.context("未配置 nonce authority，无法使交易失效"))
+                        }
+                        (_, authority) => authority.as_deref(),
+                    }
+                }
+            };
+            match settle_signed_transaction(ctx.rpc.as_ref(), &tx, lifetime, invalidate).await {
+                Some(true) => println!("非托管订单 {:?} 已成交 {:?}", order.order_id, signature),
+                Some(false) => return Err(anyhow!("交易 {} 已上链但执行失败", signature)),
+                None => {
+                    println!(
+                        "非托管订单 {:?} 的交易 {} 失效前未上链",
+                        order.order_id, signature
+                    );
+                    return Ok(OrderOutcome::Expired);
+                }
+            }
+            if let Some(fill) = signed_fill_report(&ctx, &order, &tx).await {
+                ctx.set_last_fill(order.order_id, fill).await;
+            }
+            return Ok(OrderOutcome::Filled);
+        }
+        price_feed.pace(trigger_state.trigger_price(order.kind), now_price);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        sync::atomic::{AtomicU64, Ordering},
+    };
+
+    use async_trait::async_trait;
+    use serde_json::Value;
+    use solana_client::rpc_response::RpcKeyedAccount;
+    use solana_sdk::{
+        account::Account,
+        hash::Hash,
+        message::VersionedMessage,
+        nonce::state::{Data, DurableNonce, State, Versions},
+        system_program,
+    };
+
+    use super::*;
+    use crate::solana::clients::Simulation;
+
+    /// 按脚本应答的 RPC：发送总是返回错误，签名状态依次取 `statuses`，用完后重复最后一个；
+    /// 每次查询区块高度后高度增加 `height_step`。查询任何账户都返回 nonce 账户，
+    /// 存储的 nonce 依次由 `nonces` 中的种子生成，同样重复最后一个
+    struct ScriptedRpc {
+        statuses: StdMutex<VecDeque<Option<SignatureStatus>>>,
+        height: AtomicU64,
+        height_step: u64,
+        nonces: StdMutex<VecDeque<Hash>>,
+        sent: StdMutex<Vec<VersionedTransaction>>,
+    }
+
+    impl ScriptedRpc {
+        /// `None` 表示这一次查询失败
+        fn new(statuses: Vec<Option<SignatureStatus>>, height_step: u64) -> ScriptedRpc {
+            ScriptedRpc {
+                statuses: StdMutex::new(statuses.into()),
+                height: AtomicU64::new(100),
+                height_step,
+                nonces: StdMutex::new(VecDeque::new()),
+                sent: StdMutex::new(vec![]),
+            }
+        }
+
+        fn with_nonces(self, seeds: Vec<Hash>) -> ScriptedRpc {
+            *self.nonces.lock().unwrap() = seeds.into();
+            self
+        }
+
+        /// 已发送（并返回错误）的交易
+        fn sent(&self) -> Vec<VersionedTransaction> {
+            self.sent.lock().unwrap().clone()
+        }
+    }
+
+    /// 存储的 nonce 为 `DurableNonce::from_blockhash(seed)` 的 nonce 账户
+    fn nonce_account(seed: &Hash) -> Account {
+        let state = Versions::new(State::Initialized(Data::new(
+            Pubkey::new_unique(),
+            DurableNonce::from_blockhash(seed),
+            5_000,
+        )));
+        Account {
+            lamports: 1_500_000,
+            data: bincode::serialize(&state).unwrap(),
+            owner: system_program::id(),
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    #[async_trait]
+    impl SolanaRpc for ScriptedRpc {
+        async fn get_latest_blockhash(&self) -> Result<Hash> {
+            Ok(Hash::default())
+        }
+
+        async fn get_latest_blockhash_with_height(&self) -> Result<(Hash, u64)> {
+            Err(anyhow!("不支持"))
+        }
+
+        async fn get_block_height(&self) -> Result<u64> {
+            Ok(self.height.fetch_add(self.height_step, Ordering::SeqCst))
+        }
+
+        async fn get_slot(&self) -> Result<u64> {
+            Err(anyhow!("不支持"))
+        }
+
+        async fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+            let mut nonces = self.nonces.lock().unwrap();
+            let seed = if nonces.len() > 1 {
+                nonces.pop_front()
+            } else {
+                nonces.front().copied()
+            };
+            let seed = seed.ok_or_else(|| anyhow!("不支持"))?;
+            Ok(pubkeys.iter().map(|_| Some(nonce_account(&seed))).collect())
+        }
+
+        async fn simulate(&self, _tx: &VersionedTransaction) -> Result<Simulation> {
+            Err(anyhow!("不支持"))
+        }
+
+        async fn simulate_with_accounts(
+            &self,
+            _tx: &VersionedTransaction,
+            _addresses: &[Pubkey],
+        ) -> Result<Simulation> {
+            Err(anyhow!("不支持"))
+        }
+
+        async fn send(&self, tx: &VersionedTransaction) -> Result<Signature> {
+            self.sent.lock().unwrap().push(tx.clone());
+            Err(anyhow!("发送交易超时"))
+        }
+
+        async fn signature_status(&self, _signature: &Signature) -> Result<SignatureStatus> {
+            let mut statuses = self.statuses.lock().unwrap();
+            let status = if statuses.len() > 1 {
+                statuses.pop_front().flatten()
+            } else {
+                statuses.front().copied().flatten()
+            };
+            status.ok_or_else(|| anyhow!("查询签名状态失败"))
+        }
+
+        async fn get_recent_prioritization_fees(&self, _accounts: &[Pubkey]) -> Result<Vec<u64>> {
+            Err(anyhow!("不支持"))
+        }
+
+        async fn get_parsed_transaction(&self, _signature: &Signature) -> Result<Option<Value>> {
+            Err(anyhow!("不支持"))
+        }
+
+        async fn get_token_accounts_by_owner(
+            &self,
+            _owner: &Pubkey,
+            _token_program: &Pubkey,
+        ) -> Result<Vec<RpcKeyedAccount>> {
+            Err(anyhow!("不支持"))
+        }
+
+        async fn get_minimum_balance_for_rent_exemption(&self, _data_len: usize) -> Result<u64> {
+            Err(anyhow!("不支持"))
+        }
+    }
+
+    fn transaction(signature: Signature) -> VersionedTransaction {
+        VersionedTransaction {
+            signatures: vec![signature],
+            message: VersionedMessage::Legacy(Message::default()),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn send_error_but_signature_lands_settles_as_filled() {
+        let rpc = ScriptedRpc::new(
+            vec![
+                Some(SignatureStatus::Unknown),
+                Some(SignatureStatus::Processed),
+                Some(SignatureStatus::Confirmed { succeeded: true }),
+            ],
+            1,
+        );
+        let signature = Signature::new_unique();
+        assert!(rpc.send(&transaction(signature)).await.is_err());
+        assert!(settle_signature(&rpc, &signature, 1_000).await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn unknown_signature_settles_as_dropped_after_blockhash_expires() {
+        let rpc = ScriptedRpc::new(vec![Some(SignatureStatus::Unknown)], 50);
+        let signature = Signature::new_unique();
+        assert!(rpc.send(&transaction(signature)).await.is_err());
+        assert!(!settle_signature(&rpc, &signature, 300).await);
+        // 高度从 100 开始每次增加 50，超过 300 之前一直等待
+        assert!(rpc.height.load(Ordering::SeqCst) > 300);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failed_transaction_is_not_landed() {
+        let rpc = ScriptedRpc::new(
+            vec![Some(SignatureStatus::Confirmed { succeeded: false })],
+            1,
+        );
+        assert!(!settle_signature(&rpc, &Signature::new_unique(), 1_000).await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn lookup_errors_keep_waiting_even_after_expiry() {
+        // 查询失败时无法判断，即使区块高度已超过有效高度也不能当作未上链
+        let rpc = ScriptedRpc::new(
+            vec![
+                None,
+                None,
+                Some(SignatureStatus::Confirmed { succeeded: true }),
+            ],
+            1_000,
+        );
+        assert!(settle_signature(&rpc, &Signature::new_unique(), 100).await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn signed_transaction_settles_by_blockhash_expiry() {
+        let lifetime = SignedTxLifetime::Blockhash {
+            last_valid_block_height: 300,
+        };
+        let rpc = ScriptedRpc::new(vec![Some(SignatureStatus::Unknown)], 50);
+        let tx = transaction(Signature::new_unique());
+        assert_eq!(
+            settle_signed_transaction(&rpc, &tx, lifetime, None).await,
+            None
+        );
+        assert!(rpc.height.load(Ordering::SeqCst) > 300);
+
+        let rpc = ScriptedRpc::new(
+            vec![
+                Some(SignatureStatus::Processed),
+                Some(SignatureStatus::Confirmed { succeeded: false }),
+            ],
+            1,
+        );
+        assert_eq!(
+            settle_signed_transaction(&rpc, &tx, lifetime, None).await,
+            Some(false)
+        );
+    }
+
+    /// 使用 `seed` 生成的 nonce 签名的交易
+    fn nonce_transaction(seed: &Hash) -> VersionedTransaction {
+        VersionedTransaction {
+            signatures: vec![Signature::new_unique()],
+            message: VersionedMessage::Legacy(Message {
+                recent_blockhash: *DurableNonce::from_blockhash(seed).as_hash(),
+                ..Message::default()
+            }),
+        }
+    }
+
+    /// 发送后 nonce 已被交易本身推进：交易确认即成交，不再推进 nonce
+    #[tokio::test(start_paused = true)]
+    async fn signed_nonce_transaction_settles_when_confirmed() {
+        let seed = Hash::new_unique();
+        let rpc = ScriptedRpc::new(
+            vec![Some(SignatureStatus::Confirmed { succeeded: true })],
+            1,
+        )
+        .with_nonces(vec![Hash::new_unique()]);
+        let lifetime = SignedTxLifetime::Nonce {
+            nonce_account: Pubkey::new_unique(),
+        };
+        let tx = nonce_transaction(&seed);
+        assert_eq!(
+            settle_signed_transaction(&rpc, &tx, lifetime, None).await,
+            Some(true)
+        );
+        assert!(rpc.sent().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn signed_nonce_send_error_but_transaction_lands() {
+        let seed = Hash::new_unique();
+        let authority = Keypair::new();
+        let nonce_account = Pubkey::new_unique();
+        // 第一次查询时交易尚未上链，推进 nonce 的交易发出后交易仍然确认
+        let rpc = ScriptedRpc::new(
+            vec![
+                Some(SignatureStatus::Unknown),
+                Some(SignatureStatus::Confirmed { succeeded: true }),
+            ],
+            1,
+        )
+        .with_nonces(vec![seed, Hash::new_unique()]);
+        let tx = nonce_transaction(&seed);
+        assert!(rpc.send(&tx).await.is_err());
+        let lifetime = SignedTxLifetime::Nonce { nonce_account };
+        assert_eq!(
+            settle_signed_transaction(&rpc, &tx, lifetime, Some(&authority)).await,
+            Some(true)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn signed_nonce_send_error_advances_nonce_before_expiring() {
+        let seed = Hash::new_unique();
+        let authority = Keypair::new();
+        let nonce_account = Pubkey::new_unique();
+        // 推进 nonce 之前 nonce 一直等于交易中的值，之后交易失效
+        let rpc = ScriptedRpc::new(vec![Some(SignatureStatus::Unknown)], 1)
+            .with_nonces(vec![seed, Hash::new_unique()]);
+        let tx = nonce_transaction(&seed);
+        assert!(rpc.send(&tx).await.is_err());
+        let lifetime = SignedTxLifetime::Nonce { nonce_account };
+        assert_eq!(
+            settle_signed_transaction(&rpc, &tx, lifetime, Some(&authority)).await,
+            None
+        );
+
+        let sent = rpc.sent();
+        assert_eq!(sent.len(), 2);
+        let advance = &sent[1];
+        assert_eq!(
+            advance.message.static_account_keys()[0],
+            authority.pubkey(),
+            "由 nonce authority 支付手续费"
+        );
+        let ix = &advance.message.instructions()[0];
+        assert_eq!(
+            advance.message.static_account_keys()[ix.program_id_index as usize],
+            system_program::id()
+        );
+        assert!(advance
+            .message
+            .static_account_keys()
+            .contains(&nonce_account));
+    }
+
+    fn limit_order(price: f32, trigger_condition: Option<TriggerCondition>) -> Order {
+        let mut order: Order = serde_json::from_value(serde_json::json!({
+            "order_id": Uuid::new_v4(),
+            "owner": Pubkey::new_unique().to_string(),
+            "price": price,
+            "input_mint": SOL.to_string(),
+            "output_mint": Pubkey::new_unique().to_string(),
+            "amount": 1_000_000,
+            "slippage_bps": 50,
+        }))
+        .unwrap();
+        order.trigger_condition = trigger_condition;
+        order
+    }
+
+    #[test]
+    fn limit_without_condition_never_triggers() {
+        let mut state = TriggerState::new(10.0, None);
+        for now_price in [9.0, 10.0, 11.0] {
+            assert!(!should_trigger(OrderKind::Limit, &mut state, now_price));
+        }
+        assert_eq!(state.condition, None);
+    }
+
+    #[test]
+    fn legacy_limit_order_fails_on_restore() {
+        let mut orders = HashMap::new();
+        let legacy = limit_order(10.0, None);
+        let reason = fail_missing_trigger_condition(&mut orders, None, &legacy).unwrap();
+        let restored = &orders[&legacy.order_id];
+        assert_eq!(restored.status, OrderStatus::Failed(reason));
+        assert!(restored.status.is_terminal());
+
+        let order = limit_order(10.0, Some(TriggerCondition::Above));
+        assert!(fail_missing_trigger_condition(&mut orders, None, &order).is_none());
+        let mut stop_loss = limit_order(10.0, None);
+        stop_loss.kind = OrderKind::StopLoss;
+        assert!(fail_missing_trigger_condition(&mut orders, None, &stop_loss).is_none());
+        assert_eq!(orders.len(), 1);
+    }
+
+    #[test]
+    fn rearm_keeps_condition() {
+        let mut order = limit_order(10.0, Some(TriggerCondition::Above));
+        order.repeat_count = Some(3);
+        order.reprice_offset_bps = Some(-500);
+
+        // 成交价格高于重新挂单后的价格，仍按原来的方向等待上涨
+        order.rearm(500_000);
+        assert!((order.price - 9.5).abs() < 1e-4);
+        assert_eq!(order.trigger_condition, Some(TriggerCondition::Above));
+        let mut state = TriggerState::new(order.price, order.trigger_condition);
+        assert!(!should_trigger(order.kind, &mut state, 9.0));
+        assert!(should_trigger(order.kind, &mut state, 9.6));
+    }
+
+    #[test]
+    fn compound_rearm_flips_condition() {
+        let mut order = limit_order(2.0, Some(TriggerCondition::Above));
+        order.repeat_count = Some(2);
+        order.compound = true;
+        let input_mint = order.input_mint.clone();
+        order.rearm(300);
+        assert_eq!(order.output_mint, input_mint);
+        assert_eq!(order.amount, 300);
+        assert!((order.price - 0.5).abs() < 1e-6);
+        assert_eq!(order.trigger_condition, Some(TriggerCondition::Below));
+        let mut state = TriggerState::new(order.price, order.trigger_condition);
+        assert!(!should_trigger(order.kind, &mut state, 0.6));
+        assert!(should_trigger(order.kind, &mut state, 0.5));
+    }
+
+    fn running_order(
+        owner: &Pubkey,
+    ) -> (
+        HashMap<Uuid, Order>,
+        HashMap<Uuid, CancelHandle>,
+        Receiver<()>,
+    ) {
+        let mut order = limit_order(10.0, None);
+        order.owner = owner.to_string();
+        let (tx, rx) = oneshot::channel();
+        let cancel_tasks = HashMap::from([(order.order_id, CancelHandle::new(tx))]);
+        (HashMap::from([(order.order_id, order)]), cancel_tasks, rx)
+    }
+
+    #[test]
+    fn owner_can_cancel_with_signature() {
+        let owner = Keypair::new();
+        let (mut orders, mut cancel_tasks, mut rx) = running_order(&owner.pubkey());
+        let order_id = *orders.keys().next().unwrap();
+        let signature = owner
+            .sign_message(order_id.to_string().as_bytes())
+            .to_string();
+
+        verify_cancel_signature(&owner.pubkey(), &order_id, &signature).unwrap();
+        authorize_cancel(orders.get(&order_id), &owner.pubkey()).unwrap();
+        let order = cancel_running_order(&mut orders, &mut cancel_tasks, order_id).unwrap();
+        assert_eq!(order.status, OrderStatus::Canceled);
+        // 订单任务已收到退出信号
+        assert!(rx.try_recv().is_ok());
+        assert!(cancel_tasks.is_empty());
+    }
+
+    #[test]
+    fn wrong_owner_cannot_cancel() {
+        let owner = Keypair::new();
+        let other = Keypair::new();
+        let (orders, cancel_tasks, _rx) = running_order(&owner.pubkey());
+        let order_id = *orders.keys().next().unwrap();
+
+        // 其他钱包的签名不能冒充下单钱包
+        let forged = other
+            .sign_message(order_id.to_string().as_bytes())
+            .to_string();
+        assert!(verify_cancel_signature(&owner.pubkey(), &order_id, &forged).is_err());
+        // 其他钱包即使签名有效也无权撤单
+        verify_cancel_signature(&other.pubkey(), &order_id, &forged).unwrap();
+        assert!(matches!(
+            authorize_cancel(orders.get(&order_id), &other.pubkey()),
+            Err(LimitOrderError::Unauthorized(_))
+        ));
+        assert_eq!(orders[&order_id].status, OrderStatus::Pending);
+        assert!(!cancel_tasks[&order_id].is_spent());
+    }
+
+    #[test]
+    fn malformed_signature_is_rejected() {
+        let owner = Keypair::new();
+        let order_id = Uuid::new_v4();
+        let err =
+            verify_cancel_signature(&owner.pubkey(), &order_id, "not-a-signature").unwrap_err();
+        assert!(err.to_string().contains("格式无效"));
+        // 对其他内容的签名同样不能用于撤单
+        let signature = owner
+            .sign_message(Uuid::new_v4().to_string().as_bytes())
+            .to_string();
+        assert!(verify_cancel_signature(&owner.pubkey(), &order_id, &signature).is_err());
+    }
+
+    #[test]
+    fn cancel_without_running_task_leaves_order_untouched() {
+        let owner = Pubkey::new_unique();
+        let (mut orders, _, _rx) = running_order(&owner);
+        let order_id = *orders.keys().next().unwrap();
+        assert!(cancel_running_order(&mut orders, &mut HashMap::new(), order_id).is_err());
+        assert_eq!(orders[&order_id].status, OrderStatus::Pending);
+    }
+
+    #[test]
+    fn finished_order_is_not_canceled() {
+        let owner = Pubkey::new_unique();
+        let (mut orders, mut cancel_tasks, mut rx) = running_order(&owner);
+        let order_id = *orders.keys().next().unwrap();
+        orders.get_mut(&order_id).unwrap().status = OrderStatus::Filled;
+        assert!(cancel_running_order(&mut orders, &mut cancel_tasks, order_id).is_err());
+        assert_eq!(orders[&order_id].status, OrderStatus::Filled);
+        assert!(rx.try_recv().is_err());
+        assert!(!cancel_tasks[&order_id].is_spent());
+    }
+
+    #[test]
+    fn should_trigger_table() {
+        let trail = OrderKind::TrailingStop {
+            trail_bps: Bps::new(1_000).unwrap(),
+        };
+        // (订单类型, 触发方向, 触发价格, 当前价格, 是否触发)
+        let cases = [
+            (
+                OrderKind::Limit,
+                Some(TriggerCondition::Above),
+                10.0,
+                9.99,
+                false,
+            ),
+            (
+                OrderKind::Limit,
+                Some(TriggerCondition::Above),
+                10.0,
+                10.0,
+                true,
+            ),
+            (
+                OrderKind::Limit,
+                Some(TriggerCondition::Above),
+                10.0,
+                10.01,
+                true,
+            ),
+            (
+                OrderKind::Limit,
+                Some(TriggerCondition::Below),
+                10.0,
+                10.01,
+                false,
+            ),
+            (
+                OrderKind::Limit,
+                Some(TriggerCondition::Below),
+                10.0,
+                10.0,
+                true,
+            ),
+            (
+                OrderKind::Limit,
+                Some(TriggerCondition::Below),
+                10.0,
+                9.99,
+                true,
+            ),
+            // 没有方向的限价单不会触发
+            (OrderKind::Limit, None, 10.0, 10.0, false),
+            (OrderKind::TakeProfit, None, 10.0, 9.99, false),
+            (OrderKind::TakeProfit, None, 10.0, 10.0, true),
+            (OrderKind::TakeProfit, None, 10.0, 12.0, true),
+            (OrderKind::StopLoss, None, 10.0, 10.01, false),
+            (OrderKind::StopLoss, None, 10.0, 10.0, true),
+            (OrderKind::StopLoss, None, 10.0, 8.0, true),
+            // 跟踪止损第一次观察到的价格就是最高价格，不会触发
+            (trail, None, 0.0, 10.0, false),
+        ];
+        for (kind, condition, limit_price, now_price, expected) in cases {
+            let mut state = TriggerState::new(limit_price, condition);
+            assert_eq!(
+                should_trigger(kind, &mut state, now_price),
+                expected,
+                "{:?} {:?} {} @ {}",
+                kind,
+                condition,
+                limit_price,
+                now_price
+            );
+        }
+    }
+
+    #[test]
+    fn trailing_stop_follows_high_water() {
+        let kind = OrderKind::TrailingStop {
+            trail_bps: Bps::new(1_000).unwrap(),
+        };
+        let mut state = TriggerState::new(0.0, None);
+        // (当前价格, 是否触发, 之后的最高价格)
+        let steps = [
+            (100.0, false, 100.0),
+            (95.0, false, 100.0),
+            (120.0, false, 120.0),
+            // 止损价格为 108，等于时不触发
+            (108.0, false, 120.0),
+            (107.9, true, 120.0),
+        ];
+        for (now_price, expected, high_water) in steps {
+            assert_eq!(
+                should_trigger(kind, &mut state, now_price),
+                expected,
+                "{}",
+                now_price
+            );
+            assert_eq!(state.high_water, Some(high_water));
+        }
+        assert_eq!(state.trigger_price(kind), Some(108.0));
+    }
+
+    #[test]
+    fn execution_price_per_trigger_on() {
+        // 卖出 2 SOL（9 位精度）得到 300 USDC（6 位精度），比值为 150
+        let input = (2_000_000_000, 9);
+        let output = (300_000_000, 6);
+        let price = |trigger_on, input_usd, output_usd| {
+            execution_price(trigger_on, input, output, input_usd, output_usd)
+        };
+        assert_eq!(price(TriggerOn::Ratio, None, None), Some(150.0));
+        assert_eq!(price(TriggerOn::ExecutablePrice, None, None), Some(150.0));
+        // 输入代币的 USD 价格按输出代币的 USD 价格换算
+        assert_eq!(price(TriggerOn::InputUsd, None, Some(0.5)), Some(75.0));
+        assert_eq!(price(TriggerOn::InputUsd, Some(150.0), None), None);
+        // 输出代币的 USD 价格按输入代币的 USD 价格换算
+        assert_eq!(price(TriggerOn::OutputUsd, Some(150.0), None), Some(1.0));
+        assert_eq!(price(TriggerOn::OutputUsd, None, Some(1.0)), None);
+        // 数量为 0 时无法换算
+        assert_eq!(
+            execution_price(TriggerOn::Ratio, (0, 9), output, None, None),
+            None
+        );
+        assert_eq!(
+            execution_price(TriggerOn::OutputUsd, input, (0, 6), Some(150.0), None),
+            None
+        );
+    }
+
+    #[test]
+    fn trigger_deviation_is_absolute_bps() {
+        assert_eq!(trigger_deviation_bps(100.0, 100.0), Some(0));
+        assert_eq!(trigger_deviation_bps(100.0, 101.0), Some(100));
+        assert_eq!(trigger_deviation_bps(100.0, 99.0), Some(100));
+        assert_eq!(trigger_deviation_bps(100.0, 150.0), Some(5_000));
+        // 触发价格无效时无法计算
+        assert_eq!(trigger_deviation_bps(0.0, 1.0), None);
+        assert_eq!(trigger_deviation_bps(-1.0, 1.0), None);
+        assert_eq!(trigger_deviation_bps(f64::NAN, 1.0), None);
+        assert_eq!(trigger_deviation_bps(100.0, f64::INFINITY), None);
+    }
+
+    #[test]
+    fn deviation_guard_boundaries() {
+        let max = Bps::new(100).unwrap();
+        // 恰好等于上限时放行，超过 1 个基点时拒绝
+        assert!(check_trigger_deviation(100.0, 101.0, max).is_ok());
+        assert!(check_trigger_deviation(100.0, 99.0, max).is_ok());
+        match check_trigger_deviation(100.0, 101.01, max) {
+            Err(LimitOrderError::TriggerDeviationTooHigh {
+                deviation_bps,
+                max_bps,
+                ..
+            }) => {
+                assert_eq!(deviation_bps, 101);
+                assert_eq!(max_bps, 100);
+            }
+            other => panic!("{:?}", other),
+        }
+
+        // 上限为 0 时只放行与触发价格相同的成交价格
+        assert!(check_trigger_deviation(100.0, 100.0, Bps::ZERO).is_ok());
+        assert!(check_trigger_deviation(100.0, 100.01, Bps::ZERO).is_err());
+
+        // 偏离无法计算（触发价格为 0）时检查不生效
+        assert!(check_trigger_deviation(0.0, 1_000.0, Bps::ZERO).is_ok());
+    }
+
+    /// 不同钱包的订单各自以下单钱包签名并支付手续费，互不混用
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn orders_from_different_wallets_sign_with_their_own_keys() {
+        use crate::{
+            common::utils::BLOCKHASH_EXPIRY_MARGIN,
+            solana::swap::swap_with_tax,
+            testing::{
+                fixed_keypair, MockJito, MockJupiter, MockRpc, SOL_DECIMALS, USDC, USDC_DECIMALS,
+            },
+        };
+
+        let rpc = MockRpc::new();
+        rpc.set_mint(SOL, spl_token::id(), SOL_DECIMALS);
+        rpc.set_mint(USDC, spl_token::id(), USDC_DECIMALS);
+        let jito = MockJito::new(fixed_keypair(9).pubkey());
+        let jup = MockJupiter {
+            price: 150.0,
+            output_decimals: USDC_DECIMALS,
+            lookup_tables: vec![],
+        };
+        let blockhashes = BlockhashProvider::new(BLOCKHASH_EXPIRY_MARGIN);
+        let ctx = SwapContext {
+            jup: &jup,
+            rpc: &rpc,
+            jito: &jito,
+            blockhashes: &blockhashes,
+            bundle: BundleConfig::default(),
+            tax_account: fixed_keypair(3).pubkey(),
+            tax_side: TaxSide::Input,
+        };
+        let swap = SwapParams {
+            input_mint: SOL,
+            output_mint: USDC,
+            amount: TokenAmount::new(SOL, 1_000_000_000),
+            swap_mode: SwapMode::ExactIn,
+            slippage_bps: Bps::new(50).unwrap(),
+            min_out_amount: None,
+            max_price_impact_bps: None,
+            options: &SwapOptions::default(),
+            pin: None,
+        };
+        let tax_policy = TaxPolicy::flat(Bps::new(100).unwrap());
+
+        // 与下单流程相同：从下单请求中的私钥解析出钱包，以该钱包签名
+        let wallets = [fixed_keypair(1), fixed_keypair(2)];
+        let signers: Vec<LocalKeypairSigner> = wallets
+            .iter()
+            .map(|wallet| {
+                let keypair = parse_keypair(&wallet.to_base58_string()).unwrap();
+                LocalKeypairSigner::from(Arc::new(keypair))
+            })
+            .collect();
+        let (first, second) = tokio::join!(
             swap_with_tax(
-                jup,
-                rpc,
-                jito,
-                &user_keypair,
-                tax_account,
-                tax_bps,
-                amount,
-                input_mint,
-                output_mint,
-                slippage_bps,
-                tip_amount,
+                &ctx,
+                &signers[0],
+                &tax_policy,
+                &swap,
+                ExecutionOptions::default()
+            ),
+            swap_with_tax(
+                &ctx,
+                &signers[1],
+                &tax_policy,
+                &swap,
+                ExecutionOptions::default()
+            ),
+        );
+        first.unwrap();
+        second.unwrap();
+
+        let sent = rpc.sent();
+        assert_eq!(sent.len(), 2);
+        let mut payers: Vec<Pubkey> = sent
+            .iter()
+            .map(|tx| {
+                let payer = tx.message.static_account_keys()[0];
+                assert!(tx.signatures[0].verify(payer.as_ref(), &tx.message.serialize()));
+                payer
+            })
+            .collect();
+        payers.sort();
+        let mut owners: Vec<Pubkey> = wallets.iter().map(Signer::pubkey).collect();
+        owners.sort();
+        assert_eq!(payers, owners);
+    }
+
+    /// 使用模拟客户端的订单簿：走完下单、触发和撤单的完整流程
+    #[cfg(feature = "testing")]
+    mod mocked {
+        use super::*;
+        use crate::testing::{
+            fixed_keypair, limit_leg, mock_config, wait_for_order, wallet_secret, MockStack,
+        };
+
+        fn book(stack: &MockStack) -> Mutex<OrderBook> {
+            Mutex::new(stack.order_book(&mock_config()))
+        }
+
+        fn ladder() -> Vec<OrderLeg> {
+            vec![
+                limit_leg(200.0, 1_000_000_000),
+                limit_leg(210.0, 1_000_000_000),
+                limit_leg(220.0, 1_000_000_000),
+            ]
+        }
+
+        async fn order_count(book: &Mutex<OrderBook>) -> usize {
+            book.lock().await.orders.lock().await.len()
+        }
+
+        /// 触发后 500 ms 内必须完成执行的限价单
+        fn deadline_leg(rearm_after_timeout: bool) -> OrderLeg {
+            let mut leg = limit_leg(200.0, 1_000_000_000);
+            leg.post_trigger_deadline_ms = Some(MIN_POST_TRIGGER_DEADLINE_MS);
+            leg.rearm_after_timeout = rearm_after_timeout;
+            leg
+        }
+
+        /// 价格越过触发价格后多等几轮价格轮询，足够订单触发
+        async fn trigger_and_settle(stack: &MockStack) {
+            stack.prices.set_price(SOL, 210.0);
+            tokio::time::sleep(Duration::from_millis(300)).await;
+        }
+
+        /// 同一订单组并发重试时只有一个请求创建订单，两个请求返回同一组订单
+        #[tokio::test]
+        async fn retried_ladder_creates_one_set_of_orders() {
+            let stack = MockStack::new(150.0);
+            let book = book(&stack);
+
+            let (first, second) = tokio::join!(
+                OrderBook::place_order_group(
+                    &book,
+                    wallet_secret(1),
+                    "ladder-1".to_string(),
+                    ladder()
+                ),
+                OrderBook::place_order_group(
+                    &book,
+                    wallet_secret(1),
+                    "ladder-1".to_string(),
+                    ladder()
+                ),
+            );
+            let (first, second) = (first.unwrap(), second.unwrap());
+            assert_eq!(first.group_id, second.group_id);
+            assert_eq!(first.order_ids, second.order_ids);
+            assert_eq!(first.order_ids.len(), 3);
+            assert_eq!(order_count(&book).await, 3);
+
+            // 之后的重试同样返回已创建的订单组
+            let retried = OrderBook::place_order_group(
+                &book,
+                wallet_secret(1),
+                "ladder-1".to_string(),
+                ladder(),
             )
             .await
-            .context("交易失败")?;
-            return Ok(());
+            .unwrap();
+            assert_eq!(retried.order_ids, first.order_ids);
+            assert_eq!(order_count(&book).await, 3);
+        }
+
+        /// 中间一笔订单参数无效时整组都不创建，也不占用幂等键，修正后以同一个键重试可以创建
+        #[tokio::test]
+        async fn invalid_leg_rolls_back_the_whole_group() {
+            let stack = MockStack::new(150.0);
+            let book = book(&stack);
+
+            let mut legs = ladder();
+            legs[1].amount = 0;
+            let err =
+                OrderBook::place_order_group(&book, wallet_secret(1), "ladder-1".to_string(), legs)
+                    .await
+                    .unwrap_err();
+            assert_eq!(err.code(), Some("INVALID_REQUEST"));
+            assert_eq!(order_count(&book).await, 0);
+            assert!(book.lock().await.groups.is_empty());
+
+            let group = OrderBook::place_order_group(
+                &book,
+                wallet_secret(1),
+                "ladder-1".to_string(),
+                ladder(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(group.order_ids.len(), 3);
+            assert_eq!(order_count(&book).await, 3);
+        }
+
+        /// 暂停期间触发的订单不发送交易，恢复后按新一轮价格只执行一次
+        #[tokio::test]
+        async fn trigger_while_paused_executes_once_after_resume() {
+            let stack = MockStack::new(150.0);
+            let book = book(&stack);
+            let order_id =
+                OrderBook::place_order(&book, wallet_secret(1), limit_leg(200.0, 1_000_000_000))
+                    .await
+                    .unwrap();
+            book.lock()
+                .await
+                .set_order_paused(order_id, true)
+                .await
+                .unwrap();
+
+            trigger_and_settle(&stack).await;
+            assert!(stack.rpc.sent().is_empty());
+
+            book.lock()
+                .await
+                .set_order_paused(order_id, false)
+                .await
+                .unwrap();
+            wait_for_order(&book, order_id, |status| *status == OrderStatus::Filled).await;
+            assert_eq!(stack.rpc.sent().len(), 1);
+        }
+
+        /// 暂停期间触发后撤单，订单直接结束，不会发送交易
+        #[tokio::test]
+        async fn cancel_while_paused_never_executes() {
+            let stack = MockStack::new(150.0);
+            let book = book(&stack);
+            let order_id =
+                OrderBook::place_order(&book, wallet_secret(1), limit_leg(200.0, 1_000_000_000))
+                    .await
+                    .unwrap();
+            book.lock()
+                .await
+                .set_order_paused(order_id, true)
+                .await
+                .unwrap();
+
+            trigger_and_settle(&stack).await;
+            book.lock()
+                .await
+                .cancel_order(order_id, &fixed_keypair(1).pubkey())
+                .await
+                .unwrap();
+            wait_for_order(&book, order_id, |status| *status == OrderStatus::Canceled).await;
+
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            assert!(stack.rpc.sent().is_empty());
+        }
+
+        /// 执行超时且设置了 `rearm_after_timeout` 的订单回到监控，执行路径恢复后照常成交
+        #[tokio::test]
+        async fn timed_out_order_rearms_and_keeps_monitoring() {
+            let stack = MockStack::new(150.0);
+            let book = book(&stack);
+            stack.rpc.delay_simulation(Duration::from_secs(2));
+            let order_id = OrderBook::place_order(&book, wallet_secret(1), deadline_leg(true))
+                .await
+                .unwrap();
+
+            stack.prices.set_price(SOL, 210.0);
+            let timed_out = async {
+                loop {
+                    let order = book.lock().await.orders.lock().await[&order_id].clone();
+                    if order.execution_timeouts > 0 {
+                        return order;
+                    }
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+            };
+            let order = tokio::time::timeout(Duration::from_secs(10), timed_out)
+                .await
+                .expect("执行路径变慢后订单应超时");
+            assert_eq!(order.status, OrderStatus::Pending);
+            assert!(stack.rpc.sent().is_empty());
+
+            stack.rpc.delay_simulation(Duration::ZERO);
+            wait_for_order(&book, order_id, |status| *status == OrderStatus::Filled).await;
+            assert_eq!(stack.rpc.sent().len(), 1);
+        }
+
+        /// 执行超时且未设置 `rearm_after_timeout` 的订单以 `Unfilled` 结束，不发送交易
+        #[tokio::test]
+        async fn timed_out_order_without_rearm_ends_unfilled() {
+            let stack = MockStack::new(150.0);
+            let book = book(&stack);
+            stack.rpc.delay_simulation(Duration::from_secs(2));
+            let order_id = OrderBook::place_order(&book, wallet_secret(1), deadline_leg(false))
+                .await
+                .unwrap();
+
+            stack.prices.set_price(SOL, 210.0);
+            let order = wait_for_order(&book, order_id, |status| {
+                matches!(status, OrderStatus::Unfilled(_))
+            })
+            .await;
+            let OrderStatus::Unfilled(reason) = &order.status else {
+                unreachable!()
+            };
+            assert!(reason.contains("未发送交易"), "{}", reason);
+            assert_eq!(order.execution_timeouts, 1);
+            assert!(stack.rpc.sent().is_empty());
+        }
+
+        /// 第一批成交后撤单，放弃剩余批次，订单以 `PartiallyFilled` 结束并保留第一批的成交
+        #[tokio::test]
+        async fn cancel_after_first_chunk_keeps_the_partial_fill() {
+            let stack = MockStack::new(150.0);
+            let book = book(&stack);
+            let mut leg = limit_leg(200.0, 1_000_000_000);
+            leg.split_parts = Some(2);
+            let order_id = OrderBook::place_order(&book, wallet_secret(1), leg)
+                .await
+                .unwrap();
+
+            stack.prices.set_price(SOL, 210.0);
+            tokio::time::timeout(Duration::from_secs(10), async {
+                while stack.rpc.sent().is_empty() {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+            })
+            .await
+            .expect("价格越过触发价格后订单应发送第一批交易");
+            // 第二批停在模拟执行，撤单时仍未发送
+            stack.rpc.delay_simulation(Duration::from_secs(30));
+            tokio::time::timeout(Duration::from_secs(10), async {
+                while book.lock().await.orders.lock().await[&order_id].filled_amount == 0 {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+            })
+            .await
+            .expect("第一批交易应记为成交");
+
+            book.lock()
+                .await
+                .cancel_order(order_id, &fixed_keypair(1).pubkey())
+                .await
+                .unwrap();
+            let order = wait_for_order(&book, order_id, |status| {
+                matches!(status, OrderStatus::PartiallyFilled { .. })
+            })
+            .await;
+            let OrderStatus::PartiallyFilled {
+                filled_amount,
+                signatures,
+            } = order.status
+            else {
+                unreachable!()
+            };
+            assert_eq!(filled_amount, 500_000_000);
+            assert_eq!(signatures.len(), 1);
+            assert_eq!(stack.rpc.sent().len(), 1);
+        }
+
+        /// 撤单后订单不再占用活跃订单数，同一钱包可以再次下单
+        #[tokio::test]
+        async fn canceling_releases_the_active_order_limit() {
+            let stack = MockStack::new(150.0);
+            let mut config = mock_config();
+            config.limits.max_orders_per_user = Some(1);
+            let book = Mutex::new(stack.order_book(&config));
+            let order_id =
+                OrderBook::place_order(&book, wallet_secret(1), limit_leg(200.0, 1_000_000_000))
+                    .await
+                    .unwrap();
+
+            let err =
+                OrderBook::place_order(&book, wallet_secret(1), limit_leg(210.0, 1_000_000_000))
+                    .await
+                    .unwrap_err();
+            assert!(err.to_string().contains("超过上限"), "{}", err);
+            // 上限按钱包计算，其他钱包不受影响
+            OrderBook::place_order(&book, wallet_secret(2), limit_leg(210.0, 1_000_000_000))
+                .await
+                .unwrap();
+
+            book.lock()
+                .await
+                .cancel_order(order_id, &fixed_keypair(1).pubkey())
+                .await
+                .unwrap();
+            OrderBook::place_order(&book, wallet_secret(1), limit_leg(210.0, 1_000_000_000))
+                .await
+                .unwrap();
         }
-        tokio::time::sleep(Duration::from_millis(800)).await;
     }
 }
@@ -0,0 +1,172 @@
+use std::fmt;
+
+use anyhow::{anyhow, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+
+/// 基点，1 bps = 0.01%，取值范围 0..=10_000
+///
+/// 反序列化时同样会校验取值范围，因此 API 层拿到的 `Bps` 一定是合法的。
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(try_from = "u16", into = "u16")]
+pub struct Bps(u16);
+
+impl Bps {
+    /// 100%
+    pub const MAX: u16 = 10_000;
+    pub const ZERO: Bps = Bps(0);
+
+    pub fn new(bps: u16) -> Result<Bps> {
+        if bps > Self::MAX {
+            return Err(anyhow!("基点 {} 超过上限 {}", bps, Self::MAX));
+        }
+        Ok(Bps(bps))
+    }
+
+    pub fn get(self) -> u16 {
+        self.0
+    }
+
+    /// 计算 `amount` 的基点比例部分，向下取整，中间结果使用 u128 避免溢出
+    pub fn apply(self, amount: u64) -> u64 {
+        (amount as u128 * self.0 as u128 / Self::MAX as u128) as u64
+    }
+}
+
+impl TryFrom<u16> for Bps {
+    type Error = anyhow::Error;
+
+    fn try_from(bps: u16) -> Result<Bps> {
+        Bps::new(bps)
+    }
+}
+
+impl From<Bps> for u16 {
+    fn from(bps: Bps) -> u16 {
+        bps.0
+    }
+}
+
+impl fmt::Display for Bps {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}bps", self.0)
+    }
+}
+
+/// SOL 的最小单位
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(transparent)]
+pub struct Lamports(pub u64);
+
+impl Lamports {
+    pub fn get(self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Display for Lamports {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} lamports", self.0)
+    }
+}
+
+/// 某个 mint 的代币数量，`raw` 为最小单位
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenAmount {
+    pub mint: Pubkey,
+    pub raw: u64,
+}
+
+impl TokenAmount {
+    pub fn new(mint: Pubkey, raw: u64) -> TokenAmount {
+        TokenAmount { mint, raw }
+    }
+
+    /// 按精度把界面数量转换为最小单位，向下取整
+    pub fn from_ui(mint: Pubkey, ui_amount: f64, decimals: u8) -> Result<TokenAmount> {
+        if !ui_amount.is_finite() || ui_amount < 0.0 {
+            return Err(anyhow!("无效的代币数量 {}", ui_amount));
+        }
+        let raw = (ui_amount * 10f64.powi(decimals as i32)).floor();
+        if raw > u64::MAX as f64 {
+            return Err(anyhow!("代币数量 {} 超出范围", ui_amount));
+        }
+        Ok(TokenAmount {
+            mint,
+            raw: raw as u64,
+        })
+    }
+
+    /// 按精度转换为界面数量
+    pub fn to_ui(&self, decimals: u8) -> f64 {
+        self.raw as f64 / 10f64.powi(decimals as i32)
+    }
+
+    /// 同一 mint 的数量相减，不同 mint 或结果为负时返回错误
+    pub fn checked_sub(&self, other: TokenAmount) -> Result<TokenAmount> {
+        if self.mint != other.mint {
+            return Err(anyhow!("不同代币 {} 与 {} 不能相减", self.mint, other.mint));
+        }
+        let raw = self
+            .raw
+            .checked_sub(other.raw)
+            .ok_or_else(|| anyhow!("代币数量不足"))?;
+        Ok(TokenAmount::new(self.mint, raw))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bps_bounds() {
+        assert_eq!(Bps::new(0).unwrap(), Bps::ZERO);
+        assert_eq!(Bps::new(Bps::MAX).unwrap().get(), Bps::MAX);
+        assert!(Bps::new(Bps::MAX + 1).is_err());
+        assert!(Bps::new(u16::MAX).is_err());
+        assert!(Bps::try_from(Bps::MAX + 1).is_err());
+    }
+
+    #[test]
+    fn bps_serde_round_trip() {
+        let bps = Bps::new(250).unwrap();
+        let json = serde_json::to_string(&bps).unwrap();
+        assert_eq!(json, "250");
+        assert_eq!(serde_json::from_str::<Bps>(&json).unwrap(), bps);
+    }
+
+    #[test]
+    fn bps_deserialize_rejects_out_of_range() {
+        assert!(serde_json::from_str::<Bps>("10001").is_err());
+        assert!(serde_json::from_str::<Bps>("65535").is_err());
+        assert!(serde_json::from_str::<Bps>("-1").is_err());
+        assert!(serde_json::from_str::<Bps>("70000").is_err());
+        assert!(serde_json::from_str::<Bps>("\"50\"").is_err());
+    }
+
+    #[test]
+    fn bps_apply_rounds_down() {
+        let bps = Bps::new(30).unwrap();
+        assert_eq!(bps.apply(1_000_000), 3_000);
+        // 999 * 30 / 10000 = 2.997
+        assert_eq!(bps.apply(999), 2);
+        assert_eq!(Bps::ZERO.apply(u64::MAX), 0);
+    }
+
+    #[test]
+    fn bps_apply_near_u64_max_does_not_overflow() {
+        let max = Bps::new(Bps::MAX).unwrap();
+        assert_eq!(max.apply(u64::MAX), u64::MAX);
+        assert_eq!(max.apply(u64::MAX - 1), u64::MAX - 1);
+        let half = Bps::new(5_000).unwrap();
+        assert_eq!(half.apply(u64::MAX), u64::MAX / 2);
+        let one = Bps::new(1).unwrap();
+        assert_eq!(one.apply(u64::MAX), u64::MAX / 10_000);
+    }
+}
@@ -1,31 +1,68 @@
 use serde_json::Value;
-use std::{str::FromStr, sync::Arc};
+use std::{
+    collections::HashMap,
+    env,
+    future::Future,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 use base64::{engine::general_purpose, Engine};
 use jito_sdk_rust::JitoJsonRpcSDK;
-use jupiter_swap_api_client::JupiterSwapApiClient;
 use reqwest::Client;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
-use solana_client::{nonblocking::rpc_client::RpcClient, rpc_client::SerializableTransaction};
+use solana_client::{
+    client_error::ClientError, nonblocking::rpc_client::RpcClient,
+    rpc_client::SerializableTransaction,
+};
 use solana_sdk::{
     address_lookup_table::{state::AddressLookupTable, AddressLookupTableAccount},
     bs58,
     hash::Hash,
     instruction::Instruction,
-    message::v0::Message,
+    message::{v0::Message, VersionedMessage},
+    nonce,
+    program_pack::Pack,
     pubkey::Pubkey,
     signature::{Keypair, Signature},
-    transaction::VersionedTransaction,
+    signer::Signer,
+    system_instruction, system_program,
+    transaction::{TransactionError, VersionedTransaction},
 };
 
 use anyhow::{anyhow, Result};
+use spl_associated_token_account::{
+    get_associated_token_address_with_program_id,
+    instruction::create_associated_token_account_idempotent,
+};
+use spl_token_2022::extension::{
+    transfer_fee::TransferFeeConfig, BaseStateWithExtensions, StateWithExtensions,
+};
+use tokio::sync::watch;
 
-use crate::solana::jup::get_swap_ix;
+use crate::{
+    common::{
+        metrics::metrics,
+        price_source::PriceSource,
+        units::{Bps, Lamports},
+    },
+    error::{self, LimitOrderError},
+    solana::{
+        clients::{BundleSender, SolanaRpc},
+        jito::{parse_send_bundle, JitoError},
+        signer::{sign_versioned_message, LocalKeypairSigner, TransactionSigner},
+        swap::TOKEN_2022_PROGRAM_ID,
+    },
+    SOL,
+};
 
 /// accounts -> 地址查找表的pubkey数组
 /// 返回地址查找表的账户结构
 pub async fn get_address_lookup(
-    rpc: Arc<RpcClient>,
+    rpc: &dyn SolanaRpc,
     accounts: Vec<Pubkey>,
 ) -> Result<Vec<AddressLookupTableAccount>> {
     let mut alts = vec![];
@@ -50,22 +87,378 @@ pub async fn get_address_lookup(
 }
 
 pub async fn build_versioned_transaction(
-    rpc: Arc<RpcClient>,
+    rpc: &dyn SolanaRpc,
     instructions: &[Instruction],
     user: &Pubkey,
-    keypair: &Keypair,
+    signer: &dyn TransactionSigner,
     address_lookup_tables: Vec<Pubkey>,
     blockhash: Hash,
 ) -> Result<VersionedTransaction> {
-    let alt = get_address_lookup(rpc.clone(), address_lookup_tables).await?;
-    let v0_message = Message::try_compile(user, instructions, &alt, blockhash)?;
-    let versioned_tx = VersionedTransaction::try_new(
-        solana_sdk::message::VersionedMessage::V0(v0_message),
-        &[keypair],
-    )?;
+    let alt = get_address_lookup(rpc, address_lookup_tables).await?;
+    compile_versioned_transaction(instructions, user, signer, &alt, blockhash).await
+}
+
+/// 使用已解析的地址查找表编译并签名 V0 交易，适用于同一组指令需要多次编译的场景
+pub async fn compile_versioned_transaction(
+    instructions: &[Instruction],
+    user: &Pubkey,
+    signer: &dyn TransactionSigner,
+    address_lookup_tables: &[AddressLookupTableAccount],
+    blockhash: Hash,
+) -> Result<VersionedTransaction> {
+    sign_versioned_transaction(
+        instructions,
+        user,
+        &[signer],
+        address_lookup_tables,
+        blockhash,
+    )
+    .await
+}
+
+/// 与 [`compile_versioned_transaction`] 相同，但支持多个签名者（例如 nonce authority），签名者可以是远程签名服务
+pub async fn sign_versioned_transaction(
+    instructions: &[Instruction],
+    payer: &Pubkey,
+    signers: &[&dyn TransactionSigner],
+    address_lookup_tables: &[AddressLookupTableAccount],
+    blockhash: Hash,
+) -> Result<VersionedTransaction> {
+    let v0_message = Message::try_compile(payer, instructions, address_lookup_tables, blockhash)?;
+    sign_versioned_message(VersionedMessage::V0(v0_message), signers).await
+}
+
+/// 使用本地私钥编译并签名 V0 交易，用于创建 nonce 账户等只涉及本地密钥的交易
+pub fn compile_versioned_transaction_with_signers(
+    instructions: &[Instruction],
+    payer: &Pubkey,
+    signers: &[&Keypair],
+    address_lookup_tables: &[AddressLookupTableAccount],
+    blockhash: Hash,
+) -> Result<VersionedTransaction> {
+    let v0_message = Message::try_compile(payer, instructions, address_lookup_tables, blockhash)?;
+    let versioned_tx = VersionedTransaction::try_new(VersionedMessage::V0(v0_message), signers)?;
     Ok(versioned_tx)
 }
 
+/// 交易使用的 durable nonce
+#[derive(Clone)]
+pub struct NonceInfo {
+    pub nonce_account: Pubkey,
+    /// nonce 账户当前存储的值，用作交易的 recent blockhash
+    pub nonce: Hash,
+    /// nonce 账户的 authority，需要对交易签名
+    pub authority: Arc<Keypair>,
+}
+
+/// 在指令最前面插入推进 nonce 的指令，durable nonce 交易要求它是第一条指令
+pub fn with_advance_nonce(instructions: &[Instruction], nonce: &NonceInfo) -> Vec<Instruction> {
+    let mut ixs = vec![system_instruction::advance_nonce_account(
+        &nonce.nonce_account,
+        &nonce.authority.pubkey(),
+    )];
+    ixs.extend_from_slice(instructions);
+    ixs
+}
+
+/// 使用 durable nonce 编译并签名 V0 交易，nonce 作为 recent blockhash，交易在 nonce 被推进前一直有效
+pub async fn build_versioned_transaction_with_nonce(
+    instructions: &[Instruction],
+    user: &Pubkey,
+    signer: &dyn TransactionSigner,
+    address_lookup_tables: &[AddressLookupTableAccount],
+    nonce: &NonceInfo,
+) -> Result<VersionedTransaction> {
+    let authority = LocalKeypairSigner::from(nonce.authority.clone());
+    sign_versioned_transaction(
+        &with_advance_nonce(instructions, nonce),
+        user,
+        &[signer, &authority],
+        address_lookup_tables,
+        nonce.nonce,
+    )
+    .await
+}
+
+/// 读取 nonce 账户当前的 nonce 值
+pub async fn get_nonce(rpc: &dyn SolanaRpc, nonce_account: &Pubkey) -> Result<Hash> {
+    let account = rpc
+        .get_account(nonce_account)
+        .await?
+        .ok_or_else(|| anyhow!("nonce 账户 {} 不存在", nonce_account))?;
+    if account.owner != system_program::id() {
+        return Err(anyhow!("账户 {} 不是 nonce 账户", nonce_account));
+    }
+    let versions: nonce::state::Versions = bincode::deserialize(&account.data)?;
+    match versions.state() {
+        nonce::State::Initialized(data) => Ok(data.blockhash()),
+        nonce::State::Uninitialized => Err(anyhow!("nonce 账户 {} 未初始化", nonce_account)),
+    }
+}
+
+/// 创建一个由 `authority` 管理的 nonce 账户，租金由 `payer` 支付，返回 nonce 账户地址
+pub async fn create_nonce_account(
+    rpc: &RpcClient,
+    payer: &Keypair,
+    authority: &Pubkey,
+) -> Result<Pubkey> {
+    let nonce_account = Keypair::new();
+    let lamports = rpc
+        .get_minimum_balance_for_rent_exemption(nonce::State::size())
+        .await?;
+    let ixs = system_instruction::create_nonce_account(
+        &payer.pubkey(),
+        &nonce_account.pubkey(),
+        authority,
+        lamports,
+    );
+    let blockhash = rpc.get_latest_blockhash().await?;
+    let tx = compile_versioned_transaction_with_signers(
+        &ixs,
+        &payer.pubkey(),
+        &[payer, &nonce_account],
+        &[],
+        blockhash,
+    )?;
+    rpc.send_and_confirm_transaction(&tx).await?;
+    Ok(nonce_account.pubkey())
+}
+
+/// 手动推进 nonce，使之前用该 nonce 签名的交易全部失效，`payer` 可以就是 `authority`
+pub async fn advance_nonce(
+    rpc: &dyn SolanaRpc,
+    payer: &Keypair,
+    nonce_account: &Pubkey,
+    authority: &Keypair,
+) -> Result<Signature> {
+    let ix = system_instruction::advance_nonce_account(nonce_account, &authority.pubkey());
+    let blockhash = rpc.get_latest_blockhash().await?;
+    let mut signers = vec![payer];
+    if authority.pubkey() != payer.pubkey() {
+        signers.push(authority);
+    }
+    let tx = compile_versioned_transaction_with_signers(
+        &[ix],
+        &payer.pubkey(),
+        &signers,
+        &[],
+        blockhash,
+    )?;
+    rpc.send(&tx).await
+}
+
+/// 编译未签名的 V0 交易，签名位置填充为默认值，供客户端自行签名
+pub fn unsigned_versioned_transaction(
+    instructions: &[Instruction],
+    payer: &Pubkey,
+    address_lookup_tables: &[AddressLookupTableAccount],
+    blockhash: Hash,
+) -> Result<VersionedTransaction> {
+    let message = VersionedMessage::V0(Message::try_compile(
+        payer,
+        instructions,
+        address_lookup_tables,
+        blockhash,
+    )?);
+    Ok(VersionedTransaction {
+        signatures: vec![Signature::default(); message.header().num_required_signatures as usize],
+        message,
+    })
+}
+
+/// blockhash 距离最后有效区块高度不足该区块数时视为即将过期，为模拟和发送留出时间
+pub const BLOCKHASH_EXPIRY_MARGIN: u64 = 20;
+
+/// 获取到的 blockhash 及其最后有效区块高度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CachedBlockhash {
+    pub blockhash: Hash,
+    /// 区块高度超过该值后，使用该 blockhash 的交易不会再被接受
+    pub last_valid_block_height: u64,
+}
+
+impl CachedBlockhash {
+    /// 在区块高度为 `block_height` 时是否仍然可用，距离过期不足 `margin` 个区块时视为不可用
+    pub fn is_still_valid(&self, block_height: u64, margin: u64) -> bool {
+        block_height.saturating_add(margin) <= self.last_valid_block_height
+    }
+}
+
+/// 发送前对交易所用 blockhash 的处理
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockhashAction {
+    /// 仍然可用，直接发送
+    Keep,
+    /// 即将过期，换用缓存中更新的 blockhash 重新签名
+    Use(CachedBlockhash),
+    /// 即将过期且缓存也不可用，需要重新获取
+    Refresh,
+}
+
+/// 根据当前区块高度决定交易所用的 `current` 是否需要更换，`cached` 为缓存中最新的 blockhash
+pub fn blockhash_action(
+    current: &CachedBlockhash,
+    cached: Option<CachedBlockhash>,
+    block_height: u64,
+    margin: u64,
+) -> BlockhashAction {
+    if current.is_still_valid(block_height, margin) {
+        return BlockhashAction::Keep;
+    }
+    match cached.filter(|cached| cached.is_still_valid(block_height, margin)) {
+        Some(cached) => BlockhashAction::Use(cached),
+        None => BlockhashAction::Refresh,
+    }
+}
+
+/// 所有交换共享的 blockhash 缓存
+///
+/// 后台任务按固定间隔刷新，构建交易时直接读取缓存，不必每笔交换都请求 RPC。
+/// 模拟和等待 bundle 需要时间，发送前用 [`BlockhashProvider::renew_if_stale`] 按当前区块高度再检查一次。
+/// 刷新失败时保留旧值，由发送前的检查兜底。
+///
+/// 后台任务同时记录当前 slot，按 slot 控制重发节奏时等待它前进，见 [`BlockhashProvider::wait_for_slot`]。
+pub struct BlockhashProvider {
+    cached: Mutex<Option<CachedBlockhash>>,
+    margin: u64,
+    /// 观察到的最新 slot，尚未获取过时为 None
+    slot: watch::Sender<Option<u64>>,
+}
+
+impl BlockhashProvider {
+    /// 不带后台刷新的缓存，第一次读取时获取 blockhash
+    pub fn new(margin: u64) -> BlockhashProvider {
+        BlockhashProvider {
+            cached: Mutex::new(None),
+            margin,
+            slot: watch::channel(None).0,
+        }
+    }
+
+    /// 启动后台刷新任务，任务在缓存释放后退出
+    pub fn spawn(rpc: Arc<dyn SolanaRpc>, interval: Duration) -> Arc<BlockhashProvider> {
+        let provider = Arc::new(BlockhashProvider::new(BLOCKHASH_EXPIRY_MARGIN));
+        let weak = Arc::downgrade(&provider);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                let Some(provider) = weak.upgrade() else {
+                    break;
+                };
+                if let Err(e) = provider.refresh(rpc.as_ref()).await {
+                    println!("刷新 blockhash 失败 {:?}", e);
+                }
+                if let Err(e) = provider.refresh_slot(rpc.as_ref()).await {
+                    println!("刷新 slot 失败 {:?}", e);
+                }
+            }
+        });
+        provider
+    }
+
+    /// 缓存中的 blockhash，不会发起请求
+    pub fn cached(&self) -> Option<CachedBlockhash> {
+        *self.cached.lock().unwrap()
+    }
+
+    /// 读取缓存的 blockhash，尚未获取过时立即获取
+    pub async fn get(&self, rpc: &dyn SolanaRpc) -> Result<CachedBlockhash> {
+        match self.cached() {
+            Some(cached) => Ok(cached),
+            None => self.refresh(rpc).await,
+        }
+    }
+
+    /// 立即获取最新的 blockhash 并更新缓存
+    pub async fn refresh(&self, rpc: &dyn SolanaRpc) -> Result<CachedBlockhash> {
+        let (blockhash, last_valid_block_height) = rpc.get_latest_blockhash_with_height().await?;
+        let fresh = CachedBlockhash {
+            blockhash,
+            last_valid_block_height,
+        };
+        let mut cached = self.cached.lock().unwrap();
+        // 并发刷新时保留有效期更长的结果
+        if cached
+            .is_none_or(|cached| cached.last_valid_block_height <= fresh.last_valid_block_height)
+        {
+            *cached = Some(fresh);
+        }
+        Ok(fresh)
+    }
+
+    /// 观察到的最新 slot，不会发起请求
+    pub fn slot(&self) -> Option<u64> {
+        *self.slot.borrow()
+    }
+
+    /// 立即获取当前 slot 并记录，slot 只会前进
+    pub async fn refresh_slot(&self, rpc: &dyn SolanaRpc) -> Result<u64> {
+        let slot = rpc.get_slot().await?;
+        self.slot.send_if_modified(|current| {
+            let advanced = current.is_none_or(|current| current < slot);
+            if advanced {
+                *current = Some(slot);
+            }
+            advanced
+        });
+        Ok(slot)
+    }
+
+    /// 等待记录的 slot 达到 `target`，返回达到时的 slot
+    ///
+    /// 只等待后台刷新或 [`BlockhashProvider::refresh_slot`] 记录的 slot，自身不发起请求，调用方应自行限定等待时间。
+    pub async fn wait_for_slot(&self, target: u64) -> u64 {
+        let mut slots = self.slot.subscribe();
+        match slots
+            .wait_for(|slot| slot.is_some_and(|slot| slot >= target))
+            .await
+        {
+            Ok(slot) => slot.unwrap_or(target),
+            // 发送端属于缓存本身，等待期间不会释放
+            Err(_) => target,
+        }
+    }
+
+    /// 按当前区块高度检查 `blockhash` 是否仍然可用
+    pub async fn is_still_valid(
+        &self,
+        rpc: &dyn SolanaRpc,
+        blockhash: &CachedBlockhash,
+    ) -> Result<bool> {
+        let block_height = rpc.get_block_height().await?;
+        Ok(blockhash.is_still_valid(block_height, self.margin))
+    }
+
+    /// `blockhash` 仍然可用时返回 None，否则返回用于重新签名的新 blockhash，规则见 [`blockhash_action`]
+    pub async fn renew_if_stale(
+        &self,
+        rpc: &dyn SolanaRpc,
+        blockhash: &CachedBlockhash,
+    ) -> Result<Option<CachedBlockhash>> {
+        let block_height = rpc.get_block_height().await?;
+        match blockhash_action(blockhash, self.cached(), block_height, self.margin) {
+            BlockhashAction::Keep => Ok(None),
+            BlockhashAction::Use(cached) => Ok(Some(cached)),
+            BlockhashAction::Refresh => Ok(Some(self.refresh(rpc).await?)),
+        }
+    }
+}
+
+/// 发送失败是否因为交易的 blockhash 已过期或节点尚未见过，此时交易没有上链，可以换用新的 blockhash 重新签名
+pub fn is_blockhash_not_found(e: &anyhow::Error) -> bool {
+    if e.downcast_ref::<ClientError>()
+        .and_then(ClientError::get_transaction_error)
+        == Some(TransactionError::BlockhashNotFound)
+    {
+        return true;
+    }
+    // bundle 失败等场景只有错误文本
+    let message = format!("{:#}", e);
+    message.contains("Blockhash not found") || message.contains("BlockhashNotFound")
+}
+
 pub async fn send_tx_with_jito(
     tx: impl SerializableTransaction,
     jito: Arc<JitoJsonRpcSDK>,
@@ -74,14 +467,10 @@ pub async fn send_tx_with_jito(
     let params = json!({
         "tx": serialized_tx
     });
-    match jito.send_txn(Some(params.clone()), true).await {
-        Ok(resp) => match resp["result"].as_str() {
-            Some(signature) => {
-                return Ok(Signature::from_str(signature)?);
-            }
-            None => Err(anyhow!("交易未响应")),
-        },
-        Err(e) => Err(e.into()),
+    let resp = jito.send_txn(Some(params), true).await?;
+    match resp["result"].as_str() {
+        Some(signature) => Ok(Signature::from_str(signature)?),
+        None => Err(anyhow!("交易未响应")),
     }
 }
 
@@ -92,42 +481,1163 @@ pub async fn send_tx(tx: impl SerializableTransaction, rpc: Arc<RpcClient>) -> R
     }
 }
 
+/// 以 Jito bundle 发送交易，返回 bundle id
+///
+/// 失败时返回的错误为 [`JitoError`]，调用方可以据此区分限流、被拒绝和 Jito 不可用。
 pub async fn send_bundle(
-    jito: &JitoJsonRpcSDK,
-    bundle: Vec<impl SerializableTransaction>,
-) -> Result<Option<String>> {
+    jito: &dyn BundleSender,
+    bundle: &[impl SerializableTransaction],
+) -> Result<String> {
     let mut params = vec![];
-    // 对每笔交易进行base64的编码
+    // 对每笔交易进行base58的编码
     for tx in bundle {
-        params.push(bs58::encode(bincode::serialize(&tx)?).into_string());
+        params.push(bs58::encode(bincode::serialize(tx)?).into_string());
     }
     let bundle = json!(params);
-    let result = match jito.send_bundle(Some(bundle), None).await {
-        Ok(resp) => match resp.get("result") {
-            Some(bundle_id) => Some(bundle_id.as_str().unwrap().to_string()),
-            None => None,
-        },
-        Err(_) => None,
+    let resp = jito
+        .send_bundle(bundle)
+        .await
+        .map_err(|e| JitoError::from_request(&e))?;
+    Ok(parse_send_bundle(&resp)?)
+}
+
+/// 模拟执行交易，成功时返回消耗的计算单元
+///
+/// 失败时返回的错误包含程序日志中的失败原因，例如滑点超限时的 `custom program error: 0x1771`。
+pub async fn simulate_or_fail(
+    rpc: &dyn SolanaRpc,
+    tx: &VersionedTransaction,
+) -> Result<Option<u64>> {
+    let simulation = rpc.simulate(tx).await?;
+    match simulation.err {
+        Some(err) => Err(LimitOrderError::SimulationFailed {
+            logs: simulation_failure_reason(&err, &simulation.logs),
+        }
+        .into()),
+        None => Ok(simulation.units_consumed),
+    }
+}
+
+/// 从模拟日志中挑出程序失败和 Anchor 错误的行，与交易错误拼成可读的失败原因
+pub fn simulation_failure_reason(err: &str, logs: &[String]) -> String {
+    let reasons: Vec<&str> = logs
+        .iter()
+        .filter(|line| line.contains(" failed: ") || line.contains("Error Code:"))
+        .map(|line| line.trim_start_matches("Program log: "))
+        .collect();
+    if reasons.is_empty() {
+        err.to_string()
+    } else {
+        format!("{}: {}", err, reasons.join("; "))
+    }
+}
+
+/// 估算费用时未配置 `PRIORITY_FEE_PERCENTILE` 所使用的百分位
+pub const DEFAULT_PRIORITY_FEE_PERCENTILE: u8 = 50;
+
+/// 按最近区块的优先费取分位数，作为推荐的计算单元价格（micro-lamports / CU）
+///
+/// `accounts` 为交易会写入的账户，为空时按全网的优先费计算。RPC 只返回最近 150 个区块的数据，
+/// 没有样本时返回 0。
+pub async fn recommended_priority_fee(
+    rpc: &dyn SolanaRpc,
+    accounts: &[Pubkey],
+    percentile: u8,
+) -> Result<u64> {
+    let fees = rpc.get_recent_prioritization_fees(accounts).await?;
+    Ok(fee_percentile(fees, percentile))
+}
+
+/// 取第 `percentile` 百分位的优先费，超过 100 时按 100 计算
+pub fn fee_percentile(mut fees: Vec<u64>, percentile: u8) -> u64 {
+    if fees.is_empty() {
+        return 0;
+    }
+    fees.sort_unstable();
+    let index = (fees.len() - 1) * percentile.min(100) as usize / 100;
+    fees[index]
+}
+
+/// Jito bundle 的最终状态
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BundleStatus {
+    /// 已上链（processed 或 confirmed）
+    Landed { slot: u64 },
+    /// 已最终确认
+    Finalized { slot: u64 },
+    /// 上链但执行失败
+    Failed(String),
+    /// 超时仍未查询到 bundle，视为被丢弃
+    TimedOut,
+}
+
+impl BundleStatus {
+    pub fn is_landed(&self) -> bool {
+        matches!(
+            self,
+            BundleStatus::Landed { .. } | BundleStatus::Finalized { .. }
+        )
+    }
+}
+
+/// Bundle 确认配置
+#[derive(Debug, Clone, Copy)]
+pub struct BundleConfig {
+    /// 等待 bundle 上链的最长时间
+    pub confirm_timeout: Duration,
+    /// bundle 失败或被丢弃时是否改用 RPC 单独发送交换交易，为 false 时返回错误
+    ///
+    /// tip 指令在交换交易内时经 RPC 发送仍会支付 tip
+    pub fallback_to_rpc: bool,
+}
+
+impl Default for BundleConfig {
+    fn default() -> Self {
+        BundleConfig {
+            confirm_timeout: Duration::from_secs(30),
+            fallback_to_rpc: false,
+        }
+    }
+}
+
+impl BundleConfig {
+    /// 从环境变量 `BUNDLE_CONFIRM_TIMEOUT_MS`、`BUNDLE_FALLBACK_RPC` 读取，未配置时使用默认值
+    pub fn from_env() -> Result<BundleConfig> {
+        let mut config = BundleConfig::default();
+        if let Ok(v) = env::var("BUNDLE_CONFIRM_TIMEOUT_MS") {
+            config.confirm_timeout = Duration::from_millis(v.parse()?);
+        }
+        if let Ok(v) = env::var("BUNDLE_FALLBACK_RPC") {
+            config.fallback_to_rpc = v.parse()?;
+        }
+        Ok(config)
+    }
+}
+
+/// 未指定 tip 却必须以 bundle 发送时自动选择的 tip：名义价值的 `bps`，限制在 `min_lamports` 到 `max_lamports` 之间
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TipSchedule {
+    pub min_lamports: u64,
+    pub bps: Bps,
+    pub max_lamports: u64,
+}
+
+impl Default for TipSchedule {
+    fn default() -> Self {
+        TipSchedule {
+            min_lamports: 10_000,
+            bps: Bps::new(1).unwrap(),
+            max_lamports: 10_000_000,
+        }
+    }
+}
+
+impl TipSchedule {
+    /// 名义价值为 `notional` lamports 的交换使用的 tip，名义价值未知时取下限
+    pub fn tip_for(&self, notional: Option<u64>) -> Lamports {
+        let proportional = notional
+            .map(|notional| (notional as u128 * self.bps.get() as u128 / Bps::MAX as u128) as u64)
+            .unwrap_or_default();
+        Lamports(
+            proportional
+                .max(self.min_lamports)
+                .min(self.max_lamports.max(self.min_lamports)),
+        )
+    }
+}
+
+/// 大额交换的私有发送配置
+///
+/// 名义价值超过阈值（或订单要求私有发送）的交换始终以 Jito bundle 发送，不经过公开的 RPC，避免被三明治攻击。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PrivateExecutionConfig {
+    /// 名义价值超过该值（lamports）时强制以 bundle 发送，为 None 时只有要求私有发送的订单使用 bundle
+    pub force_jito_above_lamports: Option<u64>,
+    /// 未指定 tip 时自动选择的 tip
+    pub tip: TipSchedule,
+    /// bundle 未上链时是否改用 RPC 发送并发出警告事件；为 false（默认）时本次执行失败，订单继续监控
+    pub fallback_to_rpc: bool,
+}
+
+impl PrivateExecutionConfig {
+    /// 从环境变量 `FORCE_JITO_ABOVE_LAMPORTS`、`AUTO_TIP_MIN_LAMPORTS`、`AUTO_TIP_BPS`、`AUTO_TIP_MAX_LAMPORTS`、
+    /// `PRIVATE_FALLBACK_RPC` 读取，未配置时使用默认值
+    pub fn from_env() -> Result<PrivateExecutionConfig> {
+        let mut config = PrivateExecutionConfig::default();
+        if let Ok(v) = env::var("FORCE_JITO_ABOVE_LAMPORTS") {
+            config.force_jito_above_lamports = (!v.trim().is_empty())
+                .then(|| v.trim().parse())
+                .transpose()?;
+        }
+        if let Ok(v) = env::var("AUTO_TIP_MIN_LAMPORTS") {
+            config.tip.min_lamports = v.parse()?;
+        }
+        if let Ok(v) = env::var("AUTO_TIP_BPS") {
+            config.tip.bps = Bps::new(v.parse()?)?;
+        }
+        if let Ok(v) = env::var("AUTO_TIP_MAX_LAMPORTS") {
+            config.tip.max_lamports = v.parse()?;
+        }
+        if config.tip.max_lamports < config.tip.min_lamports {
+            return Err(anyhow!(
+                "AUTO_TIP_MAX_LAMPORTS 不能小于 AUTO_TIP_MIN_LAMPORTS"
+            ));
+        }
+        if let Ok(v) = env::var("PRIVATE_FALLBACK_RPC") {
+            config.fallback_to_rpc = v.parse()?;
+        }
+        Ok(config)
+    }
+}
+
+/// 轮询 bundle 状态直到上链、失败或超时
+///
+/// 查询间隔从 500ms 开始翻倍，最长 4s。`getBundleStatuses` 暂时查询不到的 bundle 视为仍在等待。
+pub async fn confirm_bundle(
+    jito: &dyn BundleSender,
+    bundle_id: &str,
+    timeout: Duration,
+) -> Result<BundleStatus> {
+    let deadline = Instant::now() + timeout;
+    let mut delay = Duration::from_millis(500);
+    loop {
+        match jito.get_bundle_statuses(vec![bundle_id.to_string()]).await {
+            Ok(resp) => {
+                if let Some(status) = parse_bundle_status(&resp) {
+                    record_bundle_status(&status);
+                    return Ok(status);
+                }
+            }
+            Err(e) => println!("查询 bundle {} 状态失败 {:?}", bundle_id, e),
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            record_bundle_status(&BundleStatus::TimedOut);
+            return Ok(BundleStatus::TimedOut);
+        }
+        tokio::time::sleep(delay.min(deadline - now)).await;
+        delay = (delay * 2).min(Duration::from_secs(4));
+    }
+}
+
+fn record_bundle_status(status: &BundleStatus) {
+    let label = match status {
+        BundleStatus::Landed { .. } => "landed",
+        BundleStatus::Finalized { .. } => "finalized",
+        BundleStatus::Failed(_) => "failed",
+        BundleStatus::TimedOut => "timed_out",
     };
-    Ok(result)
+    metrics().bundle_status.with_label_values(&[label]).inc();
 }
 
-pub async fn get_price(client: Arc<Client>, mint: &str) -> Result<f32> {
-    let resp = client
-        .get(format!("https://api.jup.ag/price/v2?ids={}", mint))
-        .send()
-        .await?;
+/// 解析 `getBundleStatuses` 的返回，bundle 尚未上链时返回 None
+fn parse_bundle_status(resp: &Value) -> Option<BundleStatus> {
+    let status = resp["result"]["value"].as_array()?.first()?;
+    if status.is_null() {
+        return None;
+    }
+    let slot = status["slot"].as_u64().unwrap_or_default();
+    match status.get("err") {
+        Some(err) if !err.is_null() && err.get("Ok").is_none() => {
+            return Some(BundleStatus::Failed(err.to_string()))
+        }
+        _ => {}
+    }
+    match status["confirmation_status"].as_str()? {
+        "finalized" => Some(BundleStatus::Finalized { slot }),
+        "processed" | "confirmed" => Some(BundleStatus::Landed { slot }),
+        _ => None,
+    }
+}
+
+pub async fn get_price(client: Arc<Client>, mint: &str) -> error::Result<f32> {
+    let prices = get_prices(
+        client,
+        JUPITER_PRICE_URL,
+        &[mint],
+        false,
+        PriceRequestPolicy::default(),
+    )
+    .await
+    .map_err(|e| LimitOrderError::PriceFeedUnavailable(e.to_string()))?;
+    match prices.get(mint).and_then(|datum| datum.price) {
+        Some(price) => Ok(price as f32),
+        None => Err(LimitOrderError::PriceFeedUnavailable(format!(
+            "未获得代币 {} 的价格",
+            mint
+        ))),
+    }
+}
 
-    if resp.status().is_success() {
-        let resp_json: Value = resp.json().await?;
+/// Jupiter 价格接口（`price/v2`）的响应
+///
+/// 未知代币的条目为 null；价格有时为字符串、有时为数字；`showExtraInfo=true` 时每个条目多出 `extraInfo`。
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PriceV2Response {
+    #[serde(default)]
+    pub data: HashMap<String, Option<PriceDatum>>,
+}
+
+/// 单个代币的价格
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PriceDatum {
+    #[serde(default, deserialize_with = "de_opt_f64")]
+    pub price: Option<f64>,
+    #[serde(default)]
+    pub extra_info: Option<PriceExtraInfo>,
+}
+
+/// `showExtraInfo=true` 时返回的附加信息，各字段都可能缺失
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PriceExtraInfo {
+    #[serde(default)]
+    pub last_swapped_price: Option<LastSwappedPrice>,
+    #[serde(default)]
+    pub quoted_price: Option<QuotedPrice>,
+    /// `high`、`medium` 或 `low`
+    #[serde(default)]
+    pub confidence_level: Option<String>,
+}
+
+/// 最近一次通过 Jupiter 买入、卖出的成交价格，时间为 unix 秒
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LastSwappedPrice {
+    #[serde(default)]
+    pub last_jupiter_sell_at: Option<u64>,
+    #[serde(default, deserialize_with = "de_opt_f64")]
+    pub last_jupiter_sell_price: Option<f64>,
+    #[serde(default)]
+    pub last_jupiter_buy_at: Option<u64>,
+    #[serde(default, deserialize_with = "de_opt_f64")]
+    pub last_jupiter_buy_price: Option<f64>,
+}
+
+/// Jupiter 按报价得到的买入、卖出价格，时间为 unix 秒
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuotedPrice {
+    #[serde(default, deserialize_with = "de_opt_f64")]
+    pub buy_price: Option<f64>,
+    #[serde(default)]
+    pub buy_at: Option<u64>,
+    #[serde(default, deserialize_with = "de_opt_f64")]
+    pub sell_price: Option<f64>,
+    #[serde(default)]
+    pub sell_at: Option<u64>,
+}
+
+impl PriceDatum {
+    /// 报价买卖价差的一半（USD），作为价格的置信区间；没有附加信息时为 None
+    pub fn confidence(&self) -> Option<f64> {
+        let quoted = self.extra_info.as_ref()?.quoted_price.as_ref()?;
+        Some((quoted.buy_price? - quoted.sell_price?).abs() / 2.0)
+    }
+
+    /// 报价的时间（unix 毫秒），取买入、卖出报价中较新的一个；没有附加信息时为 None
+    pub fn quoted_at_ms(&self) -> Option<u64> {
+        let quoted = self.extra_info.as_ref()?.quoted_price.as_ref()?;
+        quoted.buy_at.max(quoted.sell_at).map(|secs| secs * 1000)
+    }
+
+    /// 最近一次通过 Jupiter 成交的价格，取买入、卖出中较新的一个
+    pub fn last_swap_price(&self) -> Option<f64> {
+        let last = self.extra_info.as_ref()?.last_swapped_price.as_ref()?;
+        match (last.last_jupiter_buy_at, last.last_jupiter_sell_at) {
+            (Some(buy_at), Some(sell_at)) if sell_at > buy_at => last.last_jupiter_sell_price,
+            (Some(_), _) => last.last_jupiter_buy_price.or(last.last_jupiter_sell_price),
+            _ => last.last_jupiter_sell_price.or(last.last_jupiter_buy_price),
+        }
+    }
+}
+
+/// 价格字段可能是字符串、数字或 null，字符串无法解析为数字时返回错误
+fn de_opt_f64<'de, D>(deserializer: D) -> std::result::Result<Option<f64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    match Option::<Value>::deserialize(deserializer)? {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::Number(n)) => Ok(n.as_f64()),
+        Some(Value::String(s)) => s
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| serde::de::Error::custom(format!("价格无效 {}", s))),
+        Some(other) => Err(serde::de::Error::custom(format!("价格无效 {}", other))),
+    }
+}
+
+/// Jupiter 价格接口的默认地址
+pub const JUPITER_PRICE_URL: &str = "https://api.jup.ag/price/v2";
+
+/// 价格请求的超时和重试策略
+#[derive(Debug, Clone, Copy)]
+pub struct PriceRequestPolicy {
+    /// 单次请求的超时时间
+    pub timeout: Duration,
+    /// 最多请求次数（含第一次），至少为 1
+    pub attempts: u32,
+    /// 第一次重试前的等待时间，之后每次翻倍
+    pub base_delay: Duration,
+}
+
+impl Default for PriceRequestPolicy {
+    fn default() -> Self {
+        PriceRequestPolicy {
+            timeout: Duration::from_millis(3000),
+            attempts: 3,
+            base_delay: Duration::from_millis(200),
+        }
+    }
+}
+
+impl PriceRequestPolicy {
+    /// 第 `attempt` 次请求失败后的等待时间
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+    }
+}
+
+/// 一次价格请求的失败，`retryable` 为 true 时为网络错误、超时、429 或 5xx 等暂时性错误
+struct PriceRequestError {
+    error: anyhow::Error,
+    retryable: bool,
+}
+
+/// 一次请求批量获取多个代币的 USD 价格，`show_extra_info` 为 true 时同时请求报价价差和最近成交价格
+///
+/// `base_url` 为价格接口地址，通常为 [`JUPITER_PRICE_URL`]，测试时可指向本地的模拟服务。
+///
+/// 网络错误、超时、429 和 5xx 按 `policy` 退避重试；其他错误状态和无法解析的响应不重试，直接返回错误。
+///
+/// 价格源没有返回的代币（包括条目为 null 或价格为 null 的代币）不会出现在结果中，由调用方决定如何处理。
+pub async fn get_prices(
+    client: Arc<Client>,
+    base_url: &str,
+    mints: &[&str],
+    show_extra_info: bool,
+    policy: PriceRequestPolicy,
+) -> Result<HashMap<String, PriceDatum>> {
+    if mints.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let attempts = policy.attempts.max(1);
+    let mut attempt = 0;
+    let body = loop {
+        attempt += 1;
+        match request_prices(&client, base_url, mints, show_extra_info, policy.timeout).await {
+            Ok(body) => break body,
+            Err(e) if e.retryable && attempt < attempts => {
+                println!(
+                    "价格请求失败（第 {}/{} 次），稍后重试: {}",
+                    attempt, attempts, e.error
+                );
+                tokio::time::sleep(policy.backoff(attempt)).await;
+            }
+            Err(e) => return Err(e.error),
+        }
+    };
+    let parsed: PriceV2Response =
+        serde_json::from_slice(&body).map_err(|e| anyhow!("价格接口返回的数据无法解析 {}", e))?;
+    Ok(parsed
+        .data
+        .into_iter()
+        .filter_map(|(mint, datum)| {
+            let datum = datum?;
+            let price = datum
+                .price
+                .filter(|price| price.is_finite() && *price > 0.0)?;
+            Some((
+                mint,
+                PriceDatum {
+                    price: Some(price),
+                    ..datum
+                },
+            ))
+        })
+        .collect())
+}
+
+async fn request_prices(
+    client: &Client,
+    base_url: &str,
+    mints: &[&str],
+    show_extra_info: bool,
+    timeout: Duration,
+) -> std::result::Result<Vec<u8>, PriceRequestError> {
+    let transient = |error: reqwest::Error| PriceRequestError {
+        error: error.into(),
+        retryable: true,
+    };
+    let mut request = client
+        .get(base_url)
+        .query(&[("ids", mints.join(","))])
+        .timeout(timeout);
+    if show_extra_info {
+        request = request.query(&[("showExtraInfo", "true")]);
+    }
+    let resp = request.send().await.map_err(transient)?;
+    let status = resp.status();
+    if !status.is_success() {
+        return Err(PriceRequestError {
+            error: anyhow!("价格接口返回错误状态 {}", status),
+            retryable: status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error(),
+        });
+    }
+    let body = resp.bytes().await.map_err(transient)?;
+    Ok(body.to_vec())
+}
+
+/// 代币 mint 的基础信息
+#[derive(Debug, Clone, Copy)]
+pub struct MintInfo {
+    /// mint 账户所属的代币程序
+    pub token_program: Pubkey,
+    /// 代币精度
+    pub decimals: u8,
+    /// Token-2022 转账手续费扩展的费率，没有该扩展或费率为 0 时为 None
+    pub transfer_fee: Option<TransferFee>,
+}
+
+/// Token-2022 转账手续费：每次转账从转入数量中扣除 `bps`，不超过 `maximum_fee`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct TransferFee {
+    pub bps: u16,
+    pub maximum_fee: u64,
+}
+
+impl TransferFee {
+    /// 转出 `amount` 时扣除的手续费，与 spl-token-2022 相同向上取整
+    pub fn fee(&self, amount: u64) -> u64 {
+        let fee = (amount as u128 * self.bps as u128).div_ceil(Bps::MAX as u128);
+        (fee as u64).min(self.maximum_fee)
+    }
+
+    /// 转出 `amount` 时对方实际收到的数量
+    pub fn net(&self, amount: u64) -> u64 {
+        amount - self.fee(amount)
+    }
+}
+
+/// 读取 mint 的转账手续费扩展
+///
+/// 扩展中有当前和下一纪元生效的两档费率，不查询当前纪元，两档中取较高的费率和上限，宁可少估实际收到的数量。
+fn transfer_fee(data: &[u8]) -> Result<Option<TransferFee>> {
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(data)?;
+    let Ok(config) = state.get_extension::<TransferFeeConfig>() else {
+        return Ok(None);
+    };
+    let (older, newer) = (&config.older_transfer_fee, &config.newer_transfer_fee);
+    let fee = TransferFee {
+        bps: u16::from(older.transfer_fee_basis_points)
+            .max(u16::from(newer.transfer_fee_basis_points)),
+        maximum_fee: u64::from(older.maximum_fee).max(u64::from(newer.maximum_fee)),
+    };
+    Ok((fee.bps > 0 && fee.maximum_fee > 0).then_some(fee))
+}
+
+/// 把最小单位的数量按精度格式化为界面显示的字符串，去掉小数末尾的 0，例如 1500000、6 位精度为 `1.5`
+pub fn format_amount(amount: u64, decimals: u8) -> String {
+    if decimals == 0 {
+        return amount.to_string();
+    }
+    let digits = format!("{:0>width$}", amount, width = decimals as usize + 1);
+    let (int, frac) = digits.split_at(digits.len() - decimals as usize);
+    match frac.trim_end_matches('0') {
+        "" => int.to_string(),
+        frac => format!("{}.{}", int, frac),
+    }
+}
+
+/// 查询 mint 账户，返回代币程序、精度和 Token-2022 的转账手续费
+pub async fn get_mint_info(rpc: &dyn SolanaRpc, mint: &Pubkey) -> Result<MintInfo> {
+    let account = rpc
+        .get_account(mint)
+        .await?
+        .ok_or_else(|| anyhow!("mint {} 不存在", mint))?;
+    if account.data.len() < spl_token::state::Mint::LEN {
+        return Err(anyhow!("账户 {} 不是有效的 mint", mint));
+    }
+    // Token-2022 的 mint 基础布局与 spl-token 一致，扩展数据位于其后
+    let state =
+        spl_token::state::Mint::unpack_from_slice(&account.data[..spl_token::state::Mint::LEN])?;
+    let transfer_fee = if account.owner == TOKEN_2022_PROGRAM_ID
+        && account.data.len() > spl_token::state::Mint::LEN
+    {
+        transfer_fee(&account.data)?
+    } else {
+        None
+    };
+    Ok(MintInfo {
+        token_program: account.owner,
+        decimals: state.decimals,
+        transfer_fee,
+    })
+}
+
+/// `owner` 的 ATA 不存在时返回幂等创建它的指令，租金由 `payer` 支付
+///
+/// Token-2022 代币的 ATA 地址与 spl-token 不同，`token_program` 必须是 mint 所属的代币程序。
+pub async fn ensure_ata_ix(
+    rpc: &dyn SolanaRpc,
+    payer: &Pubkey,
+    owner: &Pubkey,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+) -> Result<Option<Instruction>> {
+    let ata = get_associated_token_address_with_program_id(owner, mint, token_program);
+    let account = rpc.get_account(&ata).await?;
+    Ok(account
+        .is_none()
+        .then(|| create_associated_token_account_idempotent(payer, owner, mint, token_program)))
+}
+
+/// 指令中是否已有创建 `ata` 的指令
+///
+/// ATA 程序的创建指令中第二个账户为要创建的 ATA。
+pub fn creates_ata(ixs: &[Instruction], ata: &Pubkey) -> bool {
+    ixs.iter().any(|ix| {
+        ix.program_id == spl_associated_token_account::id()
+            && ix.accounts.get(1).is_some_and(|meta| meta.pubkey == *ata)
+    })
+}
+
+/// 健康检查中单个请求的超时时间
+pub const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// 单个上游依赖的检查结果
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct DependencyHealth {
+    pub ok: bool,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 执行一次检查并记录耗时，超过 [`HEALTH_CHECK_TIMEOUT`] 视为失败
+async fn timed_check<T>(check: impl Future<Output = Result<T>>) -> DependencyHealth {
+    let started_at = Instant::now();
+    let result = tokio::time::timeout(HEALTH_CHECK_TIMEOUT, check).await;
+    let latency_ms = started_at.elapsed().as_millis() as u64;
+    let error = match result {
+        Ok(Ok(_)) => None,
+        Ok(Err(e)) => Some(e.to_string()),
+        Err(_) => Some(format!("超过 {:?} 未响应", HEALTH_CHECK_TIMEOUT)),
+    };
+    DependencyHealth {
+        ok: error.is_none(),
+        latency_ms,
+        error,
+    }
+}
 
-        if let Some(data) = resp_json.get("data") {
-            if let Some(_data) = data.get(mint) {
-                if let Some(price) = _data.get("price") {
-                    return Ok(price.as_str().unwrap().parse::<f32>()?);
+/// 检查 RPC：获取最新的 blockhash
+pub async fn check_rpc(rpc: &dyn SolanaRpc) -> DependencyHealth {
+    timed_check(rpc.get_latest_blockhash()).await
+}
+
+/// 检查价格源：查询 SOL 的价格
+pub async fn check_price_feed(source: Arc<dyn PriceSource>) -> DependencyHealth {
+    timed_check(async {
+        let prices = source.get_prices(&[SOL]).await?;
+        if !prices.contains_key(&SOL) {
+            return Err(anyhow!("价格源 {} 未返回 SOL 的价格", source.name()));
+        }
+        Ok(())
+    })
+    .await
+}
+
+/// 检查 Jito：调用 `getTipAccounts`
+pub async fn check_jito(jito: &dyn BundleSender) -> DependencyHealth {
+    timed_check(async {
+        let resp = jito.get_tip_accounts().await?;
+        if resp.get("result").is_none() {
+            return Err(anyhow!("getTipAccounts 返回格式错误 {}", resp));
+        }
+        Ok(())
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transfer_fee_rounds_up_and_is_capped() {
+        let fee = TransferFee {
+            bps: 100,
+            maximum_fee: 5_000,
+        };
+        assert_eq!(fee.fee(0), 0);
+        // 1% 的 1 向上取整为 1
+        assert_eq!(fee.fee(1), 1);
+        assert_eq!(fee.fee(10_000), 100);
+        assert_eq!(fee.fee(10_001), 101);
+        assert_eq!(fee.fee(1_000_000), 5_000);
+        assert_eq!(fee.net(10_001), 9_900);
+        assert_eq!(fee.net(u64::MAX), u64::MAX - 5_000);
+    }
+
+    fn logs(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn failure_reason_includes_program_error() {
+        let reason = simulation_failure_reason(
+            "Error processing Instruction 3: custom program error: 0x1771",
+            &logs(&[
+                "Program ComputeBudget111111111111111111111111111111 success",
+                "Program JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4 invoke [1]",
+                "Program JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4 consumed 48213 of 248650 compute units",
+                "Program JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4 failed: custom program error: 0x1771",
+            ]),
+        );
+        assert_eq!(
+            reason,
+            "Error processing Instruction 3: custom program error: 0x1771: \
+             Program JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4 failed: custom program error: 0x1771"
+        );
+    }
+
+    #[test]
+    fn failure_reason_includes_anchor_error_code() {
+        let reason = simulation_failure_reason(
+            "InstructionError(3, Custom(6001))",
+            &logs(&[
+                "Program JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4 invoke [1]",
+                "Program log: AnchorError occurred. Error Code: SlippageToleranceExceeded. Error Number: 6001. Error Message: Slippage tolerance exceeded.",
+                "Program JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4 failed: custom program error: 0x1771",
+            ]),
+        );
+        // 去掉 `Program log: ` 前缀，多行以分号连接
+        assert_eq!(
+            reason,
+            "InstructionError(3, Custom(6001)): \
+             AnchorError occurred. Error Code: SlippageToleranceExceeded. Error Number: 6001. Error Message: Slippage tolerance exceeded.; \
+             Program JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4 failed: custom program error: 0x1771"
+        );
+    }
+
+    #[test]
+    fn failure_reason_without_matching_line_is_the_error() {
+        let err = "InsufficientFundsForRent { account_index: 0 }";
+        let cases = [
+            logs(&[]),
+            logs(&[
+                "Program 11111111111111111111111111111111 invoke [1]",
+                "Program 11111111111111111111111111111111 success",
+            ]),
+        ];
+        for logs in cases {
+            assert_eq!(simulation_failure_reason(err, &logs), err);
+        }
+    }
+
+    /// 一直查询不到的 bundle 在超时后返回 `TimedOut`，等待时间不超过超时加一次查询的间隔
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn unseen_bundle_times_out_within_the_bound() {
+        use crate::testing::MockJito;
+
+        let mut jito = MockJito::new(Pubkey::new_unique());
+        jito.confirmation_status = None;
+        let timeout = Duration::from_millis(600);
+
+        let started = Instant::now();
+        let status = confirm_bundle(&jito, "mock-bundle-1", timeout)
+            .await
+            .unwrap();
+        let elapsed = started.elapsed();
+        assert_eq!(status, BundleStatus::TimedOut);
+        assert!(elapsed >= timeout, "{:?}", elapsed);
+        assert!(
+            elapsed < timeout + Duration::from_millis(500),
+            "{:?}",
+            elapsed
+        );
+
+        let started = Instant::now();
+        let status = confirm_bundle(&jito, "mock-bundle-1", Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(status, BundleStatus::TimedOut);
+        assert!(started.elapsed() < Duration::from_millis(100));
+    }
+
+    /// 查询到的 bundle 立即返回状态，不等待超时
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn seen_bundle_returns_before_the_timeout() {
+        use crate::testing::MockJito;
+
+        let mut jito = MockJito::new(Pubkey::new_unique());
+        let started = Instant::now();
+        let status = confirm_bundle(&jito, "mock-bundle-1", Duration::from_secs(30))
+            .await
+            .unwrap();
+        assert_eq!(status, BundleStatus::Landed { slot: 1 });
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        jito.confirmation_status = Some("finalized");
+        let status = confirm_bundle(&jito, "mock-bundle-1", Duration::from_secs(30))
+            .await
+            .unwrap();
+        assert_eq!(status, BundleStatus::Finalized { slot: 1 });
+    }
+
+    /// `showExtraInfo=true` 时价格接口返回的结构，价格混用字符串和数字
+    const PRICE_V2_FIXTURE: &str = r#"{
+        "data": {
+            "So11111111111111111111111111111111111111112": {
+                "id": "So11111111111111111111111111111111111111112",
+                "type": "derivedPrice",
+                "price": "150.25",
+                "extraInfo": {
+                    "lastSwappedPrice": {
+                        "lastJupiterSellAt": 1726231876,
+                        "lastJupiterSellPrice": "150.1",
+                        "lastJupiterBuyAt": 1726231877,
+                        "lastJupiterBuyPrice": 150.3
+                    },
+                    "quotedPrice": {
+                        "buyPrice": "150.35",
+                        "buyAt": 1726231876,
+                        "sellPrice": 150.15,
+                        "sellAt": 1726231878
+                    },
+                    "confidenceLevel": "high",
+                    "depth": { "buyPriceImpactRatio": { "depth": { "10": 0.01 } } }
                 }
+            },
+            "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v": {
+                "id": "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v",
+                "type": "derivedPrice",
+                "price": 1.0001
+            },
+            "4k3Dyjzvzp8eMZWUXbBCjEvwSkkk59S5iCNLY3QrkX6R": {
+                "id": "4k3Dyjzvzp8eMZWUXbBCjEvwSkkk59S5iCNLY3QrkX6R",
+                "type": "derivedPrice",
+                "price": null
+            },
+            "11111111111111111111111111111111": null
+        },
+        "timeTaken": 0.0032
+    }"#;
+
+    #[test]
+    fn price_v2_fixture_deserializes() {
+        let resp: PriceV2Response = serde_json::from_str(PRICE_V2_FIXTURE).unwrap();
+        assert_eq!(resp.data.len(), 4);
+
+        let sol = resp.data["So11111111111111111111111111111111111111112"]
+            .as_ref()
+            .unwrap();
+        assert_eq!(sol.price, Some(150.25));
+        let extra = sol.extra_info.as_ref().unwrap();
+        assert_eq!(extra.confidence_level.as_deref(), Some("high"));
+        let quoted = extra.quoted_price.as_ref().unwrap();
+        assert_eq!(quoted.buy_price, Some(150.35));
+        assert_eq!(quoted.sell_price, Some(150.15));
+        assert!((sol.confidence().unwrap() - 0.1).abs() < 1e-9);
+        assert_eq!(sol.quoted_at_ms(), Some(1_726_231_878_000));
+        // 买入比卖出晚，取买入价格
+        assert_eq!(sol.last_swap_price(), Some(150.3));
+
+        let usdc = resp.data["EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v"]
+            .as_ref()
+            .unwrap();
+        assert_eq!(usdc.price, Some(1.0001));
+        assert!(usdc.extra_info.is_none());
+        assert_eq!(usdc.confidence(), None);
+        assert_eq!(usdc.quoted_at_ms(), None);
+
+        let unpriced = resp.data["4k3Dyjzvzp8eMZWUXbBCjEvwSkkk59S5iCNLY3QrkX6R"]
+            .as_ref()
+            .unwrap();
+        assert_eq!(unpriced.price, None);
+        assert!(resp.data["11111111111111111111111111111111"].is_none());
+    }
+
+    #[test]
+    fn missing_data_and_partial_extra_info_are_tolerated() {
+        let resp: PriceV2Response = serde_json::from_str(r#"{ "timeTaken": 0.001 }"#).unwrap();
+        assert!(resp.data.is_empty());
+
+        let datum: PriceDatum = serde_json::from_str(
+            r#"{ "price": " 2.5 ", "extraInfo": { "lastSwappedPrice": { "lastJupiterSellPrice": "2.4" } } }"#,
+        )
+        .unwrap();
+        assert_eq!(datum.price, Some(2.5));
+        assert_eq!(datum.last_swap_price(), Some(2.4));
+        assert_eq!(datum.confidence(), None);
+    }
+
+    #[test]
+    fn invalid_prices_are_rejected() {
+        for price in [r#""abc""#, "true", "[1.0]"] {
+            let json = format!(r#"{{ "price": {} }}"#, price);
+            let err = serde_json::from_str::<PriceDatum>(&json).unwrap_err();
+            assert!(err.to_string().contains("价格无效"), "{}", err);
+        }
+    }
+
+    /// 读取 mint 账户：spl-token、Token-2022 的转账手续费扩展，以及不存在或无效的账户
+    #[cfg(feature = "testing")]
+    mod mint_info {
+        use solana_sdk::{account::Account, program_option::COption};
+        use spl_token_2022::extension::{
+            transfer_fee::TransferFee as ExtensionFee, BaseStateWithExtensionsMut, ExtensionType,
+            StateWithExtensionsMut,
+        };
+
+        use super::*;
+        use crate::testing::MockRpc;
+
+        /// Token-2022 mint 账户，`fees` 为转账手续费扩展中较早和较新一档的 `(bps, maximum_fee)`
+        fn token_2022_mint(decimals: u8, fees: Option<((u16, u64), (u16, u64))>) -> Account {
+            let extensions: &[ExtensionType] = if fees.is_some() {
+                &[ExtensionType::TransferFeeConfig]
+            } else {
+                &[]
+            };
+            let len =
+                ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(extensions)
+                    .unwrap();
+            let mut data = vec![0; len];
+            let mut state =
+                StateWithExtensionsMut::<spl_token_2022::state::Mint>::unpack_uninitialized(
+                    &mut data,
+                )
+                .unwrap();
+            if let Some((older, newer)) = fees {
+                let fee = |(bps, maximum_fee): (u16, u64)| ExtensionFee {
+                    epoch: 0.into(),
+                    maximum_fee: maximum_fee.into(),
+                    transfer_fee_basis_points: bps.into(),
+                };
+                let config = state.init_extension::<TransferFeeConfig>(true).unwrap();
+                config.older_transfer_fee = fee(older);
+                config.newer_transfer_fee = fee(newer);
+            }
+            state.base = spl_token_2022::state::Mint {
+                mint_authority: COption::None,
+                supply: 0,
+                decimals,
+                is_initialized: true,
+                freeze_authority: COption::None,
+            };
+            state.pack_base();
+            if fees.is_some() {
+                state.init_account_type().unwrap();
+            }
+            Account {
+                lamports: 1_461_600,
+                data,
+                owner: TOKEN_2022_PROGRAM_ID,
+                executable: false,
+                rent_epoch: 0,
+            }
+        }
+
+        #[tokio::test]
+        async fn spl_token_mint_has_no_transfer_fee() {
+            let rpc = MockRpc::new();
+            let mint = Pubkey::new_unique();
+            rpc.set_mint(mint, spl_token::id(), 6);
+            let info = get_mint_info(&rpc, &mint).await.unwrap();
+            assert_eq!(info.token_program, spl_token::id());
+            assert_eq!(info.decimals, 6);
+            assert_eq!(info.transfer_fee, None);
+        }
+
+        /// 两档费率不同时取较高的费率和较高的上限
+        #[tokio::test]
+        async fn token_2022_transfer_fee_takes_the_higher_tier() {
+            let rpc = MockRpc::new();
+            let mint = Pubkey::new_unique();
+            rpc.set_account(mint, token_2022_mint(9, Some(((50, 10_000), (120, 4_000)))));
+            let info = get_mint_info(&rpc, &mint).await.unwrap();
+            assert_eq!(info.token_program, TOKEN_2022_PROGRAM_ID);
+            assert_eq!(info.decimals, 9);
+            assert_eq!(
+                info.transfer_fee,
+                Some(TransferFee {
+                    bps: 120,
+                    maximum_fee: 10_000,
+                })
+            );
+
+            // 与测试工具写入的 mint 一致
+            let other = Pubkey::new_unique();
+            rpc.set_mint_with_transfer_fee(other, 6, 30, 1_000);
+            let info = get_mint_info(&rpc, &other).await.unwrap();
+            assert_eq!(
+                info.transfer_fee,
+                Some(TransferFee {
+                    bps: 30,
+                    maximum_fee: 1_000,
+                })
+            );
+        }
+
+        /// 没有扩展，或扩展中的费率或上限为 0 时不收手续费
+        #[tokio::test]
+        async fn token_2022_without_effective_fee() {
+            let rpc = MockRpc::new();
+            let cases = [
+                token_2022_mint(6, None),
+                token_2022_mint(6, Some(((0, 10_000), (0, 10_000)))),
+                token_2022_mint(6, Some(((100, 0), (100, 0)))),
+            ];
+            for account in cases {
+                let mint = Pubkey::new_unique();
+                rpc.set_account(mint, account);
+                let info = get_mint_info(&rpc, &mint).await.unwrap();
+                assert_eq!(info.token_program, TOKEN_2022_PROGRAM_ID);
+                assert_eq!(info.transfer_fee, None);
+            }
+        }
+
+        #[tokio::test]
+        async fn missing_or_short_mint_is_an_error() {
+            let rpc = MockRpc::new();
+            let missing = Pubkey::new_unique();
+            let err = get_mint_info(&rpc, &missing).await.unwrap_err();
+            assert!(err.to_string().contains("不存在"), "{}", err);
+
+            let short = Pubkey::new_unique();
+            rpc.set_account(
+                short,
+                Account {
+                    lamports: 1,
+                    data: vec![0; 10],
+                    owner: spl_token::id(),
+                    executable: false,
+                    rent_epoch: 0,
+                },
+            );
+            let err = get_mint_info(&rpc, &short).await.unwrap_err();
+            assert!(err.to_string().contains("不是有效的 mint"), "{}", err);
+        }
+    }
+
+    /// 对本地模拟的价格接口请求价格：限流、无法解析的响应和缺失的代币
+    #[cfg(feature = "testing")]
+    mod prices {
+        use wiremock::{
+            matchers::{method, query_param},
+            Mock, MockServer, ResponseTemplate,
+        };
+
+        use super::*;
+
+        const SOL_MINT: &str = "So11111111111111111111111111111111111111112";
+        const USDC_MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+
+        fn policy() -> PriceRequestPolicy {
+            PriceRequestPolicy {
+                timeout: Duration::from_millis(500),
+                attempts: 3,
+                base_delay: Duration::from_millis(10),
             }
         }
+
+        async fn prices(
+            server: &MockServer,
+            mints: &[&str],
+        ) -> Result<HashMap<String, PriceDatum>> {
+            get_prices(
+                Arc::new(Client::new()),
+                &server.uri(),
+                mints,
+                false,
+                policy(),
+            )
+            .await
+        }
+
+        #[tokio::test]
+        async fn rate_limited_requests_are_retried() {
+            let server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .respond_with(ResponseTemplate::new(429))
+                .up_to_n_times(2)
+                .with_priority(1)
+                .mount(&server)
+                .await;
+            Mock::given(method("GET"))
+                .and(query_param("ids", format!("{},{}", SOL_MINT, USDC_MINT)))
+                .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                    "data": {
+                        SOL_MINT: { "id": SOL_MINT, "type": "derivedPrice", "price": "150.25" },
+                        USDC_MINT: { "id": USDC_MINT, "type": "derivedPrice", "price": 1.0 },
+                    },
+                    "timeTaken": 0.003
+                })))
+                .mount(&server)
+                .await;
+
+            let prices = prices(&server, &[SOL_MINT, USDC_MINT]).await.unwrap();
+            assert_eq!(prices[SOL_MINT].price, Some(150.25));
+            assert_eq!(prices[USDC_MINT].price, Some(1.0));
+            assert_eq!(server.received_requests().await.unwrap().len(), 3);
+        }
+
+        #[tokio::test]
+        async fn persistent_rate_limiting_gives_up_after_the_last_attempt() {
+            let server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .respond_with(ResponseTemplate::new(429))
+                .mount(&server)
+                .await;
+
+            let err = prices(&server, &[SOL_MINT]).await.unwrap_err();
+            assert!(err.to_string().contains("429"), "{}", err);
+            assert_eq!(
+                server.received_requests().await.unwrap().len(),
+                policy().attempts as usize
+            );
+        }
+
+        #[tokio::test]
+        async fn malformed_json_is_not_retried() {
+            let server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .respond_with(
+                    ResponseTemplate::new(200).set_body_string("<html>bad gateway</html>"),
+                )
+                .mount(&server)
+                .await;
+
+            let err = prices(&server, &[SOL_MINT]).await.unwrap_err();
+            assert!(err.to_string().contains("无法解析"), "{}", err);
+            assert_eq!(server.received_requests().await.unwrap().len(), 1);
+        }
+
+        #[tokio::test]
+        async fn missing_mints_are_left_out() {
+            let server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                    "data": {
+                        SOL_MINT: { "id": SOL_MINT, "type": "derivedPrice", "price": "150.25" },
+                        USDC_MINT: null,
+                    },
+                    "timeTaken": 0.003
+                })))
+                .mount(&server)
+                .await;
+
+            let prices = prices(&server, &[SOL_MINT, USDC_MINT]).await.unwrap();
+            assert_eq!(prices.len(), 1);
+            assert!(!prices.contains_key(USDC_MINT));
+        }
     }
-    Err(anyhow!("未获得代币 {} 的价格", mint))
 }
@@ -0,0 +1,341 @@
+use std::{
+    env,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use reqwest::{Client, Url};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    common::types::{Order, OrderStatus},
+    solana::fill::FillReport,
+};
+
+/// 回调的最大尝试次数
+const MAX_ATTEMPTS: u32 = 3;
+/// 第一次重试前的等待时间，之后每次翻倍
+const BASE_BACKOFF: Duration = Duration::from_millis(500);
+/// 单次回调请求的默认超时
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// 订单回调配置
+#[derive(Debug, Clone, Copy)]
+pub struct WebhookConfig {
+    /// 是否允许回调到 localhost 和内网地址
+    pub allow_private: bool,
+    /// 单次回调请求的超时
+    pub timeout: Duration,
+}
+
+impl WebhookConfig {
+    /// 从环境变量 `WEBHOOK_ALLOW_PRIVATE`（默认 false）和 `WEBHOOK_TIMEOUT_MS` 读取
+    pub fn from_env() -> Result<WebhookConfig> {
+        let allow_private = match env::var("WEBHOOK_ALLOW_PRIVATE") {
+            Ok(v) => v.parse()?,
+            Err(_) => false,
+        };
+        let timeout = match env::var("WEBHOOK_TIMEOUT_MS") {
+            Ok(v) => Duration::from_millis(v.parse()?),
+            Err(_) => DEFAULT_TIMEOUT,
+        };
+        Ok(WebhookConfig {
+            allow_private,
+            timeout,
+        })
+    }
+}
+
+/// 订单结束时发送给 `callback_url` 的内容
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookPayload {
+    pub order_id: Uuid,
+    pub status: OrderStatus,
+    /// 最后一笔交易的签名
+    pub signature: Option<String>,
+    /// 通过 Jito bundle 发送时的 bundle id
+    pub bundle_id: Option<String>,
+    /// 最近一次成交实际得到的输出数量（已扣税），未成交时为 None
+    pub out_amount: Option<u64>,
+    /// 最近一次成交的实际数量、成交价格和相对触发价格的偏离
+    pub fill: Option<FillReport>,
+    /// 失败原因
+    pub error: Option<String>,
+}
+
+impl WebhookPayload {
+    pub fn new(order: &Order, signature: Option<String>, bundle_id: Option<String>) -> Self {
+        let error = match &order.status {
+            OrderStatus::Failed(e) => Some(e.clone()),
+            OrderStatus::ResignRequired => Some("签名交易已过期，需要重新签名".to_string()),
+            OrderStatus::Unfilled(reason) => Some(reason.clone()),
+            OrderStatus::Expired => Some("订单在有效期内未触发".to_string()),
+            _ => None,
+        };
+        WebhookPayload {
+            order_id: order.order_id,
+            status: order.status.clone(),
+            signature,
+            bundle_id,
+            out_amount: order.last_fill.map(|fill| fill.out_amount),
+            fill: order.last_fill,
+            error,
+        }
+    }
+}
+
+/// 校验回调地址：只允许 http/https，未开启 `allow_private` 时拒绝 localhost 和内网 IP
+///
+/// 只检查字面量 IP 和 localhost 域名，不会解析域名。
+pub fn validate_callback_url(url: &str, allow_private: bool) -> Result<Url> {
+    let parsed = Url::parse(url).map_err(|_| anyhow!("回调地址无效 {}", url))?;
+    if parsed.scheme() != "http" && parsed.scheme() != "https" {
+        return Err(anyhow!("回调地址只支持 http 和 https"));
+    }
+    let host = parsed
+        .host_str()
+        .ok_or_else(|| anyhow!("回调地址缺少主机 {}", url))?;
+    if !allow_private && is_private_host(host) {
+        return Err(anyhow!("回调地址不能指向本机或内网 {}", host));
+    }
+    Ok(parsed)
+}
+
+fn is_private_host(host: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.eq_ignore_ascii_case("localhost") || host.to_ascii_lowercase().ends_with(".localhost") {
+        return true;
+    }
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => is_private_v4(ip),
+        Ok(IpAddr::V6(ip)) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_private_v4(ip),
+            None => is_private_v6(ip),
+        },
+        Err(_) => false,
+    }
+}
+
+fn is_private_v4(ip: Ipv4Addr) -> bool {
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        // 100.64.0.0/10 运营商级 NAT
+        || (ip.octets()[0] == 100 && (ip.octets()[1] & 0xc0) == 64)
+}
+
+fn is_private_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    ip.is_loopback()
+        || ip.is_unspecified()
+        // fc00::/7 唯一本地地址
+        || (first & 0xfe00) == 0xfc00
+        // fe80::/10 链路本地地址
+        || (first & 0xffc0) == 0xfe80
+}
+
+/// 发送回调，失败时退避重试，最多尝试 [`MAX_ATTEMPTS`] 次；每次请求最多等待 `timeout`
+pub async fn deliver(
+    http: &Client,
+    url: &str,
+    payload: &WebhookPayload,
+    timeout: Duration,
+) -> Result<()> {
+    let mut attempt = 0;
+    loop {
+        let result = http.post(url).json(payload).timeout(timeout).send().await;
+        let e = match result {
+            Ok(resp) if resp.status().is_success() => return Ok(()),
+            Ok(resp) => anyhow!("回调返回错误状态 {}", resp.status()),
+            Err(e) => e.into(),
+        };
+        attempt += 1;
+        if attempt >= MAX_ATTEMPTS {
+            return Err(e.context(format!("回调失败，已尝试 {} 次", attempt)));
+        }
+        tokio::time::sleep(BASE_BACKOFF * 2u32.pow(attempt - 1)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_http_and_https_are_allowed() {
+        assert!(validate_callback_url("https://example.com/hook", false).is_ok());
+        assert!(validate_callback_url("http://example.com/hook", false).is_ok());
+        for url in [
+            "ftp://example.com/hook",
+            "file:///etc/passwd",
+            "ws://example.com/hook",
+            "example.com/hook",
+        ] {
+            assert!(validate_callback_url(url, false).is_err(), "{}", url);
+            assert!(validate_callback_url(url, true).is_err(), "{}", url);
+        }
+    }
+
+    #[test]
+    fn private_hosts_are_rejected_unless_allowed() {
+        for url in [
+            "http://localhost/hook",
+            "http://LOCALHOST:8080/hook",
+            "http://api.localhost/hook",
+            "http://127.0.0.1/hook",
+            "http://10.1.2.3/hook",
+            "http://172.16.0.1/hook",
+            "http://192.168.1.1/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://100.64.0.1/hook",
+            "http://0.0.0.0/hook",
+            "http://[::1]/hook",
+            "http://[fc00::1]/hook",
+            "http://[fe80::1]/hook",
+            "http://[::ffff:10.0.0.1]/hook",
+        ] {
+            assert!(validate_callback_url(url, false).is_err(), "{}", url);
+            assert!(validate_callback_url(url, true).is_ok(), "{}", url);
+        }
+    }
+
+    #[test]
+    fn public_hosts_are_allowed() {
+        for url in [
+            "https://8.8.8.8/hook",
+            "https://100.128.0.1/hook",
+            "https://172.32.0.1/hook",
+            "https://[2001:4860:4860::8888]/hook",
+            "https://hooks.example.com:8443/orders?token=abc",
+        ] {
+            assert!(validate_callback_url(url, false).is_ok(), "{}", url);
+        }
+    }
+
+    /// 本地回调服务记录收到的请求
+    #[cfg(feature = "testing")]
+    mod delivery {
+        use serde_json::{json, Value};
+        use solana_sdk::pubkey::Pubkey;
+        use wiremock::{
+            matchers::{method, path},
+            Mock, MockServer, ResponseTemplate,
+        };
+
+        use super::*;
+        use crate::SOL;
+
+        fn failed_order() -> Order {
+            let mut order: Order = serde_json::from_value(json!({
+                "order_id": Uuid::new_v4(),
+                "owner": Pubkey::new_unique().to_string(),
+                "price": 150.0,
+                "input_mint": SOL.to_string(),
+                "output_mint": Pubkey::new_unique().to_string(),
+                "amount": 1_000_000,
+                "slippage_bps": 50,
+            }))
+            .unwrap();
+            order.status = OrderStatus::Failed("模拟执行失败".to_string());
+            order
+        }
+
+        async fn received_bodies(server: &MockServer) -> Vec<Value> {
+            server
+                .received_requests()
+                .await
+                .unwrap()
+                .iter()
+                .map(|request| serde_json::from_slice(&request.body).unwrap())
+                .collect()
+        }
+
+        #[tokio::test]
+        async fn delivers_the_payload_as_json() {
+            let server = MockServer::start().await;
+            Mock::given(method("POST"))
+                .and(path("/hook"))
+                .respond_with(ResponseTemplate::new(200))
+                .mount(&server)
+                .await;
+            let order = failed_order();
+            let payload = WebhookPayload::new(&order, Some("sig".to_string()), None);
+
+            deliver(
+                &Client::new(),
+                &format!("{}/hook", server.uri()),
+                &payload,
+                Duration::from_secs(5),
+            )
+            .await
+            .unwrap();
+
+            let bodies = received_bodies(&server).await;
+            assert_eq!(bodies.len(), 1);
+            assert_eq!(bodies[0]["order_id"], order.order_id.to_string());
+            assert_eq!(bodies[0]["status"], json!({ "Failed": "模拟执行失败" }));
+            assert_eq!(bodies[0]["signature"], "sig");
+            assert_eq!(bodies[0]["bundle_id"], Value::Null);
+            assert_eq!(bodies[0]["error"], "模拟执行失败");
+        }
+
+        /// 回调返回错误状态时重试，成功后不再发送
+        #[tokio::test]
+        async fn retries_after_an_error_status() {
+            let server = MockServer::start().await;
+            Mock::given(method("POST"))
+                .respond_with(ResponseTemplate::new(503))
+                .up_to_n_times(1)
+                .with_priority(1)
+                .mount(&server)
+                .await;
+            Mock::given(method("POST"))
+                .respond_with(ResponseTemplate::new(200))
+                .mount(&server)
+                .await;
+            let payload = WebhookPayload::new(&failed_order(), None, None);
+
+            deliver(
+                &Client::new(),
+                &server.uri(),
+                &payload,
+                Duration::from_secs(5),
+            )
+            .await
+            .unwrap();
+
+            let bodies = received_bodies(&server).await;
+            assert_eq!(bodies.len(), 2);
+            assert_eq!(bodies[0], bodies[1]);
+        }
+
+        /// 一直失败时尝试 [`MAX_ATTEMPTS`] 次后返回错误
+        #[tokio::test]
+        async fn gives_up_after_max_attempts() {
+            let server = MockServer::start().await;
+            Mock::given(method("POST"))
+                .respond_with(ResponseTemplate::new(500))
+                .mount(&server)
+                .await;
+            let payload = WebhookPayload::new(&failed_order(), None, None);
+
+            let err = deliver(
+                &Client::new(),
+                &server.uri(),
+                &payload,
+                Duration::from_secs(5),
+            )
+            .await
+            .unwrap_err();
+            assert!(format!("{:#}", err).contains("500"));
+            assert_eq!(
+                server.received_requests().await.unwrap().len(),
+                MAX_ATTEMPTS as usize
+            );
+        }
+    }
+}
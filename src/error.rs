@@ -0,0 +1,95 @@
+use thiserror::Error;
+
+/// 对外接口返回的错误
+///
+/// 内部仍然使用 anyhow 传递错误；在内部构造的 `LimitOrderError` 经过 anyhow 传递后，
+/// 在对外接口处会被还原为原来的类型，其余错误归为 `Other`。
+#[derive(Debug, Error)]
+pub enum LimitOrderError {
+    #[error("报价失败 {0}")]
+    QuoteFailed(String),
+    #[error("价格源不可用 {0}")]
+    PriceFeedUnavailable(String),
+    #[error("模拟执行失败 {logs}")]
+    SimulationFailed { logs: String },
+    #[error("bundle 未上链 {0}")]
+    BundleDropped(String),
+    #[error("私有发送失败，未改用公开 RPC {0}")]
+    PrivateExecutionFailed(String),
+    #[error("订单未找到")]
+    OrderNotFound,
+    #[error("订单已触发")]
+    OrderAlreadyTriggered,
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error("参数 {field} 无效 {reason}")]
+    InvalidRequest { field: &'static str, reason: String },
+    #[error("解密失败 {0}")]
+    DecryptFailed(String),
+    #[error("数据库不可用 {0}")]
+    DatabaseUnavailable(String),
+    #[error("扣税后输出数量 {out_amount} 低于最低输出 {min_out_amount}")]
+    MinOutNotMet {
+        out_amount: u64,
+        min_out_amount: u64,
+    },
+    #[error("价格影响 {impact_bps} bps 超过上限 {max_bps} bps")]
+    PriceImpactTooHigh { impact_bps: u64, max_bps: u16 },
+    #[error("报价隐含的成交价格 {execution_price} 偏离触发价格 {trigger_price} {deviation_bps} bps，超过上限 {max_bps} bps")]
+    TriggerDeviationTooHigh {
+        trigger_price: f64,
+        execution_price: f64,
+        deviation_bps: u64,
+        max_bps: u16,
+    },
+    #[error("余额不足：{mint} 需要 {required}，可用 {available}")]
+    InsufficientFunds {
+        mint: String,
+        required: u64,
+        available: u64,
+    },
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl LimitOrderError {
+    /// 机器可读的错误码，`Other` 没有固定的错误码
+    pub fn code(&self) -> Option<&'static str> {
+        match self {
+            LimitOrderError::QuoteFailed(_) => Some("QUOTE_FAILED"),
+            LimitOrderError::PriceFeedUnavailable(_) => Some("PRICE_FEED_UNAVAILABLE"),
+            LimitOrderError::SimulationFailed { .. } => Some("SIMULATION_FAILED"),
+            LimitOrderError::BundleDropped(_) => Some("BUNDLE_DROPPED"),
+            LimitOrderError::PrivateExecutionFailed(_) => Some("PRIVATE_EXECUTION_FAILED"),
+            LimitOrderError::OrderNotFound => Some("ORDER_NOT_FOUND"),
+            LimitOrderError::OrderAlreadyTriggered => Some("ORDER_ALREADY_TRIGGERED"),
+            LimitOrderError::Unauthorized(_) => Some("UNAUTHORIZED"),
+            LimitOrderError::InvalidRequest { .. } => Some("INVALID_REQUEST"),
+            LimitOrderError::DecryptFailed(_) => Some("DECRYPT_FAILED"),
+            LimitOrderError::DatabaseUnavailable(_) => Some("DATABASE_UNAVAILABLE"),
+            LimitOrderError::MinOutNotMet { .. } => Some("MIN_OUT_NOT_MET"),
+            LimitOrderError::PriceImpactTooHigh { .. } => Some("PRICE_IMPACT_TOO_HIGH"),
+            LimitOrderError::TriggerDeviationTooHigh { .. } => Some("TRIGGER_DEVIATION_TOO_HIGH"),
+            LimitOrderError::InsufficientFunds { .. } => Some("INSUFFICIENT_FUNDS"),
+            LimitOrderError::Other(_) => None,
+        }
+    }
+
+    pub fn invalid(field: &'static str, reason: impl Into<String>) -> LimitOrderError {
+        LimitOrderError::InvalidRequest {
+            field,
+            reason: reason.into(),
+        }
+    }
+}
+
+impl From<anyhow::Error> for LimitOrderError {
+    fn from(e: anyhow::Error) -> LimitOrderError {
+        match e.downcast::<LimitOrderError>() {
+            Ok(e) => e,
+            Err(e) => LimitOrderError::Other(e),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, LimitOrderError>;
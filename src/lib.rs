@@ -3,4 +3,7 @@ use solana_sdk::pubkey::Pubkey;
 pub const SOL: Pubkey = pubkey!("So11111111111111111111111111111111111111112");
 pub mod app;
 pub mod common;
+pub mod error;
 pub mod solana;
+#[cfg(feature = "testing")]
+pub mod testing;
@@ -1,17 +1,112 @@
 use anyhow::Context;
-use limit_order::app::{cancel_order, place_order};
-use limit_order::common::types::OrderBook;
-use rocket::{launch, routes};
+use limit_order::app::openapi::{openapi_json, swagger_ui, unlisted_routes};
+use limit_order::app::{
+    api_routes, bad_request, forbidden, too_many_requests, unauthorized, unprocessable_entity,
+};
+use limit_order::common::{
+    auth::ApiKeys, config::AppConfig, db::MysqlOrderStore, keys::install, rate_limit::RateLimiter,
+    types::OrderBook,
+};
+use limit_order::solana::jito::refresh_tip_accounts;
+use rocket::{catchers, fairing::AdHoc, launch, routes};
+use std::sync::Arc;
 use tokio::sync::Mutex;
 
 #[launch]
-fn rocket() -> _ {
+async fn rocket() -> _ {
     dotenv::dotenv().ok();
-    let order_book = OrderBook::new().context("环境变量配置失败").unwrap();
+    // 启动时一次性校验全部配置，列出所有缺失或无效的环境变量后退出
+    let config = AppConfig::from_env().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    let mut order_book = OrderBook::new(&config).context("初始化订单簿失败").unwrap();
+    if let Some(pool) = order_book.db.clone() {
+        order_book.enable_persistence(Arc::new(MysqlOrderStore::new(pool)));
+    }
+    let rate_limiter = RateLimiter::spawn(config.rate_limit);
+    if config.api_keys.is_empty() {
+        println!("未配置 API_KEYS，接口不启用认证，管理接口不可用");
+    }
+    let api_keys = ApiKeys::new(config.api_keys);
+    if api_keys.is_enabled() && !api_keys.has_admin() {
+        println!("API_KEYS 中没有 admin key，管理接口不可用");
+    }
+    let keys = config
+        .keys
+        .load()
+        .await
+        .context("加载加密密钥失败")
+        .unwrap();
+    install(keys).context("加密密钥配置失败").unwrap();
+    // 价格缓存单独托管，查询价格不需要订单簿的锁
+    let prices = order_book.prices.clone();
     let order_book_state = Mutex::new(order_book);
+    let mut docs_routes = routes![openapi_json];
+    if config.swagger_ui {
+        docs_routes.extend(routes![swagger_ui]);
+    }
 
     // 配置并启动 Rocket 实例
     rocket::build()
         .manage(order_book_state) // 将 OrderBook 添加到 Rocket 的托管状态中
-        .mount("/", routes![place_order, cancel_order]) // 挂载路由
+        .manage(prices)
+        .manage(rate_limiter)
+        .manage(api_keys)
+        .register(
+            "/",
+            catchers![
+                unauthorized,
+                forbidden,
+                too_many_requests,
+                bad_request,
+                unprocessable_entity
+            ],
+        )
+        .manage(limit_order::common::metrics::metrics())
+        .attach(AdHoc::on_liftoff("恢复订单快照", |rocket| {
+            Box::pin(async move {
+                if let Some(order_book) = rocket.state::<Mutex<OrderBook>>() {
+                    let mut order_book = order_book.lock().await;
+                    match order_book.restore_snapshot().await {
+                        Ok(count) => println!("已恢复 {} 笔订单", count),
+                        Err(e) => println!("恢复订单快照失败 {:?}", e),
+                    }
+                    // 进程意外退出时没有快照，从数据库恢复
+                    match order_book.restore_unfinished().await {
+                        Ok(0) => {}
+                        Ok(count) => println!("已从数据库恢复 {} 笔未结束的订单", count),
+                        Err(e) => println!("从数据库恢复订单失败 {:?}", e),
+                    }
+                }
+            })
+        }))
+        .attach(AdHoc::on_liftoff("检查接口文档", |rocket| {
+            Box::pin(async move {
+                for route in unlisted_routes(rocket.routes()) {
+                    println!("路由 {} 未登记到 OpenAPI 文档", route);
+                }
+            })
+        }))
+        .attach(AdHoc::on_liftoff("获取 Jito tip 账户", |rocket| {
+            Box::pin(async move {
+                if let Some(order_book) = rocket.state::<Mutex<OrderBook>>() {
+                    let jito = order_book.lock().await.jito.clone();
+                    if let Err(e) = refresh_tip_accounts(&jito).await {
+                        println!("获取 Jito tip 账户失败，使用内置列表 {:?}", e);
+                    }
+                }
+            })
+        }))
+        .attach(AdHoc::on_shutdown("保存订单快照", |rocket| {
+            Box::pin(async move {
+                if let Some(order_book) = rocket.state::<Mutex<OrderBook>>() {
+                    if let Err(e) = order_book.lock().await.shutdown().await {
+                        println!("保存订单快照失败 {:?}", e);
+                    }
+                }
+            })
+        }))
+        .mount("/", api_routes()) // 挂载路由
+        .mount("/", docs_routes)
 }
@@ -0,0 +1,448 @@
+//! 对 Solana RPC、Jupiter 和 Jito 客户端的精简抽象
+//!
+//! 订单簿和交换流程只依赖这里的 trait，生产环境使用真实客户端的实现，
+//! `testing` 特性下的模拟客户端实现同样的 trait，可以在不访问网络的情况下走完整个交换流程。
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use jito_sdk_rust::JitoJsonRpcSDK;
+use jupiter_swap_api_client::{
+    quote::{QuoteRequest, QuoteResponse},
+    swap::{SwapInstructionsResponse, SwapRequest},
+    JupiterSwapApiClient,
+};
+use serde_json::json;
+use serde_json::Value;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    nonblocking::rpc_client::RpcClient,
+    rpc_config::{RpcSimulateTransactionAccountsConfig, RpcSimulateTransactionConfig},
+    rpc_request::{RpcRequest, TokenAccountsFilter},
+    rpc_response::RpcKeyedAccount,
+};
+use solana_sdk::{
+    account::Account, commitment_config::CommitmentConfig, hash::Hash, instruction::Instruction,
+    pubkey::Pubkey, signature::Signature, transaction::VersionedTransaction,
+};
+
+use super::multi_rpc::EndpointStatus;
+
+/// 订单簿和交换流程用到的 Solana RPC 请求
+#[async_trait]
+pub trait SolanaRpc: Send + Sync {
+    async fn get_latest_blockhash(&self) -> Result<Hash>;
+
+    /// 最新的 blockhash 及其最后有效区块高度
+    async fn get_latest_blockhash_with_height(&self) -> Result<(Hash, u64)>;
+
+    /// 当前区块高度，用于判断 blockhash 是否过期
+    async fn get_block_height(&self) -> Result<u64>;
+
+    /// 当前 slot，用于按 slot 控制重发的节奏
+    async fn get_slot(&self) -> Result<u64>;
+
+    /// 按顺序返回账户，不存在的账户为 None
+    async fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>>;
+
+    /// 模拟执行交易
+    async fn simulate(&self, tx: &VersionedTransaction) -> Result<Simulation>;
+
+    /// 模拟执行交易，同时在 [`Simulation::accounts`] 中按顺序返回 `addresses` 在交易执行后的状态
+    async fn simulate_with_accounts(
+        &self,
+        tx: &VersionedTransaction,
+        addresses: &[Pubkey],
+    ) -> Result<Simulation>;
+
+    /// 发送交易并等待确认
+    ///
+    /// 返回错误（超时、连接断开等）不代表交易没有上链，需要通过 [`SolanaRpc::signature_status`] 确认。
+    async fn send(&self, tx: &VersionedTransaction) -> Result<Signature>;
+
+    /// 查询签名的确认状态，包括历史交易
+    async fn signature_status(&self, signature: &Signature) -> Result<SignatureStatus>;
+
+    /// 按顺序查询多个签名的确认状态，包括历史交易
+    async fn signature_statuses(&self, signatures: &[Signature]) -> Result<Vec<SignatureStatus>> {
+        let mut statuses = Vec::with_capacity(signatures.len());
+        for signature in signatures {
+            statuses.push(self.signature_status(signature).await?);
+        }
+        Ok(statuses)
+    }
+
+    /// 最近区块的优先费（micro-lamports / CU），`accounts` 为空时按全网计算
+    async fn get_recent_prioritization_fees(&self, accounts: &[Pubkey]) -> Result<Vec<u64>>;
+
+    /// 以 `jsonParsed` 编码获取已确认的交易，节点查不到时返回 None
+    async fn get_parsed_transaction(&self, signature: &Signature) -> Result<Option<Value>>;
+
+    /// `owner` 名下属于 `token_program` 的代币账户，账户数据为 `jsonParsed` 编码
+    async fn get_token_accounts_by_owner(
+        &self,
+        owner: &Pubkey,
+        token_program: &Pubkey,
+    ) -> Result<Vec<RpcKeyedAccount>>;
+
+    /// 大小为 `data_len` 的账户免租所需的最低余额
+    async fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> Result<u64>;
+
+    /// 账户的 lamports，不存在的账户为 0
+    async fn get_balance(&self, pubkey: &Pubkey) -> Result<u64> {
+        Ok(self
+            .get_account(pubkey)
+            .await?
+            .map_or(0, |account| account.lamports))
+    }
+
+    /// 查询单个账户，不存在时返回 None
+    async fn get_account(&self, pubkey: &Pubkey) -> Result<Option<Account>> {
+        Ok(self
+            .get_multiple_accounts(&[*pubkey])
+            .await?
+            .into_iter()
+            .next()
+            .flatten())
+    }
+
+    /// 各节点的健康状态，单节点的客户端为空
+    fn endpoint_status(&self) -> Vec<EndpointStatus> {
+        vec![]
+    }
+}
+
+/// 交易签名的确认状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureStatus {
+    /// 节点查不到该签名
+    Unknown,
+    /// 已处理，尚未达到 confirmed
+    Processed,
+    /// 已确认，`succeeded` 为交易是否执行成功
+    Confirmed { succeeded: bool },
+}
+
+/// 交易模拟的结果
+#[derive(Debug, Clone, Default)]
+pub struct Simulation {
+    /// 交易错误，模拟成功时为 None
+    pub err: Option<String>,
+    pub logs: Vec<String>,
+    pub units_consumed: Option<u64>,
+    /// [`SolanaRpc::simulate_with_accounts`] 请求的账户在交易执行后的状态，不存在的账户为 None
+    pub accounts: Vec<Option<Account>>,
+}
+
+#[async_trait]
+impl SolanaRpc for RpcClient {
+    async fn get_latest_blockhash(&self) -> Result<Hash> {
+        Ok(RpcClient::get_latest_blockhash(self).await?)
+    }
+
+    async fn get_latest_blockhash_with_height(&self) -> Result<(Hash, u64)> {
+        Ok(self
+            .get_latest_blockhash_with_commitment(self.commitment())
+            .await?)
+    }
+
+    async fn get_block_height(&self) -> Result<u64> {
+        Ok(RpcClient::get_block_height(self).await?)
+    }
+
+    async fn get_slot(&self) -> Result<u64> {
+        Ok(RpcClient::get_slot(self).await?)
+    }
+
+    async fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+        Ok(RpcClient::get_multiple_accounts(self, pubkeys).await?)
+    }
+
+    async fn simulate(&self, tx: &VersionedTransaction) -> Result<Simulation> {
+        let resp = self.simulate_transaction(tx).await?;
+        Ok(Simulation {
+            err: resp.value.err.map(|err| err.to_string()),
+            logs: resp.value.logs.unwrap_or_default(),
+            units_consumed: resp.value.units_consumed,
+            accounts: vec![],
+        })
+    }
+
+    async fn simulate_with_accounts(
+        &self,
+        tx: &VersionedTransaction,
+        addresses: &[Pubkey],
+    ) -> Result<Simulation> {
+        let config = RpcSimulateTransactionConfig {
+            commitment: Some(self.commitment()),
+            accounts: Some(RpcSimulateTransactionAccountsConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                addresses: addresses.iter().map(Pubkey::to_string).collect(),
+            }),
+            ..RpcSimulateTransactionConfig::default()
+        };
+        let resp = self.simulate_transaction_with_config(tx, config).await?;
+        Ok(Simulation {
+            err: resp.value.err.map(|err| err.to_string()),
+            logs: resp.value.logs.unwrap_or_default(),
+            units_consumed: resp.value.units_consumed,
+            accounts: resp
+                .value
+                .accounts
+                .unwrap_or_default()
+                .into_iter()
+                .map(|account| account.and_then(|account| account.decode::<Account>()))
+                .collect(),
+        })
+    }
+
+    async fn send(&self, tx: &VersionedTransaction) -> Result<Signature> {
+        Ok(self.send_and_confirm_transaction_with_spinner(tx).await?)
+    }
+
+    async fn signature_status(&self, signature: &Signature) -> Result<SignatureStatus> {
+        let statuses = SolanaRpc::signature_statuses(self, &[*signature]).await?;
+        Ok(statuses
+            .into_iter()
+            .next()
+            .unwrap_or(SignatureStatus::Unknown))
+    }
+
+    async fn signature_statuses(&self, signatures: &[Signature]) -> Result<Vec<SignatureStatus>> {
+        let statuses = self
+            .get_signature_statuses_with_history(signatures)
+            .await?
+            .value;
+        Ok(statuses
+            .into_iter()
+            .map(|status| match status {
+                None => SignatureStatus::Unknown,
+                Some(status) if status.satisfies_commitment(CommitmentConfig::confirmed()) => {
+                    SignatureStatus::Confirmed {
+                        succeeded: status.err.is_none(),
+                    }
+                }
+                Some(_) => SignatureStatus::Processed,
+            })
+            .collect())
+    }
+
+    async fn get_recent_prioritization_fees(&self, accounts: &[Pubkey]) -> Result<Vec<u64>> {
+        Ok(RpcClient::get_recent_prioritization_fees(self, accounts)
+            .await?
+            .into_iter()
+            .map(|fee| fee.prioritization_fee)
+            .collect())
+    }
+
+    async fn get_parsed_transaction(&self, signature: &Signature) -> Result<Option<Value>> {
+        let params = json!([
+            signature.to_string(),
+            {
+                "encoding": "jsonParsed",
+                "commitment": "confirmed",
+                "maxSupportedTransactionVersion": 0
+            }
+        ]);
+        let tx: Value = RpcClient::send(self, RpcRequest::GetTransaction, params).await?;
+        Ok((!tx.is_null()).then_some(tx))
+    }
+
+    async fn get_token_accounts_by_owner(
+        &self,
+        owner: &Pubkey,
+        token_program: &Pubkey,
+    ) -> Result<Vec<RpcKeyedAccount>> {
+        Ok(RpcClient::get_token_accounts_by_owner(
+            self,
+            owner,
+            TokenAccountsFilter::ProgramId(*token_program),
+        )
+        .await?)
+    }
+
+    async fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> Result<u64> {
+        Ok(RpcClient::get_minimum_balance_for_rent_exemption(self, data_len).await?)
+    }
+
+    async fn get_balance(&self, pubkey: &Pubkey) -> Result<u64> {
+        Ok(RpcClient::get_balance(self, pubkey).await?)
+    }
+}
+
+/// Jupiter 交换指令中交换流程用到的部分
+#[derive(Debug, Clone)]
+pub struct SwapInstructions {
+    pub setup_instructions: Vec<Instruction>,
+    pub swap_instruction: Instruction,
+    pub cleanup_instruction: Option<Instruction>,
+    pub address_lookup_table_addresses: Vec<Pubkey>,
+}
+
+impl From<SwapInstructionsResponse> for SwapInstructions {
+    fn from(resp: SwapInstructionsResponse) -> SwapInstructions {
+        SwapInstructions {
+            setup_instructions: resp.setup_instructions,
+            swap_instruction: resp.swap_instruction,
+            cleanup_instruction: resp.cleanup_instruction,
+            address_lookup_table_addresses: resp.address_lookup_table_addresses,
+        }
+    }
+}
+
+/// Jupiter Swap API 的报价和交换指令请求
+///
+/// 请求失败时真实客户端返回的错误为 [`jupiter_swap_api_client::ClientError`]，报价重试据此判断是否可以重试。
+#[async_trait]
+pub trait SwapApi: Send + Sync {
+    async fn quote(&self, request: &QuoteRequest) -> Result<QuoteResponse>;
+
+    async fn swap_instructions(&self, request: &SwapRequest) -> Result<SwapInstructions>;
+}
+
+#[async_trait]
+impl SwapApi for JupiterSwapApiClient {
+    async fn quote(&self, request: &QuoteRequest) -> Result<QuoteResponse> {
+        Ok(JupiterSwapApiClient::quote(self, request).await?)
+    }
+
+    async fn swap_instructions(&self, request: &SwapRequest) -> Result<SwapInstructions> {
+        Ok(JupiterSwapApiClient::swap_instructions(self, request)
+            .await?
+            .into())
+    }
+}
+
+/// Jito 的 bundle 请求，参数和返回均为 JSON-RPC 的原始 JSON
+#[async_trait]
+pub trait BundleSender: Send + Sync {
+    /// 发送 bundle，`bundle` 为编码后的交易数组
+    async fn send_bundle(&self, bundle: Value) -> Result<Value>;
+
+    async fn get_bundle_statuses(&self, bundle_ids: Vec<String>) -> Result<Value>;
+
+    async fn get_tip_accounts(&self) -> Result<Value>;
+}
+
+#[async_trait]
+impl BundleSender for JitoJsonRpcSDK {
+    async fn send_bundle(&self, bundle: Value) -> Result<Value> {
+        JitoJsonRpcSDK::send_bundle(self, Some(bundle), None).await
+    }
+
+    async fn get_bundle_statuses(&self, bundle_ids: Vec<String>) -> Result<Value> {
+        JitoJsonRpcSDK::get_bundle_statuses(self, bundle_ids).await
+    }
+
+    async fn get_tip_accounts(&self) -> Result<Value> {
+        JitoJsonRpcSDK::get_tip_accounts(self).await
+    }
+}
+
+// 共享的客户端以 Arc 保存，转发实现使 `&Arc<_>` 可以直接作为 trait 对象传入
+
+#[async_trait]
+impl<T: SolanaRpc + ?Sized> SolanaRpc for Arc<T> {
+    async fn get_latest_blockhash(&self) -> Result<Hash> {
+        (**self).get_latest_blockhash().await
+    }
+
+    async fn get_latest_blockhash_with_height(&self) -> Result<(Hash, u64)> {
+        (**self).get_latest_blockhash_with_height().await
+    }
+
+    async fn get_block_height(&self) -> Result<u64> {
+        (**self).get_block_height().await
+    }
+
+    async fn get_slot(&self) -> Result<u64> {
+        (**self).get_slot().await
+    }
+
+    async fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+        (**self).get_multiple_accounts(pubkeys).await
+    }
+
+    async fn simulate(&self, tx: &VersionedTransaction) -> Result<Simulation> {
+        (**self).simulate(tx).await
+    }
+
+    async fn simulate_with_accounts(
+        &self,
+        tx: &VersionedTransaction,
+        addresses: &[Pubkey],
+    ) -> Result<Simulation> {
+        (**self).simulate_with_accounts(tx, addresses).await
+    }
+
+    async fn send(&self, tx: &VersionedTransaction) -> Result<Signature> {
+        (**self).send(tx).await
+    }
+
+    async fn signature_status(&self, signature: &Signature) -> Result<SignatureStatus> {
+        (**self).signature_status(signature).await
+    }
+
+    async fn signature_statuses(&self, signatures: &[Signature]) -> Result<Vec<SignatureStatus>> {
+        (**self).signature_statuses(signatures).await
+    }
+
+    async fn get_recent_prioritization_fees(&self, accounts: &[Pubkey]) -> Result<Vec<u64>> {
+        (**self).get_recent_prioritization_fees(accounts).await
+    }
+
+    async fn get_parsed_transaction(&self, signature: &Signature) -> Result<Option<Value>> {
+        (**self).get_parsed_transaction(signature).await
+    }
+
+    async fn get_token_accounts_by_owner(
+        &self,
+        owner: &Pubkey,
+        token_program: &Pubkey,
+    ) -> Result<Vec<RpcKeyedAccount>> {
+        (**self)
+            .get_token_accounts_by_owner(owner, token_program)
+            .await
+    }
+
+    async fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> Result<u64> {
+        (**self)
+            .get_minimum_balance_for_rent_exemption(data_len)
+            .await
+    }
+
+    async fn get_balance(&self, pubkey: &Pubkey) -> Result<u64> {
+        (**self).get_balance(pubkey).await
+    }
+
+    fn endpoint_status(&self) -> Vec<EndpointStatus> {
+        (**self).endpoint_status()
+    }
+}
+
+#[async_trait]
+impl<T: SwapApi + ?Sized> SwapApi for Arc<T> {
+    async fn quote(&self, request: &QuoteRequest) -> Result<QuoteResponse> {
+        (**self).quote(request).await
+    }
+
+    async fn swap_instructions(&self, request: &SwapRequest) -> Result<SwapInstructions> {
+        (**self).swap_instructions(request).await
+    }
+}
+
+#[async_trait]
+impl<T: BundleSender + ?Sized> BundleSender for Arc<T> {
+    async fn send_bundle(&self, bundle: Value) -> Result<Value> {
+        (**self).send_bundle(bundle).await
+    }
+
+    async fn get_bundle_statuses(&self, bundle_ids: Vec<String>) -> Result<Value> {
+        (**self).get_bundle_statuses(bundle_ids).await
+    }
+
+    async fn get_tip_accounts(&self) -> Result<Value> {
+        (**self).get_tip_accounts().await
+    }
+}
@@ -0,0 +1,68 @@
+use solana_sdk::{
+    instruction::Instruction, pubkey, pubkey::Pubkey, system_instruction::SystemInstruction,
+    system_program,
+};
+use spl_token::instruction::TokenInstruction;
+
+/// Jupiter v6 聚合器程序
+pub const JUPITER_PROGRAM_ID: Pubkey = pubkey!("JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4");
+
+/// 把指令解析成便于阅读的描述，用于日志、模拟结果展示和示例程序
+///
+/// 支持系统转账、计算预算、SPL Token transfer_checked、ATA 创建和 Jupiter swap，
+/// 其他指令只输出程序地址和数据长度。
+pub fn decode_instruction(ix: &Instruction) -> String {
+    let program = ix.program_id;
+    if program == system_program::id() {
+        return match bincode::deserialize::<SystemInstruction>(&ix.data) {
+            Ok(SystemInstruction::Transfer { lamports }) => format!(
+                "System::Transfer {} -> {} {} lamports",
+                ix.accounts[0].pubkey, ix.accounts[1].pubkey, lamports
+            ),
+            Ok(other) => format!("System::{:?}", other),
+            Err(_) => "System::<无法解析>".to_string(),
+        };
+    }
+    if program == solana_sdk::compute_budget::id() {
+        return match ix.data.split_first() {
+            Some((2, rest)) if rest.len() >= 4 => format!(
+                "ComputeBudget::SetComputeUnitLimit {}",
+                u32::from_le_bytes(rest[..4].try_into().unwrap())
+            ),
+            Some((3, rest)) if rest.len() >= 8 => format!(
+                "ComputeBudget::SetComputeUnitPrice {} micro-lamports",
+                u64::from_le_bytes(rest[..8].try_into().unwrap())
+            ),
+            _ => "ComputeBudget::<其他>".to_string(),
+        };
+    }
+    if program == spl_token::id() {
+        return match TokenInstruction::unpack(&ix.data) {
+            Ok(TokenInstruction::TransferChecked { amount, decimals }) => format!(
+                "Token::TransferChecked {} -> {} {} (decimals {})",
+                ix.accounts[0].pubkey, ix.accounts[2].pubkey, amount, decimals
+            ),
+            Ok(other) => format!("Token::{:?}", other),
+            Err(_) => "Token::<无法解析>".to_string(),
+        };
+    }
+    if program == spl_associated_token_account::id() {
+        let name = match ix.data.first() {
+            None | Some(0) => "Create",
+            Some(1) => "CreateIdempotent",
+            Some(_) => "<其他>",
+        };
+        return format!(
+            "AssociatedToken::{} owner {} mint {}",
+            name, ix.accounts[2].pubkey, ix.accounts[3].pubkey
+        );
+    }
+    if program == JUPITER_PROGRAM_ID {
+        return format!(
+            "Jupiter::Swap {} 个账户，数据 {} 字节",
+            ix.accounts.len(),
+            ix.data.len()
+        );
+    }
+    format!("{}::<未知指令> 数据 {} 字节", program, ix.data.len())
+}
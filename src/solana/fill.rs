@@ -0,0 +1,429 @@
+//! 从已确认的交易中读取实际成交数量
+//!
+//! 交易以 `jsonParsed` 编码获取，成交数量取自交易前后用户的代币余额（`preTokenBalances`/`postTokenBalances`），
+//! SOL 取自用户账户的 lamports 变化并扣除手续费和 tip。
+
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+
+use crate::common::units::TokenAmount;
+use crate::SOL;
+
+use super::{clients::SolanaRpc, jup::QuoteSummary};
+
+/// 交易确认后 RPC 节点可能还查不到交易，按该间隔重试
+const FETCH_RETRY_DELAY: Duration = Duration::from_millis(500);
+const FETCH_ATTEMPTS: u32 = 4;
+
+/// 一次成交的实际结果
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct FillReport {
+    /// 实际用于交换的输入数量（最小单位），不含以输入代币收取的税收
+    pub in_amount: u64,
+    /// 实际得到的输出数量（最小单位），已扣除以输出代币收取的税收
+    pub out_amount: u64,
+    /// 按订单 `trigger_on` 所指价格换算的实际成交价格，所需的 USD 价格不可用时为 None
+    pub realized_price: Option<f64>,
+    /// 实际成交价格相对触发价格的偏离（基点），正数表示高于触发价格；跟踪止损等没有固定触发价格时为 None
+    pub slippage_from_trigger_bps: Option<i64>,
+    /// 为 true 时无法从交易中读取余额变化，数量取自报价
+    pub estimated: bool,
+}
+
+/// 以 `jsonParsed` 编码获取已确认的交易，节点暂时查不到时重试几次
+pub async fn fetch_parsed_transaction(rpc: &dyn SolanaRpc, signature: &Signature) -> Result<Value> {
+    for attempt in 1..=FETCH_ATTEMPTS {
+        if let Some(tx) = rpc.get_parsed_transaction(signature).await? {
+            return Ok(tx);
+        }
+        if attempt < FETCH_ATTEMPTS {
+            tokio::time::sleep(FETCH_RETRY_DELAY).await;
+        }
+    }
+    Err(anyhow!("查询不到交易 {}", signature))
+}
+
+/// 从 `jsonParsed` 编码的交易中读取 `owner` 付出的输入数量和得到的输出数量
+///
+/// `tip_lamports` 为交换交易中由 `owner` 支付的 tip，与手续费一起从 SOL 的变化中扣除。
+/// 交易失败、任一侧的数量不为正，或者输入、输出为 SOL 且交易中为 `owner` 新建了代币账户
+/// （租金与成交数量无法区分）时返回 None。
+pub fn parse_balance_changes(
+    tx: &Value,
+    owner: &Pubkey,
+    input_mint: &Pubkey,
+    output_mint: &Pubkey,
+    tip_lamports: u64,
+) -> Option<(u64, u64)> {
+    let meta = tx.get("meta")?;
+    if !meta.get("err").is_none_or(Value::is_null) {
+        return None;
+    }
+    let owner = owner.to_string();
+    let pre = token_balances(meta.get("preTokenBalances")?, &owner);
+    let post = token_balances(meta.get("postTokenBalances")?, &owner);
+    let token_delta = |mint: &Pubkey| -> i128 {
+        let mint = mint.to_string();
+        let sum = |balances: &[TokenBalance]| -> i128 {
+            balances
+                .iter()
+                .filter(|balance| balance.mint == mint)
+                .map(|balance| balance.amount as i128)
+                .sum()
+        };
+        sum(&post) - sum(&pre)
+    };
+    let delta = |mint: &Pubkey| -> Option<i128> {
+        if *mint != SOL {
+            return Some(token_delta(mint));
+        }
+        let created = post
+            .iter()
+            .any(|balance| !pre.iter().any(|p| p.account_index == balance.account_index));
+        if created {
+            return None;
+        }
+        let index = account_index(tx, &owner)?;
+        let lamports =
+            |field: &str| -> Option<i128> { Some(meta.get(field)?.get(index)?.as_u64()? as i128) };
+        let mut spent_on_fees = tip_lamports as i128;
+        // 手续费由第一个签名者支付
+        if index == 0 {
+            spent_on_fees += meta.get("fee")?.as_u64()? as i128;
+        }
+        // 原生 SOL 与用户 wSOL 账户的变化之和，交换过程中包装、解包不影响结果
+        Some(
+            lamports("postBalances")? - lamports("preBalances")?
+                + spent_on_fees
+                + token_delta(&SOL),
+        )
+    };
+    let spent = -delta(input_mint)?;
+    let received = delta(output_mint)?;
+    if spent <= 0 || received <= 0 {
+        return None;
+    }
+    Some((u64::try_from(spent).ok()?, u64::try_from(received).ok()?))
+}
+
+/// 托管交换的实际输入、输出数量，以及是否按报价估算
+///
+/// 读取到余额变化 `changes` 时，以输入代币收取的税收在同一笔交易中从用户账户转出，从输入数量中扣除；
+/// 以输出代币收取的税收已不在用户的余额变化中。读取不到时按报价估算，输出数量扣除以输出代币收取的税收。
+pub fn swap_fill_amounts(
+    changes: Option<(u64, u64)>,
+    quote: &QuoteSummary,
+    tax: TokenAmount,
+) -> (u64, u64, bool) {
+    match changes {
+        Some((spent, received)) => {
+            let input_tax = if tax.mint == quote.in_amount.mint {
+                tax.raw
+            } else {
+                0
+            };
+            (spent.saturating_sub(input_tax), received, false)
+        }
+        None => {
+            let output_tax = if tax.mint == quote.out_amount.mint {
+                tax.raw
+            } else {
+                0
+            };
+            (
+                quote.in_amount.raw,
+                quote.out_amount.raw.saturating_sub(output_tax),
+                true,
+            )
+        }
+    }
+}
+
+/// `preTokenBalances`/`postTokenBalances` 中的一项
+struct TokenBalance {
+    account_index: u64,
+    mint: String,
+    amount: u64,
+}
+
+/// 属于 `owner` 的代币余额，无法解析的项跳过
+fn token_balances(balances: &Value, owner: &str) -> Vec<TokenBalance> {
+    balances
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|balance| balance.get("owner").and_then(Value::as_str) == Some(owner))
+        .filter_map(|balance| {
+            Some(TokenBalance {
+                account_index: balance.get("accountIndex")?.as_u64()?,
+                mint: balance.get("mint")?.as_str()?.to_string(),
+                amount: balance
+                    .get("uiTokenAmount")?
+                    .get("amount")?
+                    .as_str()?
+                    .parse()
+                    .ok()?,
+            })
+        })
+        .collect()
+}
+
+/// `address` 在交易账户列表中的位置；`jsonParsed` 编码下账户为 `{"pubkey": ...}` 对象，其他编码为字符串
+fn account_index(tx: &Value, address: &str) -> Option<usize> {
+    tx.get("transaction")?
+        .get("message")?
+        .get("accountKeys")?
+        .as_array()?
+        .iter()
+        .position(|key| {
+            key.get("pubkey")
+                .and_then(Value::as_str)
+                .or_else(|| key.as_str())
+                == Some(address)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use solana_sdk::pubkey;
+
+    use super::*;
+
+    const USDC: Pubkey = pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
+    const BONK: Pubkey = pubkey!("DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263");
+
+    /// `getTransaction` 以 `jsonParsed` 编码返回的交易，只保留读取成交数量用到的字段
+    fn parsed_tx(
+        account_keys: &[Pubkey],
+        balances: (&[u64], &[u64]),
+        token_balances: (Value, Value),
+        err: Value,
+    ) -> Value {
+        let keys: Vec<Value> = account_keys
+            .iter()
+            .enumerate()
+            .map(|(index, key)| {
+                json!({
+                    "pubkey": key.to_string(),
+                    "signer": index == 0,
+                    "source": "transaction",
+                    "writable": true,
+                })
+            })
+            .collect();
+        json!({
+            "slot": 312_000_000,
+            "blockTime": 1_736_000_000,
+            "version": 0,
+            "transaction": {
+                "signatures": ["5h6xBEauJ3PK6SWCZ1PGjBvj8vDdWG3KpwATGy1ARAXFSDwt8GFXM7W5Ncn16wmqokgpiKRLuS83KUxyZyv2sUYv"],
+                "message": { "accountKeys": keys, "instructions": [], "recentBlockhash": "11111111111111111111111111111111" },
+            },
+            "meta": {
+                "err": err,
+                "fee": 5_000,
+                "preBalances": balances.0,
+                "postBalances": balances.1,
+                "preTokenBalances": token_balances.0,
+                "postTokenBalances": token_balances.1,
+                "logMessages": [],
+            },
+        })
+    }
+
+    /// `preTokenBalances`/`postTokenBalances` 中的一项
+    fn token_balance(account_index: u64, mint: &Pubkey, owner: &Pubkey, amount: u64) -> Value {
+        json!({
+            "accountIndex": account_index,
+            "mint": mint.to_string(),
+            "owner": owner.to_string(),
+            "programId": spl_token::id().to_string(),
+            "uiTokenAmount": {
+                "amount": amount.to_string(),
+                "decimals": 6,
+                "uiAmount": null,
+                "uiAmountString": "0",
+            },
+        })
+    }
+
+    /// 1 SOL 换 150 USDC：用户支付 5000 lamports 手续费和 10000 lamports tip，wSOL 账户在交易中创建并关闭，
+    /// 池子的代币账户不属于用户，不计入
+    fn sol_to_usdc(owner: &Pubkey) -> Value {
+        let pool = Pubkey::new_unique();
+        parsed_tx(
+            &[*owner, Pubkey::new_unique(), Pubkey::new_unique(), pool],
+            (
+                &[10_000_000_000, 0, 2_039_280, 2_039_280],
+                &[8_999_985_000, 0, 2_039_280, 2_039_280],
+            ),
+            (
+                json!([
+                    token_balance(2, &USDC, owner, 0),
+                    token_balance(3, &USDC, &pool, 900_000_000),
+                ]),
+                json!([
+                    token_balance(2, &USDC, owner, 150_000_000),
+                    token_balance(3, &USDC, &pool, 750_000_000),
+                ]),
+            ),
+            Value::Null,
+        )
+    }
+
+    #[test]
+    fn sol_input_is_net_of_fee_and_tip() {
+        let owner = Pubkey::new_unique();
+        let tx = sol_to_usdc(&owner);
+        assert_eq!(
+            parse_balance_changes(&tx, &owner, &SOL, &USDC, 10_000),
+            Some((1_000_000_000, 150_000_000))
+        );
+        // 不扣除 tip 时 tip 被算作输入
+        assert_eq!(
+            parse_balance_changes(&tx, &owner, &SOL, &USDC, 0),
+            Some((1_000_010_000, 150_000_000))
+        );
+    }
+
+    #[test]
+    fn token_to_token_uses_token_balances() {
+        let owner = Pubkey::new_unique();
+        let tx = parsed_tx(
+            &[owner, Pubkey::new_unique(), Pubkey::new_unique()],
+            (
+                &[1_000_000_000, 2_039_280, 2_039_280],
+                &[999_995_000, 2_039_280, 2_039_280],
+            ),
+            (
+                json!([
+                    token_balance(1, &USDC, &owner, 50_000_000),
+                    token_balance(2, &BONK, &owner, 0),
+                ]),
+                json!([
+                    token_balance(1, &USDC, &owner, 30_000_000),
+                    token_balance(2, &BONK, &owner, 987_654_321),
+                ]),
+            ),
+            Value::Null,
+        );
+        assert_eq!(
+            parse_balance_changes(&tx, &owner, &USDC, &BONK, 0),
+            Some((20_000_000, 987_654_321))
+        );
+    }
+
+    #[test]
+    fn string_account_keys_are_supported() {
+        let owner = Pubkey::new_unique();
+        let mut tx = sol_to_usdc(&owner);
+        let keys: Vec<Value> = tx["transaction"]["message"]["accountKeys"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|key| key["pubkey"].clone())
+            .collect();
+        tx["transaction"]["message"]["accountKeys"] = json!(keys);
+        assert_eq!(
+            parse_balance_changes(&tx, &owner, &SOL, &USDC, 10_000),
+            Some((1_000_000_000, 150_000_000))
+        );
+    }
+
+    #[test]
+    fn failed_transaction_has_no_fill() {
+        let owner = Pubkey::new_unique();
+        let mut tx = sol_to_usdc(&owner);
+        tx["meta"]["err"] = json!({ "InstructionError": [3, { "Custom": 6001 }] });
+        assert_eq!(
+            parse_balance_changes(&tx, &owner, &SOL, &USDC, 10_000),
+            None
+        );
+    }
+
+    /// 输出为 SOL 且交易中为用户新建了代币账户时，租金与成交数量无法区分
+    #[test]
+    fn created_account_with_sol_side_is_ambiguous() {
+        let owner = Pubkey::new_unique();
+        let tx = parsed_tx(
+            &[owner, Pubkey::new_unique(), Pubkey::new_unique()],
+            (
+                &[1_000_000_000, 2_039_280, 0],
+                &[1_997_955_720, 2_039_280, 2_039_280],
+            ),
+            (
+                json!([token_balance(1, &USDC, &owner, 150_000_000)]),
+                json!([
+                    token_balance(1, &USDC, &owner, 0),
+                    token_balance(2, &BONK, &owner, 0),
+                ]),
+            ),
+            Value::Null,
+        );
+        assert_eq!(parse_balance_changes(&tx, &owner, &USDC, &SOL, 0), None);
+    }
+
+    #[test]
+    fn missing_or_non_positive_changes_have_no_fill() {
+        let owner = Pubkey::new_unique();
+        // 用户在交易中没有任何余额变化
+        let tx = sol_to_usdc(&Pubkey::new_unique());
+        assert_eq!(parse_balance_changes(&tx, &owner, &USDC, &BONK, 0), None);
+        // 缺少 meta，例如节点返回了不完整的交易
+        assert_eq!(
+            parse_balance_changes(&json!({ "transaction": {} }), &owner, &SOL, &USDC, 0),
+            None
+        );
+    }
+
+    fn quote(in_amount: TokenAmount, out_amount: TokenAmount) -> QuoteSummary {
+        QuoteSummary {
+            in_amount,
+            out_amount,
+            min_out_amount: out_amount,
+            price_impact_pct: 0.0,
+            route_labels: vec![],
+        }
+    }
+
+    #[test]
+    fn fill_amounts_deduct_input_tax_from_balance_changes() {
+        let quote = quote(
+            TokenAmount::new(SOL, 990_000_000),
+            TokenAmount::new(USDC, 148_500_000),
+        );
+        let tax = TokenAmount::new(SOL, 10_000_000);
+        assert_eq!(
+            swap_fill_amounts(Some((1_000_000_000, 148_400_000)), &quote, tax),
+            (990_000_000, 148_400_000, false)
+        );
+        // 以输出代币收取的税收不在用户的余额变化中，不再扣除
+        let tax = TokenAmount::new(USDC, 1_500_000);
+        assert_eq!(
+            swap_fill_amounts(Some((1_000_000_000, 147_000_000)), &quote, tax),
+            (1_000_000_000, 147_000_000, false)
+        );
+    }
+
+    #[test]
+    fn unreadable_fill_is_estimated_from_quote() {
+        let quote = quote(
+            TokenAmount::new(SOL, 1_000_000_000),
+            TokenAmount::new(USDC, 150_000_000),
+        );
+        assert_eq!(
+            swap_fill_amounts(None, &quote, TokenAmount::new(USDC, 1_500_000)),
+            (1_000_000_000, 148_500_000, true)
+        );
+        assert_eq!(
+            swap_fill_amounts(None, &quote, TokenAmount::new(SOL, 10_000_000)),
+            (1_000_000_000, 150_000_000, true)
+        );
+    }
+}
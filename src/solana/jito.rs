@@ -1,23 +1,297 @@
-use std::str::FromStr;
+use std::{
+    str::FromStr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
 
 use anyhow::{anyhow, Result};
 use rand::{rng, seq::IteratorRandom};
+use serde_json::Value;
 use solana_sdk::pubkey::Pubkey;
+use thiserror::Error;
 
-pub fn get_tip_account() -> Result<Pubkey> {
-    let accounts = [
-        "ADaUMid9yfUytqMBgopwjb2DTLSokTSzL1zt6iGPaS49",
-        "DttWaMuVvTiduZRnguLF7jNxTgiMBZ1hyAumKUiL2KRL",
-        "Cw8CFyM9FkoMi7K7Crf6HNQqf4uEMzpKw6QNghXLvLkY",
-        "ADuUkR4vqLUMWXxW9gh6D6L8pMSawimctcNZ5pGwDcEt",
-        "HFqU5x63VTqvQss8hp11i4wVV8bD44PvwucfZ2bU7gRe",
-        "DfXygSm4jCyNCybVYYK6DwvWqjKee8pbDmJGcLWNDXjh",
-        "3AVi9Tg9Uo68tJfuvoKvqKNWKkC5wPdSSdeBnizKZ6jT",
-        "96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5",
-    ];
+use super::clients::BundleSender;
+
+/// tip 账户列表的缓存时间
+const TIP_ACCOUNTS_TTL: Duration = Duration::from_secs(600);
+
+/// 获取失败时使用的 tip 账户
+const FALLBACK_TIP_ACCOUNTS: [&str; 8] = [
+    "ADaUMid9yfUytqMBgopwjb2DTLSokTSzL1zt6iGPaS49",
+    "DttWaMuVvTiduZRnguLF7jNxTgiMBZ1hyAumKUiL2KRL",
+    "Cw8CFyM9FkoMi7K7Crf6HNQqf4uEMzpKw6QNghXLvLkY",
+    "ADuUkR4vqLUMWXxW9gh6D6L8pMSawimctcNZ5pGwDcEt",
+    "HFqU5x63VTqvQss8hp11i4wVV8bD44PvwucfZ2bU7gRe",
+    "DfXygSm4jCyNCybVYYK6DwvWqjKee8pbDmJGcLWNDXjh",
+    "3AVi9Tg9Uo68tJfuvoKvqKNWKkC5wPdSSdeBnizKZ6jT",
+    "96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5",
+];
+
+/// Jito 限流时返回的 JSON-RPC 错误码
+const RATE_LIMITED_CODE: i64 = -32097;
+
+/// Jito 发送 bundle 的错误
+#[derive(Debug, Error)]
+pub enum JitoError {
+    /// 请求过于频繁，稍后重试可能成功
+    #[error("Jito 限流 {message}")]
+    RateLimited { message: String },
+    /// Jito 返回 JSON-RPC 错误，拒绝了该 bundle
+    #[error("Jito 拒绝 bundle，错误码 {code} {message}")]
+    Rejected { code: i64, message: String },
+    /// 返回内容既不是 bundle id 也不是错误对象
+    #[error("Jito 返回格式错误 {0}")]
+    MalformedResponse(String),
+    /// 请求未得到响应，例如连接失败或超时
+    #[error("Jito 不可用 {0}")]
+    Unavailable(String),
+}
+
+impl JitoError {
+    pub fn is_rate_limited(&self) -> bool {
+        matches!(self, JitoError::RateLimited { .. })
+    }
+
+    /// 请求本身失败时的错误，状态码 429 归为限流
+    pub fn from_request(e: &anyhow::Error) -> JitoError {
+        let message = format!("{:#}", e);
+        if message.contains("429") {
+            JitoError::RateLimited { message }
+        } else {
+            JitoError::Unavailable(message)
+        }
+    }
+}
+
+/// 解析 `sendBundle` 的返回：`result` 为 bundle id，`error` 为 JSON-RPC 错误对象
+pub fn parse_send_bundle(resp: &Value) -> std::result::Result<String, JitoError> {
+    if let Some(error) = resp.get("error") {
+        let code = error
+            .get("code")
+            .and_then(Value::as_i64)
+            .unwrap_or_default();
+        let message = error
+            .get("message")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .unwrap_or_else(|| error.to_string());
+        if code == RATE_LIMITED_CODE || message.to_ascii_lowercase().contains("rate limit") {
+            return Err(JitoError::RateLimited { message });
+        }
+        return Err(JitoError::Rejected { code, message });
+    }
+    match resp.get("result").and_then(Value::as_str) {
+        Some(bundle_id) if !bundle_id.is_empty() => Ok(bundle_id.to_string()),
+        _ => Err(JitoError::MalformedResponse(resp.to_string())),
+    }
+}
+
+/// 最近一次从 Jito 获取的 tip 账户
+static TIP_ACCOUNTS: Mutex<Option<(Instant, Vec<Pubkey>)>> = Mutex::new(None);
+
+/// 随机选择一个 tip 账户
+///
+/// 优先使用从 Jito `getTipAccounts` 获取并缓存的列表，缓存过期时重新获取；
+/// 获取失败或返回的列表无效时使用内置的账户，不会导致交易失败。
+pub async fn get_tip_account(jito: &dyn BundleSender) -> Result<Pubkey> {
+    let accounts = tip_accounts(jito).await;
     let mut rng = rng();
     match accounts.iter().choose(&mut rng) {
-        Some(acc) => Ok(Pubkey::from_str(acc)?),
+        Some(acc) => Ok(*acc),
         None => Err(anyhow!("jito: no tip accounts available")),
     }
 }
+
+/// 重新获取 tip 账户列表并更新缓存，启动时调用以预热缓存
+pub async fn refresh_tip_accounts(jito: &dyn BundleSender) -> Result<Vec<Pubkey>> {
+    let resp = jito.get_tip_accounts().await?;
+    let accounts = parse_tip_accounts(&resp)?;
+    *TIP_ACCOUNTS.lock().unwrap() = Some((Instant::now(), accounts.clone()));
+    Ok(accounts)
+}
+
+async fn tip_accounts(jito: &dyn BundleSender) -> Vec<Pubkey> {
+    if let Some((fetched_at, accounts)) = TIP_ACCOUNTS.lock().unwrap().as_ref() {
+        if fetched_at.elapsed() < TIP_ACCOUNTS_TTL {
+            return accounts.clone();
+        }
+    }
+    or_fallback_tip_accounts(refresh_tip_accounts(jito).await)
+}
+
+/// 获取 tip 账户失败或返回的列表无效时改用内置的账户
+fn or_fallback_tip_accounts(fetched: Result<Vec<Pubkey>>) -> Vec<Pubkey> {
+    match fetched {
+        Ok(accounts) => accounts,
+        Err(e) => {
+            println!("获取 Jito tip 账户失败，使用内置列表 {:?}", e);
+            fallback_tip_accounts()
+        }
+    }
+}
+
+/// 解析 `getTipAccounts` 的返回，`result` 应为非空的地址数组
+fn parse_tip_accounts(resp: &Value) -> Result<Vec<Pubkey>> {
+    let accounts = resp
+        .get("result")
+        .and_then(|r| r.as_array())
+        .ok_or_else(|| anyhow!("tip 账户返回格式错误"))?
+        .iter()
+        .map(|acc| {
+            acc.as_str()
+                .and_then(|acc| Pubkey::from_str(acc).ok())
+                .ok_or_else(|| anyhow!("tip 账户地址无效 {}", acc))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if accounts.is_empty() {
+        return Err(anyhow!("tip 账户列表为空"));
+    }
+    Ok(accounts)
+}
+
+fn fallback_tip_accounts() -> Vec<Pubkey> {
+    FALLBACK_TIP_ACCOUNTS
+        .iter()
+        .map(|acc| Pubkey::from_str(acc).expect("内置 tip 账户地址有效"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn send_bundle_result_is_the_bundle_id() {
+        let resp = json!({
+            "jsonrpc": "2.0",
+            "result": "2id3YC2jK9G5Wo2phDx4gJVAew8DcY5NAojnVuao8rkxwPYPe8cSwE5GzhEgJA2y8fVjDEo6iR6ykBvDxrTQrtpb",
+            "id": 1,
+        });
+        assert_eq!(
+            parse_send_bundle(&resp).unwrap(),
+            "2id3YC2jK9G5Wo2phDx4gJVAew8DcY5NAojnVuao8rkxwPYPe8cSwE5GzhEgJA2y8fVjDEo6iR6ykBvDxrTQrtpb"
+        );
+    }
+
+    #[test]
+    fn rate_limit_errors_are_retryable() {
+        let by_code = json!({
+            "jsonrpc": "2.0",
+            "error": { "code": -32097, "message": "Rate limit exceeded. Limit: 1 per second for txn requests" },
+            "id": 1,
+        });
+        let by_message = json!({
+            "jsonrpc": "2.0",
+            "error": { "code": -32603, "message": "Network congested. Endpoint is globally rate limited." },
+            "id": 1,
+        });
+        for resp in [by_code, by_message] {
+            let err = parse_send_bundle(&resp).unwrap_err();
+            assert!(err.is_rate_limited(), "{:?}", err);
+        }
+    }
+
+    #[test]
+    fn other_errors_reject_the_bundle() {
+        let resp = json!({
+            "jsonrpc": "2.0",
+            "error": { "code": -32602, "message": "bundle must contain at least one tip transfer" },
+            "id": 1,
+        });
+        match parse_send_bundle(&resp) {
+            Err(JitoError::Rejected { code, message }) => {
+                assert_eq!(code, -32602);
+                assert_eq!(message, "bundle must contain at least one tip transfer");
+            }
+            other => panic!("应拒绝 bundle，实际为 {:?}", other),
+        }
+        // 错误对象没有 message 时保留整个错误对象
+        let resp = json!({ "error": { "data": "unknown" } });
+        match parse_send_bundle(&resp) {
+            Err(JitoError::Rejected { code, message }) => {
+                assert_eq!(code, 0);
+                assert!(message.contains("unknown"), "{}", message);
+            }
+            other => panic!("应拒绝 bundle，实际为 {:?}", other),
+        }
+    }
+
+    #[test]
+    fn malformed_responses_are_reported() {
+        let cases = [
+            json!({ "jsonrpc": "2.0", "id": 1 }),
+            json!({ "jsonrpc": "2.0", "result": "", "id": 1 }),
+            json!({ "jsonrpc": "2.0", "result": 42, "id": 1 }),
+            json!("ok"),
+        ];
+        for resp in cases {
+            assert!(
+                matches!(
+                    parse_send_bundle(&resp),
+                    Err(JitoError::MalformedResponse(_))
+                ),
+                "{}",
+                resp
+            );
+        }
+    }
+
+    /// `getTipAccounts` 的返回，不经过全局缓存
+    #[cfg(feature = "testing")]
+    async fn fetched_tip_accounts(jito: &crate::testing::MockJito) -> Result<Vec<Pubkey>> {
+        parse_tip_accounts(&jito.get_tip_accounts().await?)
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn tip_accounts_are_read_from_get_tip_accounts() {
+        use crate::testing::MockJito;
+
+        let mut jito = MockJito::new(Pubkey::new_unique());
+        jito.tip_accounts.push(Pubkey::new_unique());
+        let accounts = or_fallback_tip_accounts(fetched_tip_accounts(&jito).await);
+        assert_eq!(accounts, jito.tip_accounts);
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn empty_tip_account_list_falls_back() {
+        use crate::testing::MockJito;
+
+        let mut jito = MockJito::new(Pubkey::new_unique());
+        jito.tip_accounts.clear();
+        let fetched = fetched_tip_accounts(&jito).await;
+        assert!(fetched
+            .as_ref()
+            .is_err_and(|e| e.to_string().contains("为空")));
+        assert_eq!(or_fallback_tip_accounts(fetched), fallback_tip_accounts());
+    }
+
+    #[test]
+    fn invalid_tip_account_responses_fall_back() {
+        let cases = [
+            json!({ "jsonrpc": "2.0", "result": ["not-a-pubkey"], "id": 1 }),
+            json!({ "jsonrpc": "2.0", "result": [42], "id": 1 }),
+            json!({ "jsonrpc": "2.0", "result": "96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5", "id": 1 }),
+            json!({ "jsonrpc": "2.0", "error": { "code": -32603, "message": "internal error" }, "id": 1 }),
+        ];
+        for resp in cases {
+            let fetched = parse_tip_accounts(&resp);
+            assert!(fetched.is_err(), "{}", resp);
+            assert_eq!(or_fallback_tip_accounts(fetched), fallback_tip_accounts());
+        }
+        assert_eq!(fallback_tip_accounts().len(), FALLBACK_TIP_ACCOUNTS.len());
+    }
+
+    #[test]
+    fn request_errors_with_429_are_rate_limited() {
+        let err =
+            JitoError::from_request(&anyhow!("HTTP status client error (429 Too Many Requests)"));
+        assert!(err.is_rate_limited());
+        let err = JitoError::from_request(&anyhow!("error sending request: connection refused"));
+        assert!(matches!(err, JitoError::Unavailable(_)));
+        assert!(!err.is_rate_limited());
+    }
+}
@@ -1,41 +1,616 @@
-use std::sync::Arc;
+use std::{
+    env,
+    sync::{LazyLock, OnceLock},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use dashmap::DashMap;
 use jupiter_swap_api_client::{
-    quote::QuoteRequest,
-    swap::{SwapInstructionsResponse, SwapRequest},
+    quote::{self, QuoteRequest, QuoteResponse},
+    swap::SwapRequest,
     transaction_config::TransactionConfig,
-    JupiterSwapApiClient,
+    ClientError,
 };
-
+use rand::Rng;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use solana_sdk::pubkey::Pubkey;
 
-/// jup 交易
-/// use -> 交易发起者
-pub async fn get_swap_ix(
-    jup: Arc<JupiterSwapApiClient>,
-    user: Pubkey,
-    amount: u64,
+use crate::{
+    common::{
+        encode::{decrypt, encrypt},
+        metrics::metrics,
+        units::{Bps, TokenAmount},
+    },
+    error::{self, LimitOrderError},
+    solana::clients::{SwapApi, SwapInstructions},
+};
+
+/// 缓存条目超过该数量时清理过期条目
+const MAX_CACHED_QUOTES: usize = 1024;
+
+static QUOTE_POLICY: OnceLock<QuotePolicy> = OnceLock::new();
+static QUOTE_CACHE: LazyLock<DashMap<QuoteKey, (Instant, QuoteResponse)>> =
+    LazyLock::new(DashMap::new);
+
+/// 报价请求的重试与缓存策略
+///
+/// 报价在没有订单簿引用的函数中发起，因此策略是进程级的，启动时通过 [`configure_quotes`] 设置一次。
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct QuotePolicy {
+    /// 遇到 429 或 5xx 时的最大重试次数
+    pub max_retries: u32,
+    /// 第一次重试前等待时间的上限，实际等待时间在 0 到上限之间随机
+    pub base_delay_ms: u64,
+    /// 单次等待时间上限的最大值
+    pub max_delay_ms: u64,
+    /// 相同参数的报价在该时长内直接复用，为 0 时不缓存
+    pub cache_ttl_ms: u64,
+}
+
+impl Default for QuotePolicy {
+    fn default() -> Self {
+        QuotePolicy {
+            max_retries: 3,
+            base_delay_ms: 200,
+            max_delay_ms: 3_000,
+            cache_ttl_ms: 1_000,
+        }
+    }
+}
+
+impl QuotePolicy {
+    /// 从环境变量 `QUOTE_MAX_RETRIES`、`QUOTE_RETRY_BASE_DELAY_MS`、`QUOTE_RETRY_MAX_DELAY_MS`、
+    /// `QUOTE_CACHE_TTL_MS` 读取，未配置时使用默认值
+    pub fn from_env() -> Result<QuotePolicy> {
+        let mut policy = QuotePolicy::default();
+        if let Ok(v) = env::var("QUOTE_MAX_RETRIES") {
+            policy.max_retries = v.parse()?;
+        }
+        if let Ok(v) = env::var("QUOTE_RETRY_BASE_DELAY_MS") {
+            policy.base_delay_ms = v.parse()?;
+        }
+        if let Ok(v) = env::var("QUOTE_RETRY_MAX_DELAY_MS") {
+            policy.max_delay_ms = v.parse()?;
+        }
+        if let Ok(v) = env::var("QUOTE_CACHE_TTL_MS") {
+            policy.cache_ttl_ms = v.parse()?;
+        }
+        Ok(policy)
+    }
+
+    /// 第 `attempt` 次重试（从 1 开始）前的等待时间：指数退避的上限内随机取值，
+    /// 避免大量订单同时被限流后又同时重试
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u64.saturating_pow(attempt.saturating_sub(1));
+        let cap = self
+            .base_delay_ms
+            .saturating_mul(factor)
+            .min(self.max_delay_ms);
+        Duration::from_millis(rand::rng().random_range(0..=cap))
+    }
+}
+
+/// 设置进程级的报价策略，只有第一次调用生效
+pub fn configure_quotes(policy: QuotePolicy) {
+    if QUOTE_POLICY.set(policy).is_err() {
+        println!("报价策略已设置，忽略新的配置");
+    }
+}
+
+fn quote_policy() -> QuotePolicy {
+    QUOTE_POLICY.get().copied().unwrap_or_default()
+}
+
+/// 报价缓存的键
+///
+/// 数量按原值而不是分桶，交换指令和税收都按报价的数量计算，不能复用其他数量的报价。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct QuoteKey {
     input_mint: Pubkey,
     output_mint: Pubkey,
+    amount: u64,
     slippage_bps: u16,
-) -> Result<(u64, SwapInstructionsResponse)> {
+    swap_mode: SwapMode,
+}
+
+/// 报价失败是否值得重试：限流、服务端错误和网络错误
+fn is_retryable(e: &anyhow::Error) -> bool {
+    match e.downcast_ref::<ClientError>() {
+        Some(ClientError::RequestFailed { status, .. }) => {
+            status.as_u16() == 429 || status.is_server_error()
+        }
+        Some(ClientError::DeserializationError(e)) => e.is_timeout() || e.is_connect(),
+        None => false,
+    }
+}
+
+/// 报价模式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub enum SwapMode {
+    /// 指定输入数量
+    #[default]
+    ExactIn,
+    /// 指定输出数量
+    ExactOut,
+}
+
+impl From<SwapMode> for quote::SwapMode {
+    fn from(mode: SwapMode) -> quote::SwapMode {
+        match mode {
+            SwapMode::ExactIn => quote::SwapMode::ExactIn,
+            SwapMode::ExactOut => quote::SwapMode::ExactOut,
+        }
+    }
+}
+
+/// 交换交易的选项，对应 Jupiter `TransactionConfig` 的部分字段
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SwapOptions {
+    /// 是否由交换指令自动包装 / 解包 SOL，默认 true；关闭时使用用户已有的 wSOL 账户
+    #[serde(default = "default_true")]
+    pub wrap_and_unwrap_sol: bool,
+    /// 接收输出代币的代币账户，为空时输出到用户自己的 ATA；必须是输出代币的 ATA
+    #[serde(default)]
+    pub destination_token_account: Option<String>,
+    /// 是否使用 Jupiter 的共享中间账户，为空时由 Jupiter 决定
+    #[serde(default)]
+    pub use_shared_accounts: Option<bool>,
+    /// 是否由 Jupiter 模拟交易以确定计算单元上限，默认 false
+    #[serde(default)]
+    pub dynamic_compute_unit_limit: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for SwapOptions {
+    fn default() -> Self {
+        SwapOptions {
+            wrap_and_unwrap_sol: true,
+            destination_token_account: None,
+            use_shared_accounts: None,
+            dynamic_compute_unit_limit: false,
+        }
+    }
+}
+
+impl SwapOptions {
+    pub fn destination(&self) -> Result<Option<Pubkey>> {
+        self.destination_token_account
+            .as_deref()
+            .map(|account| {
+                account
+                    .parse()
+                    .map_err(|_| anyhow!("目标代币账户地址无效 {}", account))
+            })
+            .transpose()
+    }
+
+    pub fn to_transaction_config(&self) -> Result<TransactionConfig> {
+        let mut config = TransactionConfig {
+            wrap_and_unwrap_sol: self.wrap_and_unwrap_sol,
+            destination_token_account: self.destination()?,
+            dynamic_compute_unit_limit: self.dynamic_compute_unit_limit,
+            ..TransactionConfig::default()
+        };
+        if let Some(use_shared_accounts) = self.use_shared_accounts {
+            config.use_shared_accounts = use_shared_accounts.into();
+        }
+        Ok(config)
+    }
+}
+
+/// 报价摘要：输入、输出数量，价格影响和路由
+#[derive(Debug, Clone)]
+pub struct QuoteSummary {
+    pub in_amount: TokenAmount,
+    pub out_amount: TokenAmount,
+    /// 按滑点计算的最低输出数量，ExactOut 时即为输出数量
+    pub min_out_amount: TokenAmount,
+    /// Jupiter 返回的价格影响，为比例而不是百分数，0.01 即 1%
+    pub price_impact_pct: f64,
+    /// 路由经过的 AMM
+    pub route_labels: Vec<String>,
+}
+
+impl QuoteSummary {
+    pub fn from_quote(quote: &QuoteResponse) -> QuoteSummary {
+        QuoteSummary {
+            in_amount: TokenAmount::new(quote.input_mint, quote.in_amount),
+            out_amount: TokenAmount::new(quote.output_mint, quote.out_amount),
+            min_out_amount: TokenAmount::new(
+                quote.output_mint,
+                match quote.swap_mode {
+                    quote::SwapMode::ExactIn => quote.other_amount_threshold,
+                    quote::SwapMode::ExactOut => quote.out_amount,
+                },
+            ),
+            price_impact_pct: quote
+                .price_impact_pct
+                .to_string()
+                .parse::<f64>()
+                .unwrap_or(0.0)
+                .abs(),
+            route_labels: quote
+                .route_plan
+                .iter()
+                .map(|step| step.swap_info.label.to_string())
+                .collect(),
+        }
+    }
+
+    /// 价格影响，单位为基点
+    pub fn price_impact_bps(&self) -> u64 {
+        (self.price_impact_pct * Bps::MAX as f64).round() as u64
+    }
+}
+
+/// 固定路由：`/quote` 返回的报价，执行时直接使用该报价构造交换指令而不重新报价
+///
+/// 对外以不透明令牌的形式传递，令牌使用服务端密钥加密认证，客户端无法修改报价内容。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PinnedRoute {
+    pub quote: QuoteResponse,
+    /// 过期时间（unix 毫秒）
+    pub expires_at_ms: u64,
+}
+
+impl PinnedRoute {
+    pub fn new(quote: QuoteResponse, ttl: Duration) -> PinnedRoute {
+        PinnedRoute {
+            quote,
+            expires_at_ms: now_ms() + ttl.as_millis() as u64,
+        }
+    }
+
+    /// 编码为路由令牌
+    pub fn to_token(&self) -> Result<String> {
+        encrypt(&serde_json::to_vec(self)?)
+    }
+
+    /// 解析路由令牌
+    pub fn from_token(token: &str) -> Result<PinnedRoute> {
+        let json = decrypt(token).map_err(|_| anyhow!("路由令牌无效"))?;
+        serde_json::from_str(json.expose()).map_err(|_| anyhow!("路由令牌无效"))
+    }
+
+    pub fn is_fresh(&self) -> bool {
+        now_ms() < self.expires_at_ms
+    }
+
+    /// 检查固定路由是否适用于本次交换：代币一致、报价数量一致、滑点不超过订单允许的滑点
+    pub fn matches(
+        &self,
+        input_mint: Pubkey,
+        output_mint: Pubkey,
+        amount: TokenAmount,
+        slippage_bps: Bps,
+        swap_mode: SwapMode,
+    ) -> Result<()> {
+        if self.quote.input_mint != input_mint || self.quote.output_mint != output_mint {
+            return Err(anyhow!("固定路由的代币与订单不一致"));
+        }
+        let quoted_amount = match swap_mode {
+            SwapMode::ExactIn => self.quote.in_amount,
+            SwapMode::ExactOut => self.quote.out_amount,
+        };
+        if quoted_amount != amount.raw {
+            return Err(anyhow!(
+                "固定路由的报价数量 {} 与订单数量 {} 不一致",
+                quoted_amount,
+                amount.raw
+            ));
+        }
+        if self.quote.slippage_bps > slippage_bps.get() {
+            return Err(anyhow!(
+                "固定路由的滑点 {} 超过订单允许的 {}",
+                self.quote.slippage_bps,
+                slippage_bps
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// 订单使用的固定路由及其过期后的处理方式
+#[derive(Debug, Clone)]
+pub struct RoutePin {
+    pub route: PinnedRoute,
+    /// 固定路由过期或不再适用时是否改用新的报价，为 false 时直接失败
+    pub fallback: bool,
+}
+
+/// 向 Jupiter 请求报价
+///
+/// `ExactIn` 模式下 `amount` 为输入代币数量，`ExactOut` 模式下为输出代币数量
+pub async fn get_quote(
+    jup: &dyn SwapApi,
+    input_mint: Pubkey,
+    output_mint: Pubkey,
+    amount: TokenAmount,
+    slippage_bps: Bps,
+    swap_mode: SwapMode,
+) -> error::Result<QuoteResponse> {
+    let expected_mint = match swap_mode {
+        SwapMode::ExactIn => input_mint,
+        SwapMode::ExactOut => output_mint,
+    };
+    if amount.mint != expected_mint {
+        return Err(LimitOrderError::invalid(
+            "amount",
+            format!(
+                "{:?} 模式下数量应以 {} 计价，实际为 {}",
+                swap_mode, expected_mint, amount.mint
+            ),
+        ));
+    }
     let quote_request = QuoteRequest {
-        amount,
+        amount: amount.raw,
         input_mint,
         output_mint,
-        slippage_bps,
+        slippage_bps: slippage_bps.get(),
+        swap_mode: Some(swap_mode.into()),
         ..QuoteRequest::default()
     };
-    let quote_response = jup.quote(&quote_request).await.unwrap();
-    println!("报价 {:?}", quote_response);
-    let out_amount = quote_response.out_amount;
+    let policy = quote_policy();
+    let key = QuoteKey {
+        input_mint,
+        output_mint,
+        amount: amount.raw,
+        slippage_bps: slippage_bps.get(),
+        swap_mode,
+    };
+    let ttl = Duration::from_millis(policy.cache_ttl_ms);
+    if let Some(cached) = QUOTE_CACHE.get(&key) {
+        if cached.0.elapsed() < ttl {
+            return Ok(cached.1.clone());
+        }
+    }
+
+    let mut attempt = 0;
+    let quote_response = loop {
+        let started_at = Instant::now();
+        let result = jup.quote(&quote_request).await;
+        metrics()
+            .quote_latency_seconds
+            .observe(started_at.elapsed().as_secs_f64());
+        match result {
+            Ok(quote_response) => break quote_response,
+            Err(e) if is_retryable(&e) && attempt < policy.max_retries => {
+                attempt += 1;
+                metrics().quote_retries.inc();
+                let delay = policy.backoff(attempt);
+                println!("报价失败 {}，{:?} 后第 {} 次重试", e, delay, attempt);
+                tokio::time::sleep(delay).await;
+            }
+            Err(e) => return Err(LimitOrderError::QuoteFailed(e.to_string())),
+        }
+    };
+    let summary = QuoteSummary::from_quote(&quote_response);
+    println!(
+        "报价 {} -> {}，输入 {} 输出 {}，价格影响 {} bps，路由 {:?}",
+        input_mint,
+        output_mint,
+        summary.in_amount.raw,
+        summary.out_amount.raw,
+        summary.price_impact_bps(),
+        summary.route_labels
+    );
+    if !ttl.is_zero() {
+        if QUOTE_CACHE.len() > MAX_CACHED_QUOTES {
+            QUOTE_CACHE.retain(|_, (quoted_at, _)| quoted_at.elapsed() < ttl);
+        }
+        QUOTE_CACHE.insert(key, (Instant::now(), quote_response.clone()));
+    }
+    Ok(quote_response)
+}
+
+/// 只请求报价，不获取交换指令，返回报价摘要
+///
+/// 用于按实际成交价格触发的订单定期询价，不调用 [`get_swap_ix_for_quote`]，少一次 `swap_instructions` 请求。
+pub async fn quote_only(
+    jup: &dyn SwapApi,
+    input_mint: Pubkey,
+    output_mint: Pubkey,
+    amount: TokenAmount,
+    slippage_bps: Bps,
+    swap_mode: SwapMode,
+) -> error::Result<QuoteSummary> {
+    let quote_response = get_quote(
+        jup,
+        input_mint,
+        output_mint,
+        amount,
+        slippage_bps,
+        swap_mode,
+    )
+    .await?;
+    Ok(QuoteSummary::from_quote(&quote_response))
+}
+
+/// 使用给定的报价获取交换指令
+pub async fn get_swap_ix_for_quote(
+    jup: &dyn SwapApi,
+    user: Pubkey,
+    quote_response: QuoteResponse,
+    options: &SwapOptions,
+) -> error::Result<(QuoteSummary, SwapInstructions)> {
+    let summary = QuoteSummary::from_quote(&quote_response);
     let swap_ix_response = jup
         .swap_instructions(&SwapRequest {
             user_public_key: user,
             quote_response,
-            config: TransactionConfig::default(),
+            config: options.to_transaction_config()?,
         })
-        .await?;
-    Ok((out_amount, swap_ix_response))
+        .await
+        .map_err(|e| LimitOrderError::QuoteFailed(e.to_string()))?;
+    Ok((summary, swap_ix_response))
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_stays_within_the_capped_window() {
+        let policy = QuotePolicy {
+            max_retries: 5,
+            base_delay_ms: 100,
+            max_delay_ms: 250,
+            cache_ttl_ms: 0,
+        };
+        for attempt in 1..=5 {
+            let cap = (100u64 << (attempt - 1)).min(250);
+            assert!(policy.backoff(attempt) <= Duration::from_millis(cap));
+        }
+    }
+
+    /// 按 Jupiter 报价接口应答的本地服务
+    #[cfg(feature = "testing")]
+    mod server {
+        use jupiter_swap_api_client::JupiterSwapApiClient;
+        use serde_json::json;
+        use wiremock::{
+            matchers::{method, path},
+            Mock, MockServer, ResponseTemplate,
+        };
+
+        use super::*;
+
+        fn quote_body(input_mint: Pubkey, output_mint: Pubkey, amount: u64) -> serde_json::Value {
+            json!({
+                "inputMint": input_mint.to_string(),
+                "inAmount": amount.to_string(),
+                "outputMint": output_mint.to_string(),
+                "outAmount": "1500000",
+                "otherAmountThreshold": "1492500",
+                "swapMode": "ExactIn",
+                "slippageBps": 50,
+                "priceImpactPct": "0",
+                "routePlan": [],
+                "contextSlot": 0,
+                "timeTaken": 0.0,
+            })
+        }
+
+        /// 每个测试使用不同的代币，避免命中其他测试写入的报价缓存
+        async fn quote(
+            jup: &JupiterSwapApiClient,
+            input_mint: Pubkey,
+        ) -> error::Result<QuoteResponse> {
+            get_quote(
+                jup,
+                input_mint,
+                Pubkey::new_unique(),
+                TokenAmount::new(input_mint, 10_000_000),
+                Bps::new(50).unwrap(),
+                SwapMode::ExactIn,
+            )
+            .await
+        }
+
+        #[tokio::test]
+        async fn rate_limited_quotes_are_retried() {
+            let server = MockServer::start().await;
+            let input_mint = Pubkey::new_unique();
+            Mock::given(method("GET"))
+                .and(path("/quote"))
+                .respond_with(ResponseTemplate::new(429).set_body_string("Too Many Requests"))
+                .up_to_n_times(1)
+                .with_priority(1)
+                .mount(&server)
+                .await;
+            Mock::given(method("GET"))
+                .and(path("/quote"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(quote_body(
+                    input_mint,
+                    Pubkey::new_unique(),
+                    10_000_000,
+                )))
+                .mount(&server)
+                .await;
+
+            let jup = JupiterSwapApiClient::new(server.uri());
+            let quote = quote(&jup, input_mint).await.unwrap();
+            assert_eq!(quote.out_amount, 1_500_000);
+            assert_eq!(server.received_requests().await.unwrap().len(), 2);
+        }
+
+        #[tokio::test]
+        async fn client_errors_are_not_retried() {
+            let server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .and(path("/quote"))
+                .respond_with(
+                    ResponseTemplate::new(400).set_body_string("Could not find any route"),
+                )
+                .mount(&server)
+                .await;
+
+            let jup = JupiterSwapApiClient::new(server.uri());
+            let err = quote(&jup, Pubkey::new_unique()).await.unwrap_err();
+            assert!(matches!(err, LimitOrderError::QuoteFailed(_)), "{:?}", err);
+            assert_eq!(server.received_requests().await.unwrap().len(), 1);
+        }
+
+        #[tokio::test]
+        async fn quotes_are_reused_until_the_cache_ttl_expires() {
+            let ttl = Duration::from_millis(quote_policy().cache_ttl_ms);
+            assert!(!ttl.is_zero());
+            let server = MockServer::start().await;
+            let input_mint = Pubkey::new_unique();
+            let output_mint = Pubkey::new_unique();
+            Mock::given(method("GET"))
+                .and(path("/quote"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(quote_body(
+                    input_mint,
+                    output_mint,
+                    10_000_000,
+                )))
+                .mount(&server)
+                .await;
+
+            let jup = JupiterSwapApiClient::new(server.uri());
+            let request = || {
+                get_quote(
+                    &jup,
+                    input_mint,
+                    output_mint,
+                    TokenAmount::new(input_mint, 10_000_000),
+                    Bps::new(50).unwrap(),
+                    SwapMode::ExactIn,
+                )
+            };
+            request().await.unwrap();
+            request().await.unwrap();
+            assert_eq!(server.received_requests().await.unwrap().len(), 1);
+
+            // 数量不同的报价不复用
+            get_quote(
+                &jup,
+                input_mint,
+                output_mint,
+                TokenAmount::new(input_mint, 20_000_000),
+                Bps::new(50).unwrap(),
+                SwapMode::ExactIn,
+            )
+            .await
+            .unwrap();
+            assert_eq!(server.received_requests().await.unwrap().len(), 2);
+
+            tokio::time::sleep(ttl + Duration::from_millis(50)).await;
+            request().await.unwrap();
+            assert_eq!(server.received_requests().await.unwrap().len(), 3);
+        }
+    }
 }
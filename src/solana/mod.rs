@@ -1,3 +1,8 @@
+pub mod clients;
+pub mod decode;
+pub mod fill;
 pub mod jito;
 pub mod jup;
+pub mod multi_rpc;
+pub mod signer;
 pub mod swap;
@@ -0,0 +1,688 @@
+//! 多个 RPC 节点的故障切换
+//!
+//! 单个 RPC 节点限流或故障时整个订单簿都会停滞。[`MultiRpc`] 按配置顺序使用多个节点：
+//! 请求失败或耗时过长的节点被降级，排到健康节点之后；后台任务定期探测降级的节点，响应正常后恢复。
+//! 订单簿和交换流程只依赖 [`SolanaRpc`]，因此可以直接替换单个 `RpcClient`。
+
+use std::{
+    env,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::Value;
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    nonblocking::rpc_client::RpcClient,
+    rpc_request::RpcError,
+    rpc_response::RpcKeyedAccount,
+};
+use solana_sdk::{
+    account::Account, hash::Hash, pubkey::Pubkey, signature::Signature,
+    transaction::VersionedTransaction,
+};
+use tokio::task::JoinSet;
+
+use super::clients::{SignatureStatus, Simulation, SolanaRpc};
+
+/// 节点返回的 "Node is unhealthy" 错误码，节点落后时返回
+const NODE_UNHEALTHY: i64 = -32005;
+
+/// 多个 RPC 节点的配置
+#[derive(Debug, Clone)]
+pub struct MultiRpcConfig {
+    /// 按优先级排列的节点地址
+    pub urls: Vec<String>,
+    /// 耗时超过该值的请求视为慢请求，节点被降级
+    pub slow_call: Duration,
+    /// 探测降级节点的间隔
+    pub probe_interval: Duration,
+    /// 发送交易时是否同时发往所有健康节点，第一个成功的结果生效
+    pub broadcast_sends: bool,
+}
+
+impl MultiRpcConfig {
+    /// 从环境变量 `RPC_URL`（逗号分隔的节点地址，按优先级排列）、`RPC_SLOW_CALL_MS`、
+    /// `RPC_PROBE_INTERVAL_SECS` 和 `RPC_BROADCAST_SENDS` 读取，后三者未配置时使用默认值
+    pub fn from_env() -> Result<MultiRpcConfig> {
+        let urls: Vec<String> = env::var("RPC_URL")
+            .map_err(|_| anyhow!("缺少 RPC_URL"))?
+            .split(',')
+            .map(|url| url.trim().to_string())
+            .filter(|url| !url.is_empty())
+            .collect();
+        if urls.is_empty() {
+            return Err(anyhow!("RPC_URL 至少需要一个节点地址"));
+        }
+        let mut config = MultiRpcConfig {
+            urls,
+            slow_call: Duration::from_millis(2_000),
+            probe_interval: Duration::from_secs(15),
+            broadcast_sends: false,
+        };
+        if let Ok(v) = env::var("RPC_SLOW_CALL_MS") {
+            config.slow_call = Duration::from_millis(v.parse()?);
+        }
+        if let Ok(v) = env::var("RPC_PROBE_INTERVAL_SECS") {
+            let secs: u64 = v.parse()?;
+            if secs == 0 {
+                return Err(anyhow!("RPC_PROBE_INTERVAL_SECS 必须大于 0"));
+            }
+            config.probe_interval = Duration::from_secs(secs);
+        }
+        if let Ok(v) = env::var("RPC_BROADCAST_SENDS") {
+            config.broadcast_sends = v.parse()?;
+        }
+        Ok(config)
+    }
+}
+
+/// 单个节点的健康状态
+#[derive(Debug, Default)]
+struct EndpointHealth {
+    demoted: bool,
+    consecutive_failures: u32,
+    last_latency_ms: Option<u64>,
+    last_error: Option<String>,
+}
+
+struct Endpoint {
+    /// 去掉路径和参数后的地址，节点地址中常带有 API key，日志和健康检查只使用该地址
+    label: String,
+    client: Arc<RpcClient>,
+    health: Mutex<EndpointHealth>,
+}
+
+impl Endpoint {
+    fn is_demoted(&self) -> bool {
+        self.health.lock().unwrap().demoted
+    }
+
+    /// 按请求结果更新健康状态：节点故障或慢请求降级，正常的请求恢复
+    ///
+    /// 交易或参数错误与节点无关，不影响健康状态。
+    fn record<T>(&self, result: &Result<T>, elapsed: Duration, slow_call: Duration) {
+        let mut health = self.health.lock().unwrap();
+        health.last_latency_ms = Some(elapsed.as_millis() as u64);
+        let reason = match result {
+            Ok(_) if elapsed <= slow_call => {
+                if health.demoted {
+                    println!("RPC 节点 {} 已恢复", self.label);
+                }
+                health.demoted = false;
+                health.consecutive_failures = 0;
+                return;
+            }
+            Ok(_) => format!("请求耗时 {} ms", elapsed.as_millis()),
+            Err(e) if is_endpoint_failure(e) => {
+                health.consecutive_failures += 1;
+                format!("{:#}", e)
+            }
+            Err(_) => return,
+        };
+        if !health.demoted {
+            println!("RPC 节点 {} 降级：{}", self.label, reason);
+        }
+        health.demoted = true;
+        health.last_error = Some(reason);
+    }
+}
+
+/// 节点的健康状态，用于健康检查
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct EndpointStatus {
+    /// 去掉路径和参数后的节点地址
+    pub url: String,
+    pub healthy: bool,
+    pub consecutive_failures: u32,
+    pub last_latency_ms: Option<u64>,
+    /// 最近一次降级的原因
+    pub last_error: Option<String>,
+}
+
+/// 带故障切换的多节点 RPC 客户端
+///
+/// 每个请求先发往健康的节点（按配置顺序），节点故障（连接失败、超时、限流、节点不健康）时换用下一个节点，
+/// 降级的节点排在最后，所有节点都降级时仍按顺序尝试。交易执行失败等与节点无关的错误直接返回，不切换节点。
+/// 配置 `broadcast_sends` 时交易同时发往所有健康节点，交易内容相同，签名一致，第一个确认的结果生效。
+pub struct MultiRpc {
+    endpoints: Vec<Endpoint>,
+    slow_call: Duration,
+    broadcast_sends: bool,
+}
+
+impl MultiRpc {
+    pub fn new(config: &MultiRpcConfig) -> MultiRpc {
+        let clients = config
+            .urls
+            .iter()
+            .map(|url| Arc::new(RpcClient::new(url.clone())))
+            .collect();
+        MultiRpc::with_clients(clients, config.slow_call, config.broadcast_sends)
+    }
+
+    /// 使用已创建的客户端，例如 `RpcClient::new_mock` 创建的模拟节点，`clients` 不能为空
+    pub fn with_clients(
+        clients: Vec<Arc<RpcClient>>,
+        slow_call: Duration,
+        broadcast_sends: bool,
+    ) -> MultiRpc {
+        MultiRpc {
+            endpoints: clients
+                .into_iter()
+                .map(|client| Endpoint {
+                    label: redact_url(&client.url()),
+                    client,
+                    health: Mutex::new(EndpointHealth::default()),
+                })
+                .collect(),
+            slow_call,
+            broadcast_sends,
+        }
+    }
+
+    /// 创建客户端并启动后台探测任务，任务在客户端释放后退出
+    pub fn spawn(config: &MultiRpcConfig) -> Arc<MultiRpc> {
+        let rpc = Arc::new(MultiRpc::new(config));
+        let weak = Arc::downgrade(&rpc);
+        let interval = config.probe_interval;
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                let Some(rpc) = weak.upgrade() else {
+                    break;
+                };
+                rpc.probe().await;
+            }
+        });
+        rpc
+    }
+
+    /// 当前优先使用的节点
+    pub fn client(&self) -> Arc<RpcClient> {
+        self.ordered()[0].client.clone()
+    }
+
+    /// 各节点的健康状态，按配置顺序排列
+    pub fn status(&self) -> Vec<EndpointStatus> {
+        self.endpoints
+            .iter()
+            .map(|endpoint| {
+                let health = endpoint.health.lock().unwrap();
+                EndpointStatus {
+                    url: endpoint.label.clone(),
+                    healthy: !health.demoted,
+                    consecutive_failures: health.consecutive_failures,
+                    last_latency_ms: health.last_latency_ms,
+                    last_error: health.last_error.clone(),
+                }
+            })
+            .collect()
+    }
+
+    /// 探测降级的节点，响应正常且不慢的节点恢复
+    pub async fn probe(&self) {
+        for endpoint in self.endpoints.iter().filter(|e| e.is_demoted()) {
+            let started = Instant::now();
+            let result = SolanaRpc::get_block_height(endpoint.client.as_ref()).await;
+            endpoint.record(&result, started.elapsed(), self.slow_call);
+        }
+    }
+
+    /// 健康的节点在前，降级的节点在后，各自保持配置顺序
+    fn ordered(&self) -> Vec<&Endpoint> {
+        let (healthy, demoted): (Vec<_>, Vec<_>) =
+            self.endpoints.iter().partition(|e| !e.is_demoted());
+        healthy.into_iter().chain(demoted).collect()
+    }
+
+    /// 按顺序向节点发送请求，节点故障时换用下一个节点
+    async fn call<T, F, Fut>(&self, method: &str, request: F) -> Result<T>
+    where
+        F: Fn(Arc<RpcClient>) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut last_err = None;
+        for endpoint in self.ordered() {
+            let started = Instant::now();
+            let result = request(endpoint.client.clone()).await;
+            endpoint.record(&result, started.elapsed(), self.slow_call);
+            match result {
+                Ok(value) => return Ok(value),
+                Err(e) if is_endpoint_failure(&e) => {
+                    println!("RPC 节点 {} 的 {} 请求失败 {:#}", endpoint.label, method, e);
+                    last_err = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        match last_err {
+            Some(e) => Err(e.context(format!("所有 RPC 节点的 {} 请求均失败", method))),
+            None => Err(anyhow!("没有配置 RPC 节点")),
+        }
+    }
+
+    /// 同时向所有健康节点发送交易并等待确认，第一个成功的结果生效，其余请求随即取消
+    async fn broadcast_send(&self, tx: &VersionedTransaction) -> Result<Signature> {
+        let healthy: Vec<usize> = (0..self.endpoints.len())
+            .filter(|&index| !self.endpoints[index].is_demoted())
+            .collect();
+        if healthy.len() < 2 {
+            return self
+                .call("sendTransaction", |client| async move {
+                    SolanaRpc::send(client.as_ref(), tx).await
+                })
+                .await;
+        }
+        let mut sends = JoinSet::new();
+        for index in healthy {
+            let client = self.endpoints[index].client.clone();
+            let tx = tx.clone();
+            sends.spawn(async move {
+                let started = Instant::now();
+                let result = SolanaRpc::send(client.as_ref(), &tx).await;
+                (index, started.elapsed(), result)
+            });
+        }
+        let mut last_err = None;
+        while let Some(joined) = sends.join_next().await {
+            let (index, elapsed, result) = joined.context("发送交易的任务异常退出")?;
+            let endpoint = &self.endpoints[index];
+            endpoint.record(&result, elapsed, self.slow_call);
+            match result {
+                // 各节点发送的是同一笔交易，签名相同，只返回第一个确认的结果
+                Ok(signature) => return Ok(signature),
+                Err(e) => {
+                    println!("RPC 节点 {} 发送交易失败 {:#}", endpoint.label, e);
+                    last_err = Some(e);
+                }
+            }
+        }
+        Err(last_err.unwrap_or_else(|| anyhow!("没有可用的 RPC 节点")))
+    }
+}
+
+#[async_trait]
+impl SolanaRpc for MultiRpc {
+    async fn get_latest_blockhash(&self) -> Result<Hash> {
+        self.call("getLatestBlockhash", |client| async move {
+            SolanaRpc::get_latest_blockhash(client.as_ref()).await
+        })
+        .await
+    }
+
+    async fn get_latest_blockhash_with_height(&self) -> Result<(Hash, u64)> {
+        self.call("getLatestBlockhash", |client| async move {
+            SolanaRpc::get_latest_blockhash_with_height(client.as_ref()).await
+        })
+        .await
+    }
+
+    async fn get_block_height(&self) -> Result<u64> {
+        self.call("getBlockHeight", |client| async move {
+            SolanaRpc::get_block_height(client.as_ref()).await
+        })
+        .await
+    }
+
+    async fn get_slot(&self) -> Result<u64> {
+        self.call("getSlot", |client| async move {
+            SolanaRpc::get_slot(client.as_ref()).await
+        })
+        .await
+    }
+
+    async fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+        self.call("getMultipleAccounts", |client| async move {
+            SolanaRpc::get_multiple_accounts(client.as_ref(), pubkeys).await
+        })
+        .await
+    }
+
+    async fn simulate(&self, tx: &VersionedTransaction) -> Result<Simulation> {
+        self.call("simulateTransaction", |client| async move {
+            SolanaRpc::simulate(client.as_ref(), tx).await
+        })
+        .await
+    }
+
+    async fn simulate_with_accounts(
+        &self,
+        tx: &VersionedTransaction,
+        addresses: &[Pubkey],
+    ) -> Result<Simulation> {
+        self.call("simulateTransaction", |client| async move {
+            SolanaRpc::simulate_with_accounts(client.as_ref(), tx, addresses).await
+        })
+        .await
+    }
+
+    async fn send(&self, tx: &VersionedTransaction) -> Result<Signature> {
+        if self.broadcast_sends {
+            return self.broadcast_send(tx).await;
+        }
+        // 同一笔交易重复发送不会重复执行，换用下一个节点重发是安全的
+        self.call("sendTransaction", |client| async move {
+            SolanaRpc::send(client.as_ref(), tx).await
+        })
+        .await
+    }
+
+    async fn signature_status(&self, signature: &Signature) -> Result<SignatureStatus> {
+        self.call("getSignatureStatuses", |client| async move {
+            SolanaRpc::signature_status(client.as_ref(), signature).await
+        })
+        .await
+    }
+
+    async fn signature_statuses(&self, signatures: &[Signature]) -> Result<Vec<SignatureStatus>> {
+        self.call("getSignatureStatuses", |client| async move {
+            SolanaRpc::signature_statuses(client.as_ref(), signatures).await
+        })
+        .await
+    }
+
+    async fn get_recent_prioritization_fees(&self, accounts: &[Pubkey]) -> Result<Vec<u64>> {
+        self.call("getRecentPrioritizationFees", |client| async move {
+            SolanaRpc::get_recent_prioritization_fees(client.as_ref(), accounts).await
+        })
+        .await
+    }
+
+    async fn get_parsed_transaction(&self, signature: &Signature) -> Result<Option<Value>> {
+        self.call("getTransaction", |client| async move {
+            SolanaRpc::get_parsed_transaction(client.as_ref(), signature).await
+        })
+        .await
+    }
+
+    async fn get_token_accounts_by_owner(
+        &self,
+        owner: &Pubkey,
+        token_program: &Pubkey,
+    ) -> Result<Vec<RpcKeyedAccount>> {
+        self.call("getTokenAccountsByOwner", |client| async move {
+            SolanaRpc::get_token_accounts_by_owner(client.as_ref(), owner, token_program).await
+        })
+        .await
+    }
+
+    async fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> Result<u64> {
+        self.call("getMinimumBalanceForRentExemption", |client| async move {
+            SolanaRpc::get_minimum_balance_for_rent_exemption(client.as_ref(), data_len).await
+        })
+        .await
+    }
+
+    async fn get_balance(&self, pubkey: &Pubkey) -> Result<u64> {
+        self.call("getBalance", |client| async move {
+            SolanaRpc::get_balance(client.as_ref(), pubkey).await
+        })
+        .await
+    }
+
+    fn endpoint_status(&self) -> Vec<EndpointStatus> {
+        self.status()
+    }
+}
+
+/// 节点本身的故障：连接失败、超时、限流或节点不健康，换用其他节点可能成功
+///
+/// 交易执行失败、参数错误等由请求本身导致的错误不算节点故障。
+fn is_endpoint_failure(e: &anyhow::Error) -> bool {
+    match e.downcast_ref::<ClientError>().map(ClientError::kind) {
+        Some(
+            ClientErrorKind::Io(_) | ClientErrorKind::Reqwest(_) | ClientErrorKind::Middleware(_),
+        ) => true,
+        Some(ClientErrorKind::RpcError(RpcError::RpcRequestError(_))) => true,
+        Some(ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. })) => {
+            *code == NODE_UNHEALTHY
+        }
+        _ => false,
+    }
+}
+
+/// 只保留协议和主机部分，例如 `https://mainnet.helius-rpc.com/?api-key=...` 显示为 `https://mainnet.helius-rpc.com`
+fn redact_url(url: &str) -> String {
+    let (scheme, rest) = url.split_once("://").unwrap_or(("", url));
+    let host = rest.split(['/', '?']).next().unwrap_or(rest);
+    if scheme.is_empty() {
+        host.to_string()
+    } else {
+        format!("{}://{}", scheme, host)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use serde_json::Value;
+    use solana_client::{
+        client_error::Result as ClientResult,
+        mock_sender::MockSender,
+        rpc_client::RpcClientConfig,
+        rpc_request::RpcRequest,
+        rpc_sender::{RpcSender, RpcTransportStats},
+    };
+    use solana_sdk::{
+        commitment_config::CommitmentConfig, signature::Keypair, signer::Signer,
+        system_instruction, transaction::Transaction,
+    };
+
+    use super::*;
+
+    /// 可以随时断开的模拟节点：断开时所有请求返回连接失败，正常时由 `MockSender` 应答，并记录发送的交易
+    struct FlakySender {
+        inner: MockSender,
+        down: Arc<AtomicBool>,
+        calls: Arc<Mutex<Vec<RpcRequest>>>,
+        sent: Arc<Mutex<Vec<Value>>>,
+    }
+
+    #[async_trait]
+    impl RpcSender for FlakySender {
+        async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+            self.calls.lock().unwrap().push(request);
+            if self.down.load(Ordering::SeqCst) {
+                return Err(RpcError::RpcRequestError("connection refused".to_string()).into());
+            }
+            if request == RpcRequest::SendTransaction {
+                self.sent.lock().unwrap().push(params[0].clone());
+            }
+            self.inner.send(request, params).await
+        }
+
+        fn get_transport_stats(&self) -> RpcTransportStats {
+            self.inner.get_transport_stats()
+        }
+
+        fn url(&self) -> String {
+            self.inner.url()
+        }
+    }
+
+    /// 模拟节点的开关和请求记录
+    struct FlakyNode {
+        client: Arc<RpcClient>,
+        down: Arc<AtomicBool>,
+        calls: Arc<Mutex<Vec<RpcRequest>>>,
+        sent: Arc<Mutex<Vec<Value>>>,
+    }
+
+    impl FlakyNode {
+        fn new(url: &str, down: bool) -> FlakyNode {
+            let down = Arc::new(AtomicBool::new(down));
+            let calls = Arc::new(Mutex::new(vec![]));
+            let sent = Arc::new(Mutex::new(vec![]));
+            let sender = FlakySender {
+                inner: MockSender::new(url),
+                down: down.clone(),
+                calls: calls.clone(),
+                sent: sent.clone(),
+            };
+            FlakyNode {
+                client: Arc::new(RpcClient::new_sender(
+                    sender,
+                    RpcClientConfig::with_commitment(CommitmentConfig::default()),
+                )),
+                down,
+                calls,
+                sent,
+            }
+        }
+
+        fn calls(&self) -> usize {
+            self.calls.lock().unwrap().len()
+        }
+    }
+
+    /// 使用 legacy 消息的转账交易，`MockSender` 按 legacy 交易解析发送的数据
+    fn transfer_transaction() -> VersionedTransaction {
+        let payer = Keypair::new();
+        Transaction::new_signed_with_payer(
+            &[system_instruction::transfer(
+                &payer.pubkey(),
+                &Pubkey::new_unique(),
+                1,
+            )],
+            Some(&payer.pubkey()),
+            &[&payer],
+            Hash::default(),
+        )
+        .into()
+    }
+
+    #[test]
+    fn redact_url_drops_path_and_query() {
+        assert_eq!(
+            redact_url("https://mainnet.helius-rpc.com/?api-key=secret"),
+            "https://mainnet.helius-rpc.com"
+        );
+        assert_eq!(redact_url("http://127.0.0.1:8899"), "http://127.0.0.1:8899");
+        assert_eq!(redact_url("succeeds"), "succeeds");
+    }
+
+    /// 故障的节点被降级，请求换用下一个节点；降级的节点排到最后，之后的请求不再先发往它
+    #[tokio::test]
+    async fn failing_endpoint_is_demoted_and_skipped() {
+        let flaky = FlakyNode::new("flaky", true);
+        let healthy = Arc::new(RpcClient::new_mock("succeeds".to_string()));
+        let rpc = MultiRpc::with_clients(
+            vec![flaky.client.clone(), healthy.clone()],
+            Duration::from_secs(5),
+            false,
+        );
+        let expected = SolanaRpc::get_block_height(healthy.as_ref()).await.unwrap();
+
+        assert_eq!(rpc.get_block_height().await.unwrap(), expected);
+        let status = rpc.status();
+        assert!(!status[0].healthy);
+        assert_eq!(status[0].consecutive_failures, 1);
+        assert!(status[0]
+            .last_error
+            .as_deref()
+            .is_some_and(|e| e.contains("connection refused")));
+        assert!(status[1].healthy);
+        assert_eq!(rpc.client().url(), "succeeds");
+
+        assert_eq!(rpc.get_block_height().await.unwrap(), expected);
+        assert_eq!(flaky.calls(), 1, "降级的节点不应再被优先请求");
+    }
+
+    /// 所有节点都故障时返回最后一个错误
+    #[tokio::test]
+    async fn all_endpoints_failing_returns_error() {
+        let first = FlakyNode::new("first", true);
+        let second = FlakyNode::new("second", true);
+        let rpc = MultiRpc::with_clients(
+            vec![first.client.clone(), second.client.clone()],
+            Duration::from_secs(5),
+            false,
+        );
+        let err = rpc.get_block_height().await.unwrap_err();
+        assert!(format!("{:#}", err).contains("所有 RPC 节点的 getBlockHeight 请求均失败"));
+        assert_eq!((first.calls(), second.calls()), (1, 1));
+        assert!(rpc.status().iter().all(|status| !status.healthy));
+    }
+
+    /// 探测时仍然故障的节点保持降级，恢复后重新排在前面
+    #[tokio::test]
+    async fn probe_restores_recovered_endpoint() {
+        let flaky = FlakyNode::new("flaky", true);
+        let healthy = Arc::new(RpcClient::new_mock("succeeds".to_string()));
+        let rpc = MultiRpc::with_clients(
+            vec![flaky.client.clone(), healthy],
+            Duration::from_secs(5),
+            false,
+        );
+        rpc.get_block_height().await.unwrap();
+        assert!(!rpc.status()[0].healthy);
+
+        rpc.probe().await;
+        assert!(!rpc.status()[0].healthy);
+        assert_eq!(rpc.status()[0].consecutive_failures, 2);
+
+        flaky.down.store(false, Ordering::SeqCst);
+        rpc.probe().await;
+        let status = rpc.status();
+        assert!(status[0].healthy);
+        assert_eq!(status[0].consecutive_failures, 0);
+        assert_eq!(rpc.client().url(), "flaky");
+
+        let calls = flaky.calls();
+        rpc.get_block_height().await.unwrap();
+        assert_eq!(flaky.calls(), calls + 1, "恢复的节点应重新被优先请求");
+    }
+
+    /// 广播发送时各节点收到的是同一笔交易，只返回一个签名，即交易本身的签名
+    #[tokio::test]
+    async fn broadcast_send_returns_the_transaction_signature_once() {
+        let first = FlakyNode::new("first", false);
+        let second = FlakyNode::new("second", false);
+        let rpc = MultiRpc::with_clients(
+            vec![first.client.clone(), second.client.clone()],
+            Duration::from_secs(5),
+            true,
+        );
+        let tx = transfer_transaction();
+
+        let signature = rpc.send(&tx).await.unwrap();
+        assert_eq!(signature, tx.signatures[0]);
+
+        // 第一个确认的结果生效后其余发送被取消，因此至少一个节点收到交易，收到的交易完全相同
+        let sent: Vec<Value> = [&first, &second]
+            .iter()
+            .flat_map(|node| node.sent.lock().unwrap().clone())
+            .collect();
+        assert!(!sent.is_empty());
+        assert!(sent.iter().all(|payload| *payload == sent[0]));
+        assert!(rpc.status().iter().all(|status| status.healthy));
+    }
+
+    /// 只有一个健康节点时不广播，降级的节点排在最后作为备用
+    #[tokio::test]
+    async fn broadcast_with_single_healthy_endpoint_fails_over() {
+        let flaky = FlakyNode::new("flaky", true);
+        let healthy = FlakyNode::new("healthy", false);
+        let rpc = MultiRpc::with_clients(
+            vec![flaky.client.clone(), healthy.client.clone()],
+            Duration::from_secs(5),
+            true,
+        );
+        rpc.get_block_height().await.unwrap();
+        let flaky_calls = flaky.calls();
+
+        let tx = transfer_transaction();
+        assert_eq!(rpc.send(&tx).await.unwrap(), tx.signatures[0]);
+        assert_eq!(flaky.calls(), flaky_calls, "降级的节点不参与广播");
+        assert_eq!(healthy.sent.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,275 @@
+//! 交易签名的抽象
+//!
+//! 交换和归集只依赖 [`TransactionSigner`]，私钥可以保存在本进程（[`LocalKeypairSigner`]），
+//! 也可以交给硬件钱包或远程签名服务（[`RemoteHttpSigner`]），本进程只提交待签名的消息。
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use base64::{engine::general_purpose, Engine};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+use solana_sdk::{
+    message::VersionedMessage,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::Signer,
+    transaction::VersionedTransaction,
+};
+
+/// 远程签名请求的超时
+const REMOTE_SIGN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 交易签名者
+#[async_trait]
+pub trait TransactionSigner: Send + Sync {
+    /// 签名者的公钥
+    fn pubkey(&self) -> Pubkey;
+
+    /// 对序列化后的交易消息签名
+    async fn sign_message(&self, message: &[u8]) -> Result<Signature>;
+}
+
+/// 使用本进程持有的私钥签名
+#[derive(Clone)]
+pub struct LocalKeypairSigner(Arc<Keypair>);
+
+impl LocalKeypairSigner {
+    pub fn new(keypair: Keypair) -> LocalKeypairSigner {
+        LocalKeypairSigner(Arc::new(keypair))
+    }
+}
+
+impl From<Arc<Keypair>> for LocalKeypairSigner {
+    fn from(keypair: Arc<Keypair>) -> LocalKeypairSigner {
+        LocalKeypairSigner(keypair)
+    }
+}
+
+#[async_trait]
+impl TransactionSigner for LocalKeypairSigner {
+    fn pubkey(&self) -> Pubkey {
+        self.0.pubkey()
+    }
+
+    async fn sign_message(&self, message: &[u8]) -> Result<Signature> {
+        Ok(self.0.sign_message(message))
+    }
+}
+
+/// 远程签名服务，私钥不进入本进程
+///
+/// 以 `POST {url}` 提交 `{"pubkey": "<base58>", "message": "<base64>"}`，
+/// 服务返回 `{"signature": "<base58>"}`。配置了 `token` 时以 `Authorization: Bearer <token>` 认证。
+/// 返回的签名会按消息和公钥校验，签名服务不能替换交易内容。
+pub struct RemoteHttpSigner {
+    http: Client,
+    url: String,
+    pubkey: Pubkey,
+    token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RemoteSignResponse {
+    signature: String,
+}
+
+impl RemoteHttpSigner {
+    pub fn new(http: Client, url: String, pubkey: Pubkey, token: Option<String>) -> Self {
+        RemoteHttpSigner {
+            http,
+            url,
+            pubkey,
+            token,
+        }
+    }
+}
+
+#[async_trait]
+impl TransactionSigner for RemoteHttpSigner {
+    fn pubkey(&self) -> Pubkey {
+        self.pubkey
+    }
+
+    async fn sign_message(&self, message: &[u8]) -> Result<Signature> {
+        let mut request = self
+            .http
+            .post(&self.url)
+            .json(&json!({
+                "pubkey": self.pubkey.to_string(),
+                "message": general_purpose::STANDARD.encode(message),
+            }))
+            .timeout(REMOTE_SIGN_TIMEOUT);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        let resp = request
+            .send()
+            .await
+            .map_err(|e| anyhow!("请求签名服务失败 {}", e))?;
+        if !resp.status().is_success() {
+            return Err(anyhow!("签名服务返回 {}", resp.status()));
+        }
+        let body: RemoteSignResponse = resp
+            .json()
+            .await
+            .map_err(|e| anyhow!("签名服务响应格式错误 {}", e))?;
+        let signature: Signature = body
+            .signature
+            .parse()
+            .map_err(|_| anyhow!("签名服务返回的签名无效"))?;
+        if !signature.verify(self.pubkey.as_ref(), message) {
+            return Err(anyhow!("签名服务返回的签名与 {} 不匹配", self.pubkey));
+        }
+        Ok(signature)
+    }
+}
+
+/// 由 `signers` 对消息签名，组成完整签名的交易
+///
+/// 消息要求的每个签名者都必须在 `signers` 中，多余的签名者被忽略。
+pub async fn sign_versioned_message(
+    message: VersionedMessage,
+    signers: &[&dyn TransactionSigner],
+) -> Result<VersionedTransaction> {
+    let required = message.header().num_required_signatures as usize;
+    let message_bytes = message.serialize();
+    let mut signatures = Vec::with_capacity(required);
+    for key in &message.static_account_keys()[..required] {
+        let signer = signers
+            .iter()
+            .find(|signer| signer.pubkey() == *key)
+            .ok_or_else(|| anyhow!("缺少 {} 的签名", key))?;
+        signatures.push(signer.sign_message(&message_bytes).await?);
+    }
+    Ok(VersionedTransaction {
+        signatures,
+        message,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::{
+        hash::Hash,
+        message::{v0, VersionedMessage},
+        system_instruction,
+    };
+
+    use super::*;
+
+    /// `payer` 向新地址转账的 V0 消息
+    fn v0_message(payer: &Pubkey) -> v0::Message {
+        v0::Message::try_compile(
+            payer,
+            &[system_instruction::transfer(
+                payer,
+                &Pubkey::new_unique(),
+                1,
+            )],
+            &[],
+            Hash::new_unique(),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn missing_signer_is_rejected() {
+        let payer = Keypair::new();
+        let other = LocalKeypairSigner::new(Keypair::new());
+        let message = VersionedMessage::V0(v0_message(&payer.pubkey()));
+        let err = sign_versioned_message(message, &[&other])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains(&payer.pubkey().to_string()));
+    }
+
+    /// 按 [`RemoteHttpSigner`] 的接口应答的签名服务
+    #[cfg(feature = "testing")]
+    mod remote {
+        use wiremock::{
+            matchers::{header, method, path},
+            Mock, MockServer, Request, Respond, ResponseTemplate,
+        };
+
+        use super::*;
+
+        /// 用 `keypair` 对请求中的消息签名
+        struct SigningService {
+            keypair: Keypair,
+        }
+
+        impl Respond for SigningService {
+            fn respond(&self, request: &Request) -> ResponseTemplate {
+                let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+                let message = general_purpose::STANDARD
+                    .decode(body["message"].as_str().unwrap())
+                    .unwrap();
+                ResponseTemplate::new(200).set_body_json(json!({
+                    "signature": self.keypair.sign_message(&message).to_string(),
+                }))
+            }
+        }
+
+        async fn signing_server(keypair: Keypair) -> MockServer {
+            let server = MockServer::start().await;
+            Mock::given(method("POST"))
+                .and(path("/sign"))
+                .and(header("Authorization", "Bearer signer-token"))
+                .respond_with(SigningService { keypair })
+                .mount(&server)
+                .await;
+            server
+        }
+
+        /// 签名服务收到的是编译后的 V0 消息本身，返回的签名组成可以验证的交易
+        #[tokio::test]
+        async fn remote_signer_signs_the_compiled_v0_message() {
+            let keypair = Keypair::new();
+            let pubkey = keypair.pubkey();
+            let server = signing_server(keypair).await;
+            let signer = RemoteHttpSigner::new(
+                Client::new(),
+                format!("{}/sign", server.uri()),
+                pubkey,
+                Some("signer-token".to_string()),
+            );
+            let message = VersionedMessage::V0(v0_message(&pubkey));
+
+            let tx = sign_versioned_message(message.clone(), &[&signer])
+                .await
+                .unwrap();
+
+            let requests = server.received_requests().await.unwrap();
+            assert_eq!(requests.len(), 1);
+            let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+            assert_eq!(body["pubkey"], pubkey.to_string());
+            let signed = general_purpose::STANDARD
+                .decode(body["message"].as_str().unwrap())
+                .unwrap();
+            assert_eq!(signed, message.serialize());
+            assert_eq!(tx.message, message);
+            assert!(tx.verify_with_results().into_iter().all(|ok| ok));
+        }
+
+        /// 签名服务用其他私钥签名时拒绝返回的签名
+        #[tokio::test]
+        async fn signature_from_another_key_is_rejected() {
+            let pubkey = Keypair::new().pubkey();
+            let server = signing_server(Keypair::new()).await;
+            let signer = RemoteHttpSigner::new(
+                Client::new(),
+                format!("{}/sign", server.uri()),
+                pubkey,
+                Some("signer-token".to_string()),
+            );
+            let message = VersionedMessage::V0(v0_message(&pubkey));
+            let err = sign_versioned_message(message, &[&signer])
+                .await
+                .unwrap_err();
+            assert!(err.to_string().contains("不匹配"), "{}", err);
+        }
+    }
+}
@@ -1,23 +1,138 @@
-use std::sync::Arc;
+use std::{str::FromStr, time::Duration};
 
 use anyhow::{anyhow, Result};
-use jito_sdk_rust::JitoJsonRpcSDK;
-use jupiter_swap_api_client::JupiterSwapApiClient;
-use solana_client::nonblocking::rpc_client::RpcClient;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use solana_sdk::account::Account;
 use solana_sdk::address_lookup_table::state::AddressLookupTable;
 use solana_sdk::address_lookup_table::AddressLookupTableAccount;
-use solana_sdk::message::v0::Message;
+use solana_sdk::compute_budget::ComputeBudgetInstruction;
+use solana_sdk::hash::Hash;
+use solana_sdk::packet::PACKET_DATA_SIZE;
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey;
 use solana_sdk::pubkey::Pubkey;
-use solana_sdk::signature::Keypair;
-use solana_sdk::signer::Signer;
+use solana_sdk::signature::Signature;
 use solana_sdk::system_instruction;
 use solana_sdk::transaction::VersionedTransaction;
 
-use crate::common::utils::{build_versioned_transaction, send_bundle};
+use solana_sdk::instruction::Instruction;
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+
+use crate::common::prepared::encode_transaction;
+use crate::common::tax_policy::TaxPolicy;
+use crate::common::units::{Bps, Lamports, TokenAmount};
+use crate::common::utils::{
+    confirm_bundle, creates_ata, ensure_ata_ix, get_address_lookup, get_mint_info,
+    is_blockhash_not_found, send_bundle, sign_versioned_transaction, simulate_or_fail,
+    unsigned_versioned_transaction, with_advance_nonce, BlockhashProvider, BundleConfig,
+    BundleStatus, CachedBlockhash, MintInfo, NonceInfo, PrivateExecutionConfig, TransferFee,
+};
+use crate::error::{self, LimitOrderError};
 use crate::SOL;
 
-use super::jito::get_tip_account;
-use super::jup::get_swap_ix;
+use super::clients::{BundleSender, SolanaRpc, SwapApi};
+use super::jito::{get_tip_account, JitoError};
+use super::jup::{get_quote, get_swap_ix_for_quote, QuoteSummary, RoutePin, SwapMode, SwapOptions};
+use super::signer::{LocalKeypairSigner, TransactionSigner};
+
+/// Token-2022 程序
+pub const TOKEN_2022_PROGRAM_ID: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
+/// 收税的一侧
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum TaxSide {
+    /// 交易前以输入代币收税，适用于任意输入代币
+    #[default]
+    Input,
+    /// 交易后以输出代币收税，不支持 `ExactOut`
+    Output,
+}
+
+impl FromStr for TaxSide {
+    type Err = anyhow::Error;
+
+    /// 支持 `input` 和 `output` 两种写法
+    fn from_str(s: &str) -> Result<TaxSide> {
+        match s {
+            "input" => Ok(TaxSide::Input),
+            "output" => Ok(TaxSide::Output),
+            _ => Err(anyhow!("无法解析的收税方式 {}", s)),
+        }
+    }
+}
+
+/// Jito 限流时重新发送 bundle 前的等待时间
+const BUNDLE_RATE_LIMIT_DELAY: Duration = Duration::from_secs(1);
+/// 单笔交易允许的最大计算单元
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+/// 每个签名的基础手续费
+pub const LAMPORTS_PER_SIGNATURE: u64 = 5_000;
+/// 估算费用时假设一次交换消耗的计算单元
+pub const ESTIMATED_SWAP_COMPUTE_UNITS: u32 = 300_000;
+/// 下单时估算的交易手续费（签名费加上余量），不含创建代币账户的租金
+pub const ESTIMATED_FEE_LAMPORTS: u64 = 20_000;
+/// 根据模拟结果推导计算单元上限时额外预留的比例（百分比）
+const COMPUTE_UNIT_MARGIN_PERCENT: u64 = 20;
+
+/// 交换使用的客户端、发送配置和收税账户，同一个服务内的交换共用
+#[derive(Clone, Copy)]
+pub struct SwapContext<'a> {
+    /// Jupiter Swap API 客户端，生产环境为 `JupiterSwapApiClient`
+    pub jup: &'a dyn SwapApi,
+    /// Solana RPC 客户端，生产环境为 `MultiRpc`（单个节点时即为 `RpcClient`）
+    pub rpc: &'a dyn SolanaRpc,
+    /// Jito 客户端，用于捆绑交易和获取 tip 账户，生产环境为 `JitoJsonRpcSDK`
+    pub jito: &'a dyn BundleSender,
+    /// 共享的 blockhash 缓存，发送前据此检查交易的 blockhash 是否即将过期
+    pub blockhashes: &'a BlockhashProvider,
+    /// bundle 的确认超时及失败后是否改用 RPC 发送
+    pub bundle: BundleConfig,
+    /// 接收税收的账户
+    pub tax_account: Pubkey,
+    /// 以输入代币在交易前收税，或以输出代币在交易后收税
+    pub tax_side: TaxSide,
+}
+
+/// 一次交换的代币、数量和报价要求
+#[derive(Clone, Copy)]
+pub struct SwapParams<'a> {
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    /// `ExactIn` 时为输入代币总量（含税），`ExactOut` 时为期望得到的输出代币数量
+    pub amount: TokenAmount,
+    pub swap_mode: SwapMode,
+    /// 允许的滑点
+    pub slippage_bps: Bps,
+    /// 扣税后的最低输出数量，报价低于该数量时不执行并返回 [`LimitOrderError::MinOutNotMet`]
+    pub min_out_amount: Option<u64>,
+    /// 允许的最大价格影响，报价超过时不执行并返回 [`LimitOrderError::PriceImpactTooHigh`]
+    pub max_price_impact_bps: Option<Bps>,
+    /// SOL 包装、目标代币账户等 Jupiter 交易选项
+    pub options: &'a SwapOptions,
+    /// 固定路由，触发时优先使用其中的报价
+    pub pin: Option<&'a RoutePin>,
+}
+
+/// 交换交易的发送方式
+#[derive(Clone, Default)]
+pub struct ExecutionOptions {
+    /// 大额交换强制以 bundle 发送的阈值、自动 tip 和失败后的处理
+    pub private: PrivateExecution,
+    /// tip 金额，提供时以 Jito bundle 发送
+    pub tip_amount: Option<Lamports>,
+    /// 计算单元上限，为 None 时根据模拟消耗加上余量推导
+    pub compute_unit_limit: Option<u32>,
+    /// 优先费，单位为 micro-lamports / CU，为 None 时不设置
+    ///
+    /// 与 tip 不互斥：指定 tip 或需要私有发送时交换交易同样设置优先费。
+    /// bundle 未上链改用公开 RPC 发送的是同一笔已签名交易，不带优先费在拥堵时难以上链。
+    pub compute_unit_price: Option<u64>,
+    /// 使用 durable nonce 代替最新的 blockhash，交易由 nonce authority 共同签名
+    pub nonce: Option<NonceInfo>,
+    /// 跳过发送前的模拟执行；未指定计算单元上限时使用最大值
+    pub skip_simulation: bool,
+}
 
 /// 在 Solana 区块链上执行带有税收的代币交换操作
 ///
@@ -25,154 +140,1340 @@ use super::jup::get_swap_ix;
 /// 支持 Jito 捆绑交易（bundle transaction）和可选的 tip 支付。
 ///
 /// # 参数
-/// - `jup`: `Arc<JupiterSwapApiClient>` - Jupiter Swap API 客户端的线程安全引用
-/// - `rpc`: `Arc<RpcClient>` - Solana RPC 客户端的线程安全引用
-/// - `jito`: `Arc<JitoJsonRpcSDK>` - Jito SDK 的线程安全引用，用于捆绑交易
-/// - `user_keypair`: `&Keypair` - 用户的密钥对，用于签名交易
-/// - `tax_account`: `Pubkey` - 接收税收的账户公钥
-/// - `tax_bps`: `u16` - 税收百分比，以基点表示（1 bps = 0.01%，10000 bps = 100%）
-/// - `amount`: `u64` - 输入代币的总量
-/// - `input_mint`: `Pubkey` - 输入代币的 mint 地址
-/// - `output_mint`: `Pubkey` - 输出代币的 mint 地址
-/// - `slippage_bps`: `u16` - 允许的滑点，以基点表示
-/// - `tip_amount`: `Option<u64>` - 可选的 tip 金额，用于 Jito 捆绑交易
+/// - `ctx`: `&SwapContext` - Jupiter、RPC 和 Jito 客户端、blockhash 缓存、bundle 配置和收税账户
+/// - `user_signer`: `&dyn TransactionSigner` - 用户的签名者，本地私钥（[`LocalKeypairSigner`]）或远程签名服务
+/// - `tax_policy`: `&TaxPolicy` - 税收策略，按用户钱包和交换的名义价值解析税率（1 bps = 0.01%，10000 bps = 100%）
+/// - `swap`: `&SwapParams` - 交换的代币、数量、报价模式、滑点、最低输出、最大价格影响、交易选项和固定路由
+/// - `exec`: `ExecutionOptions` - 私有发送要求、tip、计算单元上限和优先费、durable nonce 以及是否跳过模拟
 ///
 /// # 返回值
-/// - `Result<()>` - 执行成功返回 `Ok(())`，失败返回错误
+/// - `error::Result<SwapOutcome>` - 执行成功返回上链的交易签名、bundle id（以 bundle 上链时）和报价，
+///   失败返回 [`LimitOrderError`]，例如报价失败、模拟失败或 bundle 未上链
 ///
 /// # 逻辑流程
-/// 1. 判断税收是在交易前（输入为 SOL 时）还是交易后扣除
-/// 2. 计算税收金额并构造税收转账指令
-/// 3. 调用 Jupiter Swap API 获取交换指令
-/// 4. 根据税收时机添加税收指令
-/// 5. 构建并模拟执行交易
-/// 6. 根据是否提供 tip，使用 Jito 捆绑交易或普通交易发送
+/// 1. 按 `tax_policy` 解析本次交换的税率（`amount` 不是 SOL 时按 `exec.private` 中的输入代币价格换算名义价值），
+///    按 `ctx.tax_side` 决定税收在交易前以输入代币扣除，还是在交易后以输出代币扣除
+/// 2. 计算税收金额并构造税收转账指令（SOL 使用系统转账，SPL 与 Token-2022 代币使用 transfer_checked）；
+///    `ExactOut` 时税收按报价的输入数量在交易前额外收取，由于输出数量固定，`ExactOut` 不支持交易后收税
+/// 3. 调用 Jupiter Swap API 获取交换指令，报价扣税后的输出低于 `min_out_amount` 或价格影响超过 `max_price_impact_bps` 时放弃交易
+/// 4. 根据税收时机添加税收指令，交易后税收以输出代币计价（输出为 SOL 时使用系统转账，否则使用 SPL transfer_checked）
+/// 5. 添加计算预算指令，构建并模拟执行最终发送的交易（未指定计算单元上限时以模拟消耗推导），模拟失败时错误中包含程序日志
+/// 6. 提供 tip 时将 tip 转账追加为交换交易的最后一条指令，合并后超过数据包大小时改用单独的 tip 交易；
+///    名义价值超过 `exec.private` 的阈值或要求私有发送时，即使没有提供 tip 也按 tip 表自动选择 tip 并以 bundle 发送
+/// 7. 发送前按当前区块高度检查 blockhash，即将过期时换用新的 blockhash 重新签名
+/// 8. 根据是否提供 tip，使用 Jito 捆绑交易或普通交易发送，bundle 会确认到上链或失败；
+///    发送因 blockhash 不存在被拒绝时重新获取 blockhash、重新签名后再发送一次
 ///
 /// # 示例
-/// ```rust
-/// let result = swap_with_tax(
-///     jup.clone(),
-///     rpc.clone(),
-///     jito.clone(),
-///     &keypair,
+/// ```ignore
+/// let ctx = SwapContext {
+///     jup: &jup,
+///     rpc: &rpc,
+///     jito: &jito,
+///     blockhashes: &blockhashes, // BlockhashProvider::spawn 启动的共享缓存
+///     bundle: BundleConfig::default(), // bundle 失败时返回错误
 ///     tax_account,
-///     100, // 1% 税收
-///     1_000_000, // 输入金额
-///     SOL,
-///     usdc_mint,
-///     50, // 0.5% 滑点
-///     Some(1_000_000), // tip 金额
+///     tax_side: TaxSide::Input, // 交易前以输入代币收税
+/// };
+/// let swap = SwapParams {
+///     input_mint: SOL,
+///     output_mint: usdc_mint,
+///     amount: TokenAmount::new(SOL, 1_000_000), // 输入金额
+///     swap_mode: SwapMode::ExactIn,
+///     slippage_bps: Bps::new(50)?, // 0.5% 滑点
+///     min_out_amount: Some(140_000), // 扣税后至少得到 0.14 USDC
+///     max_price_impact_bps: Some(Bps::new(100)?), // 价格影响不超过 1%
+///     options: &SwapOptions::default(),
+///     pin: None, // 不使用固定路由
+/// };
+/// let result = swap_with_tax(
+///     &ctx,
+///     &LocalKeypairSigner::new(keypair),
+///     &TaxPolicy::flat(Bps::new(100)?), // 所有钱包 1% 税收
+///     &swap,
+///     ExecutionOptions {
+///         tip_amount: Some(Lamports(1_000_000)), // tip 金额
+///         compute_unit_price: Some(10_000), // 优先费
+///         ..Default::default() // 只有提供 tip 时使用 bundle，由模拟结果推导计算单元上限
+///     },
 /// ).await;
 /// ```
 pub async fn swap_with_tax(
-    jup: Arc<JupiterSwapApiClient>,
-    rpc: Arc<RpcClient>,
-    jito: Arc<JitoJsonRpcSDK>,
-    user_keypair: &Keypair,
-    tax_account: Pubkey,
-    tax_bps: u16,
-    amount: u64,
-    input_mint: Pubkey,
-    output_mint: Pubkey,
-    slippage_bps: u16,
-    tip_amount: Option<u64>,
-) -> Result<()> {
-    // 如果输入是sol，则在swap之前进行收税
-    let tax_before_swap = input_mint == SOL;
+    ctx: &SwapContext<'_>,
+    user_signer: &dyn TransactionSigner,
+    tax_policy: &TaxPolicy,
+    swap: &SwapParams<'_>,
+    exec: ExecutionOptions,
+) -> error::Result<SwapOutcome> {
+    let SwapContext {
+        rpc,
+        jito,
+        blockhashes,
+        bundle,
+        ..
+    } = *ctx;
+    let lamports_per_unit = if swap.amount.mint == swap.input_mint {
+        exec.private.input_lamports_per_unit
+    } else {
+        None
+    };
+    let tax_bps = tax_policy.resolve(
+        Some(&user_signer.pubkey()),
+        amount_notional_lamports(swap.amount, lamports_per_unit),
+    );
+    let mut signed = build_signed_swap(ctx, user_signer, tax_bps, swap, exec).await?;
+    signed
+        .renew_blockhash(rpc, blockhashes, user_signer)
+        .await?;
+    let bundle_id = match submit_signed_swap(rpc, jito, &signed, bundle).await {
+        Err(e) if is_blockhash_not_found(&e) => {
+            println!("发送失败 {:#}，使用新的 blockhash 重新签名后发送", e);
+            signed
+                .resign(blockhashes.refresh(rpc).await?, user_signer)
+                .await?;
+            submit_signed_swap(rpc, jito, &signed, bundle).await?
+        }
+        sent => sent?,
+    };
+    Ok(signed.outcome(bundle_id))
+}
 
-    let user = user_keypair.pubkey();
+/// 已签名、等待发送的交换交易
+pub struct SignedSwap {
+    /// 提供 tip 时 tip 转账通常是交换交易的最后一条指令
+    pub swap_tx: VersionedTransaction,
+    /// tip 指令放不进交换交易时单独构建的 tip 交易，与交换交易一起以 Jito bundle 发送
+    pub tip_tx: Option<VersionedTransaction>,
+    /// 是否以 Jito bundle 发送，提供 tip 或需要私有发送时为 true
+    pub use_bundle: bool,
+    /// 是否因名义价值或订单要求必须私有发送，bundle 未上链时按 `private_fallback_to_rpc` 处理
+    pub private: bool,
+    private_fallback_to_rpc: bool,
+    /// 成交后扣税至少得到的输出数量
+    pub min_proceeds: u64,
+    /// 本次交换收取的税收，以收税一侧的代币计价
+    pub tax: TokenAmount,
+    /// 构建交易时的报价，无法从链上读取实际成交数量时据此估算
+    pub quote: QuoteSummary,
+    /// 合并在交换交易中的 tip（lamports），单独的 tip 交易或没有 tip 时为 0
+    pub merged_tip_lamports: u64,
+    /// 交换交易（未使用 nonce 时）和单独的 tip 交易使用的 blockhash
+    pub blockhash: CachedBlockhash,
+    /// 交换交易的指令，已包含计算预算和合并的 tip，不含 nonce 推进指令；重新签名时据此重新编译
+    swap_ixs: Vec<Instruction>,
+    separate_tip_ix: Option<Instruction>,
+    alts: Vec<AddressLookupTableAccount>,
+    nonce: Option<NonceInfo>,
+}
 
-    let mut ixs = vec![];
+/// 已上链的交换交易及构建时的报价
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SwapOutcome {
+    /// 交换交易的签名
+    pub signature: String,
+    /// 以 Jito bundle 上链时的 bundle id，通过 RPC 发送（包括 bundle 未上链后改用 RPC）时为 None
+    pub bundle_id: Option<String>,
+    /// 报价的输入数量（最小单位）
+    pub quoted_in_amount: u64,
+    /// 报价的输出数量（最小单位），已扣除输出代币的转账手续费
+    pub quoted_out_amount: u64,
+    /// 扣税后至少得到的输出数量
+    pub min_proceeds: u64,
+    /// 本次收取的税收，以 `tax_mint` 计价
+    pub tax_amount: u64,
+    pub tax_mint: String,
+}
 
-    let (amount_specified, tax) = sub_tax(amount, tax_bps);
+impl SignedSwap {
+    /// 交换交易的签名，发送前即可确定
+    pub fn signature(&self) -> Signature {
+        self.swap_tx.signatures[0]
+    }
 
-    let swap_amount = if tax_before_swap {
-        println!("交易前税收，税收为{:?}", tax);
-        ixs.push(system_instruction::transfer(&user, &tax_account, tax));
-        amount_specified
-    } else {
-        amount
+    /// 交易上链后的结果，`bundle_id` 为 [`submit_signed_swap`] 的返回值
+    pub fn outcome(&self, bundle_id: Option<String>) -> SwapOutcome {
+        SwapOutcome {
+            signature: self.signature().to_string(),
+            bundle_id,
+            quoted_in_amount: self.quote.in_amount.raw,
+            quoted_out_amount: self.quote.out_amount.raw,
+            min_proceeds: self.min_proceeds,
+            tax_amount: self.tax.raw,
+            tax_mint: self.tax.mint.to_string(),
+        }
+    }
+
+    /// 是否有交易依赖 blockhash：使用 nonce 且没有单独的 tip 交易时交易不会过期
+    fn expires(&self) -> bool {
+        self.nonce.is_none() || self.separate_tip_ix.is_some()
+    }
+
+    /// 使用新的 blockhash 重新编译并签名依赖 blockhash 的交易，交换交易的签名随之改变
+    ///
+    /// 指令与模拟时相同，只更换 blockhash，因此不再重新模拟。
+    pub async fn resign(
+        &mut self,
+        blockhash: CachedBlockhash,
+        user_signer: &dyn TransactionSigner,
+    ) -> Result<()> {
+        if self.nonce.is_none() {
+            self.swap_tx = sign_swap_transaction(
+                &self.swap_ixs,
+                user_signer,
+                &self.alts,
+                None,
+                blockhash.blockhash,
+            )
+            .await?;
+        }
+        if let Some(tip_ix) = &self.separate_tip_ix {
+            self.tip_tx = Some(tip_transaction(tip_ix, user_signer, blockhash.blockhash).await?);
+        }
+        self.blockhash = blockhash;
+        Ok(())
+    }
+
+    /// 发送前按当前区块高度检查 blockhash，即将过期时换用新的 blockhash 重新签名，返回是否重新签名
+    pub async fn renew_blockhash(
+        &mut self,
+        rpc: &dyn SolanaRpc,
+        blockhashes: &BlockhashProvider,
+        user_signer: &dyn TransactionSigner,
+    ) -> Result<bool> {
+        if !self.expires() {
+            return Ok(false);
+        }
+        match blockhashes.renew_if_stale(rpc, &self.blockhash).await? {
+            Some(fresh) => {
+                println!(
+                    "blockhash {} 即将过期，使用 {} 重新签名",
+                    self.blockhash.blockhash, fresh.blockhash
+                );
+                self.resign(fresh, user_signer).await?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+/// 单独的 tip 交易，只包含一条 tip 转账
+async fn tip_transaction(
+    tip_ix: &Instruction,
+    user_signer: &dyn TransactionSigner,
+    blockhash: Hash,
+) -> Result<VersionedTransaction> {
+    sign_versioned_transaction(
+        std::slice::from_ref(tip_ix),
+        &user_signer.pubkey(),
+        &[user_signer],
+        &[],
+        blockhash,
+    )
+    .await
+}
+
+/// 签名交换交易，`budget_ixs` 已包含计算预算指令
+///
+/// 使用 nonce 时以 nonce 作为 recent blockhash，并由 nonce authority 共同签名。
+async fn sign_swap_transaction(
+    budget_ixs: &[Instruction],
+    user_signer: &dyn TransactionSigner,
+    alts: &[AddressLookupTableAccount],
+    nonce: Option<&NonceInfo>,
+    blockhash: Hash,
+) -> Result<VersionedTransaction> {
+    let user = user_signer.pubkey();
+    match nonce {
+        Some(nonce) => {
+            let authority = LocalKeypairSigner::from(nonce.authority.clone());
+            sign_versioned_transaction(
+                &with_advance_nonce(budget_ixs, nonce),
+                &user,
+                &[user_signer, &authority],
+                alts,
+                nonce.nonce,
+            )
+            .await
+        }
+        None => {
+            sign_versioned_transaction(budget_ixs, &user, &[user_signer], alts, blockhash).await
+        }
+    }
+}
+
+/// 构建、模拟并签名交换交易，不发送
+///
+/// 参数与 [`swap_with_tax`] 相同，税率已按税收策略解析。调用方可以在发送前记录交易签名，避免重启后重复发送；
+/// 记录前应先调用 [`SignedSwap::renew_blockhash`]，重新签名会改变交易签名。
+pub async fn build_signed_swap(
+    ctx: &SwapContext<'_>,
+    user_signer: &dyn TransactionSigner,
+    tax_bps: Bps,
+    swap: &SwapParams<'_>,
+    exec: ExecutionOptions,
+) -> Result<SignedSwap> {
+    let SwapContext {
+        rpc,
+        jito,
+        blockhashes,
+        ..
+    } = *ctx;
+    let ExecutionOptions {
+        private,
+        tip_amount,
+        compute_unit_limit,
+        compute_unit_price,
+        nonce,
+        skip_simulation,
+    } = exec;
+    let user = user_signer.pubkey();
+    let TaxedSwapInstructions {
+        ixs,
+        alts,
+        min_proceeds,
+        tax,
+        quote,
+        ..
+    } = build_swap_with_tax_instructions(ctx, user, tax_bps, swap).await?;
+
+    let (private_required, tip_amount) = resolve_tip(&private, &quote, tip_amount);
+
+    let cached_blockhash = blockhashes.get(rpc).await?;
+    let blockhash = cached_blockhash.blockhash;
+    let tip_ix = match tip_amount {
+        Some(tip) => Some(system_instruction::transfer(
+            &user,
+            &get_tip_account(jito).await?,
+            tip.get(),
+        )),
+        None => None,
     };
 
-    // 构造swap指令
-    let (out_amount, swap_resp) = get_swap_ix(
-        jup.clone(),
-        user,
-        swap_amount,
+    // 使用 nonce 时以 nonce 作为 recent blockhash，并由 nonce authority 共同签名；单独的 tip 交易始终使用最新的 blockhash
+    let (swap_alts, swap_nonce) = (&alts, nonce.as_ref());
+    let sign = move |ixs: &[Instruction], limit: u32| {
+        let budget_ixs = with_compute_budget(ixs, limit, compute_unit_price);
+        async move {
+            sign_swap_transaction(&budget_ixs, user_signer, swap_alts, swap_nonce, blockhash).await
+        }
+    };
+
+    // 签名不改变交易大小，判断 tip 能否合并时使用未签名的交易，避免远程签名多签一次
+    let simulate_limit = compute_unit_limit.unwrap_or(MAX_COMPUTE_UNIT_LIMIT);
+    let (swap_ixs, separate_tip_ix) = merge_tip_ix(&ixs, tip_ix, |ixs| {
+        let budget_ixs = with_compute_budget(ixs, simulate_limit, compute_unit_price);
+        match &nonce {
+            Some(nonce) => unsigned_versioned_transaction(
+                &with_advance_nonce(&budget_ixs, nonce),
+                &user,
+                &alts,
+                nonce.nonce,
+            ),
+            None => unsigned_versioned_transaction(&budget_ixs, &user, &alts, blockhash),
+        }
+    })?;
+
+    // 未指定上限时先以最大计算单元模拟，再根据实际消耗收紧上限
+    let mut limit = simulate_limit;
+    let mut versioned_tx = sign(&swap_ixs, limit).await?;
+    if !skip_simulation {
+        let units = simulate_or_fail(rpc, &versioned_tx).await?;
+        if let (None, Some(units)) = (compute_unit_limit, units) {
+            limit = compute_unit_limit_with_margin(units);
+            println!("模拟消耗计算单元 {:?}，设置上限为 {:?}", units, limit);
+            versioned_tx = sign(&swap_ixs, limit).await?;
+            // 收紧上限后的交易才是最终发送的交易，再模拟一次
+            simulate_or_fail(rpc, &versioned_tx).await?;
+        }
+    }
+
+    let tip_tx = match &separate_tip_ix {
+        Some(tip_ix) => Some(tip_transaction(tip_ix, user_signer, blockhash).await?),
+        None => None,
+    };
+    let merged_tip_lamports = match (&separate_tip_ix, tip_amount) {
+        (None, Some(tip)) => tip.get(),
+        _ => 0,
+    };
+    Ok(SignedSwap {
+        use_bundle: tip_amount.is_some(),
+        private: private_required,
+        private_fallback_to_rpc: private.config.fallback_to_rpc,
+        swap_tx: versioned_tx,
+        tip_tx,
+        min_proceeds,
+        tax,
+        quote,
+        merged_tip_lamports,
+        blockhash: cached_blockhash,
+        swap_ixs: with_compute_budget(&swap_ixs, limit, compute_unit_price),
+        separate_tip_ix,
+        alts,
+        nonce,
+    })
+}
+
+/// 需要私有发送时即使没有指定 tip 也以 bundle 发送，tip 按名义价值自动选择；返回是否需要私有发送和使用的 tip
+fn resolve_tip(
+    private: &PrivateExecution,
+    quote: &QuoteSummary,
+    tip_amount: Option<Lamports>,
+) -> (bool, Option<Lamports>) {
+    let notional = notional_lamports(quote, private.input_lamports_per_unit);
+    let private_required = private.required(notional);
+    let tip_amount = match tip_amount {
+        None if private_required => {
+            let tip = private.config.tip.tip_for(notional);
+            println!(
+                "名义价值 {:?} lamports 需要私有发送，自动选择 tip {:?}",
+                notional, tip
+            );
+            Some(tip)
+        }
+        tip_amount => tip_amount,
+    };
+    (private_required, tip_amount)
+}
+
+/// tip 转账放在交换交易的最后一条指令，与交换一起成交或一起失败；
+/// 合并后超过单个数据包大小时才改用单独的 tip 交易，此时返回的第二项为单独发送的 tip 指令
+fn merge_tip_ix(
+    ixs: &[Instruction],
+    tip_ix: Option<Instruction>,
+    compile: impl Fn(&[Instruction]) -> Result<VersionedTransaction>,
+) -> Result<(Vec<Instruction>, Option<Instruction>)> {
+    let mut swap_ixs = ixs.to_vec();
+    let Some(tip_ix) = tip_ix else {
+        return Ok((swap_ixs, None));
+    };
+    swap_ixs.push(tip_ix.clone());
+    if fits_in_packet(&compile(&swap_ixs)?)? {
+        return Ok((swap_ixs, None));
+    }
+    println!(
+        "tip 指令合并后交易超过 {} 字节，改用单独的 tip 交易",
+        PACKET_DATA_SIZE
+    );
+    swap_ixs.pop();
+    Ok((swap_ixs, Some(tip_ix)))
+}
+
+/// 发送 [`build_signed_swap`] 构建的交易：有 tip 时以 Jito bundle 发送并返回 bundle id，否则通过 RPC 发送并等待确认
+///
+/// bundle 会一直确认到上链、失败或超过 `bundle.confirm_timeout`。Jito 限流时等待后重发一次；
+/// 发送失败（包括 Jito 不可用）、失败或被丢弃时按 `bundle.fallback_to_rpc`
+/// 改用 RPC 单独发送交换交易（此时返回的 bundle id 为 None），或直接返回错误。
+/// 必须私有发送的交换改按 [`PrivateExecutionConfig::fallback_to_rpc`] 处理，不改用 RPC 时返回
+/// [`LimitOrderError::PrivateExecutionFailed`]。
+pub async fn submit_signed_swap(
+    rpc: &dyn SolanaRpc,
+    jito: &dyn BundleSender,
+    swap: &SignedSwap,
+    bundle: BundleConfig,
+) -> Result<Option<String>> {
+    if swap.use_bundle {
+        let mut bundle_txs = vec![swap.swap_tx.clone()];
+        bundle_txs.extend(swap.tip_tx.clone());
+        let mut sent = send_bundle(jito, &bundle_txs).await;
+        if let Err(e) = &sent {
+            if e.downcast_ref::<JitoError>()
+                .is_some_and(JitoError::is_rate_limited)
+            {
+                println!("{:?}，{:?} 后重新发送 bundle", e, BUNDLE_RATE_LIMIT_DELAY);
+                tokio::time::sleep(BUNDLE_RATE_LIMIT_DELAY).await;
+                sent = send_bundle(jito, &bundle_txs).await;
+            }
+        }
+        let status = match sent {
+            Ok(id) => {
+                let status = confirm_bundle(jito, &id, bundle.confirm_timeout).await?;
+                println!("bundle {} status {:?}", id, status);
+                if status.is_landed() {
+                    return Ok(Some(id));
+                }
+                status
+            }
+            Err(e) => BundleStatus::Failed(format!("bundle 发送失败 {:#}", e)),
+        };
+        let fallback_to_rpc = if swap.private {
+            swap.private_fallback_to_rpc
+        } else {
+            bundle.fallback_to_rpc
+        };
+        if !fallback_to_rpc {
+            let status = format!("{:?}", status);
+            return Err(if swap.private {
+                LimitOrderError::PrivateExecutionFailed(status)
+            } else {
+                LimitOrderError::BundleDropped(status)
+            }
+            .into());
+        }
+        if swap.private {
+            println!(
+                "警告：需要私有发送的 bundle 未上链 {:?}，改用公开 RPC 发送",
+                status
+            );
+        } else {
+            println!("bundle 未上链 {:?}，改用 RPC 发送", status);
+        }
+    }
+    rpc.send(&swap.swap_tx).await?;
+    Ok(None)
+}
+
+/// 交易序列化后是否能放进单个数据包
+fn fits_in_packet(tx: &VersionedTransaction) -> Result<bool> {
+    Ok(bincode::serialize(tx)?.len() <= PACKET_DATA_SIZE)
+}
+
+/// [`build_swap_with_tax_instructions`] 组装的交换
+pub struct TaxedSwapInstructions {
+    /// 交换指令，不含计算预算指令
+    pub ixs: Vec<Instruction>,
+    /// 解析好的地址查找表
+    pub alts: Vec<AddressLookupTableAccount>,
+    /// 扣税后至少得到的输出数量
+    pub min_proceeds: u64,
+    /// 本次交换收取的税收，以收税一侧的代币计价
+    pub tax: TokenAmount,
+    /// 使用的报价，输出数量已扣除输出代币的转账手续费
+    pub quote: QuoteSummary,
+    /// 输出代币的 Token-2022 转账手续费
+    pub output_transfer_fee: Option<TransferFee>,
+}
+
+/// 查询报价并组装带税收的交换指令（不含计算预算指令）
+///
+/// 税收规则见 [`swap_with_tax`]，托管下单和非托管的待签名交易共用这一步。
+/// 提供 `pin` 时优先使用固定路由的报价；固定路由过期或与本次交换不符时，
+/// 按 `pin.fallback` 改用新的报价或直接返回错误。
+pub async fn build_swap_with_tax_instructions(
+    ctx: &SwapContext<'_>,
+    user: Pubkey,
+    tax_bps: Bps,
+    swap: &SwapParams<'_>,
+) -> Result<TaxedSwapInstructions> {
+    let SwapContext {
+        jup,
+        rpc,
+        tax_account,
+        tax_side,
+        ..
+    } = *ctx;
+    let SwapParams {
         input_mint,
         output_mint,
+        amount,
+        swap_mode,
         slippage_bps,
-    )
-    .await?;
+        min_out_amount,
+        max_price_impact_bps,
+        options,
+        pin,
+    } = *swap;
+    check_swap_mode(tax_side, swap_mode, tax_bps)?;
+    check_swap_options(tax_side, tax_bps, output_mint, options)?;
+    let swap_amount = quote_amount(tax_side, amount, swap_mode, tax_bps);
+
+    // 固定路由仍然有效时直接使用其报价
+    let pinned_quote = match pin {
+        Some(pin) => {
+            let usable = if pin.route.is_fresh() {
+                pin.route.matches(
+                    input_mint,
+                    output_mint,
+                    swap_amount,
+                    slippage_bps,
+                    swap_mode,
+                )
+            } else {
+                Err(anyhow!("固定路由已过期"))
+            };
+            match usable {
+                Ok(()) => Some(pin.route.quote.clone()),
+                Err(e) if pin.fallback => {
+                    println!("{:?}，改用新的报价", e);
+                    None
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        None => None,
+    };
+
+    // 构造swap指令
+    let (quoted, swap_resp) = match pinned_quote {
+        Some(quote) => get_swap_ix_for_quote(jup, user, quote, options).await?,
+        None => {
+            let quote = get_quote(
+                jup,
+                input_mint,
+                output_mint,
+                swap_amount,
+                slippage_bps,
+                swap_mode,
+            )
+            .await?;
+            get_swap_ix_for_quote(jup, user, quote, options).await?
+        }
+    };
+    // 输出代币有转账手续费时，之后的税收和最低输出都按用户实际收到的数量计算
+    let output_info = if output_mint == SOL {
+        None
+    } else {
+        Some(get_mint_info(rpc, &output_mint).await?)
+    };
+    let output_transfer_fee = output_info.and_then(|info| info.transfer_fee);
+    let quoted = net_of_transfer_fee(quoted, output_transfer_fee);
+    check_min_out(quoted.out_amount, tax_side, tax_bps, min_out_amount)?;
+    check_price_impact(&quoted, max_price_impact_bps)?;
 
-    // 插入swap指令
-    ixs.extend_from_slice(&swap_resp.setup_instructions);
-    ixs.push(swap_resp.swap_instruction);
+    // Input 在交易前以输入代币收税，Output 在交易后以输出代币收税
+    let tax = tax_amount(tax_side, swap_mode, amount, &quoted, tax_bps);
+    let tax_charge = if tax.raw == 0 {
+        TaxCharge::None
+    } else if tax_side == TaxSide::Input {
+        if input_mint == SOL {
+            TaxCharge::PreSwapSol(Lamports(tax.raw))
+        } else {
+            TaxCharge::PreSwapToken {
+                amount: tax,
+                mint_info: get_mint_info(rpc, &input_mint).await?,
+            }
+        }
+    } else if output_mint == SOL {
+        TaxCharge::PostSwapSol(Lamports(tax.raw))
+    } else {
+        TaxCharge::PostSwapToken {
+            amount: tax,
+            mint_info: output_info.ok_or_else(|| anyhow!("缺少输出代币 {} 的信息", output_mint))?,
+        }
+    };
+    println!("税收 {:?}", tax_charge);
+    // 以输出代币收税时税收按报价数量固定收取，从最低输出中扣除
+    let min_proceeds = match tax_side {
+        TaxSide::Input => quoted.min_out_amount.raw,
+        TaxSide::Output => quoted.min_out_amount.raw.saturating_sub(tax.raw),
+    };
 
-    // 交易后收税
-    if !tax_before_swap && out_amount != 0 {
-        let tax = sub_tax(out_amount, tax_bps).1;
-        println!("交易后税收，税收数量为 {:?}", tax);
-        ixs.push(system_instruction::transfer(&user, &tax_account, tax));
+    // Jupiter 的 setup 指令通常已经创建输出 ATA，这里只补充缺少的输出 ATA 和税收账户的 ATA
+    let mut ata_ixs = vec![];
+    let output_to_native_sol = output_mint == SOL && options.wrap_and_unwrap_sol;
+    if options.destination_token_account.is_none() && !output_to_native_sol {
+        let token_program = match output_info {
+            Some(info) => info.token_program,
+            None => get_mint_info(rpc, &output_mint).await?.token_program,
+        };
+        ata_ixs.extend(
+            missing_ata_ix(
+                rpc,
+                &user,
+                &user,
+                &output_mint,
+                &token_program,
+                &swap_resp.setup_instructions,
+            )
+            .await?,
+        );
+    }
+    if let TaxCharge::PreSwapToken { amount, mint_info }
+    | TaxCharge::PostSwapToken { amount, mint_info } = &tax_charge
+    {
+        ata_ixs.extend(
+            missing_ata_ix(
+                rpc,
+                &user,
+                &tax_account,
+                &amount.mint,
+                &mint_info.token_program,
+                &swap_resp.setup_instructions,
+            )
+            .await?,
+        );
     }
 
-    if let Some(clean) = swap_resp.cleanup_instruction {
-        ixs.push(clean);
+    let ixs = assemble_swap_instructions(
+        &user,
+        &tax_account,
+        &tax_charge,
+        &ata_ixs,
+        &swap_resp.setup_instructions,
+        &swap_resp.swap_instruction,
+        swap_resp.cleanup_instruction.as_ref(),
+    )?;
+
+    let alts = get_address_lookup(rpc, swap_resp.address_lookup_table_addresses).await?;
+    Ok(TaxedSwapInstructions {
+        ixs,
+        alts,
+        min_proceeds,
+        tax,
+        quote: quoted,
+        output_transfer_fee,
+    })
+}
+
+/// 输出代币有转账手续费时用户实际收到的数量少于报价，按手续费扣减报价的输出数量和最低输出数量
+pub fn net_of_transfer_fee(mut quoted: QuoteSummary, fee: Option<TransferFee>) -> QuoteSummary {
+    if let Some(fee) = fee {
+        quoted.out_amount.raw = fee.net(quoted.out_amount.raw);
+        quoted.min_out_amount.raw = fee.net(quoted.min_out_amount.raw);
     }
+    quoted
+}
 
-    let blockhash = rpc.get_latest_blockhash().await?;
+/// 一次交换的私有发送要求，见 [`PrivateExecutionConfig`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PrivateExecution {
+    pub config: PrivateExecutionConfig,
+    /// 订单要求无论金额大小都以 bundle 发送
+    pub force: bool,
+    /// 输入、输出都不是 SOL 时，每个最小单位的输入代币折合多少 lamports，用于计算名义价值
+    pub input_lamports_per_unit: Option<f64>,
+}
 
-    let versioned_tx = build_versioned_transaction(
-        rpc.clone(),
-        &ixs,
-        &user,
-        &user_keypair,
-        swap_resp.address_lookup_table_addresses,
-        blockhash,
-    )
-    .await?;
+impl PrivateExecution {
+    /// 名义价值为 `notional` lamports 的交换是否必须私有发送；配置了阈值但名义价值未知时按超过阈值处理
+    pub fn required(&self, notional: Option<u64>) -> bool {
+        self.force
+            || self
+                .config
+                .force_jito_above_lamports
+                .is_some_and(|threshold| notional.is_none_or(|notional| notional > threshold))
+    }
+}
 
-    println!("开始模拟执行");
-    let resp = rpc.simulate_transaction(&versioned_tx).await?;
-    if resp.value.err.is_some() {
-        println!("模拟执行失败，错误为 {:?}", resp);
-        return Err(anyhow!("模拟执行失败"));
+/// 交换的名义价值（lamports）
+///
+/// 输入为 SOL 时取报价的输入数量，输出为 SOL 时取报价的输出数量；
+/// 都不是 SOL 时按 `input_lamports_per_unit` 换算报价的输入数量，无法换算时返回 None。
+pub fn notional_lamports(
+    quote: &QuoteSummary,
+    input_lamports_per_unit: Option<f64>,
+) -> Option<u64> {
+    if quote.in_amount.mint == SOL {
+        return Some(quote.in_amount.raw);
+    }
+    if quote.out_amount.mint == SOL {
+        return Some(quote.out_amount.raw);
+    }
+    let rate = input_lamports_per_unit.filter(|rate| rate.is_finite() && *rate > 0.0)?;
+    Some((quote.in_amount.raw as f64 * rate) as u64)
+}
+
+/// 下单数量的名义价值（lamports），用于按名义价值分档收税
+///
+/// 数量为 SOL 时即为其数量，否则按 `lamports_per_unit`（每最小单位折合的 lamports）换算，无法换算时返回 None。
+pub fn amount_notional_lamports(
+    amount: TokenAmount,
+    lamports_per_unit: Option<f64>,
+) -> Option<u64> {
+    if amount.mint == SOL {
+        return Some(amount.raw);
+    }
+    let rate = lamports_per_unit.filter(|rate| rate.is_finite() && *rate > 0.0)?;
+    Some((amount.raw as f64 * rate) as u64)
+}
+
+/// `owner` 的 ATA 不存在且 Jupiter 的 setup 指令中没有创建它时，返回创建指令
+async fn missing_ata_ix(
+    rpc: &dyn SolanaRpc,
+    payer: &Pubkey,
+    owner: &Pubkey,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+    setup_instructions: &[Instruction],
+) -> Result<Option<Instruction>> {
+    let ata = get_associated_token_address_with_program_id(owner, mint, token_program);
+    if creates_ata(setup_instructions, &ata) {
+        return Ok(None);
+    }
+    ensure_ata_ix(rpc, payer, owner, mint, token_program).await
+}
+
+/// 检查报价扣税后的输出数量是否达到 `min_out_amount`
+///
+/// 滑点只限制报价到执行之间的价格变化，价格触发到报价之间价格可能已经大幅下跌，
+/// 因此在报价后再按订单的最低输出检查一次。以输出代币收税时按扣税后的数量比较。
+pub fn check_min_out(
+    quoted_out: TokenAmount,
+    tax_side: TaxSide,
+    tax_bps: Bps,
+    min_out_amount: Option<u64>,
+) -> error::Result<()> {
+    let Some(min_out_amount) = min_out_amount else {
+        return Ok(());
+    };
+    let out_amount = net_out_amount(quoted_out, tax_side, tax_bps);
+    if out_amount.raw < min_out_amount {
+        return Err(LimitOrderError::MinOutNotMet {
+            out_amount: out_amount.raw,
+            min_out_amount,
+        });
+    }
+    Ok(())
+}
+
+/// 检查报价的价格影响是否超过 `max_price_impact_bps`
+///
+/// 价格影响大说明流动性不足以承接订单数量，即使报价在滑点范围内，成交价格也会明显差于触发时的价格。
+pub fn check_price_impact(
+    quoted: &QuoteSummary,
+    max_price_impact_bps: Option<Bps>,
+) -> error::Result<()> {
+    let Some(max_bps) = max_price_impact_bps else {
+        return Ok(());
+    };
+    let impact_bps = quoted.price_impact_bps();
+    if impact_bps > max_bps.get() as u64 {
+        return Err(LimitOrderError::PriceImpactTooHigh {
+            impact_bps,
+            max_bps: max_bps.get(),
+        });
+    }
+    Ok(())
+}
+
+/// 用户实际得到的输出数量：以输出代币收税时为报价输出扣除税收后的数量
+pub fn net_out_amount(quoted_out: TokenAmount, tax_side: TaxSide, tax_bps: Bps) -> TokenAmount {
+    match tax_side {
+        TaxSide::Input => quoted_out,
+        TaxSide::Output => sub_tax(quoted_out, tax_bps).0,
+    }
+}
+
+/// 按报价计算的税收数量，`Input` 以输入代币计，`Output` 以输出代币计
+///
+/// `amount` 为下单数量，以输入代币收税时 ExactIn 按下单数量收税，ExactOut 按报价的输入数量收税。
+pub fn tax_amount(
+    tax_side: TaxSide,
+    swap_mode: SwapMode,
+    amount: TokenAmount,
+    quoted: &QuoteSummary,
+    tax_bps: Bps,
+) -> TokenAmount {
+    match (tax_side, swap_mode) {
+        (TaxSide::Input, SwapMode::ExactIn) => sub_tax(amount, tax_bps).1,
+        (TaxSide::Input, SwapMode::ExactOut) => sub_tax(quoted.in_amount, tax_bps).1,
+        (TaxSide::Output, _) => sub_tax(quoted.out_amount, tax_bps).1,
+    }
+}
+
+/// 实际用于报价的数量
+///
+/// 以输入代币收税时，ExactIn 从输入中扣除税收后再报价；ExactOut 的输出固定，税收在报价后按输入数量额外收取
+pub fn quote_amount(
+    tax_side: TaxSide,
+    amount: TokenAmount,
+    swap_mode: SwapMode,
+    tax_bps: Bps,
+) -> TokenAmount {
+    if tax_side == TaxSide::Input && swap_mode == SwapMode::ExactIn {
+        sub_tax(amount, tax_bps).0
     } else {
-        println!("模拟执行成功，开始交易");
+        amount
     }
+}
 
-    if let Some(tip) = tip_amount {
-        let tip_tx = VersionedTransaction::try_new(
-            solana_sdk::message::VersionedMessage::V0(Message::try_compile(
+/// 构造由用户自行签名的交换交易
+///
+/// 指令与 [`swap_with_tax`] 相同，不包含 tip。提供 `nonce` 时交易使用 durable nonce，
+/// 在 nonce 被推进之前一直有效，authority 的签名由服务端在提交时补充；
+/// 否则使用最新的 blockhash，同时返回其最后有效区块高度，超过该高度后交易失效，需要重新签名。
+/// 未签名的交易同样可以模拟，计算单元上限由模拟消耗推导。
+pub async fn prepare_unsigned_swap(
+    ctx: &SwapContext<'_>,
+    user: Pubkey,
+    tax_bps: Bps,
+    swap: &SwapParams<'_>,
+    compute_unit_price: Option<u64>,
+    nonce: Option<&NonceInfo>,
+) -> Result<(VersionedTransaction, Option<u64>)> {
+    let rpc = ctx.rpc;
+    let TaxedSwapInstructions { ixs, alts, .. } =
+        build_swap_with_tax_instructions(ctx, user, tax_bps, swap).await?;
+    let (blockhash, last_valid_block_height) = match nonce {
+        Some(nonce) => (nonce.nonce, None),
+        None => {
+            let (blockhash, height) = rpc.get_latest_blockhash_with_height().await?;
+            (blockhash, Some(height))
+        }
+    };
+    let compile = |limit: u32| {
+        let budget_ixs = with_compute_budget(&ixs, limit, compute_unit_price);
+        match nonce {
+            Some(nonce) => unsigned_versioned_transaction(
+                &with_advance_nonce(&budget_ixs, nonce),
                 &user,
-                &[system_instruction::transfer(
-                    &user,
-                    &get_tip_account()?,
-                    tip,
-                )],
-                &[],
+                &alts,
                 blockhash,
-            )?),
-            &[user_keypair],
-        )?;
-        let bundle_id = send_bundle(&jito, vec![versioned_tx, tip_tx]).await?;
-        if let Some(id) = bundle_id {
-            let status = jito.get_bundle_statuses(vec![id]).await?;
-            println!("status {:?}", status);
+            ),
+            None => unsigned_versioned_transaction(&budget_ixs, &user, &alts, blockhash),
         }
-    } else {
-        rpc.send_and_confirm_transaction_with_spinner(&versioned_tx)
-            .await?;
+    };
+
+    let tx = compile(MAX_COMPUTE_UNIT_LIMIT)?;
+    let limit = simulate_or_fail(rpc, &tx)
+        .await?
+        .map(compute_unit_limit_with_margin)
+        .unwrap_or(MAX_COMPUTE_UNIT_LIMIT);
+    let tx = compile(limit)?;
+    simulate_or_fail(rpc, &tx).await?;
+    Ok((tx, last_valid_block_height))
+}
+
+/// [`simulate_swap`] 的结果
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct SwapSimulation {
+    /// 交易错误，模拟成功时为 None
+    pub err: Option<String>,
+    /// 完整的程序日志
+    pub logs: Vec<String>,
+    pub units_consumed: Option<u64>,
+    /// 交易设置的计算单元上限
+    pub compute_unit_limit: u32,
+    pub compute_unit_price: Option<u64>,
+    /// 使用的 tip（lamports），需要私有发送时为自动选择的 tip
+    pub tip_amount: Option<u64>,
+    /// tip 是否放在单独的交易中，单独的 tip 交易不参与模拟
+    pub separate_tip: bool,
+    /// 是否会以 bundle 私有发送
+    pub private: bool,
+    pub in_amount: u64,
+    pub out_amount: u64,
+    /// 扣税后至少得到的输出数量
+    pub min_proceeds: u64,
+    /// 本次交换适用的税率
+    pub tax_bps: Bps,
+    pub tax_amount: u64,
+    pub tax_mint: String,
+    /// 输出代币的 Token-2022 转账手续费（基点），`out_amount` 和 `min_proceeds` 已扣除
+    pub output_transfer_fee_bps: Option<u16>,
+    pub route_labels: Vec<String>,
+    /// 用户和收税账户在模拟前后的余额
+    pub balances: Vec<BalanceChange>,
+    /// 模拟的交易（未签名，base64）
+    pub transaction: String,
+}
+
+/// 一个账户在模拟前后的余额，SOL 为钱包的 lamports，其他代币为 ATA 的最小单位数量；账户不存在时为 None
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct BalanceChange {
+    pub owner: String,
+    pub mint: String,
+    pub account: String,
+    pub pre: Option<u64>,
+    pub post: Option<u64>,
+}
+
+/// 按 [`swap_with_tax`] 当前会发送的交易（税收、tip 指令和地址查找表都相同）模拟执行，不发送任何交易
+///
+/// 交易不签名，以 `user` 为付款人，模拟时不校验签名，`exec` 中的 nonce 和 `skip_simulation` 不使用。
+/// 未指定计算单元上限时按最大上限模拟，与发送前的第一次模拟相同。不使用 `swap.pin`。模拟失败不返回错误，错误和日志在结果中返回；报价、最低输出或价格影响检查失败时返回错误。
+/// 同时返回用户输入、输出代币账户和收税账户在模拟前后的余额。
+pub async fn simulate_swap(
+    ctx: &SwapContext<'_>,
+    user: Pubkey,
+    tax_bps: Bps,
+    swap: &SwapParams<'_>,
+    exec: &ExecutionOptions,
+) -> Result<SwapSimulation> {
+    let SwapContext {
+        rpc,
+        jito,
+        tax_account,
+        ..
+    } = *ctx;
+    let (input_mint, output_mint) = (swap.input_mint, swap.output_mint);
+    let compute_unit_price = exec.compute_unit_price;
+    let TaxedSwapInstructions {
+        ixs,
+        alts,
+        min_proceeds,
+        tax,
+        quote,
+        output_transfer_fee,
+    } = build_swap_with_tax_instructions(ctx, user, tax_bps, &SwapParams { pin: None, ..*swap })
+        .await?;
+    let (private_required, tip_amount) = resolve_tip(&exec.private, &quote, exec.tip_amount);
+    let tip_ix = match tip_amount {
+        Some(tip) => Some(system_instruction::transfer(
+            &user,
+            &get_tip_account(jito).await?,
+            tip.get(),
+        )),
+        None => None,
+    };
+    let blockhash = rpc.get_latest_blockhash().await?;
+    let limit = exec.compute_unit_limit.unwrap_or(MAX_COMPUTE_UNIT_LIMIT);
+    let compile = |ixs: &[Instruction]| {
+        unsigned_versioned_transaction(
+            &with_compute_budget(ixs, limit, compute_unit_price),
+            &user,
+            &alts,
+            blockhash,
+        )
+    };
+    let (swap_ixs, separate_tip_ix) = merge_tip_ix(&ixs, tip_ix, compile)?;
+    let tx = compile(&swap_ixs)?;
+
+    let mut balance_accounts = vec![];
+    for (owner, mint) in [
+        (user, input_mint),
+        (user, output_mint),
+        (tax_account, tax.mint),
+    ] {
+        let account = if mint == SOL {
+            owner
+        } else {
+            let mint_info = get_mint_info(rpc, &mint).await?;
+            get_associated_token_address_with_program_id(&owner, &mint, &mint_info.token_program)
+        };
+        balance_accounts.push((owner, mint, account));
+    }
+    let addresses: Vec<Pubkey> = balance_accounts.iter().map(|(_, _, a)| *a).collect();
+    let pre = rpc.get_multiple_accounts(&addresses).await?;
+    let simulation = rpc.simulate_with_accounts(&tx, &addresses).await?;
+    let balances = balance_accounts
+        .iter()
+        .enumerate()
+        .map(|(i, (owner, mint, account))| BalanceChange {
+            owner: owner.to_string(),
+            mint: mint.to_string(),
+            account: account.to_string(),
+            pre: pre
+                .get(i)
+                .and_then(|a| a.as_ref())
+                .and_then(|a| account_balance(mint, a)),
+            post: simulation
+                .accounts
+                .get(i)
+                .and_then(|a| a.as_ref())
+                .and_then(|a| account_balance(mint, a)),
+        })
+        .collect();
+
+    Ok(SwapSimulation {
+        err: simulation.err,
+        logs: simulation.logs,
+        units_consumed: simulation.units_consumed,
+        compute_unit_limit: limit,
+        compute_unit_price,
+        tip_amount: tip_amount.map(Lamports::get),
+        separate_tip: separate_tip_ix.is_some(),
+        private: private_required,
+        in_amount: quote.in_amount.raw,
+        out_amount: quote.out_amount.raw,
+        min_proceeds,
+        tax_bps,
+        tax_amount: tax.raw,
+        tax_mint: tax.mint.to_string(),
+        output_transfer_fee_bps: output_transfer_fee.map(|fee| fee.bps),
+        route_labels: quote.route_labels,
+        balances,
+        transaction: encode_transaction(&tx)?,
+    })
+}
+
+/// SOL 取账户的 lamports，其他代币按代币账户的布局读取数量（Token-2022 的基础布局相同）
+fn account_balance(mint: &Pubkey, account: &Account) -> Option<u64> {
+    if *mint == SOL {
+        return Some(account.lamports);
+    }
+    token_account_amount(account)
+}
+
+/// 按代币账户的布局读取数量，Token-2022 的基础布局相同
+fn token_account_amount(account: &Account) -> Option<u64> {
+    let data = account.data.get(..spl_token::state::Account::LEN)?;
+    spl_token::state::Account::unpack_from_slice(data)
+        .ok()
+        .map(|state| state.amount)
+}
+
+/// 检查报价模式能否正确收税
+///
+/// `TaxSide::Output` 时税收在交易后以输出代币收取，而 `ExactOut` 的输出数量是用户指定的，
+/// 收税后用户实际得到的数量会少于指定值，因此这种组合在税率不为 0 时直接拒绝。
+pub fn check_swap_mode(tax_side: TaxSide, swap_mode: SwapMode, tax_bps: Bps) -> Result<()> {
+    if swap_mode == SwapMode::ExactOut && tax_side == TaxSide::Output && tax_bps != Bps::ZERO {
+        return Err(anyhow!("以输出代币收税时不支持 ExactOut 模式"));
+    }
+    Ok(())
+}
+
+/// 检查交易选项能否正确收税
+///
+/// 以输出代币收税时税收从用户自己的 ATA 转出，输出代币发往其他账户时用户的 ATA 中没有这部分代币；
+/// 输出为 SOL 且不解包时得到的是 wSOL，而税收以原生 SOL 转账。这两种组合在税率不为 0 时直接拒绝。
+pub fn check_swap_options(
+    tax_side: TaxSide,
+    tax_bps: Bps,
+    output_mint: Pubkey,
+    options: &SwapOptions,
+) -> Result<()> {
+    if tax_side != TaxSide::Output || tax_bps == Bps::ZERO {
+        return Ok(());
+    }
+    if options.destination_token_account.is_some() {
+        return Err(anyhow!("以输出代币收税时不支持 destination_token_account"));
+    }
+    if output_mint == SOL && !options.wrap_and_unwrap_sol {
+        return Err(anyhow!("以输出代币收税且输出为 SOL 时必须解包 SOL"));
+    }
+    Ok(())
+}
+
+/// 检查目标代币账户是 `mint` 的 ATA
+///
+/// 账户必须已经存在、属于 spl-token 或 Token-2022 程序、mint 与输出代币一致，
+/// 且地址由账户的所有者和 mint 推导得到，避免成交的代币发往无法找回的账户。
+pub async fn check_destination_account(
+    rpc: &dyn SolanaRpc,
+    account: &Pubkey,
+    mint: &Pubkey,
+) -> Result<()> {
+    let info = rpc
+        .get_account(account)
+        .await?
+        .ok_or_else(|| anyhow!("目标代币账户 {} 不存在", account))?;
+    if info.owner != spl_token::id() && info.owner != TOKEN_2022_PROGRAM_ID {
+        return Err(anyhow!("目标账户 {} 不是代币账户", account));
+    }
+    let len = spl_token::state::Account::LEN;
+    if info.data.len() < len {
+        return Err(anyhow!("目标账户 {} 不是代币账户", account));
+    }
+    // Token-2022 账户的基础布局与 spl-token 一致，扩展数据位于其后
+    let state = spl_token::state::Account::unpack_from_slice(&info.data[..len])?;
+    if state.mint != *mint {
+        return Err(anyhow!(
+            "目标代币账户 {} 的代币为 {}，与输出代币 {} 不一致",
+            account,
+            state.mint,
+            mint
+        ));
+    }
+    let ata = get_associated_token_address_with_program_id(&state.owner, mint, &info.owner);
+    if ata != *account {
+        return Err(anyhow!("目标代币账户 {} 不是 ATA", account));
+    }
+    Ok(())
+}
+
+/// 下单时检查钱包余额能否支付订单
+///
+/// 输入为 SOL 且由交换指令包装时，SOL 余额需要覆盖 `input_amount`、手续费、按最大计算单元估算的优先费和 tip；
+/// 其他输入代币检查钱包该代币 ATA 的余额（ATA 不存在视为 0），SOL 余额只需覆盖费用。
+/// `input_amount` 为 None（`ExactOut` 的输入数量要到报价时才知道）时只检查费用。
+pub async fn check_funding(
+    rpc: &dyn SolanaRpc,
+    owner: &Pubkey,
+    input_mint: &Pubkey,
+    input_amount: Option<u64>,
+    wrap_sol: bool,
+    tip_amount: Option<Lamports>,
+    priority_fee_micro_lamports: Option<u64>,
+) -> error::Result<()> {
+    let priority_fee = priority_fee_micro_lamports
+        .unwrap_or(0)
+        .saturating_mul(MAX_COMPUTE_UNIT_LIMIT as u64)
+        / 1_000_000;
+    let mut sol_required = ESTIMATED_FEE_LAMPORTS
+        .saturating_add(priority_fee)
+        .saturating_add(tip_amount.map_or(0, |tip| tip.get()));
+    match input_amount {
+        Some(amount) if *input_mint == SOL && wrap_sol => {
+            sol_required = sol_required.saturating_add(amount);
+        }
+        Some(amount) => {
+            let mint_info = get_mint_info(rpc, input_mint).await?;
+            let ata = get_associated_token_address_with_program_id(
+                owner,
+                input_mint,
+                &mint_info.token_program,
+            );
+            let available = match rpc.get_account(&ata).await {
+                Ok(Some(account)) => token_account_amount(&account).unwrap_or(0),
+                _ => 0,
+            };
+            if available < amount {
+                return Err(LimitOrderError::InsufficientFunds {
+                    mint: input_mint.to_string(),
+                    required: amount,
+                    available,
+                });
+            }
+        }
+        None => {}
+    }
+    let available = rpc
+        .get_balance(owner)
+        .await
+        .map_err(LimitOrderError::Other)?;
+    if available < sol_required {
+        return Err(LimitOrderError::InsufficientFunds {
+            mint: SOL.to_string(),
+            required: sol_required,
+            available,
+        });
     }
     Ok(())
 }
 
+/// 在指令列表前插入计算预算指令
+///
+/// # 参数
+/// - `ixs`: `&[Instruction]` - 原始指令
+/// - `compute_unit_limit`: `u32` - 计算单元上限
+/// - `compute_unit_price`: `Option<u64>` - 优先费（micro-lamports / CU），为 None 时不设置；交换交易何时设置见 [`ExecutionOptions::compute_unit_price`]
+pub fn with_compute_budget(
+    ixs: &[Instruction],
+    compute_unit_limit: u32,
+    compute_unit_price: Option<u64>,
+) -> Vec<Instruction> {
+    let mut budget_ixs = vec![ComputeBudgetInstruction::set_compute_unit_limit(
+        compute_unit_limit,
+    )];
+    if let Some(price) = compute_unit_price {
+        budget_ixs.push(ComputeBudgetInstruction::set_compute_unit_price(price));
+    }
+    budget_ixs.extend_from_slice(ixs);
+    budget_ixs
+}
+
+/// 在模拟消耗的计算单元上增加余量，并限制在单笔交易允许的最大值以内
+pub fn compute_unit_limit_with_margin(units_consumed: u64) -> u32 {
+    let limit = units_consumed + units_consumed * COMPUTE_UNIT_MARGIN_PERCENT / 100;
+    limit.min(MAX_COMPUTE_UNIT_LIMIT as u64) as u32
+}
+
+/// 一次交换需要收取的税
+#[derive(Debug, Clone, Copy)]
+pub enum TaxCharge {
+    /// 不收税
+    None,
+    /// 输入为 SOL，交易前系统转账
+    PreSwapSol(Lamports),
+    /// 输入为 SPL 代币，交易前以代币转账
+    PreSwapToken {
+        amount: TokenAmount,
+        mint_info: MintInfo,
+    },
+    /// 输出为 SOL，交易后系统转账
+    PostSwapSol(Lamports),
+    /// 输出为 SPL 代币，交易后以代币转账
+    PostSwapToken {
+        amount: TokenAmount,
+        mint_info: MintInfo,
+    },
+}
+
+/// 按顺序组装交换交易的指令（不含计算预算指令），不访问网络
+///
+/// 指令顺序：
+/// 1. 创建缺少的 ATA
+/// 2. 交易前 SOL 或 SPL 代币税收
+/// 3. Jupiter setup 指令与 swap 指令
+/// 4. 交易后 SPL 代币税收
+/// 5. Jupiter cleanup 指令
+/// 6. 交易后 SOL 税收（cleanup 会关闭 wSOL 账户把 lamports 还给用户，因此放在其后）
+///
+/// # 参数
+/// - `user`: `&Pubkey` - 交易发起者
+/// - `tax_account`: `&Pubkey` - 接收税收的账户
+/// - `tax`: `&TaxCharge` - 需要收取的税
+/// - `ata_instructions`: `&[Instruction]` - 创建输出 ATA、税收账户 ATA 的指令，见 [`ensure_ata_ix`]
+/// - `setup_instructions`: `&[Instruction]` - Jupiter 返回的 setup 指令
+/// - `swap_instruction`: `&Instruction` - Jupiter 返回的 swap 指令
+/// - `cleanup_instruction`: `Option<&Instruction>` - Jupiter 返回的 cleanup 指令
+pub fn assemble_swap_instructions(
+    user: &Pubkey,
+    tax_account: &Pubkey,
+    tax: &TaxCharge,
+    ata_instructions: &[Instruction],
+    setup_instructions: &[Instruction],
+    swap_instruction: &Instruction,
+    cleanup_instruction: Option<&Instruction>,
+) -> Result<Vec<Instruction>> {
+    let mut ixs = ata_instructions.to_vec();
+    match tax {
+        TaxCharge::PreSwapSol(tax) => {
+            ixs.push(system_instruction::transfer(user, tax_account, tax.get()))
+        }
+        TaxCharge::PreSwapToken { amount, mint_info } => {
+            ixs.extend(token_tax_ixs(user, tax_account, *amount, *mint_info)?)
+        }
+        _ => {}
+    }
+
+    ixs.extend_from_slice(setup_instructions);
+    ixs.push(swap_instruction.clone());
+
+    if let TaxCharge::PostSwapToken { amount, mint_info } = tax {
+        ixs.extend(token_tax_ixs(user, tax_account, *amount, *mint_info)?);
+    }
+
+    if let Some(clean) = cleanup_instruction {
+        ixs.push(clean.clone());
+    }
+
+    if let TaxCharge::PostSwapSol(tax) = tax {
+        ixs.push(system_instruction::transfer(user, tax_account, tax.get()));
+    }
+    Ok(ixs)
+}
+
+/// 构造以 SPL 代币收税的指令
+///
+/// 从用户的 ATA 转账到税收账户的 ATA，税收账户的 ATA 需要已经存在或由 `ata_instructions` 在之前创建。
+/// 支持 spl-token 和 Token-2022，两者的 transfer_checked 指令布局相同，只是程序 ID 不同。
+///
+/// # 参数
+/// - `user`: `&Pubkey` - 付税用户
+/// - `tax_account`: `&Pubkey` - 接收税收的账户（钱包地址，不是代币账户）
+/// - `tax`: `TokenAmount` - 税收代币及数量（代币最小单位）
+/// - `mint_info`: `MintInfo` - 税收代币的代币程序与精度
+///
+/// # 返回值
+/// - `Result<Vec<Instruction>>` - transfer_checked 指令
+pub fn token_tax_ixs(
+    user: &Pubkey,
+    tax_account: &Pubkey,
+    tax: TokenAmount,
+    mint_info: MintInfo,
+) -> Result<Vec<Instruction>> {
+    let mint = &tax.mint;
+    let token_program = mint_info.token_program;
+    if token_program != spl_token::id() && token_program != TOKEN_2022_PROGRAM_ID {
+        return Err(anyhow!("暂不支持代币程序 {} 的税收", token_program));
+    }
+    let source = get_associated_token_address_with_program_id(user, mint, &token_program);
+    let destination =
+        get_associated_token_address_with_program_id(tax_account, mint, &token_program);
+    // spl_token 只接受自己的程序 ID，先按 spl-token 构造再替换为实际的代币程序
+    let mut transfer = spl_token::instruction::transfer_checked(
+        &spl_token::id(),
+        &source,
+        mint,
+        &destination,
+        user,
+        &[],
+        tax.raw,
+        mint_info.decimals,
+    )?;
+    transfer.program_id = token_program;
+    Ok(vec![transfer])
+}
+
 /// 获取多个地址查找表账户的信息
 ///
 /// 从 Solana 区块链批量查询账户数据，并解析为 `AddressLookupTableAccount` 结构。
 ///
 /// # 参数
-/// - `rpc`: `&RpcClient` - Solana RPC 客户端引用
+/// - `rpc`: `&dyn SolanaRpc` - Solana RPC 客户端引用
 /// - `keys`: `Vec<Pubkey>` - 要查询的地址查找表公钥列表
 ///
 /// # 返回值
@@ -184,11 +1485,11 @@ pub async fn swap_with_tax(
 /// 3. 构造并返回 `AddressLookupTableAccount` 数组
 ///
 /// # 示例
-/// ```rust
+/// ```ignore
 /// let lookup_tables = get_address_lookup_table_accounts(&rpc, vec![table_pubkey]).await?;
 /// ```
 pub async fn get_address_lookup_table_accounts(
-    rpc: &RpcClient,
+    rpc: &dyn SolanaRpc,
     keys: Vec<Pubkey>,
 ) -> Result<Vec<AddressLookupTableAccount>> {
     // 获取多个账户信息
@@ -213,23 +1514,674 @@ pub async fn get_address_lookup_table_accounts(
 /// 根据给定的金额和税收基点，计算实际交易金额和税收部分。
 ///
 /// # 参数
-/// - `amount`: `u64` - 总金额
-/// - `tax_bps`: `u16` - 税收百分比，以基点表示（1 bps = 0.01%）
+/// - `amount`: `TokenAmount` - 总金额
+/// - `tax_bps`: `Bps` - 税收百分比，以基点表示（1 bps = 0.01%）
 ///
 /// # 返回值
-/// - `(u64, u64)` - 元组，第一个元素为扣税后的金额，第二个元素为税收金额
+/// - `(TokenAmount, TokenAmount)` - 元组，第一个元素为扣税后的金额，第二个元素为税收金额，二者与输入同一 mint
 ///
 /// # 计算公式
 /// - 税收 = (amount * tax_bps) / 10000
 /// - 扣税后金额 = amount - 税收
 ///
+/// 乘法在 [`Bps::apply`] 中以 u128 计算，`amount` 为 `u64::MAX` 时也不会溢出；`Bps` 构造和反序列化时保证不超过 10000，
+/// 税收不会大于 `amount`，减法不会下溢。
+///
 /// # 示例
-/// ```rust
-/// let (net_amount, tax) = sub_tax(1_000_000, 100); // 1% 税收
-/// assert_eq!(net_amount, 990_000);
-/// assert_eq!(tax, 10_000);
 /// ```
-pub fn sub_tax(amount: u64, tax_bps: u16) -> (u64, u64) {
-    let tax = (amount * tax_bps as u64) / 10000;
-    (amount - tax, tax)
+/// # use limit_order::{common::units::{Bps, TokenAmount}, solana::swap::sub_tax, SOL};
+/// # fn main() -> anyhow::Result<()> {
+/// let (net_amount, tax) = sub_tax(TokenAmount::new(SOL, 1_000_000), Bps::new(100)?); // 1% 税收
+/// assert_eq!(net_amount.raw, 990_000);
+/// assert_eq!(tax.raw, 10_000);
+/// # Ok(())
+/// # }
+/// ```
+pub fn sub_tax(amount: TokenAmount, tax_bps: Bps) -> (TokenAmount, TokenAmount) {
+    let tax = tax_bps.apply(amount.raw);
+    (
+        TokenAmount::new(amount.mint, amount.raw - tax),
+        TokenAmount::new(amount.mint, tax),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sol(raw: u64) -> TokenAmount {
+        TokenAmount::new(SOL, raw)
+    }
+
+    #[test]
+    fn sub_tax_zero_bps_keeps_amount() {
+        let (net, tax) = sub_tax(sol(1_000_000), Bps::ZERO);
+        assert_eq!(net, sol(1_000_000));
+        assert_eq!(tax, sol(0));
+        let (net, tax) = sub_tax(sol(u64::MAX), Bps::ZERO);
+        assert_eq!(net.raw, u64::MAX);
+        assert_eq!(tax.raw, 0);
+    }
+
+    #[test]
+    fn sub_tax_full_bps_takes_everything() {
+        let full = Bps::new(Bps::MAX).unwrap();
+        let (net, tax) = sub_tax(sol(1_000_000), full);
+        assert_eq!(net.raw, 0);
+        assert_eq!(tax.raw, 1_000_000);
+        let (net, tax) = sub_tax(sol(u64::MAX), full);
+        assert_eq!(net.raw, 0);
+        assert_eq!(tax.raw, u64::MAX);
+    }
+
+    #[test]
+    fn sub_tax_rounds_tax_down() {
+        let bps = Bps::new(30).unwrap();
+        // 税收 999 * 0.3% = 2.997，向下取整为 2，余数留给用户
+        let (net, tax) = sub_tax(sol(999), bps);
+        assert_eq!(tax.raw, 2);
+        assert_eq!(net.raw, 997);
+        // 数量太小时不收税
+        let (net, tax) = sub_tax(sol(333), bps);
+        assert_eq!(tax.raw, 0);
+        assert_eq!(net.raw, 333);
+        // 任何数量下两部分之和都等于原数量
+        for raw in [1, 7, 3_333, 10_001, u64::MAX - 1, u64::MAX] {
+            let (net, tax) = sub_tax(sol(raw), bps);
+            assert_eq!(net.raw + tax.raw, raw);
+            assert_eq!(tax.mint, SOL);
+        }
+    }
+
+    fn summary(in_amount: TokenAmount, out_amount: TokenAmount) -> QuoteSummary {
+        QuoteSummary {
+            in_amount,
+            out_amount,
+            min_out_amount: out_amount,
+            price_impact_pct: 0.0,
+            route_labels: vec![],
+        }
+    }
+
+    #[test]
+    fn notional_uses_the_sol_side_or_the_input_rate() {
+        let usdc = Pubkey::new_unique();
+        let bonk = Pubkey::new_unique();
+        let sol_in = summary(sol(2_000_000_000), TokenAmount::new(usdc, 300_000_000));
+        assert_eq!(notional_lamports(&sol_in, Some(1.0)), Some(2_000_000_000));
+        let sol_out = summary(TokenAmount::new(usdc, 300_000_000), sol(1_990_000_000));
+        assert_eq!(notional_lamports(&sol_out, None), Some(1_990_000_000));
+
+        let tokens = summary(
+            TokenAmount::new(usdc, 300_000_000),
+            TokenAmount::new(bonk, 1),
+        );
+        // 每个 USDC 最小单位折合 6.5 lamports
+        assert_eq!(notional_lamports(&tokens, Some(6.5)), Some(1_950_000_000));
+        for rate in [
+            None,
+            Some(0.0),
+            Some(-1.0),
+            Some(f64::NAN),
+            Some(f64::INFINITY),
+        ] {
+            assert_eq!(notional_lamports(&tokens, rate), None, "{:?}", rate);
+        }
+    }
+
+    #[test]
+    fn amount_notional_uses_sol_amount_or_rate() {
+        let usdc = Pubkey::new_unique();
+        assert_eq!(amount_notional_lamports(sol(5), None), Some(5));
+        assert_eq!(
+            amount_notional_lamports(TokenAmount::new(usdc, 1_000), Some(6.5)),
+            Some(6_500)
+        );
+        assert_eq!(
+            amount_notional_lamports(TokenAmount::new(usdc, 1_000), Some(f64::NAN)),
+            None
+        );
+        assert_eq!(
+            amount_notional_lamports(TokenAmount::new(usdc, 1_000), None),
+            None
+        );
+    }
+
+    #[test]
+    fn tip_schedule_is_proportional_within_bounds() {
+        use crate::common::utils::TipSchedule;
+
+        let schedule = TipSchedule {
+            min_lamports: 10_000,
+            bps: Bps::new(10).unwrap(),
+            max_lamports: 1_000_000,
+        };
+        // 名义价值的 0.1%，限制在下限和上限之间，名义价值未知时取下限
+        let cases = [
+            (None, 10_000),
+            (Some(0), 10_000),
+            (Some(9_999_999), 10_000),
+            (Some(10_000_000), 10_000),
+            (Some(50_000_000), 50_000),
+            (Some(1_000_000_000), 1_000_000),
+            (Some(u64::MAX), 1_000_000),
+        ];
+        for (notional, expected) in cases {
+            assert_eq!(
+                schedule.tip_for(notional),
+                Lamports(expected),
+                "{:?}",
+                notional
+            );
+        }
+        // 上限低于下限时取下限
+        let inverted = TipSchedule {
+            max_lamports: 1_000,
+            ..schedule
+        };
+        assert_eq!(inverted.tip_for(Some(u64::MAX)), Lamports(10_000));
+    }
+
+    #[test]
+    fn private_execution_picks_tip_only_when_required() {
+        let quote = summary(
+            sol(2_000_000_000),
+            TokenAmount::new(Pubkey::new_unique(), 1),
+        );
+        let threshold = PrivateExecution {
+            config: PrivateExecutionConfig {
+                force_jito_above_lamports: Some(1_000_000_000),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let scheduled = threshold.config.tip.tip_for(Some(2_000_000_000));
+        assert_eq!(
+            resolve_tip(&threshold, &quote, None),
+            (true, Some(scheduled))
+        );
+        // 指定的 tip 不被自动选择的 tip 替换
+        assert_eq!(
+            resolve_tip(&threshold, &quote, Some(Lamports(1))),
+            (true, Some(Lamports(1)))
+        );
+
+        let small = summary(
+            sol(1_000_000_000),
+            TokenAmount::new(Pubkey::new_unique(), 1),
+        );
+        assert_eq!(resolve_tip(&threshold, &small, None), (false, None));
+        assert_eq!(
+            resolve_tip(&PrivateExecution::default(), &quote, None),
+            (false, None)
+        );
+
+        // 配置了阈值但名义价值未知时按超过阈值处理
+        let tokens = summary(
+            TokenAmount::new(Pubkey::new_unique(), 1),
+            TokenAmount::new(Pubkey::new_unique(), 1),
+        );
+        assert!(threshold.required(notional_lamports(&tokens, None)));
+        let forced = PrivateExecution {
+            force: true,
+            ..Default::default()
+        };
+        assert!(forced.required(Some(0)));
+    }
+
+    /// 交易中属于 `program_id` 的指令数据
+    #[cfg(feature = "testing")]
+    fn program_data(tx: &VersionedTransaction, program_id: Pubkey) -> Vec<Vec<u8>> {
+        let keys = tx.message.static_account_keys();
+        tx.message
+            .instructions()
+            .iter()
+            .filter(|ix| keys[ix.program_id_index as usize] == program_id)
+            .map(|ix| ix.data.clone())
+            .collect()
+    }
+
+    /// 以 1 SOL 换 USDC 的交换参数
+    #[cfg(feature = "testing")]
+    fn sol_to_usdc(options: &SwapOptions) -> SwapParams<'_> {
+        SwapParams {
+            input_mint: SOL,
+            output_mint: crate::testing::USDC,
+            amount: sol(1_000_000_000),
+            swap_mode: SwapMode::ExactIn,
+            slippage_bps: Bps::new(50).unwrap(),
+            min_out_amount: None,
+            max_price_impact_bps: None,
+            options,
+            pin: None,
+        }
+    }
+
+    /// 以 150 USDC/SOL 报价的模拟 Jupiter，交换指令引用 `lookup_tables`
+    #[cfg(feature = "testing")]
+    fn mock_jupiter(
+        rpc: &crate::testing::MockRpc,
+        lookup_tables: Vec<Pubkey>,
+    ) -> crate::testing::MockJupiter {
+        use crate::testing::{MockJupiter, SOL_DECIMALS, USDC, USDC_DECIMALS};
+
+        rpc.set_mint(SOL, spl_token::id(), SOL_DECIMALS);
+        rpc.set_mint(USDC, spl_token::id(), USDC_DECIMALS);
+        MockJupiter {
+            price: 150.0,
+            output_decimals: USDC_DECIMALS,
+            lookup_tables,
+        }
+    }
+
+    /// 以 1 SOL 换 USDC，交易前收取 1% 的税
+    #[cfg(feature = "testing")]
+    async fn mock_swap(
+        rpc: &crate::testing::MockRpc,
+        jup: &crate::testing::MockJupiter,
+        jito: &crate::testing::MockJito,
+        signer: &crate::testing::MockSigner,
+        bundle: BundleConfig,
+        exec: ExecutionOptions,
+    ) -> error::Result<SwapOutcome> {
+        use crate::common::utils::BLOCKHASH_EXPIRY_MARGIN;
+        use crate::testing::fixed_keypair;
+        use solana_sdk::signer::Signer;
+
+        let blockhashes = BlockhashProvider::new(BLOCKHASH_EXPIRY_MARGIN);
+        let ctx = SwapContext {
+            jup,
+            rpc,
+            jito,
+            blockhashes: &blockhashes,
+            bundle,
+            tax_account: fixed_keypair(2).pubkey(),
+            tax_side: TaxSide::Input,
+        };
+        swap_with_tax(
+            &ctx,
+            signer,
+            &TaxPolicy::flat(Bps::new(100).unwrap()),
+            &sol_to_usdc(&SwapOptions::default()),
+            exec,
+        )
+        .await
+    }
+
+    /// 以 1 SOL 换 USDC，指定 tip 和优先费，tip 合并在交换交易中
+    #[cfg(feature = "testing")]
+    async fn bundled_swap(
+        rpc: &crate::testing::MockRpc,
+        jito: &crate::testing::MockJito,
+        signer: &crate::testing::MockSigner,
+        bundle: BundleConfig,
+        compute_unit_price: u64,
+    ) -> error::Result<SwapOutcome> {
+        let jup = mock_jupiter(rpc, vec![]);
+        mock_swap(
+            rpc,
+            &jup,
+            jito,
+            signer,
+            bundle,
+            ExecutionOptions {
+                tip_amount: Some(Lamports(10_000)),
+                compute_unit_price: Some(compute_unit_price),
+                ..Default::default()
+            },
+        )
+        .await
+    }
+
+    /// 以 bundle 发送的交换交易仍带有优先费指令，tip 合并在同一笔交易中
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn bundled_swap_keeps_compute_unit_price() {
+        use std::sync::Arc;
+
+        use solana_sdk::{bs58, signer::Signer};
+
+        use crate::testing::{fixed_keypair, MockJito, MockRpc, MockSigner};
+
+        let rpc = MockRpc::new();
+        let tip_account = fixed_keypair(9).pubkey();
+        let jito = MockJito::new(tip_account);
+        let signer = MockSigner::new(Arc::new(fixed_keypair(1)));
+        let compute_unit_price = 10_000;
+
+        let outcome = bundled_swap(
+            &rpc,
+            &jito,
+            &signer,
+            BundleConfig::default(),
+            compute_unit_price,
+        )
+        .await
+        .unwrap();
+        assert!(outcome.bundle_id.is_some());
+        assert!(rpc.sent().is_empty(), "bundle 上链后不应再通过 RPC 发送");
+
+        let bundles = jito.bundles();
+        assert_eq!(bundles.len(), 1);
+        let txs = bundles[0].as_array().unwrap();
+        assert_eq!(txs.len(), 1, "tip 应合并在交换交易中");
+        let bytes = bs58::decode(txs[0].as_str().unwrap()).into_vec().unwrap();
+        let tx: VersionedTransaction = bincode::deserialize(&bytes).unwrap();
+
+        let price_data = ComputeBudgetInstruction::set_compute_unit_price(compute_unit_price).data;
+        assert!(program_data(&tx, solana_sdk::compute_budget::id()).contains(&price_data));
+        let tip_data = system_instruction::transfer(&signer.pubkey(), &tip_account, 10_000).data;
+        assert!(tx.message.static_account_keys().contains(&tip_account));
+        assert!(program_data(&tx, solana_sdk::system_program::id()).contains(&tip_data));
+    }
+
+    /// bundle 未上链改用 RPC 时发送的是同一笔已签名交易，带有优先费，不会与 bundle 重复成交
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn rpc_fallback_sends_the_bundled_transaction_with_compute_unit_price() {
+        use std::sync::Arc;
+
+        use solana_sdk::{bs58, signer::Signer};
+
+        use crate::testing::{fixed_keypair, MockJito, MockRpc, MockSigner};
+
+        let rpc = MockRpc::new();
+        let tip_account = fixed_keypair(9).pubkey();
+        // bundle 一直查询不到，确认立即超时
+        let mut jito = MockJito::new(tip_account);
+        jito.confirmation_status = None;
+        let signer = MockSigner::new(Arc::new(fixed_keypair(1)));
+        let compute_unit_price = 10_000;
+        let bundle = BundleConfig {
+            confirm_timeout: Duration::ZERO,
+            fallback_to_rpc: true,
+        };
+
+        let outcome = bundled_swap(&rpc, &jito, &signer, bundle, compute_unit_price)
+            .await
+            .unwrap();
+        assert_eq!(outcome.bundle_id, None, "改用 RPC 发送时没有 bundle id");
+
+        let sent = rpc.sent();
+        assert_eq!(sent.len(), 1);
+        let tx = &sent[0];
+        assert_eq!(outcome.signature, tx.signatures[0].to_string());
+        assert_eq!(tx.message.static_account_keys()[0], signer.pubkey());
+        let price_data = ComputeBudgetInstruction::set_compute_unit_price(compute_unit_price).data;
+        assert!(
+            program_data(tx, solana_sdk::compute_budget::id()).contains(&price_data),
+            "改用 RPC 发送的交易应带有优先费"
+        );
+
+        let bundles = jito.bundles();
+        assert_eq!(bundles.len(), 1);
+        let bundled = bs58::decode(bundles[0][0].as_str().unwrap())
+            .into_vec()
+            .unwrap();
+        assert_eq!(
+            bincode::serialize(tx).unwrap(),
+            bundled,
+            "改用 RPC 发送的应是 bundle 中的同一笔交易"
+        );
+    }
+
+    /// 以 SOL 收取的交易后税收在 cleanup 指令之后，cleanup 关闭 wSOL 账户后用户才有足够的 SOL
+    #[test]
+    fn post_swap_sol_tax_follows_cleanup() {
+        let user = Pubkey::new_unique();
+        let tax_account = Pubkey::new_unique();
+        let setup = system_instruction::transfer(&user, &Pubkey::new_unique(), 1);
+        let swap_ix = Instruction::new_with_bytes(Pubkey::new_unique(), &[1], vec![]);
+        let cleanup = Instruction::new_with_bytes(Pubkey::new_unique(), &[2], vec![]);
+        let ixs = assemble_swap_instructions(
+            &user,
+            &tax_account,
+            &TaxCharge::PostSwapSol(Lamports(5_000)),
+            &[],
+            std::slice::from_ref(&setup),
+            &swap_ix,
+            Some(&cleanup),
+        )
+        .unwrap();
+        assert_eq!(
+            ixs,
+            vec![
+                setup,
+                swap_ix,
+                cleanup,
+                system_instruction::transfer(&user, &tax_account, 5_000),
+            ]
+        );
+    }
+
+    /// 以输入代币收税时税收转账在 Jupiter 的 setup 指令之前；
+    /// 以输出代币收税时先创建税收账户的 ATA，税收转账在 swap 指令之后、cleanup 指令之前
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn tax_instructions_are_ordered_around_the_swap() {
+        use solana_sdk::signer::Signer;
+        use spl_associated_token_account::get_associated_token_address;
+
+        use crate::common::utils::BLOCKHASH_EXPIRY_MARGIN;
+        use crate::solana::decode::JUPITER_PROGRAM_ID;
+        use crate::testing::{fixed_keypair, MockJito, MockRpc, USDC};
+
+        let rpc = MockRpc::new();
+        let jup = mock_jupiter(&rpc, vec![]);
+        let jito = MockJito::new(fixed_keypair(9).pubkey());
+        let blockhashes = BlockhashProvider::new(BLOCKHASH_EXPIRY_MARGIN);
+        let user = fixed_keypair(1).pubkey();
+        let tax_account = fixed_keypair(2).pubkey();
+        let options = SwapOptions::default();
+        let swap = sol_to_usdc(&options);
+        let tax_bps = Bps::new(100).unwrap();
+        let mut ctx = SwapContext {
+            jup: &jup,
+            rpc: &rpc,
+            jito: &jito,
+            blockhashes: &blockhashes,
+            bundle: BundleConfig::default(),
+            tax_account,
+            tax_side: TaxSide::Input,
+        };
+        let swap_position = |ixs: &[Instruction]| {
+            ixs.iter()
+                .position(|ix| ix.program_id == JUPITER_PROGRAM_ID)
+                .expect("swap 指令")
+        };
+
+        let input = build_swap_with_tax_instructions(&ctx, user, tax_bps, &swap)
+            .await
+            .unwrap();
+        assert_eq!(input.tax, sol(10_000_000));
+        assert_eq!(
+            input.ixs[0],
+            system_instruction::transfer(&user, &tax_account, 10_000_000),
+            "交易前的税收应是第一条指令"
+        );
+        assert!(swap_position(&input.ixs) > 0);
+
+        ctx.tax_side = TaxSide::Output;
+        let output = build_swap_with_tax_instructions(&ctx, user, tax_bps, &swap)
+            .await
+            .unwrap();
+        assert_eq!(output.tax, TokenAmount::new(USDC, 1_500_000));
+        let tax_ata = get_associated_token_address(&tax_account, &USDC);
+        let tax_position = output
+            .ixs
+            .iter()
+            .position(|ix| {
+                ix.program_id == spl_token::id()
+                    && ix
+                        .accounts
+                        .get(2)
+                        .is_some_and(|meta| meta.pubkey == tax_ata)
+            })
+            .expect("交易后的代币税收");
+        let swap_at = swap_position(&output.ixs);
+        assert!(creates_ata(&output.ixs[..swap_at], &tax_ata));
+        assert!(swap_at < tax_position);
+        let cleanup = spl_token::instruction::close_account(
+            &spl_token::id(),
+            &get_associated_token_address(&user, &SOL),
+            &user,
+            &user,
+            &[],
+        )
+        .unwrap();
+        assert_eq!(tax_position + 1, output.ixs.len() - 1);
+        assert_eq!(output.ixs.last(), Some(&cleanup));
+    }
+
+    /// Jupiter 返回的地址查找表从 RPC 读取并用于编译交换交易，不存在的查找表被跳过
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn lookup_tables_are_resolved_and_used() {
+        use std::sync::Arc;
+
+        use solana_sdk::{message::VersionedMessage, signer::Signer};
+        use spl_associated_token_account::get_associated_token_address;
+
+        use crate::testing::{fixed_keypair, MockJito, MockRpc, MockSigner, USDC};
+
+        let rpc = MockRpc::new();
+        let signer = MockSigner::new(Arc::new(fixed_keypair(1)));
+        let output_ata = get_associated_token_address(&signer.pubkey(), &USDC);
+        let table = fixed_keypair(20).pubkey();
+        let missing = fixed_keypair(21).pubkey();
+        rpc.set_lookup_table(table, &[USDC, output_ata]);
+        let jup = mock_jupiter(&rpc, vec![table, missing]);
+        let jito = MockJito::new(fixed_keypair(9).pubkey());
+
+        let alts = get_address_lookup(&rpc, vec![table, missing])
+            .await
+            .unwrap();
+        assert_eq!(alts.len(), 1, "不存在的查找表应被跳过");
+        assert_eq!(alts[0].key, table);
+        assert_eq!(alts[0].addresses, vec![USDC, output_ata]);
+
+        mock_swap(
+            &rpc,
+            &jup,
+            &jito,
+            &signer,
+            BundleConfig::default(),
+            ExecutionOptions::default(),
+        )
+        .await
+        .unwrap();
+        let sent = rpc.sent();
+        assert_eq!(sent.len(), 1);
+        let VersionedMessage::V0(message) = &sent[0].message else {
+            panic!("交换交易应为 V0 交易");
+        };
+        assert_eq!(message.address_table_lookups.len(), 1);
+        assert_eq!(message.address_table_lookups[0].account_key, table);
+        assert!(!message.account_keys.contains(&USDC));
+        assert!(!message.account_keys.contains(&output_ata));
+    }
+
+    /// 必须私有发送而没有指定 tip 时按 tip 表选择 tip，作为交换交易的最后一条指令以 bundle 发送
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn private_swap_is_bundled_with_scheduled_tip() {
+        use std::sync::Arc;
+
+        use solana_sdk::{bs58, signer::Signer};
+
+        use crate::testing::{fixed_keypair, MockJito, MockRpc, MockSigner};
+
+        let rpc = MockRpc::new();
+        let jup = mock_jupiter(&rpc, vec![]);
+        let tip_account = fixed_keypair(9).pubkey();
+        let jito = MockJito::new(tip_account);
+        let signer = MockSigner::new(Arc::new(fixed_keypair(1)));
+        let private = PrivateExecution {
+            force: true,
+            ..Default::default()
+        };
+
+        let outcome = mock_swap(
+            &rpc,
+            &jup,
+            &jito,
+            &signer,
+            BundleConfig::default(),
+            ExecutionOptions {
+                private,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(outcome.bundle_id.is_some());
+        assert!(rpc.sent().is_empty());
+
+        // 扣除 1% 税收后报价的输入为 0.99 SOL，按默认 tip 表的 1 bps 选择 tip
+        let tip = private.config.tip.tip_for(Some(990_000_000));
+        assert_eq!(tip, Lamports(99_000));
+        let bundles = jito.bundles();
+        assert_eq!(bundles.len(), 1);
+        let txs = bundles[0].as_array().unwrap();
+        assert_eq!(txs.len(), 1, "tip 应合并在交换交易中");
+        let bytes = bs58::decode(txs[0].as_str().unwrap()).into_vec().unwrap();
+        let tx: VersionedTransaction = bincode::deserialize(&bytes).unwrap();
+        let keys = tx.message.static_account_keys();
+        let last = tx.message.instructions().last().unwrap();
+        assert_eq!(
+            keys[last.program_id_index as usize],
+            solana_sdk::system_program::id()
+        );
+        assert_eq!(
+            last.data,
+            system_instruction::transfer(&signer.pubkey(), &tip_account, tip.get()).data
+        );
+        assert_eq!(keys[last.accounts[1] as usize], tip_account);
+    }
+
+    /// 模拟失败时返回带有程序日志的 `SimulationFailed`，交易既不以 bundle 也不通过 RPC 发送
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn simulation_failure_is_returned_without_sending() {
+        use std::sync::Arc;
+
+        use solana_sdk::signer::Signer;
+
+        use crate::testing::{fixed_keypair, MockJito, MockRpc, MockSigner};
+
+        let rpc = MockRpc::failing_simulation(
+            "InstructionError(3, Custom(6001))",
+            &[
+                "Program JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4 invoke [1]",
+                "Program log: AnchorError occurred. Error Code: SlippageToleranceExceeded. Error Number: 6001.",
+                "Program JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4 failed: custom program error: 0x1771",
+            ],
+        );
+        let jup = mock_jupiter(&rpc, vec![]);
+        let jito = MockJito::new(fixed_keypair(9).pubkey());
+        let signer = MockSigner::new(Arc::new(fixed_keypair(1)));
+
+        let err = mock_swap(
+            &rpc,
+            &jup,
+            &jito,
+            &signer,
+            BundleConfig::default(),
+            ExecutionOptions {
+                tip_amount: Some(Lamports(10_000)),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
+        let logs = match err {
+            LimitOrderError::SimulationFailed { logs } => logs,
+            err => panic!("应返回模拟失败，实际为 {:?}", err),
+        };
+        assert!(logs.starts_with("InstructionError(3, Custom(6001))"));
+        assert!(logs.contains("Error Code: SlippageToleranceExceeded"));
+        assert!(logs.contains("custom program error: 0x1771"));
+        assert!(rpc.sent().is_empty());
+        assert!(jito.bundles().is_empty());
+    }
 }
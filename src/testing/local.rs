@@ -0,0 +1,466 @@
+//! 本地验证节点上的集成测试工具
+//!
+//! [`LocalValidator`] 连接 `TEST_RPC_URL` 指定的节点，未配置时在临时目录启动 `solana-test-validator`；
+//! 两者都不可用时返回 None，调用方据此跳过，不会因为缺少环境而失败。
+//! 账户由空投注资，测试代币由 [`create_mint`] 现场创建，用户钱包作为 mint authority。
+//!
+//! [`JupiterFixtureServer`] 按 `fixtures/` 中的 JSON 模板应答 Jupiter 的 `/quote`、`/swap-instructions`
+//! 和价格接口。返回的指令可以在本地节点上真实执行：输入代币转入资金池账户，输出代币由用户按报价数量铸造，
+//! 因此交换前后的余额可以直接在链上核对。
+
+use std::{
+    collections::HashMap,
+    env, fs, io,
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose, Engine};
+use serde_json::{json, Map, Value};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    instruction::Instruction,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_instruction,
+    transaction::Transaction,
+};
+use spl_associated_token_account::{
+    get_associated_token_address, instruction::create_associated_token_account_idempotent,
+};
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, Request, Respond, ResponseTemplate,
+};
+
+/// 等待节点就绪的最长时间
+const VALIDATOR_STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+/// 等待空投确认的最长时间
+const AIRDROP_CONFIRM_TIMEOUT: Duration = Duration::from_secs(30);
+/// 启动 `solana-test-validator` 时默认使用的 RPC 端口
+const DEFAULT_VALIDATOR_RPC_PORT: u16 = 8899;
+
+const QUOTE_FIXTURE: &str = include_str!("fixtures/jupiter_quote.json");
+const SWAP_INSTRUCTIONS_FIXTURE: &str = include_str!("fixtures/jupiter_swap_instructions.json");
+const PRICE_FIXTURE: &str = include_str!("fixtures/jupiter_price.json");
+
+/// 集成测试使用的 Solana 节点
+///
+/// 自行启动的 `solana-test-validator` 在 drop 时结束进程并删除临时账本。
+pub struct LocalValidator {
+    rpc_url: String,
+    process: Option<Child>,
+    ledger: Option<PathBuf>,
+}
+
+impl LocalValidator {
+    /// 配置了 `TEST_RPC_URL` 时连接该节点，否则启动 `solana-test-validator`
+    ///
+    /// 自行启动时 RPC 端口读取 `TEST_VALIDATOR_RPC_PORT`（默认 8899）。
+    /// 没有配置 `TEST_RPC_URL` 且找不到 `solana-test-validator` 时返回 None；节点未能就绪时返回错误。
+    pub async fn start() -> Result<Option<LocalValidator>> {
+        if let Some(url) = env::var("TEST_RPC_URL")
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+        {
+            let mut validator = LocalValidator {
+                rpc_url: url.trim().to_string(),
+                process: None,
+                ledger: None,
+            };
+            validator.wait_ready().await?;
+            return Ok(Some(validator));
+        }
+
+        let port = match env::var("TEST_VALIDATOR_RPC_PORT") {
+            Ok(v) => v
+                .trim()
+                .parse::<u16>()
+                .map_err(|_| anyhow!("TEST_VALIDATOR_RPC_PORT 必须为端口号"))?,
+            Err(_) => DEFAULT_VALIDATOR_RPC_PORT,
+        };
+        let ledger = env::temp_dir().join(format!("limit-order-ledger-{}", std::process::id()));
+        let process = match Command::new("solana-test-validator")
+            .arg("--reset")
+            .arg("--quiet")
+            .arg("--ledger")
+            .arg(&ledger)
+            .arg("--rpc-port")
+            .arg(port.to_string())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(process) => process,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(anyhow!("启动 solana-test-validator 失败 {}", e)),
+        };
+        let mut validator = LocalValidator {
+            rpc_url: format!("http://127.0.0.1:{}", port),
+            process: Some(process),
+            ledger: Some(ledger),
+        };
+        validator.wait_ready().await?;
+        Ok(Some(validator))
+    }
+
+    pub fn rpc_url(&self) -> &str {
+        &self.rpc_url
+    }
+
+    /// 以 confirmed 确认级别访问节点的客户端
+    pub fn rpc(&self) -> RpcClient {
+        RpcClient::new_with_commitment(self.rpc_url.clone(), CommitmentConfig::confirmed())
+    }
+
+    /// 等待节点通过健康检查，自行启动的进程提前退出时立即返回错误
+    async fn wait_ready(&mut self) -> Result<()> {
+        let rpc = self.rpc();
+        let deadline = Instant::now() + VALIDATOR_STARTUP_TIMEOUT;
+        loop {
+            if rpc.get_health().await.is_ok() {
+                return Ok(());
+            }
+            if let Some(process) = self.process.as_mut() {
+                if let Some(status) = process.try_wait()? {
+                    return Err(anyhow!("solana-test-validator 已退出 {}", status));
+                }
+            }
+            if Instant::now() >= deadline {
+                return Err(anyhow!(
+                    "节点 {} 在 {:?} 内未就绪",
+                    self.rpc_url,
+                    VALIDATOR_STARTUP_TIMEOUT
+                ));
+            }
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+    }
+}
+
+impl Drop for LocalValidator {
+    fn drop(&mut self) {
+        if let Some(mut process) = self.process.take() {
+            let _ = process.kill();
+            let _ = process.wait();
+        }
+        if let Some(ledger) = self.ledger.take() {
+            let _ = fs::remove_dir_all(ledger);
+        }
+    }
+}
+
+/// 向 `pubkey` 空投 `lamports` 并等待确认
+pub async fn fund(rpc: &RpcClient, pubkey: &Pubkey, lamports: u64) -> Result<()> {
+    let signature = rpc.request_airdrop(pubkey, lamports).await?;
+    let deadline = Instant::now() + AIRDROP_CONFIRM_TIMEOUT;
+    while !rpc.confirm_transaction(&signature).await? {
+        if Instant::now() >= deadline {
+            return Err(anyhow!(
+                "空投 {} 未在 {:?} 内确认",
+                signature,
+                AIRDROP_CONFIRM_TIMEOUT
+            ));
+        }
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+    Ok(())
+}
+
+/// 由 `payer` 付费并签名发送指令，等待确认
+async fn send_instructions(
+    rpc: &RpcClient,
+    payer: &Keypair,
+    ixs: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<()> {
+    let blockhash = rpc.get_latest_blockhash().await?;
+    let mut all_signers = vec![payer];
+    all_signers.extend_from_slice(signers);
+    let tx =
+        Transaction::new_signed_with_payer(ixs, Some(&payer.pubkey()), &all_signers, blockhash);
+    rpc.send_and_confirm_transaction(&tx).await?;
+    Ok(())
+}
+
+/// 创建 spl-token 代币，mint authority 为 `payer`
+pub async fn create_mint(rpc: &RpcClient, payer: &Keypair, decimals: u8) -> Result<Pubkey> {
+    let mint = Keypair::new();
+    let len = spl_token::state::Mint::LEN;
+    let rent = rpc.get_minimum_balance_for_rent_exemption(len).await?;
+    let ixs = vec![
+        system_instruction::create_account(
+            &payer.pubkey(),
+            &mint.pubkey(),
+            rent,
+            len as u64,
+            &spl_token::id(),
+        ),
+        spl_token::instruction::initialize_mint2(
+            &spl_token::id(),
+            &mint.pubkey(),
+            &payer.pubkey(),
+            None,
+            decimals,
+        )?,
+    ];
+    send_instructions(rpc, payer, &ixs, &[&mint]).await?;
+    Ok(mint.pubkey())
+}
+
+/// 为 `owner` 创建 `mint` 的 ATA（已存在时不报错），返回 ATA 地址
+pub async fn create_token_account(
+    rpc: &RpcClient,
+    payer: &Keypair,
+    owner: &Pubkey,
+    mint: &Pubkey,
+) -> Result<Pubkey> {
+    let ix =
+        create_associated_token_account_idempotent(&payer.pubkey(), owner, mint, &spl_token::id());
+    send_instructions(rpc, payer, &[ix], &[]).await?;
+    Ok(get_associated_token_address(owner, mint))
+}
+
+/// 由 mint authority `authority` 向 `owner` 的 ATA 铸造 `amount`（最小单位），ATA 不存在时一并创建
+pub async fn mint_to(
+    rpc: &RpcClient,
+    authority: &Keypair,
+    mint: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+) -> Result<Pubkey> {
+    let account = get_associated_token_address(owner, mint);
+    let ixs = vec![
+        create_associated_token_account_idempotent(
+            &authority.pubkey(),
+            owner,
+            mint,
+            &spl_token::id(),
+        ),
+        spl_token::instruction::mint_to(
+            &spl_token::id(),
+            mint,
+            &account,
+            &authority.pubkey(),
+            &[],
+            amount,
+        )?,
+    ];
+    send_instructions(rpc, authority, &ixs, &[]).await?;
+    Ok(account)
+}
+
+/// 代币账户的余额（最小单位）
+pub async fn token_balance(rpc: &RpcClient, account: &Pubkey) -> Result<u64> {
+    Ok(rpc
+        .get_token_account_balance(account)
+        .await?
+        .amount
+        .parse()?)
+}
+
+/// 将模板中等于 `{{名称}}` 的字符串值和对象键替换为对应的值
+fn render(template: &Value, vars: &HashMap<&str, Value>) -> Value {
+    let lookup = |s: &str| {
+        s.strip_prefix("{{")
+            .and_then(|s| s.strip_suffix("}}"))
+            .and_then(|name| vars.get(name))
+    };
+    match template {
+        Value::String(s) => lookup(s).cloned().unwrap_or_else(|| template.clone()),
+        Value::Array(items) => Value::Array(items.iter().map(|item| render(item, vars)).collect()),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, value)| {
+                    let key = match lookup(key) {
+                        Some(Value::String(key)) => key.clone(),
+                        _ => key.clone(),
+                    };
+                    (key, render(value, vars))
+                })
+                .collect::<Map<String, Value>>(),
+        ),
+        _ => template.clone(),
+    }
+}
+
+fn fixture(source: &str) -> Value {
+    serde_json::from_str(source).expect("fixture 为合法的 JSON")
+}
+
+/// spl-token 指令的数据：指令序号加 u64 数量，base64 编码
+fn token_instruction_data(tag: u8, amount: u64) -> String {
+    let mut data = vec![tag];
+    data.extend_from_slice(&amount.to_le_bytes());
+    general_purpose::STANDARD.encode(data)
+}
+
+/// 按固定兑换率报价的 `/quote` 应答，只支持 ExactIn
+struct QuoteResponder {
+    rate: f64,
+}
+
+impl Respond for QuoteResponder {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        let query: HashMap<String, String> = request.url.query_pairs().into_owned().collect();
+        if query.get("swapMode").is_some_and(|mode| mode != "ExactIn") {
+            return ResponseTemplate::new(400)
+                .set_body_json(json!({ "error": "fixture 只支持 ExactIn 报价" }));
+        }
+        let (Some(input_mint), Some(output_mint), Some(in_amount)) = (
+            query.get("inputMint"),
+            query.get("outputMint"),
+            query.get("amount").and_then(|v| v.parse::<u64>().ok()),
+        ) else {
+            return ResponseTemplate::new(400).set_body_json(json!({ "error": "缺少报价参数" }));
+        };
+        let slippage_bps: u64 = query
+            .get("slippageBps")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0)
+            .min(10_000);
+        let out_amount = (in_amount as f64 * self.rate).floor() as u64;
+        let threshold = out_amount as u128 * (10_000 - slippage_bps as u128) / 10_000;
+        let vars = HashMap::from([
+            ("INPUT_MINT", json!(input_mint)),
+            ("OUTPUT_MINT", json!(output_mint)),
+            ("IN_AMOUNT", json!(in_amount.to_string())),
+            ("OUT_AMOUNT", json!(out_amount.to_string())),
+            ("THRESHOLD", json!(threshold.to_string())),
+            ("SLIPPAGE_BPS", json!(slippage_bps)),
+        ]);
+        ResponseTemplate::new(200).set_body_json(render(&fixture(QUOTE_FIXTURE), &vars))
+    }
+}
+
+/// `/swap-instructions` 应答：输入转入 `pool` 的 ATA，输出按报价数量铸造给用户
+struct SwapInstructionsResponder {
+    pool: Pubkey,
+}
+
+impl SwapInstructionsResponder {
+    fn render(&self, body: &Value) -> Result<Value> {
+        let parse = |v: &Value| -> Result<Pubkey> {
+            v.as_str()
+                .ok_or_else(|| anyhow!("缺少地址"))?
+                .parse()
+                .map_err(|_| anyhow!("地址无效 {}", v))
+        };
+        let amount = |v: &Value| -> Result<u64> {
+            v.as_str()
+                .ok_or_else(|| anyhow!("缺少数量"))?
+                .parse()
+                .map_err(|_| anyhow!("数量无效 {}", v))
+        };
+        let quote = &body["quoteResponse"];
+        let user = parse(&body["userPublicKey"])?;
+        let input_mint = parse(&quote["inputMint"])?;
+        let output_mint = parse(&quote["outputMint"])?;
+        let in_amount = amount(&quote["inAmount"])?;
+        let out_amount = amount(&quote["outAmount"])?;
+        let vars = HashMap::from([
+            ("USER", json!(user.to_string())),
+            ("OUTPUT_MINT", json!(output_mint.to_string())),
+            (
+                "USER_INPUT_ACCOUNT",
+                json!(get_associated_token_address(&user, &input_mint).to_string()),
+            ),
+            (
+                "USER_OUTPUT_ACCOUNT",
+                json!(get_associated_token_address(&user, &output_mint).to_string()),
+            ),
+            (
+                "POOL_INPUT_ACCOUNT",
+                json!(get_associated_token_address(&self.pool, &input_mint).to_string()),
+            ),
+            // spl-token 的 Transfer 为 3，MintTo 为 7
+            ("TRANSFER_DATA", json!(token_instruction_data(3, in_amount))),
+            ("MINT_TO_DATA", json!(token_instruction_data(7, out_amount))),
+        ]);
+        Ok(render(&fixture(SWAP_INSTRUCTIONS_FIXTURE), &vars))
+    }
+}
+
+impl Respond for SwapInstructionsResponder {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        let rendered = serde_json::from_slice::<Value>(&request.body)
+            .map_err(|e| anyhow!("请求体无法解析 {}", e))
+            .and_then(|body| self.render(&body));
+        match rendered {
+            Ok(body) => ResponseTemplate::new(200).set_body_json(body),
+            Err(e) => ResponseTemplate::new(400).set_body_json(json!({ "error": e.to_string() })),
+        }
+    }
+}
+
+/// 按 fixture 应答的本地 Jupiter 服务
+///
+/// `url()` 作为 `JUP_URL` 传给 `JupiterSwapApiClient`，`price_url()` 作为 `JUPITER_PRICE_URL` 传给价格源。
+/// 输入和输出都必须是 spl-token 代币：用户需要持有输入代币的 ATA，`pool` 需要已有输入代币的 ATA，
+/// 用户需要是输出代币的 mint authority。
+pub struct JupiterFixtureServer {
+    server: MockServer,
+}
+
+impl JupiterFixtureServer {
+    /// `rate` 为每个最小单位的输入可换得的输出数量（最小单位），向下取整
+    pub async fn start(rate: f64, pool: Pubkey) -> JupiterFixtureServer {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/quote"))
+            .respond_with(QuoteResponder { rate })
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/swap-instructions"))
+            .respond_with(SwapInstructionsResponder { pool })
+            .mount(&server)
+            .await;
+        JupiterFixtureServer { server }
+    }
+
+    pub fn url(&self) -> String {
+        self.server.uri()
+    }
+
+    pub fn price_url(&self) -> String {
+        format!("{}/price/v2", self.server.uri())
+    }
+
+    /// 设置价格接口返回的 USD 价格，未设置的代币不出现在响应中；每个服务只应设置一次
+    pub async fn set_prices(&self, prices: &HashMap<Pubkey, f64>) {
+        let mut data = Map::new();
+        for (mint, price) in prices {
+            let vars = HashMap::from([
+                ("MINT", json!(mint.to_string())),
+                ("PRICE", json!(price.to_string())),
+            ]);
+            if let Value::Object(entries) = &render(&fixture(PRICE_FIXTURE), &vars)["data"] {
+                data.extend(entries.clone());
+            }
+        }
+        let mut body = fixture(PRICE_FIXTURE);
+        body["data"] = Value::Object(data);
+        Mock::given(method("GET"))
+            .and(path("/price/v2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// 收到的 `/swap-instructions` 请求数
+    pub async fn swap_requests(&self) -> usize {
+        self.server
+            .received_requests()
+            .await
+            .unwrap_or_default()
+            .iter()
+            .filter(|request| request.url.path() == "/swap-instructions")
+            .count()
+    }
+}
@@ -0,0 +1,846 @@
+//! 确定性的模拟客户端，用于示例程序和本地演示
+//!
+//! 不访问网络、不读取环境变量：价格由脚本给出，Jupiter 报价按固定价格计算，
+//! 钥匙对由固定种子派生，因此每次运行输出完全一致。
+//! [`MockRpc`]、[`MockJupiter`] 和 [`MockJito`] 实现了 [`crate::solana::clients`] 中的 trait，
+//! 可以直接传给 [`crate::solana::swap::swap_with_tax`] 走完整个交换流程，并检查发送的交易和 bundle。
+//! [`MockSigner`] 像远程签名服务一样只接触消息字节，可以检查交换流程请求签名的消息。
+//! [`MockStack`] 和 [`mock_config`] 用这些客户端创建完整的订单簿，测试下单、触发和撤单流程。
+//!
+//! 需要真实链上执行时使用 [`local`]：在本地验证节点上创建代币和账户，Jupiter 接口由按 fixture 应答的本地服务代替。
+
+pub mod local;
+
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use jupiter_swap_api_client::{
+    quote::{self, QuoteRequest, QuoteResponse},
+    swap::SwapRequest,
+};
+use reqwest::Client;
+use serde_json::{json, Value};
+use solana_client::rpc_response::RpcKeyedAccount;
+use solana_sdk::{
+    account::Account,
+    address_lookup_table::{
+        self,
+        state::{AddressLookupTable, LookupTableMeta},
+    },
+    hash::Hash,
+    instruction::{AccountMeta, Instruction},
+    program_option::COption,
+    program_pack::Pack,
+    pubkey,
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::{Keypair, Signature},
+    signer::{keypair::keypair_from_seed, Signer},
+    system_instruction,
+    transaction::VersionedTransaction,
+};
+use spl_associated_token_account::{
+    get_associated_token_address, instruction::create_associated_token_account_idempotent,
+};
+use spl_token_2022::{
+    extension::{
+        transfer_fee::TransferFeeConfig, BaseStateWithExtensionsMut, ExtensionType,
+        StateWithExtensionsMut,
+    },
+    state::Mint as Mint2022,
+};
+use uuid::Uuid;
+
+use crate::{
+    common::{
+        config::AppConfig,
+        encode::SecretString,
+        keys::{KeyProvider, KeySource},
+        price::now_ms,
+        price_source::{PriceQuote, PriceSource, PriceSourceConfig},
+        quote_feed::QuoteFeedConfig,
+        rate_limit::RateLimitConfig,
+        retry::RetryPolicy,
+        session::DEFAULT_SESSION_TTL,
+        tax_policy::TaxPolicy,
+        token_info::TokenInfoConfig,
+        types::{FundingCheck, Order, OrderBook, OrderClients, OrderLeg, OrderLimits, OrderStatus},
+        units::{Bps, TokenAmount},
+        utils::{BundleConfig, PrivateExecutionConfig},
+        webhook::WebhookConfig,
+    },
+    solana::{
+        clients::{
+            BundleSender, SignatureStatus, Simulation, SolanaRpc, SwapApi, SwapInstructions,
+        },
+        decode::JUPITER_PROGRAM_ID,
+        jup::QuotePolicy,
+        multi_rpc::MultiRpcConfig,
+        signer::TransactionSigner,
+        swap::TaxSide,
+        swap::TOKEN_2022_PROGRAM_ID,
+    },
+    SOL,
+};
+
+pub const USDC: Pubkey = pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
+pub const SOL_DECIMALS: u8 = 9;
+pub const USDC_DECIMALS: u8 = 6;
+
+/// 由固定种子派生的钥匙对
+pub fn fixed_keypair(seed: u8) -> Keypair {
+    keypair_from_seed(&[seed; 32]).expect("32 字节种子")
+}
+
+/// 由 `fixed_keypair(seed)` 的私钥组成的下单凭证，与解密 `encrypt_pk` 得到的内容相同
+pub fn wallet_secret(seed: u8) -> SecretString {
+    SecretString::new(fixed_keypair(seed).to_base58_string())
+}
+
+/// 以 `amount` lamports 的 SOL 换 USDC 的限价单，SOL 的 USD 价格涨到 `price` 以上时触发
+pub fn limit_leg(price: f32, amount: u64) -> OrderLeg {
+    serde_json::from_value(json!({
+        "input_mint": SOL.to_string(),
+        "output_mint": USDC.to_string(),
+        "price": price,
+        "amount": amount,
+        "slippage_bps": 50,
+        "trigger_condition": "Above",
+    }))
+    .expect("订单参数有效")
+}
+
+/// 按顺序返回预设价格的价格源，每个价格对应一轮轮询
+pub struct ScriptedPrices {
+    prices: Vec<f64>,
+    next: usize,
+}
+
+impl ScriptedPrices {
+    pub fn new(prices: Vec<f64>) -> ScriptedPrices {
+        ScriptedPrices { prices, next: 0 }
+    }
+}
+
+impl Iterator for ScriptedPrices {
+    type Item = f64;
+
+    fn next(&mut self) -> Option<f64> {
+        let price = self.prices.get(self.next).copied();
+        self.next += 1;
+        price
+    }
+}
+
+/// 返回预设价格的价格源，可模拟请求失败，并记录被请求的次数，
+/// 用于组合 [`crate::common::price_source::FallbackPriceSource`] 检查回退顺序；
+/// 价格可以用 [`MockPriceSource::set_price`] 修改，用于触发订单
+pub struct MockPriceSource {
+    pub name: &'static str,
+    prices: Mutex<HashMap<Pubkey, f64>>,
+    /// 为 true 时每次请求都返回错误
+    pub fail: bool,
+    /// 价格时间比当前时间早的毫秒数，用于模拟过期价格
+    pub age_ms: u64,
+    calls: AtomicUsize,
+}
+
+impl MockPriceSource {
+    pub fn new(name: &'static str, prices: HashMap<Pubkey, f64>) -> MockPriceSource {
+        MockPriceSource {
+            name,
+            prices: Mutex::new(prices),
+            fail: false,
+            age_ms: 0,
+            calls: AtomicUsize::new(0),
+        }
+    }
+
+    /// 每次请求都失败的价格源
+    pub fn failing(name: &'static str) -> MockPriceSource {
+        MockPriceSource {
+            fail: true,
+            ..MockPriceSource::new(name, HashMap::new())
+        }
+    }
+
+    /// 已被请求的次数
+    pub fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+
+    /// 修改 `mint` 的价格，下一次请求起生效
+    pub fn set_price(&self, mint: Pubkey, price: f64) {
+        self.prices.lock().unwrap().insert(mint, price);
+    }
+}
+
+#[async_trait]
+impl PriceSource for MockPriceSource {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    async fn get_prices(&self, mints: &[Pubkey]) -> Result<HashMap<Pubkey, PriceQuote>> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        if self.fail {
+            return Err(anyhow!("价格源 {} 模拟失败", self.name));
+        }
+        let timestamp_ms = now_ms().saturating_sub(self.age_ms);
+        let prices = self.prices.lock().unwrap();
+        Ok(mints
+            .iter()
+            .filter_map(|mint| {
+                let price = *prices.get(mint)?;
+                Some((
+                    *mint,
+                    PriceQuote {
+                        price,
+                        confidence: None,
+                        confidence_level: None,
+                        last_swap_price: None,
+                        timestamp_ms,
+                        source: self.name,
+                    },
+                ))
+            })
+            .collect())
+    }
+}
+
+/// 模拟的 Jupiter 交换指令，字段与 `SwapInstructionsResponse` 对应
+pub struct MockSwapInstructions {
+    pub setup_instructions: Vec<Instruction>,
+    pub swap_instruction: Instruction,
+    pub cleanup_instruction: Option<Instruction>,
+    /// 报价的输出数量
+    pub out_amount: TokenAmount,
+}
+
+/// 以固定价格报价的 SOL -> SPL 代币 Jupiter 客户端
+pub struct MockJupiter {
+    /// 1 SOL 可换得的输出代币数量（界面单位）
+    pub price: f64,
+    pub output_decimals: u8,
+    /// 交换指令引用的地址查找表，账户需要先用 [`MockRpc::set_lookup_table`] 写入
+    pub lookup_tables: Vec<Pubkey>,
+}
+
+impl MockJupiter {
+    /// 按固定价格计算输出数量，向下取整
+    pub fn quote(&self, amount: TokenAmount, output_mint: Pubkey) -> TokenAmount {
+        let out = amount.to_ui(SOL_DECIMALS) * self.price;
+        TokenAmount::new(
+            output_mint,
+            (out * 10f64.powi(self.output_decimals as i32)).floor() as u64,
+        )
+    }
+
+    /// 构造与 Jupiter 返回结构一致的指令：包装 SOL、交换、关闭 wSOL 账户
+    pub fn swap_instructions(
+        &self,
+        user: &Pubkey,
+        amount: TokenAmount,
+        output_mint: Pubkey,
+    ) -> MockSwapInstructions {
+        let wsol_account = get_associated_token_address(user, &SOL);
+        let output_account = get_associated_token_address(user, &output_mint);
+        let out_amount = self.quote(amount, output_mint);
+
+        let setup_instructions = vec![
+            create_associated_token_account_idempotent(user, user, &SOL, &spl_token::id()),
+            create_associated_token_account_idempotent(user, user, &output_mint, &spl_token::id()),
+            system_instruction::transfer(user, &wsol_account, amount.raw),
+            spl_token::instruction::sync_native(&spl_token::id(), &wsol_account)
+                .expect("sync_native"),
+        ];
+
+        // 数据只编码输入与报价输出，便于阅读，不对应真实的 Jupiter 指令布局
+        let mut data = amount.raw.to_le_bytes().to_vec();
+        data.extend_from_slice(&out_amount.raw.to_le_bytes());
+        let swap_instruction = Instruction {
+            program_id: JUPITER_PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new_readonly(spl_token::id(), false),
+                AccountMeta::new(*user, true),
+                AccountMeta::new(wsol_account, false),
+                AccountMeta::new(output_account, false),
+                AccountMeta::new_readonly(SOL, false),
+                AccountMeta::new_readonly(output_mint, false),
+            ],
+            data,
+        };
+
+        let cleanup_instruction =
+            spl_token::instruction::close_account(&spl_token::id(), &wsol_account, user, user, &[])
+                .ok();
+
+        MockSwapInstructions {
+            setup_instructions,
+            swap_instruction,
+            cleanup_instruction,
+            out_amount,
+        }
+    }
+}
+
+#[async_trait]
+impl SwapApi for MockJupiter {
+    /// 只支持以 SOL 为输入的 `ExactIn` 报价，最低输出按滑点从报价数量中扣除
+    async fn quote(&self, request: &QuoteRequest) -> Result<QuoteResponse> {
+        if request.input_mint != SOL
+            || !matches!(request.swap_mode, None | Some(quote::SwapMode::ExactIn))
+        {
+            return Err(anyhow!("模拟 Jupiter 只支持以 SOL 为输入的 ExactIn 报价"));
+        }
+        let out = self.quote(TokenAmount::new(SOL, request.amount), request.output_mint);
+        let threshold =
+            out.raw as u128 * (10_000 - request.slippage_bps.min(10_000) as u128) / 10_000;
+        // 与 Jupiter 报价接口返回的 JSON 结构一致
+        Ok(serde_json::from_value(json!({
+            "inputMint": SOL.to_string(),
+            "inAmount": request.amount.to_string(),
+            "outputMint": request.output_mint.to_string(),
+            "outAmount": out.raw.to_string(),
+            "otherAmountThreshold": threshold.to_string(),
+            "swapMode": "ExactIn",
+            "slippageBps": request.slippage_bps,
+            "priceImpactPct": "0",
+            "routePlan": [],
+            "contextSlot": 0,
+            "timeTaken": 0.0,
+        }))?)
+    }
+
+    async fn swap_instructions(&self, request: &SwapRequest) -> Result<SwapInstructions> {
+        let quote = &request.quote_response;
+        let ixs = self.swap_instructions(
+            &request.user_public_key,
+            TokenAmount::new(quote.input_mint, quote.in_amount),
+            quote.output_mint,
+        );
+        Ok(SwapInstructions {
+            setup_instructions: ixs.setup_instructions,
+            swap_instruction: ixs.swap_instruction,
+            cleanup_instruction: ixs.cleanup_instruction,
+            address_lookup_table_addresses: self.lookup_tables.clone(),
+        })
+    }
+}
+
+/// 内存中的 Solana RPC：账户由调用方预先写入，模拟执行返回固定结果，发送的交易被记录下来
+pub struct MockRpc {
+    accounts: Mutex<HashMap<Pubkey, Account>>,
+    pub blockhash: Hash,
+    /// 当前区块高度，返回的 blockhash 在此后 [`MockRpc::BLOCKHASH_LIFETIME`] 个区块内有效
+    block_height: AtomicU64,
+    /// 当前 slot
+    slot: AtomicU64,
+    /// 每次模拟执行返回的结果
+    pub simulation: Simulation,
+    sent: Mutex<Vec<VersionedTransaction>>,
+    /// 设置过的签名状态，其他签名查询不到
+    signature_statuses: Mutex<HashMap<Signature, SignatureStatus>>,
+    /// 发送时返回的错误，交易仍被记录，用于模拟发送超时但交易可能已上链
+    send_error: Mutex<Option<String>>,
+    /// 每次模拟执行前的等待，用于模拟执行路径变慢
+    simulation_delay: Mutex<Duration>,
+}
+
+impl MockRpc {
+    /// blockhash 的有效区块数，与主网一致
+    pub const BLOCKHASH_LIFETIME: u64 = 150;
+
+    /// 没有任何账户、模拟执行成功并消耗 150_000 计算单元的 RPC
+    pub fn new() -> MockRpc {
+        MockRpc {
+            accounts: Mutex::new(HashMap::new()),
+            blockhash: Hash::new_from_array([1; 32]),
+            block_height: AtomicU64::new(1_000),
+            slot: AtomicU64::new(1_200),
+            simulation: Simulation {
+                err: None,
+                logs: vec![],
+                units_consumed: Some(150_000),
+                accounts: vec![],
+            },
+            sent: Mutex::new(vec![]),
+            signature_statuses: Mutex::new(HashMap::new()),
+            send_error: Mutex::new(None),
+            simulation_delay: Mutex::new(Duration::ZERO),
+        }
+    }
+
+    /// 模拟执行失败，`err` 和 `logs` 与链上返回的格式一致
+    pub fn failing_simulation(err: &str, logs: &[&str]) -> MockRpc {
+        MockRpc {
+            simulation: Simulation {
+                err: Some(err.to_string()),
+                logs: logs.iter().map(|log| log.to_string()).collect(),
+                units_consumed: None,
+                accounts: vec![],
+            },
+            ..MockRpc::new()
+        }
+    }
+
+    pub fn set_account(&self, pubkey: Pubkey, account: Account) {
+        self.accounts.lock().unwrap().insert(pubkey, account);
+    }
+
+    /// 写入一个已初始化的 mint 账户，`token_program` 为其所属的代币程序
+    pub fn set_mint(&self, mint: Pubkey, token_program: Pubkey, decimals: u8) {
+        let mut data = vec![0; spl_token::state::Mint::LEN];
+        spl_token::state::Mint {
+            mint_authority: COption::None,
+            supply: 0,
+            decimals,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        }
+        .pack_into_slice(&mut data);
+        self.set_account(
+            mint,
+            Account {
+                lamports: 1_461_600,
+                data,
+                owner: token_program,
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+    }
+
+    /// 写入一个带转账手续费扩展的 Token-2022 mint 账户，两档费率相同
+    pub fn set_mint_with_transfer_fee(
+        &self,
+        mint: Pubkey,
+        decimals: u8,
+        transfer_fee_bps: u16,
+        maximum_fee: u64,
+    ) {
+        let len = ExtensionType::try_calculate_account_len::<Mint2022>(&[
+            ExtensionType::TransferFeeConfig,
+        ])
+        .expect("mint 账户长度有效");
+        let mut data = vec![0; len];
+        let mut state = StateWithExtensionsMut::<Mint2022>::unpack_uninitialized(&mut data)
+            .expect("mint 账户未初始化");
+        let fee = spl_token_2022::extension::transfer_fee::TransferFee {
+            epoch: 0.into(),
+            maximum_fee: maximum_fee.into(),
+            transfer_fee_basis_points: transfer_fee_bps.into(),
+        };
+        let config = state
+            .init_extension::<TransferFeeConfig>(true)
+            .expect("初始化转账手续费扩展");
+        config.older_transfer_fee = fee;
+        config.newer_transfer_fee = fee;
+        state.base = Mint2022 {
+            decimals,
+            is_initialized: true,
+            ..Mint2022::default()
+        };
+        state.pack_base();
+        state.init_account_type().expect("写入账户类型");
+        self.set_account(
+            mint,
+            Account {
+                lamports: 1_461_600,
+                data,
+                owner: TOKEN_2022_PROGRAM_ID,
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+    }
+
+    /// 写入一个包含 `addresses` 的地址查找表账户
+    pub fn set_lookup_table(&self, key: Pubkey, addresses: &[Pubkey]) {
+        let data = AddressLookupTable {
+            meta: LookupTableMeta::default(),
+            addresses: Cow::Borrowed(addresses),
+        }
+        .serialize_for_tests()
+        .expect("序列化地址查找表");
+        self.set_account(
+            key,
+            Account {
+                lamports: 1_000_000,
+                data,
+                owner: address_lookup_table::program::id(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+    }
+
+    /// 设置当前区块高度，用于模拟 blockhash 过期
+    pub fn set_block_height(&self, height: u64) {
+        self.block_height.store(height, Ordering::SeqCst);
+    }
+
+    /// 设置当前 slot，用于模拟链上前进
+    pub fn set_slot(&self, slot: u64) {
+        self.slot.store(slot, Ordering::SeqCst);
+    }
+
+    /// 已发送的交易，按发送顺序排列
+    pub fn sent(&self) -> Vec<VersionedTransaction> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    /// 设置签名的确认状态
+    pub fn set_signature_status(&self, signature: Signature, status: SignatureStatus) {
+        self.signature_statuses
+            .lock()
+            .unwrap()
+            .insert(signature, status);
+    }
+
+    /// 之后的发送都返回 `err`，交易仍被记录
+    pub fn fail_sends(&self, err: &str) {
+        *self.send_error.lock().unwrap() = Some(err.to_string());
+    }
+
+    /// 之后的每次模拟执行先等待 `delay`，`Duration::ZERO` 恢复立即返回
+    pub fn delay_simulation(&self, delay: Duration) {
+        *self.simulation_delay.lock().unwrap() = delay;
+    }
+}
+
+impl Default for MockRpc {
+    fn default() -> MockRpc {
+        MockRpc::new()
+    }
+}
+
+#[async_trait]
+impl SolanaRpc for MockRpc {
+    async fn get_latest_blockhash(&self) -> Result<Hash> {
+        Ok(self.blockhash)
+    }
+
+    async fn get_latest_blockhash_with_height(&self) -> Result<(Hash, u64)> {
+        let height = self.block_height.load(Ordering::SeqCst);
+        Ok((self.blockhash, height + MockRpc::BLOCKHASH_LIFETIME))
+    }
+
+    async fn get_block_height(&self) -> Result<u64> {
+        Ok(self.block_height.load(Ordering::SeqCst))
+    }
+
+    async fn get_slot(&self) -> Result<u64> {
+        Ok(self.slot.load(Ordering::SeqCst))
+    }
+
+    async fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+        let accounts = self.accounts.lock().unwrap();
+        Ok(pubkeys
+            .iter()
+            .map(|pubkey| accounts.get(pubkey).cloned())
+            .collect())
+    }
+
+    async fn simulate(&self, _tx: &VersionedTransaction) -> Result<Simulation> {
+        let delay = *self.simulation_delay.lock().unwrap();
+        tokio::time::sleep(delay).await;
+        Ok(self.simulation.clone())
+    }
+
+    /// 模拟不执行指令，账户的状态即当前设置的账户
+    async fn simulate_with_accounts(
+        &self,
+        tx: &VersionedTransaction,
+        addresses: &[Pubkey],
+    ) -> Result<Simulation> {
+        Ok(Simulation {
+            accounts: self.get_multiple_accounts(addresses).await?,
+            ..self.simulate(tx).await?
+        })
+    }
+
+    async fn send(&self, tx: &VersionedTransaction) -> Result<Signature> {
+        self.sent.lock().unwrap().push(tx.clone());
+        if let Some(err) = self.send_error.lock().unwrap().clone() {
+            return Err(anyhow!(err));
+        }
+        Ok(tx.signatures[0])
+    }
+
+    async fn signature_status(&self, signature: &Signature) -> Result<SignatureStatus> {
+        Ok(self
+            .signature_statuses
+            .lock()
+            .unwrap()
+            .get(signature)
+            .copied()
+            .unwrap_or(SignatureStatus::Unknown))
+    }
+
+    /// 没有优先费样本
+    async fn get_recent_prioritization_fees(&self, _accounts: &[Pubkey]) -> Result<Vec<u64>> {
+        Ok(vec![])
+    }
+
+    /// 交易不会真正上链，查不到任何交易
+    async fn get_parsed_transaction(&self, _signature: &Signature) -> Result<Option<Value>> {
+        Ok(None)
+    }
+
+    async fn get_token_accounts_by_owner(
+        &self,
+        _owner: &Pubkey,
+        _token_program: &Pubkey,
+    ) -> Result<Vec<RpcKeyedAccount>> {
+        Ok(vec![])
+    }
+
+    async fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> Result<u64> {
+        Ok(Rent::default().minimum_balance(data_len))
+    }
+}
+
+/// 模拟的 Jito：记录发送的 bundle，`getBundleStatuses` 返回固定的确认状态
+pub struct MockJito {
+    pub tip_accounts: Vec<Pubkey>,
+    /// bundle 的确认状态，例如 `confirmed`；为 None 时 bundle 一直查询不到，直到确认超时
+    pub confirmation_status: Option<&'static str>,
+    /// 为 true 时 `sendBundle` 返回限流错误
+    pub rate_limited: bool,
+    bundles: Mutex<Vec<Value>>,
+}
+
+impl MockJito {
+    /// bundle 发送后立即确认
+    pub fn new(tip_account: Pubkey) -> MockJito {
+        MockJito {
+            tip_accounts: vec![tip_account],
+            confirmation_status: Some("confirmed"),
+            rate_limited: false,
+            bundles: Mutex::new(vec![]),
+        }
+    }
+
+    /// 已发送的 bundle，每个 bundle 为 base58 编码的交易数组
+    pub fn bundles(&self) -> Vec<Value> {
+        self.bundles.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl BundleSender for MockJito {
+    async fn send_bundle(&self, bundle: Value) -> Result<Value> {
+        if self.rate_limited {
+            return Ok(json!({
+                "jsonrpc": "2.0",
+                "error": { "code": -32097, "message": "Rate limit exceeded" },
+                "id": 1,
+            }));
+        }
+        let mut bundles = self.bundles.lock().unwrap();
+        bundles.push(bundle);
+        Ok(json!({
+            "jsonrpc": "2.0",
+            "result": format!("mock-bundle-{}", bundles.len()),
+            "id": 1,
+        }))
+    }
+
+    async fn get_bundle_statuses(&self, bundle_ids: Vec<String>) -> Result<Value> {
+        let value: Vec<Value> = bundle_ids
+            .iter()
+            .map(|bundle_id| match self.confirmation_status {
+                Some(status) => json!({
+                    "bundle_id": bundle_id,
+                    "slot": 1,
+                    "confirmation_status": status,
+                    "err": { "Ok": null },
+                }),
+                None => Value::Null,
+            })
+            .collect();
+        Ok(json!({
+            "jsonrpc": "2.0",
+            "result": { "context": { "slot": 1 }, "value": value },
+            "id": 1,
+        }))
+    }
+
+    async fn get_tip_accounts(&self) -> Result<Value> {
+        let accounts: Vec<String> = self.tip_accounts.iter().map(Pubkey::to_string).collect();
+        Ok(json!({ "jsonrpc": "2.0", "result": accounts, "id": 1 }))
+    }
+}
+
+/// 模拟的远程签名者：与 [`crate::solana::signer::RemoteHttpSigner`] 一样只拿到序列化后的消息，
+/// 记录每次签名的消息后用持有的私钥签名
+pub struct MockSigner {
+    keypair: Arc<Keypair>,
+    messages: Mutex<Vec<Vec<u8>>>,
+}
+
+impl MockSigner {
+    pub fn new(keypair: Arc<Keypair>) -> MockSigner {
+        MockSigner {
+            keypair,
+            messages: Mutex::new(vec![]),
+        }
+    }
+
+    /// 按顺序返回请求签名的消息
+    pub fn messages(&self) -> Vec<Vec<u8>> {
+        self.messages.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl TransactionSigner for MockSigner {
+    fn pubkey(&self) -> Pubkey {
+        self.keypair.pubkey()
+    }
+
+    async fn sign_message(&self, message: &[u8]) -> Result<Signature> {
+        self.messages.lock().unwrap().push(message.to_vec());
+        Ok(self.keypair.sign_message(message))
+    }
+}
+
+/// 使用模拟客户端的订单簿配置：不连接数据库、不检查余额，税率 1%，收税账户为 `fixed_keypair(3)`，
+/// 价格每 50 毫秒轮询一次；代币信息接口指向本机不存在的服务，符号和名称查询直接失败
+pub fn mock_config() -> AppConfig {
+    AppConfig {
+        rpc: MultiRpcConfig {
+            urls: vec!["http://127.0.0.1:8899".to_string()],
+            slow_call: Duration::from_secs(2),
+            probe_interval: Duration::from_secs(30),
+            broadcast_sends: false,
+        },
+        jup_url: "http://127.0.0.1:9".to_string(),
+        jito_url: "http://127.0.0.1:9".to_string(),
+        tax_account: fixed_keypair(3).pubkey(),
+        tax_policy: TaxPolicy::flat(Bps::new(100).expect("100 bps 有效")),
+        tax_side: TaxSide::default(),
+        database_url: None,
+        database_pool_size: 1,
+        audit_retention: None,
+        keys: KeySource::Env(KeyProvider::new(1, [7; 32])),
+        price_poll_interval: Duration::from_millis(50),
+        blockhash_refresh_interval: Duration::from_secs(2),
+        price_sources: PriceSourceConfig::default(),
+        price_max_age: Duration::from_secs(10),
+        quote_feed: QuoteFeedConfig::default(),
+        token_info: TokenInfoConfig {
+            url: "http://127.0.0.1:9/tokens".to_string(),
+            ..TokenInfoConfig::default()
+        },
+        quote_policy: QuotePolicy::default(),
+        max_concurrent_swaps: 4,
+        default_slippage_bps: Bps::new(50).expect("50 bps 有效"),
+        default_priority_fee_micro_lamports: Some(1_000),
+        priority_fee_percentile: None,
+        default_max_price_impact_bps: None,
+        max_trigger_deviation_bps: None,
+        session_ttl: DEFAULT_SESSION_TTL,
+        route_pin_ttl: Duration::from_secs(30),
+        shutdown_timeout: Duration::from_secs(10),
+        retry_policy: RetryPolicy::default(),
+        bundle: BundleConfig::default(),
+        private_execution: PrivateExecutionConfig::default(),
+        limits: OrderLimits::default(),
+        funding_check: FundingCheck::Off,
+        webhook: WebhookConfig {
+            allow_private: false,
+            timeout: Duration::from_secs(5),
+        },
+        nonces: None,
+        sweep: None,
+        rate_limit: RateLimitConfig::default(),
+        api_keys: vec![],
+        swagger_ui: false,
+    }
+}
+
+/// 订单簿使用的一组模拟客户端，测试保留这些引用来修改价格、检查发送的交易和 bundle
+pub struct MockStack {
+    pub rpc: Arc<MockRpc>,
+    pub jup: Arc<MockJupiter>,
+    pub jito: Arc<MockJito>,
+    pub prices: Arc<MockPriceSource>,
+}
+
+impl MockStack {
+    /// 写入 SOL 和 USDC 的 mint，Jupiter 报价和价格源都按 1 SOL = `sol_price` USDC 计算
+    pub fn new(sol_price: f64) -> MockStack {
+        MockStack::with_rpc(MockRpc::new(), sol_price)
+    }
+
+    /// 使用指定的 RPC，例如 [`MockRpc::failing_simulation`]，同样写入 SOL 和 USDC 的 mint
+    pub fn with_rpc(rpc: MockRpc, sol_price: f64) -> MockStack {
+        rpc.set_mint(SOL, spl_token::id(), SOL_DECIMALS);
+        rpc.set_mint(USDC, spl_token::id(), USDC_DECIMALS);
+        MockStack {
+            rpc: Arc::new(rpc),
+            jup: Arc::new(MockJupiter {
+                price: sol_price,
+                output_decimals: USDC_DECIMALS,
+                lookup_tables: vec![],
+            }),
+            jito: Arc::new(MockJito::new(fixed_keypair(9).pubkey())),
+            prices: Arc::new(MockPriceSource::new(
+                "mock",
+                HashMap::from([(SOL, sol_price), (USDC, 1.0)]),
+            )),
+        }
+    }
+
+    /// 以这组客户端创建订单簿
+    pub fn order_book(&self, config: &AppConfig) -> OrderBook {
+        OrderBook::with_clients(
+            config,
+            OrderClients {
+                rpc: self.rpc.clone(),
+                jup: self.jup.clone(),
+                jito: self.jito.clone(),
+                price_source: self.prices.clone(),
+                http: Arc::new(Client::new()),
+            },
+        )
+    }
+}
+
+/// 等待订单状态满足 `done` 并返回此时的订单，10 秒内未满足时 panic
+pub async fn wait_for_order(
+    book: &tokio::sync::Mutex<OrderBook>,
+    order_id: Uuid,
+    done: impl Fn(&OrderStatus) -> bool,
+) -> Order {
+    let wait = async {
+        loop {
+            let order = book
+                .lock()
+                .await
+                .orders
+                .lock()
+                .await
+                .get(&order_id)
+                .cloned();
+            if let Some(order) = order.filter(|order| done(&order.status)) {
+                return order;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(10), wait)
+        .await
+        .unwrap_or_else(|_| panic!("订单 {} 在 10 秒内未达到预期状态", order_id))
+}
@@ -0,0 +1,172 @@
+//! 在本地验证节点上真实执行带税收的交换
+//!
+//! ```bash
+//! # 自动启动 solana-test-validator（需要在 PATH 中）
+//! cargo test --features testing --test local_validator -- --ignored
+//! # 或者连接已经运行的节点
+//! TEST_RPC_URL=http://127.0.0.1:8899 cargo test --features testing --test local_validator -- --ignored
+//! ```
+//!
+//! 需要本地节点，默认不运行；没有配置 `TEST_RPC_URL` 且找不到 `solana-test-validator` 时直接通过。
+//! Jupiter 接口由 [`JupiterFixtureServer`] 代替，交换后在链上核对税收、资金池和用户收到的数量。
+
+use std::sync::Arc;
+
+use anyhow::{ensure, Result};
+use jupiter_swap_api_client::JupiterSwapApiClient;
+use limit_order::{
+    common::{
+        tax_policy::TaxPolicy,
+        units::{Bps, TokenAmount},
+        utils::{BlockhashProvider, BundleConfig, BLOCKHASH_EXPIRY_MARGIN},
+    },
+    solana::{
+        jup::{SwapMode, SwapOptions},
+        swap::{swap_with_tax, ExecutionOptions, SwapContext, SwapParams, TaxSide},
+    },
+    testing::{
+        fixed_keypair,
+        local::{
+            create_mint, create_token_account, fund, mint_to, token_balance, JupiterFixtureServer,
+            LocalValidator,
+        },
+        MockJito, MockSigner,
+    },
+};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    native_token::LAMPORTS_PER_SOL,
+    signature::{Keypair, Signer},
+};
+use spl_associated_token_account::get_associated_token_address;
+
+/// 测试代币的精度
+const DECIMALS: u8 = 6;
+/// 每个最小单位的输入换得的输出数量
+const RATE: f64 = 2.0;
+/// 下单的输入数量
+const AMOUNT: u64 = 100_000_000;
+
+/// 一次交换后的链上余额
+#[derive(Debug, PartialEq)]
+struct Balances {
+    /// 收税账户在收税代币上的余额
+    tax: u64,
+    /// 资金池收到的输入代币
+    pool: u64,
+    /// 用户收到的输出代币
+    user_output: u64,
+}
+
+/// 使用新的钱包和代币执行一次 1% 税收的 ExactIn 交换，返回交换后的余额
+async fn swap_on_chain(rpc: &RpcClient, tax_side: TaxSide) -> Result<Balances> {
+    // 每次使用新钱包，连接共享节点时也不会互相影响
+    let user = Arc::new(Keypair::new());
+    let pool = Keypair::new().pubkey();
+    let tax_account = Keypair::new().pubkey();
+    fund(rpc, &user.pubkey(), 10 * LAMPORTS_PER_SOL).await?;
+
+    let input_mint = create_mint(rpc, &user, DECIMALS).await?;
+    let output_mint = create_mint(rpc, &user, DECIMALS).await?;
+    mint_to(rpc, &user, &input_mint, &user.pubkey(), 1_000_000_000).await?;
+    let pool_account = create_token_account(rpc, &user, &pool, &input_mint).await?;
+    // 以输入代币收税时收税账户的 ATA 需要事先存在，以输出代币收税时由交换交易创建
+    let tax_mint = match tax_side {
+        TaxSide::Input => {
+            create_token_account(rpc, &user, &tax_account, &input_mint).await?;
+            input_mint
+        }
+        TaxSide::Output => output_mint,
+    };
+
+    let jupiter = JupiterFixtureServer::start(RATE, pool).await;
+    let jup = JupiterSwapApiClient::new(jupiter.url());
+    let jito = MockJito::new(fixed_keypair(9).pubkey());
+    let blockhashes = BlockhashProvider::new(BLOCKHASH_EXPIRY_MARGIN);
+    let ctx = SwapContext {
+        jup: &jup,
+        rpc,
+        jito: &jito,
+        blockhashes: &blockhashes,
+        bundle: BundleConfig::default(),
+        tax_account,
+        tax_side,
+    };
+    let signer = MockSigner::new(user.clone());
+    let outcome = swap_with_tax(
+        &ctx,
+        &signer,
+        &TaxPolicy::flat(Bps::new(100)?),
+        &SwapParams {
+            input_mint,
+            output_mint,
+            amount: TokenAmount::new(input_mint, AMOUNT),
+            swap_mode: SwapMode::ExactIn,
+            slippage_bps: Bps::new(50)?,
+            min_out_amount: None,
+            max_price_impact_bps: None,
+            options: &SwapOptions::default(),
+            pin: None,
+        },
+        ExecutionOptions::default(),
+    )
+    .await?;
+    ensure!(jupiter.swap_requests().await == 1, "应只请求一次交换指令");
+    ensure!(!signer.messages().is_empty(), "交换交易没有经过签名者签名");
+    ensure!(outcome.bundle_id.is_none(), "没有 tip 时不应以 bundle 发送");
+    ensure!(
+        outcome.tax_mint == tax_mint.to_string(),
+        "税收代币应为 {}",
+        tax_mint
+    );
+
+    let tax_token_account = get_associated_token_address(&tax_account, &tax_mint);
+    let user_output = get_associated_token_address(&user.pubkey(), &output_mint);
+    let balances = Balances {
+        tax: token_balance(rpc, &tax_token_account).await?,
+        pool: token_balance(rpc, &pool_account).await?,
+        user_output: token_balance(rpc, &user_output).await?,
+    };
+    ensure!(
+        outcome.tax_amount == balances.tax,
+        "返回的税收 {} 与链上 {} 不符",
+        outcome.tax_amount,
+        balances.tax
+    );
+    Ok(balances)
+}
+
+#[tokio::test]
+#[ignore = "需要本地验证节点"]
+async fn taxed_swaps_settle_on_chain() -> Result<()> {
+    let Some(validator) = LocalValidator::start().await? else {
+        println!("未配置 TEST_RPC_URL 且找不到 solana-test-validator，跳过");
+        return Ok(());
+    };
+    let rpc = validator.rpc();
+
+    // 交易前以输入代币收取 1%，其余输入按兑换率换成输出代币
+    let tax = AMOUNT / 100;
+    let swapped = AMOUNT - tax;
+    assert_eq!(
+        swap_on_chain(&rpc, TaxSide::Input).await?,
+        Balances {
+            tax,
+            pool: swapped,
+            user_output: (swapped as f64 * RATE) as u64,
+        }
+    );
+
+    // 全部输入参与交换，交易后从输出代币中收取 1%
+    let out_amount = (AMOUNT as f64 * RATE) as u64;
+    let tax = out_amount / 100;
+    assert_eq!(
+        swap_on_chain(&rpc, TaxSide::Output).await?,
+        Balances {
+            tax,
+            pool: AMOUNT,
+            user_output: out_amount - tax,
+        }
+    );
+    Ok(())
+}